@@ -0,0 +1,317 @@
+//! Runtime settings shared by fake_ssp, fake_bidder and cat_scan, so the
+//! three can be configured the same way side by side in containers.
+//!
+//! Each setting has a key, e.g. `replay_speed`, and comes from the first of:
+//!
+//! 1. its flag: `--replay-speed 2`
+//! 2. `<APP>_<KEY>` in the environment: `FAKE_SSP_REPLAY_SPEED=2`
+//! 3. the older environment variable some settings also have: `REPLAY_SPEED=2`
+//! 4. the app's section of a TOML file named by `--config FILE` or
+//!    CAT_CONFIG: `[fake_ssp]` then `replay_speed = 2`. One file holds the
+//!    sections of all three; each binary only reads its own.
+//!
+//! Flags are the binaries' own business: they either read settings by key
+//! (or older variable name, see `Settings::var`) and apply their flags on
+//! top, or put `Settings::flags` ahead of their command line.
+
+use std::{collections::BTreeMap, env, fs};
+
+use anyhow::{bail, Context, Result};
+use toml::{Table, Value};
+
+/// Names the config file when --config doesn't
+pub const CONFIG_ENV: &str = "CAT_CONFIG";
+
+/// A setting a binary understands
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Setting {
+    /// Name in the config file; the flag is `--key-with-dashes` and the
+    /// environment variable `<APP>_KEY`
+    pub key: &'static str,
+    /// Environment variable the setting was read from before this crate
+    pub legacy_env: Option<&'static str>,
+    /// A flag without a value, on when set to 1, true or yes
+    pub switch: bool,
+}
+
+impl Setting {
+    pub const fn value(key: &'static str) -> Self {
+        Self {
+            key,
+            legacy_env: None,
+            switch: false,
+        }
+    }
+
+    pub const fn switch(key: &'static str) -> Self {
+        Self {
+            key,
+            legacy_env: None,
+            switch: true,
+        }
+    }
+
+    /// Also read from the environment variable `name`
+    pub const fn env(self, name: &'static str) -> Self {
+        Self {
+            legacy_env: Some(name),
+            ..self
+        }
+    }
+}
+
+/// Whether a switch's value turns it on
+pub fn is_on(value: &str) -> bool {
+    matches!(value, "1" | "true" | "yes")
+}
+
+/// Looks up an environment variable
+type Env = Box<dyn Fn(&str) -> Option<String>>;
+
+/// One binary's settings from the environment and the config file, plus
+/// the command line left once --config is taken out
+pub struct Settings {
+    app: &'static str,
+    known: Vec<Setting>,
+    env: Env,
+    /// Values from the app's section; a list gives several
+    file: BTreeMap<String, Vec<String>>,
+    args: Vec<String>,
+}
+
+impl Settings {
+    /// Settings of `app` (its binary's name) for the running process
+    pub fn load(app: &'static str, known: &[Setting]) -> Result<Self> {
+        Self::new(
+            app,
+            known,
+            |name| env::var(name).ok(),
+            env::args().skip(1).collect(),
+            |path| {
+                fs::read_to_string(path).with_context(|| format!("Failed to read config {path}"))
+            },
+        )
+    }
+
+    fn new(
+        app: &'static str,
+        known: &[Setting],
+        env: impl Fn(&str) -> Option<String> + 'static,
+        mut args: Vec<String>,
+        read: impl Fn(&str) -> Result<String>,
+    ) -> Result<Self> {
+        let mut path = env(CONFIG_ENV).filter(|p| !p.is_empty());
+        if let Some(at) = args.iter().position(|a| a == "--config") {
+            if at + 1 >= args.len() {
+                bail!("--config requires a file path");
+            }
+            path = Some(args.remove(at + 1));
+            args.remove(at);
+        }
+
+        let file = match path {
+            Some(path) => parse_file(app, known, &read(&path)?)
+                .with_context(|| format!("Invalid config {path}"))?,
+            None => BTreeMap::new(),
+        };
+        Ok(Self {
+            app,
+            known: known.to_vec(),
+            env: Box::new(env),
+            file,
+            args,
+        })
+    }
+
+    /// The command line, without --config
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// A setting's value from the environment or the file; a list from the
+    /// file comes comma-separated
+    pub fn get(&self, key: &str) -> Option<String> {
+        self.env_value(key)
+            .or_else(|| self.file.get(key).map(|values| values.join(",")))
+    }
+
+    /// `get` for the setting whose older environment variable is `name`,
+    /// for parsers written against the environment
+    pub fn var(&self, name: &str) -> Option<String> {
+        match self.known.iter().find(|s| s.legacy_env == Some(name)) {
+            Some(setting) => self.get(setting.key),
+            None => (self.env)(name),
+        }
+    }
+
+    /// The given settings as command-line flags, to go ahead of the actual
+    /// command line so its flags win. A list from the file repeats the flag.
+    pub fn flags(&self, settings: &[Setting]) -> Vec<String> {
+        let mut flags = Vec::new();
+        for setting in settings {
+            let values = match self.env_value(setting.key) {
+                Some(value) => vec![value],
+                None => self.file.get(setting.key).cloned().unwrap_or_default(),
+            };
+            let flag = format!("--{}", setting.key.replace('_', "-"));
+            if setting.switch {
+                if values.iter().any(|v| is_on(v)) {
+                    flags.push(flag);
+                }
+            } else {
+                for value in values {
+                    flags.push(flag.clone());
+                    flags.push(value);
+                }
+            }
+        }
+        flags
+    }
+
+    fn env_value(&self, key: &str) -> Option<String> {
+        let name = format!("{}_{}", self.app, key).to_uppercase();
+        (self.env)(&name).or_else(|| {
+            self.known
+                .iter()
+                .find(|s| s.key == key)
+                .and_then(|s| s.legacy_env)
+                .and_then(|legacy| (self.env)(legacy))
+        })
+    }
+}
+
+/// The app's section of a config file; other apps' sections are skipped
+fn parse_file(app: &str, known: &[Setting], text: &str) -> Result<BTreeMap<String, Vec<String>>> {
+    let table: Table = text.parse()?;
+    let mut settings = BTreeMap::new();
+    for (name, value) in &table {
+        let Value::Table(section) = value else {
+            bail!("'{name}' is outside a section; settings go under [fake_ssp], [fake_bidder] or [cat_scan]");
+        };
+        if name != app {
+            continue;
+        }
+        for (key, value) in section {
+            if !known.iter().any(|s| s.key == key) {
+                bail!("unknown setting '{key}' in [{app}]");
+            }
+            let scalar = |value: &Value| match value {
+                Value::String(s) => Ok(s.clone()),
+                Value::Integer(_) | Value::Float(_) | Value::Boolean(_) | Value::Datetime(_) => {
+                    Ok(value.to_string())
+                }
+                _ => bail!("[{app}] {key} must be a string, number or boolean, or a list of them"),
+            };
+            let values = match value {
+                Value::Array(items) => items.iter().map(scalar).collect::<Result<_>>()?,
+                value => vec![scalar(value)?],
+            };
+            settings.insert(key.clone(), values);
+        }
+    }
+    Ok(settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KNOWN: &[Setting] = &[
+        Setting::value("requests").env("NUM_REQUESTS"),
+        Setting::value("fail_on"),
+        Setting::switch("quiet"),
+        Setting::value("out"),
+    ];
+
+    const FILE: &str = r#"
+        [fake_ssp]
+        requests = 500
+        fail_on = ["bid_rate<10%", "zero_bid_formats>5"]
+        quiet = true
+
+        [cat_scan]
+        bogus = "ignored, not ours"
+    "#;
+
+    fn settings(env: &[(&str, &str)], args: &[&str]) -> Result<Settings> {
+        let env: Vec<(String, String)> = env
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        Settings::new(
+            "fake_ssp",
+            KNOWN,
+            move |name| env.iter().find(|(k, _)| k == name).map(|(_, v)| v.clone()),
+            args.iter().map(|a| a.to_string()).collect(),
+            |path| match path {
+                "cat.toml" => Ok(FILE.to_string()),
+                "flat.toml" => Ok("requests = 5".to_string()),
+                "typo.toml" => Ok("[fake_ssp]\nrequsts = 5".to_string()),
+                _ => bail!("Failed to read config {path}"),
+            },
+        )
+    }
+
+    #[test]
+    fn test_layers() {
+        let s = settings(&[], &["--config", "cat.toml", "--qps", "5"]).unwrap();
+        assert_eq!(s.args(), ["--qps", "5"]);
+        assert_eq!(s.get("requests").as_deref(), Some("500"));
+        assert_eq!(s.var("NUM_REQUESTS").as_deref(), Some("500"));
+        assert_eq!(s.get("out"), None);
+
+        // The prefixed variable, then the older one, then the file
+        let s = settings(&[("CAT_CONFIG", "cat.toml"), ("NUM_REQUESTS", "7")], &[]).unwrap();
+        assert_eq!(s.var("NUM_REQUESTS").as_deref(), Some("7"));
+        let s = settings(
+            &[
+                ("CAT_CONFIG", "cat.toml"),
+                ("NUM_REQUESTS", "7"),
+                ("FAKE_SSP_REQUESTS", "9"),
+            ],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(s.get("requests").as_deref(), Some("9"));
+
+        // Variables the binary didn't declare still come from the environment
+        let s = settings(&[("AWS_REGION", "eu-west-1")], &[]).unwrap();
+        assert_eq!(s.var("AWS_REGION").as_deref(), Some("eu-west-1"));
+
+        assert!(settings(&[], &["--config"]).is_err());
+        assert!(settings(&[], &["--config", "missing.toml"]).is_err());
+        assert!(settings(&[], &["--config", "flat.toml"]).is_err());
+        assert!(settings(&[], &["--config", "typo.toml"]).is_err());
+    }
+
+    #[test]
+    fn test_flags() {
+        let s = settings(&[("FAKE_SSP_OUT", "/reports")], &["--config", "cat.toml"]).unwrap();
+        assert_eq!(
+            s.flags(KNOWN),
+            [
+                "--requests",
+                "500",
+                "--fail-on",
+                "bid_rate<10%",
+                "--fail-on",
+                "zero_bid_formats>5",
+                "--quiet",
+                "--out",
+                "/reports",
+            ]
+        );
+
+        // The environment replaces a list and can turn a switch off
+        let s = settings(
+            &[
+                ("CAT_CONFIG", "cat.toml"),
+                ("FAKE_SSP_FAIL_ON", "bid_rate<5%"),
+                ("FAKE_SSP_QUIET", "0"),
+            ],
+            &[],
+        )
+        .unwrap();
+        assert_eq!(s.flags(&KNOWN[1..3]), ["--fail-on", "bid_rate<5%"]);
+    }
+}
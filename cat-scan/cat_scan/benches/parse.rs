@@ -0,0 +1,100 @@
+//! JSON parsing throughput on fake_ssp-style log lines, the step that
+//! dominates scans of large files (see `--profile`).
+//!
+//!     cargo bench -p cat_scan --bench parse
+//!     cargo bench -p cat_scan --bench parse --features simd
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+/// Same shape as cat_scan's stats::LogRecord
+#[derive(Deserialize)]
+#[allow(dead_code)]
+struct LogRecord {
+    request: Value,
+    #[serde(default)]
+    response: Value,
+    #[serde(default)]
+    ts_ms: Option<u64>,
+}
+
+const LINES: usize = 1_000;
+
+/// Log lines as fake_ssp writes them, two in three with a bid
+fn log_lines() -> Vec<String> {
+    let formats = [(300, 250), (320, 50), (160, 600), (728, 90)];
+    (0..LINES)
+        .map(|i| {
+            let (w, h) = formats[i % formats.len()];
+            let response = if i % 3 == 0 {
+                json!({})
+            } else {
+                json!({
+                    "id": format!("req-{w}x{h}-{i}"),
+                    "seatbid": [{ "seat": "fake_bidder", "bid": [{
+                        "id": "1", "impid": "1", "price": 1.25, "w": w, "h": h,
+                        "adomain": ["advertiser.example.com"], "crid": format!("cr-{}", i % 17),
+                        "adm": "<div>ad</div>"
+                    }] }]
+                })
+            };
+            json!({
+                "ts_ms": 1_717_200_000_000u64 + i as u64 * 10,
+                "request": {
+                    "id": format!("req-{w}x{h}-{i}"),
+                    "source": { "ssp": "fake_ssp" },
+                    "site": { "publisher": { "id": "pub-news" }, "domain": "news.example.com" },
+                    "device": { "ua": "Mozilla/5.0 (X11; Linux x86_64)", "ip": "203.0.113.7" },
+                    "user": { "data": [{ "segment": [{ "id": "travel" }] }] },
+                    "imp": [{ "id": "1", "banner": { "w": w, "h": h }, "bidfloor": 0.5 }]
+                },
+                "response": response
+            })
+            .to_string()
+        })
+        .collect()
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let lines = log_lines();
+    let bytes: usize = lines.iter().map(|l| l.len() + 1).sum();
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Bytes(bytes as u64));
+
+    group.bench_function("serde_json_from_str", |b| {
+        b.iter(|| {
+            for line in &lines {
+                let record: LogRecord = serde_json::from_str(line).unwrap();
+                black_box(record);
+            }
+        })
+    });
+
+    group.bench_function("serde_json_from_slice", |b| {
+        b.iter(|| {
+            for line in &lines {
+                let record: LogRecord = serde_json::from_slice(line.as_bytes()).unwrap();
+                black_box(record);
+            }
+        })
+    });
+
+    #[cfg(feature = "simd")]
+    group.bench_function("simd_json_reused_buffer", |b| {
+        let mut scratch = Vec::new();
+        b.iter(|| {
+            for line in &lines {
+                scratch.clear();
+                scratch.extend_from_slice(line.as_bytes());
+                let record: LogRecord = simd_json::serde::from_slice(&mut scratch).unwrap();
+                black_box(record);
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_parse);
+criterion_main!(benches);
@@ -0,0 +1,287 @@
+use std::{collections::BTreeMap, io::Write};
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::bids::NO_SEAT;
+use crate::metadata::{create_csv, ScanMetadata};
+use crate::stats::scale_count;
+
+/// Winning price macro; also accepted URL-encoded
+const PRICE_MACROS: &[&str] = &["${AUCTION_PRICE}", "%24%7BAUCTION_PRICE%7D"];
+
+/// What the markup audit found wrong with one bid
+#[derive(Debug, Default, PartialEq)]
+pub struct AdmIssues {
+    /// No markup in adm and no nurl to fetch it from
+    pub empty_adm: bool,
+    /// ${AUCTION_PRICE} appears in none of adm, nurl or burl
+    pub no_price_macro: bool,
+    /// http:// asset references in a bid on an imp with secure=1
+    pub insecure_assets: bool,
+}
+
+/// Whether `adm` references an asset over plain http. Namespace and schema
+/// URIs in XML (VAST) are identifiers, not fetched, so they don't count.
+pub fn has_insecure_asset(adm: &str) -> bool {
+    let lower = adm.to_ascii_lowercase();
+    lower.match_indices("http://").any(|(at, _)| {
+        // The attribute or text the URL sits in: back to the last space or tag
+        let start = lower[..at]
+            .rfind(|c: char| c.is_whitespace() || c == '<')
+            .map_or(0, |i| i + 1);
+        let attribute = &lower[start..at];
+        !(attribute.starts_with("xmlns") || attribute.contains("schemalocation"))
+    })
+}
+
+fn has_price_macro(text: &str) -> bool {
+    PRICE_MACROS.iter().any(|m| text.contains(m))
+}
+
+/// Whether the imp a bid is for asks for secure creatives. Bids without a
+/// matching `impid` are taken to be for the first imp.
+fn imp_is_secure(request: &Value, impid: Option<&str>) -> bool {
+    let imps = request["imp"].as_array().map(Vec::as_slice).unwrap_or(&[]);
+    let imp = impid
+        .and_then(|id| imps.iter().find(|imp| imp["id"].as_str() == Some(id)))
+        .or(imps.first());
+    imp.is_some_and(|imp| imp["secure"].as_u64() == Some(1))
+}
+
+/// Audit one bid against its request
+pub fn audit_bid(request: &Value, bid: &Value) -> AdmIssues {
+    let adm = bid["adm"].as_str().unwrap_or("");
+    let nurl = bid["nurl"].as_str().unwrap_or("");
+    let burl = bid["burl"].as_str().unwrap_or("");
+    AdmIssues {
+        empty_adm: adm.trim().is_empty() && nurl.trim().is_empty(),
+        no_price_macro: ![adm, nurl, burl].iter().any(|text| has_price_macro(text)),
+        insecure_assets: imp_is_secure(request, bid["impid"].as_str()) && has_insecure_asset(adm),
+    }
+}
+
+/// Markup audit counts for one key
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct AdmCounts {
+    pub bids: u64,
+    pub empty_adm: u64,
+    pub no_price_macro: u64,
+    /// Bids on secure imps, the base for `insecure_assets`
+    pub secure_bids: u64,
+    pub insecure_assets: u64,
+}
+
+impl AdmCounts {
+    fn add(&mut self, issues: &AdmIssues, secure: bool) {
+        self.bids += 1;
+        self.empty_adm += issues.empty_adm as u64;
+        self.no_price_macro += issues.no_price_macro as u64;
+        self.secure_bids += secure as u64;
+        self.insecure_assets += issues.insecure_assets as u64;
+    }
+
+    fn merge(&mut self, other: &AdmCounts) {
+        self.bids += other.bids;
+        self.empty_adm += other.empty_adm;
+        self.no_price_macro += other.no_price_macro;
+        self.secure_bids += other.secure_bids;
+        self.insecure_assets += other.insecure_assets;
+    }
+
+    fn scale(&mut self, factor: f64) {
+        for count in [
+            &mut self.bids,
+            &mut self.empty_adm,
+            &mut self.no_price_macro,
+            &mut self.secure_bids,
+            &mut self.insecure_assets,
+        ] {
+            scale_count(count, factor);
+        }
+    }
+}
+
+/// Markup audit per dimension ("format", "seat") and key
+#[derive(Debug, Default, Clone)]
+pub struct AdmAudit {
+    pub by_key: BTreeMap<(&'static str, String), AdmCounts>,
+}
+
+impl AdmAudit {
+    /// Audit every bid of a response, counting it under `format` and its seat
+    pub fn observe(&mut self, request: &Value, response: &Value, format: &str) {
+        let Some(seatbids) = response["seatbid"].as_array() else {
+            return;
+        };
+        for seatbid in seatbids {
+            let seat = seatbid["seat"].as_str().unwrap_or(NO_SEAT);
+            for bid in seatbid["bid"].as_array().into_iter().flatten() {
+                let issues = audit_bid(request, bid);
+                let secure = imp_is_secure(request, bid["impid"].as_str());
+                for key in [("format", format), ("seat", seat)] {
+                    self.by_key
+                        .entry((key.0, key.1.to_string()))
+                        .or_default()
+                        .add(&issues, secure);
+                }
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &AdmAudit) {
+        for (key, counts) in &other.by_key {
+            self.by_key.entry(key.clone()).or_default().merge(counts);
+        }
+    }
+
+    /// Multiply counts by `factor` (extrapolating from a sample)
+    pub fn scale(&mut self, factor: f64) {
+        for counts in self.by_key.values_mut() {
+            counts.scale(factor);
+        }
+    }
+
+    /// One row per dimension and key, most issues first within a dimension
+    pub fn summarize(&self) -> Vec<AdmAuditSummary> {
+        let rate = |n: u64, d: u64| if d == 0 { 0.0 } else { n as f64 / d as f64 };
+        let mut rows: Vec<AdmAuditSummary> = self
+            .by_key
+            .iter()
+            .map(|((dimension, key), c)| AdmAuditSummary {
+                dimension,
+                key: key.clone(),
+                bids: c.bids,
+                empty_adm: c.empty_adm,
+                empty_adm_rate: rate(c.empty_adm, c.bids),
+                no_price_macro: c.no_price_macro,
+                no_price_macro_rate: rate(c.no_price_macro, c.bids),
+                secure_bids: c.secure_bids,
+                insecure_assets: c.insecure_assets,
+                insecure_assets_rate: rate(c.insecure_assets, c.secure_bids),
+            })
+            .collect();
+        rows.sort_by(|a, b| {
+            let issues = |r: &AdmAuditSummary| r.empty_adm + r.no_price_macro + r.insecure_assets;
+            a.dimension
+                .cmp(b.dimension)
+                .then_with(|| issues(b).cmp(&issues(a)))
+                .then_with(|| b.bids.cmp(&a.bids))
+                .then_with(|| a.key.cmp(&b.key))
+        });
+        rows
+    }
+}
+
+/// Markup audit results for one format or seat
+#[derive(serde::Serialize)]
+pub struct AdmAuditSummary {
+    pub dimension: &'static str,
+    pub key: String,
+    pub bids: u64,
+    pub empty_adm: u64,
+    pub empty_adm_rate: f64,
+    pub no_price_macro: u64,
+    pub no_price_macro_rate: f64,
+    pub secure_bids: u64,
+    pub insecure_assets: u64,
+    /// Share of bids on secure imps
+    pub insecure_assets_rate: f64,
+}
+
+/// Write adm_audit.csv (markup issues per format and seat)
+pub fn write_adm_audit_csv(
+    out_dir: &str,
+    rows: &[AdmAuditSummary],
+    meta: &ScanMetadata,
+) -> Result<()> {
+    let path = format!("{}/adm_audit.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    writeln!(
+        csv,
+        "dimension,key,bids,empty_adm,empty_adm_rate,no_price_macro,no_price_macro_rate,secure_bids,insecure_assets,insecure_assets_rate"
+    )?;
+    for r in rows {
+        writeln!(
+            csv,
+            "{},{},{},{},{:.4},{},{:.4},{},{},{:.4}",
+            r.dimension,
+            r.key,
+            r.bids,
+            r.empty_adm,
+            r.empty_adm_rate,
+            r.no_price_macro,
+            r.no_price_macro_rate,
+            r.secure_bids,
+            r.insecure_assets,
+            r.insecure_assets_rate
+        )?;
+    }
+    eprintln!("Markup audit written to: {}", path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_audit_bid() {
+        let request = json!({ "imp": [{ "id": "1" }, { "id": "2", "secure": 1 }] });
+        let bid = |impid: &str, adm: &str, nurl: &str| json!({ "impid": impid, "adm": adm, "nurl": nurl });
+
+        let clean = bid(
+            "2",
+            "<img src=\"https://cdn/x.png?p=${AUCTION_PRICE}\">",
+            "",
+        );
+        assert_eq!(audit_bid(&request, &clean), AdmIssues::default());
+
+        let insecure = audit_bid(&request, &bid("2", "<img src='http://cdn/x.png'>", ""));
+        assert!(insecure.insecure_assets && insecure.no_price_macro);
+        // Same markup on a non-secure imp is fine
+        let plain = audit_bid(&request, &bid("1", "<img src='http://cdn/x.png'>", ""));
+        assert!(!plain.insecure_assets);
+
+        let nurl_only = audit_bid(
+            &request,
+            &bid("2", "", "https://win?p=%24%7BAUCTION_PRICE%7D"),
+        );
+        assert_eq!(nurl_only, AdmIssues::default());
+        assert!(audit_bid(&request, &bid("2", " ", "")).empty_adm);
+
+        let vast = r#"<VAST xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance" xsi:noNamespaceSchemaLocation="http://x/vast.xsd"><MediaFile><![CDATA[https://cdn/v.mp4]]></MediaFile></VAST>"#;
+        assert!(!has_insecure_asset(vast));
+        assert!(has_insecure_asset(
+            "<MediaFile><![CDATA[http://cdn/v.mp4]]></MediaFile>"
+        ));
+    }
+
+    #[test]
+    fn test_adm_audit_counts_per_format_and_seat() {
+        let request = json!({ "imp": [{ "id": "1", "secure": 1 }] });
+        let response = json!({ "seatbid": [
+            { "seat": "dsp_a", "bid": [{ "impid": "1", "adm": "<a href=\"http://x\">${AUCTION_PRICE}</a>" }] },
+            { "bid": [{ "impid": "1" }] }
+        ]});
+        let mut audit = AdmAudit::default();
+        audit.observe(&request, &response, "300x250");
+        let format = &audit.by_key[&("format", "300x250".to_string())];
+        assert_eq!(
+            format,
+            &AdmCounts {
+                bids: 2,
+                empty_adm: 1,
+                no_price_macro: 1,
+                secure_bids: 2,
+                insecure_assets: 1,
+            }
+        );
+        assert_eq!(
+            audit.by_key[&("seat", "dsp_a".to_string())].insecure_assets,
+            1
+        );
+        assert_eq!(audit.by_key[&("seat", NO_SEAT.to_string())].empty_adm, 1);
+    }
+}
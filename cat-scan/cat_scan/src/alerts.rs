@@ -0,0 +1,342 @@
+use std::cmp::Reverse;
+
+use anyhow::{bail, Context, Result};
+
+use crate::cli::parse_percent;
+use crate::groups::{Group, GroupBy};
+use crate::stats::{avg_bid_price, bid_rate, FormatStats, GlobalStats};
+use crate::summary::{percent, thousands};
+
+/// What an --alert-rule looks at: the whole scan, or each key of one
+/// aggregation
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scope {
+    Scan,
+    Format,
+    Ssp,
+    Publisher,
+    Segment,
+    Country,
+}
+
+impl Scope {
+    const ALL: [(&'static str, Scope); 6] = [
+        ("scan", Scope::Scan),
+        ("format", Scope::Format),
+        ("ssp", Scope::Ssp),
+        ("publisher", Scope::Publisher),
+        ("segment", Scope::Segment),
+        ("country", Scope::Country),
+    ];
+
+    fn name(self) -> &'static str {
+        Self::ALL.iter().find(|(_, s)| *s == self).unwrap().0
+    }
+
+    /// The --group-by group whose stats the scope reads
+    fn group(self) -> Option<Group> {
+        match self {
+            Self::Scan => None,
+            Self::Format => Some(Group::Format),
+            Self::Ssp => Some(Group::Ssp),
+            Self::Publisher => Some(Group::Publisher),
+            Self::Segment => Some(Group::Segment),
+            Self::Country => Some(Group::Geo),
+        }
+    }
+
+    /// (name a rule matches, label in alerts, stats) for every key
+    fn keys(self, global: &GlobalStats) -> Vec<(String, String, FormatStats)> {
+        let same = |name: String, stats: &FormatStats| (name.clone(), name, stats.clone());
+        match self {
+            Self::Scan => {
+                let mut total = FormatStats::default();
+                for stats in global.by_canonical_format.values() {
+                    total.merge(stats);
+                }
+                vec![(String::new(), "scan".to_string(), total)]
+            }
+            Self::Format => global
+                .by_canonical_format
+                .iter()
+                .map(|(&(w, h), stats)| same(format!("{}x{}", w, h), stats))
+                .collect(),
+            Self::Ssp => global
+                .by_ssp
+                .iter()
+                .map(|(ssp, stats)| same(ssp.clone(), stats))
+                .collect(),
+            Self::Publisher => global
+                .by_publisher
+                .iter()
+                .map(|(key, stats)| {
+                    let label = format!("{} ({})", key.publisher_id, key.ssp);
+                    (key.publisher_id.clone(), label, stats.clone())
+                })
+                .collect(),
+            Self::Segment => global
+                .by_segment
+                .iter()
+                .map(|(key, stats)| {
+                    let label = format!("{} ({})", key.segment, key.ssp);
+                    (key.segment.clone(), label, stats.clone())
+                })
+                .collect(),
+            Self::Country => global
+                .by_country
+                .iter()
+                .map(|(country, stats)| same(country.clone(), stats))
+                .collect(),
+        }
+    }
+}
+
+/// Figures of one key an --alert-rule can test
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum RuleMetric {
+    Requests,
+    Bids,
+    BidRate,
+    AvgBidPrice,
+}
+
+impl RuleMetric {
+    const ALL: [(&'static str, RuleMetric); 4] = [
+        ("requests", RuleMetric::Requests),
+        ("bids", RuleMetric::Bids),
+        ("bid_rate", RuleMetric::BidRate),
+        ("avg_bid_price", RuleMetric::AvgBidPrice),
+    ];
+
+    fn name(self) -> &'static str {
+        Self::ALL.iter().find(|(_, m)| *m == self).unwrap().0
+    }
+
+    fn value(self, stats: &FormatStats) -> f64 {
+        match self {
+            Self::Requests => stats.requests as f64,
+            Self::Bids => stats.bids as f64,
+            Self::BidRate => bid_rate(stats),
+            Self::AvgBidPrice => avg_bid_price(stats),
+        }
+    }
+
+    fn format(self, value: f64) -> String {
+        match self {
+            Self::BidRate => percent(value),
+            Self::AvgBidPrice => format!("{:.4}", value),
+            _ => thousands(value as u64),
+        }
+    }
+}
+
+/// One --alert-rule, e.g. "publisher pub-1 bid_rate < 5% over >= 1000
+/// requests": `[scope name|*] metric op value [over >= N requests]`
+#[derive(Debug, Clone, PartialEq)]
+pub struct AlertRule {
+    /// As given
+    pub spec: String,
+    scope: Scope,
+    /// Key to check; None checks every key of the scope (`*`)
+    name: Option<String>,
+    metric: RuleMetric,
+    op: &'static str,
+    threshold: f64,
+    /// Keys with fewer requests are left alone
+    min_requests: u64,
+}
+
+impl AlertRule {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim();
+        let (condition, over) = match spec.split_once(" over ") {
+            Some((condition, over)) => (condition.trim(), Some(over.trim())),
+            None => (spec, None),
+        };
+
+        let mut words = condition.split_whitespace().peekable();
+        let scope = match words
+            .peek()
+            .and_then(|w| Scope::ALL.iter().find(|(n, _)| n == w))
+        {
+            Some(&(_, scope)) => {
+                words.next();
+                scope
+            }
+            None => Scope::Scan,
+        };
+        let name = match scope {
+            Scope::Scan => None,
+            _ => match words.next() {
+                Some("*") => None,
+                Some(name) => Some(name.to_string()),
+                None => bail!("alert rule '{spec}' needs a {} or *", scope.name()),
+            },
+        };
+
+        let test = words.collect::<Vec<_>>().join(" ");
+        let (at, op) = [">=", "<=", ">", "<"]
+            .iter()
+            .find_map(|op| test.find(op).map(|at| (at, *op)))
+            .with_context(|| {
+                format!("alert rule '{spec}' needs a comparison, e.g. bid_rate < 5%")
+            })?;
+        let metric_name = test[..at].trim();
+        let value = test[at + op.len()..].trim();
+        let Some(&(_, metric)) = RuleMetric::ALL.iter().find(|(n, _)| *n == metric_name) else {
+            let names: Vec<&str> = RuleMetric::ALL.iter().map(|(n, _)| *n).collect();
+            bail!(
+                "unknown metric '{metric_name}' in alert rule '{spec}', expected one of: {}",
+                names.join(", ")
+            );
+        };
+        let threshold = if metric == RuleMetric::BidRate {
+            parse_percent(value)?
+        } else {
+            value
+                .parse::<f64>()
+                .with_context(|| format!("invalid number '{value}' in alert rule '{spec}'"))?
+        };
+
+        let min_requests = match over {
+            Some(over) => {
+                let count = over
+                    .trim_end_matches("requests")
+                    .trim()
+                    .trim_start_matches(">=")
+                    .trim();
+                count.parse::<u64>().with_context(|| {
+                    format!("alert rule '{spec}': expected 'over >= N requests'")
+                })?
+            }
+            None => 0,
+        };
+
+        Ok(Self {
+            spec: spec.to_string(),
+            scope,
+            name,
+            metric,
+            op,
+            threshold,
+            min_requests,
+        })
+    }
+
+    /// Reject a rule on stats --group-by leaves out, which could never fire
+    pub fn check_groups(&self, group_by: GroupBy) -> Result<()> {
+        match self.scope.group() {
+            Some(group) if !group_by.has(group) => bail!(
+                "alert rule '{}' needs the {} stats; add it to --group-by",
+                self.spec,
+                self.scope.name()
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    fn holds(&self, value: f64) -> bool {
+        match self.op {
+            ">=" => value >= self.threshold,
+            "<=" => value <= self.threshold,
+            ">" => value > self.threshold,
+            _ => value < self.threshold,
+        }
+    }
+}
+
+/// A rule that held for one key of its scope
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct RuleAlert {
+    pub rule: String,
+    pub scope: &'static str,
+    pub key: String,
+    pub metric: &'static str,
+    pub value: f64,
+    pub requests: u64,
+    /// One line for people, e.g. in the webhook post
+    pub message: String,
+}
+
+/// Every (rule, key) the rules hold for, busiest keys first within a rule
+pub fn evaluate_rules(rules: &[AlertRule], global: &GlobalStats) -> Vec<RuleAlert> {
+    let mut alerts = Vec::new();
+    for rule in rules {
+        let mut fired: Vec<RuleAlert> = rule
+            .scope
+            .keys(global)
+            .into_iter()
+            .filter(|(name, _, stats)| {
+                rule.name.as_ref().is_none_or(|n| n == name) && stats.requests >= rule.min_requests
+            })
+            .filter_map(|(_, label, stats)| {
+                let value = rule.metric.value(&stats);
+                rule.holds(value).then(|| RuleAlert {
+                    message: format!(
+                        "{}{}: {} {} {} {} ({} requests)",
+                        match rule.scope {
+                            Scope::Scan => String::new(),
+                            scope => format!("{} ", scope.name()),
+                        },
+                        label,
+                        rule.metric.name(),
+                        rule.metric.format(value),
+                        rule.op,
+                        rule.metric.format(rule.threshold),
+                        thousands(stats.requests)
+                    ),
+                    rule: rule.spec.clone(),
+                    scope: rule.scope.name(),
+                    key: label,
+                    metric: rule.metric.name(),
+                    value,
+                    requests: stats.requests,
+                })
+            })
+            .collect();
+        fired.sort_by_key(|a| Reverse(a.requests));
+        alerts.extend(fired);
+    }
+    alerts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::process_record_global;
+    use crate::stats::tests::make_record;
+
+    #[test]
+    fn test_alert_rules() {
+        let mut global = GlobalStats::new();
+        for i in 0..40 {
+            process_record_global(&make_record(300, 250, i % 10 == 0, 1.0), &mut global);
+        }
+        for _ in 0..5 {
+            process_record_global(&make_record(728, 90, false, 0.0), &mut global);
+        }
+
+        let parse = |spec: &str| AlertRule::parse(spec).unwrap();
+        let rules = vec![
+            parse("format * bid_rate < 20% over >= 10 requests"),
+            parse("format 728x90 bids < 1"),
+            parse("bid_rate>=50%"),
+        ];
+        let alerts = evaluate_rules(&rules, &global);
+        assert_eq!(alerts.len(), 2);
+        assert_eq!(
+            alerts[0].message,
+            "format 300x250: bid_rate 10.0% < 20.0% (40 requests)"
+        );
+        assert_eq!(alerts[1].key, "728x90");
+        assert_eq!(alerts[1].rule, "format 728x90 bids < 1");
+
+        assert_eq!(parse("requests > 100").scope, Scope::Scan);
+        assert!(AlertRule::parse("publisher bid_rate < 5%").is_err());
+        assert!(AlertRule::parse("ssp a latency > 5").is_err());
+        assert!(AlertRule::parse("bid_rate < 5% over lots").is_err());
+        assert!(parse("ssp * bids < 1")
+            .check_groups(GroupBy::parse("format").unwrap())
+            .is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use std::{collections::BTreeMap, io::Write};
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::metadata::{create_csv, ScanMetadata};
+use crate::stats::scale_count;
+
+/// Seat name used for seatbids without `seat`
+pub const NO_SEAT: &str = "(none)";
+
+/// One seat's bids within a response
+#[derive(Debug, PartialEq)]
+pub struct SeatBids {
+    pub seat: String,
+    pub bids: u64,
+    pub sum_price: f64,
+    pub lowest_price: f64,
+    pub highest_price: f64,
+}
+
+/// Every bid of a response, across all seatbids
+#[derive(Debug, Default, PartialEq)]
+pub struct ResponseBids {
+    pub count: u64,
+    /// Price of the first bid of the first seatbid
+    pub first_price: f64,
+    pub highest_price: f64,
+    /// Seats in response order; a seat repeated across seatbids is merged
+    pub seats: Vec<SeatBids>,
+}
+
+impl ResponseBids {
+    pub fn from_response(response: &Value) -> Self {
+        let mut bids = ResponseBids::default();
+        let Some(seatbids) = response["seatbid"].as_array() else {
+            return bids;
+        };
+        for seatbid in seatbids {
+            let Some(seat_bids) = seatbid["bid"].as_array().filter(|b| !b.is_empty()) else {
+                continue;
+            };
+            let seat = seatbid["seat"].as_str().unwrap_or(NO_SEAT);
+            let index = match bids.seats.iter().position(|s| s.seat == seat) {
+                Some(index) => index,
+                None => {
+                    bids.seats.push(SeatBids {
+                        seat: seat.to_string(),
+                        bids: 0,
+                        sum_price: 0.0,
+                        lowest_price: f64::INFINITY,
+                        highest_price: 0.0,
+                    });
+                    bids.seats.len() - 1
+                }
+            };
+            for bid in seat_bids {
+                let price = bid["price"].as_f64().unwrap_or(0.0);
+                if bids.count == 0 {
+                    bids.first_price = price;
+                }
+                bids.count += 1;
+                bids.highest_price = bids.highest_price.max(price);
+                let entry = &mut bids.seats[index];
+                entry.bids += 1;
+                entry.sum_price += price;
+                entry.lowest_price = entry.lowest_price.min(price);
+                entry.highest_price = entry.highest_price.max(price);
+            }
+        }
+        bids
+    }
+
+    /// The seat holding the highest bid (the first one on a tie)
+    pub fn winning_seat(&self) -> Option<&str> {
+        self.seats
+            .iter()
+            .find(|s| s.highest_price >= self.highest_price)
+            .map(|s| s.seat.as_str())
+    }
+}
+
+/// Bids-per-request distribution and first vs highest price
+#[derive(Debug, Default, Clone)]
+pub struct BidDensityStats {
+    /// Requests by the number of bids in their response (0 = no bid)
+    pub bids_per_request: BTreeMap<u64, u64>,
+    /// Over responses with bids
+    pub sum_first_price: f64,
+    pub sum_highest_price: f64,
+}
+
+impl BidDensityStats {
+    pub fn observe(&mut self, bids: &ResponseBids) {
+        *self.bids_per_request.entry(bids.count).or_default() += 1;
+        if bids.count == 0 {
+            return;
+        }
+        self.sum_first_price += bids.first_price;
+        self.sum_highest_price += bids.highest_price;
+    }
+
+    pub fn merge(&mut self, other: &BidDensityStats) {
+        for (count, requests) in &other.bids_per_request {
+            *self.bids_per_request.entry(*count).or_default() += requests;
+        }
+        self.sum_first_price += other.sum_first_price;
+        self.sum_highest_price += other.sum_highest_price;
+    }
+
+    /// Multiply counts by `factor` (extrapolating from a sample)
+    pub fn scale(&mut self, factor: f64) {
+        for requests in self.bids_per_request.values_mut() {
+            scale_count(requests, factor);
+        }
+        self.sum_first_price *= factor;
+        self.sum_highest_price *= factor;
+    }
+
+    /// Requests whose response carried at least one bid
+    pub fn requests_with_bids(&self) -> u64 {
+        self.bids_per_request
+            .iter()
+            .filter(|(&count, _)| count > 0)
+            .map(|(_, requests)| requests)
+            .sum()
+    }
+
+    pub fn summarize(&self) -> BidDensityReport {
+        let requests: u64 = self.bids_per_request.values().sum();
+        let with_bids = self.requests_with_bids();
+        let per_bid = |sum: f64| {
+            if with_bids == 0 {
+                0.0
+            } else {
+                sum / with_bids as f64
+            }
+        };
+        BidDensityReport {
+            requests_with_bids: with_bids,
+            multi_bid_requests: self.bids_per_request.range(2..).map(|(_, r)| r).sum(),
+            avg_first_price: per_bid(self.sum_first_price),
+            avg_highest_price: per_bid(self.sum_highest_price),
+            bids_per_request: self
+                .bids_per_request
+                .iter()
+                .map(|(&bids, &count)| BidCountRow {
+                    bids,
+                    requests: count,
+                    share: if requests == 0 {
+                        0.0
+                    } else {
+                        count as f64 / requests as f64
+                    },
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct BidCountRow {
+    pub bids: u64,
+    pub requests: u64,
+    pub share: f64,
+}
+
+/// Multi-bid section of the report
+#[derive(serde::Serialize)]
+pub struct BidDensityReport {
+    pub requests_with_bids: u64,
+    /// Requests answered with more than one bid
+    pub multi_bid_requests: u64,
+    pub avg_first_price: f64,
+    pub avg_highest_price: f64,
+    pub bids_per_request: Vec<BidCountRow>,
+}
+
+/// Write bid_density.csv (requests by bids per response)
+pub fn write_bid_density_csv(
+    out_dir: &str,
+    report: &BidDensityReport,
+    meta: &ScanMetadata,
+) -> Result<()> {
+    let path = format!("{}/bid_density.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    writeln!(csv, "bids,requests,share")?;
+    for row in &report.bids_per_request {
+        writeln!(csv, "{},{},{:.4}", row.bids, row.requests, row.share)?;
+    }
+    eprintln!("Bid density written to: {}", path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_multi_seat_response() {
+        let response = json!({ "seatbid": [
+            { "seat": "dsp_a", "bid": [{ "price": 1.0 }, { "price": 0.5 }] },
+            { "bid": [] },
+            { "seat": "dsp_b", "bid": [{ "price": 2.5 }] },
+            { "seat": "dsp_a", "bid": [{ "price": 0.25 }] }
+        ]});
+        let bids = ResponseBids::from_response(&response);
+        assert_eq!(bids.count, 4);
+        assert_eq!(bids.first_price, 1.0);
+        assert_eq!(bids.highest_price, 2.5);
+        assert_eq!(bids.seats.len(), 2);
+        assert_eq!(bids.seats[0].bids, 3);
+        assert_eq!(bids.seats[0].lowest_price, 0.25);
+        assert_eq!(bids.winning_seat(), Some("dsp_b"));
+
+        let mut stats = BidDensityStats::default();
+        stats.observe(&bids);
+        stats.observe(&ResponseBids::from_response(&json!({ "seatbid": [] })));
+        stats.observe(&ResponseBids::from_response(&json!({
+            "seatbid": [{ "bid": [{ "price": 1.5 }] }]
+        })));
+        let report = stats.summarize();
+        assert_eq!(report.requests_with_bids, 2);
+        assert_eq!(report.multi_bid_requests, 1);
+        assert!((report.avg_first_price - 1.25).abs() < 1e-9);
+        assert!((report.avg_highest_price - 2.0).abs() < 1e-9);
+        let counts: Vec<(u64, u64)> = report
+            .bids_per_request
+            .iter()
+            .map(|r| (r.bids, r.requests))
+            .collect();
+        assert_eq!(counts, vec![(0, 1), (1, 1), (4, 1)]);
+    }
+}
@@ -0,0 +1,86 @@
+use serde_json::Value;
+
+/// Tier-1 IAB content categories (Content Taxonomy 1.0) and their names
+const IAB_TIER1: &[(&str, &str)] = &[
+    ("IAB1", "Arts & Entertainment"),
+    ("IAB2", "Automotive"),
+    ("IAB3", "Business"),
+    ("IAB4", "Careers"),
+    ("IAB5", "Education"),
+    ("IAB6", "Family & Parenting"),
+    ("IAB7", "Health & Fitness"),
+    ("IAB8", "Food & Drink"),
+    ("IAB9", "Hobbies & Interests"),
+    ("IAB10", "Home & Garden"),
+    ("IAB11", "Law, Gov't & Politics"),
+    ("IAB12", "News"),
+    ("IAB13", "Personal Finance"),
+    ("IAB14", "Society"),
+    ("IAB15", "Science"),
+    ("IAB16", "Pets"),
+    ("IAB17", "Sports"),
+    ("IAB18", "Style & Fashion"),
+    ("IAB19", "Technology & Computing"),
+    ("IAB20", "Travel"),
+    ("IAB21", "Real Estate"),
+    ("IAB22", "Shopping"),
+    ("IAB23", "Religion & Spirituality"),
+    ("IAB24", "Uncategorized"),
+    ("IAB25", "Non-Standard Content"),
+    ("IAB26", "Illegal Content"),
+];
+
+/// Readable name of an IAB 1.0 category; a subcategory ("IAB17-12") gets
+/// its tier-1 name. Empty for codes from other taxonomies.
+pub fn category_label(code: &str) -> &'static str {
+    let tier1 = code.split('-').next().unwrap_or(code);
+    IAB_TIER1
+        .iter()
+        .find(|(c, _)| c.eq_ignore_ascii_case(tier1))
+        .map_or("", |(_, label)| label)
+}
+
+/// Content categories of one request, each once: site.cat or app.cat plus
+/// the categories of their `content` object
+pub fn request_categories(request: &Value) -> Vec<String> {
+    let mut categories: Vec<String> = ["site", "app"]
+        .iter()
+        .flat_map(|dist| [&request[dist]["cat"], &request[dist]["content"]["cat"]])
+        .filter_map(Value::as_array)
+        .flatten()
+        .filter_map(Value::as_str)
+        .map(str::trim)
+        .filter(|c| !c.is_empty())
+        .map(str::to_string)
+        .collect();
+    categories.sort_unstable();
+    categories.dedup();
+    categories
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_request_categories() {
+        let request = serde_json::json!({
+            "site": {
+                "cat": ["IAB17", "IAB17-12"],
+                "content": { "cat": ["IAB17", "IAB12", ""] }
+            }
+        });
+        assert_eq!(
+            request_categories(&request),
+            vec!["IAB12", "IAB17", "IAB17-12"]
+        );
+
+        let app = serde_json::json!({ "app": { "content": { "cat": ["IAB19-6"] } } });
+        assert_eq!(request_categories(&app), vec!["IAB19-6"]);
+        assert!(request_categories(&serde_json::json!({ "site": {} })).is_empty());
+
+        assert_eq!(category_label("IAB17"), "Sports");
+        assert_eq!(category_label("IAB19-6"), "Technology & Computing");
+        assert_eq!(category_label("483"), "");
+    }
+}
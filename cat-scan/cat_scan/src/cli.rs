@@ -0,0 +1,1740 @@
+use anyhow::{anyhow, bail, Context, Result};
+use cat_config::{Setting, Settings};
+
+use crate::alerts::AlertRule;
+use crate::clickhouse::ClickHouseTarget;
+use crate::decode::{ColumnMap, InputFormat, RecordLayout};
+use crate::examples::DEFAULT_EXAMPLE_BUDGET;
+use crate::extract::ExtractRule;
+use crate::filter::RecordFilter;
+use crate::gate::FailCondition;
+use crate::groups::{Group, GroupBy};
+use crate::history::DEFAULT_HISTORY_RUNS;
+use crate::html::check_report_template;
+use crate::input::{OnError, Sampler};
+use crate::join::{JoinInputs, DEFAULT_JOIN_MEMORY_MB};
+use crate::live::now_ms;
+use crate::notify::{AlertThresholds, NotifyConfig, NotifyFormat};
+use crate::prices::{parse_price_buckets, DEFAULT_PRICE_BUCKETS};
+use crate::problems::Detectors;
+use crate::query::{Metric, PivotQuery};
+use crate::redact::Redaction;
+use crate::source::{parse_object_uri, parse_time_bound, DateRange};
+use crate::stats::DEFAULT_TIME_BUCKET_MS;
+use crate::validate::OrtbVersion;
+use crate::watch::{DEFAULT_CURSOR_FILE, DEFAULT_WATCH_EVERY_SECS};
+
+const USAGE: &str = "Usage: cat_scan <path|s3://|gs://|az://bucket/key|kafka://brokers/topic> [OPTIONS]\n       \
+     cat_scan --requests <path> --responses <path> [OPTIONS]\n       \
+     cat_scan live <path|kafka://...> [LIVE OPTIONS]\n       \
+     cat_scan tail <path|kafka://...> [TAIL OPTIONS]\n       \
+     cat_scan watch <s3://|gs://|az://bucket/prefix/> --out DIR [--every DURATION] [OPTIONS]\n       \
+     cat_scan serve --out DIR [--port N]\n       \
+     cat_scan validate <path|s3://...> [VALIDATE OPTIONS]\n       \
+     cat_scan diff <old> <new> [--confidence PCT]\n       \
+     cat_scan query <out dir> \"<rows> by <columns>\" [--metric M] [--top N]\n       \
+     cat_scan tui <out dir|report.json>\n       \
+     cat_scan generate-sample [--lines N] [--out FILE] [--seed N] [--traffic FILE]\n\n\
+     Every option can also be set as CAT_SCAN_<OPTION> in the environment, e.g.\n\
+     CAT_SCAN_MIN_REQUESTS=100 or CAT_SCAN_SEGMENT_STATS=1, or in the [cat_scan] section\n\
+     of a TOML file shared with fake_ssp and fake_bidder (min_requests = 100); the\n\
+     scan input too (CAT_SCAN_INPUT, input). Flags win, and repeatable ones add up.\n\n\
+     Options:\n  \
+     --config FILE              Read settings from FILE (CAT_CONFIG)\n  \
+     --min-requests N           Only show formats with >= N requests\n  \
+     --sort-by format|requests|bid_rate\n  \
+     --format csv|table|md      Print format rows as CSV, as an aligned table with bid rate colors\n                                and STOP badges (without --out; the default is a table on a\n                                terminal, CSV when piped) or a Markdown summary for Slack/email\n                                (also written to summary.md with --out)\n  \
+     --out DIR                  Output directory for CSV and HTML files\n  \
+     --html-out PATH            Generate HTML report at PATH (deprecated, use --out)\n  \
+     --template PATH            Render the HTML report with a custom template (minijinja)\n  \
+     --max-rows-per-table N     Keep at most N rows per table (and per dimension) in the HTML\n                                report and report.json; the CSV outputs keep every row\n  \
+     --notify-webhook URL       POST a summary of each scan to URL (Slack incoming webhook or any\n                                JSON endpoint)\n  \
+     --notify-format json|slack Body of the webhook post (default: slack for hooks.slack.com, else json)\n  \
+     --alert-problems N         Flag the notification as an alert above N problem formats\n  \
+     --alert-wasted PCT         Flag it as an alert when more than PCT% of requests go to zero-bid formats\n  \
+     --alerts-only              Only post when an --alert-* threshold is exceeded\n  \
+     --fail-on COND             Exit with code 2 when COND holds, e.g. wasted_traffic>20%,\n                                zero_bid_formats>5, parse_errors>1% (repeatable or comma-separated;\n                                also bid_rate, problem_formats and alerts)\n  \
+     --alert-rule RULE          Report an alert when RULE holds, e.g. 'publisher pub-1 bid_rate < 5%\n                                over >= 1000 requests' or 'ssp * bids < 1' (repeatable or\n                                ;-separated; see the README for the syntax)\n  \
+     --bundle                   Keep the report data in report.json only; report.html loads it\n                                (for very large reports; view with `cat_scan serve`)\n  \
+     --time-analysis            Show bid rate trends over time\n  \
+     --bucket DURATION          Time analysis bucket size, e.g. 30s, 5m, 1h (default: 1m)\n  \
+     --ts-field PATH            Read timestamps from PATH in each log line instead of ts_ms,\n                                e.g. request.ext.ts (epoch s/ms/us/ns or ISO8601)\n  \
+     --segment-stats            Show per-publisher and per-segment stats\n  \
+     --examples K               Keep up to K example record refs per aggregation key\n  \
+     --examples-budget N        Cap on total example refs kept (default: 100000)\n  \
+     --samples N                Embed up to N raw records per problem format in the HTML drill-down\n  \
+     --price-buckets LIST       Price histogram bucket edges (default: 0.1,0.5,1,2,5,10,20)\n  \
+     --ivt                      Detect suspicious traffic (heavy IPs/IFAs, missing UA, impossible sizes)\n  \
+     --datacenter-ips FILE      CIDR list of datacenter ranges to flag (implies --ivt)\n  \
+     --allowlist FILE           Site domains / app bundles we target, one per line: audit traffic\n                                outside it per SSP\n  \
+     --denylist FILE            Site domains / app bundles we exclude: audit traffic on it per SSP\n  \
+     --sellers-json [SSP=]PATH|URL\n                                An SSP's sellers.json: flag publisher and schain seller ids it\n                                doesn't list as unauthorized supply (repeatable)\n  \
+     --spoof                    Flag likely spoofed inventory per SSP (site.domain vs site.page,\n                                malformed app.bundle, app.storeurl for another app)\n  \
+     --geoip FILE.mmdb          Derive device.geo country/region from device.ip with a MaxMind-format\n                                database when the request has no country\n  \
+     --fingerprint              Find byte-identical and near-identical requests per SSP\n                                (SSP-side duplication or resold impressions)\n  \
+     --exclude-test             Drop test traffic (BidRequest.test=1) from the stats; it is\n                                counted separately either way\n  \
+     --state FILE               Track format/publisher first/last seen across runs in FILE\n  \
+     --history-dir DIR          Append this scan's summary to DIR/history.jsonl and chart the\n                                last runs in DIR/trend.html\n  \
+     --history-runs N           Scans shown in trend.html (default: 30)\n  \
+     --extract NAME=PATH        Custom dimension from a request path, e.g. gpid=imp[0].ext.gpid (repeatable)\n  \
+     --extract-config FILE      Load NAME=PATH extract rules from FILE, one per line\n  \
+     --plugin FILE.wasm         Add dimensions from a WebAssembly module to each request under\n                                ext.enrich, for --extract/--filter (repeatable; build with\n                                --features wasm)\n  \
+     --group-by LIST            Only compute these aggregations: format (always), ssp, publisher,\n                                segment, geo, hour, content, bids (default: all)\n  \
+     --detectors LIST           Problem detectors to run: zero_bids, non_standard, low_bid_rate\n                                (default: all)\n  \
+     --redact LIST              Take identifiers out of every record before any output: publisher\n                                (hash ids), ip, ifa, domain (cut to the registrable domain), or all\n  \
+     --redact-salt SECRET       Mix SECRET into the publisher id hashes of --redact\n  \
+     --filter EXPR              Only aggregate records matching EXPR, e.g.\n                                'ssp == \"fake_ssp\" && w >= 300 && country == \"SE\"' (repeatable, ANDed)\n  \
+     --sample RATE              Only parse a deterministic RATE fraction of lines (e.g. 0.01), scaling counts\n  \
+     --sample-every N           Only parse every Nth line, scaling counts by N\n  \
+     --quiet                    Don't show the progress bar\n  \
+     --profile                  Print time spent reading, parsing, aggregating and rendering,\n                                and lines/sec, at the end of the run\n  \
+     --input-format FORMAT      jsonl (default), proto (length-delimited LogEntry), csv, tsv,\n                                prebid (Prebid.js / Prebid Server auction logs) or google-ab\n                                (Authorized Buyers protobuf, built with --features google-ab)\n  \
+     --column-map MAP           CSV/TSV columns, e.g. request=req_json,response=resp_json,ts=time (or a file)\n  \
+     --request-field KEY        Top-level key holding the request in JSON lines (default: detected,\n                                e.g. request, req, bid_request)\n  \
+     --response-field KEY       Top-level key holding the response (default: detected)\n  \
+     --perspective bidder|ssp   Whose logs these are (default: bidder); ssp also compares the DSPs\n                                that answered, named by --dsp-field\n  \
+     --dsp-field PATH           Where each log line names the DSP, e.g. response.ext.dsp (default: dsp)\n  \
+     --on-error skip|abort      Skip or abort on malformed lines (default: abort)\n  \
+     --max-errors N             Abort once more than N lines were skipped (implies --on-error skip)\n  \
+     --bad-lines FILE           Write skipped lines to FILE (implies --on-error skip)\n  \
+     --export clickhouse://HOST/DB  Append format/publisher/time bucket rows to ClickHouse tables\n  \
+     --dataset DIR              Write a Parquet table per dimension + manifest.json + queries.sql for DuckDB\n  \
+     --since TIME               With a prefix input (s3://bucket/logs/), only scan objects from TIME on\n  \
+     --until TIME               ...and before TIME; RFC3339, a date, or relative like 24h\n  \
+     --requests PATH            Request log to join with --responses on request id (instead of <path>)\n  \
+     --responses PATH           Response log (BidResponse.id or request_id = the request id)\n  \
+     --join-memory MB           Response table size before the join spills to disk (default: 1024)\n\n\
+     Live options (follow a growing log and serve a rolling-window dashboard):\n  \
+     --window DURATION          Rolling window size, e.g. 15m, 1h (default: 15m)\n  \
+     --port N                   Dashboard port (default: 8080)\n  \
+     --refresh DURATION         Dashboard refresh interval (default: 2s)\n  \
+     --from-start               Read the existing file contents before following\n\n\
+     Tail options (follow a growing log and print rolling-window stats):\n  \
+     --windows LIST             Comma-separated windows, e.g. 5m,15m,60m (default)\n  \
+     --interval DURATION        How often to print (default: 5s)\n  \
+     --top N                    Rows in the top formats / problems lists (default: 10)\n  \
+     --from-start               Read the existing file contents before following\n\n\
+     Watch options (rescan a prefix on a schedule, reading only the objects added since the\n\
+     previous run, and rewrite the report in --out; takes the scan options above too):\n  \
+     --every DURATION           Time between scans, e.g. 5m, 1h (default: 15m)\n  \
+     --cursor FILE              Where the last scanned object is recorded (default:\n                                OUT/watch_cursor.json)\n\n\
+     Serve options (serve the report in an --out directory over HTTP):\n  \
+     --out DIR                  Directory a scan wrote its report to\n  \
+     --port N                   Port (default: 8080)\n\n\
+     Validate options (check requests against OpenRTB structural rules):\n  \
+     --ortb 2.5|2.6             OpenRTB version to check against (default: 2.6)\n  \
+     --top N                    Worst (SSP, rule) offenders to list (default: 20)\n  \
+     --out DIR                  Write validation.json and validation_stats.csv to DIR\n  \
+     --quiet                    Don't show the progress bar\n\n\
+     Diff options (per-format bid rate changes between two scans' format_stats.csv,\n\
+     given as the files or the --out directories holding them):\n  \
+     --confidence PCT           Confidence for a change to count as significant (two-proportion\n                                z-test; default: 95%)\n\n\
+     Query options (a cross-tab of two dimensions read from a scan's --out CSVs: format by ssp,\n\
+     publisher|segment by ssp, segment by publisher, secure|consent by ssp, inventory by\n\
+     format|ssp|publisher, position|viewability by publisher; either order):\n  \
+     --metric M                 Cell value: requests|bids|bid_rate|avg_bid_price (default: requests)\n  \
+     --top N                    Busiest rows and columns to show, 0 for all (default: 20)\n\n\
+     Generate-sample options (write a synthetic log of fake_ssp requests answered by\n\
+     fake_bidder's built-in policy, to try cat_scan without running them):\n  \
+     --lines N                  Log lines to write (default: 10000)\n  \
+     --out FILE                 Where to write them (default: sample.jsonl)\n  \
+     --seed N                   Seed of the random traffic; the same seed gives the same file\n                                (default: 1)\n  \
+     --traffic FILE             fake_ssp traffic profile, TOML or JSON (default: fake_ssp's built-in)\n\n\
+     Examples:\n  \
+     cat_scan fake_ssp_logs.jsonl --out ./reports\n  \
+     cat_scan s3://bucket/logs.jsonl --out ./reports\n  \
+     cat_scan s3://bucket/logs/ --since 24h --out ./reports\n  \
+     cat_scan gs://bucket/logs.jsonl --out ./reports   (build with --features gcs)\n  \
+     cat_scan 'kafka://broker:9092/bid-logs?group=cat_scan&duration=10m' --out ./reports\n  \
+     cat_scan logs.jsonl --time-analysis --segment-stats\n  \
+     cat_scan live fake_ssp_logs.jsonl --window 5m --port 8080\n  \
+     cat_scan tail fake_ssp_logs.jsonl --windows 1m,5m,15m\n  \
+     cat_scan watch --every 15m s3://bucket/logs/ --out ./reports --history-dir ./history\n  \
+     cat_scan serve --out ./reports --port 8080\n  \
+     cat_scan validate logs.jsonl --ortb 2.5 --out ./reports\n  \
+     cat_scan diff ./reports/monday ./reports/tuesday\n  \
+     cat_scan query ./reports \"format by ssp\" --metric bid_rate\n  \
+     cat_scan tui ./reports\n  \
+     cat_scan generate-sample --lines 10000 --out sample.jsonl";
+
+/// Scan options that can come from cat_config settings
+const SCAN_SETTINGS: &[Setting] = &[
+    Setting::value("min_requests"),
+    Setting::value("sort_by"),
+    Setting::value("format"),
+    Setting::value("out"),
+    Setting::value("html_out"),
+    Setting::value("template"),
+    Setting::value("max_rows_per_table"),
+    Setting::value("notify_webhook"),
+    Setting::value("notify_format"),
+    Setting::value("alert_problems"),
+    Setting::value("alert_wasted"),
+    Setting::switch("alerts_only"),
+    Setting::value("fail_on"),
+    Setting::value("alert_rule"),
+    Setting::switch("bundle"),
+    Setting::switch("time_analysis"),
+    Setting::value("bucket"),
+    Setting::value("ts_field"),
+    Setting::switch("segment_stats"),
+    Setting::value("examples"),
+    Setting::value("examples_budget"),
+    Setting::value("samples"),
+    Setting::value("price_buckets"),
+    Setting::switch("ivt"),
+    Setting::value("datacenter_ips"),
+    Setting::value("allowlist"),
+    Setting::value("denylist"),
+    Setting::value("sellers_json"),
+    Setting::switch("spoof"),
+    Setting::value("geoip"),
+    Setting::switch("fingerprint"),
+    Setting::switch("exclude_test"),
+    Setting::value("state"),
+    Setting::value("history_dir"),
+    Setting::value("history_runs"),
+    Setting::value("extract"),
+    Setting::value("extract_config"),
+    Setting::value("plugin"),
+    Setting::value("filter"),
+    Setting::value("group_by"),
+    Setting::value("detectors"),
+    Setting::value("redact"),
+    Setting::value("redact_salt"),
+    Setting::value("sample"),
+    Setting::value("sample_every"),
+    Setting::switch("quiet"),
+    Setting::switch("profile"),
+    Setting::value("input_format"),
+    Setting::value("column_map"),
+    Setting::value("request_field"),
+    Setting::value("response_field"),
+    Setting::value("perspective"),
+    Setting::value("dsp_field"),
+    Setting::value("on_error"),
+    Setting::value("max_errors"),
+    Setting::value("bad_lines"),
+    Setting::value("export"),
+    Setting::value("dataset"),
+    Setting::value("since"),
+    Setting::value("until"),
+    Setting::value("requests"),
+    Setting::value("responses"),
+    Setting::value("join_memory"),
+];
+
+const LIVE_SETTINGS: &[Setting] = &[
+    Setting::value("window"),
+    Setting::value("port"),
+    Setting::value("refresh"),
+    Setting::switch("from_start"),
+];
+
+const TAIL_SETTINGS: &[Setting] = &[
+    Setting::value("windows"),
+    Setting::value("interval"),
+    Setting::value("top"),
+    Setting::switch("from_start"),
+];
+
+const WATCH_SETTINGS: &[Setting] = &[Setting::value("every"), Setting::value("cursor")];
+
+const SERVE_SETTINGS: &[Setting] = &[Setting::value("out"), Setting::value("port")];
+
+const DIFF_SETTINGS: &[Setting] = &[Setting::value("confidence")];
+
+const QUERY_SETTINGS: &[Setting] = &[Setting::value("metric"), Setting::value("top")];
+
+const VALIDATE_SETTINGS: &[Setting] = &[
+    Setting::value("ortb"),
+    Setting::value("top"),
+    Setting::value("out"),
+    Setting::switch("quiet"),
+];
+
+#[derive(Debug, Clone, Copy)]
+pub enum SortBy {
+    Format,
+    RequestsDesc,
+    BidRateDesc,
+}
+
+/// What a scan prints to stdout (--format)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// Canonical format rows as CSV, unless --out is given
+    Csv,
+    /// Canonical format rows as an aligned, colored table, unless --out is
+    /// given
+    Table,
+    /// Markdown executive summary, also written to summary.md with --out
+    Markdown,
+}
+
+#[derive(Debug)]
+pub struct Config {
+    pub input_path: String,
+    pub min_requests: u64,
+    pub sort_by: SortBy,
+    /// None prints a table when stdout is a terminal and CSV otherwise
+    pub output_format: Option<OutputFormat>,
+    pub html_out: Option<String>,
+    pub out_dir: Option<String>,
+    /// Contents of the --template file replacing the built-in report template
+    pub report_template: Option<String>,
+    /// report.html fetches report.json instead of embedding the data
+    pub bundle: bool,
+    /// Cap on the rows of the report's big tables (--max-rows-per-table)
+    pub max_rows_per_table: Option<usize>,
+    pub time_analysis: bool,
+    /// Time analysis bucket width
+    pub time_bucket_ms: u64,
+    /// Where each log line keeps its timestamp, when not `ts_ms`
+    pub ts_field: Option<ExtractRule>,
+    pub segment_stats: bool,
+    pub examples_per_key: usize,
+    pub examples_budget: usize,
+    pub samples_per_format: usize,
+    /// Upper edges of the price histogram buckets
+    pub price_buckets: Vec<f64>,
+    pub ivt: bool,
+    pub datacenter_ips: Option<String>,
+    /// Domain lists to audit traffic against (see domains::DomainList)
+    pub allowlist: Option<String>,
+    pub denylist: Option<String>,
+    /// sellers.json files to check supply against (see
+    /// sellers::SellersJson::load)
+    pub sellers_json: Vec<String>,
+    /// Look for spoofed domains and bundles (see spoof::SpoofStats)
+    pub spoof: bool,
+    /// MaxMind-format database filling in device.geo (see geoip::GeoIp)
+    pub geoip: Option<String>,
+    /// Fingerprint requests to find repeats (see fingerprint::RequestFingerprints)
+    pub fingerprint: bool,
+    /// Leave test=1 requests out of the stats (--exclude-test)
+    pub exclude_test: bool,
+    pub state_path: Option<String>,
+    /// Scan summaries kept across runs, with trend.html (--history-dir)
+    pub history_dir: Option<String>,
+    /// Most recent scans charted in trend.html
+    pub history_runs: usize,
+    pub extract_rules: Vec<ExtractRule>,
+    /// WebAssembly modules adding dimensions to each record (see
+    /// plugin::Plugin)
+    pub plugins: Vec<String>,
+    /// Aggregations to compute (--group-by)
+    pub group_by: GroupBy,
+    /// Problem detectors to run (--detectors)
+    pub detectors: Detectors,
+    /// Identifiers taken out of each record (--redact)
+    pub redact: Option<Redaction>,
+    /// Records to aggregate; the rest are dropped (--filter)
+    pub filter: Option<RecordFilter>,
+    pub sampler: Sampler,
+    pub quiet: bool,
+    /// Time each phase of the scan (see profile::ScanProfile)
+    pub profile: bool,
+    pub on_error: OnError,
+    pub max_errors: Option<u64>,
+    pub bad_lines: Option<String>,
+    pub input_format: InputFormat,
+    pub column_map: ColumnMap,
+    /// Request/response keys of JSON lines; unset ones are detected
+    pub layout: RecordLayout,
+    /// Where each log line names the DSP that answered (--perspective ssp)
+    pub dsp_field: Option<ExtractRule>,
+    /// Separate request and response logs to join instead of `input_path`
+    pub join: Option<JoinInputs>,
+    pub export: Option<ClickHouseTarget>,
+    /// Webhook to post the scan summary to
+    pub notify: Option<NotifyConfig>,
+    /// --fail-on conditions; any that holds fails the scan (CI gating)
+    pub fail_on: Vec<FailCondition>,
+    /// --alert-rule rules, checked after aggregation
+    pub alert_rules: Vec<AlertRule>,
+    /// The options as given, recorded in each output (see metadata::ScanMetadata)
+    pub flags: Vec<String>,
+    pub dataset_dir: Option<String>,
+    pub date_range: DateRange,
+}
+
+/// Settings for `cat_scan live`
+#[derive(Debug)]
+pub struct LiveConfig {
+    pub input_path: String,
+    pub window_secs: u64,
+    pub port: u16,
+    pub refresh_secs: u64,
+    pub from_start: bool,
+}
+
+/// Settings for `cat_scan tail`
+#[derive(Debug)]
+pub struct TailConfig {
+    pub input_path: String,
+    /// Rolling windows to report, shortest first
+    pub windows_minutes: Vec<u64>,
+    pub interval_secs: u64,
+    pub from_start: bool,
+    pub top: usize,
+}
+
+/// Settings for `cat_scan watch`
+#[derive(Debug)]
+pub struct WatchConfig {
+    /// The prefix to watch, ending in `/`
+    pub input_path: String,
+    pub every_secs: u64,
+    pub cursor_path: String,
+    /// Scan options, parsed again for each run (see parse_scan_args)
+    pub scan_args: Vec<String>,
+}
+
+/// Settings for `cat_scan serve`
+#[derive(Debug)]
+pub struct ServeConfig {
+    pub out_dir: String,
+    pub port: u16,
+}
+
+/// Settings for `cat_scan validate`
+#[derive(Debug)]
+pub struct ValidateConfig {
+    pub input_path: String,
+    pub version: OrtbVersion,
+    pub top: usize,
+    pub out_dir: Option<String>,
+    pub quiet: bool,
+}
+
+/// Settings for `cat_scan diff`
+#[derive(Debug)]
+pub struct DiffConfig {
+    pub old_path: String,
+    pub new_path: String,
+    /// A bid rate change is significant when its p-value is under 1 - confidence
+    pub confidence: f64,
+}
+
+/// Settings for `cat_scan query`
+#[derive(Debug)]
+pub struct QueryConfig {
+    pub out_dir: String,
+    pub query: PivotQuery,
+    pub metric: Metric,
+    /// Busiest rows and columns to print; 0 prints all
+    pub top: usize,
+}
+
+/// Settings for `cat_scan tui`
+#[derive(Debug)]
+pub struct TuiConfig {
+    /// report.json, or the --out directory holding it
+    pub report_path: String,
+}
+
+/// Settings for `cat_scan generate-sample`
+#[derive(Debug)]
+pub struct GenerateConfig {
+    pub lines: u64,
+    pub out_path: String,
+    pub seed: u64,
+    /// fake_ssp traffic profile; its built-in one when unset
+    pub traffic: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum Command {
+    Scan(Box<Config>),
+    Live(LiveConfig),
+    Tail(TailConfig),
+    Watch(WatchConfig),
+    Serve(ServeConfig),
+    Validate(ValidateConfig),
+    Diff(DiffConfig),
+    Query(QueryConfig),
+    Tui(TuiConfig),
+    GenerateSample(GenerateConfig),
+}
+
+/// Parse a percentage like "20" or "20%" into a fraction (0.2)
+pub fn parse_percent(value: &str) -> Result<f64> {
+    let number = value.trim().trim_end_matches('%');
+    let pct: f64 = number
+        .parse()
+        .with_context(|| format!("invalid percentage '{value}'"))?;
+    if !(0.0..=100.0).contains(&pct) {
+        bail!("percentage must be between 0 and 100, got '{value}'");
+    }
+    Ok(pct / 100.0)
+}
+
+/// Parse a duration like "90s", "15m", "24h" or "7d" into seconds.
+/// A bare number is taken as seconds.
+pub fn parse_duration_secs(value: &str) -> Result<u64> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (digits, unit) = value.split_at(split);
+    let amount: u64 = digits
+        .parse()
+        .with_context(|| format!("invalid duration '{value}', expected e.g. 30s, 15m, 24h"))?;
+    let multiplier = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        other => bail!("unknown duration unit '{other}' in '{value}', expected s|m|h|d"),
+    };
+    amount
+        .checked_mul(multiplier)
+        .ok_or_else(|| anyhow!("duration too large: {value}"))
+}
+
+pub fn parse_args() -> Result<Command> {
+    let known = [
+        SCAN_SETTINGS,
+        LIVE_SETTINGS,
+        TAIL_SETTINGS,
+        WATCH_SETTINGS,
+        SERVE_SETTINGS,
+        VALIDATE_SETTINGS,
+        DIFF_SETTINGS,
+        QUERY_SETTINGS,
+        &[Setting::value("input")],
+    ]
+    .concat();
+    let settings = Settings::load("cat_scan", &known)?;
+    let args = settings.args();
+    // Settings go ahead of the command line, so its flags win
+    let with_settings =
+        |mode: &[Setting], rest: &[String]| [settings.flags(mode), rest.to_vec()].concat();
+
+    let first = args.first().map(String::as_str);
+    match first {
+        Some("--help" | "-h") => bail!(USAGE),
+        Some("live") => {
+            parse_live_args(&with_settings(LIVE_SETTINGS, &args[1..])).map(Command::Live)
+        }
+        Some("tail") => {
+            parse_tail_args(&with_settings(TAIL_SETTINGS, &args[1..])).map(Command::Tail)
+        }
+        Some("watch") => parse_watch_args(
+            &with_settings(WATCH_SETTINGS, &args[1..]),
+            settings.flags(SCAN_SETTINGS),
+            settings.get("input"),
+        )
+        .map(Command::Watch),
+        Some("serve") => {
+            parse_serve_args(&with_settings(SERVE_SETTINGS, &args[1..])).map(Command::Serve)
+        }
+        Some("validate") => parse_validate_args(&with_settings(VALIDATE_SETTINGS, &args[1..]))
+            .map(Command::Validate),
+        Some("diff") => {
+            parse_diff_args(&with_settings(DIFF_SETTINGS, &args[1..])).map(Command::Diff)
+        }
+        Some("query") => {
+            parse_query_args(&with_settings(QUERY_SETTINGS, &args[1..])).map(Command::Query)
+        }
+        Some("tui") => parse_tui_args(&args[1..]).map(Command::Tui),
+        Some("generate-sample") => parse_generate_args(&args[1..]).map(Command::GenerateSample),
+        _ => {
+            let (input_path, rest) = match first {
+                Some(first) if !first.starts_with('-') => (first.to_string(), &args[1..]),
+                _ => (String::new(), args),
+            };
+            let rest = with_settings(SCAN_SETTINGS, rest);
+            // Join mode has no input path
+            let join = rest.iter().any(|a| a == "--requests" || a == "--responses");
+            let input_path = if input_path.is_empty() && !join {
+                settings.get("input").unwrap_or_default()
+            } else {
+                input_path
+            };
+            parse_scan_args(input_path, &rest).map(|config| Command::Scan(Box::new(config)))
+        }
+    }
+}
+
+pub(crate) fn parse_scan_args(input_path: String, rest: &[String]) -> Result<Config> {
+    let mut min_requests: u64 = 0;
+    let mut sort_by = SortBy::Format;
+    let mut output_format: Option<OutputFormat> = None;
+    let mut html_out: Option<String> = None;
+    let mut out_dir: Option<String> = None;
+    let mut report_template: Option<String> = None;
+    let mut bundle = false;
+    let mut max_rows_per_table: Option<usize> = None;
+    let mut time_analysis = false;
+    let mut time_bucket_ms = DEFAULT_TIME_BUCKET_MS;
+    let mut ts_field: Option<ExtractRule> = None;
+    let mut segment_stats = false;
+    let mut examples_per_key: usize = 0;
+    let mut examples_budget = DEFAULT_EXAMPLE_BUDGET;
+    let mut samples_per_format: usize = 0;
+    let mut price_buckets = DEFAULT_PRICE_BUCKETS.to_vec();
+    let mut ivt = false;
+    let mut fingerprint = false;
+    let mut exclude_test = false;
+    let mut datacenter_ips: Option<String> = None;
+    let mut allowlist: Option<String> = None;
+    let mut denylist: Option<String> = None;
+    let mut sellers_json: Vec<String> = Vec::new();
+    let mut spoof = false;
+    let mut geoip: Option<String> = None;
+    let mut state_path: Option<String> = None;
+    let mut history_dir: Option<String> = None;
+    let mut history_runs = DEFAULT_HISTORY_RUNS;
+    let mut extract_rules: Vec<ExtractRule> = Vec::new();
+    let mut plugins: Vec<String> = Vec::new();
+    let mut filters: Vec<String> = Vec::new();
+    let mut group_by = GroupBy::default();
+    let mut detectors = Detectors::default();
+    let mut redact: Option<Redaction> = None;
+    let mut redact_salt: Option<String> = None;
+    let mut sampler = Sampler::All;
+    let mut quiet = false;
+    let mut profile = false;
+    let mut on_error: Option<OnError> = None;
+    let mut max_errors: Option<u64> = None;
+    let mut bad_lines: Option<String> = None;
+    let mut input_format = InputFormat::Jsonl;
+    let mut column_map: Option<ColumnMap> = None;
+    let mut layout = RecordLayout::default();
+    let mut ssp_perspective = false;
+    let mut dsp_field: Option<ExtractRule> = None;
+    let mut join_requests: Option<String> = None;
+    let mut join_responses: Option<String> = None;
+    let mut join_memory_mb = DEFAULT_JOIN_MEMORY_MB;
+    let mut export: Option<ClickHouseTarget> = None;
+    let mut notify_webhook: Option<String> = None;
+    let mut notify_format: Option<NotifyFormat> = None;
+    let mut alert_thresholds = AlertThresholds::default();
+    let mut alerts_only = false;
+    let mut fail_on: Vec<FailCondition> = Vec::new();
+    let mut alert_rules: Vec<AlertRule> = Vec::new();
+    let mut dataset_dir: Option<String> = None;
+    let mut date_range = DateRange::default();
+
+    let mut i = 0;
+    while i < rest.len() {
+        match rest[i].as_str() {
+            "--min-requests" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--min-requests requires a numeric value")?;
+                min_requests = value
+                    .parse::<u64>()
+                    .context("invalid value for --min-requests")?;
+                i += 2;
+            }
+            "--sort-by" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--sort-by requires one of: format|requests|bid_rate")?;
+                sort_by = match value.as_str() {
+                    "format" => SortBy::Format,
+                    "requests" => SortBy::RequestsDesc,
+                    "bid_rate" => SortBy::BidRateDesc,
+                    other => bail!(
+                        "unknown sort key '{other}', expected one of: format|requests|bid_rate"
+                    ),
+                };
+                i += 2;
+            }
+            "--format" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--format requires csv, table or md")?;
+                output_format = Some(match value.as_str() {
+                    "csv" => OutputFormat::Csv,
+                    "table" => OutputFormat::Table,
+                    "md" | "markdown" => OutputFormat::Markdown,
+                    other => bail!("unknown output format '{other}', expected csv, table or md"),
+                });
+                i += 2;
+            }
+            "--html-out" => {
+                let value = rest.get(i + 1).context("--html-out requires a file path")?;
+                html_out = Some(value.clone());
+                i += 2;
+            }
+            "--out" => {
+                let value = rest.get(i + 1).context("--out requires a directory path")?;
+                out_dir = Some(value.clone());
+                i += 2;
+            }
+            "--template" => {
+                let value = rest.get(i + 1).context("--template requires a file path")?;
+                let template = std::fs::read_to_string(value)
+                    .with_context(|| format!("Failed to read template {}", value))?;
+                check_report_template(&template)
+                    .with_context(|| format!("Invalid report template {}", value))?;
+                report_template = Some(template);
+                i += 2;
+            }
+            "--bundle" => {
+                bundle = true;
+                i += 1;
+            }
+            "--max-rows-per-table" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--max-rows-per-table requires a numeric value")?;
+                let max = value
+                    .parse::<usize>()
+                    .context("invalid value for --max-rows-per-table")?;
+                if max == 0 {
+                    bail!("--max-rows-per-table must be at least 1");
+                }
+                max_rows_per_table = Some(max);
+                i += 2;
+            }
+            "--time-analysis" => {
+                time_analysis = true;
+                i += 1;
+            }
+            "--bucket" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--bucket requires a duration like 30s, 5m or 1h")?;
+                let secs = parse_duration_secs(value)?;
+                if secs == 0 {
+                    bail!("--bucket must be at least 1s");
+                }
+                time_bucket_ms = secs * 1000;
+                i += 2;
+            }
+            "--ts-field" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--ts-field requires a path like request.ext.ts")?;
+                ts_field = Some(ExtractRule::for_path(value).context("invalid --ts-field")?);
+                i += 2;
+            }
+            "--segment-stats" => {
+                segment_stats = true;
+                i += 1;
+            }
+            "--examples" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--examples requires a numeric value")?;
+                examples_per_key = value
+                    .parse::<usize>()
+                    .context("invalid value for --examples")?;
+                i += 2;
+            }
+            "--examples-budget" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--examples-budget requires a numeric value")?;
+                examples_budget = value
+                    .parse::<usize>()
+                    .context("invalid value for --examples-budget")?;
+                i += 2;
+            }
+            "--samples" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--samples requires a numeric value")?;
+                samples_per_format = value
+                    .parse::<usize>()
+                    .context("invalid value for --samples")?;
+                i += 2;
+            }
+            "--price-buckets" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--price-buckets requires a list like 0.1,0.5,1,2,5")?;
+                price_buckets = parse_price_buckets(value)?;
+                i += 2;
+            }
+            "--ivt" => {
+                ivt = true;
+                i += 1;
+            }
+            "--fingerprint" => {
+                fingerprint = true;
+                i += 1;
+            }
+            "--exclude-test" => {
+                exclude_test = true;
+                i += 1;
+            }
+            "--datacenter-ips" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--datacenter-ips requires a file path")?;
+                datacenter_ips = Some(value.clone());
+                ivt = true;
+                i += 2;
+            }
+            "--allowlist" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--allowlist requires a file path")?;
+                allowlist = Some(value.clone());
+                i += 2;
+            }
+            "--denylist" => {
+                let value = rest.get(i + 1).context("--denylist requires a file path")?;
+                denylist = Some(value.clone());
+                i += 2;
+            }
+            "--sellers-json" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--sellers-json requires a sellers.json path or URL")?;
+                sellers_json.push(value.clone());
+                i += 2;
+            }
+            "--spoof" => {
+                spoof = true;
+                i += 1;
+            }
+            "--geoip" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--geoip requires a .mmdb file path")?;
+                geoip = Some(value.clone());
+                i += 2;
+            }
+            "--state" => {
+                let value = rest.get(i + 1).context("--state requires a file path")?;
+                state_path = Some(value.clone());
+                i += 2;
+            }
+            "--history-dir" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--history-dir requires a directory path")?;
+                history_dir = Some(value.clone());
+                i += 2;
+            }
+            "--history-runs" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--history-runs requires a numeric value")?;
+                history_runs = value
+                    .parse::<usize>()
+                    .context("invalid value for --history-runs")?;
+                if history_runs == 0 {
+                    bail!("--history-runs must be at least 1");
+                }
+                i += 2;
+            }
+            "--extract" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--extract requires NAME=PATH, e.g. gpid=imp[0].ext.gpid")?;
+                extract_rules.push(ExtractRule::parse(value)?);
+                i += 2;
+            }
+            "--extract-config" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--extract-config requires a file path")?;
+                extract_rules.extend(ExtractRule::load_file(value)?);
+                i += 2;
+            }
+            "--plugin" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--plugin requires a .wasm file path")?;
+                plugins.push(value.clone());
+                i += 2;
+            }
+            "--group-by" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--group-by requires a list like format,publisher,geo,hour")?;
+                group_by = GroupBy::parse(value).context("invalid --group-by")?;
+                i += 2;
+            }
+            "--detectors" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--detectors requires a list like zero_bids,low_bid_rate")?;
+                detectors = Detectors::parse(value).context("invalid --detectors")?;
+                i += 2;
+            }
+            "--redact" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--redact requires a list like publisher,ip,ifa,domain or all")?;
+                redact = Some(Redaction::parse(value).context("invalid --redact")?);
+                i += 2;
+            }
+            "--redact-salt" => {
+                let value = rest.get(i + 1).context("--redact-salt requires a secret")?;
+                redact_salt = Some(value.clone());
+                i += 2;
+            }
+            "--filter" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--filter requires an expression, e.g. 'ssp == \"fake_ssp\"'")?;
+                filters.push(value.clone());
+                i += 2;
+            }
+            "--sample" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--sample requires a rate between 0 and 1, e.g. 0.01")?;
+                let rate = value.parse::<f64>().context("invalid value for --sample")?;
+                if !(rate > 0.0 && rate <= 1.0) {
+                    bail!("--sample must be in (0, 1], got {value}");
+                }
+                sampler = Sampler::Rate(rate);
+                i += 2;
+            }
+            "--sample-every" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--sample-every requires a numeric value")?;
+                let n = value
+                    .parse::<u64>()
+                    .context("invalid value for --sample-every")?;
+                if n == 0 {
+                    bail!("--sample-every must be at least 1");
+                }
+                sampler = Sampler::Every(n);
+                i += 2;
+            }
+            "--quiet" | "-q" => {
+                quiet = true;
+                i += 1;
+            }
+            "--profile" => {
+                profile = true;
+                i += 1;
+            }
+            "--input-format" => {
+                let value = rest.get(i + 1).context(
+                    "--input-format requires jsonl, proto, csv, tsv, prebid or google-ab",
+                )?;
+                input_format = InputFormat::parse(value).with_context(|| {
+                    if value == "google-ab" {
+                        return "cat_scan was built without Authorized Buyers support; rebuild with `--features google-ab`".to_string();
+                    }
+                    format!(
+                        "unknown input format '{value}', expected jsonl, proto, csv, tsv, prebid or google-ab"
+                    )
+                })?;
+                i += 2;
+            }
+            "--column-map" => {
+                let value = rest.get(i + 1).context(
+                    "--column-map requires e.g. request=COLUMN,response=COLUMN,ts=COLUMN",
+                )?;
+                column_map = Some(ColumnMap::load(value)?);
+                i += 2;
+            }
+            "--request-field" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--request-field requires a key name, e.g. bid_request")?;
+                layout.request = Some(value.clone());
+                i += 2;
+            }
+            "--response-field" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--response-field requires a key name, e.g. bid_response")?;
+                layout.response = Some(value.clone());
+                i += 2;
+            }
+            "--perspective" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--perspective requires one of: bidder|ssp")?;
+                ssp_perspective = match value.as_str() {
+                    "bidder" => false,
+                    "ssp" => true,
+                    other => bail!("unknown --perspective '{other}', expected bidder or ssp"),
+                };
+                i += 2;
+            }
+            "--dsp-field" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--dsp-field requires a path like response.ext.dsp")?;
+                dsp_field = Some(ExtractRule::for_path(value).context("invalid --dsp-field")?);
+                i += 2;
+            }
+            "--on-error" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--on-error requires one of: skip|abort")?;
+                on_error = Some(match value.as_str() {
+                    "skip" => OnError::Skip,
+                    "abort" => OnError::Abort,
+                    other => {
+                        bail!("unknown --on-error mode '{other}', expected one of: skip|abort")
+                    }
+                });
+                i += 2;
+            }
+            "--max-errors" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--max-errors requires a numeric value")?;
+                max_errors = Some(
+                    value
+                        .parse::<u64>()
+                        .context("invalid value for --max-errors")?,
+                );
+                i += 2;
+            }
+            "--bad-lines" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--bad-lines requires a file path")?;
+                bad_lines = Some(value.clone());
+                i += 2;
+            }
+            "--export" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--export requires a target like clickhouse://host/db")?;
+                export = Some(ClickHouseTarget::parse(value)?);
+                i += 2;
+            }
+            "--notify-webhook" => {
+                let value = rest.get(i + 1).context("--notify-webhook requires a URL")?;
+                if !value.starts_with("http://") && !value.starts_with("https://") {
+                    bail!("--notify-webhook needs an http(s) URL, got '{value}'");
+                }
+                notify_webhook = Some(value.clone());
+                i += 2;
+            }
+            "--notify-format" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--notify-format requires json or slack")?;
+                notify_format = Some(NotifyFormat::parse(value).with_context(|| {
+                    format!("unknown notify format '{value}', expected json or slack")
+                })?);
+                i += 2;
+            }
+            "--alert-problems" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--alert-problems requires a numeric value")?;
+                alert_thresholds.problem_formats = Some(
+                    value
+                        .parse::<usize>()
+                        .context("invalid value for --alert-problems")?,
+                );
+                i += 2;
+            }
+            "--alerts-only" => {
+                alerts_only = true;
+                i += 1;
+            }
+            "--alert-wasted" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--alert-wasted requires a percentage, e.g. 20")?;
+                alert_thresholds.wasted_share = Some(parse_percent(value)?);
+                i += 2;
+            }
+            "--fail-on" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--fail-on requires a condition, e.g. wasted_traffic>20%")?;
+                for spec in value.split(',').filter(|s| !s.trim().is_empty()) {
+                    fail_on.push(FailCondition::parse(spec)?);
+                }
+                i += 2;
+            }
+            "--alert-rule" => {
+                let value = rest.get(i + 1).context(
+                    "--alert-rule requires a rule, e.g. 'publisher pub-1 bid_rate < 5% over >= 1000 requests'",
+                )?;
+                for spec in value.split(';').filter(|s| !s.trim().is_empty()) {
+                    alert_rules.push(AlertRule::parse(spec)?);
+                }
+                i += 2;
+            }
+            "--dataset" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--dataset requires a directory path")?;
+                dataset_dir = Some(value.clone());
+                i += 2;
+            }
+            flag @ ("--since" | "--until") => {
+                let value = rest
+                    .get(i + 1)
+                    .with_context(|| format!("{flag} requires a time like 2024-06-01 or 24h"))?;
+                let secs = parse_time_bound(value, now_ms() / 1000)?;
+                if flag == "--since" {
+                    date_range.since = Some(secs);
+                } else {
+                    date_range.until = Some(secs);
+                }
+                i += 2;
+            }
+            "--requests" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--requests requires a request log path")?;
+                join_requests = Some(value.clone());
+                i += 2;
+            }
+            "--responses" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--responses requires a response log path")?;
+                join_responses = Some(value.clone());
+                i += 2;
+            }
+            "--join-memory" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--join-memory requires a size in MB")?;
+                join_memory_mb = value
+                    .parse::<u64>()
+                    .context("invalid value for --join-memory")?;
+                i += 2;
+            }
+            other => bail!("Unknown argument: {other}"),
+        }
+    }
+
+    if let (Some(since), Some(until)) = (date_range.since, date_range.until) {
+        if since >= until {
+            bail!("--since must be before --until");
+        }
+    }
+
+    if bad_lines.is_some() && !input_format.is_line_based() {
+        bail!("--bad-lines only works with line-based input formats (jsonl, csv, tsv)");
+    }
+    if column_map.is_some() && !matches!(input_format, InputFormat::Csv | InputFormat::Tsv) {
+        bail!("--column-map needs --input-format csv or tsv");
+    }
+    if layout != RecordLayout::default() && input_format != InputFormat::Jsonl {
+        bail!("--request-field/--response-field read JSON lines; map CSV/TSV columns with --column-map");
+    }
+    if ts_field.is_some() && input_format != InputFormat::Jsonl {
+        bail!("--ts-field reads JSON lines; map a CSV/TSV timestamp column with --column-map ts=COLUMN");
+    }
+    let dsp_field = match (ssp_perspective, dsp_field) {
+        (true, dsp_field) => Some(match dsp_field {
+            Some(dsp_field) => dsp_field,
+            None => ExtractRule::for_path("dsp")?,
+        }),
+        (false, Some(_)) => bail!("--dsp-field needs --perspective ssp"),
+        (false, None) => None,
+    };
+    if dsp_field.is_some() && input_format != InputFormat::Jsonl {
+        bail!("--perspective ssp reads the DSP from JSON lines (--input-format jsonl)");
+    }
+
+    let join = match (join_requests, join_responses) {
+        (Some(requests), Some(responses)) => {
+            if !input_path.is_empty() {
+                bail!("Give either an input path or --requests/--responses, not both");
+            }
+            if input_format != InputFormat::Jsonl
+                || date_range.is_set()
+                || layout != RecordLayout::default()
+            {
+                bail!("--requests/--responses read JSON lines; --input-format, --since/--until and --request-field/--response-field don't apply");
+            }
+            Some(JoinInputs {
+                requests,
+                responses,
+                memory_cap_bytes: join_memory_mb << 20,
+            })
+        }
+        (None, None) => None,
+        _ => bail!("--requests and --responses must be given together"),
+    };
+    if join.is_some() && !plugins.is_empty() {
+        bail!("--plugin doesn't apply to --requests/--responses");
+    }
+    if join.is_some() && geoip.is_some() {
+        bail!("--geoip doesn't apply to --requests/--responses");
+    }
+    if join.is_some() && dsp_field.is_some() {
+        bail!("--perspective ssp reads one log with both sides; it doesn't apply to --requests/--responses");
+    }
+    let input_path = match &join {
+        Some(join) => format!("{} + {}", join.requests, join.responses),
+        None if input_path.is_empty() => bail!(USAGE),
+        None => input_path,
+    };
+
+    let redact = match (redact, redact_salt) {
+        (Some(redact), salt) => Some(Redaction {
+            salt: salt.unwrap_or_default(),
+            ..redact
+        }),
+        (None, Some(_)) => bail!("--redact-salt needs --redact publisher"),
+        (None, None) => None,
+    };
+    if redact.as_ref().is_some_and(|r| r.publisher) && !sellers_json.is_empty() {
+        bail!("--redact publisher hashes the publisher ids --sellers-json checks; leave publisher out of --redact");
+    }
+
+    if alerts_only && alert_thresholds == AlertThresholds::default() {
+        bail!("--alerts-only needs --alert-problems or --alert-wasted");
+    }
+    let notify = match notify_webhook {
+        Some(url) => Some(NotifyConfig {
+            format: notify_format.unwrap_or_else(|| NotifyFormat::for_url(&url)),
+            url,
+            thresholds: alert_thresholds,
+            alerts_only,
+        }),
+        None if notify_format.is_some()
+            || alert_thresholds != AlertThresholds::default()
+            || alerts_only =>
+        {
+            bail!("--notify-format, --alert-* and --alerts-only need --notify-webhook URL")
+        }
+        None => None,
+    };
+
+    if bundle && out_dir.is_none() {
+        bail!("--bundle writes report.html next to report.json and requires --out DIR");
+    }
+
+    if segment_stats && !group_by.has(Group::Segment) {
+        bail!("--segment-stats needs segment in --group-by");
+    }
+    if time_analysis && !group_by.has(Group::Hour) {
+        bail!("--time-analysis needs hour in --group-by");
+    }
+    for rule in &alert_rules {
+        rule.check_groups(group_by)?;
+    }
+
+    // Repeated filters must all match
+    let filter = match filters.as_slice() {
+        [] => None,
+        [filter] => Some(filter.clone()),
+        filters => Some(format!("({})", filters.join(") && ("))),
+    };
+    let filter = filter
+        .map(|text| {
+            RecordFilter::parse(&text).with_context(|| format!("invalid --filter '{text}'"))
+        })
+        .transpose()?;
+
+    // An error budget or bad-lines dump only makes sense when skipping
+    let on_error = on_error.unwrap_or(if max_errors.is_some() || bad_lines.is_some() {
+        OnError::Skip
+    } else {
+        OnError::Abort
+    });
+
+    Ok(Config {
+        input_path,
+        min_requests,
+        sort_by,
+        output_format,
+        html_out,
+        out_dir,
+        report_template,
+        bundle,
+        max_rows_per_table,
+        time_analysis,
+        time_bucket_ms,
+        ts_field,
+        segment_stats,
+        examples_per_key,
+        examples_budget,
+        samples_per_format,
+        price_buckets,
+        ivt,
+        datacenter_ips,
+        allowlist,
+        denylist,
+        sellers_json,
+        spoof,
+        geoip,
+        fingerprint,
+        exclude_test,
+        state_path,
+        history_dir,
+        history_runs,
+        extract_rules,
+        plugins,
+        group_by,
+        detectors,
+        redact,
+        filter,
+        sampler,
+        quiet,
+        profile,
+        on_error,
+        max_errors,
+        bad_lines,
+        input_format,
+        column_map: column_map.unwrap_or_default(),
+        layout,
+        dsp_field,
+        join,
+        export,
+        notify,
+        fail_on,
+        alert_rules,
+        flags: rest.to_vec(),
+        dataset_dir,
+        date_range,
+    })
+}
+
+fn parse_live_args(rest: &[String]) -> Result<LiveConfig> {
+    let mut input_path: Option<String> = None;
+    let mut window_secs = 15 * 60;
+    let mut port: u16 = 8080;
+    let mut refresh_secs = 2;
+    let mut from_start = false;
+
+    let mut i = 0;
+    while i < rest.len() {
+        match rest[i].as_str() {
+            "--window" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--window requires a duration, e.g. 15m")?;
+                window_secs = parse_duration_secs(value)?;
+                if window_secs < 60 {
+                    bail!("--window must be at least 1m");
+                }
+                i += 2;
+            }
+            "--port" => {
+                let value = rest.get(i + 1).context("--port requires a port number")?;
+                port = value.parse::<u16>().context("invalid value for --port")?;
+                i += 2;
+            }
+            "--refresh" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--refresh requires a duration, e.g. 2s")?;
+                refresh_secs = parse_duration_secs(value)?.max(1);
+                i += 2;
+            }
+            "--from-start" => {
+                from_start = true;
+                i += 1;
+            }
+            other if !other.starts_with("--") && input_path.is_none() => {
+                input_path = Some(other.to_string());
+                i += 1;
+            }
+            other => bail!("Unknown argument: {other}"),
+        }
+    }
+
+    Ok(LiveConfig {
+        input_path: input_path.context("live requires a log file path")?,
+        window_secs,
+        port,
+        refresh_secs,
+        from_start,
+    })
+}
+
+fn parse_tail_args(rest: &[String]) -> Result<TailConfig> {
+    let mut input_path: Option<String> = None;
+    let mut windows_minutes = vec![5, 15, 60];
+    let mut interval_secs = 5;
+    let mut from_start = false;
+    let mut top = 10;
+
+    let mut i = 0;
+    while i < rest.len() {
+        match rest[i].as_str() {
+            "--windows" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--windows requires a list of durations, e.g. 5m,15m,60m")?;
+                windows_minutes = value
+                    .split(',')
+                    .map(|w| parse_duration_secs(w).map(|secs| secs.div_ceil(60).max(1)))
+                    .collect::<Result<Vec<_>>>()?;
+                windows_minutes.sort_unstable();
+                windows_minutes.dedup();
+                i += 2;
+            }
+            "--interval" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--interval requires a duration, e.g. 5s")?;
+                interval_secs = parse_duration_secs(value)?.max(1);
+                i += 2;
+            }
+            "--top" => {
+                let value = rest.get(i + 1).context("--top requires a numeric value")?;
+                top = value.parse::<usize>().context("invalid value for --top")?;
+                i += 2;
+            }
+            "--from-start" => {
+                from_start = true;
+                i += 1;
+            }
+            other if !other.starts_with("--") && input_path.is_none() => {
+                input_path = Some(other.to_string());
+                i += 1;
+            }
+            other => bail!("Unknown argument: {other}"),
+        }
+    }
+
+    Ok(TailConfig {
+        input_path: input_path.context("tail requires a log file path")?,
+        windows_minutes,
+        interval_secs,
+        from_start,
+        top,
+    })
+}
+
+/// `watch` options, then the prefix (or `default_input`) and scan options,
+/// which are checked here and applied after `scan_flags` on every run
+fn parse_watch_args(
+    rest: &[String],
+    scan_flags: Vec<String>,
+    default_input: Option<String>,
+) -> Result<WatchConfig> {
+    let mut every_secs = DEFAULT_WATCH_EVERY_SECS;
+    let mut cursor_path: Option<String> = None;
+    let mut scan_rest = Vec::new();
+
+    let mut i = 0;
+    while i < rest.len() {
+        match rest[i].as_str() {
+            "--every" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--every requires a duration, e.g. 15m")?;
+                every_secs = parse_duration_secs(value)?.max(1);
+                i += 2;
+            }
+            "--cursor" => {
+                let value = rest.get(i + 1).context("--cursor requires a file path")?;
+                cursor_path = Some(value.clone());
+                i += 2;
+            }
+            other => {
+                scan_rest.push(other.to_string());
+                i += 1;
+            }
+        }
+    }
+
+    let (mut input_path, scan_rest) = match scan_rest.split_first() {
+        Some((first, rest)) if !first.starts_with('-') => (first.clone(), rest),
+        _ => (default_input.unwrap_or_default(), &scan_rest[..]),
+    };
+    if parse_object_uri(&input_path).is_none() {
+        bail!("watch requires an object store prefix, e.g. cat_scan watch s3://bucket/logs/ --out DIR");
+    }
+    // The input is always a prefix; s3://bucket/logs means s3://bucket/logs/
+    if !input_path.ends_with('/') {
+        input_path.push('/');
+    }
+
+    let scan_args = [scan_flags, scan_rest.to_vec()].concat();
+    let scan = parse_scan_args(input_path.clone(), &scan_args)?;
+    if scan.join.is_some() {
+        bail!("watch scans one prefix; --requests/--responses are not supported");
+    }
+    if !scan.fail_on.is_empty() {
+        bail!("--fail-on would stop the watch; use --notify-webhook with --alert-* instead");
+    }
+    let out_dir = scan
+        .out_dir
+        .context("watch rewrites the report on every run and requires --out DIR")?;
+    let cursor_path = cursor_path.unwrap_or_else(|| {
+        std::path::Path::new(&out_dir)
+            .join(DEFAULT_CURSOR_FILE)
+            .display()
+            .to_string()
+    });
+
+    Ok(WatchConfig {
+        input_path,
+        every_secs,
+        cursor_path,
+        scan_args,
+    })
+}
+
+fn parse_serve_args(rest: &[String]) -> Result<ServeConfig> {
+    let mut out_dir: Option<String> = None;
+    let mut port: u16 = 8080;
+
+    let mut i = 0;
+    while i < rest.len() {
+        match rest[i].as_str() {
+            "--out" => {
+                let value = rest.get(i + 1).context("--out requires a directory path")?;
+                out_dir = Some(value.clone());
+                i += 2;
+            }
+            "--port" => {
+                let value = rest.get(i + 1).context("--port requires a port number")?;
+                port = value.parse::<u16>().context("invalid value for --port")?;
+                i += 2;
+            }
+            other if !other.starts_with("--") && out_dir.is_none() => {
+                out_dir = Some(other.to_string());
+                i += 1;
+            }
+            other => bail!("Unknown argument: {other}"),
+        }
+    }
+
+    Ok(ServeConfig {
+        out_dir: out_dir.context("serve requires --out DIR")?,
+        port,
+    })
+}
+
+fn parse_validate_args(rest: &[String]) -> Result<ValidateConfig> {
+    let mut input_path: Option<String> = None;
+    let mut version = OrtbVersion::V2_6;
+    let mut top = 20;
+    let mut out_dir: Option<String> = None;
+    let mut quiet = false;
+
+    let mut i = 0;
+    while i < rest.len() {
+        match rest[i].as_str() {
+            "--ortb" => {
+                let value = rest.get(i + 1).context("--ortb requires 2.5 or 2.6")?;
+                version = OrtbVersion::parse(value).with_context(|| {
+                    format!("unsupported OpenRTB version '{value}', expected 2.5 or 2.6")
+                })?;
+                i += 2;
+            }
+            "--top" => {
+                let value = rest.get(i + 1).context("--top requires a numeric value")?;
+                top = value.parse::<usize>().context("invalid value for --top")?;
+                i += 2;
+            }
+            "--out" => {
+                let value = rest.get(i + 1).context("--out requires a directory path")?;
+                out_dir = Some(value.clone());
+                i += 2;
+            }
+            "--quiet" | "-q" => {
+                quiet = true;
+                i += 1;
+            }
+            other if !other.starts_with("--") && input_path.is_none() => {
+                input_path = Some(other.to_string());
+                i += 1;
+            }
+            other => bail!("Unknown argument: {other}"),
+        }
+    }
+
+    Ok(ValidateConfig {
+        input_path: input_path.context("validate requires a log file path")?,
+        version,
+        top,
+        out_dir,
+        quiet,
+    })
+}
+
+fn parse_diff_args(rest: &[String]) -> Result<DiffConfig> {
+    let mut paths: Vec<String> = Vec::new();
+    let mut confidence = 0.95;
+
+    let mut i = 0;
+    while i < rest.len() {
+        match rest[i].as_str() {
+            "--confidence" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--confidence requires a percentage, e.g. 95%")?;
+                confidence = parse_percent(value)?;
+                if confidence == 0.0 || confidence == 1.0 {
+                    bail!("--confidence must be between 0 and 100%, exclusive");
+                }
+                i += 2;
+            }
+            other if !other.starts_with("--") && paths.len() < 2 => {
+                paths.push(other.to_string());
+                i += 1;
+            }
+            other => bail!("Unknown argument: {other}"),
+        }
+    }
+
+    let mut paths = paths.into_iter();
+    let (Some(old_path), Some(new_path)) = (paths.next(), paths.next()) else {
+        bail!("diff requires two scans: cat_scan diff <old> <new>");
+    };
+    Ok(DiffConfig {
+        old_path,
+        new_path,
+        confidence,
+    })
+}
+
+fn parse_query_args(rest: &[String]) -> Result<QueryConfig> {
+    let mut positional: Vec<String> = Vec::new();
+    let mut metric = Metric::Requests;
+    let mut top: usize = 20;
+
+    let mut i = 0;
+    while i < rest.len() {
+        match rest[i].as_str() {
+            "--metric" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--metric requires one of: requests|bids|bid_rate|avg_bid_price")?;
+                metric = Metric::parse(value)?;
+                i += 2;
+            }
+            "--top" => {
+                let value = rest.get(i + 1).context("--top requires a numeric value")?;
+                top = value.parse::<usize>().context("invalid value for --top")?;
+                i += 2;
+            }
+            other if !other.starts_with("--") && positional.len() < 2 => {
+                positional.push(other.to_string());
+                i += 1;
+            }
+            other => bail!("Unknown argument: {other}"),
+        }
+    }
+
+    let mut positional = positional.into_iter();
+    let (Some(out_dir), Some(query)) = (positional.next(), positional.next()) else {
+        bail!("query requires a scan and a query: cat_scan query <out dir> \"format by ssp\"");
+    };
+    Ok(QueryConfig {
+        out_dir,
+        query: PivotQuery::parse(&query)?,
+        metric,
+        top,
+    })
+}
+
+fn parse_tui_args(rest: &[String]) -> Result<TuiConfig> {
+    match rest {
+        [report_path] if !report_path.starts_with("--") => Ok(TuiConfig {
+            report_path: report_path.clone(),
+        }),
+        [] => bail!("tui requires a scan: cat_scan tui <out dir|report.json>"),
+        [_, other, ..] | [other] => bail!("Unknown argument: {other}"),
+    }
+}
+
+fn parse_generate_args(rest: &[String]) -> Result<GenerateConfig> {
+    let mut config = GenerateConfig {
+        lines: 10_000,
+        out_path: "sample.jsonl".to_string(),
+        seed: 1,
+        traffic: None,
+    };
+
+    let mut i = 0;
+    while i < rest.len() {
+        match rest[i].as_str() {
+            "--lines" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--lines requires a numeric value")?;
+                config.lines = value.parse::<u64>().context("invalid value for --lines")?;
+                i += 2;
+            }
+            "--out" => {
+                let value = rest.get(i + 1).context("--out requires a file path")?;
+                config.out_path = value.clone();
+                i += 2;
+            }
+            "--seed" => {
+                let value = rest.get(i + 1).context("--seed requires a numeric value")?;
+                config.seed = value.parse::<u64>().context("invalid value for --seed")?;
+                i += 2;
+            }
+            "--traffic" => {
+                let value = rest
+                    .get(i + 1)
+                    .context("--traffic requires a traffic profile path")?;
+                config.traffic = Some(value.clone());
+                i += 2;
+            }
+            other => bail!("Unknown argument: {other}"),
+        }
+    }
+    Ok(config)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_percent() {
+        assert_eq!(parse_percent("20").unwrap(), 0.2);
+        assert_eq!(parse_percent("12.5%").unwrap(), 0.125);
+        assert!(parse_percent("120%").is_err());
+        assert!(parse_percent("lots").is_err());
+    }
+
+    #[test]
+    fn test_parse_duration_secs() {
+        assert_eq!(parse_duration_secs("90").unwrap(), 90);
+        assert_eq!(parse_duration_secs("30s").unwrap(), 30);
+        assert_eq!(parse_duration_secs("15m").unwrap(), 900);
+        assert_eq!(parse_duration_secs("24h").unwrap(), 86400);
+        assert_eq!(parse_duration_secs("2d").unwrap(), 172800);
+        assert!(parse_duration_secs("m").is_err());
+        assert!(parse_duration_secs("5w").is_err());
+        assert!(parse_duration_secs("999999999999999999d").is_err());
+    }
+
+    #[test]
+    fn test_parse_diff_args() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let config = parse_diff_args(&args(&["a", "b", "--confidence", "99%"])).unwrap();
+        assert_eq!(
+            (config.old_path.as_str(), config.new_path.as_str()),
+            ("a", "b")
+        );
+        assert_eq!(config.confidence, 0.99);
+        assert_eq!(
+            parse_diff_args(&args(&["a", "b"])).unwrap().confidence,
+            0.95
+        );
+        assert!(parse_diff_args(&args(&["a"])).is_err());
+        assert!(parse_diff_args(&args(&["a", "b", "c"])).is_err());
+        assert!(parse_diff_args(&args(&["a", "b", "--confidence", "100"])).is_err());
+    }
+
+    #[test]
+    fn test_parse_since_until() {
+        let args = |list: &[&str]| list.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        let config = parse_scan_args(
+            "s3://bucket/logs/".into(),
+            &args(&["--since", "2024-06-01", "--until", "2024-06-02T00:00:00Z"]),
+        )
+        .unwrap();
+        assert_eq!(
+            config.date_range,
+            DateRange {
+                since: Some(1_717_200_000),
+                until: Some(1_717_286_400),
+            }
+        );
+        let reversed = args(&["--since", "2024-06-02", "--until", "2024-06-01"]);
+        assert!(parse_scan_args("s3://bucket/logs/".into(), &reversed).is_err());
+    }
+}
@@ -0,0 +1,107 @@
+use serde_json::Value;
+
+/// Consent buckets reported per SSP, in display order. The first four are
+/// mutually exclusive (every request lands in exactly one); the rest are
+/// independent flags counted on top.
+pub const CONSENT_BUCKETS: &[&str] = &[
+    "gdpr_consent",
+    "gdpr_no_consent",
+    "non_gdpr",
+    "gdpr_unknown",
+    "coppa",
+    "us_privacy",
+    "us_privacy_opt_out",
+];
+
+/// Privacy signals carried by one bid request. Reads both the OpenRTB 2.5
+/// `ext` locations and the 2.6 top-level fields.
+#[derive(Debug, Default, PartialEq)]
+pub struct PrivacySignals {
+    /// regs.gdpr: Some(true) when GDPR applies, None when not signalled
+    pub gdpr: Option<bool>,
+    /// A non-empty TCF consent string in user.consent
+    pub has_consent: bool,
+    pub coppa: bool,
+    /// The 4-character CCPA string, e.g. "1YNN"
+    pub us_privacy: Option<String>,
+}
+
+impl PrivacySignals {
+    pub fn from_request(request: &Value) -> Self {
+        let regs = &request["regs"];
+        let user = &request["user"];
+        let flag = |v: &Value| match v {
+            Value::Number(n) => n.as_u64().map(|n| n == 1),
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        };
+        let text = |v: &Value| v.as_str().filter(|s| !s.is_empty()).map(str::to_string);
+
+        Self {
+            gdpr: flag(&regs["gdpr"]).or_else(|| flag(&regs["ext"]["gdpr"])),
+            has_consent: text(&user["consent"])
+                .or_else(|| text(&user["ext"]["consent"]))
+                .is_some(),
+            coppa: flag(&regs["coppa"]).unwrap_or(false),
+            us_privacy: text(&regs["us_privacy"]).or_else(|| text(&regs["ext"]["us_privacy"])),
+        }
+    }
+
+    /// Whether any privacy signal was present at all
+    pub fn is_signalled(&self) -> bool {
+        self.gdpr.is_some() || self.has_consent || self.coppa || self.us_privacy.is_some()
+    }
+
+    /// The buckets this request counts towards (see CONSENT_BUCKETS)
+    pub fn buckets(&self) -> Vec<&'static str> {
+        let mut buckets = vec![match (self.gdpr, self.has_consent) {
+            (Some(true), true) => "gdpr_consent",
+            (Some(true), false) => "gdpr_no_consent",
+            (Some(false), _) => "non_gdpr",
+            (None, _) => "gdpr_unknown",
+        }];
+        if self.coppa {
+            buckets.push("coppa");
+        }
+        if let Some(usp) = &self.us_privacy {
+            buckets.push("us_privacy");
+            // Third character is "opt-out of sale"
+            if usp.as_bytes().get(2) == Some(&b'Y') {
+                buckets.push("us_privacy_opt_out");
+            }
+        }
+        buckets
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signals_from_ext_and_top_level() {
+        let legacy = serde_json::json!({
+            "regs": { "ext": { "gdpr": 1, "us_privacy": "1YYN" } },
+            "user": { "ext": { "consent": "CPXxRfAPXxRfAAfKABENB" } }
+        });
+        let signals = PrivacySignals::from_request(&legacy);
+        assert_eq!(signals.gdpr, Some(true));
+        assert!(signals.has_consent);
+        assert_eq!(
+            signals.buckets(),
+            vec!["gdpr_consent", "us_privacy", "us_privacy_opt_out"]
+        );
+
+        let v26 = serde_json::json!({
+            "regs": { "gdpr": 1, "coppa": 1 },
+            "user": { "consent": "" }
+        });
+        let signals = PrivacySignals::from_request(&v26);
+        assert_eq!(signals.buckets(), vec!["gdpr_no_consent", "coppa"]);
+
+        let bare = serde_json::json!({ "imp": [] });
+        let signals = PrivacySignals::from_request(&bare);
+        assert!(!signals.is_signalled());
+        assert_eq!(signals.buckets(), vec!["gdpr_unknown"]);
+    }
+}
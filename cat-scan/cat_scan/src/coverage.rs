@@ -0,0 +1,105 @@
+use serde_json::Value;
+
+/// A field counts as present unless it is missing, null, "" or []
+fn present(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::String(s) => !s.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        _ => true,
+    }
+}
+
+/// `site` or `app`, whichever the request has
+fn distribution(request: &Value) -> &Value {
+    match request.get("site") {
+        Some(site) => site,
+        None => &request["app"],
+    }
+}
+
+fn any_imp(request: &Value, field: &str) -> bool {
+    request["imp"]
+        .as_array()
+        .is_some_and(|imps| imps.iter().any(|imp| present(&imp[field])))
+}
+
+/// (field name, whether a request carries it)
+type FieldCheck = (&'static str, fn(&Value) -> bool);
+
+/// Notable OpenRTB fields in the coverage report, in display order, with
+/// the check for whether a request carries them. Fields that moved out of
+/// `ext` in 2.6 are also accepted at their 2.5 location.
+pub const COVERAGE_FIELDS: &[FieldCheck] = &[
+    ("device.ifa", |r| present(&r["device"]["ifa"])),
+    ("device.ip", |r| {
+        present(&r["device"]["ip"]) || present(&r["device"]["ipv6"])
+    }),
+    ("device.ua", |r| present(&r["device"]["ua"])),
+    ("device.sua", |r| present(&r["device"]["sua"])),
+    ("device.geo", |r| present(&r["device"]["geo"])),
+    ("user.id", |r| present(&r["user"]["id"])),
+    ("user.buyeruid", |r| present(&r["user"]["buyeruid"])),
+    ("user.eids", |r| {
+        present(&r["user"]["eids"]) || present(&r["user"]["ext"]["eids"])
+    }),
+    ("user.consent", |r| {
+        present(&r["user"]["consent"]) || present(&r["user"]["ext"]["consent"])
+    }),
+    ("site.page", |r| present(&r["site"]["page"])),
+    ("app.bundle", |r| present(&r["app"]["bundle"])),
+    ("site/app.cat", |r| present(&distribution(r)["cat"])),
+    ("site/app.content.cat", |r| {
+        present(&distribution(r)["content"]["cat"])
+    }),
+    ("site/app.publisher.id", |r| {
+        present(&distribution(r)["publisher"]["id"])
+    }),
+    ("imp.bidfloor", |r| any_imp(r, "bidfloor")),
+    ("imp.pmp", |r| any_imp(r, "pmp")),
+    ("imp.instl", |r| any_imp(r, "instl")),
+    ("imp.rwdd", |r| any_imp(r, "rwdd")),
+    ("source.schain", |r| {
+        present(&r["source"]["schain"]) || present(&r["source"]["ext"]["schain"])
+    }),
+    ("regs.gdpr", |r| {
+        present(&r["regs"]["gdpr"]) || present(&r["regs"]["ext"]["gdpr"])
+    }),
+    ("dooh", |r| present(&r["dooh"])),
+];
+
+/// Names of the COVERAGE_FIELDS present on one request
+pub fn present_fields(request: &Value) -> impl Iterator<Item = &'static str> + '_ {
+    COVERAGE_FIELDS
+        .iter()
+        .filter(move |(_, check)| check(request))
+        .map(|(field, _)| *field)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_present_fields() {
+        let request = serde_json::json!({
+            "imp": [{ "banner": {} }, { "rwdd": 0, "pmp": { "deals": [] } }],
+            "app": { "bundle": "com.example", "content": { "cat": ["IAB1"] } },
+            "device": { "ifa": "", "ua": "Mozilla", "ipv6": "::1" },
+            "user": { "ext": { "eids": [{ "source": "id5-sync.com" }] } },
+            "regs": { "gdpr": null }
+        });
+        assert_eq!(
+            present_fields(&request).collect::<Vec<_>>(),
+            vec![
+                "device.ip",
+                "device.ua",
+                "user.eids",
+                "app.bundle",
+                "site/app.content.cat",
+                "imp.pmp",
+                "imp.rwdd",
+            ]
+        );
+    }
+}
@@ -0,0 +1,363 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    io::Write,
+};
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::bids::NO_SEAT;
+use crate::metadata::{create_csv, ScanMetadata};
+use crate::stats::scale_count;
+
+/// Stop tracking new distinct domains/creative ids per key past this many
+/// (memory guard); counts for ids already seen keep growing
+const MAX_DISTINCT_PER_KEY: usize = 1000;
+
+/// Domains and creative ids listed per key in the report
+const TOP_CREATIVES: usize = 10;
+
+/// A key is flagged when at least this share of its bids lack adomain/crid
+const MISSING_CREATIVE_THRESHOLD: f64 = 0.1;
+
+/// The creative fields of one bid
+#[derive(Debug, PartialEq)]
+pub struct CreativeBid {
+    pub seat: String,
+    pub adomain: Vec<String>,
+    pub crid: Option<String>,
+    pub attr: Vec<u64>,
+}
+
+/// Creative fields of every bid in a response
+pub fn creative_bids(response: &Value) -> Vec<CreativeBid> {
+    let Some(seatbids) = response["seatbid"].as_array() else {
+        return Vec::new();
+    };
+    let mut bids = Vec::new();
+    for seatbid in seatbids {
+        let seat = seatbid["seat"].as_str().unwrap_or(NO_SEAT);
+        for bid in seatbid["bid"].as_array().into_iter().flatten() {
+            let strings = |field: &str| -> Vec<String> {
+                bid[field]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|v| v.as_str())
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_string)
+                    .collect()
+            };
+            bids.push(CreativeBid {
+                seat: seat.to_string(),
+                adomain: strings("adomain"),
+                crid: bid["crid"]
+                    .as_str()
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_string),
+                attr: bid["attr"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|v| v.as_u64())
+                    .collect(),
+            });
+        }
+    }
+    bids
+}
+
+fn count_capped(map: &mut HashMap<String, u64>, key: &str, count: u64) {
+    if let Some(existing) = map.get_mut(key) {
+        *existing += count;
+    } else if map.len() < MAX_DISTINCT_PER_KEY {
+        map.insert(key.to_string(), count);
+    }
+}
+
+/// Creative fields seen on the bids for one key
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct CreativeCounts {
+    pub bids: u64,
+    pub missing_adomain: u64,
+    pub missing_crid: u64,
+    pub adomains: HashMap<String, u64>,
+    pub crids: HashMap<String, u64>,
+    /// Bids per IAB creative attribute (bid.attr)
+    pub attrs: BTreeMap<u64, u64>,
+}
+
+impl CreativeCounts {
+    fn observe(&mut self, bid: &CreativeBid) {
+        self.bids += 1;
+        if bid.adomain.is_empty() {
+            self.missing_adomain += 1;
+        }
+        for domain in &bid.adomain {
+            count_capped(&mut self.adomains, domain, 1);
+        }
+        match &bid.crid {
+            Some(crid) => count_capped(&mut self.crids, crid, 1),
+            None => self.missing_crid += 1,
+        }
+        for attr in &bid.attr {
+            *self.attrs.entry(*attr).or_default() += 1;
+        }
+    }
+
+    fn merge(&mut self, other: &CreativeCounts) {
+        self.bids += other.bids;
+        self.missing_adomain += other.missing_adomain;
+        self.missing_crid += other.missing_crid;
+        for (domain, count) in &other.adomains {
+            count_capped(&mut self.adomains, domain, *count);
+        }
+        for (crid, count) in &other.crids {
+            count_capped(&mut self.crids, crid, *count);
+        }
+        for (attr, count) in &other.attrs {
+            *self.attrs.entry(*attr).or_default() += count;
+        }
+    }
+
+    fn scale(&mut self, factor: f64) {
+        scale_count(&mut self.bids, factor);
+        scale_count(&mut self.missing_adomain, factor);
+        scale_count(&mut self.missing_crid, factor);
+        let counts = self
+            .adomains
+            .values_mut()
+            .chain(self.crids.values_mut())
+            .chain(self.attrs.values_mut());
+        for count in counts {
+            scale_count(count, factor);
+        }
+    }
+
+    pub fn missing_adomain_rate(&self) -> f64 {
+        if self.bids == 0 {
+            0.0
+        } else {
+            self.missing_adomain as f64 / self.bids as f64
+        }
+    }
+
+    pub fn missing_crid_rate(&self) -> f64 {
+        if self.bids == 0 {
+            0.0
+        } else {
+            self.missing_crid as f64 / self.bids as f64
+        }
+    }
+}
+
+/// Advertiser domains, creative ids and attributes per dimension
+/// ("format", "publisher", "seat") and key
+#[derive(Debug, Default, Clone)]
+pub struct CreativeStats {
+    pub by_key: BTreeMap<(&'static str, String), CreativeCounts>,
+}
+
+impl CreativeStats {
+    /// Count a response's bids under one format or publisher key
+    pub fn observe(&mut self, dimension: &'static str, key: &str, bids: &[CreativeBid]) {
+        if bids.is_empty() {
+            return;
+        }
+        let counts = self.by_key.entry((dimension, key.to_string())).or_default();
+        for bid in bids {
+            counts.observe(bid);
+        }
+    }
+
+    /// Count each bid under its own seat
+    pub fn observe_seats(&mut self, bids: &[CreativeBid]) {
+        for bid in bids {
+            self.by_key
+                .entry(("seat", bid.seat.clone()))
+                .or_default()
+                .observe(bid);
+        }
+    }
+
+    pub fn merge(&mut self, other: &CreativeStats) {
+        for (key, counts) in &other.by_key {
+            self.by_key.entry(key.clone()).or_default().merge(counts);
+        }
+    }
+
+    /// Multiply counts by `factor` (extrapolating from a sample)
+    pub fn scale(&mut self, factor: f64) {
+        for counts in self.by_key.values_mut() {
+            counts.scale(factor);
+        }
+    }
+
+    /// One row per dimension and key, busiest first within a dimension
+    pub fn summarize(&self) -> Vec<CreativeSummary> {
+        let top = |map: &HashMap<String, u64>| {
+            let mut top: Vec<(String, u64)> =
+                map.iter().map(|(k, &count)| (k.clone(), count)).collect();
+            top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            top.truncate(TOP_CREATIVES);
+            top
+        };
+        let mut rows: Vec<CreativeSummary> = self
+            .by_key
+            .iter()
+            .map(|((dimension, key), c)| CreativeSummary {
+                dimension,
+                key: key.clone(),
+                bids: c.bids,
+                missing_adomain_rate: c.missing_adomain_rate(),
+                missing_crid_rate: c.missing_crid_rate(),
+                problems: [
+                    ("missing_adomain", c.missing_adomain_rate()),
+                    ("missing_crid", c.missing_crid_rate()),
+                ]
+                .into_iter()
+                .filter(|(_, rate)| *rate >= MISSING_CREATIVE_THRESHOLD)
+                .map(|(problem, _)| problem)
+                .collect(),
+                top_adomains: top(&c.adomains),
+                top_crids: top(&c.crids),
+                attrs: c
+                    .attrs
+                    .iter()
+                    .map(|(&attr, &count)| (attr, count))
+                    .collect(),
+            })
+            .collect();
+        rows.sort_by(|a, b| {
+            (a.dimension, Reverse(a.bids), &a.key).cmp(&(b.dimension, Reverse(b.bids), &b.key))
+        });
+        rows
+    }
+}
+
+/// Creative fields for one format, publisher or seat
+#[derive(serde::Serialize)]
+pub struct CreativeSummary {
+    pub dimension: &'static str,
+    pub key: String,
+    pub bids: u64,
+    pub missing_adomain_rate: f64,
+    pub missing_crid_rate: f64,
+    /// Quality problems: "missing_adomain" / "missing_crid" when at least
+    /// 10% of bids lack the field
+    pub problems: Vec<&'static str>,
+    /// (domain, bids), most frequent first
+    pub top_adomains: Vec<(String, u64)>,
+    pub top_crids: Vec<(String, u64)>,
+    /// (IAB creative attribute, bids)
+    pub attrs: Vec<(u64, u64)>,
+}
+
+/// Write creatives.csv (top domains, creative ids and attributes per key)
+/// and creative_quality.csv (bids missing adomain/crid per key)
+pub fn write_creative_csvs(
+    out_dir: &str,
+    rows: &[CreativeSummary],
+    meta: &ScanMetadata,
+) -> Result<()> {
+    let path = format!("{}/creatives.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    writeln!(csv, "dimension,key,field,value,bids,share")?;
+    for row in rows {
+        let share = |count: u64| {
+            if row.bids == 0 {
+                0.0
+            } else {
+                count as f64 / row.bids as f64
+            }
+        };
+        let values = row
+            .top_adomains
+            .iter()
+            .map(|(domain, count)| ("adomain", domain.clone(), *count))
+            .chain(
+                row.top_crids
+                    .iter()
+                    .map(|(crid, count)| ("crid", crid.clone(), *count)),
+            )
+            .chain(
+                row.attrs
+                    .iter()
+                    .map(|(attr, count)| ("attr", attr.to_string(), *count)),
+            );
+        for (field, value, count) in values {
+            writeln!(
+                csv,
+                "{},{},{},{},{},{:.4}",
+                row.dimension,
+                row.key,
+                field,
+                value,
+                count,
+                share(count)
+            )?;
+        }
+    }
+    eprintln!("Creatives written to: {}", path);
+
+    let path = format!("{}/creative_quality.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    writeln!(
+        csv,
+        "dimension,key,bids,missing_adomain_rate,missing_crid_rate"
+    )?;
+    for row in rows {
+        writeln!(
+            csv,
+            "{},{},{},{:.4},{:.4}",
+            row.dimension, row.key, row.bids, row.missing_adomain_rate, row.missing_crid_rate
+        )?;
+    }
+    eprintln!("Creative quality written to: {}", path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_creative_counts() {
+        let response = json!({ "seatbid": [
+            { "seat": "dsp_a", "bid": [
+                { "adomain": ["nike.com"], "crid": "c1", "attr": [1, 3] },
+                { "adomain": ["nike.com", "adidas.com"], "crid": "" }
+            ]},
+            { "bid": [{ "adomain": [], "crid": "c1" }] }
+        ]});
+        let bids = creative_bids(&response);
+        assert_eq!(bids.len(), 3);
+        assert_eq!(bids[1].crid, None);
+        assert_eq!(bids[2].seat, NO_SEAT);
+
+        let mut stats = CreativeStats::default();
+        stats.observe("format", "300x250", &bids);
+        stats.observe_seats(&bids);
+        let rows = stats.summarize();
+        let format = rows.iter().find(|r| r.dimension == "format").unwrap();
+        assert_eq!(format.bids, 3);
+        assert!((format.missing_adomain_rate - 1.0 / 3.0).abs() < 1e-9);
+        assert!((format.missing_crid_rate - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(
+            format.top_adomains,
+            vec![("nike.com".to_string(), 2), ("adidas.com".to_string(), 1)]
+        );
+        assert_eq!(format.top_crids, vec![("c1".to_string(), 2)]);
+        assert_eq!(format.attrs, vec![(1, 1), (3, 1)]);
+        assert_eq!(format.problems, vec!["missing_adomain", "missing_crid"]);
+
+        let seat_a = &stats.by_key[&("seat", "dsp_a".to_string())];
+        assert_eq!((seat_a.bids, seat_a.missing_crid), (2, 1));
+        assert_eq!(
+            stats.by_key[&("seat", NO_SEAT.to_string())].missing_adomain,
+            1
+        );
+    }
+}
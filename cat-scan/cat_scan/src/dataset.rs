@@ -0,0 +1,388 @@
+use std::{fs::File, path::Path, sync::Arc};
+
+use anyhow::{Context, Result};
+use arrow_array::{ArrayRef, Float64Array, RecordBatch, StringArray, UInt32Array, UInt64Array};
+use arrow_schema::{DataType, Field, Schema};
+use parquet::{arrow::ArrowWriter, basic::Compression, file::properties::WriterProperties};
+
+use crate::cli::SortBy;
+use crate::report::{
+    build_extract_summaries, build_format_summaries, build_publisher_summaries,
+    build_segment_summaries, build_ssp_summaries,
+};
+use crate::stats::GlobalStats;
+
+/// One column of a dataset table
+enum Column {
+    Utf8(Vec<String>),
+    UInt32(Vec<u32>),
+    UInt64(Vec<u64>),
+    Float64(Vec<f64>),
+}
+
+impl Column {
+    fn data_type(&self) -> DataType {
+        match self {
+            Column::Utf8(_) => DataType::Utf8,
+            Column::UInt32(_) => DataType::UInt32,
+            Column::UInt64(_) => DataType::UInt64,
+            Column::Float64(_) => DataType::Float64,
+        }
+    }
+
+    fn into_array(self) -> ArrayRef {
+        match self {
+            Column::Utf8(v) => Arc::new(StringArray::from(v)),
+            Column::UInt32(v) => Arc::new(UInt32Array::from(v)),
+            Column::UInt64(v) => Arc::new(UInt64Array::from(v)),
+            Column::Float64(v) => Arc::new(Float64Array::from(v)),
+        }
+    }
+}
+
+/// One dimension of the dataset, written to `<name>/part-0.parquet`
+struct Table {
+    name: &'static str,
+    description: &'static str,
+    rows: usize,
+    columns: Vec<(&'static str, Column)>,
+}
+
+/// Every table is keyed by one dimension and carries the same rate columns
+fn rate_table<T>(
+    name: &'static str,
+    description: &'static str,
+    rows: &[T],
+    mut keys: Vec<(&'static str, Column)>,
+    rates: impl Fn(&T) -> (u64, u64, f64, f64),
+) -> Table {
+    let (mut requests, mut bids, mut bid_rate, mut avg_bid_price) =
+        (Vec::new(), Vec::new(), Vec::new(), Vec::new());
+    for row in rows {
+        let (r, b, rate, price) = rates(row);
+        requests.push(r);
+        bids.push(b);
+        bid_rate.push(rate);
+        avg_bid_price.push(price);
+    }
+    keys.extend([
+        ("requests", Column::UInt64(requests)),
+        ("bids", Column::UInt64(bids)),
+        ("bid_rate", Column::Float64(bid_rate)),
+        ("avg_bid_price", Column::Float64(avg_bid_price)),
+    ]);
+    Table {
+        name,
+        description,
+        rows: rows.len(),
+        columns: keys,
+    }
+}
+
+fn build_tables(global: &GlobalStats) -> Vec<Table> {
+    let formats = build_format_summaries(global, 0, SortBy::RequestsDesc);
+    let publishers = build_publisher_summaries(global);
+    let segments = build_segment_summaries(global);
+    let ssps = build_ssp_summaries(global);
+    let time: Vec<_> = global.time_stats.iter().collect();
+
+    let mut tables = vec![
+        rate_table(
+            "formats",
+            "Canonical banner formats (all, regardless of --min-requests)",
+            &formats,
+            vec![
+                ("w", Column::UInt32(formats.iter().map(|f| f.w).collect())),
+                ("h", Column::UInt32(formats.iter().map(|f| f.h).collect())),
+            ],
+            |s| (s.requests, s.bids, s.bid_rate, s.avg_bid_price),
+        ),
+        rate_table(
+            "publishers",
+            "Publishers (site.publisher.id) per SSP",
+            &publishers,
+            vec![
+                (
+                    "ssp",
+                    Column::Utf8(publishers.iter().map(|p| p.ssp.clone()).collect()),
+                ),
+                (
+                    "publisher_id",
+                    Column::Utf8(publishers.iter().map(|p| p.publisher_id.clone()).collect()),
+                ),
+            ],
+            |s| (s.requests, s.bids, s.bid_rate, s.avg_bid_price),
+        ),
+        rate_table(
+            "segments",
+            "User segments per SSP",
+            &segments,
+            vec![
+                (
+                    "ssp",
+                    Column::Utf8(segments.iter().map(|s| s.ssp.clone()).collect()),
+                ),
+                (
+                    "segment",
+                    Column::Utf8(segments.iter().map(|s| s.segment.clone()).collect()),
+                ),
+            ],
+            |s| (s.requests, s.bids, s.bid_rate, s.avg_bid_price),
+        ),
+        rate_table(
+            "ssps",
+            "Supply-side platforms",
+            &ssps,
+            vec![(
+                "ssp",
+                Column::Utf8(ssps.iter().map(|s| s.ssp.clone()).collect()),
+            )],
+            |s| (s.requests, s.bids, s.bid_rate, s.avg_bid_price),
+        ),
+        rate_table(
+            "time_buckets",
+            "Traffic per time bucket (bucket_ts_ms is the start of the bucket, one minute unless --bucket)",
+            &time,
+            vec![(
+                "bucket_ts_ms",
+                Column::UInt64(time.iter().map(|(bucket, _)| **bucket).collect()),
+            )],
+            |(_, t)| {
+                let rate = if t.requests == 0 {
+                    0.0
+                } else {
+                    t.bids as f64 / t.requests as f64
+                };
+                let price = if t.bids == 0 {
+                    0.0
+                } else {
+                    t.sum_bid_price / t.bids as f64
+                };
+                (t.requests, t.bids, rate, price)
+            },
+        ),
+    ];
+
+    if global.extract.is_some() {
+        let custom = build_extract_summaries(global);
+        tables.push(rate_table(
+            "custom_dimensions",
+            "Values of --extract rules per SSP",
+            &custom,
+            vec![
+                (
+                    "name",
+                    Column::Utf8(custom.iter().map(|c| c.name.clone()).collect()),
+                ),
+                (
+                    "ssp",
+                    Column::Utf8(custom.iter().map(|c| c.ssp.clone()).collect()),
+                ),
+                (
+                    "value",
+                    Column::Utf8(custom.iter().map(|c| c.value.clone()).collect()),
+                ),
+            ],
+            |s| (s.requests, s.bids, s.bid_rate, s.avg_bid_price),
+        ));
+    }
+    tables
+}
+
+#[derive(serde::Serialize)]
+struct ManifestColumn {
+    name: &'static str,
+    #[serde(rename = "type")]
+    data_type: String,
+}
+
+#[derive(serde::Serialize)]
+struct ManifestTable {
+    name: &'static str,
+    path: String,
+    description: &'static str,
+    rows: usize,
+    columns: Vec<ManifestColumn>,
+}
+
+/// manifest.json: what the dataset directory contains
+#[derive(serde::Serialize)]
+struct Manifest {
+    source: String,
+    generated_at_ms: u64,
+    total_requests: u64,
+    total_bids: u64,
+    tables: Vec<ManifestTable>,
+}
+
+fn write_table(dir: &Path, table: Table) -> Result<ManifestTable> {
+    let table_dir = dir.join(table.name);
+    std::fs::create_dir_all(&table_dir)
+        .with_context(|| format!("Failed to create {}", table_dir.display()))?;
+    let path = table_dir.join("part-0.parquet");
+
+    let schema = Arc::new(Schema::new(
+        table
+            .columns
+            .iter()
+            .map(|(name, column)| Field::new(*name, column.data_type(), false))
+            .collect::<Vec<_>>(),
+    ));
+    let manifest_columns = table
+        .columns
+        .iter()
+        .map(|(name, column)| ManifestColumn {
+            name,
+            data_type: column.data_type().to_string(),
+        })
+        .collect();
+    let arrays: Vec<ArrayRef> = table
+        .columns
+        .into_iter()
+        .map(|(_, column)| column.into_array())
+        .collect();
+    let batch = RecordBatch::try_new(Arc::clone(&schema), arrays)
+        .with_context(|| format!("Failed to build {} table", table.name))?;
+
+    let file =
+        File::create(&path).with_context(|| format!("Failed to create {}", path.display()))?;
+    let props = WriterProperties::builder()
+        .set_compression(Compression::SNAPPY)
+        .build();
+    let mut writer = ArrowWriter::try_new(file, schema, Some(props))?;
+    writer.write(&batch)?;
+    writer
+        .close()
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+
+    Ok(ManifestTable {
+        name: table.name,
+        path: format!("{}/part-0.parquet", table.name),
+        description: table.description,
+        rows: table.rows,
+        columns: manifest_columns,
+    })
+}
+
+/// Views over every table, then a few common analyses
+fn queries_sql(tables: &[ManifestTable]) -> String {
+    let mut sql =
+        String::from("-- Cat Scan dataset. From this directory, run: duckdb -init queries.sql\n\n");
+    for table in tables {
+        sql.push_str(&format!(
+            "CREATE OR REPLACE VIEW {} AS SELECT * FROM read_parquet('{}/*.parquet');\n",
+            table.name, table.name
+        ));
+    }
+    sql.push_str(
+        "
+-- Top zero-bid formats
+SELECT w, h, requests
+FROM formats
+WHERE bids = 0
+ORDER BY requests DESC
+LIMIT 20;
+
+-- Worst publishers: lowest bid rate among publishers with real traffic
+SELECT ssp, publisher_id, requests, round(bid_rate * 100, 2) AS bid_rate_pct
+FROM publishers
+WHERE requests >= 1000
+ORDER BY bid_rate, requests DESC
+LIMIT 20;
+
+-- Bid rate and price per SSP
+SELECT ssp, requests, round(bid_rate * 100, 2) AS bid_rate_pct, round(avg_bid_price, 4) AS avg_bid_price
+FROM ssps
+ORDER BY requests DESC;
+
+-- Hourly traffic
+SELECT date_trunc('hour', to_timestamp(bucket_ts_ms / 1000)) AS hour,
+       sum(requests) AS requests,
+       round(sum(bids) * 100.0 / sum(requests), 2) AS bid_rate_pct
+FROM time_buckets
+GROUP BY hour
+ORDER BY hour;
+",
+    );
+    sql
+}
+
+/// `--dataset DIR`: one Parquet table per dimension plus manifest.json and
+/// queries.sql, for exploring a scan with DuckDB
+pub fn write_dataset(dir: &str, global: &GlobalStats, source: &str, now_ms: u64) -> Result<()> {
+    let dir = Path::new(dir);
+    std::fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create dataset directory: {}", dir.display()))?;
+
+    let tables = build_tables(global)
+        .into_iter()
+        .map(|table| write_table(dir, table))
+        .collect::<Result<Vec<_>>>()?;
+
+    std::fs::write(dir.join("queries.sql"), queries_sql(&tables))
+        .context("Failed to write queries.sql")?;
+
+    let manifest = Manifest {
+        source: source.to_string(),
+        generated_at_ms: now_ms,
+        total_requests: global.total_requests(),
+        total_bids: global.total_bids(),
+        tables,
+    };
+    let json = serde_json::to_string_pretty(&manifest)?;
+    std::fs::write(dir.join("manifest.json"), json).context("Failed to write manifest.json")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    use crate::stats::{process_record_global, tests::make_record};
+
+    #[test]
+    fn test_write_dataset() {
+        let mut global = GlobalStats::new();
+        for (w, with_bid) in [(300, true), (300, false), (728, false)] {
+            let mut record = make_record(w, if w == 300 { 250 } else { 90 }, with_bid, 1.5);
+            record.ts_ms = Some(90_000);
+            process_record_global(&record, &mut global);
+        }
+
+        let dir = std::env::temp_dir().join(format!("cat_scan_dataset_{}", std::process::id()));
+        write_dataset(dir.to_str().unwrap(), &global, "logs.jsonl", 1_000).unwrap();
+
+        let file = File::open(dir.join("formats/part-0.parquet")).unwrap();
+        let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap();
+        let batches: Vec<RecordBatch> = reader.map(|b| b.unwrap()).collect();
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 2);
+        let requests = batches[0]
+            .column_by_name("requests")
+            .unwrap()
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .unwrap();
+        assert_eq!(requests.value(0), 2);
+
+        let manifest: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("manifest.json")).unwrap())
+                .unwrap();
+        assert_eq!(manifest["total_requests"], 3);
+        let tables = manifest["tables"].as_array().unwrap();
+        assert_eq!(tables.len(), 5);
+        assert_eq!(tables[0]["path"], "formats/part-0.parquet");
+        assert_eq!(tables[0]["columns"][0]["name"], "w");
+        let time = tables.iter().find(|t| t["name"] == "time_buckets").unwrap();
+        assert_eq!(time["rows"], 1);
+
+        let sql = std::fs::read_to_string(dir.join("queries.sql")).unwrap();
+        assert!(sql.contains("CREATE OR REPLACE VIEW publishers AS"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
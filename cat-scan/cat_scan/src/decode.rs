@@ -0,0 +1,926 @@
+use std::{
+    collections::VecDeque,
+    io::{BufRead, ErrorKind, Read},
+};
+
+use anyhow::{bail, Context, Result};
+use prost::Message;
+use serde_json::Value;
+
+use crate::extract::ExtractRule;
+#[cfg(feature = "google-ab")]
+use crate::google_ab;
+use crate::openrtb_proto::LogEntry;
+use crate::prebid::auction_records;
+use crate::source::parse_rfc3339;
+use crate::stats::LogRecord;
+
+/// Largest length prefix accepted before the input is taken to be corrupt
+const MAX_PROTO_RECORD_BYTES: u64 = 64 << 20;
+
+/// Encoding of the input records (--input-format)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputFormat {
+    /// One JSON log record per line
+    Jsonl,
+    /// Length-delimited cat_scan.LogEntry messages (proto/cat_scan_log.proto)
+    Proto,
+    /// One row per record with JSON columns named by --column-map
+    Csv,
+    /// As Csv, tab-separated
+    Tsv,
+    /// One Prebid.js or Prebid Server auction per line, split per bidder
+    /// and ad unit (see prebid::auction_records)
+    Prebid,
+    /// Length-delimited Google Authorized Buyers BidRequest/BidResponse
+    /// pairs (proto/google_ab_log.proto)
+    #[cfg(feature = "google-ab")]
+    GoogleAb,
+}
+
+impl InputFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "jsonl" | "json" => Some(Self::Jsonl),
+            "proto" | "protobuf" => Some(Self::Proto),
+            "csv" => Some(Self::Csv),
+            "tsv" => Some(Self::Tsv),
+            "prebid" | "pbs" => Some(Self::Prebid),
+            #[cfg(feature = "google-ab")]
+            "google-ab" => Some(Self::GoogleAb),
+            _ => None,
+        }
+    }
+
+    /// Whether a raw frame can be written back as one line of the input
+    /// (for --bad-lines)
+    pub fn is_line_based(self) -> bool {
+        matches!(self, Self::Jsonl | Self::Csv | Self::Tsv | Self::Prebid)
+    }
+
+    /// `layout` (--request-field / --response-field), `ts_field`
+    /// (--ts-field) and `dsp_field` (--dsp-field) only apply to JSON lines
+    pub fn decoder<'a, R: BufRead + 'a>(
+        self,
+        reader: R,
+        columns: &ColumnMap,
+        layout: &RecordLayout,
+        ts_field: Option<&ExtractRule>,
+        dsp_field: Option<&ExtractRule>,
+    ) -> Box<dyn RecordDecoder + 'a> {
+        match self {
+            Self::Jsonl => Box::new(
+                JsonlDecoder::new(reader)
+                    .with_layout(layout.clone())
+                    .with_ts_field(ts_field.cloned())
+                    .with_dsp_field(dsp_field.cloned()),
+            ),
+            Self::Proto => Box::new(ProtoDecoder::new(reader)),
+            Self::Csv => Box::new(CsvDecoder::new(reader, b',', columns.clone())),
+            Self::Tsv => Box::new(CsvDecoder::new(reader, b'\t', columns.clone())),
+            Self::Prebid => Box::new(PrebidDecoder::new(reader)),
+            #[cfg(feature = "google-ab")]
+            Self::GoogleAb => Box::new(ProtoDecoder::with_convert(reader, google_ab::decode_entry)),
+        }
+    }
+}
+
+/// Splits an input stream into records and decodes them. Framing and
+/// decoding are separate steps so records the sampler skips are never
+/// decoded.
+pub trait RecordDecoder {
+    /// Read the next raw record into `buf` (cleared first); false at the end
+    /// of the input
+    fn next_frame(&mut self, buf: &mut Vec<u8>) -> Result<bool>;
+
+    /// Decode the frame `next_frame` just read; Ok(None) for a blank one
+    fn decode(&mut self, frame: &[u8]) -> Result<Option<LogRecord>, String>;
+
+    /// The 1-based input line of the frame just read, for decoders whose
+    /// frames are not one per line
+    fn frame_line(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Top-level (request, response) key pairs recognised without
+/// --request-field / --response-field, checked in order
+const KNOWN_LAYOUTS: &[(&str, &str)] = &[
+    ("request", "response"),
+    ("req", "res"),
+    ("bid_request", "bid_response"),
+    ("bidRequest", "bidResponse"),
+    ("BidRequest", "BidResponse"),
+];
+
+/// Which top-level keys of a JSON log line hold the request and the
+/// response (--request-field / --response-field). Unset keys are detected
+/// from the first line (see KNOWN_LAYOUTS).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RecordLayout {
+    pub request: Option<String>,
+    pub response: Option<String>,
+}
+
+impl RecordLayout {
+    /// The (request, response) keys for a log line shaped like `line`; None
+    /// when no request key is given and none of the known ones is present
+    pub fn resolve(&self, line: &Value) -> Option<(String, String)> {
+        let known = KNOWN_LAYOUTS
+            .iter()
+            .find(|(request, _)| line.get(request).is_some());
+        let request = match (&self.request, known) {
+            (Some(request), _) => request.clone(),
+            (None, Some((request, _))) => request.to_string(),
+            (None, None) => return None,
+        };
+        let response = match (&self.response, known) {
+            (Some(response), _) => response.clone(),
+            // A custom request key usually comes with a matching response key
+            (None, Some((known_request, response))) if *known_request == request => {
+                response.to_string()
+            }
+            (None, _) => KNOWN_LAYOUTS
+                .iter()
+                .map(|(_, response)| *response)
+                .find(|response| line.get(response).is_some())
+                .unwrap_or("response")
+                .to_string(),
+        };
+        Some((request, response))
+    }
+}
+
+/// Parse one JSON document. With the `simd` feature this goes through
+/// simd-json, which parses in place: `text` is copied into `scratch`, kept
+/// by the caller so its allocation is reused from line to line.
+#[cfg(feature = "simd")]
+pub fn parse_json<T: serde::de::DeserializeOwned>(
+    text: &str,
+    scratch: &mut Vec<u8>,
+) -> Result<T, String> {
+    scratch.clear();
+    scratch.extend_from_slice(text.as_bytes());
+    simd_json::serde::from_slice(scratch).map_err(|e| e.to_string())
+}
+
+/// Parse one JSON document (serde_json; see the `simd` feature)
+#[cfg(not(feature = "simd"))]
+pub fn parse_json<T: serde::de::DeserializeOwned>(
+    text: &str,
+    _scratch: &mut Vec<u8>,
+) -> Result<T, String> {
+    serde_json::from_str(text).map_err(|e| e.to_string())
+}
+
+/// One JSON object per line
+pub struct JsonlDecoder<R> {
+    reader: R,
+    /// Parse buffer reused across lines (see parse_json)
+    scratch: Vec<u8>,
+    layout: RecordLayout,
+    /// The (request, response) keys, once resolved from the first line
+    keys: Option<(String, String)>,
+    /// Where to read the timestamp instead of the top-level `ts_ms`
+    ts_field: Option<ExtractRule>,
+    /// Where to read the DSP that answered, copied to `source.dsp` of the
+    /// request (--perspective ssp)
+    dsp_field: Option<ExtractRule>,
+}
+
+impl<R: BufRead> JsonlDecoder<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            scratch: Vec::new(),
+            layout: RecordLayout::default(),
+            keys: None,
+            ts_field: None,
+            dsp_field: None,
+        }
+    }
+
+    pub fn with_layout(mut self, layout: RecordLayout) -> Self {
+        self.layout = layout;
+        self
+    }
+
+    pub fn with_ts_field(mut self, ts_field: Option<ExtractRule>) -> Self {
+        self.ts_field = ts_field;
+        self
+    }
+
+    pub fn with_dsp_field(mut self, dsp_field: Option<ExtractRule>) -> Self {
+        self.dsp_field = dsp_field;
+        self
+    }
+
+    /// Whether lines can be deserialized straight into a LogRecord
+    fn is_plain(&self) -> bool {
+        self.ts_field.is_none()
+            && self.dsp_field.is_none()
+            && self
+                .keys
+                .as_ref()
+                .is_some_and(|(request, response)| request == "request" && response == "response")
+    }
+}
+
+/// The timestamp at `ts_field` in a log line, as epoch ms; None when the
+/// field is missing
+pub fn ts_at(ts_field: &ExtractRule, line: &Value) -> Result<Option<u64>, String> {
+    match ts_field.values(line).first() {
+        Some(ts) => parse_ts_ms(ts)
+            .map(Some)
+            .ok_or_else(|| format!("invalid timestamp '{ts}' at {}", ts_field.path)),
+        None => Ok(None),
+    }
+}
+
+/// Record the DSP on the request as `source.dsp`, next to `source.ssp`
+fn set_dsp(request: &mut Value, dsp: String) {
+    let Some(request) = request.as_object_mut() else {
+        return;
+    };
+    let source = request
+        .entry("source")
+        .or_insert_with(|| Value::Object(Default::default()));
+    if source.is_null() {
+        *source = Value::Object(Default::default());
+    }
+    if let Some(source) = source.as_object_mut() {
+        source.insert("dsp".to_string(), Value::String(dsp));
+    }
+}
+
+impl<R: BufRead> RecordDecoder for JsonlDecoder<R> {
+    fn next_frame(&mut self, buf: &mut Vec<u8>) -> Result<bool> {
+        buf.clear();
+        Ok(self.reader.read_until(b'\n', buf)? > 0)
+    }
+
+    fn decode(&mut self, frame: &[u8]) -> Result<Option<LogRecord>, String> {
+        let line = std::str::from_utf8(frame).map_err(|e| format!("invalid UTF-8: {}", e))?;
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            return Ok(None);
+        }
+        if self.is_plain() {
+            return parse_json(trimmed, &mut self.scratch).map(Some);
+        }
+        let mut line: Value = parse_json(trimmed, &mut self.scratch)?;
+        if self.keys.is_none() {
+            self.keys = Some(
+                self.layout
+                    .resolve(&line)
+                    .ok_or_else(|| "no request field; name it with --request-field".to_string())?,
+            );
+        }
+        let ts_ms = match &self.ts_field {
+            Some(ts_field) => ts_at(ts_field, &line)?,
+            None => match line.get("ts_ms") {
+                None | Some(Value::Null) => None,
+                Some(ts) => {
+                    Some(serde_json::from_value(ts.clone()).map_err(|e| format!("ts_ms: {e}"))?)
+                }
+            },
+        };
+        let dsp = self
+            .dsp_field
+            .as_ref()
+            .and_then(|dsp_field| dsp_field.values(&line).into_iter().next());
+        let (request_key, response_key) = self.keys.as_ref().expect("resolved above");
+        let mut request = match line.get_mut(request_key.as_str()) {
+            Some(request) => request.take(),
+            None => return Err(format!("missing field `{request_key}`")),
+        };
+        if let Some(dsp) = dsp {
+            set_dsp(&mut request, dsp);
+        }
+        let response = line
+            .get_mut(response_key.as_str())
+            .map(Value::take)
+            .unwrap_or_default();
+        let outcome = line.get_mut("outcome").map(Value::take).unwrap_or_default();
+        let http_status = line
+            .get_mut("http_status")
+            .map(Value::take)
+            .unwrap_or_default();
+        Ok(Some(LogRecord {
+            request,
+            response,
+            ts_ms,
+            outcome,
+            http_status,
+            line_no: 0,
+        }))
+    }
+}
+
+/// Varint length prefix followed by one encoded message (a LogEntry,
+/// unless built `with_convert`)
+pub struct ProtoDecoder<R> {
+    reader: R,
+    /// Decodes one message into a record; Ok(None) to skip it
+    convert: fn(&[u8]) -> Result<Option<LogRecord>, String>,
+}
+
+impl<R: Read> ProtoDecoder<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_convert(reader, decode_log_entry)
+    }
+
+    pub fn with_convert(
+        reader: R,
+        convert: fn(&[u8]) -> Result<Option<LogRecord>, String>,
+    ) -> Self {
+        Self { reader, convert }
+    }
+
+    /// The next length prefix, or None at a clean end of input
+    fn read_length(&mut self) -> Result<Option<u64>> {
+        let mut len = 0u64;
+        for shift in (0..64).step_by(7) {
+            let mut byte = [0u8];
+            match self.reader.read_exact(&mut byte) {
+                Ok(()) => {}
+                Err(e) if e.kind() == ErrorKind::UnexpectedEof && shift == 0 => return Ok(None),
+                Err(e) => return Err(e).context("Truncated protobuf length prefix"),
+            }
+            len |= u64::from(byte[0] & 0x7f) << shift;
+            if byte[0] & 0x80 == 0 {
+                return Ok(Some(len));
+            }
+        }
+        bail!("Invalid protobuf length prefix")
+    }
+}
+
+impl<R: Read> RecordDecoder for ProtoDecoder<R> {
+    fn next_frame(&mut self, buf: &mut Vec<u8>) -> Result<bool> {
+        buf.clear();
+        let Some(len) = self.read_length()? else {
+            return Ok(false);
+        };
+        if len > MAX_PROTO_RECORD_BYTES {
+            bail!("Protobuf record of {len} bytes; is the input really length-delimited protobuf?");
+        }
+        buf.resize(len as usize, 0);
+        self.reader
+            .read_exact(buf)
+            .context("Truncated protobuf record")?;
+        Ok(true)
+    }
+
+    fn decode(&mut self, frame: &[u8]) -> Result<Option<LogRecord>, String> {
+        (self.convert)(frame)
+    }
+}
+
+/// Prebid auction logs. One line holds a whole auction, so the line is
+/// decoded while framing and each of its records becomes a frame (holding
+/// the line, for --bad-lines); the sampler then picks records, not lines.
+pub struct PrebidDecoder<R> {
+    reader: R,
+    line: Vec<u8>,
+    lines_read: u64,
+    pending: VecDeque<LogRecord>,
+    current: Option<Result<LogRecord, String>>,
+}
+
+impl<R: BufRead> PrebidDecoder<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            line: Vec::new(),
+            lines_read: 0,
+            pending: VecDeque::new(),
+            current: None,
+        }
+    }
+
+    /// The records of the line just read; none for a blank line
+    fn parse_line(&self) -> Result<Vec<LogRecord>, String> {
+        let line = std::str::from_utf8(&self.line).map_err(|e| format!("invalid UTF-8: {}", e))?;
+        let trimmed = line.trim();
+        if trimmed.is_empty() {
+            return Ok(Vec::new());
+        }
+        let auction: Value = serde_json::from_str(trimmed).map_err(|e| e.to_string())?;
+        auction_records(&auction)
+    }
+}
+
+impl<R: BufRead> RecordDecoder for PrebidDecoder<R> {
+    fn next_frame(&mut self, buf: &mut Vec<u8>) -> Result<bool> {
+        loop {
+            if let Some(mut record) = self.pending.pop_front() {
+                record.line_no = self.lines_read;
+                self.current = Some(Ok(record));
+                buf.clone_from(&self.line);
+                return Ok(true);
+            }
+            self.line.clear();
+            if self.reader.read_until(b'\n', &mut self.line)? == 0 {
+                return Ok(false);
+            }
+            self.lines_read += 1;
+            match self.parse_line() {
+                Ok(records) => self.pending = records.into(),
+                Err(e) => {
+                    self.current = Some(Err(e));
+                    buf.clone_from(&self.line);
+                    return Ok(true);
+                }
+            }
+        }
+    }
+
+    fn decode(&mut self, _frame: &[u8]) -> Result<Option<LogRecord>, String> {
+        self.current.take().transpose()
+    }
+
+    fn frame_line(&self) -> Option<u64> {
+        Some(self.lines_read)
+    }
+}
+
+/// Which CSV columns hold the request JSON, the response JSON and the
+/// timestamp (--column-map). Unset entries fall back to columns named
+/// "request", "response" and "ts_ms"; only the request column is required.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColumnMap {
+    pub request: Option<String>,
+    pub response: Option<String>,
+    pub ts: Option<String>,
+}
+
+impl ColumnMap {
+    /// Parse "request=COL,response=COL,ts=COL", or read the same NAME=COL
+    /// pairs from a file, one per line
+    pub fn load(value: &str) -> Result<Self> {
+        let spec = if std::path::Path::new(value).is_file() {
+            std::fs::read_to_string(value)
+                .with_context(|| format!("Failed to read column map {}", value))?
+        } else {
+            value.to_string()
+        };
+        let mut map = Self::default();
+        for entry in spec
+            .split([',', '\n'])
+            .map(str::trim)
+            .filter(|e| !e.is_empty() && !e.starts_with('#'))
+        {
+            let (name, column) = entry.split_once('=').with_context(|| {
+                format!("invalid column map entry '{entry}', expected NAME=COLUMN")
+            })?;
+            let column = Some(column.trim().to_string());
+            match name.trim() {
+                "request" => map.request = column,
+                "response" => map.response = column,
+                "ts" | "ts_ms" => map.ts = column,
+                other => {
+                    bail!("unknown column map entry '{other}', expected request, response or ts")
+                }
+            }
+        }
+        Ok(map)
+    }
+}
+
+/// Position of `column` in the header: a header name, or a 1-based number
+fn column_index(headers: &csv::ByteRecord, column: &str) -> Option<usize> {
+    headers
+        .iter()
+        .position(|h| h == column.as_bytes())
+        .or_else(|| {
+            column
+                .parse::<usize>()
+                .ok()
+                .filter(|&n| n >= 1 && n <= headers.len())
+                .map(|n| n - 1)
+        })
+}
+
+/// Epoch seconds, milliseconds, microseconds or nanoseconds (told apart by
+/// magnitude; fractions allowed) or an RFC3339 / ISO8601 timestamp, as
+/// epoch ms
+pub fn parse_ts_ms(value: &str) -> Option<u64> {
+    let value = value.trim();
+    let epoch_ms = |n: f64| -> Option<u64> {
+        // Anything before ~1973 in ms is taken to be seconds, and so on up
+        let ms = match n {
+            n if n < 1e11 => n * 1e3,
+            n if n < 1e14 => n,
+            n if n < 1e17 => n / 1e3,
+            n => n / 1e6,
+        };
+        (ms.is_finite() && ms >= 0.0).then_some(ms as u64)
+    };
+    match value.parse::<u64>() {
+        Ok(n) if (100_000_000_000..100_000_000_000_000).contains(&n) => Some(n),
+        Ok(n) => epoch_ms(n as f64),
+        Err(_) => match value.parse::<f64>() {
+            Ok(n) => epoch_ms(n),
+            Err(_) => parse_rfc3339(value).map(|secs| secs * 1000),
+        },
+    }
+}
+
+/// Request, response and timestamp column positions
+#[derive(Debug, Clone, Copy)]
+struct CsvColumns {
+    request: usize,
+    response: Option<usize>,
+    ts: Option<usize>,
+}
+
+/// CSV/TSV rows with a header line. Quoted fields may span lines.
+pub struct CsvDecoder<R> {
+    reader: csv::Reader<R>,
+    map: ColumnMap,
+    columns: Option<CsvColumns>,
+    record: csv::ByteRecord,
+    delimiter: u8,
+}
+
+impl<R: Read> CsvDecoder<R> {
+    pub fn new(reader: R, delimiter: u8, map: ColumnMap) -> Self {
+        let reader = csv::ReaderBuilder::new()
+            .delimiter(delimiter)
+            .flexible(true)
+            .from_reader(reader);
+        Self {
+            reader,
+            map,
+            columns: None,
+            record: csv::ByteRecord::new(),
+            delimiter,
+        }
+    }
+
+    /// Look the mapped columns up in the header (read lazily, since the
+    /// input may block)
+    fn resolve_columns(&mut self) -> Result<CsvColumns> {
+        let headers = self
+            .reader
+            .byte_headers()
+            .context("Failed to read CSV header")?;
+        let find = |mapped: &Option<String>, default: &str| -> Result<Option<usize>> {
+            match mapped {
+                Some(column) => column_index(headers, column)
+                    .map(Some)
+                    .with_context(|| format!("column '{column}' not found in the CSV header")),
+                None => Ok(column_index(headers, default)),
+            }
+        };
+        let request = find(&self.map.request, "request")?
+            .context("no request column; name it with --column-map request=COLUMN")?;
+        Ok(CsvColumns {
+            request,
+            response: find(&self.map.response, "response")?,
+            ts: find(&self.map.ts, "ts_ms")?,
+        })
+    }
+}
+
+impl<R: Read> RecordDecoder for CsvDecoder<R> {
+    fn next_frame(&mut self, buf: &mut Vec<u8>) -> Result<bool> {
+        buf.clear();
+        if self.columns.is_none() {
+            self.columns = Some(self.resolve_columns()?);
+        }
+        if !self.reader.read_byte_record(&mut self.record)? {
+            return Ok(false);
+        }
+        // The row as it would appear in the input, for --bad-lines
+        let mut writer = csv::WriterBuilder::new()
+            .delimiter(self.delimiter)
+            .from_writer(buf);
+        writer.write_byte_record(&self.record)?;
+        writer.flush()?;
+        Ok(true)
+    }
+
+    fn decode(&mut self, _frame: &[u8]) -> Result<Option<LogRecord>, String> {
+        let Some(columns) = self.columns else {
+            return Ok(None);
+        };
+        let field = |index: Option<usize>| -> Result<&str, String> {
+            let bytes = index.and_then(|i| self.record.get(i)).unwrap_or_default();
+            std::str::from_utf8(bytes)
+                .map(str::trim)
+                .map_err(|e| format!("invalid UTF-8: {}", e))
+        };
+        let json = |text: &str, name: &str| -> Result<Value, String> {
+            if text.is_empty() {
+                return Ok(Value::Null);
+            }
+            serde_json::from_str(text).map_err(|e| format!("{name} column: {e}"))
+        };
+
+        let request = field(Some(columns.request))?;
+        if request.is_empty() {
+            return Err("empty request column".to_string());
+        }
+        let ts = field(columns.ts)?;
+        Ok(Some(LogRecord {
+            request: json(request, "request")?,
+            response: json(field(columns.response)?, "response")?,
+            ts_ms: if ts.is_empty() {
+                None
+            } else {
+                Some(parse_ts_ms(ts).ok_or_else(|| format!("invalid timestamp '{ts}'"))?)
+            },
+            outcome: Value::Null,
+            http_status: Value::Null,
+            line_no: 0,
+        }))
+    }
+}
+
+/// Drop unset (null) fields and empty repeated fields, so a decoded message
+/// looks like the JSON the same request would be logged as
+pub fn prune(value: Value) -> Value {
+    match value {
+        Value::Object(map) => Value::Object(
+            map.into_iter()
+                .filter(|(_, v)| !v.is_null() && v.as_array().is_none_or(|a| !a.is_empty()))
+                .map(|(k, v)| (k, prune(v)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.into_iter().map(prune).collect()),
+        other => other,
+    }
+}
+
+fn to_json<T: serde::Serialize>(message: T) -> Value {
+    prune(serde_json::to_value(message).unwrap_or_default())
+}
+
+fn decode_log_entry(frame: &[u8]) -> Result<Option<LogRecord>, String> {
+    let entry = LogEntry::decode(frame).map_err(|e| e.to_string())?;
+    Ok(Some(entry_to_record(entry)))
+}
+
+/// The LogRecord a JSONL log line with the same content would parse into
+fn entry_to_record(entry: LogEntry) -> LogRecord {
+    let mut request = entry.request.map_or(Value::Null, to_json);
+    if let Some(ssp) = entry.ssp {
+        request["source"]["ssp"] = Value::String(ssp);
+    }
+    LogRecord {
+        request,
+        response: entry.response.map_or(Value::Null, to_json),
+        ts_ms: entry.ts_ms,
+        outcome: Value::Null,
+        http_status: Value::Null,
+        line_no: 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::openrtb_proto::{Banner, Bid, BidRequest, BidResponse, Imp, SeatBid};
+
+    fn proto_entry(w: i32, h: i32, price: Option<f64>) -> LogEntry {
+        LogEntry {
+            request: Some(BidRequest {
+                id: Some("r1".to_string()),
+                imp: vec![Imp {
+                    id: Some("1".to_string()),
+                    banner: Some(Banner {
+                        w: Some(w),
+                        h: Some(h),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }),
+            response: Some(BidResponse {
+                seatbid: price
+                    .map(|price| SeatBid {
+                        bid: vec![Bid {
+                            price: Some(price),
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    })
+                    .into_iter()
+                    .collect(),
+                ..Default::default()
+            }),
+            ts_ms: Some(1_700_000_000_000),
+            ssp: Some("ssp_a".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_proto_decoder_matches_jsonl() {
+        let mut bytes = Vec::new();
+        for entry in [proto_entry(300, 250, Some(1.5)), proto_entry(728, 90, None)] {
+            entry.encode_length_delimited(&mut bytes).unwrap();
+        }
+
+        let mut decoder = InputFormat::Proto.decoder(
+            bytes.as_slice(),
+            &ColumnMap::default(),
+            &RecordLayout::default(),
+            None,
+            None,
+        );
+        let mut buf = Vec::new();
+        let mut records = Vec::new();
+        while decoder.next_frame(&mut buf).unwrap() {
+            records.push(decoder.decode(&buf).unwrap().unwrap());
+        }
+        assert_eq!(records.len(), 2);
+        assert_eq!(
+            records[0].request,
+            serde_json::json!({
+                "id": "r1",
+                "imp": [{ "id": "1", "banner": { "w": 300, "h": 250 } }],
+                "source": { "ssp": "ssp_a" }
+            })
+        );
+        assert_eq!(
+            records[0].response,
+            serde_json::json!({ "seatbid": [{ "bid": [{ "price": 1.5 }] }] })
+        );
+        assert_eq!(records[1].response, serde_json::json!({}));
+        assert_eq!(records[1].ts_ms, Some(1_700_000_000_000));
+    }
+
+    #[test]
+    fn test_csv_decoder_with_column_map() {
+        let input = "ts,ssp,req,resp\n\
+            2024-06-01T00:00:00Z,a,\"{\"\"id\"\":\"\"r1\"\"}\",\"{\"\"seatbid\"\":[]}\"\n\
+            1717200000123,a,\"{\"\"id\"\":\n\"\"r2\"\"}\",\n\
+            ,b,not json,\n";
+        let map = ColumnMap::load("request=req, response=resp,ts=1").unwrap();
+        let mut decoder =
+            InputFormat::Csv.decoder(input.as_bytes(), &map, &RecordLayout::default(), None, None);
+        let mut buf = Vec::new();
+
+        assert!(decoder.next_frame(&mut buf).unwrap());
+        let record = decoder.decode(&buf).unwrap().unwrap();
+        assert_eq!(record.request, serde_json::json!({ "id": "r1" }));
+        assert_eq!(record.response, serde_json::json!({ "seatbid": [] }));
+        assert_eq!(record.ts_ms, Some(1_717_200_000_000));
+
+        // A quoted field spanning two lines is still one record
+        assert!(decoder.next_frame(&mut buf).unwrap());
+        let record = decoder.decode(&buf).unwrap().unwrap();
+        assert_eq!(record.request["id"], "r2");
+        assert_eq!(record.response, Value::Null);
+        assert_eq!(record.ts_ms, Some(1_717_200_000_123));
+
+        assert!(decoder.next_frame(&mut buf).unwrap());
+        assert_eq!(buf, b",b,not json,\n");
+        let err = decoder.decode(&buf).err().unwrap();
+        assert!(err.starts_with("request column"), "{err}");
+        assert!(!decoder.next_frame(&mut buf).unwrap());
+
+        let missing = ColumnMap::load("request=payload").unwrap();
+        let mut decoder = InputFormat::Tsv.decoder(
+            &b"request\tts_ms\n"[..],
+            &missing,
+            &RecordLayout::default(),
+            None,
+            None,
+        );
+        assert!(decoder.next_frame(&mut buf).is_err());
+    }
+
+    #[test]
+    fn test_jsonl_decoder_with_ts_field() {
+        let input = r#"{"request": {"ext": {"ts": 1717200000}}, "ts_ms": 1}
+{"request": {"ext": {"ts": "2024-06-01T00:00:05Z"}}}
+{"request": {"ext": {"ts": 1717200000123456}}}
+{"request": {}, "ts_ms": 1}
+{"request": {"ext": {"ts": "yesterday"}}}
+"#;
+        let ts_field = ExtractRule::for_path("request.ext.ts").unwrap();
+        let mut decoder = InputFormat::Jsonl.decoder(
+            input.as_bytes(),
+            &ColumnMap::default(),
+            &RecordLayout::default(),
+            Some(&ts_field),
+            None,
+        );
+        let mut buf = Vec::new();
+        let mut next = || {
+            assert!(decoder.next_frame(&mut buf).unwrap());
+            decoder.decode(&buf).map(|r| r.unwrap().ts_ms)
+        };
+        assert_eq!(next(), Ok(Some(1_717_200_000_000)));
+        assert_eq!(next(), Ok(Some(1_717_200_005_000)));
+        assert_eq!(next(), Ok(Some(1_717_200_000_123)));
+        // The field replaces ts_ms, even when missing
+        assert_eq!(next(), Ok(None));
+        assert!(next().unwrap_err().contains("invalid timestamp"));
+
+        assert_eq!(parse_ts_ms("1717200000.5"), Some(1_717_200_000_500));
+        assert_eq!(parse_ts_ms("1717200000123456789"), Some(1_717_200_000_123));
+    }
+
+    #[test]
+    fn test_jsonl_decoder_with_dsp_field() {
+        let input = r#"{"request": {"id": "r1", "source": {"ssp": "x"}}, "response": {"ext": {"dsp": "dsp_a"}}}
+{"request": {"id": "r2"}, "response": {}}
+"#;
+        let dsp_field = ExtractRule::for_path("response.ext.dsp").unwrap();
+        let mut decoder = InputFormat::Jsonl.decoder(
+            input.as_bytes(),
+            &ColumnMap::default(),
+            &RecordLayout::default(),
+            None,
+            Some(&dsp_field),
+        );
+        let mut buf = Vec::new();
+        let mut next = || {
+            assert!(decoder.next_frame(&mut buf).unwrap());
+            decoder.decode(&buf).unwrap().unwrap().request
+        };
+        assert_eq!(
+            next()["source"],
+            serde_json::json!({ "ssp": "x", "dsp": "dsp_a" })
+        );
+        assert_eq!(next().get("source"), None);
+    }
+
+    #[test]
+    fn test_jsonl_decoder_detects_layout() {
+        let decode_all = |input: &str, layout: RecordLayout| {
+            let mut decoder = InputFormat::Jsonl.decoder(
+                input.as_bytes(),
+                &ColumnMap::default(),
+                &layout,
+                None,
+                None,
+            );
+            let mut buf = Vec::new();
+            let mut records = Vec::new();
+            while decoder.next_frame(&mut buf).unwrap() {
+                records.push(decoder.decode(&buf));
+            }
+            records
+        };
+
+        let input = r#"{"req": {"id": "r1"}, "res": {"seatbid": []}, "ts_ms": 5}
+{"req": {"id": "r2"}}
+{"request": {"id": "r3"}}
+"#;
+        let records = decode_all(input, RecordLayout::default());
+        let first = records[0].as_ref().unwrap().as_ref().unwrap();
+        assert_eq!(first.request["id"], "r1");
+        assert_eq!(first.response, serde_json::json!({ "seatbid": [] }));
+        assert_eq!(first.ts_ms, Some(5));
+        assert_eq!(
+            records[1].as_ref().unwrap().as_ref().unwrap().response,
+            Value::Null
+        );
+        // The layout is fixed by the first line
+        assert_eq!(records[2].as_ref().err().unwrap(), "missing field `req`");
+
+        let input = r#"{"payload": {"id": "r1"}, "bid_response": {"id": "r1"}}"#;
+        assert!(decode_all(input, RecordLayout::default())[0].is_err());
+        let layout = RecordLayout {
+            request: Some("payload".to_string()),
+            response: None,
+        };
+        let record = decode_all(input, layout).remove(0).unwrap().unwrap();
+        assert_eq!(record.request["id"], "r1");
+        assert_eq!(record.response["id"], "r1");
+    }
+
+    #[test]
+    fn test_proto_decoder_rejects_truncated_input() {
+        let mut bytes = Vec::new();
+        proto_entry(300, 250, None)
+            .encode_length_delimited(&mut bytes)
+            .unwrap();
+        bytes.truncate(bytes.len() - 3);
+
+        let mut decoder = InputFormat::Proto.decoder(
+            bytes.as_slice(),
+            &ColumnMap::default(),
+            &RecordLayout::default(),
+            None,
+            None,
+        );
+        assert!(decoder.next_frame(&mut Vec::new()).is_err());
+        assert!(!InputFormat::Proto
+            .decoder(
+                &b""[..],
+                &ColumnMap::default(),
+                &RecordLayout::default(),
+                None,
+                None
+            )
+            .next_frame(&mut Vec::new())
+            .unwrap());
+    }
+}
@@ -0,0 +1,227 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use anyhow::{bail, Context, Result};
+
+use crate::cli::DiffConfig;
+
+/// Requests and bids per canonical format of one scan
+type FormatCounts = BTreeMap<(u32, u32), (u64, u64)>;
+
+/// Bid rate of one canonical format in two scans
+#[derive(Debug)]
+pub struct FormatChange {
+    pub w: u32,
+    pub h: u32,
+    pub old_requests: u64,
+    pub old_bids: u64,
+    pub new_requests: u64,
+    pub new_bids: u64,
+    /// Two-proportion z statistic of the bid rate change; none when the
+    /// format is missing from either scan
+    pub z: Option<f64>,
+    /// Two-sided p-value of `z`
+    pub p_value: Option<f64>,
+    pub significant: bool,
+}
+
+impl FormatChange {
+    pub fn old_bid_rate(&self) -> f64 {
+        rate(self.old_bids, self.old_requests)
+    }
+
+    pub fn new_bid_rate(&self) -> f64 {
+        rate(self.new_bids, self.new_requests)
+    }
+
+    /// Bid rate change, new minus old (0.1 = 10 points up)
+    pub fn change(&self) -> f64 {
+        self.new_bid_rate() - self.old_bid_rate()
+    }
+}
+
+fn rate(bids: u64, requests: u64) -> f64 {
+    if requests == 0 {
+        0.0
+    } else {
+        bids as f64 / requests as f64
+    }
+}
+
+/// z statistic for the change from `b1` bids in `n1` requests to `b2` in
+/// `n2`, using the pooled rate for the standard error
+pub fn two_proportion_z(b1: u64, n1: u64, b2: u64, n2: u64) -> Option<f64> {
+    if n1 == 0 || n2 == 0 {
+        return None;
+    }
+    let (n1, n2) = (n1 as f64, n2 as f64);
+    let pooled = (b1 + b2) as f64 / (n1 + n2);
+    let se = (pooled * (1.0 - pooled) * (1.0 / n1 + 1.0 / n2)).sqrt();
+    if se == 0.0 {
+        // Both scans bid on all or none of the format's requests
+        return Some(0.0);
+    }
+    Some((b2 as f64 / n2 - b1 as f64 / n1) / se)
+}
+
+/// Two-sided p-value of a standard normal z
+pub fn p_value(z: f64) -> f64 {
+    erfc(z.abs() / std::f64::consts::SQRT_2)
+}
+
+/// Complementary error function (Abramowitz & Stegun 7.1.26, error < 1.5e-7)
+fn erfc(x: f64) -> f64 {
+    let t = 1.0 / (1.0 + 0.3275911 * x);
+    let poly = t
+        * (0.254829592
+            + t * (-0.284496736 + t * (1.421413741 + t * (-1.453152027 + t * 1.061405429))));
+    poly * (-x * x).exp()
+}
+
+/// Every format seen in either scan, significant changes first, then by
+/// size of the change
+pub fn diff_formats(old: &FormatCounts, new: &FormatCounts, confidence: f64) -> Vec<FormatChange> {
+    let mut formats: Vec<(u32, u32)> = old.keys().chain(new.keys()).copied().collect();
+    formats.sort();
+    formats.dedup();
+
+    let mut changes: Vec<FormatChange> = formats
+        .into_iter()
+        .map(|(w, h)| {
+            let (old_requests, old_bids) = old.get(&(w, h)).copied().unwrap_or_default();
+            let (new_requests, new_bids) = new.get(&(w, h)).copied().unwrap_or_default();
+            let z = two_proportion_z(old_bids, old_requests, new_bids, new_requests);
+            let p_value = z.map(p_value);
+            FormatChange {
+                w,
+                h,
+                old_requests,
+                old_bids,
+                new_requests,
+                new_bids,
+                z,
+                p_value,
+                significant: p_value.is_some_and(|p| p < 1.0 - confidence),
+            }
+        })
+        .collect();
+    changes.sort_by(|a, b| {
+        b.significant
+            .cmp(&a.significant)
+            .then_with(|| b.change().abs().total_cmp(&a.change().abs()))
+            .then_with(|| (a.w, a.h).cmp(&(b.w, b.h)))
+    });
+    changes
+}
+
+/// Requests and bids per format from a scan's format_stats.csv, given the
+/// file or the --out directory holding it
+fn read_format_stats(path: &str) -> Result<FormatCounts> {
+    let file = if Path::new(path).is_dir() {
+        Path::new(path).join("format_stats.csv")
+    } else {
+        Path::new(path).to_path_buf()
+    };
+    let text =
+        fs::read_to_string(&file).with_context(|| format!("Failed to read {}", file.display()))?;
+    parse_format_stats(&text).with_context(|| format!("Invalid format stats in {}", file.display()))
+}
+
+fn parse_format_stats(text: &str) -> Result<FormatCounts> {
+    let mut counts = FormatCounts::new();
+    for line in text.lines() {
+        // Scan metadata, the header and blank lines
+        if line.starts_with('#') || line.starts_with("w,") || line.trim().is_empty() {
+            continue;
+        }
+        let fields: Vec<&str> = line.split(',').collect();
+        if fields.len() < 4 {
+            bail!("expected w,h,requests,bids,... but got '{line}'");
+        }
+        let number = |i: usize| {
+            fields[i]
+                .trim()
+                .parse::<u64>()
+                .with_context(|| format!("invalid number '{}' in '{line}'", fields[i]))
+        };
+        let (w, h) = (number(0)? as u32, number(1)? as u32);
+        counts.insert((w, h), (number(2)?, number(3)?));
+    }
+    Ok(counts)
+}
+
+/// Print the per-format bid rate changes between two scans as CSV
+pub fn run_diff(config: DiffConfig) -> Result<()> {
+    let old = read_format_stats(&config.old_path)?;
+    let new = read_format_stats(&config.new_path)?;
+    let changes = diff_formats(&old, &new, config.confidence);
+
+    println!(
+        "w,h,old_requests,old_bid_rate,new_requests,new_bid_rate,change,z,p_value,significant"
+    );
+    for c in &changes {
+        println!(
+            "{},{},{},{:.4},{},{:.4},{:+.4},{},{},{}",
+            c.w,
+            c.h,
+            c.old_requests,
+            c.old_bid_rate(),
+            c.new_requests,
+            c.new_bid_rate(),
+            c.change(),
+            c.z.map(|z| format!("{z:.2}")).unwrap_or_default(),
+            c.p_value.map(|p| format!("{p:.4}")).unwrap_or_default(),
+            c.significant
+        );
+    }
+    let significant = changes.iter().filter(|c| c.significant).count();
+    eprintln!(
+        "{} of {} format bid rate changes are significant at {:.0}% confidence",
+        significant,
+        changes.len(),
+        config.confidence * 100.0
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_two_proportion_z() {
+        // 50% -> 60% on 1000 requests each: z ~ 4.49
+        let z = two_proportion_z(500, 1000, 600, 1000).unwrap();
+        assert!((z - 4.49).abs() < 0.01);
+        assert!(p_value(z) < 0.001);
+        assert!((p_value(1.96) - 0.05).abs() < 0.001);
+        assert!((p_value(0.0) - 1.0).abs() < 1e-6);
+
+        assert_eq!(two_proportion_z(0, 10, 0, 20), Some(0.0));
+        assert_eq!(two_proportion_z(5, 10, 3, 0), None);
+    }
+
+    #[test]
+    fn test_diff_formats_significance() {
+        let text = "# cat_scan 0.1.0\nw,h,requests,bids,bid_rate,avg_bid_price\n\
+                    300,250,1000,500,0.5000,1.0000\n728,90,10,5,0.5000,1.0000\n";
+        let old = parse_format_stats(text).unwrap();
+        let new = parse_format_stats(
+            "w,h,requests,bids,bid_rate,avg_bid_price\n\
+             300,250,1000,600,0.6000,1.0000\n728,90,10,8,0.8000,1.0000\n320,50,50,0,0.0000,0.0000\n",
+        )
+        .unwrap();
+
+        let changes = diff_formats(&old, &new, 0.95);
+        let find = |w, h| changes.iter().find(|c| (c.w, c.h) == (w, h)).unwrap();
+        // Same 10 point rise on 1000 requests is real; 30 points on 10 is noise
+        assert!(find(300, 250).significant);
+        assert!(!find(728, 90).significant);
+        assert!((find(728, 90).change() - 0.3).abs() < 1e-9);
+        // New in the second scan: no test
+        assert_eq!(find(320, 50).z, None);
+        assert!(!find(320, 50).significant);
+        assert_eq!((changes[0].w, changes[0].h), (300, 250));
+
+        assert!(parse_format_stats("300,250,many,5").is_err());
+    }
+}
@@ -0,0 +1,412 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, HashSet},
+    io::Write,
+    sync::Arc,
+};
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+use crate::metadata::{create_csv, ScanMetadata};
+use crate::qps::ALL_SSPS;
+use crate::stats::scale_count;
+
+/// Stop tracking new off-list (SSP, domain) pairs past this many (memory
+/// guard)
+const MAX_TRACKED_DOMAINS: usize = 100_000;
+
+/// Rows kept in the off-list domain list
+const TOP_DOMAINS: usize = 20;
+
+/// Site domains and app bundles, e.g. a targeting allowlist or denylist.
+/// An entry covers its subdomains too: `example.com` matches
+/// `news.example.com`.
+#[derive(Debug, Default)]
+pub struct DomainList {
+    entries: HashSet<String>,
+}
+
+impl DomainList {
+    /// One domain or bundle per line; blank lines and `#` comments are
+    /// skipped, as are a leading `*.` or `www.`
+    pub fn parse(text: &str) -> Self {
+        let entries = text
+            .lines()
+            .map(|line| normalize(line.split('#').next().unwrap_or("")))
+            .filter(|entry| !entry.is_empty())
+            .collect();
+        Self { entries }
+    }
+
+    pub fn load(path: &str) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read domain list: {}", path))?;
+        Ok(Self::parse(&text))
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether `domain` (normalized) or one of its parent domains is listed
+    pub fn contains(&self, domain: &str) -> bool {
+        let mut rest = domain;
+        loop {
+            if self.entries.contains(rest) {
+                return true;
+            }
+            match rest.split_once('.') {
+                Some((_, parent)) if !parent.is_empty() => rest = parent,
+                _ => return false,
+            }
+        }
+    }
+}
+
+/// Second-level labels under which a country TLD sells domains, as in
+/// `co.uk`: the site is then the label before
+const SECOND_LEVEL: &[&str] = &["co", "com", "net", "org", "gov", "edu", "ac", "ne", "or"];
+
+/// The registrable part of a host: `news.example.co.uk` -> `example.co.uk`
+pub fn site_of(host: &str) -> &str {
+    let labels: Vec<&str> = host.rsplitn(4, '.').collect();
+    let keep = match labels.as_slice() {
+        [tld, second, ..] if tld.len() == 2 && SECOND_LEVEL.contains(second) => 3,
+        _ => 2,
+    };
+    let cut = labels.iter().take(keep).map(|l| l.len() + 1).sum::<usize>();
+    &host[host.len().saturating_sub(cut - 1)..]
+}
+
+/// Lower-cased, without a trailing dot or a leading `*.` or `www.`
+pub fn normalize(domain: &str) -> String {
+    let domain = domain.trim().trim_end_matches('.').to_ascii_lowercase();
+    let domain = domain.strip_prefix("*.").unwrap_or(&domain);
+    domain.strip_prefix("www.").unwrap_or(domain).to_string()
+}
+
+/// Host of a URL, without scheme, port or path
+pub fn url_host(url: &str) -> &str {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let host = rest.split(['/', '?', '#']).next().unwrap_or("");
+    let host = host.rsplit_once('@').map_or(host, |(_, host)| host);
+    host.split(':').next().unwrap_or("")
+}
+
+/// What a domain list can name a request by: site.domain (or the host of
+/// site.page), or app.bundle and app.domain. Normalized; empty when the
+/// request carries none.
+pub fn request_domains(request: &Value) -> Vec<String> {
+    let site = &request["site"];
+    let app = &request["app"];
+    let candidates = [
+        site["domain"].as_str(),
+        site["page"].as_str().map(url_host),
+        app["bundle"].as_str(),
+        app["domain"].as_str(),
+    ];
+    let mut domains: Vec<String> = Vec::new();
+    for domain in candidates.into_iter().flatten().map(normalize) {
+        if !domain.is_empty() && !domains.contains(&domain) {
+            domains.push(domain);
+        }
+    }
+    domains
+}
+
+/// Per-SSP counts against the lists
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct DomainListCounts {
+    pub requests: u64,
+    /// No site domain, page or app bundle to check
+    pub no_domain: u64,
+    pub outside_allowlist: u64,
+    pub on_denylist: u64,
+    /// Outside the allowlist or on the denylist
+    pub off_list: u64,
+    pub off_list_bids: u64,
+}
+
+impl DomainListCounts {
+    fn merge(&mut self, other: &DomainListCounts) {
+        self.requests += other.requests;
+        self.no_domain += other.no_domain;
+        self.outside_allowlist += other.outside_allowlist;
+        self.on_denylist += other.on_denylist;
+        self.off_list += other.off_list;
+        self.off_list_bids += other.off_list_bids;
+    }
+
+    fn scale(&mut self, factor: f64) {
+        for count in [
+            &mut self.requests,
+            &mut self.no_domain,
+            &mut self.outside_allowlist,
+            &mut self.on_denylist,
+            &mut self.off_list,
+            &mut self.off_list_bids,
+        ] {
+            scale_count(count, factor);
+        }
+    }
+}
+
+/// Traffic per SSP outside our allowlist or on our denylist
+/// (--allowlist / --denylist): whether SSPs honor our targeting lists
+#[derive(Debug, Default, Clone)]
+pub struct DomainListStats {
+    allowlist: Option<Arc<DomainList>>,
+    denylist: Option<Arc<DomainList>>,
+    pub by_ssp: BTreeMap<String, DomainListCounts>,
+    /// Off-list requests per (SSP, domain)
+    pub off_list_domains: HashMap<(String, String), u64>,
+}
+
+impl DomainListStats {
+    pub fn new(allowlist: Option<DomainList>, denylist: Option<DomainList>) -> Self {
+        Self {
+            allowlist: allowlist.map(Arc::new),
+            denylist: denylist.map(Arc::new),
+            ..Self::default()
+        }
+    }
+
+    pub fn observe(&mut self, request: &Value, ssp: &str, has_bid: bool) {
+        let counts = self.by_ssp.entry(ssp.to_string()).or_default();
+        counts.requests += 1;
+        let domains = request_domains(request);
+        let Some(first) = domains.first() else {
+            counts.no_domain += 1;
+            return;
+        };
+        let outside = self
+            .allowlist
+            .as_ref()
+            .is_some_and(|list| !domains.iter().any(|d| list.contains(d)));
+        let denied = self
+            .denylist
+            .as_ref()
+            .is_some_and(|list| domains.iter().any(|d| list.contains(d)));
+        counts.outside_allowlist += u64::from(outside);
+        counts.on_denylist += u64::from(denied);
+        if !(outside || denied) {
+            return;
+        }
+        counts.off_list += 1;
+        counts.off_list_bids += u64::from(has_bid);
+        let key = (ssp.to_string(), first.clone());
+        if let Some(count) = self.off_list_domains.get_mut(&key) {
+            *count += 1;
+        } else if self.off_list_domains.len() < MAX_TRACKED_DOMAINS {
+            self.off_list_domains.insert(key, 1);
+        }
+    }
+
+    pub fn merge(&mut self, other: &DomainListStats) {
+        if self.allowlist.is_none() {
+            self.allowlist = other.allowlist.clone();
+        }
+        if self.denylist.is_none() {
+            self.denylist = other.denylist.clone();
+        }
+        for (ssp, counts) in &other.by_ssp {
+            self.by_ssp.entry(ssp.clone()).or_default().merge(counts);
+        }
+        for (key, count) in &other.off_list_domains {
+            *self.off_list_domains.entry(key.clone()).or_default() += count;
+        }
+    }
+
+    /// Multiply counts by `factor` (extrapolating from a sample)
+    pub fn scale(&mut self, factor: f64) {
+        for counts in self.by_ssp.values_mut() {
+            counts.scale(factor);
+        }
+        for count in self.off_list_domains.values_mut() {
+            scale_count(count, factor);
+        }
+    }
+
+    /// An ALL_SSPS row then SSPs by off-list volume, and the busiest
+    /// off-list domains. Wasted QPS needs the scan window (timestamps).
+    pub fn summarize(&self, window_secs: Option<u64>) -> DomainListReport {
+        let mut total = DomainListCounts::default();
+        for counts in self.by_ssp.values() {
+            total.merge(counts);
+        }
+        let summary = |ssp: &str, c: &DomainListCounts| DomainListSummary {
+            ssp: ssp.to_string(),
+            requests: c.requests,
+            no_domain: c.no_domain,
+            outside_allowlist: c.outside_allowlist,
+            on_denylist: c.on_denylist,
+            off_list: c.off_list,
+            off_list_share: if c.requests == 0 {
+                0.0
+            } else {
+                c.off_list as f64 / c.requests as f64
+            },
+            off_list_bids: c.off_list_bids,
+            wasted_qps: window_secs.map(|secs| c.off_list as f64 / secs.max(1) as f64),
+        };
+        let mut ssps: Vec<DomainListSummary> = self
+            .by_ssp
+            .iter()
+            .map(|(ssp, counts)| summary(ssp, counts))
+            .collect();
+        ssps.sort_by(|a, b| {
+            b.off_list
+                .cmp(&a.off_list)
+                .then_with(|| b.requests.cmp(&a.requests))
+                .then_with(|| a.ssp.cmp(&b.ssp))
+        });
+        ssps.insert(0, summary(ALL_SSPS, &total));
+
+        let mut top_domains: Vec<OffListDomain> = self
+            .off_list_domains
+            .iter()
+            .map(|((ssp, domain), &requests)| OffListDomain {
+                ssp: ssp.clone(),
+                domain: domain.clone(),
+                requests,
+                on_denylist: self.denylist.as_ref().is_some_and(|l| l.contains(domain)),
+            })
+            .collect();
+        top_domains.sort_by(|a, b| {
+            (Reverse(a.requests), &a.ssp, &a.domain).cmp(&(Reverse(b.requests), &b.ssp, &b.domain))
+        });
+        top_domains.truncate(TOP_DOMAINS);
+
+        DomainListReport {
+            allowlist_size: self.allowlist.as_ref().map(|l| l.len()),
+            denylist_size: self.denylist.as_ref().map(|l| l.len()),
+            ssps,
+            top_domains,
+        }
+    }
+}
+
+/// Domain list audit of one SSP
+#[derive(serde::Serialize)]
+pub struct DomainListSummary {
+    pub ssp: String,
+    pub requests: u64,
+    pub no_domain: u64,
+    pub outside_allowlist: u64,
+    pub on_denylist: u64,
+    pub off_list: u64,
+    pub off_list_share: f64,
+    /// Bids we placed on off-list traffic anyway
+    pub off_list_bids: u64,
+    /// Off-list requests per second over the scan window
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wasted_qps: Option<f64>,
+}
+
+/// A busy off-list domain of one SSP
+#[derive(serde::Serialize)]
+pub struct OffListDomain {
+    pub ssp: String,
+    pub domain: String,
+    pub requests: u64,
+    /// On the denylist, rather than only missing from the allowlist
+    pub on_denylist: bool,
+}
+
+/// "Domain lists" section of the report
+#[derive(serde::Serialize)]
+pub struct DomainListReport {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allowlist_size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub denylist_size: Option<usize>,
+    pub ssps: Vec<DomainListSummary>,
+    pub top_domains: Vec<OffListDomain>,
+}
+
+/// Write domain_lists.csv (off-list traffic per SSP, then the busiest
+/// off-list domains)
+pub fn write_domain_list_csv(
+    out_dir: &str,
+    report: &DomainListReport,
+    meta: &ScanMetadata,
+) -> Result<()> {
+    let path = format!("{}/domain_lists.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+
+    writeln!(csv, "# SSPs")?;
+    writeln!(
+        csv,
+        "ssp,requests,no_domain,outside_allowlist,on_denylist,off_list,off_list_share,off_list_bids,wasted_qps"
+    )?;
+    for s in &report.ssps {
+        writeln!(
+            csv,
+            "{},{},{},{},{},{},{:.4},{},{}",
+            s.ssp,
+            s.requests,
+            s.no_domain,
+            s.outside_allowlist,
+            s.on_denylist,
+            s.off_list,
+            s.off_list_share,
+            s.off_list_bids,
+            s.wasted_qps
+                .map_or(String::new(), |qps| format!("{:.2}", qps))
+        )?;
+    }
+
+    writeln!(csv, "\n# Off-list domains")?;
+    writeln!(csv, "ssp,domain,requests,on_denylist")?;
+    for d in &report.top_domains {
+        writeln!(
+            csv,
+            "{},{},{},{}",
+            d.ssp, d.domain, d.requests, d.on_denylist
+        )?;
+    }
+
+    eprintln!("Domain list audit written to: {}", path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_domain_list_audit() {
+        let allow = DomainList::parse("# ours\nexample.com\n*.news.org\ncom.good.app\n");
+        assert!(allow.contains("sport.example.com"));
+        assert!(!allow.contains("badexample.com"));
+        let deny = DomainList::parse("spam.example.com");
+        let mut stats = DomainListStats::new(Some(allow), Some(deny));
+
+        let site = |page: &str| json!({ "site": { "page": page } });
+        stats.observe(&site("https://www.example.com/a?b=c"), "ssp_a", true);
+        stats.observe(&site("https://other.net/"), "ssp_a", true);
+        stats.observe(&site("http://spam.example.com:8080/"), "ssp_a", false);
+        stats.observe(
+            &json!({ "app": { "bundle": "com.good.app" } }),
+            "ssp_b",
+            false,
+        );
+        stats.observe(&json!({ "app": {} }), "ssp_b", false);
+
+        let report = stats.summarize(Some(2));
+        let (all, a, b) = (&report.ssps[0], &report.ssps[1], &report.ssps[2]);
+        assert_eq!((all.ssp.as_str(), all.off_list), (ALL_SSPS, 2));
+        assert_eq!((a.outside_allowlist, a.on_denylist), (1, 1));
+        assert_eq!((a.off_list, a.off_list_bids), (2, 1));
+        assert_eq!(a.wasted_qps, Some(1.0));
+        assert_eq!((b.ssp.as_str(), b.off_list, b.no_domain), ("ssp_b", 0, 1));
+        let domains: Vec<(&str, bool)> = report
+            .top_domains
+            .iter()
+            .map(|d| (d.domain.as_str(), d.on_denylist))
+            .collect();
+        assert_eq!(domains, [("other.net", false), ("spam.example.com", true)]);
+    }
+}
@@ -0,0 +1,200 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashSet},
+    hash::{Hash, Hasher},
+    io::Write,
+};
+
+use anyhow::Result;
+
+use crate::metadata::{create_csv, ScanMetadata};
+use crate::stats::scale_count;
+
+/// Stop remembering new request ids past this many (memory guard). Only a
+/// 64-bit hash of each id is kept.
+const MAX_TRACKED_IDS: usize = 5_000_000;
+
+/// Duplicated ids kept per SSP as evidence
+const SAMPLE_IDS: usize = 10;
+
+fn id_hash(id: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    id.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Request id collisions for one SSP
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SspIds {
+    pub requests: u64,
+    /// Requests without a request.id
+    pub missing_id: u64,
+    /// Requests whose id was already seen earlier in the scan
+    pub duplicates: u64,
+    /// Hashes of ids seen so far
+    seen: HashSet<u64>,
+    /// Ids seen more than once, in the order first repeated
+    pub samples: Vec<String>,
+}
+
+impl SspIds {
+    fn add_sample(&mut self, id: &str) {
+        if self.samples.len() < SAMPLE_IDS && !self.samples.iter().any(|s| s == id) {
+            self.samples.push(id.to_string());
+        }
+    }
+}
+
+/// request.id collisions per SSP within one scan
+#[derive(Debug, Default, Clone)]
+pub struct DuplicateIds {
+    pub by_ssp: BTreeMap<String, SspIds>,
+    /// Ids remembered across all SSPs (see MAX_TRACKED_IDS)
+    tracked: usize,
+}
+
+impl DuplicateIds {
+    pub fn observe(&mut self, request: &serde_json::Value, ssp: &str) {
+        let ids = self.by_ssp.entry(ssp.to_string()).or_default();
+        ids.requests += 1;
+        let Some(id) = request["id"].as_str().filter(|id| !id.is_empty()) else {
+            ids.missing_id += 1;
+            return;
+        };
+        let hash = id_hash(id);
+        if ids.seen.contains(&hash) {
+            ids.duplicates += 1;
+            ids.add_sample(id);
+        } else if self.tracked < MAX_TRACKED_IDS {
+            ids.seen.insert(hash);
+            self.tracked += 1;
+        }
+    }
+
+    /// Fold in a later stretch of the scan; ids seen on both sides count
+    /// once more as duplicates
+    pub fn merge(&mut self, other: &DuplicateIds) {
+        for (ssp, theirs) in &other.by_ssp {
+            let ours = self.by_ssp.entry(ssp.clone()).or_default();
+            ours.requests += theirs.requests;
+            ours.missing_id += theirs.missing_id;
+            ours.duplicates += theirs.duplicates;
+            for hash in &theirs.seen {
+                if ours.seen.contains(hash) {
+                    ours.duplicates += 1;
+                } else if self.tracked < MAX_TRACKED_IDS {
+                    ours.seen.insert(*hash);
+                    self.tracked += 1;
+                }
+            }
+            for id in &theirs.samples {
+                ours.add_sample(id);
+            }
+        }
+    }
+
+    /// Multiply counts by `factor` (extrapolating from a sample)
+    pub fn scale(&mut self, factor: f64) {
+        for ids in self.by_ssp.values_mut() {
+            for count in [&mut ids.requests, &mut ids.missing_id, &mut ids.duplicates] {
+                scale_count(count, factor);
+            }
+        }
+    }
+
+    pub fn total_duplicates(&self) -> u64 {
+        self.by_ssp.values().map(|ids| ids.duplicates).sum()
+    }
+
+    /// One row per SSP, highest duplication rate first
+    pub fn summarize(&self) -> Vec<DuplicateSummary> {
+        let rate = |n: u64, d: u64| if d == 0 { 0.0 } else { n as f64 / d as f64 };
+        let mut rows: Vec<DuplicateSummary> = self
+            .by_ssp
+            .iter()
+            .map(|(ssp, ids)| DuplicateSummary {
+                ssp: ssp.clone(),
+                requests: ids.requests,
+                missing_id: ids.missing_id,
+                duplicates: ids.duplicates,
+                duplicate_rate: rate(ids.duplicates, ids.requests),
+                sample_ids: ids.samples.clone(),
+            })
+            .collect();
+        rows.sort_by(|a, b| {
+            b.duplicate_rate
+                .total_cmp(&a.duplicate_rate)
+                .then_with(|| b.requests.cmp(&a.requests))
+                .then_with(|| a.ssp.cmp(&b.ssp))
+        });
+        rows
+    }
+}
+
+/// Duplicate request ids for one SSP
+#[derive(serde::Serialize)]
+pub struct DuplicateSummary {
+    pub ssp: String,
+    pub requests: u64,
+    pub missing_id: u64,
+    pub duplicates: u64,
+    /// Share of requests repeating an id seen earlier in the scan
+    pub duplicate_rate: f64,
+    pub sample_ids: Vec<String>,
+}
+
+/// Write duplicate_ids.csv (request id collisions per SSP)
+pub fn write_duplicates_csv(
+    out_dir: &str,
+    rows: &[DuplicateSummary],
+    meta: &ScanMetadata,
+) -> Result<()> {
+    let path = format!("{}/duplicate_ids.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    writeln!(
+        csv,
+        "ssp,requests,missing_id,duplicates,duplicate_rate,sample_ids"
+    )?;
+    for r in rows {
+        writeln!(
+            csv,
+            "{},{},{},{},{:.4},{}",
+            r.ssp,
+            r.requests,
+            r.missing_id,
+            r.duplicates,
+            r.duplicate_rate,
+            r.sample_ids.join(" ")
+        )?;
+    }
+    eprintln!("Duplicate request ids written to: {}", path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_duplicate_ids_per_ssp() {
+        let mut first = DuplicateIds::default();
+        for (id, ssp) in [("a", "x"), ("b", "x"), ("a", "x"), ("a", "x"), ("a", "y")] {
+            first.observe(&json!({ "id": id }), ssp);
+        }
+        first.observe(&json!({}), "y");
+        let mut second = DuplicateIds::default();
+        second.observe(&json!({ "id": "b" }), "x");
+
+        first.merge(&second);
+        let x = &first.by_ssp["x"];
+        assert_eq!((x.requests, x.duplicates), (5, 3));
+        assert_eq!(x.samples, vec!["a"]);
+        let y = &first.by_ssp["y"];
+        assert_eq!((y.requests, y.missing_id, y.duplicates), (2, 1, 0));
+
+        let rows = first.summarize();
+        assert_eq!(rows[0].ssp, "x");
+        assert!((rows[0].duplicate_rate - 0.6).abs() < 1e-9);
+        assert_eq!(first.total_duplicates(), 3);
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::BTreeMap;
+
+use crate::stats::LogRecord;
+
+/// Default cap on the total number of example references kept per scan
+pub const DEFAULT_EXAMPLE_BUDGET: usize = 100_000;
+
+/// Cap on the total number of raw records kept with --samples
+pub const DEFAULT_SAMPLE_BUDGET: usize = 2_000;
+
+/// Longest prettified record embedded in the report, in characters
+pub const MAX_SAMPLE_CHARS: usize = 4_000;
+
+/// Pointer back to one concrete log record
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct ExampleRef {
+    /// 1-based line number in the input
+    pub line: u64,
+    /// request.id, when the record had one
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub id: String,
+}
+
+/// Up to `per_key` example references for every aggregation key, keyed by
+/// dimension name ("raw_format", "publisher", ...) and then by key.
+/// Once `budget` references are stored, new ones are dropped.
+#[derive(Debug, Default, Clone)]
+pub struct ExampleStore {
+    per_key: usize,
+    budget: usize,
+    stored: usize,
+    pub by_dimension: BTreeMap<&'static str, BTreeMap<String, Vec<ExampleRef>>>,
+}
+
+impl ExampleStore {
+    pub fn new(per_key: usize, budget: usize) -> Self {
+        Self {
+            per_key,
+            budget,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.per_key > 0
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.stored >= self.budget
+    }
+
+    /// Keep `example` for `key` if that key still has room and the budget allows
+    pub fn offer(&mut self, dimension: &'static str, key: &str, example: &ExampleRef) {
+        if !self.is_enabled() || self.is_full() {
+            return;
+        }
+        let per_key = self.per_key;
+        let dim = self.by_dimension.entry(dimension).or_default();
+        if let Some(refs) = dim.get_mut(key) {
+            if refs.len() >= per_key {
+                return;
+            }
+            refs.push(example.clone());
+        } else {
+            dim.insert(key.to_string(), vec![example.clone()]);
+        }
+        self.stored += 1;
+    }
+
+    pub fn merge(&mut self, other: &ExampleStore) {
+        if !self.is_enabled() {
+            self.per_key = other.per_key;
+            self.budget = other.budget;
+        }
+        for (&dimension, keys) in &other.by_dimension {
+            for (key, refs) in keys {
+                for example in refs {
+                    self.offer(dimension, key, example);
+                }
+            }
+        }
+    }
+}
+
+/// One raw log record, prettified and truncated, shown in the report drill-down
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct RecordSample {
+    /// 1-based line number in the input
+    pub line: u64,
+    pub bid: bool,
+    pub record: String,
+}
+
+impl RecordSample {
+    pub fn new(record: &LogRecord, bid: bool) -> Self {
+        let value = serde_json::json!({
+            "request": record.request,
+            "response": record.response,
+        });
+        let mut text = serde_json::to_string_pretty(&value).unwrap_or_default();
+        if let Some((cut, _)) = text.char_indices().nth(MAX_SAMPLE_CHARS) {
+            text.truncate(cut);
+            text.push_str("\n... (truncated)");
+        }
+        Self {
+            line: record.line_no,
+            bid,
+            record: text,
+        }
+    }
+}
+
+/// Up to `per_format` raw records for every raw format (--samples). Records
+/// without a bid are preferred, since those show why a format goes unbid.
+/// Once `budget` records are stored, new formats get none.
+#[derive(Debug, Default, Clone)]
+pub struct SampleStore {
+    per_format: usize,
+    budget: usize,
+    stored: usize,
+    pub by_format: BTreeMap<(u32, u32), Vec<RecordSample>>,
+}
+
+impl SampleStore {
+    pub fn new(per_format: usize, budget: usize) -> Self {
+        Self {
+            per_format,
+            budget,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.per_format > 0
+    }
+
+    /// Keep the record built by `sample` if `format` has room for it, or if
+    /// it has no bid and can replace a kept record that had one
+    pub fn offer(&mut self, format: (u32, u32), bid: bool, sample: impl FnOnce() -> RecordSample) {
+        if !self.is_enabled() {
+            return;
+        }
+        let kept = self.by_format.get(&format).map_or(0, Vec::len);
+        if kept < self.per_format {
+            if self.stored >= self.budget {
+                return;
+            }
+            self.by_format.entry(format).or_default().push(sample());
+            self.stored += 1;
+        } else if !bid {
+            let samples = self.by_format.get_mut(&format).expect("format has samples");
+            if let Some(slot) = samples.iter_mut().find(|s| s.bid) {
+                *slot = sample();
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &SampleStore) {
+        if !self.is_enabled() {
+            self.per_format = other.per_format;
+            self.budget = other.budget;
+        }
+        for (&format, samples) in &other.by_format {
+            for sample in samples {
+                self.offer(format, sample.bid, || sample.clone());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// Examples stored for one key, if any
+    pub fn examples_for<'a>(
+        store: &'a ExampleStore,
+        dimension: &str,
+        key: &str,
+    ) -> &'a [ExampleRef] {
+        store
+            .by_dimension
+            .get(dimension)
+            .and_then(|keys| keys.get(key))
+            .map(Vec::as_slice)
+            .unwrap_or(&[])
+    }
+
+    fn example(line: u64) -> ExampleRef {
+        ExampleRef {
+            line,
+            id: format!("req-{line}"),
+        }
+    }
+
+    #[test]
+    fn test_per_key_limit_and_budget() {
+        let mut store = ExampleStore::new(2, 3);
+        for line in 1..=5 {
+            store.offer("raw_format", "300x250", &example(line));
+        }
+        store.offer("raw_format", "320x50", &example(6));
+        store.offer("raw_format", "728x90", &example(7)); // over budget
+
+        assert_eq!(
+            examples_for(&store, "raw_format", "300x250"),
+            &[example(1), example(2)]
+        );
+        assert_eq!(examples_for(&store, "raw_format", "320x50"), &[example(6)]);
+        assert!(examples_for(&store, "raw_format", "728x90").is_empty());
+        assert!(store.is_full());
+    }
+
+    #[test]
+    fn test_samples_prefer_records_without_bids() {
+        let sample = |line: u64, bid: bool| RecordSample {
+            line,
+            bid,
+            record: String::new(),
+        };
+        let mut store = SampleStore::new(2, 3);
+        for (line, bid) in [(1, true), (2, true), (3, false), (4, false), (5, false)] {
+            store.offer((300, 250), bid, || sample(line, bid));
+        }
+        store.offer((320, 50), true, || sample(6, true));
+        store.offer((728, 90), false, || sample(7, false)); // over budget
+
+        let lines = |format| {
+            store
+                .by_format
+                .get(&format)
+                .map_or(vec![], |s| s.iter().map(|s| s.line).collect())
+        };
+        assert_eq!(lines((300, 250)), vec![3, 4]);
+        assert_eq!(lines((320, 50)), vec![6]);
+        assert!(lines((728, 90)).is_empty());
+    }
+
+    #[test]
+    fn test_record_sample_is_truncated() {
+        let record = LogRecord {
+            request: serde_json::json!({ "id": "r1", "site": { "page": "x".repeat(MAX_SAMPLE_CHARS * 2) } }),
+            response: serde_json::Value::Null,
+            ts_ms: None,
+            outcome: serde_json::Value::Null,
+            http_status: serde_json::Value::Null,
+            line_no: 42,
+        };
+        let sample = RecordSample::new(&record, false);
+        assert_eq!(sample.line, 42);
+        assert!(sample.record.contains("\"id\": \"r1\""));
+        assert!(sample.record.ends_with("... (truncated)"));
+        assert!(sample.record.chars().count() < MAX_SAMPLE_CHARS + 20);
+    }
+
+    #[test]
+    fn test_disabled_store_keeps_nothing() {
+        let mut store = ExampleStore::default();
+        store.offer("ssp", "fake_ssp", &example(1));
+        assert!(store.by_dimension.is_empty());
+    }
+}
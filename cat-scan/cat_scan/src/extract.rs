@@ -0,0 +1,275 @@
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+use crate::stats::FormatStats;
+
+/// Distinct values tracked per custom dimension; the rest fold into "(other)"
+const MAX_VALUES_PER_DIMENSION: usize = 10_000;
+
+/// One step of a path like `imp[0].ext.gpid`
+#[derive(Debug, Clone, PartialEq)]
+enum Step {
+    Field(String),
+    Index(usize),
+    /// `[*]`: every element of an array
+    All,
+}
+
+/// A named custom dimension: a JSONPath-like expression evaluated against
+/// the bid request. Supports `.field`, `[N]`, `[*]` and `['quoted.field']`;
+/// a leading `$` or `$.` is optional.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExtractRule {
+    pub name: String,
+    pub path: String,
+    steps: Vec<Step>,
+}
+
+impl ExtractRule {
+    /// Parse `name=path`, e.g. `gpid=imp[0].ext.gpid`
+    pub fn parse(spec: &str) -> Result<Self> {
+        let (name, path) = spec
+            .split_once('=')
+            .with_context(|| format!("invalid extract rule '{spec}', expected name=path"))?;
+        let (name, path) = (name.trim(), path.trim());
+        if name.is_empty() {
+            bail!("invalid extract rule '{spec}': empty name");
+        }
+        Ok(Self {
+            name: name.to_string(),
+            path: path.to_string(),
+            steps: parse_path(path).with_context(|| format!("invalid path in rule '{spec}'"))?,
+        })
+    }
+
+    /// A rule for a bare path, named after it (e.g. --ts-field)
+    pub fn for_path(path: &str) -> Result<Self> {
+        let path = path.trim();
+        let steps = parse_path(path).with_context(|| format!("invalid path '{path}'"))?;
+        if steps.is_empty() {
+            bail!("empty path '{path}'");
+        }
+        Ok(Self {
+            name: path.to_string(),
+            path: path.to_string(),
+            steps,
+        })
+    }
+
+    /// Load one `name=path` rule per line; blank lines and `#` comments are skipped
+    pub fn load_file(path: &str) -> Result<Vec<Self>> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read extract config {}", path))?;
+        text.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(Self::parse)
+            .collect()
+    }
+
+    /// Scalar values at this path (strings as-is, numbers/bools stringified)
+    pub fn values(&self, request: &Value) -> Vec<String> {
+        let mut current = vec![request];
+        for step in &self.steps {
+            current = current
+                .into_iter()
+                .flat_map(|v| -> Vec<&Value> {
+                    match step {
+                        Step::Field(name) => v.get(name).into_iter().collect(),
+                        Step::Index(i) => v.get(i).into_iter().collect(),
+                        Step::All => v.as_array().map(|a| a.iter().collect()).unwrap_or_default(),
+                    }
+                })
+                .collect();
+        }
+        current
+            .into_iter()
+            .filter_map(|v| match v {
+                Value::String(s) => Some(s.clone()),
+                Value::Number(n) => Some(n.to_string()),
+                Value::Bool(b) => Some(b.to_string()),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+fn parse_path(path: &str) -> Result<Vec<Step>> {
+    let path = path.strip_prefix('$').unwrap_or(path);
+    let mut steps = Vec::new();
+    let mut chars = path.chars().peekable();
+    let mut field = String::new();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '.' => {
+                if !field.is_empty() {
+                    steps.push(Step::Field(std::mem::take(&mut field)));
+                }
+            }
+            '[' => {
+                if !field.is_empty() {
+                    steps.push(Step::Field(std::mem::take(&mut field)));
+                }
+                let mut inner = String::new();
+                loop {
+                    match chars.next() {
+                        Some(']') => break,
+                        Some(c) => inner.push(c),
+                        None => bail!("unclosed '[' in '{path}'"),
+                    }
+                }
+                let inner = inner.trim();
+                let step = if inner == "*" {
+                    Step::All
+                } else if let Some(quoted) = inner
+                    .strip_prefix('\'')
+                    .and_then(|s| s.strip_suffix('\''))
+                    .or_else(|| inner.strip_prefix('"').and_then(|s| s.strip_suffix('"')))
+                {
+                    Step::Field(quoted.to_string())
+                } else {
+                    Step::Index(
+                        inner
+                            .parse()
+                            .with_context(|| format!("invalid index '[{inner}]' in '{path}'"))?,
+                    )
+                };
+                steps.push(step);
+            }
+            c => field.push(c),
+        }
+    }
+    if !field.is_empty() {
+        steps.push(Step::Field(field));
+    }
+    if steps.is_empty() {
+        bail!("empty path");
+    }
+    Ok(steps)
+}
+
+/// Key for custom dimension aggregation
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub struct ExtractKey {
+    pub name: String,
+    pub ssp: String,
+    pub value: String,
+}
+
+/// Per-value stats for every custom dimension (--extract)
+#[derive(Debug, Default, Clone)]
+pub struct ExtractStats {
+    pub rules: Vec<ExtractRule>,
+    pub by_value: BTreeMap<ExtractKey, FormatStats>,
+    /// Distinct values seen per dimension name (cardinality guard)
+    distinct: BTreeMap<String, usize>,
+}
+
+impl ExtractStats {
+    pub fn new(rules: Vec<ExtractRule>) -> Self {
+        Self {
+            rules,
+            ..Self::default()
+        }
+    }
+
+    /// Count `request` once per value each rule yields, with `update`
+    /// adding the record to a value's stats
+    pub fn observe(&mut self, request: &Value, ssp: &str, update: impl Fn(&mut FormatStats)) {
+        for rule in &self.rules {
+            for value in rule.values(request) {
+                let mut key = ExtractKey {
+                    name: rule.name.clone(),
+                    ssp: ssp.to_string(),
+                    value,
+                };
+                if !self.by_value.contains_key(&key) {
+                    let distinct = self.distinct.entry(rule.name.clone()).or_default();
+                    if *distinct >= MAX_VALUES_PER_DIMENSION {
+                        key.value = "(other)".to_string();
+                    } else {
+                        *distinct += 1;
+                    }
+                }
+                update(self.by_value.entry(key).or_default());
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &ExtractStats) {
+        if self.rules.is_empty() {
+            self.rules = other.rules.clone();
+        }
+        for (key, stats) in &other.by_value {
+            if !self.by_value.contains_key(key) {
+                *self.distinct.entry(key.name.clone()).or_default() += 1;
+            }
+            self.by_value.entry(key.clone()).or_default().merge(stats);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_evaluate_paths() {
+        let request = serde_json::json!({
+            "imp": [
+                { "ext": { "gpid": "/123/home", "data.pbadslot": "slot-a" } },
+                { "ext": { "gpid": "/123/article" } }
+            ],
+            "site": { "ext": { "amp": 1 } }
+        });
+
+        let gpid = ExtractRule::parse("gpid=imp[0].ext.gpid").unwrap();
+        assert_eq!(gpid.values(&request), vec!["/123/home"]);
+
+        let all = ExtractRule::parse("gpid = $.imp[*].ext.gpid").unwrap();
+        assert_eq!(all.values(&request), vec!["/123/home", "/123/article"]);
+
+        let quoted = ExtractRule::parse("slot=imp[0].ext['data.pbadslot']").unwrap();
+        assert_eq!(quoted.values(&request), vec!["slot-a"]);
+
+        let amp = ExtractRule::parse("amp=site.ext.amp").unwrap();
+        assert_eq!(amp.values(&request), vec!["1"]);
+
+        assert!(ExtractRule::parse("site.ext.amp").is_err());
+        assert!(ExtractRule::parse("x=imp[a]").is_err());
+        assert!(ExtractRule::parse("x=imp[0").is_err());
+    }
+
+    #[test]
+    fn test_observe_counts_per_value() {
+        let mut stats = ExtractStats::new(vec![ExtractRule::parse("amp=site.ext.amp").unwrap()]);
+        let amp = serde_json::json!({ "site": { "ext": { "amp": 1 } } });
+        let count = |bid: Option<f64>| {
+            move |entry: &mut FormatStats| {
+                entry.requests += 1;
+                if let Some(price) = bid {
+                    entry.add_bid(price);
+                }
+            }
+        };
+        stats.observe(&amp, "ssp_a", count(Some(2.0)));
+        stats.observe(&amp, "ssp_a", count(None));
+        stats.observe(&serde_json::json!({}), "ssp_a", count(Some(1.0)));
+
+        let key = ExtractKey {
+            name: "amp".to_string(),
+            ssp: "ssp_a".to_string(),
+            value: "1".to_string(),
+        };
+        let mut expected = FormatStats {
+            requests: 2,
+            ..Default::default()
+        };
+        expected.add_bid(2.0);
+        assert_eq!(stats.by_value.len(), 1);
+        assert_eq!(stats.by_value[&key], expected);
+    }
+}
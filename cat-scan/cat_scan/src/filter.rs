@@ -0,0 +1,433 @@
+use anyhow::{bail, Context, Result};
+
+use crate::bids::ResponseBids;
+use crate::extract::ExtractRule;
+use crate::stats::LogRecord;
+
+/// Short names for common request fields, as paths into the bid request.
+/// A name with several paths matches any of them.
+const FIELD_ALIASES: &[(&str, &[&str])] = &[
+    ("ssp", &["source.ssp"]),
+    ("w", &["imp[0].banner.w"]),
+    ("h", &["imp[0].banner.h"]),
+    ("country", &["device.geo.country"]),
+    ("region", &["device.geo.region"]),
+    ("publisher", &["site.publisher.id", "app.publisher.id"]),
+    ("domain", &["site.domain"]),
+    ("bundle", &["app.bundle"]),
+    ("os", &["device.os"]),
+    ("devicetype", &["device.devicetype"]),
+    ("bidfloor", &["imp[0].bidfloor"]),
+    ("tmax", &["tmax"]),
+    ("test", &["test"]),
+];
+
+/// Where a filter field reads its values from
+#[derive(Debug, Clone)]
+enum Field {
+    /// Paths into the request (an alias or a path like `imp[0].ext.gpid`)
+    Request(Vec<ExtractRule>),
+    /// A path into the response, written `response.seatbid[0].seat`
+    Response(ExtractRule),
+    /// Whether the response carried a bid
+    Bid,
+    /// Highest bid price; no value without a bid
+    Price,
+}
+
+impl Field {
+    fn parse(name: &str) -> Result<Self> {
+        if let Some((_, paths)) = FIELD_ALIASES.iter().find(|(alias, _)| *alias == name) {
+            return paths
+                .iter()
+                .map(|path| ExtractRule::for_path(path))
+                .collect::<Result<_>>()
+                .map(Field::Request);
+        }
+        Ok(match name {
+            "bid" => Field::Bid,
+            "price" => Field::Price,
+            _ => match name.strip_prefix("response.") {
+                Some(path) => Field::Response(ExtractRule::for_path(path)?),
+                None => Field::Request(vec![ExtractRule::for_path(
+                    name.strip_prefix("request.").unwrap_or(name),
+                )?]),
+            },
+        })
+    }
+
+    /// Scalar values of this field in one record (strings as-is,
+    /// numbers/bools stringified)
+    fn values(&self, record: &LogRecord) -> Vec<String> {
+        match self {
+            Field::Request(rules) => rules
+                .iter()
+                .flat_map(|rule| rule.values(&record.request))
+                .collect(),
+            Field::Response(rule) => rule.values(&record.response),
+            Field::Bid => {
+                let bids = ResponseBids::from_response(&record.response);
+                vec![(bids.count > 0).to_string()]
+            }
+            Field::Price => {
+                let bids = ResponseBids::from_response(&record.response);
+                if bids.count > 0 {
+                    vec![bids.highest_price.to_string()]
+                } else {
+                    Vec::new()
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Op {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+impl Op {
+    fn holds(self, ordering: std::cmp::Ordering) -> bool {
+        use std::cmp::Ordering::*;
+        match self {
+            Op::Eq => ordering == Equal,
+            Op::Ne => ordering != Equal,
+            Op::Lt => ordering == Less,
+            Op::Le => ordering != Greater,
+            Op::Gt => ordering == Greater,
+            Op::Ge => ordering != Less,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Literal {
+    Str(String),
+    /// Numbers, and true/false as 1/0
+    Num(f64),
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Or(Box<Expr>, Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Not(Box<Expr>),
+    Compare(Field, Op, Literal),
+    /// A bare field: holds when it has a value other than "", 0 or false
+    Present(Field),
+}
+
+impl Expr {
+    fn eval(&self, record: &LogRecord) -> bool {
+        match self {
+            Expr::Or(a, b) => a.eval(record) || b.eval(record),
+            Expr::And(a, b) => a.eval(record) && b.eval(record),
+            Expr::Not(e) => !e.eval(record),
+            Expr::Compare(field, op, literal) => {
+                field.values(record).iter().any(|value| match literal {
+                    Literal::Str(s) => op.holds(value.as_str().cmp(s.as_str())),
+                    Literal::Num(n) => {
+                        number(value).is_some_and(|v| v.partial_cmp(n).is_some_and(|o| op.holds(o)))
+                    }
+                })
+            }
+            Expr::Present(field) => field
+                .values(record)
+                .iter()
+                .any(|v| !v.is_empty() && number(v) != Some(0.0)),
+        }
+    }
+}
+
+/// A field value as a number; true/false count as 1/0
+fn number(value: &str) -> Option<f64> {
+    match value {
+        "true" => Some(1.0),
+        "false" => Some(0.0),
+        _ => value.parse().ok(),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Ident(String),
+    Str(String),
+    Num(f64),
+    Op(Op),
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+}
+
+/// Split a filter into tokens, each with its byte offset for errors
+fn tokenize(text: &str) -> Result<Vec<(usize, Token)>> {
+    let mut tokens = Vec::new();
+    let mut chars = text.char_indices().peekable();
+    while let Some(&(at, c)) = chars.peek() {
+        let next = text[at + c.len_utf8()..].chars().next();
+        let token = match (c, next) {
+            _ if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            ('&', Some('&'))
+            | ('|', Some('|'))
+            | ('=', Some('='))
+            | ('!', Some('='))
+            | ('<', Some('='))
+            | ('>', Some('=')) => {
+                chars.next();
+                chars.next();
+                match c {
+                    '&' => Token::And,
+                    '|' => Token::Or,
+                    '=' => Token::Op(Op::Eq),
+                    '!' => Token::Op(Op::Ne),
+                    '<' => Token::Op(Op::Le),
+                    _ => Token::Op(Op::Ge),
+                }
+            }
+            ('<' | '>' | '!' | '(' | ')', _) => {
+                chars.next();
+                match c {
+                    '<' => Token::Op(Op::Lt),
+                    '>' => Token::Op(Op::Gt),
+                    '!' => Token::Not,
+                    '(' => Token::Open,
+                    _ => Token::Close,
+                }
+            }
+            ('"' | '\'', _) => {
+                chars.next();
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, escaped)) => value.push(escaped),
+                            None => bail!("unterminated string at {at}"),
+                        },
+                        Some((_, end)) if end == c => break,
+                        Some((_, ch)) => value.push(ch),
+                        None => bail!("unterminated string at {at}"),
+                    }
+                }
+                Token::Str(value)
+            }
+            _ if c.is_ascii_digit() || c == '-' || c == '.' => {
+                let mut end = at;
+                while let Some(&(i, ch)) = chars.peek() {
+                    if !(ch.is_ascii_alphanumeric() || ch == '.' || (i == at && ch == '-')) {
+                        break;
+                    }
+                    end = i + ch.len_utf8();
+                    chars.next();
+                }
+                let number = &text[at..end];
+                Token::Num(
+                    number
+                        .parse()
+                        .with_context(|| format!("invalid number '{number}' at {at}"))?,
+                )
+            }
+            _ if c.is_alphabetic() || c == '_' || c == '$' => {
+                // A name or path; brackets may hold quoted keys with dots
+                let mut end = at;
+                let mut in_brackets = false;
+                while let Some(&(i, ch)) = chars.peek() {
+                    let name_char =
+                        ch.is_alphanumeric() || matches!(ch, '_' | '$' | '.' | '-' | '[');
+                    if !(in_brackets || name_char) {
+                        break;
+                    }
+                    in_brackets = match ch {
+                        '[' => true,
+                        ']' => false,
+                        _ => in_brackets,
+                    };
+                    end = i + ch.len_utf8();
+                    chars.next();
+                }
+                if in_brackets {
+                    bail!("unclosed '[' at {at}");
+                }
+                match &text[at..end] {
+                    "true" => Token::Num(1.0),
+                    "false" => Token::Num(0.0),
+                    name => Token::Ident(name.to_string()),
+                }
+            }
+            ('=', _) => bail!("unexpected '=' at {at} (use == to compare)"),
+            ('&' | '|', _) => bail!("unexpected '{c}' at {at} (use && or ||)"),
+            _ => bail!("unexpected '{c}' at {at}"),
+        };
+        tokens.push((at, token));
+    }
+    Ok(tokens)
+}
+
+/// Recursive descent over the tokens: || binds loosest, then &&, then !
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    pos: usize,
+    len: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos).map(|(_, t)| t)
+    }
+
+    /// Byte offset of the next token, or the end of the text
+    fn offset(&self) -> usize {
+        self.tokens.get(self.pos).map_or(self.len, |(at, _)| *at)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).map(|(_, t)| t.clone());
+        self.pos += 1;
+        token
+    }
+
+    fn or(&mut self) -> Result<Expr> {
+        let mut expr = self.and()?;
+        while self.peek() == Some(&Token::Or) {
+            self.pos += 1;
+            expr = Expr::Or(Box::new(expr), Box::new(self.and()?));
+        }
+        Ok(expr)
+    }
+
+    fn and(&mut self) -> Result<Expr> {
+        let mut expr = self.unary()?;
+        while self.peek() == Some(&Token::And) {
+            self.pos += 1;
+            expr = Expr::And(Box::new(expr), Box::new(self.unary()?));
+        }
+        Ok(expr)
+    }
+
+    fn unary(&mut self) -> Result<Expr> {
+        let at = self.offset();
+        match self.next() {
+            Some(Token::Not) => Ok(Expr::Not(Box::new(self.unary()?))),
+            Some(Token::Open) => {
+                let expr = self.or()?;
+                let at = self.offset();
+                if self.next() != Some(Token::Close) {
+                    bail!("expected ')' at {at}");
+                }
+                Ok(expr)
+            }
+            Some(Token::Ident(name)) => {
+                let field =
+                    Field::parse(&name).with_context(|| format!("invalid field at {at}"))?;
+                let Some(Token::Op(op)) = self.peek().cloned() else {
+                    return Ok(Expr::Present(field));
+                };
+                self.pos += 1;
+                let at = self.offset();
+                let literal = match self.next() {
+                    Some(Token::Str(s)) => Literal::Str(s),
+                    Some(Token::Num(n)) => Literal::Num(n),
+                    _ => bail!("expected a string or number at {at}"),
+                };
+                Ok(Expr::Compare(field, op, literal))
+            }
+            _ => bail!("expected a field, '!' or '(' at {at}"),
+        }
+    }
+}
+
+/// A --filter expression: records it doesn't match are dropped before
+/// aggregation. Comparisons hold when any value of the field matches, so a
+/// missing field matches nothing, `!=` included.
+#[derive(Debug, Clone)]
+pub struct RecordFilter {
+    pub text: String,
+    expr: Expr,
+}
+
+impl RecordFilter {
+    /// Parse e.g. `ssp == "fake_ssp" && w >= 300 && country == "SE"`
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            pos: 0,
+            len: text.len(),
+        };
+        let expr = parser.or()?;
+        if parser.pos < parser.tokens.len() {
+            bail!("unexpected input at {}", parser.offset());
+        }
+        Ok(Self {
+            text: text.to_string(),
+            expr,
+        })
+    }
+
+    pub fn matches(&self, record: &LogRecord) -> bool {
+        self.expr.eval(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::tests::make_record;
+    use serde_json::json;
+
+    fn record(ssp: &str, w: u32, country: Option<&str>, bid: bool) -> LogRecord {
+        let mut record = make_record(w, 250, bid, 1.5);
+        record.request["source"] = json!({ "ssp": ssp });
+        if let Some(country) = country {
+            record.request["device"] = json!({ "geo": { "country": country } });
+        }
+        record
+    }
+
+    #[test]
+    fn test_filter_matches() {
+        let filter =
+            RecordFilter::parse(r#"ssp == "fake_ssp" && w >= 300 && country == "SE""#).unwrap();
+        assert!(filter.matches(&record("fake_ssp", 300, Some("SE"), false)));
+        assert!(!filter.matches(&record("fake_ssp", 160, Some("SE"), false)));
+        assert!(!filter.matches(&record("other", 300, Some("SE"), false)));
+        assert!(!filter.matches(&record("fake_ssp", 300, None, false)));
+
+        let filter = RecordFilter::parse("!(country == 'SE' || country == 'NO') && bid").unwrap();
+        assert!(filter.matches(&record("a", 300, Some("DE"), true)));
+        assert!(!filter.matches(&record("a", 300, Some("NO"), true)));
+        assert!(!filter.matches(&record("a", 300, Some("DE"), false)));
+        // A missing field matches no comparison, != included
+        let filter = RecordFilter::parse("country != 'SE'").unwrap();
+        assert!(!filter.matches(&record("a", 300, None, false)));
+
+        let filter = RecordFilter::parse("price > 1 && imp[*].banner.w < 400").unwrap();
+        assert!(filter.matches(&record("a", 300, None, true)));
+        assert!(!filter.matches(&record("a", 300, None, false)));
+        let filter = RecordFilter::parse("response.seatbid[0].bid[0].price == 1.5").unwrap();
+        assert!(filter.matches(&record("a", 300, None, true)));
+    }
+
+    #[test]
+    fn test_filter_errors() {
+        for (text, error) in [
+            ("ssp = 'a'", "use == to compare"),
+            ("ssp == 'a' & w > 1", "use && or ||"),
+            ("ssp ==", "expected a string or number at 6"),
+            ("(ssp == 'a'", "expected ')'"),
+            ("ssp == 'a' w", "unexpected input at 11"),
+            ("ssp == 'a", "unterminated string"),
+        ] {
+            let message = RecordFilter::parse(text).unwrap_err().to_string();
+            assert!(message.contains(error), "{text}: {message}");
+        }
+    }
+}
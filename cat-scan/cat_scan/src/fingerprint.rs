@@ -0,0 +1,351 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+    hash::{Hash, Hasher},
+    io::Write,
+};
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::metadata::{create_csv, ScanMetadata};
+use crate::stats::scale_count;
+
+/// Stop remembering new fingerprints past this many (memory guard)
+const MAX_TRACKED: usize = 5_000_000;
+
+/// Keys dropped anywhere in a request before fingerprinting
+const TIMESTAMP_KEYS: &[&str] = &["ts", "ts_ms", "timestamp", "time"];
+
+/// Two requests for the same impression this close together (ms) are
+/// near-duplicates
+pub const NEAR_WINDOW_MS: u64 = 2_000;
+
+fn hash_str(value: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn strip_timestamps(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            map.retain(|key, _| !TIMESTAMP_KEYS.contains(&key.as_str()));
+            map.values_mut().for_each(strip_timestamps);
+        }
+        Value::Array(items) => items.iter_mut().for_each(strip_timestamps),
+        _ => {}
+    }
+}
+
+/// Hash of the request without its per-auction ids (`id`, `imp.id`,
+/// `source.tid`) and timestamps: equal for byte-identical resends
+pub fn exact_fingerprint(request: &Value) -> u64 {
+    let mut request = request.clone();
+    if let Some(map) = request.as_object_mut() {
+        map.remove("id");
+    }
+    if let Some(imps) = request["imp"].as_array_mut() {
+        for imp in imps.iter_mut().filter_map(Value::as_object_mut) {
+            imp.remove("id");
+        }
+    }
+    if let Some(source) = request["source"].as_object_mut() {
+        source.remove("tid");
+    }
+    strip_timestamps(&mut request);
+    hash_str(&request.to_string())
+}
+
+/// Hash of what identifies the impression: the page or app, the device
+/// (ua, ip, ifa) and the banner sizes. None without an ip or ifa, which
+/// would lump different users together.
+pub fn impression_key(request: &Value) -> Option<u64> {
+    let device = &request["device"];
+    let str_at = |value: &Value| value.as_str().unwrap_or("").to_string();
+    let ip = device["ip"].as_str().or_else(|| device["ipv6"].as_str());
+    let ifa = device["ifa"].as_str();
+    if ip.is_none() && ifa.is_none() {
+        return None;
+    }
+    let placement = if request["app"].is_object() {
+        str_at(&request["app"]["bundle"])
+    } else {
+        let page = str_at(&request["site"]["page"]);
+        if page.is_empty() {
+            str_at(&request["site"]["domain"])
+        } else {
+            page
+        }
+    };
+    let sizes: Vec<String> = request["imp"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|imp| format!("{}x{}", imp["banner"]["w"], imp["banner"]["h"]))
+        .collect();
+    let key = [
+        placement,
+        str_at(&device["ua"]),
+        ip.unwrap_or("").to_string(),
+        ifa.unwrap_or("").to_string(),
+        sizes.join(","),
+    ];
+    Some(hash_str(&key.join("\u{1f}")))
+}
+
+/// Repeated requests for one SSP
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SspFingerprints {
+    pub requests: u64,
+    /// Identical, ids and timestamps aside, to an earlier request
+    pub exact_duplicates: u64,
+    /// Not identical, but for the same impression as a request less than
+    /// NEAR_WINDOW_MS earlier
+    pub near_duplicates: u64,
+    /// Duplicates (either kind) whose earlier request came from another SSP
+    pub cross_ssp: u64,
+}
+
+/// Byte-identical and near-identical requests per SSP (--fingerprint):
+/// SSP-side duplication, or the same impression resold through several paths
+#[derive(Debug, Default, Clone)]
+pub struct RequestFingerprints {
+    pub by_ssp: BTreeMap<String, SspFingerprints>,
+    /// Exact fingerprint -> hash of the SSP that first sent it
+    exact: HashMap<u64, u64>,
+    /// Impression key -> (latest timestamp, SSP hash)
+    near: HashMap<u64, (u64, u64)>,
+    /// SSP hash -> name
+    ssps: HashMap<u64, String>,
+}
+
+impl RequestFingerprints {
+    pub fn observe(&mut self, request: &Value, ssp: &str, ts_ms: Option<u64>) {
+        let ssp_hash = hash_str(ssp);
+        self.ssps.entry(ssp_hash).or_insert_with(|| ssp.to_string());
+        let counts = self.by_ssp.entry(ssp.to_string()).or_default();
+        counts.requests += 1;
+
+        let fingerprint = exact_fingerprint(request);
+        let exact_from = self.exact.get(&fingerprint).copied();
+        if let Some(first) = exact_from {
+            counts.exact_duplicates += 1;
+            if first != ssp_hash {
+                counts.cross_ssp += 1;
+            }
+        } else if self.exact.len() < MAX_TRACKED {
+            self.exact.insert(fingerprint, ssp_hash);
+        }
+
+        // Near-duplicates need timestamps to tell a resend from a revisit
+        let (Some(ts), Some(key)) = (ts_ms, impression_key(request)) else {
+            return;
+        };
+        if let Some(&(last_ts, last_ssp)) = self.near.get(&key) {
+            if exact_from.is_none() && ts.abs_diff(last_ts) < NEAR_WINDOW_MS {
+                counts.near_duplicates += 1;
+                if last_ssp != ssp_hash {
+                    counts.cross_ssp += 1;
+                }
+            }
+        }
+        if self.near.len() < MAX_TRACKED || self.near.contains_key(&key) {
+            self.near.insert(key, (ts, ssp_hash));
+        }
+    }
+
+    /// Fold in a later stretch of the scan. Exact repeats across the two
+    /// count as duplicates of the later stretch's SSP; near-duplicates are
+    /// only found within a stretch.
+    pub fn merge(&mut self, other: &RequestFingerprints) {
+        for (ssp, theirs) in &other.by_ssp {
+            let ours = self.by_ssp.entry(ssp.clone()).or_default();
+            ours.requests += theirs.requests;
+            ours.exact_duplicates += theirs.exact_duplicates;
+            ours.near_duplicates += theirs.near_duplicates;
+            ours.cross_ssp += theirs.cross_ssp;
+        }
+        for (hash, name) in &other.ssps {
+            self.ssps.entry(*hash).or_insert_with(|| name.clone());
+        }
+        for (fingerprint, ssp_hash) in &other.exact {
+            match self.exact.get(fingerprint) {
+                Some(first) => {
+                    if let Some(counts) = self.by_ssp.get_mut(&self.ssps[ssp_hash]) {
+                        counts.exact_duplicates += 1;
+                        if first != ssp_hash {
+                            counts.cross_ssp += 1;
+                        }
+                    }
+                }
+                None if self.exact.len() < MAX_TRACKED => {
+                    self.exact.insert(*fingerprint, *ssp_hash);
+                }
+                None => {}
+            }
+        }
+        for (key, &(ts, ssp_hash)) in &other.near {
+            let latest = self.near.get(key).map_or(0, |(last_ts, _)| *last_ts);
+            let tracked = self.near.contains_key(key) || self.near.len() < MAX_TRACKED;
+            if tracked && ts >= latest {
+                self.near.insert(*key, (ts, ssp_hash));
+            }
+        }
+    }
+
+    /// Multiply counts by `factor` (extrapolating from a sample)
+    pub fn scale(&mut self, factor: f64) {
+        for counts in self.by_ssp.values_mut() {
+            for count in [
+                &mut counts.requests,
+                &mut counts.exact_duplicates,
+                &mut counts.near_duplicates,
+                &mut counts.cross_ssp,
+            ] {
+                scale_count(count, factor);
+            }
+        }
+    }
+
+    /// Exact and near duplicates across all SSPs
+    pub fn total_duplicates(&self) -> (u64, u64) {
+        self.by_ssp.values().fold((0, 0), |(exact, near), c| {
+            (exact + c.exact_duplicates, near + c.near_duplicates)
+        })
+    }
+
+    /// One row per SSP, highest duplicate share first
+    pub fn summarize(&self) -> Vec<FingerprintSummary> {
+        let rate = |n: u64, d: u64| if d == 0 { 0.0 } else { n as f64 / d as f64 };
+        let mut rows: Vec<FingerprintSummary> = self
+            .by_ssp
+            .iter()
+            .map(|(ssp, c)| FingerprintSummary {
+                ssp: ssp.clone(),
+                requests: c.requests,
+                exact_duplicates: c.exact_duplicates,
+                exact_rate: rate(c.exact_duplicates, c.requests),
+                near_duplicates: c.near_duplicates,
+                near_rate: rate(c.near_duplicates, c.requests),
+                cross_ssp: c.cross_ssp,
+            })
+            .collect();
+        rows.sort_by(|a, b| {
+            (b.exact_rate + b.near_rate)
+                .total_cmp(&(a.exact_rate + a.near_rate))
+                .then_with(|| b.requests.cmp(&a.requests))
+                .then_with(|| a.ssp.cmp(&b.ssp))
+        });
+        rows
+    }
+}
+
+/// Duplicate and near-duplicate requests for one SSP
+#[derive(serde::Serialize)]
+pub struct FingerprintSummary {
+    pub ssp: String,
+    pub requests: u64,
+    pub exact_duplicates: u64,
+    pub exact_rate: f64,
+    pub near_duplicates: u64,
+    pub near_rate: f64,
+    /// Duplicates of a request first sent by another SSP
+    pub cross_ssp: u64,
+}
+
+/// Write request_fingerprints.csv (repeated requests per SSP)
+pub fn write_fingerprints_csv(
+    out_dir: &str,
+    rows: &[FingerprintSummary],
+    meta: &ScanMetadata,
+) -> Result<()> {
+    let path = format!("{}/request_fingerprints.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    writeln!(
+        csv,
+        "ssp,requests,exact_duplicates,exact_rate,near_duplicates,near_rate,cross_ssp"
+    )?;
+    for r in rows {
+        writeln!(
+            csv,
+            "{},{},{},{:.4},{},{:.4},{}",
+            r.ssp,
+            r.requests,
+            r.exact_duplicates,
+            r.exact_rate,
+            r.near_duplicates,
+            r.near_rate,
+            r.cross_ssp
+        )?;
+    }
+    eprintln!("Request fingerprints written to: {}", path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn request(id: &str, ip: &str, floor: f64) -> Value {
+        json!({
+            "id": id,
+            "imp": [{ "id": "1", "banner": { "w": 300, "h": 250 }, "bidfloor": floor }],
+            "site": { "page": "https://news.example.com/a" },
+            "device": { "ua": "Mozilla/5.0", "ip": ip },
+            "source": { "tid": id },
+            "ext": { "ts": 1 }
+        })
+    }
+
+    #[test]
+    fn test_request_fingerprints() {
+        assert_eq!(
+            exact_fingerprint(&request("a", "1.2.3.4", 0.5)),
+            exact_fingerprint(&request("b", "1.2.3.4", 0.5))
+        );
+        assert_ne!(
+            exact_fingerprint(&request("a", "1.2.3.4", 0.5)),
+            exact_fingerprint(&request("a", "1.2.3.4", 0.6))
+        );
+        assert_eq!(impression_key(&json!({ "site": {} })), None);
+
+        let mut prints = RequestFingerprints::default();
+        prints.observe(&request("a", "1.2.3.4", 0.5), "ssp_a", Some(1_000));
+        // Resent by the same SSP with a new id
+        prints.observe(&request("b", "1.2.3.4", 0.5), "ssp_a", Some(1_200));
+        // The same impression resold through ssp_b with another floor
+        prints.observe(&request("c", "1.2.3.4", 0.8), "ssp_b", Some(1_500));
+        // Same page and device, but long after: a revisit
+        prints.observe(&request("d", "1.2.3.4", 0.9), "ssp_b", Some(60_000));
+        // Another user
+        prints.observe(&request("e", "5.6.7.8", 0.7), "ssp_b", Some(60_100));
+
+        let a = &prints.by_ssp["ssp_a"];
+        assert_eq!(
+            (a.requests, a.exact_duplicates, a.near_duplicates),
+            (2, 1, 0)
+        );
+        let b = &prints.by_ssp["ssp_b"];
+        assert_eq!(
+            (
+                b.requests,
+                b.exact_duplicates,
+                b.near_duplicates,
+                b.cross_ssp
+            ),
+            (3, 0, 1, 1)
+        );
+        assert_eq!(prints.total_duplicates(), (1, 1));
+
+        let mut later = RequestFingerprints::default();
+        later.observe(&request("f", "5.6.7.8", 0.7), "ssp_a", None);
+        prints.merge(&later);
+        let a = &prints.by_ssp["ssp_a"];
+        assert_eq!((a.requests, a.exact_duplicates, a.cross_ssp), (3, 2, 1));
+
+        let rows = prints.summarize();
+        assert_eq!(rows[0].ssp, "ssp_a");
+        assert!((rows[0].exact_rate - 2.0 / 3.0).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,260 @@
+use std::{cmp::Reverse, collections::BTreeMap, io::Write};
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::metadata::{create_csv, ScanMetadata};
+use crate::stats::scale_count;
+
+/// A bid at least this many times the floor counts as far above it
+pub const FAR_ABOVE_RATIO: f64 = 2.0;
+
+/// Share of far-above-floor bids that flags a format or SSP as overbidding
+pub const OVERBID_SHARE: f64 = 0.8;
+
+/// Bids on floored requests needed before a key can be flagged
+pub const MIN_FLOOR_BIDS: u64 = 20;
+
+/// The floor and bid currencies, "USD" when absent
+fn currency(value: &Value) -> &str {
+    value.as_str().filter(|c| !c.is_empty()).unwrap_or("USD")
+}
+
+/// Bids on requests with a floor, for one format or SSP
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FloorCounts {
+    pub bids: u64,
+    pub sum_floor: f64,
+    pub sum_price: f64,
+    /// Sum of price / floor over the bids
+    pub sum_ratio: f64,
+    /// Bids at FAR_ABOVE_RATIO times the floor or more
+    pub far_above: u64,
+    /// Bids under the floor (the SSP would normally reject them)
+    pub below_floor: u64,
+}
+
+impl FloorCounts {
+    fn add(&mut self, floor: f64, price: f64) {
+        let ratio = price / floor;
+        self.bids += 1;
+        self.sum_floor += floor;
+        self.sum_price += price;
+        self.sum_ratio += ratio;
+        if ratio >= FAR_ABOVE_RATIO {
+            self.far_above += 1;
+        }
+        if price < floor {
+            self.below_floor += 1;
+        }
+    }
+
+    fn merge(&mut self, other: &FloorCounts) {
+        self.bids += other.bids;
+        self.sum_floor += other.sum_floor;
+        self.sum_price += other.sum_price;
+        self.sum_ratio += other.sum_ratio;
+        self.far_above += other.far_above;
+        self.below_floor += other.below_floor;
+    }
+
+    fn scale(&mut self, factor: f64) {
+        for count in [&mut self.bids, &mut self.far_above, &mut self.below_floor] {
+            scale_count(count, factor);
+        }
+        self.sum_floor *= factor;
+        self.sum_price *= factor;
+        self.sum_ratio *= factor;
+    }
+}
+
+/// Bid price relative to `imp.bidfloor` per canonical format and SSP, for
+/// bids in the floor's currency
+#[derive(Debug, Default, Clone)]
+pub struct FloorStats {
+    pub by_format: BTreeMap<(u32, u32), FloorCounts>,
+    pub by_ssp: BTreeMap<String, FloorCounts>,
+}
+
+impl FloorStats {
+    /// Count a response's highest bid against the first imp's floor
+    pub fn observe(
+        &mut self,
+        request: &Value,
+        response: &Value,
+        format: (u32, u32),
+        ssp: &str,
+        price: f64,
+    ) {
+        let imp = &request["imp"][0];
+        let Some(floor) = imp["bidfloor"].as_f64().filter(|f| *f > 0.0) else {
+            return;
+        };
+        if currency(&imp["bidfloorcur"]) != currency(&response["cur"]) {
+            return;
+        }
+        self.by_format.entry(format).or_default().add(floor, price);
+        if !ssp.is_empty() {
+            self.by_ssp
+                .entry(ssp.to_string())
+                .or_default()
+                .add(floor, price);
+        }
+    }
+
+    pub fn merge(&mut self, other: &FloorStats) {
+        for (format, counts) in &other.by_format {
+            self.by_format.entry(*format).or_default().merge(counts);
+        }
+        for (ssp, counts) in &other.by_ssp {
+            self.by_ssp.entry(ssp.clone()).or_default().merge(counts);
+        }
+    }
+
+    /// Multiply counts by `factor` (extrapolating from a sample)
+    pub fn scale(&mut self, factor: f64) {
+        for counts in self.by_format.values_mut().chain(self.by_ssp.values_mut()) {
+            counts.scale(factor);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_format.is_empty()
+    }
+
+    /// Format rows then SSP rows, each with the most bids first
+    pub fn summarize(&self) -> Vec<FloorSummary> {
+        let row = |dimension: &'static str, key: String, c: &FloorCounts| {
+            let per_bid = |sum: f64| {
+                if c.bids == 0 {
+                    0.0
+                } else {
+                    sum / c.bids as f64
+                }
+            };
+            let far_above_share = per_bid(c.far_above as f64);
+            FloorSummary {
+                dimension,
+                key,
+                bids: c.bids,
+                avg_floor: per_bid(c.sum_floor),
+                avg_bid_price: per_bid(c.sum_price),
+                avg_bid_to_floor: per_bid(c.sum_ratio),
+                far_above_share,
+                below_floor: c.below_floor,
+                overbidding: c.bids >= MIN_FLOOR_BIDS && far_above_share >= OVERBID_SHARE,
+            }
+        };
+        let mut formats: Vec<FloorSummary> = self
+            .by_format
+            .iter()
+            .map(|((w, h), c)| row("format", format!("{}x{}", w, h), c))
+            .collect();
+        // Stable, so ties stay in (w, h) order
+        formats.sort_by_key(|r| Reverse(r.bids));
+        let mut ssps: Vec<FloorSummary> = self
+            .by_ssp
+            .iter()
+            .map(|(ssp, c)| row("ssp", ssp.clone(), c))
+            .collect();
+        ssps.sort_by(|a, b| b.bids.cmp(&a.bids).then_with(|| a.key.cmp(&b.key)));
+        formats.extend(ssps);
+        formats
+    }
+}
+
+/// Bid-to-floor efficiency for one format or SSP
+#[derive(serde::Serialize)]
+pub struct FloorSummary {
+    /// "format" or "ssp"
+    pub dimension: &'static str,
+    pub key: String,
+    /// Bids on requests with a floor
+    pub bids: u64,
+    pub avg_floor: f64,
+    pub avg_bid_price: f64,
+    /// Average of price / floor over the bids
+    pub avg_bid_to_floor: f64,
+    /// Share of bids at FAR_ABOVE_RATIO times the floor or more
+    pub far_above_share: f64,
+    pub below_floor: u64,
+    /// Consistently far above the floor: room to shade bids down
+    pub overbidding: bool,
+}
+
+/// Write floor_efficiency.csv (bid-to-floor ratio per format and SSP)
+pub fn write_floor_csv(out_dir: &str, rows: &[FloorSummary], meta: &ScanMetadata) -> Result<()> {
+    let path = format!("{}/floor_efficiency.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    writeln!(
+        csv,
+        "dimension,key,bids,avg_floor,avg_bid_price,avg_bid_to_floor,far_above_share,below_floor,overbidding"
+    )?;
+    for r in rows {
+        writeln!(
+            csv,
+            "{},{},{},{:.4},{:.4},{:.2},{:.4},{},{}",
+            r.dimension,
+            r.key,
+            r.bids,
+            r.avg_floor,
+            r.avg_bid_price,
+            r.avg_bid_to_floor,
+            r.far_above_share,
+            r.below_floor,
+            r.overbidding
+        )?;
+    }
+    eprintln!("Floor efficiency written to: {}", path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_bid_to_floor() {
+        let request =
+            |floor: f64, cur: &str| json!({ "imp": [{ "bidfloor": floor, "bidfloorcur": cur }] });
+        let usd = json!({ "cur": "USD" });
+        let mut floors = FloorStats::default();
+        // 300x250 bids 3x a 0.5 floor on every request
+        for _ in 0..MIN_FLOOR_BIDS {
+            floors.observe(&request(0.5, "USD"), &usd, (300, 250), "ssp_a", 1.5);
+        }
+        // 728x90: one bid at 1.2x the floor, one under it
+        floors.observe(&request(1.0, ""), &json!({}), (728, 90), "ssp_a", 1.2);
+        floors.observe(&request(1.0, "USD"), &usd, (728, 90), "ssp_b", 0.8);
+        // Skipped: no floor, or a floor in another currency
+        floors.observe(&json!({ "imp": [{}] }), &usd, (728, 90), "ssp_b", 2.0);
+        floors.observe(&request(1.0, "EUR"), &usd, (728, 90), "ssp_b", 2.0);
+
+        let mut other = FloorStats::default();
+        other.observe(&request(1.0, "USD"), &usd, (728, 90), "ssp_b", 3.0);
+        floors.merge(&other);
+
+        let rows = floors.summarize();
+        assert_eq!(
+            rows.iter()
+                .map(|r| (r.dimension, r.key.as_str(), r.bids))
+                .collect::<Vec<_>>(),
+            vec![
+                ("format", "300x250", 20),
+                ("format", "728x90", 3),
+                ("ssp", "ssp_a", 21),
+                ("ssp", "ssp_b", 2),
+            ]
+        );
+        let banner = &rows[0];
+        assert!((banner.avg_bid_to_floor - 3.0).abs() < 1e-9);
+        assert!(banner.overbidding);
+        let leaderboard = &rows[1];
+        assert!((leaderboard.avg_floor - 1.0).abs() < 1e-9);
+        assert!((leaderboard.avg_bid_to_floor - 5.0 / 3.0).abs() < 1e-9);
+        assert!((leaderboard.far_above_share - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(leaderboard.below_floor, 1);
+        assert!(!leaderboard.overbidding);
+    }
+}
@@ -0,0 +1,184 @@
+use anyhow::{bail, Context, Result};
+
+use crate::cli::parse_percent;
+use crate::summary::{percent, ScanSummary};
+
+/// Exit code of a scan that failed a --fail-on condition (errors exit 1)
+pub const FAIL_EXIT_CODE: i32 = 2;
+
+/// Scan-level figures a --fail-on condition can test
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GateMetric {
+    /// Share of requests to formats that never got a bid
+    WastedTraffic,
+    BidRate,
+    /// Share of input lines that failed to parse
+    ParseErrors,
+    ZeroBidFormats,
+    ProblemFormats,
+    /// --alert-rule alerts raised
+    Alerts,
+    /// Share of requests with a seller id their sellers.json doesn't list
+    UnauthorizedSupply,
+}
+
+impl GateMetric {
+    const ALL: [(&'static str, GateMetric); 7] = [
+        ("wasted_traffic", GateMetric::WastedTraffic),
+        ("bid_rate", GateMetric::BidRate),
+        ("parse_errors", GateMetric::ParseErrors),
+        ("zero_bid_formats", GateMetric::ZeroBidFormats),
+        ("problem_formats", GateMetric::ProblemFormats),
+        ("alerts", GateMetric::Alerts),
+        ("unauthorized_supply", GateMetric::UnauthorizedSupply),
+    ];
+
+    /// Shares are compared as fractions; the rest are counts
+    fn is_share(self) -> bool {
+        matches!(
+            self,
+            Self::WastedTraffic | Self::BidRate | Self::ParseErrors | Self::UnauthorizedSupply
+        )
+    }
+
+    fn value(self, summary: &ScanSummary) -> f64 {
+        match self {
+            Self::WastedTraffic => summary.wasted_share,
+            Self::BidRate => summary.bid_rate,
+            Self::ParseErrors => summary.parse_error_share,
+            Self::ZeroBidFormats => summary.zero_bid_formats as f64,
+            Self::ProblemFormats => summary.problem_formats as f64,
+            Self::Alerts => summary.alerts.unwrap_or(0) as f64,
+            Self::UnauthorizedSupply => summary.unauthorized_share.unwrap_or(0.0),
+        }
+    }
+}
+
+/// One --fail-on condition, e.g. "wasted_traffic>20%" or "zero_bid_formats>5"
+#[derive(Debug, Clone, PartialEq)]
+pub struct FailCondition {
+    /// As given on the command line
+    pub spec: String,
+    metric: GateMetric,
+    op: &'static str,
+    threshold: f64,
+}
+
+impl FailCondition {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim();
+        let (at, op) = [">=", "<=", ">", "<"]
+            .iter()
+            .find_map(|op| spec.find(op).map(|at| (at, *op)))
+            .with_context(|| {
+                format!("--fail-on '{spec}' needs a comparison, e.g. wasted_traffic>20%")
+            })?;
+        let name = spec[..at].trim();
+        let value = spec[at + op.len()..].trim();
+        let Some(&(_, metric)) = GateMetric::ALL.iter().find(|(n, _)| *n == name) else {
+            let names: Vec<&str> = GateMetric::ALL.iter().map(|(n, _)| *n).collect();
+            bail!(
+                "unknown --fail-on metric '{name}', expected one of: {}",
+                names.join(", ")
+            );
+        };
+        let threshold = if metric.is_share() {
+            parse_percent(value)?
+        } else {
+            value
+                .parse::<f64>()
+                .with_context(|| format!("invalid number '{value}' in --fail-on '{spec}'"))?
+        };
+        Ok(Self {
+            spec: spec.to_string(),
+            metric,
+            op,
+            threshold,
+        })
+    }
+
+    /// Whether the scan meets the condition (and so fails)
+    pub fn is_met(&self, summary: &ScanSummary) -> bool {
+        let value = self.metric.value(summary);
+        match self.op {
+            ">=" => value >= self.threshold,
+            "<=" => value <= self.threshold,
+            ">" => value > self.threshold,
+            _ => value < self.threshold,
+        }
+    }
+
+    /// The measured value, formatted like the threshold
+    pub fn actual(&self, summary: &ScanSummary) -> String {
+        let value = self.metric.value(summary);
+        if self.metric.is_share() {
+            percent(value)
+        } else {
+            format!("{}", value)
+        }
+    }
+}
+
+/// The conditions the scan meets, each with the measured value
+pub fn failed_conditions<'a>(
+    conditions: &'a [FailCondition],
+    summary: &ScanSummary,
+) -> Vec<(&'a FailCondition, String)> {
+    conditions
+        .iter()
+        .filter(|c| c.is_met(summary))
+        .map(|c| (c, c.actual(summary)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary() -> ScanSummary {
+        ScanSummary {
+            source: "logs.jsonl".to_string(),
+            requests: 1_000,
+            bids: 300,
+            bid_rate: 0.3,
+            wasted_requests: 250,
+            wasted_share: 0.25,
+            zero_bid_formats: 6,
+            healthy_formats: 4,
+            problem_formats: 8,
+            parse_errors: 5,
+            parse_error_share: 0.005,
+            sample_rate: None,
+            test_requests: None,
+            avg_qps: None,
+            peak_qps: None,
+            alerts: None,
+            unauthorized_requests: None,
+            unauthorized_share: None,
+        }
+    }
+
+    #[test]
+    fn test_fail_conditions() {
+        let parse = |spec: &str| FailCondition::parse(spec).unwrap();
+        let s = summary();
+        assert!(parse("wasted_traffic>20%").is_met(&s));
+        assert!(!parse("wasted_traffic > 25").is_met(&s));
+        assert!(parse("wasted_traffic>=25%").is_met(&s));
+        assert!(parse("zero_bid_formats>5").is_met(&s));
+        assert!(!parse("parse_errors>1%").is_met(&s));
+        assert!(parse("bid_rate<40%").is_met(&s));
+        assert!(!parse("problem_formats<=7").is_met(&s));
+        assert!(!parse("alerts>0").is_met(&s));
+
+        let conditions = vec![parse("wasted_traffic>20%"), parse("parse_errors>1%")];
+        let failed = failed_conditions(&conditions, &s);
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0.spec, "wasted_traffic>20%");
+        assert_eq!(failed[0].1, "25.0%");
+
+        assert!(FailCondition::parse("wasted_traffic").is_err());
+        assert!(FailCondition::parse("latency>5").is_err());
+        assert!(FailCondition::parse("zero_bid_formats>many").is_err());
+    }
+}
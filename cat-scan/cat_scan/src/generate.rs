@@ -0,0 +1,144 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+    path::Path,
+};
+
+use anyhow::{Context, Result};
+use fake_ssp::{
+    request::{build_request, CountRange},
+    traffic::{TrafficProfile, TrafficSampler},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use serde_json::{json, Value};
+
+use crate::cli::GenerateConfig;
+
+/// ts_ms of the first line: 2024-01-01T00:00:00Z
+const START_MS: u64 = 1_704_067_200_000;
+
+/// Time between lines, as if fake_ssp sent them at its default 10 QPS
+const LINE_INTERVAL_MS: u64 = 100;
+
+/// fake_bidder's built-in policy: bid on 300x250 banners at 1.2x the
+/// floor, pass on everything else
+const BID_FORMAT: (u64, u64) = (300, 250);
+const FLOOR_MARGIN: f64 = 1.2;
+
+/// The floor priced against when an impression has none
+const NO_FLOOR: f64 = 0.5;
+
+/// Write `config.lines` log lines as fake_ssp would have logged them
+/// against fake_bidder's built-in policy, so cat_scan can be tried without
+/// running the pair. The same seed and profile always give the same file.
+pub fn run_generate(config: GenerateConfig) -> Result<()> {
+    let profile = match &config.traffic {
+        Some(path) => TrafficProfile::load(Path::new(path))?,
+        None => TrafficProfile::builtin(),
+    };
+    let sampler = TrafficSampler::new(profile)?;
+    let mut rng = StdRng::seed_from_u64(config.seed);
+
+    let file = File::create(&config.out_path)
+        .with_context(|| format!("Failed to create {}", config.out_path))?;
+    let mut out = BufWriter::new(file);
+    for n in 0..config.lines {
+        let line = sample_line(&sampler, &mut rng, n);
+        writeln!(out, "{line}").with_context(|| format!("Failed to write {}", config.out_path))?;
+    }
+    out.flush()
+        .with_context(|| format!("Failed to write {}", config.out_path))?;
+    eprintln!(
+        "Wrote {} sample log lines to {} (seed {}); scan them with: cat_scan {} --out ./reports",
+        config.lines, config.out_path, config.seed, config.out_path
+    );
+    Ok(())
+}
+
+/// Log line `n`: a fake_ssp request and the response fake_bidder would
+/// give it, or `{}` when the SSP times it out
+fn sample_line<R: Rng>(sampler: &TrafficSampler, rng: &mut R, n: u64) -> Value {
+    let (request, draw) = build_request(sampler, CountRange::ONE, CountRange::ONE, rng, n);
+    let timed_out = draw.ssp.timeout_rate > 0.0 && rng.random_bool(draw.ssp.timeout_rate);
+    let response = if timed_out {
+        json!({})
+    } else {
+        bid_response(&request)
+    };
+    json!({
+        "ts_ms": START_MS + n * LINE_INTERVAL_MS,
+        "request": request,
+        "response": response,
+    })
+}
+
+/// One seatbid with a bid per 300x250 banner, or an empty seatbid to pass
+fn bid_response(request: &Value) -> Value {
+    let imps = request["imp"]
+        .as_array()
+        .map(Vec::as_slice)
+        .unwrap_or_default();
+    let bids: Vec<Value> = imps
+        .iter()
+        .filter(|imp| {
+            let banner = &imp["banner"];
+            (banner["w"].as_u64(), banner["h"].as_u64()) == (Some(BID_FORMAT.0), Some(BID_FORMAT.1))
+        })
+        .enumerate()
+        .map(|(i, imp)| {
+            let floor = imp["bidfloor"].as_f64().unwrap_or(NO_FLOOR);
+            json!({
+                "id": format!("bid-{}", i + 1),
+                "impid": imp["id"],
+                "price": (floor * FLOOR_MARGIN * 100.0).round() / 100.0,
+                "adm": "<div>Fake ad</div>",
+            })
+        })
+        .collect();
+    let seatbid = if bids.is_empty() {
+        Vec::new()
+    } else {
+        vec![json!({ "bid": bids })]
+    };
+    json!({
+        "id": request["id"],
+        "seatbid": seatbid,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sample_line() {
+        let sampler = TrafficSampler::new(TrafficProfile::builtin()).unwrap();
+        let lines = |seed| {
+            let mut rng = StdRng::seed_from_u64(seed);
+            (0..50)
+                .map(|n| sample_line(&sampler, &mut rng, n))
+                .collect::<Vec<_>>()
+        };
+        let sample = lines(42);
+        assert_eq!(sample, lines(42));
+        assert_ne!(sample, lines(43));
+        assert_eq!(sample[1]["ts_ms"], START_MS + LINE_INTERVAL_MS);
+
+        // Bids on 300x250 only, at 1.2x the floor
+        let mut bids = 0;
+        for line in &sample {
+            let imp = &line["request"]["imp"][0];
+            let seatbid = line["response"]["seatbid"].as_array().unwrap();
+            assert_eq!(line["response"]["id"], line["request"]["id"]);
+            if imp["banner"]["w"] == 300 && imp["banner"]["h"] == 250 {
+                let price = seatbid[0]["bid"][0]["price"].as_f64().unwrap();
+                let floor = imp["bidfloor"].as_f64().unwrap();
+                assert!((price - floor * FLOOR_MARGIN).abs() <= 0.005);
+                bids += 1;
+            } else {
+                assert!(seatbid.is_empty());
+            }
+        }
+        assert!(bids > 0 && bids < sample.len());
+    }
+}
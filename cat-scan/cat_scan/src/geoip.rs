@@ -0,0 +1,153 @@
+use std::net::IpAddr;
+
+use anyhow::{Context, Result};
+use isocountry::CountryCode;
+use maxminddb::{geoip2, Reader};
+use serde_json::{json, Value};
+
+use crate::decode::RecordDecoder;
+use crate::stats::LogRecord;
+
+/// Country and region lookups in a MaxMind-format database (--geoip), such
+/// as GeoLite2-Country or GeoIP2-City
+pub struct GeoIp {
+    reader: Reader<Vec<u8>>,
+}
+
+/// Where an IP address is, in OpenRTB terms
+#[derive(Debug, Clone, PartialEq)]
+pub struct Geo {
+    /// ISO-3166-1 alpha-3, as in device.geo.country (alpha-2 for codes
+    /// without one)
+    pub country: String,
+    /// ISO-3166-2 subdivision, without the country prefix; only City
+    /// databases have it
+    pub region: Option<String>,
+}
+
+impl GeoIp {
+    pub fn open(path: &str) -> Result<Self> {
+        let reader = Reader::open_readfile(path)
+            .with_context(|| format!("Failed to open GeoIP database {path}"))?;
+        Ok(Self { reader })
+    }
+
+    /// None for addresses the database doesn't place in a country
+    pub fn lookup(&self, ip: IpAddr) -> Option<Geo> {
+        let city: geoip2::City = self.reader.lookup(ip).ok()?;
+        let country = city.country?.iso_code?;
+        let region = city
+            .subdivisions
+            .and_then(|subdivisions| subdivisions.into_iter().next())
+            .and_then(|subdivision| subdivision.iso_code)
+            .map(str::to_string);
+        Some(Geo {
+            country: CountryCode::for_alpha2(country)
+                .map_or_else(|_| country.to_string(), |code| code.alpha3().to_string()),
+            region,
+        })
+    }
+}
+
+/// Fill device.geo from device.ip (or device.ipv6) when the request
+/// carries no country, marking it IP-derived (type 2). True when it did.
+pub fn fill_geo(request: &mut Value, lookup: impl FnOnce(IpAddr) -> Option<Geo>) -> bool {
+    let device = &request["device"];
+    if device["geo"]["country"]
+        .as_str()
+        .is_some_and(|country| !country.trim().is_empty())
+    {
+        return false;
+    }
+    let ip = [&device["ip"], &device["ipv6"]]
+        .into_iter()
+        .filter_map(|ip| ip.as_str()?.parse::<IpAddr>().ok())
+        .next();
+    let Some(geo) = ip.and_then(lookup) else {
+        return false;
+    };
+    let geo_value = &mut request["device"]["geo"];
+    if !geo_value.is_object() {
+        *geo_value = json!({});
+    }
+    geo_value["country"] = Value::String(geo.country);
+    if let Some(region) = geo.region {
+        if geo_value["region"].is_null() {
+            geo_value["region"] = Value::String(region);
+        }
+    }
+    if geo_value["type"].is_null() {
+        geo_value["type"] = json!(2);
+    }
+    true
+}
+
+/// Fills in device.geo of each decoded record from a GeoIP database
+pub struct GeoIpDecoder<'a> {
+    inner: Box<dyn RecordDecoder + 'a>,
+    geoip: GeoIp,
+}
+
+impl<'a> GeoIpDecoder<'a> {
+    pub fn new(inner: Box<dyn RecordDecoder + 'a>, geoip: GeoIp) -> Self {
+        Self { inner, geoip }
+    }
+}
+
+impl RecordDecoder for GeoIpDecoder<'_> {
+    fn next_frame(&mut self, buf: &mut Vec<u8>) -> Result<bool> {
+        self.inner.next_frame(buf)
+    }
+
+    fn decode(&mut self, frame: &[u8]) -> Result<Option<LogRecord>, String> {
+        let Some(mut record) = self.inner.decode(frame)? else {
+            return Ok(None);
+        };
+        fill_geo(&mut record.request, |ip| self.geoip.lookup(ip));
+        Ok(Some(record))
+    }
+
+    fn frame_line(&self) -> Option<u64> {
+        self.inner.frame_line()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fill_geo() {
+        let lookup = |ip: IpAddr| {
+            (ip.to_string() == "192.0.2.1").then(|| Geo {
+                country: "USA".to_string(),
+                region: Some("CA".to_string()),
+            })
+        };
+
+        let mut request = json!({ "device": { "ip": "192.0.2.1" } });
+        assert!(fill_geo(&mut request, lookup));
+        assert_eq!(
+            request["device"]["geo"],
+            json!({ "country": "USA", "region": "CA", "type": 2 })
+        );
+
+        // A country the SSP sent wins
+        let mut request = json!({ "device": { "ip": "192.0.2.1", "geo": { "country": "SWE" } } });
+        assert!(!fill_geo(&mut request, lookup));
+        assert_eq!(request["device"]["geo"], json!({ "country": "SWE" }));
+
+        // Lat/lon without a country get one; unknown or missing IPs don't
+        let mut request =
+            json!({ "device": { "ip": "192.0.2.1", "geo": { "lat": 1.5, "type": 1 } } });
+        assert!(fill_geo(&mut request, lookup));
+        assert_eq!(request["device"]["geo"]["type"], 1);
+        for mut request in [
+            json!({ "device": { "ip": "198.51.100.7" } }),
+            json!({ "device": { "ip": "not an ip" } }),
+            json!({ "site": {} }),
+        ] {
+            assert!(!fill_geo(&mut request, lookup));
+        }
+    }
+}
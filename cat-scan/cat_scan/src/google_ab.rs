@@ -0,0 +1,331 @@
+// Rust types for proto/google_ab_log.proto, in the form prost-build emits
+// for it, and their mapping onto the OpenRTB-shaped LogRecord. Keep the
+// types in sync with the proto: tags and wire types must match.
+
+use std::collections::BTreeMap;
+
+use prost::Message;
+use serde_json::{json, Value};
+
+use crate::decode::prune;
+use crate::stats::LogRecord;
+
+/// request.source.ssp for entries that don't name one
+const DEFAULT_SSP: &str = "google_ab";
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GoogleAbLogEntry {
+    #[prost(message, optional, tag = "1")]
+    pub request: Option<BidRequest>,
+    #[prost(message, optional, tag = "2")]
+    pub response: Option<BidResponse>,
+    #[prost(uint64, optional, tag = "3")]
+    pub ts_ms: Option<u64>,
+    #[prost(string, optional, tag = "4")]
+    pub ssp: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BidRequest {
+    #[prost(bytes = "vec", optional, tag = "2")]
+    pub id: Option<Vec<u8>>,
+    /// Truncated by Google: 3 bytes of IPv4, 6 of IPv6
+    #[prost(bytes = "vec", optional, tag = "4")]
+    pub ip: Option<Vec<u8>>,
+    #[prost(string, optional, tag = "6")]
+    pub user_agent: Option<String>,
+    #[prost(string, optional, tag = "11")]
+    pub url: Option<String>,
+    #[prost(message, repeated, tag = "14")]
+    pub adslot: Vec<bid_request::AdSlot>,
+    #[prost(bool, optional, tag = "15")]
+    pub is_test: Option<bool>,
+    #[prost(bool, optional, tag = "17")]
+    pub is_ping: Option<bool>,
+    #[prost(string, optional, tag = "89")]
+    pub publisher_id: Option<String>,
+}
+
+pub mod bid_request {
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct AdSlot {
+        #[prost(int32, optional, tag = "1")]
+        pub id: Option<i32>,
+        #[prost(message, repeated, tag = "6")]
+        pub matching_ad_data: Vec<ad_slot::MatchingAdData>,
+        #[prost(int32, repeated, packed = "false", tag = "14")]
+        pub width: Vec<i32>,
+        #[prost(int32, repeated, packed = "false", tag = "15")]
+        pub height: Vec<i32>,
+    }
+
+    pub mod ad_slot {
+        #[derive(Clone, PartialEq, ::prost::Message)]
+        pub struct MatchingAdData {
+            #[prost(int64, repeated, packed = "false", tag = "2")]
+            pub billing_id: Vec<i64>,
+            #[prost(int64, optional, tag = "3")]
+            pub minimum_cpm_micros: Option<i64>,
+        }
+    }
+}
+
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BidResponse {
+    #[prost(message, repeated, tag = "2")]
+    pub ad: Vec<bid_response::Ad>,
+    #[prost(int32, optional, tag = "4")]
+    pub processing_time_ms: Option<i32>,
+}
+
+pub mod bid_response {
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Ad {
+        #[prost(message, repeated, tag = "3")]
+        pub adslot: Vec<ad::AdSlot>,
+        #[prost(string, repeated, tag = "4")]
+        pub click_through_url: Vec<String>,
+        #[prost(string, optional, tag = "10")]
+        pub buyer_creative_id: Option<String>,
+        #[prost(int32, optional, tag = "14")]
+        pub width: Option<i32>,
+        #[prost(int32, optional, tag = "15")]
+        pub height: Option<i32>,
+    }
+
+    pub mod ad {
+        #[derive(Clone, PartialEq, ::prost::Message)]
+        pub struct AdSlot {
+            #[prost(int32, optional, tag = "1")]
+            pub id: Option<i32>,
+            #[prost(int64, optional, tag = "2")]
+            pub max_cpm_micros: Option<i64>,
+            #[prost(int64, optional, tag = "4")]
+            pub billing_id: Option<i64>,
+        }
+    }
+}
+
+/// A (truncated) IPv4 or IPv6 address, zero-filled to full length
+fn ip_string(bytes: &[u8]) -> Option<String> {
+    match bytes.len() {
+        1..=4 => {
+            let mut octets = [0u8; 4];
+            octets[..bytes.len()].copy_from_slice(bytes);
+            Some(std::net::Ipv4Addr::from(octets).to_string())
+        }
+        5..=16 => {
+            let mut octets = [0u8; 16];
+            octets[..bytes.len()].copy_from_slice(bytes);
+            Some(std::net::Ipv6Addr::from(octets).to_string())
+        }
+        _ => None,
+    }
+}
+
+/// "https://shop.example.com/landing?x=1" -> "shop.example.com"
+fn url_host(url: &str) -> Option<&str> {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let host = rest.split(['/', '?', '#', ':']).next()?;
+    (!host.is_empty()).then_some(host)
+}
+
+fn micros_to_cpm(micros: i64) -> f64 {
+    micros as f64 / 1e6
+}
+
+/// The OpenRTB-shaped request for an Authorized Buyers one: one imp per ad
+/// slot, sized by its first width/height, with its billing ids under
+/// `ext.billing_id`
+fn request_json(request: &BidRequest, ssp: &str) -> Value {
+    let imps: Vec<Value> = request
+        .adslot
+        .iter()
+        .map(|slot| {
+            let format: Vec<Value> = slot
+                .width
+                .iter()
+                .zip(&slot.height)
+                .map(|(w, h)| json!({ "w": w, "h": h }))
+                .collect();
+            let billing_ids: Vec<i64> = slot
+                .matching_ad_data
+                .iter()
+                .flat_map(|m| m.billing_id.iter().copied())
+                .collect();
+            let floor = slot
+                .matching_ad_data
+                .iter()
+                .filter_map(|m| m.minimum_cpm_micros)
+                .filter(|micros| *micros > 0)
+                .min()
+                .map(micros_to_cpm);
+            json!({
+                "id": slot.id.map(|id| id.to_string()),
+                "banner": format.first().map(|first| json!({
+                    "w": first["w"],
+                    "h": first["h"],
+                    "format": format,
+                })),
+                "bidfloor": floor,
+                "ext": { "billing_id": billing_ids },
+            })
+        })
+        .collect();
+    let id = request.id.as_ref().map(|id| {
+        id.iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect::<String>()
+    });
+    prune(json!({
+        "id": id,
+        "imp": imps,
+        "site": {
+            "page": request.url,
+            "publisher": { "id": request.publisher_id },
+        },
+        "device": {
+            "ip": request.ip.as_deref().and_then(ip_string),
+            "ua": request.user_agent,
+        },
+        "source": { "ssp": ssp },
+        "test": request.is_test.filter(|t| *t).map(|_| 1),
+    }))
+}
+
+/// The OpenRTB-shaped response: one seatbid per billing id (the buyer seat),
+/// one bid per ad slot an ad bids on, priced from max_cpm_micros
+fn response_json(response: &BidResponse) -> Value {
+    let mut seats: BTreeMap<Option<i64>, Vec<Value>> = BTreeMap::new();
+    for ad in &response.ad {
+        let adomain: Vec<&str> = ad
+            .click_through_url
+            .iter()
+            .filter_map(|url| url_host(url))
+            .collect();
+        for slot in &ad.adslot {
+            seats.entry(slot.billing_id).or_default().push(json!({
+                "impid": slot.id.map(|id| id.to_string()),
+                "price": micros_to_cpm(slot.max_cpm_micros.unwrap_or(0)),
+                "crid": ad.buyer_creative_id,
+                "w": ad.width,
+                "h": ad.height,
+                "adomain": adomain,
+            }));
+        }
+    }
+    let seatbid: Vec<Value> = seats
+        .into_iter()
+        .map(|(billing_id, bids)| {
+            json!({ "seat": billing_id.map(|id| id.to_string()), "bid": bids })
+        })
+        .collect();
+    prune(json!({ "seatbid": seatbid }))
+}
+
+/// Decode one GoogleAbLogEntry into the LogRecord the same auction would
+/// be logged as in JSONL; None for ping requests (not real traffic)
+pub fn decode_entry(frame: &[u8]) -> Result<Option<LogRecord>, String> {
+    let entry = GoogleAbLogEntry::decode(frame).map_err(|e| e.to_string())?;
+    if entry
+        .request
+        .as_ref()
+        .is_some_and(|r| r.is_ping == Some(true))
+    {
+        return Ok(None);
+    }
+    let ssp = entry.ssp.as_deref().unwrap_or(DEFAULT_SSP);
+    Ok(Some(LogRecord {
+        request: entry
+            .request
+            .as_ref()
+            .map_or(Value::Null, |r| request_json(r, ssp)),
+        response: entry.response.as_ref().map_or(Value::Null, response_json),
+        ts_ms: entry.ts_ms,
+        outcome: Value::Null,
+        http_status: Value::Null,
+        line_no: 0,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bid_request::{ad_slot::MatchingAdData, AdSlot};
+    use bid_response::{ad, Ad};
+
+    #[test]
+    fn test_google_ab_entry() {
+        let entry = GoogleAbLogEntry {
+            request: Some(BidRequest {
+                id: Some(vec![0xab, 0x01]),
+                ip: Some(vec![192, 168, 1]),
+                url: Some("https://news.example.com/a".to_string()),
+                publisher_id: Some("pub-123".to_string()),
+                adslot: vec![AdSlot {
+                    id: Some(1),
+                    width: vec![300, 336],
+                    height: vec![250, 280],
+                    matching_ad_data: vec![MatchingAdData {
+                        billing_id: vec![111, 222],
+                        minimum_cpm_micros: Some(500_000),
+                    }],
+                }],
+                ..Default::default()
+            }),
+            response: Some(BidResponse {
+                ad: vec![Ad {
+                    adslot: vec![ad::AdSlot {
+                        id: Some(1),
+                        max_cpm_micros: Some(1_250_000),
+                        billing_id: Some(222),
+                    }],
+                    click_through_url: vec!["https://shop.example.com/landing".to_string()],
+                    buyer_creative_id: Some("cr-9".to_string()),
+                    width: Some(300),
+                    height: Some(250),
+                }],
+                processing_time_ms: Some(12),
+            }),
+            ts_ms: Some(1_700_000_000_000),
+            ssp: None,
+        };
+        let record = decode_entry(&entry.encode_to_vec()).unwrap().unwrap();
+        assert_eq!(
+            record.request,
+            json!({
+                "id": "ab01",
+                "imp": [{
+                    "id": "1",
+                    "banner": { "w": 300, "h": 250, "format": [{ "w": 300, "h": 250 }, { "w": 336, "h": 280 }] },
+                    "bidfloor": 0.5,
+                    "ext": { "billing_id": [111, 222] }
+                }],
+                "site": { "page": "https://news.example.com/a", "publisher": { "id": "pub-123" } },
+                "device": { "ip": "192.168.1.0" },
+                "source": { "ssp": "google_ab" }
+            })
+        );
+        assert_eq!(
+            record.response,
+            json!({ "seatbid": [{ "seat": "222", "bid": [{
+                "impid": "1", "price": 1.25, "crid": "cr-9", "w": 300, "h": 250,
+                "adomain": ["shop.example.com"]
+            }] }] })
+        );
+        assert_eq!(record.ts_ms, Some(1_700_000_000_000));
+
+        let ping = GoogleAbLogEntry {
+            request: Some(BidRequest {
+                is_ping: Some(true),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(decode_entry(&ping.encode_to_vec()).unwrap().is_none());
+        assert_eq!(
+            ip_string(&[0x20, 0x01, 0x0d, 0xb8, 0, 1]).unwrap(),
+            "2001:db8:1::"
+        );
+    }
+}
@@ -0,0 +1,121 @@
+use anyhow::{bail, Result};
+
+/// An aggregation a scan can be limited to with --group-by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Group {
+    /// Raw and canonical formats; always computed
+    Format,
+    /// Per-SSP views: SSP x format, tmax, imp.secure, consent, user IDs,
+    /// field coverage and duplicate ids
+    Ssp,
+    /// Per-publisher views, including their inventory, position and creatives
+    Publisher,
+    /// Per-segment views, within each publisher too
+    Segment,
+    /// Per-country stats (device.geo.country)
+    Geo,
+    /// Time buckets and QPS (needs timestamps)
+    Hour,
+    /// Content categories, browser/OS families, inventory type, ad position
+    /// and viewability
+    Content,
+    /// Prices, bids per response, floors, seats, creatives and markup
+    Bids,
+}
+
+/// --group-by names, in display order
+const GROUP_NAMES: &[(&str, Group)] = &[
+    ("format", Group::Format),
+    ("ssp", Group::Ssp),
+    ("publisher", Group::Publisher),
+    ("segment", Group::Segment),
+    ("geo", Group::Geo),
+    ("hour", Group::Hour),
+    ("content", Group::Content),
+    ("bids", Group::Bids),
+];
+
+/// The aggregations a scan computes; every one unless --group-by narrows it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GroupBy {
+    mask: u16,
+}
+
+impl Default for GroupBy {
+    fn default() -> Self {
+        let mask = GROUP_NAMES
+            .iter()
+            .fold(0, |mask, (_, group)| mask | Self::bit(*group));
+        Self { mask }
+    }
+}
+
+impl GroupBy {
+    /// Parse a comma-separated list like `format,publisher,geo,hour`
+    /// ("time" also means hour). Formats are always computed, listed or not.
+    pub fn parse(list: &str) -> Result<Self> {
+        let mut group_by = Self {
+            mask: Self::bit(Group::Format),
+        };
+        for name in list.split(',').map(str::trim).filter(|n| !n.is_empty()) {
+            let lookup = if name.eq_ignore_ascii_case("time") {
+                "hour"
+            } else {
+                name
+            };
+            let Some((_, group)) = GROUP_NAMES
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(lookup))
+            else {
+                let names: Vec<&str> = GROUP_NAMES.iter().map(|(n, _)| *n).collect();
+                bail!(
+                    "unknown group '{name}', expected one of {}",
+                    names.join(", ")
+                );
+            };
+            group_by.mask |= Self::bit(*group);
+        }
+        Ok(group_by)
+    }
+
+    fn bit(group: Group) -> u16 {
+        1 << group as u16
+    }
+
+    pub fn has(self, group: Group) -> bool {
+        self.mask & Self::bit(group) != 0
+    }
+
+    /// Whether every aggregation is computed (no --group-by)
+    pub fn is_all(self) -> bool {
+        self == Self::default()
+    }
+
+    /// Names of the computed groups, e.g. ["format", "geo"]
+    pub fn names(self) -> Vec<&'static str> {
+        GROUP_NAMES
+            .iter()
+            .filter(|(_, group)| self.has(*group))
+            .map(|(name, _)| *name)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_group_by() {
+        let group_by = GroupBy::parse("publisher, GEO,time").unwrap();
+        assert_eq!(group_by.names(), vec!["format", "publisher", "geo", "hour"]);
+        assert!(group_by.has(Group::Format));
+        assert!(!group_by.has(Group::Ssp));
+        assert!(!group_by.is_all());
+        assert!(GroupBy::default().has(Group::Bids));
+        assert!(GroupBy::default().is_all());
+
+        let error = GroupBy::parse("format,country").unwrap_err().to_string();
+        assert!(error.contains("unknown group 'country'"), "{error}");
+    }
+}
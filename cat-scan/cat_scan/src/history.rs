@@ -0,0 +1,129 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::html::render_trend_report;
+use crate::report::HtmlReportData;
+use crate::summary::ScanSummary;
+
+/// Scans charted in trend.html unless --history-runs says otherwise
+pub const DEFAULT_HISTORY_RUNS: usize = 30;
+
+/// One line of history.jsonl: a scan's summary.json KPIs and when it ran
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct HistoryEntry {
+    pub generated_at_ms: u64,
+    #[serde(flatten)]
+    pub summary: ScanSummary,
+}
+
+/// Append a line to `history.jsonl` in `dir`, creating both if needed
+fn append_entry(dir: &Path, entry: &HistoryEntry) -> Result<()> {
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create history directory {}", dir.display()))?;
+    let path = dir.join("history.jsonl");
+    let line = serde_json::to_string(entry).context("Failed to serialize history entry")?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    writeln!(file, "{}", line).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// The last `runs` entries of `history.jsonl` in `dir`, oldest first
+fn load_entries(dir: &Path, runs: usize) -> Result<Vec<HistoryEntry>> {
+    let path = dir.join("history.jsonl");
+    let text =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut entries = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(line) {
+            Ok(entry) => entries.push(entry),
+            Err(e) => bail!(
+                "Invalid entry on line {} of {}: {}",
+                i + 1,
+                path.display(),
+                e
+            ),
+        }
+    }
+    let skip = entries.len().saturating_sub(runs);
+    Ok(entries.split_off(skip))
+}
+
+/// --history-dir: record this scan and redraw trend.html over the last
+/// `runs` scans. Returns the trend.html path and how many scans it shows.
+pub fn record_scan(dir: &str, runs: usize, report: &HtmlReportData) -> Result<(String, usize)> {
+    let dir = Path::new(dir);
+    append_entry(
+        dir,
+        &HistoryEntry {
+            generated_at_ms: report.metadata.generated_at_ms,
+            summary: ScanSummary::from_report(report),
+        },
+    )?;
+    let entries = load_entries(dir, runs)?;
+    let path = dir.join("trend.html");
+    fs::write(&path, render_trend_report(&entries)?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    Ok((path.display().to_string(), entries.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(generated_at_ms: u64, requests: u64, bids: u64) -> HistoryEntry {
+        HistoryEntry {
+            generated_at_ms,
+            summary: ScanSummary {
+                source: "logs.jsonl".to_string(),
+                requests,
+                bids,
+                bid_rate: bids as f64 / requests as f64,
+                wasted_requests: 0,
+                wasted_share: 0.0,
+                zero_bid_formats: 0,
+                healthy_formats: 1,
+                problem_formats: 0,
+                parse_errors: 0,
+                parse_error_share: 0.0,
+                sample_rate: None,
+                test_requests: None,
+                avg_qps: None,
+                peak_qps: None,
+                alerts: None,
+                unauthorized_requests: None,
+                unauthorized_share: None,
+            },
+        }
+    }
+
+    #[test]
+    fn test_history_keeps_last_runs() {
+        let dir = tempfile::tempdir().unwrap();
+        for (i, bids) in [50, 40, 30].into_iter().enumerate() {
+            append_entry(dir.path(), &entry(i as u64 * 1000, 100, bids)).unwrap();
+        }
+        let entries = load_entries(dir.path(), 2).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].generated_at_ms, 1000);
+        assert_eq!(entries[1].summary.bids, 30);
+        assert_eq!(load_entries(dir.path(), 10).unwrap().len(), 3);
+
+        let html = render_trend_report(&entries).unwrap();
+        assert!(html.contains("\"generated_at_ms\":2000"));
+
+        fs::write(dir.path().join("history.jsonl"), "{\"requests\": 1}\n").unwrap();
+        let error = load_entries(dir.path(), 2).unwrap_err().to_string();
+        assert!(error.contains("line 1"), "{error}");
+    }
+}
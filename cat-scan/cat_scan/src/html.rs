@@ -0,0 +1,412 @@
+use anyhow::{Context, Result};
+use minijinja::{context, Environment};
+
+use crate::history::HistoryEntry;
+use crate::metadata::ScanMetadata;
+use crate::report::HtmlReportData;
+
+/// The built-in report template. A --template file replaces it; it gets the
+/// same variables: `report` (the data behind report.json), `report_json`
+/// (that data as a JSON literal safe to embed in a <script>),
+/// `scan_metadata` (ScanMetadata::lines, for the footer), `logo_base64` and
+/// `bundle` (see ReportHeader).
+pub const DEFAULT_REPORT_TEMPLATE: &str = include_str!("../templates/report.html");
+
+/// What `report` holds in a --bundle page, which loads everything else from
+/// report.json; `report_json` is not set
+#[derive(serde::Serialize)]
+struct ReportHeader<'a> {
+    source: &'a str,
+    total_requests: u64,
+    total_publishers: u64,
+    total_raw_formats: u64,
+    total_canonical_formats: u64,
+    min_requests_filter: u64,
+    metadata: &'a ScanMetadata,
+}
+
+/// Render the report with `template` (the built-in one when None). With
+/// `bundle` the page fetches report.json instead of embedding the data.
+pub fn render_html_report(
+    report: &HtmlReportData,
+    template: Option<&str>,
+    bundle: bool,
+) -> Result<String> {
+    let mut env = Environment::new();
+    // Named *.html so values are HTML-escaped unless marked |safe
+    env.add_template("report.html", template.unwrap_or(DEFAULT_REPORT_TEMPLATE))
+        .context("Invalid report template")?;
+    let template = env.get_template("report.html")?;
+    let logo_base64 = include_str!("../../rtbCatLogo-horizontal.svg.b64");
+    let scan_metadata = report.metadata.lines();
+
+    let html = if bundle {
+        let header = ReportHeader {
+            source: &report.source,
+            total_requests: report.total_requests,
+            total_publishers: report.total_publishers,
+            total_raw_formats: report.total_raw_formats,
+            total_canonical_formats: report.total_canonical_formats,
+            min_requests_filter: report.min_requests_filter,
+            metadata: &report.metadata,
+        };
+        template.render(context! { report => header, scan_metadata, logo_base64, bundle })
+    } else {
+        // Raw records (--samples) may contain "</script>" inside creatives
+        let report_json = serde_json::to_string(report)
+            .context("Failed to serialize report to JSON")?
+            .replace("</", "<\\/");
+        template.render(context! { report, report_json, scan_metadata, logo_base64, bundle })
+    };
+    html.context("Failed to render report template")
+}
+
+/// Reject a --template with a syntax error before the scan runs
+pub fn check_report_template(template: &str) -> Result<()> {
+    Environment::new().template_from_str(template)?;
+    Ok(())
+}
+
+pub fn write_html_report_full(
+    path: &str,
+    report: &HtmlReportData,
+    template: Option<&str>,
+    bundle: bool,
+) -> Result<()> {
+    let html = render_html_report(report, template, bundle)?;
+
+    std::fs::write(path, html)
+        .with_context(|| format!("Failed to write HTML report to {}", path))?;
+
+    Ok(())
+}
+
+/// Page for `cat_scan live`: polls /api/live and redraws itself
+pub fn render_live_dashboard(source: &str, window_secs: u64, refresh_secs: u64) -> String {
+    // The source is a path or URL, which may contain anything
+    let source_json = serde_json::Value::from(source)
+        .to_string()
+        .replace("</", "<\\/");
+    format!(
+        r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>Cat Scan Live</title>
+    <style>
+        * {{ box-sizing: border-box; }}
+        body {{ font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, sans-serif; margin: 0; padding: 20px; background: #f5f5f5; }}
+        .container {{ max-width: 1400px; margin: 0 auto; }}
+        h1 {{ color: #333; margin-bottom: 10px; }}
+        h3 {{ color: #333; margin: 25px 0 10px 0; }}
+        .meta {{ color: #666; margin-bottom: 20px; font-size: 14px; }}
+        .live-dot {{ display: inline-block; width: 10px; height: 10px; border-radius: 50%; background: #28a745; margin-right: 6px; }}
+        .live-dot.stale {{ background: #dc3545; }}
+        .summary-dashboard {{ display: grid; grid-template-columns: repeat(auto-fit, minmax(200px, 1fr)); gap: 15px; margin-bottom: 25px; }}
+        .metric-card {{ background: white; padding: 20px; border-radius: 8px; box-shadow: 0 1px 3px rgba(0,0,0,0.1); text-align: center; }}
+        .metric-card.alert {{ border-left: 4px solid #dc3545; }}
+        .metric-card.warning {{ border-left: 4px solid #ffc107; }}
+        .metric-card.success {{ border-left: 4px solid #28a745; }}
+        .metric-value {{ font-size: 2rem; font-weight: 700; color: #333; }}
+        .metric-label {{ font-size: 0.85rem; color: #666; margin-top: 5px; text-transform: uppercase; letter-spacing: 0.5px; }}
+        .metric-detail {{ font-size: 0.8rem; color: #999; margin-top: 3px; }}
+        .chart {{ background: white; border-radius: 8px; box-shadow: 0 1px 3px rgba(0,0,0,0.1); padding: 15px; }}
+        .chart svg {{ width: 100%; height: 160px; }}
+        .grid {{ display: grid; grid-template-columns: repeat(auto-fit, minmax(420px, 1fr)); gap: 20px; }}
+        table {{ width: 100%; border-collapse: collapse; background: white; border-radius: 8px; overflow: hidden; box-shadow: 0 1px 3px rgba(0,0,0,0.1); }}
+        th, td {{ padding: 10px 15px; text-align: left; border-bottom: 1px solid #eee; }}
+        th {{ background: #4a90a4; color: white; }}
+        .no-bid {{ color: #999; }}
+        .low-bid-rate {{ color: #dc3545; }}
+        .badge {{ display: inline-block; padding: 2px 8px; border-radius: 4px; font-size: 12px; }}
+        .badge-danger {{ background: #f8d7da; color: #721c24; }}
+        .badge-warning {{ background: #fff3cd; color: #856404; }}
+    </style>
+</head>
+<body>
+    <div class="container">
+        <h1>Cat Scan Live</h1>
+        <div class="meta"><span class="live-dot" id="liveDot"></span>Following <span id="source"></span> | Window: last {window_minutes} minutes | <span id="updatedAt">waiting for data...</span></div>
+
+        <div class="summary-dashboard" id="summaryDashboard"></div>
+
+        <div class="chart">
+            <svg id="qpsChart" viewBox="0 0 600 160" preserveAspectRatio="none"></svg>
+        </div>
+
+        <div class="grid">
+            <div>
+                <h3>Top Formats</h3>
+                <table id="formatsTable">
+                    <thead><tr><th>Format</th><th>Requests</th><th>Bids</th><th>Bid Rate</th></tr></thead>
+                    <tbody></tbody>
+                </table>
+            </div>
+            <div>
+                <h3>Problems</h3>
+                <table id="problemsTable">
+                    <thead><tr><th>Format</th><th>Requests</th><th>Bid Rate</th><th>Problem Type</th></tr></thead>
+                    <tbody></tbody>
+                </table>
+                <h3>SSPs</h3>
+                <table id="sspsTable">
+                    <thead><tr><th>SSP</th><th>Requests</th><th>Bids</th><th>Bid Rate</th></tr></thead>
+                    <tbody></tbody>
+                </table>
+            </div>
+        </div>
+    </div>
+    <script>
+        const REFRESH_MS = {refresh_ms};
+        document.getElementById('source').textContent = {source_json};
+
+        function escapeHtml(text) {{
+            return String(text).replace(/[&<>"']/g, c => ({{ '&': '&amp;', '<': '&lt;', '>': '&gt;', '"': '&quot;', "'": '&#39;' }})[c]);
+        }}
+
+        function pct(v) {{ return (v * 100).toFixed(2) + '%'; }}
+        function rateClass(r) {{ return r.bid_rate === 0 ? 'no-bid' : (r.bid_rate < 0.05 ? 'low-bid-rate' : ''); }}
+
+        function renderCards(s) {{
+            const bidRateClass = s.bid_rate >= 0.3 ? 'success' : (s.bid_rate >= 0.1 ? '' : 'warning');
+            const problemClass = s.problems.length > 5 ? 'alert' : (s.problems.length > 0 ? 'warning' : 'success');
+            document.getElementById('summaryDashboard').innerHTML = `
+                <div class="metric-card">
+                    <div class="metric-value">${{s.total_requests.toLocaleString()}}</div>
+                    <div class="metric-label">Requests in Window</div>
+                    <div class="metric-detail">${{s.total_bids.toLocaleString()}} bids placed</div>
+                </div>
+                <div class="metric-card ${{bidRateClass}}">
+                    <div class="metric-value">${{(s.bid_rate * 100).toFixed(1)}}%</div>
+                    <div class="metric-label">Bid Rate</div>
+                    <div class="metric-detail">rolling {window_minutes}m</div>
+                </div>
+                <div class="metric-card">
+                    <div class="metric-value">${{s.current_qps.toFixed(1)}}</div>
+                    <div class="metric-label">Current QPS</div>
+                    <div class="metric-detail">${{s.avg_qps.toFixed(1)}} avg over window</div>
+                </div>
+                <div class="metric-card ${{problemClass}}">
+                    <div class="metric-value">${{s.problems.length}}</div>
+                    <div class="metric-label">Problem Formats</div>
+                    <div class="metric-detail">${{s.parse_errors.toLocaleString()}} unparseable lines</div>
+                </div>
+            `;
+        }}
+
+        function renderChart(series) {{
+            const svg = document.getElementById('qpsChart');
+            if (series.length === 0) {{ svg.innerHTML = ''; return; }}
+            const maxQps = Math.max(...series.map(p => p.qps), 0.001);
+            const step = series.length > 1 ? 600 / (series.length - 1) : 600;
+            const pts = (f, max) => series.map((p, i) => `${{(i * step).toFixed(1)}},${{(150 - f(p) / max * 140).toFixed(1)}}`).join(' ');
+            svg.innerHTML = `
+                <polyline fill="none" stroke="#4a90a4" stroke-width="2" points="${{pts(p => p.qps, maxQps)}}"></polyline>
+                <polyline fill="none" stroke="#28a745" stroke-width="2" stroke-dasharray="4 3" points="${{pts(p => p.bid_rate, 1)}}"></polyline>
+                <text x="4" y="12" font-size="11" fill="#4a90a4">QPS (peak ${{maxQps.toFixed(1)}})</text>
+                <text x="150" y="12" font-size="11" fill="#28a745">Bid rate</text>
+            `;
+        }}
+
+        function fillTable(id, rows, cells) {{
+            document.querySelector(`#${{id}} tbody`).innerHTML = rows.map(r => `<tr>${{cells(r)}}</tr>`).join('');
+        }}
+
+        function render(s) {{
+            renderCards(s);
+            renderChart(s.series);
+            fillTable('formatsTable', s.formats, r => `<td><strong>${{r.w}}x${{r.h}}</strong></td><td>${{r.requests.toLocaleString()}}</td><td>${{r.bids.toLocaleString()}}</td><td class="${{rateClass(r)}}">${{pct(r.bid_rate)}}</td>`);
+            fillTable('problemsTable', s.problems, r => `<td><strong>${{r.w}}x${{r.h}}</strong></td><td>${{r.requests.toLocaleString()}}</td><td class="low-bid-rate">${{pct(r.bid_rate)}}</td><td><span class="badge ${{r.problem_type === 'zero_bids' ? 'badge-danger' : 'badge-warning'}}">${{escapeHtml(r.problem_type)}}</span></td>`);
+            fillTable('sspsTable', s.ssps, r => `<td><strong>${{escapeHtml(r.ssp)}}</strong></td><td>${{r.requests.toLocaleString()}}</td><td>${{r.bids.toLocaleString()}}</td><td class="${{rateClass(r)}}">${{pct(r.bid_rate)}}</td>`);
+            document.getElementById('updatedAt').textContent = 'Updated ' + new Date(s.generated_at_ms).toLocaleTimeString() + ` (${{s.lines_read.toLocaleString()}} lines read)`;
+        }}
+
+        async function refresh() {{
+            const dot = document.getElementById('liveDot');
+            try {{
+                const resp = await fetch('api/live', {{ cache: 'no-store' }});
+                render(await resp.json());
+                dot.classList.remove('stale');
+            }} catch (e) {{
+                dot.classList.add('stale');
+            }}
+        }}
+
+        refresh();
+        setInterval(refresh, REFRESH_MS);
+    </script>
+</body>
+</html>"##,
+        source_json = source_json,
+        window_minutes = window_secs.div_ceil(60),
+        refresh_ms = refresh_secs * 1000,
+    )
+}
+
+/// trend.html for --history-dir: bid rate, wasted traffic and problem
+/// formats across the recorded scans, with scan-over-scan deltas
+pub fn render_trend_report(entries: &[HistoryEntry]) -> Result<String> {
+    // Sources are file paths, which may contain anything
+    let entries_json = serde_json::to_string(entries)
+        .context("Failed to serialize scan history to JSON")?
+        .replace("</", "<\\/");
+    Ok(format!(
+        r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="UTF-8">
+    <meta name="viewport" content="width=device-width, initial-scale=1.0">
+    <title>Cat Scan Trends</title>
+    <style>
+        * {{ box-sizing: border-box; }}
+        body {{ font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, sans-serif; margin: 0; padding: 20px; background: #f5f5f5; }}
+        .container {{ max-width: 1400px; margin: 0 auto; }}
+        h1 {{ color: #333; margin-bottom: 10px; }}
+        h3 {{ color: #333; margin: 25px 0 10px 0; }}
+        .meta {{ color: #666; margin-bottom: 20px; font-size: 14px; }}
+        .grid {{ display: grid; grid-template-columns: repeat(auto-fit, minmax(420px, 1fr)); gap: 20px; }}
+        .chart {{ background: white; border-radius: 8px; box-shadow: 0 1px 3px rgba(0,0,0,0.1); padding: 15px; }}
+        .chart h3 {{ margin: 0 0 10px 0; font-size: 15px; }}
+        .chart svg {{ width: 100%; height: 160px; }}
+        table {{ width: 100%; border-collapse: collapse; background: white; border-radius: 8px; overflow: hidden; box-shadow: 0 1px 3px rgba(0,0,0,0.1); }}
+        th, td {{ padding: 10px 15px; text-align: left; border-bottom: 1px solid #eee; }}
+        th {{ background: #4a90a4; color: white; }}
+        .delta {{ font-size: 12px; margin-left: 6px; color: #999; }}
+        .delta.worse {{ color: #dc3545; font-weight: 600; }}
+        .delta.better {{ color: #28a745; }}
+        tr.regression {{ background: #fdf2f3; }}
+        .badge {{ display: inline-block; padding: 2px 8px; border-radius: 4px; font-size: 12px; background: #f8d7da; color: #721c24; }}
+    </style>
+</head>
+<body>
+    <div class="container">
+        <h1>Cat Scan Trends</h1>
+        <div class="meta" id="meta"></div>
+
+        <div class="grid">
+            <div class="chart"><h3>Bid Rate</h3><svg id="bidRateChart" viewBox="0 0 600 160" preserveAspectRatio="none"></svg></div>
+            <div class="chart"><h3>Wasted Traffic</h3><svg id="wastedChart" viewBox="0 0 600 160" preserveAspectRatio="none"></svg></div>
+            <div class="chart"><h3>Problem Formats</h3><svg id="problemsChart" viewBox="0 0 600 160" preserveAspectRatio="none"></svg></div>
+        </div>
+
+        <h3>Scans</h3>
+        <table id="runsTable">
+            <thead><tr><th>Scanned</th><th>Source</th><th>Requests</th><th>Bid Rate</th><th>Wasted</th><th>Problem Formats</th><th>Parse Errors</th><th></th></tr></thead>
+            <tbody></tbody>
+        </table>
+    </div>
+    <script>
+        const RUNS = {entries_json};
+        // A drop this large in bid rate (in points) flags a scan as a regression
+        const BID_RATE_DROP = 0.05;
+
+        function pct(v) {{ return (v * 100).toFixed(1) + '%'; }}
+        function esc(s) {{ return String(s).replace(/[&<>"]/g, c => ({{ '&': '&amp;', '<': '&lt;', '>': '&gt;', '"': '&quot;' }})[c]); }}
+        function when(ms) {{ return new Date(ms).toLocaleString(); }}
+
+        function chart(id, value, max, color, label) {{
+            const svg = document.getElementById(id);
+            if (RUNS.length === 0) return;
+            const top = Math.max(max, 0.001);
+            const step = RUNS.length > 1 ? 580 / (RUNS.length - 1) : 0;
+            const pts = RUNS.map((r, i) => [10 + i * step, 150 - value(r) / top * 130]);
+            svg.innerHTML = `
+                <polyline fill="none" stroke="${{color}}" stroke-width="2" points="${{pts.map(p => p.map(v => v.toFixed(1)).join(',')).join(' ')}}"></polyline>
+                ${{pts.map((p, i) => `<circle cx="${{p[0].toFixed(1)}}" cy="${{p[1].toFixed(1)}}" r="3" fill="${{color}}"><title>${{esc(when(RUNS[i].generated_at_ms))}}: ${{esc(label(RUNS[i]))}}</title></circle>`).join('')}}
+                <text x="4" y="12" font-size="11" fill="#666">latest ${{esc(label(RUNS[RUNS.length - 1]))}}</text>
+            `;
+        }}
+
+        // Change from the previous scan; `higherIsWorse` picks the coloring
+        function delta(cur, prev, fmt, higherIsWorse) {{
+            if (prev === undefined || cur === prev) return '';
+            const worse = (cur > prev) === higherIsWorse;
+            return `<span class="delta ${{worse ? 'worse' : 'better'}}">${{cur > prev ? '+' : '-'}}${{fmt(Math.abs(cur - prev))}}</span>`;
+        }}
+
+        document.getElementById('meta').textContent = RUNS.length === 0
+            ? 'No scans recorded yet'
+            : `Last ${{RUNS.length}} scans, ${{when(RUNS[0].generated_at_ms)}} to ${{when(RUNS[RUNS.length - 1].generated_at_ms)}}`;
+
+        chart('bidRateChart', r => r.bid_rate, 1, '#28a745', r => pct(r.bid_rate));
+        chart('wastedChart', r => r.wasted_share, 1, '#dc3545', r => `${{pct(r.wasted_share)}} (${{r.wasted_requests.toLocaleString()}} requests)`);
+        chart('problemsChart', r => r.problem_formats, Math.max(...RUNS.map(r => r.problem_formats), 1), '#ffc107', r => `${{r.problem_formats}} (${{r.zero_bid_formats}} with zero bids)`);
+
+        // Newest first
+        document.querySelector('#runsTable tbody').innerHTML = RUNS.map((r, i) => {{
+            const p = RUNS[i - 1];
+            const regression = p !== undefined && (p.bid_rate - r.bid_rate >= BID_RATE_DROP || r.problem_formats > p.problem_formats);
+            return `<tr class="${{regression ? 'regression' : ''}}">
+                <td>${{esc(when(r.generated_at_ms))}}</td>
+                <td>${{esc(r.source)}}</td>
+                <td>${{r.requests.toLocaleString()}}${{delta(r.requests, p && p.requests, v => v.toLocaleString(), false)}}</td>
+                <td>${{pct(r.bid_rate)}}${{delta(r.bid_rate, p && p.bid_rate, pct, false)}}</td>
+                <td>${{pct(r.wasted_share)}}${{delta(r.wasted_share, p && p.wasted_share, pct, true)}}</td>
+                <td>${{r.problem_formats}}${{delta(r.problem_formats, p && p.problem_formats, v => v, true)}}</td>
+                <td>${{r.parse_errors.toLocaleString()}}${{delta(r.parse_errors, p && p.parse_errors, v => v.toLocaleString(), true)}}</td>
+                <td>${{regression ? '<span class="badge">Regression</span>' : ''}}</td>
+            </tr>`;
+        }}).reverse().join('');
+    </script>
+</body>
+</html>"##,
+        entries_json = entries_json,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::parse_scan_args;
+    use crate::input::SkippedLines;
+    use crate::report::build_report_data;
+    use crate::stats::GlobalStats;
+
+    #[test]
+    fn test_custom_report_template() {
+        let config = parse_scan_args("<logs>.jsonl".into(), &[]).unwrap();
+        let report = build_report_data(
+            &GlobalStats::new(),
+            &config,
+            &[],
+            None,
+            &SkippedLines::default(),
+        );
+
+        let template = "<h1>{{ report.source }} ({{ report.total_requests }})</h1>\n\
+            <script>const R = {{ report_json|safe }};</script>";
+        let html = render_html_report(&report, Some(template), false).unwrap();
+        assert!(
+            html.starts_with("<h1>&lt;logs&gt;.jsonl (0)</h1>"),
+            "{html}"
+        );
+        assert!(
+            html.contains(r#"const R = {"source":"<logs>.jsonl""#),
+            "{html}"
+        );
+
+        assert!(render_html_report(&report, Some("{{ report.source"), false).is_err());
+        let builtin = render_html_report(&report, None, false).unwrap();
+        assert!(builtin.contains("init({\"source\""));
+        assert!(builtin.contains("records: 0 requests, 0 parse errors"));
+
+        // --bundle: only the header figures, the data comes from report.json
+        let bundled = render_html_report(&report, Some(template), true).unwrap();
+        assert!(
+            bundled.starts_with("<h1>&lt;logs&gt;.jsonl (0)</h1>"),
+            "{bundled}"
+        );
+        assert!(bundled.contains("const R = ;"), "{bundled}");
+        let builtin = render_html_report(&report, None, true).unwrap();
+        assert!(builtin.contains("fetch('report.json')"));
+        assert!(!builtin.contains("init({"));
+    }
+
+    #[test]
+    fn test_live_dashboard_escapes_source() {
+        let page = render_live_dashboard("</script><img src=x onerror=alert(1)>", 300, 5);
+        assert!(!page.contains("</script><img"), "{page}");
+        assert!(page.contains(r#"textContent = "<\/script><img src=x onerror=alert(1)>";"#));
+    }
+}
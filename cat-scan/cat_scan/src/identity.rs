@@ -0,0 +1,67 @@
+use serde_json::Value;
+
+/// Identifier types reported per SSP, in display order. Per-source extended
+/// IDs follow as "eid:<source>".
+pub const ID_TYPES: &[&str] = &["any", "user.id", "buyeruid", "eids"];
+
+/// Identifier types present on one request: any of ID_TYPES plus one
+/// "eid:<source>" entry per extended ID source with at least one uid.
+/// Reads eids from user.eids (OpenRTB 2.6) or user.ext.eids.
+pub fn present_ids(request: &Value) -> Vec<String> {
+    let user = &request["user"];
+    let non_empty = |v: &Value| v.as_str().is_some_and(|s| !s.is_empty());
+
+    let mut ids = Vec::new();
+    if non_empty(&user["id"]) {
+        ids.push("user.id".to_string());
+    }
+    if non_empty(&user["buyeruid"]) {
+        ids.push("buyeruid".to_string());
+    }
+
+    let eids = user["eids"]
+        .as_array()
+        .or_else(|| user["ext"]["eids"].as_array());
+    let mut sources: Vec<&str> = eids
+        .into_iter()
+        .flatten()
+        .filter(|eid| eid["uids"].as_array().is_some_and(|u| !u.is_empty()))
+        .filter_map(|eid| eid["source"].as_str())
+        .collect();
+    sources.sort_unstable();
+    sources.dedup();
+    if !sources.is_empty() {
+        ids.push("eids".to_string());
+    }
+
+    if !ids.is_empty() {
+        ids.push("any".to_string());
+    }
+    ids.extend(sources.into_iter().map(|s| format!("eid:{}", s)));
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_present_ids() {
+        let request = serde_json::json!({
+            "user": {
+                "id": "u1",
+                "buyeruid": "",
+                "ext": { "eids": [
+                    { "source": "id5-sync.com", "uids": [{ "id": "x" }] },
+                    { "source": "uidapi.com", "uids": [] },
+                    { "source": "id5-sync.com", "uids": [{ "id": "y" }] }
+                ] }
+            }
+        });
+        assert_eq!(
+            present_ids(&request),
+            vec!["user.id", "eids", "any", "eid:id5-sync.com"]
+        );
+        assert!(present_ids(&serde_json::json!({ "user": { "id": null } })).is_empty());
+    }
+}
@@ -0,0 +1,403 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, BufWriter, Seek, SeekFrom, Write},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use indicatif::{ProgressBar, ProgressStyle};
+
+use crate::decode::RecordDecoder;
+use crate::kafka::{self, KafkaSource};
+use crate::profile::{Phase, ScanProfile};
+use crate::stats::{process_record_global, GlobalStats};
+
+/// Parse an S3 URI like s3://bucket/key into (bucket, key)
+pub fn parse_s3_uri(uri: &str) -> Option<(String, String)> {
+    let stripped = uri.strip_prefix("s3://")?;
+    let (bucket, key) = stripped.split_once('/')?;
+    Some((bucket.to_string(), key.to_string()))
+}
+
+/// Parse errors kept verbatim for the report under --on-error skip
+const MAX_ERROR_SAMPLES: usize = 10;
+
+/// Update the lines/sec message every this many lines
+const PROGRESS_LINE_INTERVAL: u64 = 10_000;
+
+/// Byte-based progress bar on stderr, or a spinner when the total isn't
+/// known (Kafka). Hidden with --quiet; indicatif also hides it when stderr
+/// is not a terminal.
+pub fn scan_progress(total_bytes: Option<u64>, quiet: bool, label: &'static str) -> ProgressBar {
+    if quiet {
+        return ProgressBar::hidden();
+    }
+    let bar = match total_bytes {
+        Some(total) => {
+            let bar = ProgressBar::new(total);
+            bar.set_style(
+                ProgressStyle::with_template(
+                    "{prefix} [{elapsed_precise}] {wide_bar} {bytes}/{total_bytes} ({binary_bytes_per_sec}, ETA {eta}) {msg}",
+                )
+                .expect("valid progress template")
+                .progress_chars("=> "),
+            );
+            bar
+        }
+        None => {
+            let bar = ProgressBar::new_spinner();
+            bar.set_style(
+                ProgressStyle::with_template(
+                    "{prefix} [{elapsed_precise}] {spinner} {bytes} ({binary_bytes_per_sec}) {msg}",
+                )
+                .expect("valid progress template"),
+            );
+            bar.enable_steady_tick(Duration::from_millis(200));
+            bar
+        }
+    };
+    bar.set_prefix(label);
+    bar
+}
+
+/// Which input lines to aggregate (--sample / --sample-every)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Sampler {
+    All,
+    /// Keep about this fraction of lines, picked by a hash of the line number
+    /// so repeated runs see the same sample
+    Rate(f64),
+    /// Keep every Nth line
+    Every(u64),
+}
+
+impl Sampler {
+    /// Whether to parse the (1-based) line `line_no`
+    pub fn keeps(&self, line_no: u64) -> bool {
+        match *self {
+            Sampler::All => true,
+            Sampler::Rate(rate) => (splitmix64(line_no) as f64 / u64::MAX as f64) < rate,
+            Sampler::Every(n) => (line_no - 1).is_multiple_of(n),
+        }
+    }
+
+    /// Fraction of lines kept
+    pub fn fraction(&self) -> f64 {
+        match *self {
+            Sampler::All => 1.0,
+            Sampler::Rate(rate) => rate,
+            Sampler::Every(n) => 1.0 / n as f64,
+        }
+    }
+}
+
+/// Cheap, well-mixed 64-bit hash for deterministic sampling
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+/// What to do with a line that isn't valid UTF-8 JSON (--on-error)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OnError {
+    Abort,
+    Skip,
+}
+
+/// One malformed line, as shown in the report
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct BadLine {
+    pub line: u64,
+    pub error: String,
+}
+
+/// Malformed lines skipped under --on-error skip
+#[derive(Debug, Default, Clone, serde::Serialize)]
+pub struct SkippedLines {
+    pub count: u64,
+    /// The first MAX_ERROR_SAMPLES errors
+    pub samples: Vec<BadLine>,
+}
+
+/// Applies the --on-error policy, the --max-errors budget and the optional
+/// --bad-lines dump to malformed input lines
+pub struct ErrorHandler {
+    on_error: OnError,
+    max_errors: Option<u64>,
+    bad_lines: Option<BufWriter<File>>,
+    pub skipped: SkippedLines,
+}
+
+impl ErrorHandler {
+    pub fn new(
+        on_error: OnError,
+        max_errors: Option<u64>,
+        bad_lines: Option<&str>,
+    ) -> Result<Self> {
+        let bad_lines = match bad_lines {
+            Some(path) => Some(BufWriter::new(
+                File::create(path).with_context(|| format!("Failed to create {}", path))?,
+            )),
+            None => None,
+        };
+        Ok(Self {
+            on_error,
+            max_errors,
+            bad_lines,
+            skipped: SkippedLines::default(),
+        })
+    }
+
+    /// Abort, or record and skip, one malformed line
+    pub fn handle(&mut self, line_no: u64, raw: &[u8], error: String) -> Result<()> {
+        if self.on_error == OnError::Abort {
+            bail!("Failed to parse line {}: {}", line_no, error);
+        }
+        self.skipped.count += 1;
+        if self.skipped.samples.len() < MAX_ERROR_SAMPLES {
+            self.skipped.samples.push(BadLine {
+                line: line_no,
+                error: error.clone(),
+            });
+        }
+        if let Some(out) = &mut self.bad_lines {
+            out.write_all(raw)?;
+            if !raw.ends_with(b"\n") {
+                out.write_all(b"\n")?;
+            }
+        }
+        if let Some(max) = self.max_errors {
+            if self.skipped.count > max {
+                bail!(
+                    "Too many malformed lines ({} > --max-errors {}); last on line {}: {}",
+                    self.skipped.count,
+                    max,
+                    line_no,
+                    error
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Flush the bad-lines file and hand back what was skipped
+    pub fn finish(mut self) -> Result<SkippedLines> {
+        if let Some(out) = &mut self.bad_lines {
+            out.flush().context("Failed to write bad lines file")?;
+        }
+        Ok(self.skipped)
+    }
+}
+
+/// Decode records and aggregate them into GlobalStats. Records the sampler
+/// skips are not decoded at all; malformed ones go to `errors`. Records are
+/// numbered as lines, whatever the input format. `progress` gets a
+/// lines/sec message; byte progress comes from wrapping the reader with it.
+/// Each step is timed into `profile` when --profile is on.
+pub fn process_records_global(
+    decoder: &mut dyn RecordDecoder,
+    global: &mut GlobalStats,
+    sampler: Sampler,
+    errors: &mut ErrorHandler,
+    progress: &ProgressBar,
+    profile: &mut ScanProfile,
+) -> Result<()> {
+    let mut buf = Vec::new();
+    let mut line_no: u64 = 0;
+    loop {
+        let started = profile.start();
+        let more = decoder
+            .next_frame(&mut buf)
+            .with_context(|| format!("Failed to read line {}", line_no + 1))?;
+        profile.stop(Phase::Read, started);
+        if !more {
+            break;
+        }
+        line_no += 1;
+        profile.lines += 1;
+        profile.bytes += buf.len() as u64;
+        if line_no.is_multiple_of(PROGRESS_LINE_INTERVAL) {
+            let secs = progress.elapsed().as_secs_f64().max(0.001);
+            progress.set_message(format!(
+                "{} lines, {:.0} lines/s",
+                line_no,
+                line_no as f64 / secs
+            ));
+        }
+        if !sampler.keeps(line_no) {
+            continue;
+        }
+
+        let input_line = decoder.frame_line().unwrap_or(line_no);
+        let started = profile.start();
+        let decoded = decoder.decode(&buf);
+        profile.stop(Phase::Parse, started);
+        let mut record = match decoded {
+            Ok(Some(record)) => record,
+            Ok(None) => continue,
+            Err(e) => {
+                errors.handle(input_line, &buf, e)?;
+                continue;
+            }
+        };
+        record.line_no = input_line;
+        profile.records += 1;
+
+        let started = profile.start();
+        process_record_global(&record, global);
+        profile.stop(Phase::Aggregate, started);
+    }
+    Ok(())
+}
+
+/// Follow a log file, or a Kafka topic given as kafka://, calling `on_line`
+/// for every line (message) as it arrives
+pub fn follow_input<F: FnMut(&str)>(
+    path: &str,
+    from_start: bool,
+    poll_interval: Duration,
+    on_line: F,
+) -> Result<()> {
+    match KafkaSource::parse(path) {
+        Some(source) => kafka::follow(&source?, from_start, on_line),
+        None => follow_lines(path, from_start, poll_interval, on_line),
+    }
+}
+
+/// Follow a file that is still being written (like `tail -f`), calling
+/// `on_line` for every complete line. Starts at the end of the file unless
+/// `from_start` is set, and reopens from the beginning when the file is
+/// truncated or rotated. Never returns unless reading fails.
+pub fn follow_lines<F: FnMut(&str)>(
+    path: &str,
+    from_start: bool,
+    poll_interval: Duration,
+    mut on_line: F,
+) -> Result<()> {
+    let file = File::open(path).with_context(|| format!("Failed to open log file: {}", path))?;
+    let mut reader = BufReader::new(file);
+    let mut pos = if from_start {
+        0
+    } else {
+        reader.seek(SeekFrom::End(0))?
+    };
+    let mut partial = String::new();
+
+    loop {
+        let mut chunk = String::new();
+        let read = reader
+            .read_line(&mut chunk)
+            .with_context(|| format!("Failed to read from {}", path))?;
+
+        if read == 0 {
+            thread::sleep(poll_interval);
+
+            // Truncated or replaced: start over from the top of the new file
+            let len = std::fs::metadata(path).map(|m| m.len()).unwrap_or(pos);
+            if len < pos {
+                let file = File::open(path)
+                    .with_context(|| format!("Failed to reopen log file: {}", path))?;
+                reader = BufReader::new(file);
+                pos = 0;
+                partial.clear();
+            }
+            continue;
+        }
+
+        pos += read as u64;
+        partial.push_str(&chunk);
+
+        // Writer hasn't finished this line yet; wait for the rest
+        if !partial.ends_with('\n') {
+            continue;
+        }
+
+        let line = partial.trim();
+        if !line.is_empty() {
+            on_line(line);
+        }
+        partial.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decode::JsonlDecoder;
+
+    #[test]
+    fn test_parse_s3_uri() {
+        assert_eq!(
+            parse_s3_uri("s3://bucket/path/logs.jsonl"),
+            Some(("bucket".to_string(), "path/logs.jsonl".to_string()))
+        );
+        assert_eq!(parse_s3_uri("logs.jsonl"), None);
+        assert_eq!(parse_s3_uri("s3://bucket-only"), None);
+    }
+
+    #[test]
+    fn test_skip_malformed_lines() {
+        let input = "{\"request\":{}}\nnot json\n{\"request\":{}}\n";
+        let mut global = GlobalStats::new();
+
+        let mut abort = ErrorHandler::new(OnError::Abort, None, None).unwrap();
+        let err = process_records_global(
+            &mut JsonlDecoder::new(input.as_bytes()),
+            &mut global,
+            Sampler::All,
+            &mut abort,
+            &ProgressBar::hidden(),
+            &mut ScanProfile::default(),
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("line 2"), "{err}");
+
+        let mut skip = ErrorHandler::new(OnError::Skip, None, None).unwrap();
+        let bytes = [input.as_bytes(), b"\xff\xfe\n"].concat();
+        process_records_global(
+            &mut JsonlDecoder::new(bytes.as_slice()),
+            &mut global,
+            Sampler::All,
+            &mut skip,
+            &ProgressBar::hidden(),
+            &mut ScanProfile::default(),
+        )
+        .unwrap();
+        let skipped = skip.finish().unwrap();
+        assert_eq!(skipped.count, 2);
+        assert_eq!(skipped.samples[0].line, 2);
+        assert_eq!(skipped.samples[1].line, 4);
+        assert!(skipped.samples[1].error.starts_with("invalid UTF-8"));
+
+        let mut budget = ErrorHandler::new(OnError::Skip, Some(1), None).unwrap();
+        assert!(process_records_global(
+            &mut JsonlDecoder::new(bytes.as_slice()),
+            &mut global,
+            Sampler::All,
+            &mut budget,
+            &ProgressBar::hidden(),
+            &mut ScanProfile::default(),
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_sampler() {
+        let every = Sampler::Every(10);
+        let kept: Vec<u64> = (1..=30).filter(|&n| every.keeps(n)).collect();
+        assert_eq!(kept, vec![1, 11, 21]);
+
+        let rate = Sampler::Rate(0.1);
+        let kept = (1..=100_000).filter(|&n| rate.keeps(n)).count();
+        assert!((9_000..11_000).contains(&kept), "kept {kept}");
+        // A smaller rate keeps a subset of a larger one
+        let wide = Sampler::Rate(0.5);
+        assert!((1..=10_000)
+            .filter(|&n| rate.keeps(n))
+            .all(|n| wide.keeps(n)));
+        assert!((1..=1000).all(|n| Sampler::All.keeps(n) && Sampler::Rate(1.0).keeps(n)));
+    }
+}
@@ -0,0 +1,65 @@
+use serde_json::Value;
+
+/// Inventory types, in display order. Interstitial and rewarded slots sell
+/// very differently from standard display, so they are reported apart.
+pub const INVENTORY_TYPES: &[&str] = &["standard", "interstitial", "rewarded"];
+
+/// Dimensions the inventory breakdown is reported for, in display order
+pub const INVENTORY_DIMENSIONS: &[&str] = &["format", "ssp", "publisher"];
+
+/// Inventory type of a request's first impression (the one the format
+/// stats are keyed by):
+///
+/// - rewarded: `imp.rwdd` = 1, interstitial or not
+/// - interstitial: `imp.instl` = 1, or a video placement of 5
+///   (interstitial/slider/floating, 2.5) or plcmt 3 (interstitial, 2.6)
+/// - standard: anything else
+pub fn inventory_type(request: &Value) -> &'static str {
+    let imp = &request["imp"][0];
+    let flag = |v: &Value| v.as_u64() == Some(1) || v.as_bool() == Some(true);
+
+    if flag(&imp["rwdd"]) {
+        "rewarded"
+    } else if flag(&imp["instl"])
+        || imp["video"]["placement"].as_u64() == Some(5)
+        || imp["video"]["plcmt"].as_u64() == Some(3)
+    {
+        "interstitial"
+    } else {
+        "standard"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_inventory_type() {
+        let imp = |imp: Value| json!({ "imp": [imp] });
+        assert_eq!(inventory_type(&imp(json!({ "banner": {} }))), "standard");
+        assert_eq!(
+            inventory_type(&imp(json!({ "banner": {}, "instl": 1 }))),
+            "interstitial"
+        );
+        assert_eq!(
+            inventory_type(&imp(json!({ "banner": {}, "instl": 1, "rwdd": 1 }))),
+            "rewarded"
+        );
+        assert_eq!(
+            inventory_type(&imp(json!({ "video": { "placement": 5 } }))),
+            "interstitial"
+        );
+        assert_eq!(
+            inventory_type(&imp(json!({ "video": { "plcmt": 3 } }))),
+            "interstitial"
+        );
+        assert_eq!(
+            inventory_type(&imp(
+                json!({ "video": { "plcmt": 1 }, "instl": 0, "rwdd": 0 })
+            )),
+            "standard"
+        );
+    }
+}
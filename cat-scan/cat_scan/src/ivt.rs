@@ -0,0 +1,413 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    io::Write,
+    net::IpAddr,
+    sync::Arc,
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::metadata::{create_csv, ScanMetadata};
+use crate::stats::LogRecord;
+
+/// Ignore IPs/IFAs with fewer requests than this, however skewed
+const MIN_SUSPECT_VOLUME: u64 = 50;
+
+/// An IP/IFA is suspect when it sends this many times the mean per-id volume
+const VOLUME_MULTIPLIER: f64 = 20.0;
+
+/// Stop tracking new distinct IPs/IFAs past this many (memory guard)
+const MAX_TRACKED_IDS: usize = 1_000_000;
+
+/// Rows kept per top-offender list
+const TOP_OFFENDERS: usize = 20;
+
+/// IPv4/IPv6 CIDR ranges, e.g. a datacenter / hosting provider list
+#[derive(Debug, Default)]
+pub struct CidrSet {
+    ranges: Vec<(IpAddr, u8)>,
+}
+
+impl CidrSet {
+    /// One CIDR (or bare IP) per line; blank lines and `#` comments are skipped
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut ranges = Vec::new();
+        for (line_no, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (addr, prefix) = match line.split_once('/') {
+                Some((addr, prefix)) => (addr, Some(prefix)),
+                None => (line, None),
+            };
+            let addr: IpAddr = addr
+                .parse()
+                .with_context(|| format!("invalid IP on line {}: {}", line_no + 1, line))?;
+            let max_prefix = if addr.is_ipv4() { 32 } else { 128 };
+            let prefix = match prefix {
+                Some(p) => p
+                    .parse::<u8>()
+                    .with_context(|| format!("invalid prefix on line {}: {}", line_no + 1, line))?,
+                None => max_prefix,
+            };
+            if prefix > max_prefix {
+                bail!(
+                    "prefix /{} too long on line {}: {}",
+                    prefix,
+                    line_no + 1,
+                    line
+                );
+            }
+            ranges.push((addr, prefix));
+        }
+        Ok(Self { ranges })
+    }
+
+    pub fn load(path: &str) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read IP range file: {}", path))?;
+        Self::parse(&text)
+    }
+
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.ranges.iter().any(|&(net, prefix)| match (net, ip) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        })
+    }
+}
+
+/// Size/devicetype combinations that can't be real inventory:
+/// leaderboard-or-wider slots on phones, and phone banners on CTV/set-top boxes
+pub fn is_impossible_size_for_device(w: u32, h: u32, devicetype: u64) -> bool {
+    match devicetype {
+        // 4 = Phone
+        4 => w >= 728,
+        // 3 = Connected TV, 7 = Set Top Box
+        3 | 7 => h <= 100 && w <= 320,
+        _ => false,
+    }
+}
+
+/// Per-SSP counters for each heuristic
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SuspectCounts {
+    pub requests: u64,
+    pub missing_ua: u64,
+    pub datacenter_ip: u64,
+    pub impossible_size: u64,
+}
+
+impl SuspectCounts {
+    fn merge(&mut self, other: &SuspectCounts) {
+        self.requests += other.requests;
+        self.missing_ua += other.missing_ua;
+        self.datacenter_ip += other.datacenter_ip;
+        self.impossible_size += other.impossible_size;
+    }
+}
+
+/// Suspicious / invalid traffic heuristics (--ivt)
+#[derive(Debug, Default, Clone)]
+pub struct IvtStats {
+    datacenter: Option<Arc<CidrSet>>,
+    pub by_ssp: BTreeMap<String, SuspectCounts>,
+    pub by_ip: HashMap<String, u64>,
+    pub by_ifa: HashMap<String, u64>,
+    /// Impossible combos seen, keyed by (w, h, devicetype)
+    pub impossible: BTreeMap<(u32, u32, u64), u64>,
+}
+
+fn count_id(map: &mut HashMap<String, u64>, id: &str) {
+    if let Some(count) = map.get_mut(id) {
+        *count += 1;
+    } else if map.len() < MAX_TRACKED_IDS {
+        map.insert(id.to_string(), 1);
+    }
+}
+
+impl IvtStats {
+    pub fn new(datacenter: Option<CidrSet>) -> Self {
+        Self {
+            datacenter: datacenter.map(Arc::new),
+            ..Self::default()
+        }
+    }
+
+    pub fn observe(&mut self, record: &LogRecord, ssp: &str, w: u32, h: u32) {
+        let device = &record.request["device"];
+        let counts = self.by_ssp.entry(ssp.to_string()).or_default();
+        counts.requests += 1;
+
+        let ua = device["ua"].as_str().unwrap_or("").trim();
+        if ua.is_empty() {
+            counts.missing_ua += 1;
+        }
+
+        let ip = device["ip"]
+            .as_str()
+            .or_else(|| device["ipv6"].as_str())
+            .unwrap_or("")
+            .trim();
+        if !ip.is_empty() {
+            if let (Some(ranges), Ok(addr)) = (&self.datacenter, ip.parse::<IpAddr>()) {
+                if ranges.contains(addr) {
+                    counts.datacenter_ip += 1;
+                }
+            }
+        }
+
+        if let Some(devicetype) = device["devicetype"].as_u64() {
+            if is_impossible_size_for_device(w, h, devicetype) {
+                counts.impossible_size += 1;
+                *self.impossible.entry((w, h, devicetype)).or_default() += 1;
+            }
+        }
+
+        if !ip.is_empty() {
+            count_id(&mut self.by_ip, ip);
+        }
+        let ifa = device["ifa"].as_str().unwrap_or("").trim();
+        // All-zero IFAs are the "limit ad tracking" placeholder, not one device
+        if !ifa.is_empty() && ifa.chars().any(|c| c != '0' && c != '-') {
+            count_id(&mut self.by_ifa, ifa);
+        }
+    }
+
+    pub fn merge(&mut self, other: &IvtStats) {
+        if self.datacenter.is_none() {
+            self.datacenter = other.datacenter.clone();
+        }
+        for (ssp, counts) in &other.by_ssp {
+            self.by_ssp.entry(ssp.clone()).or_default().merge(counts);
+        }
+        for (ip, count) in &other.by_ip {
+            *self.by_ip.entry(ip.clone()).or_default() += count;
+        }
+        for (ifa, count) in &other.by_ifa {
+            *self.by_ifa.entry(ifa.clone()).or_default() += count;
+        }
+        for (key, count) in &other.impossible {
+            *self.impossible.entry(*key).or_default() += count;
+        }
+    }
+
+    pub fn summarize(&self) -> SuspectTrafficReport {
+        let ssps = self
+            .by_ssp
+            .iter()
+            .map(|(ssp, c)| SuspectSspSummary {
+                ssp: ssp.clone(),
+                requests: c.requests,
+                missing_ua: c.missing_ua,
+                datacenter_ip: c.datacenter_ip,
+                impossible_size: c.impossible_size,
+                suspect_rate: if c.requests == 0 {
+                    0.0
+                } else {
+                    // Heuristics overlap, so cap at 100%
+                    ((c.missing_ua + c.datacenter_ip + c.impossible_size) as f64
+                        / c.requests as f64)
+                        .min(1.0)
+                },
+            })
+            .collect();
+
+        let mut impossible_sizes: Vec<ImpossibleSize> = self
+            .impossible
+            .iter()
+            .map(|(&(w, h, devicetype), &requests)| ImpossibleSize {
+                w,
+                h,
+                devicetype,
+                requests,
+            })
+            .collect();
+        impossible_sizes.sort_by_key(|r| (Reverse(r.requests), r.w, r.h, r.devicetype));
+
+        SuspectTrafficReport {
+            datacenter_ranges_loaded: self.datacenter.as_ref().map(|d| d.len()).unwrap_or(0),
+            ssps,
+            heavy_ips: heavy_hitters(&self.by_ip),
+            heavy_ifas: heavy_hitters(&self.by_ifa),
+            impossible_sizes,
+        }
+    }
+}
+
+/// Ids whose volume is far above the mean per-id volume
+fn heavy_hitters(counts: &HashMap<String, u64>) -> Vec<HeavyHitter> {
+    if counts.is_empty() {
+        return Vec::new();
+    }
+    let total: u64 = counts.values().sum();
+    let mean = total as f64 / counts.len() as f64;
+    let threshold = (mean * VOLUME_MULTIPLIER).max(MIN_SUSPECT_VOLUME as f64);
+
+    let mut hitters: Vec<HeavyHitter> = counts
+        .iter()
+        .filter(|(_, &count)| count as f64 >= threshold)
+        .map(|(id, &requests)| HeavyHitter {
+            id: id.clone(),
+            requests,
+            share: requests as f64 / total as f64,
+        })
+        .collect();
+    hitters.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.id.cmp(&b.id)));
+    hitters.truncate(TOP_OFFENDERS);
+    hitters
+}
+
+#[derive(serde::Serialize)]
+pub struct SuspectSspSummary {
+    pub ssp: String,
+    pub requests: u64,
+    pub missing_ua: u64,
+    pub datacenter_ip: u64,
+    pub impossible_size: u64,
+    pub suspect_rate: f64,
+}
+
+#[derive(serde::Serialize)]
+pub struct HeavyHitter {
+    pub id: String,
+    pub requests: u64,
+    pub share: f64,
+}
+
+#[derive(serde::Serialize)]
+pub struct ImpossibleSize {
+    pub w: u32,
+    pub h: u32,
+    pub devicetype: u64,
+    pub requests: u64,
+}
+
+/// "Suspect traffic" section of the report
+#[derive(serde::Serialize)]
+pub struct SuspectTrafficReport {
+    pub datacenter_ranges_loaded: usize,
+    pub ssps: Vec<SuspectSspSummary>,
+    pub heavy_ips: Vec<HeavyHitter>,
+    pub heavy_ifas: Vec<HeavyHitter>,
+    pub impossible_sizes: Vec<ImpossibleSize>,
+}
+
+/// Write suspect_traffic.csv into the output directory
+pub fn write_suspect_csv(
+    out_dir: &str,
+    report: &SuspectTrafficReport,
+    meta: &ScanMetadata,
+) -> Result<()> {
+    let path = format!("{}/suspect_traffic.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+
+    writeln!(csv, "# SSPs")?;
+    writeln!(
+        csv,
+        "ssp,requests,missing_ua,datacenter_ip,impossible_size,suspect_rate"
+    )?;
+    for s in &report.ssps {
+        writeln!(
+            csv,
+            "{},{},{},{},{},{:.4}",
+            s.ssp, s.requests, s.missing_ua, s.datacenter_ip, s.impossible_size, s.suspect_rate
+        )?;
+    }
+
+    writeln!(csv, "\n# Heavy hitters")?;
+    writeln!(csv, "type,id,requests,share")?;
+    for (kind, hitters) in [("ip", &report.heavy_ips), ("ifa", &report.heavy_ifas)] {
+        for hh in hitters {
+            writeln!(csv, "{},{},{},{:.4}", kind, hh.id, hh.requests, hh.share)?;
+        }
+    }
+
+    writeln!(csv, "\n# Impossible size/devicetype")?;
+    writeln!(csv, "w,h,devicetype,requests")?;
+    for r in &report.impossible_sizes {
+        writeln!(csv, "{},{},{},{}", r.w, r.h, r.devicetype, r.requests)?;
+    }
+
+    eprintln!("Suspect traffic written to: {}", path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(device: serde_json::Value) -> LogRecord {
+        LogRecord {
+            request: serde_json::json!({ "device": device }),
+            response: serde_json::Value::Null,
+            ts_ms: None,
+            outcome: serde_json::Value::Null,
+            http_status: serde_json::Value::Null,
+            line_no: 0,
+        }
+    }
+
+    #[test]
+    fn test_cidr_set_matching() {
+        let set = CidrSet::parse("# hosting\n10.0.0.0/8\n192.168.1.7\n2001:db8::/32\n").unwrap();
+        assert_eq!(set.len(), 3);
+        assert!(set.contains("10.20.30.40".parse().unwrap()));
+        assert!(set.contains("192.168.1.7".parse().unwrap()));
+        assert!(!set.contains("192.168.1.8".parse().unwrap()));
+        assert!(set.contains("2001:db8::1".parse().unwrap()));
+        assert!(!set.contains("11.0.0.1".parse().unwrap()));
+        assert!(CidrSet::parse("10.0.0.0/33").is_err());
+    }
+
+    #[test]
+    fn test_ivt_heuristics() {
+        let dc = CidrSet::parse("10.0.0.0/8").unwrap();
+        let mut ivt = IvtStats::new(Some(dc));
+
+        // Phone asking for a billboard, from a datacenter IP, with no UA
+        ivt.observe(
+            &record(serde_json::json!({"devicetype": 4, "ip": "10.1.2.3"})),
+            "ssp_a",
+            970,
+            250,
+        );
+        ivt.observe(
+            &record(serde_json::json!({"devicetype": 4, "ip": "8.8.8.8", "ua": "Mozilla/5.0"})),
+            "ssp_a",
+            320,
+            50,
+        );
+
+        let counts = &ivt.by_ssp["ssp_a"];
+        assert_eq!(counts.requests, 2);
+        assert_eq!(counts.missing_ua, 1);
+        assert_eq!(counts.datacenter_ip, 1);
+        assert_eq!(counts.impossible_size, 1);
+        assert_eq!(ivt.impossible[&(970, 250, 4)], 1);
+    }
+
+    #[test]
+    fn test_heavy_hitters() {
+        let mut counts: HashMap<String, u64> = (0..100).map(|i| (format!("ip{i}"), 1)).collect();
+        counts.insert("bot".to_string(), 500);
+
+        let hitters = heavy_hitters(&counts);
+        assert_eq!(hitters.len(), 1);
+        assert_eq!(hitters[0].id, "bot");
+        assert_eq!(hitters[0].requests, 500);
+    }
+}
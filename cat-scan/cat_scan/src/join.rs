@@ -0,0 +1,448 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap},
+    fs::File,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{BufRead, BufReader, BufWriter, Write},
+};
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+use crate::decode::ts_at;
+use crate::extract::ExtractRule;
+use crate::input::{ErrorHandler, Sampler};
+use crate::stats::{process_record_global, GlobalStats, LogRecord};
+
+/// Default cap on the response table before the join spills to disk
+pub const DEFAULT_JOIN_MEMORY_MB: u64 = 1024;
+
+/// Partition files per side once the join spills
+const SPILL_PARTITIONS: usize = 64;
+
+/// A spilled request joined with its response: line number, timestamp,
+/// request, response
+type Joined = (u64, Option<u64>, Value, Option<Value>);
+
+/// Separate request and response logs scanned as one (--requests/--responses)
+#[derive(Debug, Clone, PartialEq)]
+pub struct JoinInputs {
+    pub requests: String,
+    pub responses: String,
+    /// Response bytes held in memory before spilling to disk
+    pub memory_cap_bytes: u64,
+}
+
+/// How the logs matched up
+#[derive(Debug, Default, PartialEq)]
+pub struct JoinSummary {
+    pub requests: u64,
+    pub matched: u64,
+    /// Responses whose request id never appeared in the request log
+    pub unmatched_responses: u64,
+    /// Whether the response table outgrew the memory cap
+    pub spilled: bool,
+}
+
+/// A request log line: a `{"request": ..., "ts_ms": ...}` record like the
+/// combined logs, or a bare OpenRTB BidRequest. With `ts_field` the
+/// timestamp is read from that path of the line instead (unparseable
+/// timestamps are dropped).
+fn split_request(mut value: Value, ts_field: Option<&ExtractRule>) -> (Value, Option<u64>) {
+    let ts_ms = match ts_field {
+        Some(ts_field) => ts_at(ts_field, &value).ok().flatten(),
+        None => value["ts_ms"].as_u64(),
+    };
+    match value.get_mut("request").map(Value::take) {
+        Some(request) => (request, ts_ms),
+        None => (value, ts_ms),
+    }
+}
+
+/// A response log line and the request id it answers: a `{"response": ...}`
+/// record with `request_id` (or `id`), or a bare BidResponse, whose `id` is
+/// the request id
+fn split_response(mut value: Value) -> Option<(String, Value)> {
+    let response = match value.get_mut("response").map(Value::take) {
+        Some(response) => {
+            let key = value
+                .get("request_id")
+                .or_else(|| value.get("id"))
+                .unwrap_or(&response["id"]);
+            let key = key.as_str()?.to_string();
+            return Some((key, response));
+        }
+        None => value,
+    };
+    let key = response["id"].as_str()?.to_string();
+    Some((key, response))
+}
+
+fn partition(id: &str) -> usize {
+    let mut hasher = DefaultHasher::new();
+    id.hash(&mut hasher);
+    (hasher.finish() % SPILL_PARTITIONS as u64) as usize
+}
+
+/// Numbered, non-empty lines of a reader, parsed as JSON. Malformed lines
+/// go to `errors` and are skipped; `keep` filters by line number before
+/// parsing.
+fn for_each_json_line<R: BufRead>(
+    mut reader: R,
+    label: &str,
+    errors: &mut ErrorHandler,
+    keep: impl Fn(u64) -> bool,
+    mut on_value: impl FnMut(u64, Value) -> Result<()>,
+) -> Result<()> {
+    let mut buf = Vec::new();
+    let mut line_no = 0;
+    loop {
+        buf.clear();
+        let read = reader
+            .read_until(b'\n', &mut buf)
+            .with_context(|| format!("Failed to read {} line {}", label, line_no + 1))?;
+        if read == 0 {
+            return Ok(());
+        }
+        line_no += 1;
+        if !keep(line_no) {
+            continue;
+        }
+        let parsed = std::str::from_utf8(&buf)
+            .map_err(|e| format!("invalid UTF-8: {}", e))
+            .and_then(|line| {
+                let line = line.trim();
+                if line.is_empty() {
+                    return Ok(None);
+                }
+                serde_json::from_str::<Value>(line)
+                    .map(Some)
+                    .map_err(|e| e.to_string())
+            });
+        match parsed {
+            Ok(Some(value)) => on_value(line_no, value)?,
+            Ok(None) => {}
+            Err(e) => errors.handle(line_no, &buf, format!("{}: {}", label, e))?,
+        }
+    }
+}
+
+/// Join one request with its response (if any) and count it
+fn process_joined(
+    global: &mut GlobalStats,
+    summary: &mut JoinSummary,
+    line_no: u64,
+    request: Value,
+    ts_ms: Option<u64>,
+    response: Option<Value>,
+) {
+    summary.requests += 1;
+    if response.is_some() {
+        summary.matched += 1;
+    }
+    let record = LogRecord {
+        request,
+        response: response.unwrap_or(Value::Null),
+        ts_ms,
+        outcome: Value::Null,
+        http_status: Value::Null,
+        line_no,
+    };
+    process_record_global(&record, global);
+}
+
+/// Hash-join request and response logs on the request id and aggregate the
+/// pairs into `global`, as if they had been logged together. Requests
+/// without a response count as no-bids. Responses are held in memory up to
+/// `memory_cap_bytes` (of raw JSON); beyond that both sides are partitioned
+/// by id into temporary files and joined one partition at a time.
+pub fn join_logs<Q: BufRead, S: BufRead>(
+    requests: Q,
+    responses: S,
+    memory_cap_bytes: u64,
+    global: &mut GlobalStats,
+    sampler: Sampler,
+    errors: &mut ErrorHandler,
+    ts_field: Option<&ExtractRule>,
+) -> Result<JoinSummary> {
+    let mut summary = JoinSummary::default();
+    let mut table: HashMap<String, Value> = HashMap::new();
+    let mut table_bytes = 0u64;
+    let mut spill: Option<Spill> = None;
+
+    for_each_json_line(
+        responses,
+        "responses",
+        errors,
+        |_| true,
+        |_, value| {
+            let Some((id, response)) = split_response(value) else {
+                return Ok(());
+            };
+            if let Some(spill) = &mut spill {
+                return spill.write_response(&id, &response);
+            }
+            table_bytes += (id.len() + response.to_string().len()) as u64;
+            table.entry(id).or_insert(response);
+            if table_bytes > memory_cap_bytes {
+                let mut new_spill = Spill::new()?;
+                for (id, response) in table.drain() {
+                    new_spill.write_response(&id, &response)?;
+                }
+                spill = Some(new_spill);
+            }
+            Ok(())
+        },
+    )?;
+
+    let Some(mut spill) = spill else {
+        for_each_json_line(
+            requests,
+            "requests",
+            errors,
+            |line_no| sampler.keeps(line_no),
+            |line_no, value| {
+                let (request, ts_ms) = split_request(value, ts_field);
+                let response = request["id"].as_str().and_then(|id| table.remove(id));
+                process_joined(global, &mut summary, line_no, request, ts_ms, response);
+                Ok(())
+            },
+        )?;
+        summary.unmatched_responses = table.len() as u64;
+        return Ok(summary);
+    };
+
+    summary.spilled = true;
+    for_each_json_line(
+        requests,
+        "requests",
+        errors,
+        |line_no| sampler.keeps(line_no),
+        |line_no, value| {
+            let (request, ts_ms) = split_request(value, ts_field);
+            spill.write_request(line_no, request, ts_ms)
+        },
+    )?;
+    spill.join(global, &mut summary)?;
+    Ok(summary)
+}
+
+/// Both sides of the join partitioned by request id into temporary files
+struct Spill {
+    dir: tempfile::TempDir,
+    responses: Vec<BufWriter<File>>,
+    requests: Vec<BufWriter<File>>,
+}
+
+impl Spill {
+    fn new() -> Result<Self> {
+        let dir = tempfile::Builder::new()
+            .prefix("cat_scan_join")
+            .tempdir()
+            .context("Failed to create a temporary directory for the join")?;
+        let open = |side: &str| -> Result<Vec<BufWriter<File>>> {
+            (0..SPILL_PARTITIONS)
+                .map(|i| {
+                    let path = dir.path().join(format!("{side}-{i}.jsonl"));
+                    File::create(&path)
+                        .map(BufWriter::new)
+                        .with_context(|| format!("Failed to create {}", path.display()))
+                })
+                .collect()
+        };
+        Ok(Self {
+            responses: open("responses")?,
+            requests: open("requests")?,
+            dir,
+        })
+    }
+
+    fn write_response(&mut self, id: &str, response: &Value) -> Result<()> {
+        let out = &mut self.responses[partition(id)];
+        serde_json::to_writer(&mut *out, &(id, response))?;
+        out.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Requests without an id can't match; they still count, as no-bids
+    fn write_request(&mut self, line_no: u64, request: Value, ts_ms: Option<u64>) -> Result<()> {
+        let out = &mut self.requests[request["id"].as_str().map_or(0, partition)];
+        serde_json::to_writer(&mut *out, &(line_no, ts_ms, request))?;
+        out.write_all(b"\n")?;
+        Ok(())
+    }
+
+    /// Join each partition into a file of its own, then merge those back
+    /// into request line order, so examples, samples and first-seen ids
+    /// come out as they do without spilling
+    fn join(self, global: &mut GlobalStats, summary: &mut JoinSummary) -> Result<()> {
+        for out in self.responses.into_iter().chain(self.requests) {
+            out.into_inner()
+                .map_err(|e| e.into_error())
+                .context("Failed to write join spill file")?;
+        }
+        let path = |side: &str, i: usize| self.dir.path().join(format!("{side}-{i}.jsonl"));
+        let lines = |side: &str, i: usize| -> Result<std::io::Lines<BufReader<File>>> {
+            let path = path(side, i);
+            let file =
+                File::open(&path).with_context(|| format!("Failed to open {}", path.display()))?;
+            Ok(BufReader::new(file).lines())
+        };
+
+        // Requests went into each partition in line order, so each joined
+        // file is sorted by line number
+        for i in 0..SPILL_PARTITIONS {
+            let mut table: HashMap<String, Value> = HashMap::new();
+            for line in lines("responses", i)? {
+                let (id, response): (String, Value) = serde_json::from_str(&line?)?;
+                table.entry(id).or_insert(response);
+            }
+            let joined_path = path("joined", i);
+            let mut joined = BufWriter::new(
+                File::create(&joined_path)
+                    .with_context(|| format!("Failed to create {}", joined_path.display()))?,
+            );
+            for line in lines("requests", i)? {
+                let (line_no, ts_ms, request): (u64, Option<u64>, Value) =
+                    serde_json::from_str(&line?)?;
+                let response = request["id"].as_str().and_then(|id| table.remove(id));
+                serde_json::to_writer(&mut joined, &(line_no, ts_ms, request, response))?;
+                joined.write_all(b"\n")?;
+            }
+            joined
+                .into_inner()
+                .map_err(|e| e.into_error())
+                .context("Failed to write join spill file")?;
+            summary.unmatched_responses += table.len() as u64;
+        }
+
+        let mut partitions = (0..SPILL_PARTITIONS)
+            .map(|i| lines("joined", i))
+            .collect::<Result<Vec<_>>>()?;
+        let mut next = |i: usize| -> Result<Option<Joined>> {
+            match partitions[i].next() {
+                Some(line) => Ok(Some(serde_json::from_str(&line?)?)),
+                None => Ok(None),
+            }
+        };
+        // The next record of each partition, and a heap of their line
+        // numbers to take the lowest from
+        let mut pending: Vec<Option<Joined>> = Vec::with_capacity(SPILL_PARTITIONS);
+        let mut heads = BinaryHeap::new();
+        for i in 0..SPILL_PARTITIONS {
+            let joined = next(i)?;
+            if let Some((line_no, ..)) = &joined {
+                heads.push(Reverse((*line_no, i)));
+            }
+            pending.push(joined);
+        }
+        while let Some(Reverse((_, i))) = heads.pop() {
+            let Some((line_no, ts_ms, request, response)) = pending[i].take() else {
+                continue;
+            };
+            process_joined(global, summary, line_no, request, ts_ms, response);
+            pending[i] = next(i)?;
+            if let Some((line_no, ..)) = &pending[i] {
+                heads.push(Reverse((*line_no, i)));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{parse_scan_args, SortBy};
+    use crate::examples::{ExampleStore, SampleStore};
+    use crate::input::{OnError, SkippedLines};
+    use crate::report::{build_format_summaries, build_report_data};
+
+    const REQUESTS: &str = r#"{"ts_ms": 60000, "request": {"id": "a", "imp": [{"banner": {"w": 300, "h": 250}}]}}
+{"id": "b", "imp": [{"banner": {"w": 300, "h": 250}}]}
+{"id": "c", "imp": [{"banner": {"w": 728, "h": 90}}]}
+not json
+"#;
+
+    const RESPONSES: &str = r#"{"id": "a", "seatbid": [{"bid": [{"price": 1.0}]}]}
+{"request_id": "c", "response": {"seatbid": [{"bid": [{"price": 3.0}]}]}}
+{"id": "zzz", "seatbid": [{"bid": [{"price": 9.0}]}]}
+"#;
+
+    fn join(requests: &str, responses: &str, memory_cap_bytes: u64) -> (GlobalStats, JoinSummary) {
+        let mut global = GlobalStats::new();
+        global.examples = ExampleStore::new(1, 100);
+        global.samples = SampleStore::new(1, 100);
+        let mut errors = ErrorHandler::new(OnError::Skip, None, None).unwrap();
+        let summary = join_logs(
+            requests.as_bytes(),
+            responses.as_bytes(),
+            memory_cap_bytes,
+            &mut global,
+            Sampler::All,
+            &mut errors,
+            None,
+        )
+        .unwrap();
+        // Only the one malformed line in REQUESTS
+        assert_eq!(errors.skipped.count, u64::from(requests == REQUESTS));
+        (global, summary)
+    }
+
+    /// report.json of a joined scan, without its generation time
+    fn report(global: &GlobalStats) -> Value {
+        let config = parse_scan_args("logs.jsonl".into(), &[]).unwrap();
+        let summaries = build_format_summaries(global, 0, SortBy::Format);
+        let report = build_report_data(global, &config, &summaries, None, &SkippedLines::default());
+        let mut json = serde_json::to_value(&report).unwrap();
+        json["metadata"]["generated_at_ms"].take();
+        json
+    }
+
+    #[test]
+    fn test_join_in_memory_and_spilled_agree() {
+        let (global, summary) = join(REQUESTS, RESPONSES, u64::MAX);
+        assert_eq!(
+            summary,
+            JoinSummary {
+                requests: 3,
+                matched: 2,
+                unmatched_responses: 1,
+                spilled: false,
+            }
+        );
+        let banner = global.by_raw_format.get(&(300, 250)).unwrap();
+        assert_eq!((banner.requests, banner.bids), (2, 1));
+        assert_eq!(global.by_raw_format.get(&(728, 90)).unwrap().bids, 1);
+        assert_eq!(global.time_stats.len(), 1);
+
+        let (spilled, spilled_summary) = join(REQUESTS, RESPONSES, 0);
+        assert!(spilled_summary.spilled);
+        assert_eq!(spilled_summary.matched, 2);
+        assert_eq!(spilled_summary.unmatched_responses, 1);
+        assert_eq!(spilled.by_raw_format, global.by_raw_format);
+
+        // Spilled partitions are merged back in line order, so the
+        // order-sensitive outputs (examples, samples, first-seen duplicate
+        // ids) match too
+        let mut requests = String::new();
+        let mut responses = String::new();
+        for n in 0..200 {
+            let (w, h) = [(300, 250), (728, 90), (320, 50)][n % 3];
+            let id = format!("req-{}", n % 150);
+            requests.push_str(&format!(
+                "{{\"ts_ms\": {}, \"request\": {{\"id\": \"{id}\", \"imp\": [{{\"banner\": {{\"w\": {w}, \"h\": {h}}}}}]}}}}\n",
+                60000 + n * 1000
+            ));
+            if n % 4 == 0 {
+                responses.push_str(&format!(
+                    "{{\"id\": \"{id}\", \"seatbid\": [{{\"bid\": [{{\"price\": 1.5}}]}}]}}\n"
+                ));
+            }
+        }
+        let (in_memory, _) = join(&requests, &responses, u64::MAX);
+        let (spilled, summary) = join(&requests, &responses, 0);
+        assert!(summary.spilled);
+        assert_eq!(report(&spilled), report(&in_memory));
+    }
+}
@@ -0,0 +1,321 @@
+use anyhow::{bail, Context, Result};
+
+use crate::cli::parse_duration_secs;
+
+/// Group id used when the URI doesn't set one
+const DEFAULT_GROUP: &str = "cat_scan";
+
+/// A bounded scan stops once no message arrived for this long
+const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 10;
+
+/// Where to start when the consumer group has no committed offset
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KafkaOffset {
+    Earliest,
+    Latest,
+}
+
+/// A Kafka topic given as an input path:
+///
+/// `kafka://broker1:9092,broker2:9092/topic?group=cat_scan&offset=earliest`
+///
+/// Query parameters: `group`, `offset` (earliest|latest), and for scans the
+/// bounds `max_messages`, `duration` (stop after consuming this long) and
+/// `idle_timeout` (stop once caught up, default 10s). Any parameter with a
+/// dot in its name (e.g. `security.protocol=SASL_SSL`) is passed straight to
+/// librdkafka.
+#[derive(Debug, Clone, PartialEq)]
+pub struct KafkaSource {
+    pub brokers: String,
+    pub topic: String,
+    pub group: String,
+    pub offset: Option<KafkaOffset>,
+    pub max_messages: Option<u64>,
+    pub duration_secs: Option<u64>,
+    pub idle_timeout_secs: u64,
+    pub client_config: Vec<(String, String)>,
+}
+
+impl KafkaSource {
+    /// Parse a kafka:// URI; returns None for any other input path
+    pub fn parse(uri: &str) -> Option<Result<Self>> {
+        let stripped = uri.strip_prefix("kafka://")?;
+        Some(Self::parse_parts(stripped).with_context(|| format!("invalid Kafka URI '{uri}'")))
+    }
+
+    fn parse_parts(stripped: &str) -> Result<Self> {
+        let (location, query) = stripped.split_once('?').unwrap_or((stripped, ""));
+        let (brokers, topic) = location
+            .split_once('/')
+            .context("expected kafka://BROKERS/TOPIC")?;
+        if brokers.is_empty() || topic.is_empty() || topic.contains('/') {
+            bail!("expected kafka://BROKERS/TOPIC");
+        }
+
+        let mut source = Self {
+            brokers: brokers.to_string(),
+            topic: topic.to_string(),
+            group: DEFAULT_GROUP.to_string(),
+            offset: None,
+            max_messages: None,
+            duration_secs: None,
+            idle_timeout_secs: DEFAULT_IDLE_TIMEOUT_SECS,
+            client_config: Vec::new(),
+        };
+        for param in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = param
+                .split_once('=')
+                .with_context(|| format!("expected key=value, got '{param}'"))?;
+            match key {
+                "group" => source.group = value.to_string(),
+                "offset" => {
+                    source.offset = Some(match value {
+                        "earliest" => KafkaOffset::Earliest,
+                        "latest" => KafkaOffset::Latest,
+                        other => bail!("invalid offset '{other}', expected earliest|latest"),
+                    })
+                }
+                "max_messages" => {
+                    source.max_messages =
+                        Some(value.parse().context("invalid value for max_messages")?)
+                }
+                "duration" => source.duration_secs = Some(parse_duration_secs(value)?),
+                "idle_timeout" => source.idle_timeout_secs = parse_duration_secs(value)?,
+                key if key.contains('.') => source
+                    .client_config
+                    .push((key.to_string(), value.to_string())),
+                other => bail!("unknown parameter '{other}'"),
+            }
+        }
+        Ok(source)
+    }
+}
+
+#[cfg(feature = "kafka")]
+mod consumer {
+    use std::{
+        io::{self, Read},
+        time::{Duration, Instant},
+    };
+
+    use anyhow::{Context, Result};
+    use rdkafka::{
+        consumer::{BaseConsumer, Consumer},
+        error::{KafkaError, RDKafkaErrorCode},
+        ClientConfig, Message,
+    };
+
+    use super::{KafkaOffset, KafkaSource};
+
+    const POLL_TIMEOUT: Duration = Duration::from_millis(500);
+
+    /// librdkafka retries most errors by itself (and logs them); only give
+    /// up when no broker is reachable at all
+    fn is_fatal(e: &KafkaError) -> bool {
+        e.rdkafka_error_code() == Some(RDKafkaErrorCode::AllBrokersDown)
+    }
+
+    pub fn subscribe(source: &KafkaSource, default_offset: KafkaOffset) -> Result<BaseConsumer> {
+        let offset = match source.offset.unwrap_or(default_offset) {
+            KafkaOffset::Earliest => "earliest",
+            KafkaOffset::Latest => "latest",
+        };
+        let mut config = ClientConfig::new();
+        config
+            .set("bootstrap.servers", &source.brokers)
+            .set("group.id", &source.group)
+            .set("auto.offset.reset", offset);
+        for (key, value) in &source.client_config {
+            config.set(key, value);
+        }
+        let consumer: BaseConsumer = config.create().context("Failed to create Kafka consumer")?;
+        consumer
+            .subscribe(&[source.topic.as_str()])
+            .with_context(|| format!("Failed to subscribe to Kafka topic {}", source.topic))?;
+        Ok(consumer)
+    }
+
+    /// Newline-delimited message payloads as a `Read`, ending at the first
+    /// bound the source sets (message count, duration or idle timeout).
+    pub struct KafkaReader {
+        consumer: BaseConsumer,
+        buf: Vec<u8>,
+        pos: usize,
+        messages: u64,
+        max_messages: Option<u64>,
+        deadline: Option<Instant>,
+        idle_timeout: Duration,
+        last_message: Instant,
+    }
+
+    impl KafkaReader {
+        pub fn new(source: &KafkaSource) -> Result<Self> {
+            let now = Instant::now();
+            Ok(Self {
+                consumer: subscribe(source, KafkaOffset::Earliest)?,
+                buf: Vec::new(),
+                pos: 0,
+                messages: 0,
+                max_messages: source.max_messages,
+                deadline: source.duration_secs.map(|s| now + Duration::from_secs(s)),
+                idle_timeout: Duration::from_secs(source.idle_timeout_secs),
+                last_message: now,
+            })
+        }
+
+        /// Load the next payload into `buf`; false once a bound is hit
+        fn next_message(&mut self) -> io::Result<bool> {
+            loop {
+                if self.max_messages.is_some_and(|max| self.messages >= max)
+                    || self.deadline.is_some_and(|d| Instant::now() >= d)
+                {
+                    return Ok(false);
+                }
+                match self.consumer.poll(POLL_TIMEOUT) {
+                    Some(Ok(message)) => {
+                        self.messages += 1;
+                        self.last_message = Instant::now();
+                        self.buf.clear();
+                        self.buf
+                            .extend_from_slice(message.payload().unwrap_or_default());
+                        if !self.buf.ends_with(b"\n") {
+                            self.buf.push(b'\n');
+                        }
+                        self.pos = 0;
+                        return Ok(true);
+                    }
+                    Some(Err(e)) if is_fatal(&e) => return Err(io::Error::other(e)),
+                    Some(Err(_)) => {}
+                    None if self.last_message.elapsed() >= self.idle_timeout => return Ok(false),
+                    None => {}
+                }
+            }
+        }
+    }
+
+    impl Read for KafkaReader {
+        fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+            while self.pos >= self.buf.len() {
+                if !self.next_message()? {
+                    return Ok(0);
+                }
+            }
+            let n = out.len().min(self.buf.len() - self.pos);
+            out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+            self.pos += n;
+            Ok(n)
+        }
+    }
+
+    /// Consume the topic forever, calling `on_line` for every message
+    pub fn follow<F: FnMut(&str)>(
+        source: &KafkaSource,
+        from_start: bool,
+        mut on_line: F,
+    ) -> Result<()> {
+        let default_offset = if from_start {
+            KafkaOffset::Earliest
+        } else {
+            KafkaOffset::Latest
+        };
+        let consumer = subscribe(source, default_offset)?;
+        loop {
+            match consumer.poll(POLL_TIMEOUT) {
+                Some(Ok(message)) => {
+                    let payload = String::from_utf8_lossy(message.payload().unwrap_or_default());
+                    for line in payload.lines().map(str::trim).filter(|l| !l.is_empty()) {
+                        on_line(line);
+                    }
+                }
+                Some(Err(e)) if is_fatal(&e) => {
+                    return Err(e).context("Failed to consume from Kafka")
+                }
+                Some(Err(_)) | None => {}
+            }
+        }
+    }
+}
+
+#[cfg(feature = "kafka")]
+pub use consumer::{follow, KafkaReader};
+
+#[cfg(not(feature = "kafka"))]
+const NO_KAFKA: &str = "cat_scan was built without Kafka support; rebuild with `--features kafka`";
+
+/// Stand-in for builds without the `kafka` feature
+#[cfg(not(feature = "kafka"))]
+pub struct KafkaReader;
+
+#[cfg(not(feature = "kafka"))]
+impl KafkaReader {
+    pub fn new(_source: &KafkaSource) -> Result<Self> {
+        bail!(NO_KAFKA)
+    }
+}
+
+#[cfg(not(feature = "kafka"))]
+impl std::io::Read for KafkaReader {
+    fn read(&mut self, _out: &mut [u8]) -> std::io::Result<usize> {
+        Ok(0)
+    }
+}
+
+#[cfg(not(feature = "kafka"))]
+pub fn follow<F: FnMut(&str)>(_source: &KafkaSource, _from_start: bool, _on_line: F) -> Result<()> {
+    bail!(NO_KAFKA)
+}
+
+/// When a bounded scan stops, for the startup message
+pub fn describe_bounds(source: &KafkaSource) -> String {
+    let mut bounds = Vec::new();
+    if let Some(max) = source.max_messages {
+        bounds.push(format!("{max} messages"));
+    }
+    if let Some(secs) = source.duration_secs {
+        bounds.push(format!("{secs}s"));
+    }
+    bounds.push(format!("idle {}s", source.idle_timeout_secs));
+    bounds.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_kafka_uri() {
+        assert!(KafkaSource::parse("logs.jsonl").is_none());
+        assert!(KafkaSource::parse("s3://bucket/key").is_none());
+
+        let source = KafkaSource::parse("kafka://b1:9092,b2:9092/bid-logs")
+            .unwrap()
+            .unwrap();
+        assert_eq!(source.brokers, "b1:9092,b2:9092");
+        assert_eq!(source.topic, "bid-logs");
+        assert_eq!(source.group, "cat_scan");
+        assert_eq!(source.offset, None);
+        assert_eq!(source.idle_timeout_secs, 10);
+
+        let source = KafkaSource::parse(
+            "kafka://b1:9092/bid-logs?group=audit&offset=latest&max_messages=5000&duration=10m&idle_timeout=1m&security.protocol=SASL_SSL",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(source.group, "audit");
+        assert_eq!(source.offset, Some(KafkaOffset::Latest));
+        assert_eq!(source.max_messages, Some(5000));
+        assert_eq!(source.duration_secs, Some(600));
+        assert_eq!(source.idle_timeout_secs, 60);
+        assert_eq!(
+            source.client_config,
+            vec![("security.protocol".to_string(), "SASL_SSL".to_string())]
+        );
+
+        assert!(KafkaSource::parse("kafka://b1:9092").unwrap().is_err());
+        assert!(KafkaSource::parse("kafka:///topic").unwrap().is_err());
+        assert!(KafkaSource::parse("kafka://b1/t?offset=middle")
+            .unwrap()
+            .is_err());
+        assert!(KafkaSource::parse("kafka://b1/t?bogus=1").unwrap().is_err());
+    }
+}
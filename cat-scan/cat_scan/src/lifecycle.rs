@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+
+use crate::stats::GlobalStats;
+
+/// When one format/publisher key was first and last seen across runs
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SeenRecord {
+    pub first_run: u64,
+    pub last_run: u64,
+    /// Unix seconds of the first/last run that saw this key
+    pub first_seen: u64,
+    pub last_seen: u64,
+    /// Requests in the last run that saw this key
+    pub last_requests: u64,
+}
+
+/// Cross-run state persisted with `--state FILE`
+#[derive(Debug, Default, Clone, serde::Serialize, serde::Deserialize)]
+pub struct LifecycleState {
+    /// Number of runs recorded so far
+    pub runs: u64,
+    /// Keyed by canonical "WxH"
+    #[serde(default)]
+    pub formats: BTreeMap<String, SeenRecord>,
+    /// Keyed by "ssp|publisher_id"
+    #[serde(default)]
+    pub publishers: BTreeMap<String, SeenRecord>,
+}
+
+/// A key that was present in the previous run but not in this one
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct Disappeared {
+    pub key: String,
+    pub last_seen: u64,
+    pub last_requests: u64,
+}
+
+/// "New this run" / "disappeared" annotations for the report
+#[derive(Debug, Default, Clone, serde::Serialize)]
+pub struct LifecycleReport {
+    /// Runs recorded before this one (nothing is flagged on the first run)
+    pub previous_runs: u64,
+    pub new_formats: Vec<String>,
+    pub disappeared_formats: Vec<Disappeared>,
+    pub new_publishers: Vec<String>,
+    pub disappeared_publishers: Vec<Disappeared>,
+}
+
+impl LifecycleState {
+    /// Load state from `path`; a missing file starts a fresh history
+    pub fn load(path: &str) -> Result<Self> {
+        match std::fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .with_context(|| format!("Failed to parse state file {}", path)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Failed to read state file {}", path)),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).context("Failed to serialize state")?;
+        std::fs::write(path, json).with_context(|| format!("Failed to write state file {}", path))
+    }
+
+    /// Record this run's keys and report what appeared or vanished since the previous run
+    pub fn record_run(&mut self, global: &GlobalStats, now_secs: u64) -> LifecycleReport {
+        let previous_runs = self.runs;
+        self.runs += 1;
+        let run = self.runs;
+
+        let formats = global
+            .by_canonical_format
+            .iter()
+            .map(|(&(w, h), s)| (format!("{}x{}", w, h), s.requests));
+        let publishers = global
+            .by_publisher
+            .iter()
+            .map(|(k, s)| (format!("{}|{}", k.ssp, k.publisher_id), s.requests));
+
+        let (new_formats, disappeared_formats) =
+            update_seen(&mut self.formats, formats, run, now_secs);
+        let (new_publishers, disappeared_publishers) =
+            update_seen(&mut self.publishers, publishers, run, now_secs);
+
+        if previous_runs == 0 {
+            return LifecycleReport::default();
+        }
+        LifecycleReport {
+            previous_runs,
+            new_formats,
+            disappeared_formats,
+            new_publishers,
+            disappeared_publishers,
+        }
+    }
+}
+
+fn update_seen(
+    seen: &mut BTreeMap<String, SeenRecord>,
+    current: impl Iterator<Item = (String, u64)>,
+    run: u64,
+    now_secs: u64,
+) -> (Vec<String>, Vec<Disappeared>) {
+    let mut new_keys = Vec::new();
+    for (key, requests) in current {
+        let record = seen.entry(key.clone()).or_insert_with(|| {
+            new_keys.push(key);
+            SeenRecord {
+                first_run: run,
+                last_run: run,
+                first_seen: now_secs,
+                last_seen: now_secs,
+                last_requests: 0,
+            }
+        });
+        record.last_run = run;
+        record.last_seen = now_secs;
+        record.last_requests = requests;
+    }
+
+    let disappeared = seen
+        .iter()
+        .filter(|(_, r)| r.last_run + 1 == run)
+        .map(|(key, r)| Disappeared {
+            key: key.clone(),
+            last_seen: r.last_seen,
+            last_requests: r.last_requests,
+        })
+        .collect();
+    (new_keys, disappeared)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::{process_record_global, tests::make_record};
+
+    fn run_with(sizes: &[(u32, u32)]) -> GlobalStats {
+        let mut global = GlobalStats::new();
+        for &(w, h) in sizes {
+            process_record_global(&make_record(w, h, false, 0.0), &mut global);
+        }
+        global
+    }
+
+    #[test]
+    fn test_new_and_disappeared_formats() {
+        let mut state = LifecycleState::default();
+
+        let first = state.record_run(&run_with(&[(300, 250), (728, 90)]), 100);
+        assert_eq!(first.previous_runs, 0);
+        assert!(first.new_formats.is_empty());
+
+        let second = state.record_run(&run_with(&[(300, 250), (123, 456)]), 200);
+        assert_eq!(second.previous_runs, 1);
+        assert_eq!(second.new_formats, vec!["123x456".to_string()]);
+        assert_eq!(
+            second.disappeared_formats,
+            vec![Disappeared {
+                key: "728x90".to_string(),
+                last_seen: 100,
+                last_requests: 1,
+            }]
+        );
+
+        // Gone for two runs now: no longer reported as freshly disappeared
+        let third = state.record_run(&run_with(&[(300, 250), (123, 456)]), 300);
+        assert!(third.new_formats.is_empty());
+        assert!(third.disappeared_formats.is_empty());
+        assert_eq!(state.formats["123x456"].first_seen, 200);
+        assert_eq!(state.formats["300x250"].first_run, 1);
+        assert_eq!(state.formats["300x250"].last_run, 3);
+    }
+}
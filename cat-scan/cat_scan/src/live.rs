@@ -0,0 +1,314 @@
+use std::{
+    collections::BTreeMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use axum::{extract::State, response::Html, routing::get, Json, Router};
+
+use crate::cli::{LiveConfig, SortBy};
+use crate::html::render_live_dashboard;
+use crate::input::follow_input;
+use crate::problems::{find_problem_formats, Detectors, ProblemFormat};
+use crate::report::{build_format_summaries, build_ssp_summaries, FormatSummary, SspSummary};
+use crate::stats::{process_record_global, GlobalStats, LogRecord};
+
+/// Minimum requests before a format shows up as a problem in the live view
+const LIVE_PROBLEM_THRESHOLD: u64 = 10;
+
+/// Rows per table in the live dashboard payload
+const LIVE_TOP_ROWS: usize = 20;
+
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Aggregates for the last N minutes, kept as one GlobalStats per minute so
+/// old minutes can be dropped without rescanning anything.
+#[derive(Debug, Default)]
+pub struct RollingWindow {
+    window_minutes: u64,
+    buckets: BTreeMap<u64, GlobalStats>,
+    pub lines_read: u64,
+    pub parse_errors: u64,
+}
+
+impl RollingWindow {
+    pub fn new(window_secs: u64) -> Self {
+        Self {
+            window_minutes: window_secs.div_ceil(60).max(1),
+            ..Self::default()
+        }
+    }
+
+    /// Parse and count one log line. Records are bucketed by their ts_ms,
+    /// falling back to `now_ms` for lines without a timestamp.
+    pub fn observe_line(&mut self, line: &str, now_ms: u64) {
+        self.lines_read += 1;
+        match serde_json::from_str::<LogRecord>(line) {
+            Ok(mut record) => {
+                record.line_no = self.lines_read;
+                self.observe(&record, now_ms)
+            }
+            Err(_) => self.parse_errors += 1,
+        }
+    }
+
+    pub fn observe(&mut self, record: &LogRecord, now_ms: u64) {
+        let minute = record.ts_ms.unwrap_or(now_ms) / 60000;
+        if minute < self.oldest_minute(now_ms) {
+            return;
+        }
+        process_record_global(record, self.buckets.entry(minute).or_default());
+        self.evict(now_ms);
+    }
+
+    fn oldest_minute(&self, now_ms: u64) -> u64 {
+        (now_ms / 60000).saturating_sub(self.window_minutes - 1)
+    }
+
+    /// Drop minute buckets that have fallen out of the window
+    pub fn evict(&mut self, now_ms: u64) {
+        let oldest = self.oldest_minute(now_ms);
+        self.buckets = self.buckets.split_off(&oldest);
+    }
+
+    /// Merge every minute still inside the window into one GlobalStats
+    pub fn merged(&self) -> GlobalStats {
+        let mut merged = GlobalStats::new();
+        for stats in self.buckets.values() {
+            merged.merge(stats);
+        }
+        merged
+    }
+
+    /// Merge only the last `minutes` minutes (up to and including now)
+    pub fn merged_last(&self, minutes: u64, now_ms: u64) -> GlobalStats {
+        let oldest = (now_ms / 60000).saturating_sub(minutes.saturating_sub(1));
+        let mut merged = GlobalStats::new();
+        for stats in self.buckets.range(oldest..).map(|(_, s)| s) {
+            merged.merge(stats);
+        }
+        merged
+    }
+
+    /// Seconds of data available within the last `minutes` minutes, for
+    /// turning request counts into QPS before the window has filled up
+    pub fn covered_secs(&self, minutes: u64, now_ms: u64) -> f64 {
+        let oldest = (now_ms / 60000).saturating_sub(minutes.saturating_sub(1));
+        match self.buckets.range(oldest..).next() {
+            Some((&first, _)) => ((now_ms.saturating_sub(first * 60000)) as f64 / 1000.0).max(1.0),
+            None => 1.0,
+        }
+    }
+
+    pub fn snapshot(&mut self, now_ms: u64) -> LiveSnapshot {
+        self.evict(now_ms);
+        let merged = self.merged();
+        let total_requests = merged.total_requests();
+        let total_bids = merged.total_bids();
+
+        let series: Vec<LivePoint> = self
+            .buckets
+            .iter()
+            .map(|(&minute, stats)| {
+                let requests = stats.total_requests();
+                let bids = stats.total_bids();
+                LivePoint {
+                    minute_ts_ms: minute * 60000,
+                    requests,
+                    bids,
+                    bid_rate: ratio(bids, requests),
+                    qps: requests as f64 / 60.0,
+                }
+            })
+            .collect();
+
+        // The current minute is still filling up, so scale by elapsed seconds
+        let current_minute = now_ms / 60000;
+        let elapsed_secs = ((now_ms % 60000) as f64 / 1000.0).max(1.0);
+        let current_qps = self
+            .buckets
+            .get(&current_minute)
+            .map(|s| s.total_requests() as f64 / elapsed_secs)
+            .unwrap_or(0.0);
+        let covered_secs = self.covered_secs(self.window_minutes, now_ms);
+
+        let mut formats = build_format_summaries(&merged, 0, SortBy::RequestsDesc);
+        formats.truncate(LIVE_TOP_ROWS);
+        let mut ssps = build_ssp_summaries(&merged);
+        ssps.truncate(LIVE_TOP_ROWS);
+        let mut problems =
+            find_problem_formats(&merged, &Detectors::default(), LIVE_PROBLEM_THRESHOLD);
+        problems.truncate(LIVE_TOP_ROWS);
+
+        LiveSnapshot {
+            generated_at_ms: now_ms,
+            window_minutes: self.window_minutes,
+            lines_read: self.lines_read,
+            parse_errors: self.parse_errors,
+            total_requests,
+            total_bids,
+            bid_rate: ratio(total_bids, total_requests),
+            current_qps,
+            avg_qps: total_requests as f64 / covered_secs,
+            series,
+            formats,
+            ssps,
+            problems,
+        }
+    }
+}
+
+pub fn ratio(num: u64, den: u64) -> f64 {
+    if den == 0 {
+        0.0
+    } else {
+        num as f64 / den as f64
+    }
+}
+
+/// One minute of traffic in the live time series
+#[derive(serde::Serialize)]
+pub struct LivePoint {
+    pub minute_ts_ms: u64,
+    pub requests: u64,
+    pub bids: u64,
+    pub bid_rate: f64,
+    pub qps: f64,
+}
+
+/// Payload served by /api/live
+#[derive(serde::Serialize)]
+pub struct LiveSnapshot {
+    pub generated_at_ms: u64,
+    pub window_minutes: u64,
+    pub lines_read: u64,
+    pub parse_errors: u64,
+    pub total_requests: u64,
+    pub total_bids: u64,
+    pub bid_rate: f64,
+    pub current_qps: f64,
+    pub avg_qps: f64,
+    pub series: Vec<LivePoint>,
+    pub formats: Vec<FormatSummary>,
+    pub ssps: Vec<SspSummary>,
+    pub problems: Vec<ProblemFormat>,
+}
+
+#[derive(Clone)]
+struct LiveState {
+    window: Arc<Mutex<RollingWindow>>,
+    page: Arc<String>,
+}
+
+async fn live_page(State(state): State<LiveState>) -> Html<String> {
+    Html(state.page.as_ref().clone())
+}
+
+async fn live_api(State(state): State<LiveState>) -> Json<LiveSnapshot> {
+    let mut window = state.window.lock().unwrap_or_else(|e| e.into_inner());
+    Json(window.snapshot(now_ms()))
+}
+
+/// `cat_scan live`: follow a log file and serve a rolling-window dashboard
+pub async fn run_live(config: LiveConfig) -> Result<()> {
+    let window = Arc::new(Mutex::new(RollingWindow::new(config.window_secs)));
+
+    let follower_window = Arc::clone(&window);
+    let path = config.input_path.clone();
+    let from_start = config.from_start;
+    let follower = thread::spawn(move || {
+        follow_input(&path, from_start, Duration::from_millis(250), |line| {
+            let mut window = follower_window.lock().unwrap_or_else(|e| e.into_inner());
+            window.observe_line(line, now_ms());
+        })
+    });
+
+    let state = LiveState {
+        window,
+        page: Arc::new(render_live_dashboard(
+            &config.input_path,
+            config.window_secs,
+            config.refresh_secs,
+        )),
+    };
+    let app = Router::new()
+        .route("/", get(live_page))
+        .route("/api/live", get(live_api))
+        .with_state(state);
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind live dashboard to {}", addr))?;
+    eprintln!(
+        "Following {} ({}m window), live dashboard at http://{}",
+        config.input_path,
+        config.window_secs.div_ceil(60),
+        addr
+    );
+
+    tokio::select! {
+        served = axum::serve(listener, app) => served.context("Live dashboard server failed"),
+        followed = tokio::task::spawn_blocking(move || follower.join()) => {
+            match followed {
+                Ok(Ok(result)) => result,
+                _ => anyhow::bail!("Log follower thread panicked"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(ts_ms: u64, w: u32, with_bid: bool) -> String {
+        let response = if with_bid {
+            serde_json::json!({"seatbid": [{"bid": [{"price": 1.0}]}]})
+        } else {
+            serde_json::json!({"seatbid": []})
+        };
+        serde_json::json!({
+            "ts_ms": ts_ms,
+            "request": {"imp": [{"banner": {"w": w, "h": 250}}]},
+            "response": response,
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn test_rolling_window_evicts_old_minutes() {
+        let now = 10 * 60000 + 30000;
+        let mut window = RollingWindow::new(5 * 60);
+
+        window.observe_line(&line(now - 9 * 60000, 300, true), now); // too old
+        window.observe_line(&line(now - 4 * 60000, 300, true), now);
+        window.observe_line(&line(now, 300, false), now);
+        window.observe_line("not json", now);
+
+        let snap = window.snapshot(now);
+        assert_eq!(snap.total_requests, 2);
+        assert_eq!(snap.total_bids, 1);
+        assert_eq!(snap.parse_errors, 1);
+        assert_eq!(snap.lines_read, 4);
+        assert_eq!(snap.series.len(), 2);
+
+        // Narrower views only see the most recent minutes
+        assert_eq!(window.merged_last(1, now).total_requests(), 1);
+        assert_eq!(window.merged_last(5, now).total_requests(), 2);
+        assert_eq!(window.covered_secs(1, now), 30.0);
+
+        // Two minutes later the older bucket has aged out
+        let snap = window.snapshot(now + 2 * 60000);
+        assert_eq!(snap.total_requests, 1);
+        assert_eq!(snap.total_bids, 0);
+    }
+}
@@ -0,0 +1,110 @@
+use anyhow::{Context, Result};
+
+use crate::report::HtmlReportData;
+use crate::summary::{percent, thousands, ScanSummary};
+
+/// Problem formats listed in the summary
+const TOP_PROBLEMS: usize = 10;
+
+/// Markdown executive summary (--format md): the headline figures of the
+/// report dashboard and the biggest problem formats with what to do about
+/// them, short enough to post to Slack or paste into an email
+pub fn render_markdown_summary(report: &HtmlReportData) -> String {
+    let summary = ScanSummary::from_report(report);
+    let mut lines = vec![
+        format!("## Cat Scan summary: `{}`", summary.source),
+        String::new(),
+        format!(
+            "- **Requests:** {} ({} bids)",
+            thousands(summary.requests),
+            thousands(summary.bids)
+        ),
+        format!("- **Bid rate:** {}", percent(summary.bid_rate)),
+        format!(
+            "- **Wasted traffic:** {} ({} requests for {} formats that never got a bid)",
+            percent(summary.wasted_share),
+            thousands(summary.wasted_requests),
+            summary.zero_bid_formats
+        ),
+        format!("- **Problem formats:** {}", summary.problem_formats),
+    ];
+    if let (Some(requests), Some(share)) =
+        (summary.unauthorized_requests, summary.unauthorized_share)
+    {
+        lines.push(format!(
+            "- **Unauthorized supply:** {} ({} requests with a seller id missing from sellers.json)",
+            percent(share),
+            thousands(requests)
+        ));
+    }
+    if let Some(rate) = summary.sample_rate {
+        lines.push(format!(
+            "- _Sampled {:.2}% of lines: counts are scaled estimates_",
+            rate * 100.0
+        ));
+    }
+    if let Some(test) = &report.test_traffic {
+        lines.push(format!(
+            "- _{} test requests (test=1) {}_",
+            thousands(test.requests),
+            if test.excluded {
+                "excluded from these numbers"
+            } else {
+                "included; --exclude-test leaves them out"
+            }
+        ));
+    }
+
+    if let Some(alerts) = report.alerts.as_ref().filter(|a| !a.is_empty()) {
+        lines.push(String::new());
+        lines.push(format!("### {} alerts", alerts.len()));
+        lines.push(String::new());
+        for alert in alerts {
+            lines.push(format!("- {} (rule `{}`)", alert.message, alert.rule));
+        }
+    }
+
+    if !report.problems.is_empty() {
+        lines.push(String::new());
+        lines.push(format!(
+            "### Top {} problems",
+            report.problems.len().min(TOP_PROBLEMS)
+        ));
+        lines.push(String::new());
+        lines.push("| Format | Requests | Bid rate | Problem | Recommendation |".to_string());
+        lines.push("|:-------|---------:|---------:|:--------|:---------------|".to_string());
+        for p in report.problems.iter().take(TOP_PROBLEMS) {
+            lines.push(format!(
+                "| {}x{} | {} | {} | {} | {} |",
+                p.w,
+                p.h,
+                thousands(p.requests),
+                percent(p.bid_rate),
+                p.label(),
+                p.recommendation()
+            ));
+        }
+        if report.problems.len() > TOP_PROBLEMS {
+            lines.push(String::new());
+            lines.push(format!(
+                "_{} more in the full report._",
+                report.problems.len() - TOP_PROBLEMS
+            ));
+        }
+    }
+    // Provenance footer, in a code block so paths and flags stay verbatim
+    lines.push(String::new());
+    lines.push("```text".to_string());
+    lines.extend(report.metadata.lines());
+    lines.push("```".to_string());
+    lines.push(String::new());
+    lines.join("\n")
+}
+
+/// Write summary.md (the --format md summary) into the output directory
+pub fn write_markdown_summary(out_dir: &str, summary: &str) -> Result<()> {
+    let path = format!("{}/summary.md", out_dir);
+    std::fs::write(&path, summary).with_context(|| format!("Failed to write {}", path))?;
+    eprintln!("Markdown summary written to: {}", path);
+    Ok(())
+}
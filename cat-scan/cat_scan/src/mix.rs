@@ -0,0 +1,83 @@
+use std::collections::BTreeMap;
+
+use crate::stats::{bid_rate, GlobalStats, PublisherKey};
+
+/// Publisher bid rates standardized to the scan's format mix, so
+/// publishers can be ranked without rewarding whoever sends more of the
+/// formats the bidder likes.
+///
+/// Each format a publisher sends is weighted by its share of all
+/// requests rather than of the publisher's own: the publisher's bid rate
+/// on a format counts as much as that format does in the whole scan.
+/// Weights are renormalized over the formats the publisher sends, so one
+/// with only 300x250s gets its 300x250 bid rate.
+pub fn adjusted_bid_rates(global: &GlobalStats) -> BTreeMap<&PublisherKey, f64> {
+    let total: u64 = global
+        .by_canonical_format
+        .values()
+        .map(|stats| stats.requests)
+        .sum();
+    if total == 0 {
+        return BTreeMap::new();
+    }
+    let weight = |format: &(u32, u32)| {
+        global
+            .by_canonical_format
+            .get(format)
+            .map_or(0.0, |stats| stats.requests as f64 / total as f64)
+    };
+
+    // (weighted bid rate, weight) per publisher
+    let mut sums: BTreeMap<&PublisherKey, (f64, f64)> = BTreeMap::new();
+    for ((key, format), stats) in &global.by_publisher_format {
+        let weight = weight(format);
+        let sum = sums.entry(key).or_default();
+        sum.0 += weight * bid_rate(stats);
+        sum.1 += weight;
+    }
+    sums.into_iter()
+        .filter(|(_, (_, weight))| *weight > 0.0)
+        .map(|(key, (rate, weight))| (key, rate / weight))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::process_record_global;
+    use crate::stats::tests::make_record;
+
+    #[test]
+    fn test_adjusted_bid_rates() {
+        let mut global = GlobalStats::new();
+        let mut observe = |publisher: &str, w, h, bid| {
+            let mut record = make_record(w, h, bid, 1.0);
+            record.request["site"] = serde_json::json!({ "publisher": { "id": publisher } });
+            process_record_global(&record, &mut global);
+        };
+        // Both publishers bid 50% on 300x250 and 0% on 728x90, but pub-a
+        // sends mostly 300x250s and pub-b mostly 728x90s
+        for (publisher, big, small) in [("pub-a", 8, 2), ("pub-b", 2, 8)] {
+            for i in 0..big {
+                observe(publisher, 300, 250, i % 2 == 0);
+            }
+            for _ in 0..small {
+                observe(publisher, 728, 90, false);
+            }
+        }
+
+        let rates = adjusted_bid_rates(&global);
+        let rate = |publisher: &str| {
+            let key = PublisherKey {
+                ssp: String::new(),
+                publisher_id: publisher.to_string(),
+            };
+            rates[&key]
+        };
+        assert!((rate("pub-a") - 0.25).abs() < 1e-9);
+        assert!((rate("pub-b") - 0.25).abs() < 1e-9);
+
+        let raw: Vec<f64> = global.by_publisher.values().map(bid_rate).collect();
+        assert_eq!(raw, vec![0.4, 0.1]);
+    }
+}
@@ -0,0 +1,231 @@
+use anyhow::{bail, Context, Result};
+use serde_json::{json, Value};
+
+use crate::problems::ProblemFormat;
+use crate::report::HtmlReportData;
+use crate::summary::{percent, thousands, ScanSummary};
+
+/// Problem formats listed in a Slack message
+const SLACK_PROBLEMS: usize = 5;
+
+/// Alerts spelled out in a Slack message; the rest are counted
+const SLACK_ALERTS: usize = 5;
+
+/// Body of the webhook post (--notify-format)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NotifyFormat {
+    /// {"summary": ScanSummary, "alerts": [...], "problems": [...]}
+    Json,
+    /// A Slack incoming-webhook message ({"text": ...})
+    Slack,
+}
+
+impl NotifyFormat {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "json" => Some(Self::Json),
+            "slack" => Some(Self::Slack),
+            _ => None,
+        }
+    }
+
+    /// Slack for Slack incoming webhooks, JSON anywhere else
+    pub fn for_url(url: &str) -> Self {
+        if url.contains("hooks.slack.com") {
+            Self::Slack
+        } else {
+            Self::Json
+        }
+    }
+}
+
+/// Limits past which a notification is flagged as an alert
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AlertThresholds {
+    /// --alert-problems: more problem formats than this
+    pub problem_formats: Option<usize>,
+    /// --alert-wasted: a larger share of requests to zero-bid formats
+    pub wasted_share: Option<f64>,
+}
+
+impl AlertThresholds {
+    /// One line per threshold the scan exceeded
+    pub fn check(&self, summary: &ScanSummary) -> Vec<String> {
+        let mut alerts = Vec::new();
+        if let Some(max) = self.problem_formats {
+            if summary.problem_formats > max {
+                alerts.push(format!(
+                    "{} problem formats (threshold {})",
+                    summary.problem_formats, max
+                ));
+            }
+        }
+        if let Some(max) = self.wasted_share {
+            if summary.wasted_share > max {
+                alerts.push(format!(
+                    "{} of traffic wasted on zero-bid formats (threshold {})",
+                    percent(summary.wasted_share),
+                    percent(max)
+                ));
+            }
+        }
+        alerts
+    }
+}
+
+/// Where to post a summary after each scan (--notify-webhook)
+#[derive(Debug, Clone)]
+pub struct NotifyConfig {
+    pub url: String,
+    pub format: NotifyFormat,
+    pub thresholds: AlertThresholds,
+    /// --alerts-only: post only when a threshold is exceeded
+    pub alerts_only: bool,
+}
+
+fn json_payload(summary: &ScanSummary, alerts: &[String], problems: &[ProblemFormat]) -> Value {
+    json!({
+        "summary": summary,
+        "alert": !alerts.is_empty(),
+        "alerts": alerts,
+        "problems": problems,
+    })
+}
+
+fn slack_payload(summary: &ScanSummary, alerts: &[String], problems: &[ProblemFormat]) -> Value {
+    let mut lines = Vec::new();
+    if !alerts.is_empty() {
+        let mut text = alerts[..alerts.len().min(SLACK_ALERTS)].join("; ");
+        if alerts.len() > SLACK_ALERTS {
+            text.push_str(&format!(" and {} more", alerts.len() - SLACK_ALERTS));
+        }
+        lines.push(format!(":rotating_light: *Alert:* {}", text));
+    }
+    lines.push(format!("*Cat Scan* `{}`", summary.source));
+    lines.push(format!(
+        "{} requests · bid rate {} · {} wasted · {} problem formats",
+        thousands(summary.requests),
+        percent(summary.bid_rate),
+        percent(summary.wasted_share),
+        summary.problem_formats
+    ));
+    for p in problems.iter().take(SLACK_PROBLEMS) {
+        lines.push(format!(
+            "• `{}x{}` {} requests, {}: {}",
+            p.w,
+            p.h,
+            thousands(p.requests),
+            p.label().to_lowercase(),
+            p.recommendation()
+        ));
+    }
+    json!({ "text": lines.join("\n") })
+}
+
+/// The body posted for `report`, and whether any threshold was exceeded
+pub fn notification(config: &NotifyConfig, report: &HtmlReportData) -> (Value, bool) {
+    let summary = ScanSummary::from_report(report);
+    let mut alerts = config.thresholds.check(&summary);
+    alerts.extend(report.alerts.iter().flatten().map(|a| a.message.clone()));
+    let payload = match config.format {
+        NotifyFormat::Json => json_payload(&summary, &alerts, &report.problems),
+        NotifyFormat::Slack => slack_payload(&summary, &alerts, &report.problems),
+    };
+    (payload, !alerts.is_empty())
+}
+
+/// Post the scan summary to the webhook; returns whether it was an alert,
+/// or None when --alerts-only held back a scan within the thresholds
+pub fn send_notification(config: &NotifyConfig, report: &HtmlReportData) -> Result<Option<bool>> {
+    let (payload, alert) = notification(config, report);
+    if config.alerts_only && !alert {
+        return Ok(None);
+    }
+    match ureq::post(&config.url)
+        .set("Content-Type", "application/json")
+        .send_string(&payload.to_string())
+    {
+        Ok(_) => Ok(Some(alert)),
+        Err(ureq::Error::Status(code, response)) => {
+            let message = response.into_string().unwrap_or_default();
+            bail!("Webhook returned {}: {}", code, message.trim())
+        }
+        Err(e) => Err(e).with_context(|| format!("Failed to post to webhook {}", config.url)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn summary(problem_formats: usize, wasted_share: f64) -> ScanSummary {
+        ScanSummary {
+            source: "logs.jsonl".to_string(),
+            requests: 12_345,
+            bids: 1_000,
+            bid_rate: 0.081,
+            wasted_requests: 0,
+            wasted_share,
+            zero_bid_formats: 1,
+            healthy_formats: 0,
+            problem_formats,
+            parse_errors: 0,
+            parse_error_share: 0.0,
+            sample_rate: None,
+            test_requests: None,
+            avg_qps: None,
+            peak_qps: None,
+            alerts: None,
+            unauthorized_requests: None,
+            unauthorized_share: None,
+        }
+    }
+
+    #[test]
+    fn test_alert_thresholds() {
+        let thresholds = AlertThresholds {
+            problem_formats: Some(5),
+            wasted_share: Some(0.2),
+        };
+        assert!(thresholds.check(&summary(5, 0.2)).is_empty());
+        let alerts = thresholds.check(&summary(6, 0.25));
+        assert_eq!(alerts.len(), 2);
+        assert!(alerts[1].starts_with("25.0% of traffic"), "{}", alerts[1]);
+        assert!(AlertThresholds::default()
+            .check(&summary(100, 1.0))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_payloads() {
+        let problems = vec![ProblemFormat {
+            w: 320,
+            h: 50,
+            requests: 2_000,
+            bids: 0,
+            bid_rate: 0.0,
+            problem_type: "zero_bids".to_string(),
+        }];
+        let alerts = vec!["6 problem formats (threshold 5)".to_string()];
+
+        let slack = slack_payload(&summary(6, 0.1), &alerts, &problems);
+        let text = slack["text"].as_str().unwrap();
+        assert!(text.starts_with(":rotating_light: *Alert:* 6 problem formats"));
+        assert!(text.contains("12,345 requests · bid rate 8.1%"));
+        assert!(text.contains("`320x50` 2,000 requests, zero bids"));
+
+        let body = json_payload(&summary(6, 0.1), &[], &problems);
+        assert_eq!(body["alert"], false);
+        assert_eq!(body["summary"]["requests"], 12_345);
+        assert_eq!(body["problems"][0]["w"], 320);
+
+        assert_eq!(
+            NotifyFormat::for_url("https://hooks.slack.com/services/T/B/x"),
+            NotifyFormat::Slack
+        );
+        assert_eq!(
+            NotifyFormat::for_url("https://example.com/hook"),
+            NotifyFormat::Json
+        );
+    }
+}
@@ -0,0 +1,301 @@
+// Rust types for proto/cat_scan_log.proto, in the form prost-build emits
+// for it. Keep the two in sync: tags and wire types must match the proto.
+
+#[derive(Clone, PartialEq, ::prost::Message, serde::Serialize)]
+pub struct LogEntry {
+    #[prost(message, optional, tag = "1")]
+    pub request: Option<BidRequest>,
+    #[prost(message, optional, tag = "2")]
+    pub response: Option<BidResponse>,
+    #[prost(uint64, optional, tag = "3")]
+    pub ts_ms: Option<u64>,
+    /// Becomes request.source.ssp, as in the JSONL logs
+    #[prost(string, optional, tag = "4")]
+    pub ssp: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message, serde::Serialize)]
+pub struct BidRequest {
+    #[prost(string, optional, tag = "1")]
+    pub id: Option<String>,
+    #[prost(message, repeated, tag = "2")]
+    pub imp: Vec<Imp>,
+    #[prost(message, optional, tag = "3")]
+    pub site: Option<Site>,
+    #[prost(message, optional, tag = "4")]
+    pub app: Option<App>,
+    #[prost(message, optional, tag = "5")]
+    pub device: Option<Device>,
+    #[prost(message, optional, tag = "6")]
+    pub user: Option<User>,
+    #[prost(int32, optional, tag = "7")]
+    pub at: Option<i32>,
+    #[prost(int32, optional, tag = "8")]
+    pub tmax: Option<i32>,
+    #[prost(string, repeated, tag = "11")]
+    pub cur: Vec<String>,
+    #[prost(string, repeated, tag = "12")]
+    pub bcat: Vec<String>,
+    #[prost(string, repeated, tag = "13")]
+    pub badv: Vec<String>,
+    #[prost(message, optional, tag = "14")]
+    pub regs: Option<Regs>,
+    #[prost(int32, optional, tag = "15")]
+    pub test: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message, serde::Serialize)]
+pub struct Imp {
+    #[prost(string, optional, tag = "1")]
+    pub id: Option<String>,
+    #[prost(message, optional, tag = "2")]
+    pub banner: Option<Banner>,
+    #[prost(message, optional, tag = "3")]
+    pub video: Option<Video>,
+    #[prost(int32, optional, tag = "6")]
+    pub instl: Option<i32>,
+    #[prost(string, optional, tag = "7")]
+    pub tagid: Option<String>,
+    #[prost(double, optional, tag = "8")]
+    pub bidfloor: Option<f64>,
+    #[prost(string, optional, tag = "9")]
+    pub bidfloorcur: Option<String>,
+    #[prost(int32, optional, tag = "12")]
+    pub secure: Option<i32>,
+    #[prost(message, optional, tag = "13")]
+    pub native: Option<Native>,
+    #[prost(message, optional, tag = "15")]
+    pub audio: Option<Audio>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message, serde::Serialize)]
+pub struct Format {
+    #[prost(int32, optional, tag = "1")]
+    pub w: Option<i32>,
+    #[prost(int32, optional, tag = "2")]
+    pub h: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message, serde::Serialize)]
+pub struct Banner {
+    #[prost(int32, optional, tag = "1")]
+    pub w: Option<i32>,
+    #[prost(int32, optional, tag = "2")]
+    pub h: Option<i32>,
+    #[prost(int32, optional, tag = "4")]
+    pub pos: Option<i32>,
+    #[prost(message, repeated, tag = "15")]
+    pub format: Vec<Format>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message, serde::Serialize)]
+pub struct Video {
+    #[prost(string, repeated, tag = "1")]
+    pub mimes: Vec<String>,
+    #[prost(int32, optional, tag = "3")]
+    pub minduration: Option<i32>,
+    #[prost(int32, optional, tag = "4")]
+    pub maxduration: Option<i32>,
+    #[prost(int32, optional, tag = "7")]
+    pub w: Option<i32>,
+    #[prost(int32, optional, tag = "8")]
+    pub h: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message, serde::Serialize)]
+pub struct Audio {
+    #[prost(string, repeated, tag = "1")]
+    pub mimes: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message, serde::Serialize)]
+pub struct Native {
+    #[prost(string, optional, tag = "1")]
+    pub request: Option<String>,
+    #[prost(string, optional, tag = "2")]
+    pub ver: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message, serde::Serialize)]
+pub struct Publisher {
+    #[prost(string, optional, tag = "1")]
+    pub id: Option<String>,
+    #[prost(string, optional, tag = "2")]
+    pub name: Option<String>,
+    #[prost(string, repeated, tag = "3")]
+    pub cat: Vec<String>,
+    #[prost(string, optional, tag = "4")]
+    pub domain: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message, serde::Serialize)]
+pub struct Content {
+    #[prost(string, optional, tag = "1")]
+    pub id: Option<String>,
+    #[prost(string, repeated, tag = "10")]
+    pub cat: Vec<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message, serde::Serialize)]
+pub struct Site {
+    #[prost(string, optional, tag = "1")]
+    pub id: Option<String>,
+    #[prost(string, optional, tag = "2")]
+    pub name: Option<String>,
+    #[prost(string, optional, tag = "3")]
+    pub domain: Option<String>,
+    #[prost(string, repeated, tag = "4")]
+    pub cat: Vec<String>,
+    #[prost(string, optional, tag = "7")]
+    pub page: Option<String>,
+    #[prost(string, optional, tag = "9")]
+    pub r#ref: Option<String>,
+    #[prost(message, optional, tag = "11")]
+    pub publisher: Option<Publisher>,
+    #[prost(message, optional, tag = "12")]
+    pub content: Option<Content>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message, serde::Serialize)]
+pub struct App {
+    #[prost(string, optional, tag = "1")]
+    pub id: Option<String>,
+    #[prost(string, optional, tag = "2")]
+    pub name: Option<String>,
+    #[prost(string, optional, tag = "3")]
+    pub domain: Option<String>,
+    #[prost(string, repeated, tag = "4")]
+    pub cat: Vec<String>,
+    #[prost(string, optional, tag = "7")]
+    pub ver: Option<String>,
+    #[prost(string, optional, tag = "8")]
+    pub bundle: Option<String>,
+    #[prost(message, optional, tag = "11")]
+    pub publisher: Option<Publisher>,
+    #[prost(message, optional, tag = "12")]
+    pub content: Option<Content>,
+    #[prost(string, optional, tag = "16")]
+    pub storeurl: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message, serde::Serialize)]
+pub struct Geo {
+    #[prost(double, optional, tag = "1")]
+    pub lat: Option<f64>,
+    #[prost(double, optional, tag = "2")]
+    pub lon: Option<f64>,
+    #[prost(string, optional, tag = "3")]
+    pub country: Option<String>,
+    #[prost(string, optional, tag = "4")]
+    pub region: Option<String>,
+    #[prost(string, optional, tag = "7")]
+    pub city: Option<String>,
+    #[prost(int32, optional, tag = "9")]
+    pub r#type: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message, serde::Serialize)]
+pub struct Device {
+    #[prost(string, optional, tag = "2")]
+    pub ua: Option<String>,
+    #[prost(string, optional, tag = "3")]
+    pub ip: Option<String>,
+    #[prost(message, optional, tag = "4")]
+    pub geo: Option<Geo>,
+    #[prost(string, optional, tag = "9")]
+    pub ipv6: Option<String>,
+    #[prost(string, optional, tag = "12")]
+    pub make: Option<String>,
+    #[prost(string, optional, tag = "13")]
+    pub model: Option<String>,
+    #[prost(string, optional, tag = "14")]
+    pub os: Option<String>,
+    #[prost(string, optional, tag = "15")]
+    pub osv: Option<String>,
+    #[prost(int32, optional, tag = "17")]
+    pub connectiontype: Option<i32>,
+    #[prost(int32, optional, tag = "18")]
+    pub devicetype: Option<i32>,
+    #[prost(string, optional, tag = "20")]
+    pub ifa: Option<String>,
+    #[prost(int32, optional, tag = "23")]
+    pub lmt: Option<i32>,
+    #[prost(int32, optional, tag = "25")]
+    pub w: Option<i32>,
+    #[prost(int32, optional, tag = "26")]
+    pub h: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message, serde::Serialize)]
+pub struct User {
+    #[prost(string, optional, tag = "1")]
+    pub id: Option<String>,
+    #[prost(string, optional, tag = "2")]
+    pub buyeruid: Option<String>,
+    #[prost(int32, optional, tag = "3")]
+    pub yob: Option<i32>,
+    #[prost(string, optional, tag = "4")]
+    pub gender: Option<String>,
+    #[prost(message, optional, tag = "7")]
+    pub geo: Option<Geo>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message, serde::Serialize)]
+pub struct Regs {
+    #[prost(int32, optional, tag = "1")]
+    pub coppa: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message, serde::Serialize)]
+pub struct BidResponse {
+    #[prost(string, optional, tag = "1")]
+    pub id: Option<String>,
+    #[prost(message, repeated, tag = "2")]
+    pub seatbid: Vec<SeatBid>,
+    #[prost(string, optional, tag = "3")]
+    pub bidid: Option<String>,
+    #[prost(string, optional, tag = "4")]
+    pub cur: Option<String>,
+    #[prost(int32, optional, tag = "6")]
+    pub nbr: Option<i32>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message, serde::Serialize)]
+pub struct SeatBid {
+    #[prost(message, repeated, tag = "1")]
+    pub bid: Vec<Bid>,
+    #[prost(string, optional, tag = "2")]
+    pub seat: Option<String>,
+}
+
+#[derive(Clone, PartialEq, ::prost::Message, serde::Serialize)]
+pub struct Bid {
+    #[prost(string, optional, tag = "1")]
+    pub id: Option<String>,
+    #[prost(string, optional, tag = "2")]
+    pub impid: Option<String>,
+    #[prost(double, optional, tag = "3")]
+    pub price: Option<f64>,
+    #[prost(string, optional, tag = "4")]
+    pub adid: Option<String>,
+    #[prost(string, optional, tag = "5")]
+    pub nurl: Option<String>,
+    #[prost(string, optional, tag = "6")]
+    pub adm: Option<String>,
+    #[prost(string, repeated, tag = "7")]
+    pub adomain: Vec<String>,
+    #[prost(string, optional, tag = "8")]
+    pub iurl: Option<String>,
+    #[prost(string, optional, tag = "9")]
+    pub cid: Option<String>,
+    #[prost(string, optional, tag = "10")]
+    pub crid: Option<String>,
+    #[prost(int32, repeated, packed = "false", tag = "11")]
+    pub attr: Vec<i32>,
+    #[prost(string, optional, tag = "13")]
+    pub dealid: Option<String>,
+    #[prost(int32, optional, tag = "16")]
+    pub w: Option<i32>,
+    #[prost(int32, optional, tag = "17")]
+    pub h: Option<i32>,
+}
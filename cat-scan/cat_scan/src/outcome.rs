@@ -0,0 +1,62 @@
+use serde_json::Value;
+
+/// What happened in the auction after the bid, when a log line carries it
+/// next to the request and response:
+/// `"outcome": {"win": true, "clearing_price": 1.2, "revenue": 0.0012}`
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Outcome {
+    pub win: bool,
+    /// `revenue`, or a won impression's `clearing_price` CPM / 1000
+    pub revenue: f64,
+}
+
+impl Outcome {
+    /// The record's outcome; None when it has no `outcome` object
+    pub fn from_value(outcome: &Value) -> Option<Self> {
+        let outcome = outcome.as_object()?;
+        let win = match outcome.get("win") {
+            Some(Value::Bool(win)) => *win,
+            Some(win) => win.as_u64() == Some(1),
+            None => false,
+        };
+        let revenue = match outcome.get("revenue").and_then(Value::as_f64) {
+            Some(revenue) => revenue,
+            None if win => outcome
+                .get("clearing_price")
+                .and_then(Value::as_f64)
+                .map_or(0.0, |cpm| cpm / 1000.0),
+            None => 0.0,
+        };
+        Some(Self { win, revenue })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_outcome_from_value() {
+        let outcome = |value| Outcome::from_value(&value);
+        assert_eq!(outcome(Value::Null), None);
+        assert_eq!(
+            outcome(json!({ "win": true, "clearing_price": 2.5 })),
+            Some(Outcome {
+                win: true,
+                revenue: 0.0025
+            })
+        );
+        assert_eq!(
+            outcome(json!({ "win": 1, "clearing_price": 2.5, "revenue": 0.003 })),
+            Some(Outcome {
+                win: true,
+                revenue: 0.003
+            })
+        );
+        assert_eq!(
+            outcome(json!({ "win": false, "clearing_price": 2.5 })),
+            Some(Outcome::default())
+        );
+    }
+}
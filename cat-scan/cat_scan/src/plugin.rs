@@ -0,0 +1,243 @@
+use anyhow::Result;
+use serde_json::{Map, Value};
+
+use crate::decode::RecordDecoder;
+use crate::stats::LogRecord;
+
+/// A user-supplied WebAssembly module deriving extra dimensions from each
+/// record (--plugin), e.g. a browser family from device.ua or an in-house
+/// taxonomy from site.cat.
+///
+/// The module exports its `memory` and two functions:
+///
+/// - `cat_scan_alloc(len: i32) -> i32`: room for `len` input bytes
+/// - `cat_scan_enrich(ptr: i32, len: i32) -> i64`: reads
+///   `{"request": ..., "response": ...}` as JSON from `ptr`, and returns
+///   where it wrote its answer as `out_ptr << 32 | out_len`
+///
+/// The answer is a JSON object of dimension name to a string, number, bool
+/// or array of them (`out_len` 0 for none). The dimensions are added to the
+/// request under `ext.enrich`, where --extract and --filter pick them up.
+/// Modules get no imports (no WASI) and are called once per record.
+pub struct Plugin {
+    #[cfg(feature = "wasm")]
+    path: String,
+    #[cfg(feature = "wasm")]
+    runtime: runtime::Runtime,
+}
+
+#[cfg(feature = "wasm")]
+mod runtime {
+    use anyhow::{Context, Result};
+    use wasmtime::{Engine, Instance, Memory, Module, Store, TypedFunc};
+
+    pub struct Runtime {
+        store: Store<()>,
+        memory: Memory,
+        alloc: TypedFunc<i32, i32>,
+        enrich: TypedFunc<(i32, i32), i64>,
+    }
+
+    impl Runtime {
+        pub fn load(path: &str) -> Result<Self> {
+            let engine = Engine::default();
+            // Also accepts the text format (.wat)
+            let module = Module::from_file(&engine, path)
+                .with_context(|| format!("Failed to compile plugin {path}"))?;
+            let mut store = Store::new(&engine, ());
+            let instance = Instance::new(&mut store, &module, &[])
+                .with_context(|| format!("Failed to instantiate plugin {path}"))?;
+            let memory = instance
+                .get_memory(&mut store, "memory")
+                .with_context(|| format!("plugin {path} does not export its memory"))?;
+            let alloc = instance
+                .get_typed_func(&mut store, "cat_scan_alloc")
+                .with_context(|| format!("plugin {path}: cat_scan_alloc"))?;
+            let enrich = instance
+                .get_typed_func(&mut store, "cat_scan_enrich")
+                .with_context(|| format!("plugin {path}: cat_scan_enrich"))?;
+            Ok(Self {
+                store,
+                memory,
+                alloc,
+                enrich,
+            })
+        }
+
+        /// Run the plugin on one JSON input; its JSON answer
+        pub fn call(&mut self, input: &[u8]) -> Result<Vec<u8>> {
+            let len = i32::try_from(input.len()).context("record too large")?;
+            let ptr = self.alloc.call(&mut self.store, len)?;
+            self.memory
+                .write(&mut self.store, ptr as u32 as usize, input)?;
+            let packed = self.enrich.call(&mut self.store, (ptr, len))? as u64;
+            let (out_ptr, out_len) = ((packed >> 32) as usize, (packed & 0xffff_ffff) as usize);
+            let data = self.memory.data(&self.store);
+            let output = data
+                .get(out_ptr..out_ptr + out_len)
+                .context("answer outside the plugin's memory")?;
+            Ok(output.to_vec())
+        }
+    }
+}
+
+#[cfg(feature = "wasm")]
+impl Plugin {
+    pub fn load(path: &str) -> Result<Self> {
+        Ok(Self {
+            path: path.to_string(),
+            runtime: runtime::Runtime::load(path)?,
+        })
+    }
+
+    /// The dimensions the plugin derives for `record`
+    pub fn enrich(&mut self, record: &LogRecord) -> Result<Value, String> {
+        let input = serde_json::json!({ "request": record.request, "response": record.response });
+        let output = self
+            .runtime
+            .call(input.to_string().as_bytes())
+            .map_err(|e| format!("plugin {}: {e:#}", self.path))?;
+        if output.is_empty() {
+            return Ok(Value::Null);
+        }
+        serde_json::from_slice(&output)
+            .map_err(|e| format!("plugin {} answered invalid JSON: {e}", self.path))
+    }
+}
+
+/// Stand-in for builds without the `wasm` feature
+#[cfg(not(feature = "wasm"))]
+impl Plugin {
+    pub fn load(_path: &str) -> Result<Self> {
+        anyhow::bail!("cat_scan was built without plugin support; rebuild with `--features wasm`")
+    }
+
+    pub fn enrich(&mut self, _record: &LogRecord) -> Result<Value, String> {
+        Ok(Value::Null)
+    }
+}
+
+/// Add a plugin's dimensions to the request under `ext.enrich`
+pub fn add_dimensions(request: &mut Value, dimensions: Value) -> Result<(), String> {
+    let dimensions = match dimensions {
+        Value::Null => return Ok(()),
+        Value::Object(dimensions) => dimensions,
+        other => return Err(format!("plugin answered {other} instead of an object")),
+    };
+    let Some(request) = request.as_object_mut() else {
+        return Ok(());
+    };
+    let ext = request
+        .entry("ext")
+        .or_insert_with(|| Value::Object(Map::new()));
+    if !ext.is_object() {
+        *ext = Value::Object(Map::new());
+    }
+    let enrich = ext
+        .as_object_mut()
+        .expect("made an object above")
+        .entry("enrich")
+        .or_insert_with(|| Value::Object(Map::new()));
+    if let Value::Object(enrich) = enrich {
+        enrich.extend(dimensions);
+    }
+    Ok(())
+}
+
+/// Runs each decoded record through the plugins, in order
+pub struct EnrichingDecoder<'a> {
+    inner: Box<dyn RecordDecoder + 'a>,
+    plugins: Vec<Plugin>,
+}
+
+impl<'a> EnrichingDecoder<'a> {
+    pub fn new(inner: Box<dyn RecordDecoder + 'a>, plugins: Vec<Plugin>) -> Self {
+        Self { inner, plugins }
+    }
+}
+
+impl RecordDecoder for EnrichingDecoder<'_> {
+    fn next_frame(&mut self, buf: &mut Vec<u8>) -> Result<bool> {
+        self.inner.next_frame(buf)
+    }
+
+    fn decode(&mut self, frame: &[u8]) -> Result<Option<LogRecord>, String> {
+        let Some(mut record) = self.inner.decode(frame)? else {
+            return Ok(None);
+        };
+        for plugin in &mut self.plugins {
+            let dimensions = plugin.enrich(&record)?;
+            add_dimensions(&mut record.request, dimensions)?;
+        }
+        Ok(Some(record))
+    }
+
+    fn frame_line(&self) -> Option<u64> {
+        self.inner.frame_line()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_add_dimensions() {
+        let mut request = json!({ "id": "r1", "ext": { "gpid": "/home" } });
+        add_dimensions(
+            &mut request,
+            json!({ "browser": "Chrome", "tags": ["a", "b"] }),
+        )
+        .unwrap();
+        add_dimensions(&mut request, Value::Null).unwrap();
+        assert_eq!(
+            request["ext"],
+            json!({ "gpid": "/home", "enrich": { "browser": "Chrome", "tags": ["a", "b"] } })
+        );
+        assert!(add_dimensions(&mut request, json!("Chrome")).is_err());
+    }
+
+    #[cfg(feature = "wasm")]
+    #[test]
+    fn test_wasm_plugin() {
+        // Answers {"app":"yes"} when the record mentions "app", else nothing
+        let wat = r#"(module
+            (memory (export "memory") 1)
+            (data (i32.const 0) "{\"app\":\"yes\"}")
+            (func (export "cat_scan_alloc") (param i32) (result i32) i32.const 1024)
+            (func (export "cat_scan_enrich") (param $ptr i32) (param $len i32) (result i64)
+                (local $i i32)
+                (block $done
+                    (loop $scan
+                        (br_if $done (i32.gt_u (i32.add (local.get $i) (i32.const 3)) (local.get $len)))
+                        (if (i32.eq
+                                (i32.and (i32.load (i32.add (local.get $ptr) (local.get $i))) (i32.const 0xffffff))
+                                (i32.const 0x707061))
+                            (then (return (i64.const 13))))
+                        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+                        (br $scan)))
+                i64.const 0))"#;
+        let path = std::env::temp_dir().join(format!("cat_scan_plugin_{}.wat", std::process::id()));
+        std::fs::write(&path, wat).unwrap();
+        let mut plugin = Plugin::load(path.to_str().unwrap()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let record = |request: Value| LogRecord {
+            request,
+            response: Value::Null,
+            ts_ms: None,
+            outcome: Value::Null,
+            http_status: Value::Null,
+            line_no: 0,
+        };
+        assert_eq!(
+            plugin.enrich(&record(json!({ "app": {} }))),
+            Ok(json!({ "app": "yes" }))
+        );
+        assert_eq!(
+            plugin.enrich(&record(json!({ "site": {} }))),
+            Ok(Value::Null)
+        );
+    }
+}
@@ -0,0 +1,89 @@
+use serde_json::Value;
+
+/// Ad positions (OpenRTB `pos`, list 5.4) in display order; "unknown"
+/// covers pos 0 and requests without one
+pub const POSITIONS: &[&str] = &[
+    "above_fold",
+    "below_fold",
+    "header",
+    "footer",
+    "sidebar",
+    "fullscreen",
+    "maybe_visible",
+    "unknown",
+];
+
+/// Viewability buckets from `imp.metric` in display order; "none" when the
+/// request carries no viewability metric
+pub const VIEWABILITY_BUCKETS: &[&str] = &["80-100%", "60-80%", "40-60%", "0-40%", "none"];
+
+/// Position of a request's first impression, from `banner.pos` or else
+/// `video.pos`
+pub fn ad_position(request: &Value) -> &'static str {
+    let imp = &request["imp"][0];
+    let pos = imp["banner"]["pos"]
+        .as_u64()
+        .or_else(|| imp["video"]["pos"].as_u64());
+    match pos {
+        Some(1) => "above_fold",
+        // Deprecated in 2.5: may or may not be initially visible
+        Some(2) => "maybe_visible",
+        Some(3) => "below_fold",
+        Some(4) => "header",
+        Some(5) => "footer",
+        Some(6) => "sidebar",
+        Some(7) => "fullscreen",
+        _ => "unknown",
+    }
+}
+
+/// Bucket of the viewability the SSP predicts for a request's first
+/// impression (`imp.metric` of type "viewability", 0 to 1)
+pub fn viewability_bucket(request: &Value) -> &'static str {
+    let viewability = request["imp"][0]["metric"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|m| {
+            m["type"]
+                .as_str()
+                .is_some_and(|t| t.eq_ignore_ascii_case("viewability"))
+        })
+        .and_then(|m| m["value"].as_f64());
+    match viewability {
+        None => "none",
+        Some(v) if v >= 0.8 => "80-100%",
+        Some(v) if v >= 0.6 => "60-80%",
+        Some(v) if v >= 0.4 => "40-60%",
+        Some(_) => "0-40%",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_position_and_viewability() {
+        let request = json!({
+            "imp": [{
+                "banner": { "w": 300, "h": 250, "pos": 3 },
+                "metric": [
+                    { "type": "click_through_rate", "value": 0.01 },
+                    { "type": "viewability", "value": 0.72, "vendor": "ias" }
+                ]
+            }]
+        });
+        assert_eq!(ad_position(&request), "below_fold");
+        assert_eq!(viewability_bucket(&request), "60-80%");
+
+        let video = json!({ "imp": [{ "video": { "pos": 7 } }] });
+        assert_eq!(ad_position(&video), "fullscreen");
+        assert_eq!(viewability_bucket(&video), "none");
+
+        let unset = json!({ "imp": [{ "banner": { "pos": 0 } }] });
+        assert_eq!(ad_position(&unset), "unknown");
+        assert_eq!(ad_position(&json!({ "imp": [] })), "unknown");
+    }
+}
@@ -0,0 +1,332 @@
+use serde_json::{json, Map, Value};
+
+use crate::decode::parse_ts_ms;
+use crate::stats::LogRecord;
+
+/// A timestamp given as epoch (s/ms/us/ns) or RFC3339, as epoch ms
+fn ts_ms(value: &Value) -> Option<u64> {
+    match value {
+        Value::Number(n) => parse_ts_ms(&n.to_string()),
+        Value::String(s) => parse_ts_ms(s),
+        _ => None,
+    }
+}
+
+/// The first entry of a Prebid.js size list, which is either one [w, h]
+/// pair or a list of them, plus every pair as OpenRTB `format` objects
+fn banner_from_sizes(sizes: &Value) -> Option<Value> {
+    let pair = |v: &Value| Some((v[0].as_u64()?, v[1].as_u64()?));
+    let pairs: Vec<(u64, u64)> = match pair(sizes) {
+        Some(single) => vec![single],
+        None => sizes.as_array()?.iter().filter_map(pair).collect(),
+    };
+    let (w, h) = *pairs.first()?;
+    let format: Vec<Value> = pairs
+        .iter()
+        .map(|(w, h)| json!({ "w": w, "h": h }))
+        .collect();
+    Some(json!({ "w": w, "h": h, "format": format }))
+}
+
+/// Copy the non-null entries of `from` into `into`
+fn set_present(into: &mut Map<String, Value>, from: &[(&str, &Value)]) {
+    for (key, value) in from {
+        if !value.is_null() {
+            into.insert(key.to_string(), (*value).clone());
+        }
+    }
+}
+
+/// Split one Prebid auction log line into a LogRecord per bidder and ad
+/// unit, with the bidder as the SSP. Two layouts are understood:
+///
+/// - Prebid.js analytics auction objects (`auctionEnd`): `bidderRequests`,
+///   each with a `bidderCode` and `bids` per ad unit, and the bids in
+///   `bidderResponses` or `bidsReceived`
+/// - Prebid Server auction objects: an OpenRTB `request` whose imps name
+///   their bidders under `ext.prebid.bidder`, and a `response` with one
+///   seatbid per bidder. Without bidders the request is taken as is.
+pub fn auction_records(auction: &Value) -> Result<Vec<LogRecord>, String> {
+    if let Some(bidder_requests) = auction["bidderRequests"].as_array() {
+        Ok(prebid_js_records(auction, bidder_requests))
+    } else if auction["request"].is_object() {
+        Ok(prebid_server_records(auction))
+    } else {
+        Err("not a Prebid auction: no bidderRequests or request".to_string())
+    }
+}
+
+fn prebid_js_records(auction: &Value, bidder_requests: &[Value]) -> Vec<LogRecord> {
+    let responses: &[Value] = auction["bidderResponses"]
+        .as_array()
+        .or_else(|| auction["bidsReceived"].as_array())
+        .map_or(&[], Vec::as_slice);
+    let auction_id = &auction["auctionId"];
+    let auction_ts = ts_ms(&auction["auctionStart"]).or_else(|| ts_ms(&auction["timestamp"]));
+
+    let mut records = Vec::new();
+    for bidder_request in bidder_requests {
+        let bidder = bidder_request["bidderCode"].as_str().unwrap_or("");
+        let ts = ts_ms(&bidder_request["auctionStart"]).or(auction_ts);
+        for bid in bidder_request["bids"].as_array().into_iter().flatten() {
+            let ad_unit = &bid["adUnitCode"];
+
+            let mut imp = bid["ortb2Imp"].as_object().cloned().unwrap_or_default();
+            set_present(&mut imp, &[("id", ad_unit)]);
+            if !imp.contains_key("tagid") {
+                set_present(&mut imp, &[("tagid", ad_unit)]);
+            }
+            let sizes = match &bid["mediaTypes"]["banner"]["sizes"] {
+                Value::Null => &bid["sizes"],
+                sizes => sizes,
+            };
+            if let Some(banner) = banner_from_sizes(sizes) {
+                imp.insert("banner".to_string(), banner);
+            }
+
+            let mut request = bidder_request["ortb2"]
+                .as_object()
+                .cloned()
+                .unwrap_or_default();
+            let id = match &bid["bidId"] {
+                Value::Null => auction_id,
+                bid_id => bid_id,
+            };
+            set_present(
+                &mut request,
+                &[("id", id), ("tmax", &bidder_request["timeout"])],
+            );
+            request.insert("imp".to_string(), json!([imp]));
+            if !request.contains_key("site") && !request.contains_key("app") {
+                let referer = &bidder_request["refererInfo"];
+                let mut site = Map::new();
+                set_present(
+                    &mut site,
+                    &[("page", &referer["page"]), ("domain", &referer["domain"])],
+                );
+                if !site.contains_key("page") {
+                    set_present(&mut site, &[("page", &referer["topmostLocation"])]);
+                }
+                request.insert("site".to_string(), Value::Object(site));
+            }
+            let mut request = Value::Object(request);
+            request["source"]["ssp"] = json!(bidder);
+            if !auction_id.is_null() {
+                request["source"]["tid"] = auction_id.clone();
+            }
+
+            // Bids for this request: by bid id, or by bidder and ad unit
+            let received: Vec<&Value> = responses
+                .iter()
+                .filter(|r| match (&r["requestId"], &bid["bidId"]) {
+                    (Value::Null, _) | (_, Value::Null) => {
+                        r["bidderCode"].as_str().or_else(|| r["bidder"].as_str()) == Some(bidder)
+                            && r["adUnitCode"] == *ad_unit
+                    }
+                    (request_id, bid_id) => request_id == bid_id,
+                })
+                .collect();
+            let response = if received.is_empty() {
+                Value::Null
+            } else {
+                let bids: Vec<Value> = received
+                    .iter()
+                    .map(|r| {
+                        let mut out = Map::new();
+                        set_present(
+                            &mut out,
+                            &[
+                                ("id", &r["adId"]),
+                                ("impid", ad_unit),
+                                ("price", &r["cpm"]),
+                                ("w", &r["width"]),
+                                ("h", &r["height"]),
+                                ("crid", &r["creativeId"]),
+                                ("adomain", &r["meta"]["advertiserDomains"]),
+                            ],
+                        );
+                        Value::Object(out)
+                    })
+                    .collect();
+                let mut response = json!({ "seatbid": [{ "seat": bidder, "bid": bids }] });
+                if let Some(cur) = received.iter().find_map(|r| r["currency"].as_str()) {
+                    response["cur"] = json!(cur);
+                }
+                response
+            };
+
+            records.push(LogRecord {
+                request,
+                response,
+                ts_ms: ts,
+                outcome: Value::Null,
+                http_status: Value::Null,
+                line_no: 0,
+            });
+        }
+    }
+    records
+}
+
+fn prebid_server_records(auction: &Value) -> Vec<LogRecord> {
+    let request = &auction["request"];
+    let response = &auction["response"];
+    let ts = ts_ms(&auction["ts_ms"]).or_else(|| ts_ms(&auction["startTime"]));
+    let imps: &[Value] = request["imp"].as_array().map_or(&[], Vec::as_slice);
+
+    let mut records = Vec::new();
+    for imp in imps {
+        let Some(bidders) = imp["ext"]["prebid"]["bidder"].as_object() else {
+            continue;
+        };
+        for bidder in bidders.keys() {
+            let mut split = request.clone();
+            split["imp"] = json!([imp]);
+            split["source"]["ssp"] = json!(bidder);
+            if imps.len() > 1 {
+                if let (Some(id), Some(imp_id)) = (request["id"].as_str(), imp["id"].as_str()) {
+                    split["id"] = json!(format!("{}:{}", id, imp_id));
+                }
+            }
+
+            // The bidder's seatbid, with its bids for this imp
+            let bids: Vec<Value> = response["seatbid"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|seatbid| seatbid["seat"].as_str() == Some(bidder))
+                .flat_map(|seatbid| seatbid["bid"].as_array().into_iter().flatten())
+                .filter(|bid| bid["impid"] == imp["id"] || imp["id"].is_null())
+                .cloned()
+                .collect();
+            let response = if bids.is_empty() {
+                Value::Null
+            } else {
+                let mut split = json!({ "seatbid": [{ "seat": bidder, "bid": bids }] });
+                if !response["cur"].is_null() {
+                    split["cur"] = response["cur"].clone();
+                }
+                split
+            };
+            records.push(LogRecord {
+                request: split,
+                response,
+                ts_ms: ts,
+                outcome: Value::Null,
+                http_status: Value::Null,
+                line_no: 0,
+            });
+        }
+    }
+    if records.is_empty() {
+        records.push(LogRecord {
+            request: request.clone(),
+            response: response.clone(),
+            ts_ms: ts,
+            outcome: Value::Null,
+            http_status: Value::Null,
+            line_no: 0,
+        });
+    }
+    records
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_prebid_auction_records() {
+        let auction = json!({
+            "auctionId": "a1",
+            "auctionStart": 1717200000000u64,
+            "bidderRequests": [{
+                "bidderCode": "appnexus",
+                "timeout": 1000,
+                "refererInfo": { "page": "https://news.example.com/a", "domain": "news.example.com" },
+                "bids": [
+                    { "adUnitCode": "top", "bidId": "b1", "mediaTypes": { "banner": { "sizes": [[728, 90], [970, 90]] } } },
+                    { "adUnitCode": "side", "bidId": "b2", "sizes": [300, 250] }
+                ]
+            }, {
+                "bidderCode": "rubicon",
+                "ortb2": { "site": { "page": "https://news.example.com/a", "publisher": { "id": "pub1" } } },
+                "bids": [{ "adUnitCode": "side", "bidId": "b3", "sizes": [[300, 250]] }]
+            }],
+            "bidsReceived": [
+                { "bidderCode": "appnexus", "adUnitCode": "side", "requestId": "b2", "cpm": 1.25,
+                  "currency": "USD", "width": 300, "height": 250, "creativeId": "c9",
+                  "meta": { "advertiserDomains": ["brand.com"] } }
+            ]
+        });
+        let records = auction_records(&auction).unwrap();
+        assert_eq!(records.len(), 3);
+
+        let top = &records[0];
+        assert_eq!(top.request["id"], "b1");
+        assert_eq!(top.request["tmax"], 1000);
+        assert_eq!(
+            top.request["source"],
+            json!({ "ssp": "appnexus", "tid": "a1" })
+        );
+        assert_eq!(top.request["imp"][0]["banner"]["w"], 728);
+        assert_eq!(top.request["imp"][0]["banner"]["format"][1]["w"], 970);
+        assert_eq!(top.request["site"]["domain"], "news.example.com");
+        assert_eq!(top.response, Value::Null);
+        assert_eq!(top.ts_ms, Some(1_717_200_000_000));
+
+        let side = &records[1];
+        assert_eq!(side.request["imp"][0]["tagid"], "side");
+        assert_eq!(side.response["cur"], "USD");
+        let bid = &side.response["seatbid"][0]["bid"][0];
+        assert_eq!(
+            (bid["price"].as_f64(), &bid["crid"]),
+            (Some(1.25), &json!("c9"))
+        );
+        assert_eq!(bid["adomain"], json!(["brand.com"]));
+
+        assert_eq!(records[2].request["site"]["publisher"]["id"], "pub1");
+        assert_eq!(records[2].response, Value::Null);
+
+        let pbs = json!({
+            "startTime": "2024-06-01T00:00:05.250Z",
+            "request": {
+                "id": "r1",
+                "imp": [
+                    { "id": "1", "banner": { "w": 300, "h": 250 },
+                      "ext": { "prebid": { "bidder": { "appnexus": {}, "ix": {} } } } },
+                    { "id": "2", "banner": { "w": 728, "h": 90 },
+                      "ext": { "prebid": { "bidder": { "ix": {} } } } }
+                ]
+            },
+            "response": {
+                "cur": "USD",
+                "seatbid": [
+                    { "seat": "ix", "bid": [{ "impid": "1", "price": 0.8 }, { "impid": "2", "price": 0.4 }] }
+                ]
+            }
+        });
+        let records = auction_records(&pbs).unwrap();
+        let summary: Vec<_> = records
+            .iter()
+            .map(|r| {
+                (
+                    r.request["source"]["ssp"].as_str().unwrap(),
+                    r.request["id"].as_str().unwrap(),
+                    r.response["seatbid"][0]["bid"][0]["price"].as_f64(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("appnexus", "r1:1", None),
+                ("ix", "r1:1", Some(0.8)),
+                ("ix", "r1:2", Some(0.4)),
+            ]
+        );
+        assert_eq!(records[1].ts_ms, Some(1_717_200_005_000));
+
+        assert!(auction_records(&json!({ "id": "r1" })).is_err());
+    }
+}
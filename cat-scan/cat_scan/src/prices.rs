@@ -0,0 +1,398 @@
+use std::{cmp::Reverse, collections::BTreeMap, io::Write};
+
+use anyhow::{bail, Context, Result};
+
+use crate::metadata::{create_csv, ScanMetadata};
+use crate::stats::scale_count;
+
+/// Upper edges of the default price histogram buckets (CPM); the last
+/// bucket is open-ended
+pub const DEFAULT_PRICE_BUCKETS: &[f64] = &[0.1, 0.5, 1.0, 2.0, 5.0, 10.0, 20.0];
+
+/// Percentiles of the bid price distribution shown in the report
+pub const PRICE_PERCENTILES: &[u32] = &[25, 50, 90, 99];
+
+/// Exact price distribution is kept at this resolution (tenths of a cent)
+const PRICE_STEPS_PER_UNIT: f64 = 1000.0;
+
+/// Parse `--price-buckets`, a comma-separated list of increasing bucket
+/// edges like `0.1,0.5,1,2,5`
+pub fn parse_price_buckets(value: &str) -> Result<Vec<f64>> {
+    let edges = value
+        .split(',')
+        .map(|edge| {
+            edge.trim()
+                .parse::<f64>()
+                .with_context(|| format!("invalid price bucket edge '{}'", edge.trim()))
+        })
+        .collect::<Result<Vec<f64>>>()?;
+    if edges.iter().any(|e| !e.is_finite() || *e <= 0.0) {
+        bail!("--price-buckets edges must be positive prices, got '{value}'");
+    }
+    if edges.windows(2).any(|pair| pair[0] >= pair[1]) {
+        bail!("--price-buckets edges must be increasing, got '{value}'");
+    }
+    Ok(edges)
+}
+
+/// Bid price histograms per canonical format and per SSP, plus the overall
+/// price distribution for percentiles
+#[derive(Debug, Clone)]
+pub struct PriceStats {
+    /// Upper bucket edges; `edges.len() + 1` buckets
+    pub edges: Vec<f64>,
+    pub by_format: BTreeMap<(u32, u32), Vec<u64>>,
+    pub by_ssp: BTreeMap<String, Vec<u64>>,
+    /// Bid counts by price in tenths of a cent
+    pub distribution: BTreeMap<u64, u64>,
+}
+
+impl Default for PriceStats {
+    fn default() -> Self {
+        Self::new(DEFAULT_PRICE_BUCKETS.to_vec())
+    }
+}
+
+fn add_counts(dst: &mut Vec<u64>, src: &[u64]) {
+    if dst.len() < src.len() {
+        dst.resize(src.len(), 0);
+    }
+    for (d, s) in dst.iter_mut().zip(src) {
+        *d += s;
+    }
+}
+
+impl PriceStats {
+    pub fn new(edges: Vec<f64>) -> Self {
+        Self {
+            edges,
+            by_format: BTreeMap::new(),
+            by_ssp: BTreeMap::new(),
+            distribution: BTreeMap::new(),
+        }
+    }
+
+    fn bucket(&self, price: f64) -> usize {
+        self.edges.partition_point(|&edge| edge <= price)
+    }
+
+    /// Count one bid. An empty `ssp` only counts towards the format and
+    /// the overall distribution.
+    pub fn observe(&mut self, format: (u32, u32), ssp: &str, price: f64) {
+        let bucket = self.bucket(price);
+        let buckets = self.edges.len() + 1;
+        self.by_format
+            .entry(format)
+            .or_insert_with(|| vec![0; buckets])[bucket] += 1;
+        if !ssp.is_empty() {
+            self.by_ssp
+                .entry(ssp.to_string())
+                .or_insert_with(|| vec![0; buckets])[bucket] += 1;
+        }
+        let step = (price.max(0.0) * PRICE_STEPS_PER_UNIT).round() as u64;
+        *self.distribution.entry(step).or_default() += 1;
+    }
+
+    pub fn merge(&mut self, other: &PriceStats) {
+        for (format, counts) in &other.by_format {
+            add_counts(self.by_format.entry(*format).or_default(), counts);
+        }
+        for (ssp, counts) in &other.by_ssp {
+            add_counts(self.by_ssp.entry(ssp.clone()).or_default(), counts);
+        }
+        for (step, count) in &other.distribution {
+            *self.distribution.entry(*step).or_default() += count;
+        }
+    }
+
+    /// Multiply counts by `factor` (extrapolating from a sample)
+    pub fn scale(&mut self, factor: f64) {
+        let histograms = self.by_format.values_mut().chain(self.by_ssp.values_mut());
+        for count in histograms.flatten() {
+            scale_count(count, factor);
+        }
+        for count in self.distribution.values_mut() {
+            scale_count(count, factor);
+        }
+    }
+
+    /// Bids counted in the overall distribution
+    pub fn bids(&self) -> u64 {
+        self.distribution.values().sum()
+    }
+
+    /// Nearest-rank percentile of the bid price, None without bids
+    pub fn percentile(&self, percentile: u32) -> Option<f64> {
+        let bids = self.bids();
+        if bids == 0 {
+            return None;
+        }
+        let rank = (bids * percentile as u64).div_ceil(100).max(1);
+        let mut seen = 0;
+        for (step, count) in &self.distribution {
+            seen += count;
+            if seen >= rank {
+                return Some(*step as f64 / PRICE_STEPS_PER_UNIT);
+            }
+        }
+        None
+    }
+
+    /// Bucket labels like "0.10-0.50", the last one open-ended ("20.00+")
+    pub fn labels(&self) -> Vec<String> {
+        let mut lower = 0.0;
+        let mut labels: Vec<String> = self
+            .edges
+            .iter()
+            .map(|&upper| {
+                let label = format!("{:.2}-{:.2}", lower, upper);
+                lower = upper;
+                label
+            })
+            .collect();
+        labels.push(format!("{:.2}+", lower));
+        labels
+    }
+
+    pub fn summarize(&self) -> PriceReport {
+        let row = |key: String, counts: &Vec<u64>| PriceHistogramRow {
+            key,
+            bids: counts.iter().sum(),
+            counts: counts.clone(),
+        };
+        let mut by_format: Vec<PriceHistogramRow> = self
+            .by_format
+            .iter()
+            .map(|((w, h), counts)| row(format!("{}x{}", w, h), counts))
+            .collect();
+        // Stable, so ties stay in (w, h) order
+        by_format.sort_by_key(|row| Reverse(row.bids));
+        PriceReport {
+            buckets: self.labels(),
+            bids: self.bids(),
+            percentiles: PRICE_PERCENTILES
+                .iter()
+                .filter_map(|&percentile| {
+                    Some(PricePercentile {
+                        percentile,
+                        price: self.percentile(percentile)?,
+                    })
+                })
+                .collect(),
+            by_format,
+            by_ssp: self
+                .by_ssp
+                .iter()
+                .map(|(ssp, counts)| row(ssp.clone(), counts))
+                .collect(),
+        }
+    }
+}
+
+/// Ratio between the edges of neighbouring PriceSketch buckets, so a
+/// percentile is within about 1% of the true price
+const SKETCH_GAMMA: f64 = 1.02;
+
+/// Approximate bid price distribution of one summary row, for its median
+/// and p90. Prices fall into buckets growing by SKETCH_GAMMA, so the
+/// sketch stays small however many bids or distinct prices a key sees.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct PriceSketch {
+    /// Bid counts by bucket index (see PriceSketch::bucket)
+    buckets: BTreeMap<i32, u64>,
+    /// Bids at a price of zero or below
+    zero: u64,
+    /// Lowest and highest price seen; percentiles are clamped to them, so
+    /// a key bid at one price reports that price exactly
+    range: Option<(f64, f64)>,
+}
+
+impl PriceSketch {
+    fn bucket(price: f64) -> i32 {
+        (price.ln() / SKETCH_GAMMA.ln()).ceil() as i32
+    }
+
+    /// Price reported for a bucket: the middle of its edges
+    fn value(bucket: i32) -> f64 {
+        2.0 * SKETCH_GAMMA.powi(bucket) / (SKETCH_GAMMA + 1.0)
+    }
+
+    pub fn observe(&mut self, price: f64) {
+        self.widen(price, price);
+        if price > 0.0 {
+            *self.buckets.entry(Self::bucket(price)).or_default() += 1;
+        } else {
+            self.zero += 1;
+        }
+    }
+
+    fn widen(&mut self, low: f64, high: f64) {
+        self.range = Some(match self.range {
+            Some((min, max)) => (min.min(low), max.max(high)),
+            None => (low, high),
+        });
+    }
+
+    pub fn merge(&mut self, other: &PriceSketch) {
+        if let Some((low, high)) = other.range {
+            self.widen(low, high);
+        }
+        for (bucket, count) in &other.buckets {
+            *self.buckets.entry(*bucket).or_default() += count;
+        }
+        self.zero += other.zero;
+    }
+
+    /// Multiply counts by `factor` (extrapolating from a sample)
+    pub fn scale(&mut self, factor: f64) {
+        for count in self.buckets.values_mut() {
+            scale_count(count, factor);
+        }
+        scale_count(&mut self.zero, factor);
+    }
+
+    /// Nearest-rank percentile of the bid price, None without bids
+    pub fn percentile(&self, percentile: u32) -> Option<f64> {
+        let bids = self.zero + self.buckets.values().sum::<u64>();
+        if bids == 0 {
+            return None;
+        }
+        let rank = (bids * percentile as u64).div_ceil(100).max(1);
+        let (min, max) = self.range?;
+        if self.zero >= rank {
+            return Some(min.min(0.0));
+        }
+        let mut seen = self.zero;
+        for (bucket, count) in &self.buckets {
+            seen += count;
+            if seen >= rank {
+                return Some(Self::value(*bucket).clamp(min, max));
+            }
+        }
+        None
+    }
+}
+
+#[derive(serde::Serialize)]
+pub struct PricePercentile {
+    pub percentile: u32,
+    pub price: f64,
+}
+
+/// Bid counts per price bucket for one format or SSP
+#[derive(serde::Serialize)]
+pub struct PriceHistogramRow {
+    pub key: String,
+    pub bids: u64,
+    pub counts: Vec<u64>,
+}
+
+/// Price distribution section of the report
+#[derive(serde::Serialize)]
+pub struct PriceReport {
+    pub buckets: Vec<String>,
+    pub bids: u64,
+    pub percentiles: Vec<PricePercentile>,
+    /// Canonical formats, most bids first
+    pub by_format: Vec<PriceHistogramRow>,
+    pub by_ssp: Vec<PriceHistogramRow>,
+}
+
+/// Write price_histogram.csv (bids per price bucket, per format and SSP)
+/// and price_percentiles.csv (the overall distribution)
+pub fn write_price_csvs(out_dir: &str, report: &PriceReport, meta: &ScanMetadata) -> Result<()> {
+    let path = format!("{}/price_histogram.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    writeln!(csv, "dimension,key,bucket,bids,share")?;
+    let dimensions = [("format", &report.by_format), ("ssp", &report.by_ssp)];
+    for (dimension, rows) in dimensions {
+        for row in rows {
+            for (bucket, count) in report.buckets.iter().zip(&row.counts) {
+                let share = if row.bids == 0 {
+                    0.0
+                } else {
+                    *count as f64 / row.bids as f64
+                };
+                writeln!(
+                    csv,
+                    "{},{},{},{},{:.4}",
+                    dimension, row.key, bucket, count, share
+                )?;
+            }
+        }
+    }
+    eprintln!("Price histogram written to: {}", path);
+
+    let path = format!("{}/price_percentiles.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    writeln!(csv, "percentile,price")?;
+    for p in &report.percentiles {
+        writeln!(csv, "p{},{:.4}", p.percentile, p.price)?;
+    }
+    eprintln!("Price percentiles written to: {}", path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_price_buckets_and_percentiles() {
+        let mut prices = PriceStats::new(parse_price_buckets("0.5, 1,2").unwrap());
+        for price in [0.05, 0.5, 0.75, 1.0, 1.5, 1.5, 2.0, 2.5, 10.0, 0.001] {
+            prices.observe((300, 250), "ssp_a", price);
+        }
+        prices.observe((728, 90), "", 3.0);
+
+        assert_eq!(
+            prices.labels(),
+            vec!["0.00-0.50", "0.50-1.00", "1.00-2.00", "2.00+"]
+        );
+        assert_eq!(prices.by_format[&(300, 250)], vec![2, 2, 3, 3]);
+        assert_eq!(prices.by_ssp["ssp_a"], vec![2, 2, 3, 3]);
+        assert_eq!(prices.by_format[&(728, 90)], vec![0, 0, 0, 1]);
+        assert_eq!(prices.bids(), 11);
+        assert_eq!(prices.percentile(25), Some(0.5));
+        assert_eq!(prices.percentile(50), Some(1.5));
+        assert_eq!(prices.percentile(90), Some(3.0));
+        assert_eq!(prices.percentile(99), Some(10.0));
+
+        let mut doubled = prices.clone();
+        doubled.merge(&prices);
+        prices.scale(2.0);
+        assert_eq!(doubled.by_format, prices.by_format);
+        assert_eq!(doubled.distribution, prices.distribution);
+
+        assert!(parse_price_buckets("1,0.5").is_err());
+        assert!(parse_price_buckets("0,1").is_err());
+        assert!(PriceStats::default().percentile(50).is_none());
+    }
+
+    #[test]
+    fn test_price_sketch_percentiles() {
+        let mut sketch = PriceSketch::default();
+        for price in [0.0, 0.5, 1.0, 1.0, 1.2, 2.0, 2.5, 3.0, 4.0, 250.0] {
+            sketch.observe(price);
+        }
+        let near = |value: Option<f64>, expected: f64| {
+            let value = value.unwrap();
+            assert!((value - expected).abs() <= expected * 0.01, "{value}");
+        };
+        assert_eq!(sketch.percentile(10), Some(0.0));
+        near(sketch.percentile(50), 1.2);
+        near(sketch.percentile(90), 4.0);
+        near(sketch.percentile(100), 250.0);
+
+        let mut doubled = sketch.clone();
+        doubled.merge(&sketch);
+        sketch.scale(2.0);
+        assert_eq!(doubled, sketch);
+        assert!(PriceSketch::default().percentile(50).is_none());
+
+        let mut flat = PriceSketch::default();
+        flat.observe(0.6);
+        flat.observe(0.6);
+        assert_eq!(flat.percentile(90), Some(0.6));
+    }
+}
@@ -0,0 +1,260 @@
+use std::cmp::Reverse;
+
+use anyhow::{bail, Result};
+
+use crate::stats::{is_standard_size, FormatStats, GlobalStats};
+
+/// Problem formats identified during analysis
+#[derive(Debug, serde::Serialize)]
+pub struct ProblemFormat {
+    pub w: u32,
+    pub h: u32,
+    pub requests: u64,
+    pub bids: u64,
+    pub bid_rate: f64,
+    /// Name of the detector that flagged it
+    pub problem_type: String,
+}
+
+impl ProblemFormat {
+    fn detector(&self) -> Option<&'static dyn ProblemDetector> {
+        DETECTORS
+            .iter()
+            .copied()
+            .find(|d| d.name() == self.problem_type)
+    }
+
+    pub fn label(&self) -> &'static str {
+        self.detector().map_or("Problem", |d| d.label())
+    }
+
+    /// What to do about it, for summaries read outside the report
+    pub fn recommendation(&self) -> &'static str {
+        self.detector()
+            .map_or("Review this size", |d| d.recommendation())
+    }
+}
+
+/// What a detector looks at: one raw format with enough volume to judge
+pub struct FormatCheck<'a> {
+    pub w: u32,
+    pub h: u32,
+    pub stats: &'a FormatStats,
+    pub bid_rate: f64,
+}
+
+/// One kind of problem format. To add one, implement this and list it in
+/// DETECTORS; --detectors then turns it on and off by name.
+pub trait ProblemDetector: Sync {
+    /// problem_type in the outputs, and the name --detectors takes
+    fn name(&self) -> &'static str;
+    /// Human-readable problem, e.g. in the Slack and Markdown summaries
+    fn label(&self) -> &'static str;
+    /// What to do about it, for summaries read outside the report
+    fn recommendation(&self) -> &'static str;
+    fn detect(&self, format: &FormatCheck) -> bool;
+}
+
+/// Formats that never got a bid
+struct ZeroBids;
+
+impl ProblemDetector for ZeroBids {
+    fn name(&self) -> &'static str {
+        "zero_bids"
+    }
+
+    fn label(&self) -> &'static str {
+        "Zero bids"
+    }
+
+    fn recommendation(&self) -> &'static str {
+        "Stop listening: ask the SSP to stop sending this size"
+    }
+
+    fn detect(&self, format: &FormatCheck) -> bool {
+        format.stats.bids == 0
+    }
+}
+
+/// Sizes off the IAB standard list
+struct NonStandard;
+
+impl ProblemDetector for NonStandard {
+    fn name(&self) -> &'static str {
+        "non_standard"
+    }
+
+    fn label(&self) -> &'static str {
+        "Non-standard size"
+    }
+
+    fn recommendation(&self) -> &'static str {
+        "Map it to a standard size or stop listening"
+    }
+
+    fn detect(&self, format: &FormatCheck) -> bool {
+        !is_standard_size(format.w, format.h)
+    }
+}
+
+/// Formats that bid, but on under 1% of requests
+struct LowBidRate;
+
+impl ProblemDetector for LowBidRate {
+    fn name(&self) -> &'static str {
+        "low_bid_rate"
+    }
+
+    fn label(&self) -> &'static str {
+        "Low bid rate"
+    }
+
+    fn recommendation(&self) -> &'static str {
+        "Review targeting and floors for this size"
+    }
+
+    fn detect(&self, format: &FormatCheck) -> bool {
+        format.bid_rate < 0.01 && format.stats.bids > 0
+    }
+}
+
+/// Every detector, in the order they are tried: a format is reported once,
+/// under the first detector that flags it
+pub const DETECTORS: &[&dyn ProblemDetector] = &[&ZeroBids, &NonStandard, &LowBidRate];
+
+/// The detectors a scan runs (--detectors; all of them by default)
+#[derive(Clone)]
+pub struct Detectors(Vec<&'static dyn ProblemDetector>);
+
+impl Default for Detectors {
+    fn default() -> Self {
+        Self(DETECTORS.to_vec())
+    }
+}
+
+impl std::fmt::Debug for Detectors {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list()
+            .entries(self.0.iter().map(|d| d.name()))
+            .finish()
+    }
+}
+
+impl Detectors {
+    /// Parse a comma-separated list of detector names, e.g.
+    /// `zero_bids,low_bid_rate`; they still run in DETECTORS order
+    pub fn parse(list: &str) -> Result<Self> {
+        let names: Vec<&str> = list
+            .split(',')
+            .map(str::trim)
+            .filter(|n| !n.is_empty())
+            .collect();
+        for name in &names {
+            if !DETECTORS.iter().any(|d| d.name() == *name) {
+                let known: Vec<&str> = DETECTORS.iter().map(|d| d.name()).collect();
+                bail!(
+                    "unknown detector '{name}', expected one of {}",
+                    known.join(", ")
+                );
+            }
+        }
+        if names.is_empty() {
+            bail!("no detectors listed");
+        }
+        Ok(Self(
+            DETECTORS
+                .iter()
+                .copied()
+                .filter(|d| names.contains(&d.name()))
+                .collect(),
+        ))
+    }
+}
+
+/// Identify problem formats from the stats. Formats under
+/// `min_volume_threshold` requests are never flagged.
+pub fn find_problem_formats(
+    global: &GlobalStats,
+    detectors: &Detectors,
+    min_volume_threshold: u64,
+) -> Vec<ProblemFormat> {
+    let mut problems = Vec::new();
+
+    for (&(w, h), stats) in &global.by_raw_format {
+        if stats.requests < min_volume_threshold {
+            continue;
+        }
+        let rate = if stats.requests == 0 {
+            0.0
+        } else {
+            stats.bids as f64 / stats.requests as f64
+        };
+        let format = FormatCheck {
+            w,
+            h,
+            stats,
+            bid_rate: rate,
+        };
+        if let Some(detector) = detectors.0.iter().find(|d| d.detect(&format)) {
+            problems.push(ProblemFormat {
+                w,
+                h,
+                requests: stats.requests,
+                bids: stats.bids,
+                bid_rate: rate,
+                problem_type: detector.name().to_string(),
+            });
+        }
+    }
+
+    // Sort by requests descending
+    problems.sort_by_key(|p| (Reverse(p.requests), p.w, p.h));
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::stats::process_record_global;
+    use crate::stats::tests::make_record;
+
+    #[test]
+    fn test_problem_format_detection() {
+        let mut global = GlobalStats::new();
+
+        // Add a non-standard size with volume
+        for _ in 0..20 {
+            let record = make_record(123, 456, true, 0.5);
+            process_record_global(&record, &mut global);
+        }
+
+        // Add a zero-bid format with volume
+        for _ in 0..15 {
+            let record = make_record(300, 250, false, 0.0);
+            process_record_global(&record, &mut global);
+        }
+
+        let problems = find_problem_formats(&global, &Detectors::default(), 10);
+
+        // Should find both problems
+        assert_eq!(problems.len(), 2);
+
+        // Check non-standard problem
+        let non_std = problems.iter().find(|p| p.w == 123).unwrap();
+        assert_eq!(non_std.problem_type, "non_standard");
+        assert_eq!(non_std.requests, 20);
+
+        // Check zero-bid problem
+        let zero_bid = problems.iter().find(|p| p.w == 300).unwrap();
+        assert_eq!(zero_bid.problem_type, "zero_bids");
+        assert_eq!(zero_bid.requests, 15);
+        assert_eq!(zero_bid.label(), "Zero bids");
+
+        // Without zero_bids, the 300x250 format isn't flagged at all
+        let detectors = Detectors::parse("non_standard, low_bid_rate").unwrap();
+        let problems = find_problem_formats(&global, &detectors, 10);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].problem_type, "non_standard");
+        assert!(Detectors::parse("zero_bids,latency").is_err());
+    }
+}
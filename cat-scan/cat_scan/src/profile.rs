@@ -0,0 +1,156 @@
+use std::time::{Duration, Instant};
+
+/// A step of the scan timed by --profile
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Phase {
+    /// Reading and framing input (download, decompression, line splitting)
+    Read,
+    /// Decoding frames into records
+    Parse,
+    /// Folding records into GlobalStats
+    Aggregate,
+    /// Request/response join (reading, parsing and aggregating together)
+    Join,
+    /// Writing reports and exports after the scan
+    Render,
+}
+
+impl Phase {
+    const ALL: [Phase; 5] = [
+        Phase::Read,
+        Phase::Parse,
+        Phase::Aggregate,
+        Phase::Join,
+        Phase::Render,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Phase::Read => "read",
+            Phase::Parse => "parse",
+            Phase::Aggregate => "aggregate",
+            Phase::Join => "join",
+            Phase::Render => "render",
+        }
+    }
+
+    fn index(self) -> usize {
+        self as usize
+    }
+}
+
+/// Where a scan spends its time (--profile). Disabled, `start` returns None
+/// and nothing is timed, so the per-line cost is a branch.
+#[derive(Debug, Default)]
+pub struct ScanProfile {
+    enabled: bool,
+    totals: [Duration; 5],
+    /// Input lines (frames) read
+    pub lines: u64,
+    /// Bytes of those lines
+    pub bytes: u64,
+    /// Lines decoded into a record (the rest were sampled out, blank or bad)
+    pub records: u64,
+}
+
+impl ScanProfile {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Start timing a phase; pass the result to `stop`
+    pub fn start(&self) -> Option<Instant> {
+        self.enabled.then(Instant::now)
+    }
+
+    pub fn stop(&mut self, phase: Phase, started: Option<Instant>) {
+        if let Some(started) = started {
+            self.totals[phase.index()] += started.elapsed();
+        }
+    }
+
+    pub fn total(&self, phase: Phase) -> Duration {
+        self.totals[phase.index()]
+    }
+
+    /// Per-phase totals, their share of the run, time per input line and
+    /// scan throughput (rendering excluded), for stderr
+    pub fn render(&self) -> String {
+        let total: Duration = self.totals.iter().sum();
+        let secs = total.as_secs_f64().max(1e-9);
+        let scan_secs = (total - self.total(Phase::Render)).as_secs_f64().max(1e-9);
+        let mut out = format!(
+            "Profile: {} lines, {} records, {:.1} MiB ({:.0} B/line)\n",
+            self.lines,
+            self.records,
+            self.bytes as f64 / (1 << 20) as f64,
+            self.bytes as f64 / self.lines.max(1) as f64
+        );
+        for phase in Phase::ALL {
+            let spent = self.total(phase);
+            if spent.is_zero() {
+                continue;
+            }
+            out.push_str(&format!(
+                "  {:<10} {:>9.3}s {:>5.1}% {:>9.2} µs/line\n",
+                phase.label(),
+                spent.as_secs_f64(),
+                spent.as_secs_f64() / secs * 100.0,
+                spent.as_secs_f64() * 1e6 / self.lines.max(1) as f64
+            ));
+        }
+        out.push_str(&format!(
+            "  {:<10} {:>9.3}s {:>6} {:>9.0} lines/s\n",
+            "total",
+            total.as_secs_f64(),
+            "",
+            self.lines as f64 / scan_secs
+        ));
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_disabled_profile_times_nothing() {
+        let mut profile = ScanProfile::new(false);
+        let started = profile.start();
+        assert!(started.is_none());
+        profile.stop(Phase::Parse, started);
+        assert!(profile.total(Phase::Parse).is_zero());
+    }
+
+    #[test]
+    fn test_render_lists_timed_phases() {
+        let mut profile = ScanProfile::new(true);
+        profile.totals[Phase::Read.index()] = Duration::from_millis(250);
+        profile.totals[Phase::Parse.index()] = Duration::from_millis(750);
+        profile.totals[Phase::Render.index()] = Duration::from_millis(1000);
+        profile.lines = 1000;
+        profile.bytes = 512_000;
+        profile.records = 990;
+
+        let text = profile.render();
+        assert!(text.starts_with("Profile: 1000 lines, 990 records, 0.5 MiB (512 B/line)"));
+        assert!(
+            text.contains("read           0.250s  12.5%    250.00 µs/line"),
+            "{text}"
+        );
+        assert!(text.contains("parse          0.750s  37.5%"), "{text}");
+        assert!(!text.contains("aggregate"));
+        assert!(
+            text.contains("total          2.000s             1000 lines/s"),
+            "{text}"
+        );
+    }
+}
@@ -0,0 +1,127 @@
+use std::{cmp::Reverse, collections::BTreeMap, io::Write};
+
+use anyhow::Result;
+
+use crate::metadata::{create_csv, ScanMetadata};
+use crate::stats::TimeStats;
+
+/// SSP label of the row covering all traffic
+pub const ALL_SSPS: &str = "(all)";
+
+/// Request rate of one SSP over the scan window
+#[derive(Debug, serde::Serialize)]
+pub struct QpsSummary {
+    pub ssp: String,
+    pub requests: u64,
+    /// Seconds from the first to the last timestamped request of the scan
+    pub window_secs: u64,
+    pub avg_qps: f64,
+    /// Most requests in any one second
+    pub peak_qps: u64,
+    /// Start (epoch ms) of the first second that reached `peak_qps`
+    pub peak_at_ms: u64,
+    /// peak_qps / avg_qps: how much headroom a QPS cap at the average needs
+    pub burstiness: f64,
+}
+
+/// Average and peak QPS per SSP, plus an ALL_SSPS row first. Every SSP is
+/// averaged over the same window (the whole scan), so an SSP that only
+/// sends traffic part of the time shows up as bursty.
+pub fn qps_by_ssp(time_stats: &BTreeMap<u64, TimeStats>) -> Vec<QpsSummary> {
+    let mut seconds: BTreeMap<&str, BTreeMap<u64, u64>> = BTreeMap::new();
+    for stats in time_stats.values() {
+        for ((ssp, second), count) in &stats.per_second {
+            *seconds.entry(ssp).or_default().entry(*second).or_default() += count;
+            *seconds
+                .entry(ALL_SSPS)
+                .or_default()
+                .entry(*second)
+                .or_default() += count;
+        }
+    }
+    let Some(all) = seconds.get(ALL_SSPS) else {
+        return Vec::new();
+    };
+    let first = all.keys().next().copied().unwrap_or(0);
+    let last = all.keys().next_back().copied().unwrap_or(0);
+    let window_secs = last - first + 1;
+
+    let mut rows: Vec<QpsSummary> = seconds
+        .iter()
+        .map(|(ssp, per_second)| {
+            let requests: u64 = per_second.values().sum();
+            let (peak_at, peak_qps) = per_second
+                .iter()
+                .max_by_key(|(second, count)| (**count, Reverse(**second)))
+                .map_or((0, 0), |(second, count)| (*second, *count));
+            let avg_qps = requests as f64 / window_secs as f64;
+            QpsSummary {
+                ssp: ssp.to_string(),
+                requests,
+                window_secs,
+                avg_qps,
+                peak_qps,
+                peak_at_ms: peak_at * 1000,
+                burstiness: if avg_qps > 0.0 {
+                    peak_qps as f64 / avg_qps
+                } else {
+                    0.0
+                },
+            }
+        })
+        .collect();
+    rows.sort_by(|a, b| {
+        (a.ssp != ALL_SSPS, Reverse(a.requests), &a.ssp).cmp(&(
+            b.ssp != ALL_SSPS,
+            Reverse(b.requests),
+            &b.ssp,
+        ))
+    });
+    rows
+}
+
+/// Write qps_stats.csv (average / peak QPS and burstiness per SSP)
+pub fn write_qps_csv(out_dir: &str, rows: &[QpsSummary], meta: &ScanMetadata) -> Result<()> {
+    let path = format!("{}/qps_stats.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    writeln!(
+        csv,
+        "ssp,requests,window_secs,avg_qps,peak_qps,peak_at_ms,burstiness"
+    )?;
+    for r in rows {
+        writeln!(
+            csv,
+            "{},{},{},{:.4},{},{},{:.2}",
+            r.ssp, r.requests, r.window_secs, r.avg_qps, r.peak_qps, r.peak_at_ms, r.burstiness
+        )?;
+    }
+    eprintln!("QPS stats written to: {}", path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_qps_by_ssp() {
+        let mut time_stats: BTreeMap<u64, TimeStats> = BTreeMap::new();
+        // ssp_a: 4 requests in second 100, 1 in 101; ssp_b: 1 in 109
+        for (ssp, second, count) in [("ssp_a", 100, 4), ("ssp_a", 101, 1), ("ssp_b", 109, 1)] {
+            let bucket = time_stats.entry(second / 60 * 60_000).or_default();
+            bucket.requests += count;
+            bucket.per_second.insert((ssp.to_string(), second), count);
+        }
+        assert_eq!(time_stats[&60_000].peak_qps(), 4);
+
+        let rows = qps_by_ssp(&time_stats);
+        assert_eq!(rows[0].ssp, ALL_SSPS);
+        assert_eq!((rows[0].requests, rows[0].window_secs), (6, 10));
+        let a = &rows[1];
+        assert_eq!(a.ssp, "ssp_a");
+        assert_eq!((a.peak_qps, a.peak_at_ms), (4, 100_000));
+        assert!((a.avg_qps - 0.5).abs() < 1e-9);
+        assert!((a.burstiness - 8.0).abs() < 1e-9);
+        assert_eq!(rows[2].peak_qps, 1);
+    }
+}
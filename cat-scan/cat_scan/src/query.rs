@@ -0,0 +1,370 @@
+use std::{cmp::Reverse, collections::BTreeMap, fmt::Write as _, fs, path::Path};
+
+use anyhow::{bail, Context, Result};
+
+use crate::cli::QueryConfig;
+use crate::stats::{avg_bid_price, bid_rate, FormatStats};
+
+/// A cross-tab `cat_scan query` can print, read back from one of a scan's
+/// CSV outputs (which, unlike report.json, keep every row)
+#[derive(Debug)]
+pub struct Pivot {
+    dimensions: (&'static str, &'static str),
+    file: &'static str,
+    /// Columns holding each dimension's key, joined with "x" (w,h -> 300x250)
+    keys: (&'static [&'static str], &'static [&'static str]),
+    /// Only the lines whose column has this value
+    only: Option<(&'static str, &'static str)>,
+}
+
+const PIVOTS: &[Pivot] = &[
+    Pivot {
+        dimensions: ("format", "ssp"),
+        file: "ssp_format_stats.csv",
+        keys: (&["w", "h"], &["ssp"]),
+        only: None,
+    },
+    Pivot {
+        dimensions: ("publisher", "ssp"),
+        file: "segment_stats.csv",
+        keys: (&["id"], &["ssp"]),
+        only: Some(("type", "publisher")),
+    },
+    Pivot {
+        dimensions: ("segment", "ssp"),
+        file: "segment_stats.csv",
+        keys: (&["id"], &["ssp"]),
+        only: Some(("type", "segment")),
+    },
+    Pivot {
+        dimensions: ("segment", "publisher"),
+        file: "publisher_segment_stats.csv",
+        keys: (&["segment"], &["publisher_id"]),
+        only: None,
+    },
+    Pivot {
+        dimensions: ("secure", "ssp"),
+        file: "secure_stats.csv",
+        keys: (&["bucket"], &["ssp"]),
+        only: None,
+    },
+    Pivot {
+        dimensions: ("consent", "ssp"),
+        file: "consent_stats.csv",
+        keys: (&["bucket"], &["ssp"]),
+        only: None,
+    },
+    Pivot {
+        dimensions: ("inventory", "format"),
+        file: "inventory_stats.csv",
+        keys: (&["inventory"], &["key"]),
+        only: Some(("dimension", "format")),
+    },
+    Pivot {
+        dimensions: ("inventory", "ssp"),
+        file: "inventory_stats.csv",
+        keys: (&["inventory"], &["key"]),
+        only: Some(("dimension", "ssp")),
+    },
+    Pivot {
+        dimensions: ("inventory", "publisher"),
+        file: "inventory_stats.csv",
+        keys: (&["inventory"], &["key"]),
+        only: Some(("dimension", "publisher")),
+    },
+    Pivot {
+        dimensions: ("position", "publisher"),
+        file: "position_stats.csv",
+        keys: (&["bucket"], &["publisher"]),
+        only: Some(("signal", "position")),
+    },
+    Pivot {
+        dimensions: ("viewability", "publisher"),
+        file: "position_stats.csv",
+        keys: (&["bucket"], &["publisher"]),
+        only: Some(("signal", "viewability")),
+    },
+];
+
+/// Metric shown in each cell of the cross-tab
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Metric {
+    Requests,
+    Bids,
+    BidRate,
+    AvgBidPrice,
+}
+
+impl Metric {
+    pub fn parse(value: &str) -> Result<Self> {
+        Ok(match value {
+            "requests" => Metric::Requests,
+            "bids" => Metric::Bids,
+            "bid_rate" => Metric::BidRate,
+            "avg_bid_price" | "price" => Metric::AvgBidPrice,
+            other => bail!(
+                "unknown metric '{other}', expected one of: requests|bids|bid_rate|avg_bid_price"
+            ),
+        })
+    }
+
+    fn cell(self, stats: &FormatStats) -> String {
+        match self {
+            Metric::Requests => stats.requests.to_string(),
+            Metric::Bids => stats.bids.to_string(),
+            Metric::BidRate => format!("{:.2}%", bid_rate(stats) * 100.0),
+            Metric::AvgBidPrice => format!("{:.4}", avg_bid_price(stats)),
+        }
+    }
+}
+
+/// A parsed "<rows> by <columns>" query
+#[derive(Debug)]
+pub struct PivotQuery {
+    pub rows: &'static str,
+    pub columns: &'static str,
+    pivot: &'static Pivot,
+}
+
+impl PivotQuery {
+    /// Parse e.g. "format by ssp"; either dimension can come first
+    pub fn parse(text: &str) -> Result<Self> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let [rows, "by", columns] = words[..] else {
+            bail!("expected '<rows> by <columns>', e.g. 'format by ssp', got '{text}'");
+        };
+        let find = |a: &str, b: &str| {
+            PIVOTS.iter().find(|p| {
+                p.dimensions.0.eq_ignore_ascii_case(a) && p.dimensions.1.eq_ignore_ascii_case(b)
+            })
+        };
+        if let Some(pivot) = find(rows, columns) {
+            return Ok(Self {
+                rows: pivot.dimensions.0,
+                columns: pivot.dimensions.1,
+                pivot,
+            });
+        }
+        if let Some(pivot) = find(columns, rows) {
+            return Ok(Self {
+                rows: pivot.dimensions.1,
+                columns: pivot.dimensions.0,
+                pivot,
+            });
+        }
+        let pairs: Vec<String> = PIVOTS
+            .iter()
+            .map(|p| format!("{} by {}", p.dimensions.0, p.dimensions.1))
+            .collect();
+        bail!(
+            "no cross-tab of {rows} by {columns}; available: {}",
+            pairs.join(", ")
+        );
+    }
+
+    fn transposed(&self) -> bool {
+        self.rows != self.pivot.dimensions.0
+    }
+}
+
+/// Cells of a cross-tab, keyed by (row, column)
+type Cells = BTreeMap<(String, String), FormatStats>;
+
+/// Read the cells of `query` from the text of its CSV
+fn read_cells(text: &str, query: &PivotQuery) -> Result<Cells> {
+    let mut reader = csv::ReaderBuilder::new()
+        .comment(Some(b'#'))
+        .flexible(true)
+        .from_reader(text.as_bytes());
+    let headers = reader.headers()?.clone();
+    let index = |column: &str| {
+        headers
+            .iter()
+            .position(|h| h == column)
+            .with_context(|| format!("no '{column}' column"))
+    };
+    let key_columns =
+        |columns: &[&str]| columns.iter().map(|c| index(c)).collect::<Result<Vec<_>>>();
+    let (row_columns, column_columns) = (
+        key_columns(query.pivot.keys.0)?,
+        key_columns(query.pivot.keys.1)?,
+    );
+    let only = match query.pivot.only {
+        Some((column, value)) => Some((index(column)?, value)),
+        None => None,
+    };
+    let (requests, bids, price) = (index("requests")?, index("bids")?, index("avg_bid_price")?);
+
+    let mut cells = Cells::new();
+    for record in reader.records() {
+        let record = record?;
+        let field = |i: usize| record.get(i).unwrap_or_default();
+        if only.is_some_and(|(i, value)| field(i) != value) {
+            continue;
+        }
+        let key = |columns: &[usize]| {
+            columns
+                .iter()
+                .map(|&i| field(i))
+                .collect::<Vec<_>>()
+                .join("x")
+        };
+        let (mut row, mut column) = (key(&row_columns), key(&column_columns));
+        if query.transposed() {
+            std::mem::swap(&mut row, &mut column);
+        }
+        let number = |i: usize| {
+            field(i)
+                .parse::<f64>()
+                .with_context(|| format!("invalid number '{}' in {:?}", field(i), record))
+        };
+        let bids = number(bids)? as u64;
+        cells.entry((row, column)).or_default().merge(&FormatStats {
+            requests: number(requests)? as u64,
+            bids,
+            sum_bid_price: number(price)? * bids as f64,
+            ..Default::default()
+        });
+    }
+    Ok(cells)
+}
+
+/// Keys of one side of the cross-tab with their totals, busiest first
+fn totals<'a>(
+    cells: &'a Cells,
+    key: impl Fn(&'a (String, String)) -> &'a str,
+) -> Vec<(&'a str, FormatStats)> {
+    let mut totals: BTreeMap<&str, FormatStats> = BTreeMap::new();
+    for (k, stats) in cells {
+        totals.entry(key(k)).or_default().merge(stats);
+    }
+    let mut totals: Vec<_> = totals.into_iter().collect();
+    totals.sort_by_key(|(key, stats)| (Reverse(stats.requests), *key));
+    totals
+}
+
+/// Render the cross-tab as an aligned text table with row and column
+/// totals, keeping the `top` busiest rows and columns (0 keeps all)
+fn render(cells: &Cells, query: &PivotQuery, metric: Metric, top: usize) -> String {
+    let rows = totals(cells, |(row, _)| row.as_str());
+    let columns = totals(cells, |(_, column)| column.as_str());
+    let limit = |n: usize| if top == 0 { n } else { n.min(top) };
+    let (shown_rows, shown_columns) = (limit(rows.len()), limit(columns.len()));
+
+    let mut grand_total = FormatStats::default();
+    for (_, stats) in &rows {
+        grand_total.merge(stats);
+    }
+
+    let mut table: Vec<Vec<String>> = Vec::new();
+    let mut header = vec![format!("{} \\ {}", query.rows, query.columns)];
+    header.extend(columns[..shown_columns].iter().map(|(c, _)| c.to_string()));
+    header.push("total".to_string());
+    table.push(header);
+    for (row, row_total) in &rows[..shown_rows] {
+        let mut line = vec![row.to_string()];
+        for (column, _) in &columns[..shown_columns] {
+            let cell = cells.get(&(row.to_string(), column.to_string()));
+            line.push(cell.map_or("-".to_string(), |stats| metric.cell(stats)));
+        }
+        line.push(metric.cell(row_total));
+        table.push(line);
+    }
+    let mut footer = vec!["total".to_string()];
+    footer.extend(
+        columns[..shown_columns]
+            .iter()
+            .map(|(_, stats)| metric.cell(stats)),
+    );
+    footer.push(metric.cell(&grand_total));
+    table.push(footer);
+
+    let widths: Vec<usize> = (0..table[0].len())
+        .map(|i| table.iter().map(|line| line[i].len()).max().unwrap_or(0))
+        .collect();
+    let mut out = String::new();
+    for line in &table {
+        let mut text = format!("{:<width$}", line[0], width = widths[0]);
+        for (cell, width) in line.iter().zip(&widths).skip(1) {
+            let _ = write!(text, "  {:>width$}", cell, width = width);
+        }
+        let _ = writeln!(out, "{}", text.trim_end());
+    }
+    if shown_rows < rows.len() || shown_columns < columns.len() {
+        let _ = writeln!(
+            out,
+            "({} of {} {} rows and {} of {} {} columns; the totals cover all of them)",
+            shown_rows,
+            rows.len(),
+            query.rows,
+            shown_columns,
+            columns.len(),
+            query.columns
+        );
+    }
+    out
+}
+
+/// `cat_scan query`: print a cross-tab of two dimensions of a finished
+/// scan, read from the CSVs in its --out directory
+pub fn run_query(config: QueryConfig) -> Result<()> {
+    let file = Path::new(&config.out_dir).join(config.query.pivot.file);
+    let text = fs::read_to_string(&file).with_context(|| {
+        format!(
+            "Failed to read {} (a scan writes it to --out when it sees {} by {} data)",
+            file.display(),
+            config.query.rows,
+            config.query.columns
+        )
+    })?;
+    let cells = read_cells(&text, &config.query)
+        .with_context(|| format!("Invalid stats in {}", file.display()))?;
+    if cells.is_empty() {
+        bail!(
+            "{} has no {} by {} rows",
+            file.display(),
+            config.query.rows,
+            config.query.columns
+        );
+    }
+    print!(
+        "{}",
+        render(&cells, &config.query, config.metric, config.top)
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_pivot() {
+        let text =
+            "# cat_scan 0.1.0\n# Publishers\ntype,id,ssp,requests,bids,bid_rate,avg_bid_price\n\
+                    publisher,pub-1,alpha,100,50,0.5000,2.0000\n\
+                    publisher,pub-1,beta,300,30,0.1000,1.0000\n\
+                    publisher,pub-2,beta,40,0,0.0000,0.0000\n\
+                    \n# Segments\nsegment,news,alpha,100,50,0.5000,2.0000\n";
+        let query = PivotQuery::parse("ssp by publisher").unwrap();
+        assert_eq!((query.rows, query.columns), ("ssp", "publisher"));
+        let cells = read_cells(text, &query).unwrap();
+        assert_eq!(cells.len(), 3);
+        assert_eq!(cells[&("beta".into(), "pub-1".into())].requests, 300);
+
+        let table = render(&cells, &query, Metric::BidRate, 0);
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "ssp \\ publisher   pub-1  pub-2   total");
+        assert_eq!(lines[1], "beta             10.00%  0.00%   8.82%");
+        assert_eq!(lines[2], "alpha            50.00%      -  50.00%");
+        assert_eq!(lines[3], "total            20.00%  0.00%  18.18%");
+
+        let top = render(&cells, &query, Metric::Requests, 1);
+        assert!(top.contains("1 of 2 ssp rows and 1 of 2 publisher columns"));
+        assert!(top.lines().last().unwrap().starts_with('('));
+
+        assert!(PivotQuery::parse("format by geo").is_err());
+        assert!(PivotQuery::parse("format ssp").is_err());
+        assert!(Metric::parse("revenue").is_err());
+    }
+}
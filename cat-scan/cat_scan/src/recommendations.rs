@@ -0,0 +1,468 @@
+use std::collections::BTreeMap;
+
+use anyhow::{Context, Result};
+
+use crate::metadata::ScanMetadata;
+use crate::qps::{QpsSummary, ALL_SSPS};
+use crate::report::HtmlReportData;
+use crate::stats::{FormatStats, GlobalStats};
+use crate::summary::{percent, thousands};
+
+/// tmax buckets, shortest first
+pub const TMAX_BUCKETS: &[&str] = &["<100ms", "100-199ms", "200-499ms", "500ms+"];
+
+/// Bid rate under which an SSP format is worth reviewing
+const LOW_BID_RATE: f64 = 0.01;
+
+/// Share of an SSP's requests in the shortest tmax bucket that is worth
+/// asking about
+const SHORT_TMAX_SHARE: f64 = 0.1;
+
+/// peak / average QPS from which an SSP needs a QPS cap
+const BURSTY_RATIO: f64 = 5.0;
+
+/// Share of repeated requests worth raising with the SSP
+const DUPLICATE_SHARE: f64 = 0.01;
+
+/// Formats named in an action before "and N more"
+const LISTED_FORMATS: usize = 3;
+
+/// The TMAX_BUCKETS entry for a request's tmax (ms)
+pub fn tmax_bucket(tmax: u64) -> &'static str {
+    match tmax {
+        0..=99 => TMAX_BUCKETS[0],
+        100..=199 => TMAX_BUCKETS[1],
+        200..=499 => TMAX_BUCKETS[2],
+        _ => TMAX_BUCKETS[3],
+    }
+}
+
+/// "high" at 20%+ of the SSP's requests, "medium" at 5%+, else "low"
+fn priority(share: f64) -> &'static str {
+    if share >= 0.2 {
+        "high"
+    } else if share >= 0.05 {
+        "medium"
+    } else {
+        "low"
+    }
+}
+
+fn priority_rank(priority: &str) -> u8 {
+    match priority {
+        "high" => 0,
+        "medium" => 1,
+        _ => 2,
+    }
+}
+
+fn rate(n: u64, d: u64) -> f64 {
+    if d == 0 {
+        0.0
+    } else {
+        n as f64 / d as f64
+    }
+}
+
+/// "320x480, 970x250, 300x600 and 2 more"
+fn format_list(formats: &[String]) -> String {
+    let listed = formats[..formats.len().min(LISTED_FORMATS)].join(", ");
+    match formats.len().saturating_sub(LISTED_FORMATS) {
+        0 => listed,
+        more => format!("{} and {} more", listed, more),
+    }
+}
+
+/// One action for one SSP, from the problem detectors
+#[derive(Debug, serde::Serialize)]
+pub struct Recommendation {
+    pub ssp: String,
+    /// "high", "medium" or "low", by the share of the SSP's requests
+    /// affected; floor and QPS cap actions are always "medium"
+    pub priority: &'static str,
+    /// cut_formats, review_formats, raise_floors, raise_tmax, require_secure,
+    /// enforce_domain_lists, cap_qps or dedupe_requests
+    pub kind: &'static str,
+    /// What to do, as a sentence
+    pub action: String,
+    /// Requests the action is about
+    pub requests: u64,
+    /// requests / the SSP's requests
+    pub share: f64,
+    /// QPS the bidder stops handling (needs timestamps)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub qps_saving: Option<f64>,
+    /// Canonical formats the action is about
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub formats: Vec<String>,
+}
+
+/// Prioritized actions per SSP: the zero-bid and low bid rate formats to
+/// cut, floors to raise, tmax to lengthen, non-secure traffic to stop, QPS
+/// to cap and duplicates to stop, highest priority and most requests first. Formats need
+/// `min_requests` requests from the SSP to be named.
+pub fn build_recommendations(
+    global: &GlobalStats,
+    qps: &[QpsSummary],
+    min_requests: u64,
+) -> Vec<Recommendation> {
+    let window_secs = qps
+        .iter()
+        .find(|q| q.ssp == ALL_SSPS)
+        .map(|q| q.window_secs);
+    let qps_saving = |requests: u64| window_secs.map(|secs| requests as f64 / secs as f64);
+    let mut recommendations = Vec::new();
+    let mut add = |ssp: &str,
+                   priority: &'static str,
+                   kind: &'static str,
+                   action: String,
+                   requests: u64,
+                   saving: Option<f64>,
+                   formats: Vec<String>| {
+        let total = global.by_ssp.get(ssp).map_or(0, |s| s.requests);
+        recommendations.push(Recommendation {
+            ssp: ssp.to_string(),
+            priority,
+            kind,
+            action,
+            requests,
+            share: rate(requests, total),
+            qps_saving: saving,
+            formats,
+        });
+    };
+
+    // Formats to cut or review, per SSP
+    let mut ssp_formats: BTreeMap<&str, Vec<(String, &FormatStats)>> = BTreeMap::new();
+    for ((ssp, (w, h)), stats) in &global.by_ssp_format {
+        if stats.requests >= min_requests {
+            ssp_formats
+                .entry(ssp)
+                .or_default()
+                .push((format!("{}x{}", w, h), stats));
+        }
+    }
+    for (ssp, mut formats) in ssp_formats {
+        let total = global.by_ssp.get(ssp).map_or(0, |s| s.requests);
+        // Stable, so ties stay in (w, h) order
+        formats.sort_by_key(|(_, stats)| std::cmp::Reverse(stats.requests));
+        let zero_bid: Vec<_> = formats.iter().filter(|(_, s)| s.bids == 0).collect();
+        if !zero_bid.is_empty() {
+            let requests: u64 = zero_bid.iter().map(|(_, s)| s.requests).sum();
+            let names: Vec<String> = zero_bid.iter().map(|(f, _)| f.clone()).collect();
+            let saving = qps_saving(requests);
+            let mut action = format!(
+                "Cut {} format{} with no bids ({}): {} requests",
+                names.len(),
+                if names.len() == 1 { "" } else { "s" },
+                format_list(&names),
+                thousands(requests)
+            );
+            if let Some(saving) = saving {
+                action.push_str(&format!(", saving ~{:.2} QPS", saving));
+            }
+            add(
+                ssp,
+                priority(rate(requests, total)),
+                "cut_formats",
+                action,
+                requests,
+                saving,
+                names,
+            );
+        }
+        let low: Vec<_> = formats
+            .iter()
+            .filter(|(_, s)| s.bids > 0 && rate(s.bids, s.requests) < LOW_BID_RATE)
+            .collect();
+        if !low.is_empty() {
+            let requests: u64 = low.iter().map(|(_, s)| s.requests).sum();
+            let names: Vec<String> = low.iter().map(|(f, _)| f.clone()).collect();
+            add(
+                ssp,
+                priority(rate(requests, total)),
+                "review_formats",
+                format!(
+                    "Review {} format{} bidding on under {} of requests ({}): {} requests",
+                    names.len(),
+                    if names.len() == 1 { "" } else { "s" },
+                    percent(LOW_BID_RATE),
+                    format_list(&names),
+                    thousands(requests)
+                ),
+                requests,
+                None,
+                names,
+            );
+        }
+    }
+
+    // Floors far below what the bidder pays
+    for floor in global.floors.summarize() {
+        if floor.dimension == "ssp" && floor.overbidding {
+            add(
+                &floor.key,
+                "medium",
+                "raise_floors",
+                format!(
+                    "Raise floors or shade bids: bids average {:.1}x the ${:.2} floor",
+                    floor.avg_bid_to_floor, floor.avg_floor
+                ),
+                floor.bids,
+                None,
+                Vec::new(),
+            );
+        }
+    }
+
+    // Requests too short-lived to bid on
+    for (ssp, total) in &global.by_ssp {
+        let short = global.by_ssp_tmax.get(&(ssp.clone(), TMAX_BUCKETS[0]));
+        let Some(short) = short.filter(|s| s.requests >= min_requests) else {
+            continue;
+        };
+        let timed: u64 = TMAX_BUCKETS
+            .iter()
+            .filter_map(|bucket| global.by_ssp_tmax.get(&(ssp.clone(), *bucket)))
+            .map(|s| s.requests)
+            .sum();
+        let longer_requests = timed - short.requests;
+        let longer_bids: u64 = TMAX_BUCKETS[1..]
+            .iter()
+            .filter_map(|bucket| global.by_ssp_tmax.get(&(ssp.clone(), *bucket)))
+            .map(|s| s.bids)
+            .sum();
+        let short_rate = rate(short.bids, short.requests);
+        let longer_rate = rate(longer_bids, longer_requests);
+        if rate(short.requests, total.requests) >= SHORT_TMAX_SHARE
+            && longer_bids > 0
+            && short_rate < longer_rate / 2.0
+        {
+            add(
+                ssp,
+                priority(rate(short.requests, total.requests)),
+                "raise_tmax",
+                format!(
+                    "Ask for a longer tmax: {} requests allow {} and bid {} vs {} otherwise",
+                    thousands(short.requests),
+                    TMAX_BUCKETS[0],
+                    percent(short_rate),
+                    percent(longer_rate)
+                ),
+                short.requests,
+                None,
+                Vec::new(),
+            );
+        }
+    }
+
+    // Requests still allowing non-HTTPS creatives
+    for (ssp, total) in &global.by_ssp {
+        let non_secure = global.by_secure.get(&(ssp.clone(), "non_secure"));
+        let Some(non_secure) = non_secure.filter(|s| s.requests >= min_requests) else {
+            continue;
+        };
+        let share = rate(non_secure.requests, total.requests);
+        let mut action = format!(
+            "Ask for secure-only traffic: {} requests ({}) are sent with imp.secure=0",
+            thousands(non_secure.requests),
+            percent(share)
+        );
+        if let Some(secure) = global.by_secure.get(&(ssp.clone(), "secure")) {
+            action.push_str(&format!(
+                " and bid {} vs {} on secure requests",
+                percent(rate(non_secure.bids, non_secure.requests)),
+                percent(rate(secure.bids, secure.requests))
+            ));
+        }
+        add(
+            ssp,
+            priority(share),
+            "require_secure",
+            action,
+            non_secure.requests,
+            None,
+            Vec::new(),
+        );
+    }
+
+    // Traffic outside our allowlist or on our denylist
+    if let Some(domain_lists) = &global.domain_lists {
+        for (ssp, counts) in &domain_lists.by_ssp {
+            if counts.off_list < min_requests {
+                continue;
+            }
+            let share = rate(counts.off_list, counts.requests);
+            add(
+                ssp,
+                priority(share),
+                "enforce_domain_lists",
+                format!(
+                    "Apply our domain lists: {} requests ({}) are for domains outside the allowlist or on the denylist",
+                    thousands(counts.off_list),
+                    percent(share)
+                ),
+                counts.off_list,
+                qps_saving(counts.off_list),
+                Vec::new(),
+            );
+        }
+    }
+
+    // Bursty traffic
+    for row in qps {
+        if row.ssp != ALL_SSPS && row.burstiness >= BURSTY_RATIO && row.requests >= min_requests {
+            add(
+                &row.ssp,
+                "medium",
+                "cap_qps",
+                format!(
+                    "Set a QPS cap: peaks at {} QPS, {:.1}x the {:.1} QPS average",
+                    row.peak_qps, row.burstiness, row.avg_qps
+                ),
+                row.requests,
+                None,
+                Vec::new(),
+            );
+        }
+    }
+
+    // Repeated requests (request ids, plus fingerprints with --fingerprint)
+    for (ssp, ids) in &global.duplicate_ids.by_ssp {
+        let fingerprinted = global
+            .fingerprints
+            .as_ref()
+            .and_then(|f| f.by_ssp.get(ssp))
+            .map_or(0, |c| c.exact_duplicates + c.near_duplicates);
+        let repeated = ids.duplicates.max(fingerprinted);
+        let share = rate(repeated, ids.requests);
+        if share >= DUPLICATE_SHARE {
+            let saving = qps_saving(repeated);
+            add(
+                ssp,
+                priority(share),
+                "dedupe_requests",
+                format!(
+                    "Ask the SSP to dedupe: {} of requests ({}) repeat an earlier one",
+                    percent(share),
+                    thousands(repeated)
+                ),
+                repeated,
+                saving,
+                Vec::new(),
+            );
+        }
+    }
+
+    recommendations.sort_by(|a, b| {
+        priority_rank(a.priority)
+            .cmp(&priority_rank(b.priority))
+            .then_with(|| b.requests.cmp(&a.requests))
+            .then_with(|| a.ssp.cmp(&b.ssp))
+    });
+    recommendations
+}
+
+/// recommendations.json: the actions next to the scan metadata
+#[derive(serde::Serialize)]
+struct RecommendationsFile<'a> {
+    metadata: &'a ScanMetadata,
+    recommendations: &'a [Recommendation],
+}
+
+/// Write recommendations.json (prioritized actions per SSP)
+pub fn write_recommendations_json(out_dir: &str, report: &HtmlReportData) -> Result<()> {
+    let path = format!("{}/recommendations.json", out_dir);
+    let file = RecommendationsFile {
+        metadata: &report.metadata,
+        recommendations: &report.recommendations,
+    };
+    let json = serde_json::to_string_pretty(&file)
+        .context("Failed to serialize recommendations to JSON")?;
+    std::fs::write(&path, json).with_context(|| format!("Failed to write {}", path))?;
+    eprintln!("Recommendations written to: {}", path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::qps::qps_by_ssp;
+    use crate::stats::{process_record_global, tests::make_record};
+
+    #[test]
+    fn test_recommendations() {
+        let mut global = GlobalStats::new();
+        for i in 0..100u64 {
+            // ssp_a: 300x250 bids, 320x480 never does; a third of the
+            // requests allow 50ms and get no bids
+            let (w, h, bid) = if i % 4 == 0 {
+                (320, 480, false)
+            } else {
+                (300, 250, i % 3 != 0)
+            };
+            let mut record = make_record(w, h, bid, 1.0);
+            record.request["source"] = serde_json::json!({ "ssp": "ssp_a" });
+            record.request["tmax"] = serde_json::json!(if i % 3 == 0 { 50 } else { 300 });
+            record.request["id"] = serde_json::json!(format!("a{}", i));
+            record.ts_ms = Some(1_000_000 + i * 100);
+            process_record_global(&record, &mut global);
+        }
+        let qps = qps_by_ssp(&global.time_stats);
+        let recommendations = build_recommendations(&global, &qps, 10);
+
+        let kinds: Vec<_> = recommendations
+            .iter()
+            .map(|r| (r.ssp.as_str(), r.priority, r.kind))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("ssp_a", "high", "raise_tmax"),
+                ("ssp_a", "high", "cut_formats"),
+            ]
+        );
+        let cut = &recommendations[1];
+        assert_eq!(cut.requests, 25);
+        assert_eq!(cut.formats, vec!["320x480"]);
+        // 25 requests over a 10s window
+        assert!((cut.qps_saving.unwrap() - 2.5).abs() < 1e-9);
+        assert!(cut
+            .action
+            .starts_with("Cut 1 format with no bids (320x480)"));
+        assert_eq!(recommendations[0].requests, 34);
+        assert_eq!(tmax_bucket(120), "100-199ms");
+    }
+
+    #[test]
+    fn test_require_secure() {
+        let mut global = GlobalStats::new();
+        for i in 0..40u64 {
+            // ssp_a sends a quarter of its requests with secure=0; ssp_b
+            // leaves it unset
+            let mut record = make_record(300, 250, i % 2 == 0, 1.0);
+            record.request["source"] = serde_json::json!({ "ssp": "ssp_a" });
+            record.request["imp"][0]["secure"] = serde_json::json!(u64::from(i % 4 != 0));
+            process_record_global(&record, &mut global);
+
+            let mut record = make_record(300, 250, true, 1.0);
+            record.request["source"] = serde_json::json!({ "ssp": "ssp_b" });
+            process_record_global(&record, &mut global);
+        }
+        let recommendations = build_recommendations(&global, &[], 10);
+
+        assert_eq!(recommendations.len(), 1);
+        let secure = &recommendations[0];
+        assert_eq!(
+            (secure.ssp.as_str(), secure.kind),
+            ("ssp_a", "require_secure")
+        );
+        assert_eq!(secure.priority, "high");
+        assert_eq!(secure.requests, 10);
+        assert_eq!(
+            secure.action,
+            "Ask for secure-only traffic: 10 requests (25.0%) are sent with imp.secure=0 \
+             and bid 100.0% vs 33.3% on secure requests"
+        );
+    }
+}
@@ -0,0 +1,211 @@
+use anyhow::{bail, Result};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::domains::{site_of, url_host};
+use crate::stats::LogRecord;
+
+/// What --redact can take out, in the order the report lists them
+pub const REDACTIONS: &[&str] = &["publisher", "ip", "ifa", "domain"];
+
+/// Hex digits kept of a hashed publisher id
+const HASH_DIGITS: usize = 16;
+
+/// Identifiers --redact takes out of every record before it is
+/// aggregated, so no output can carry them
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Redaction {
+    /// Hash site/app.publisher.id and drop publisher.name
+    pub publisher: bool,
+    /// Drop device.ip and device.ipv6
+    pub ip: bool,
+    /// Drop device.ifa
+    pub ifa: bool,
+    /// Cut site.domain, app.domain and the site.page / site.ref URLs down
+    /// to the registrable domain
+    pub domain: bool,
+    /// Mixed into publisher id hashes (--redact-salt)
+    pub salt: String,
+}
+
+impl Redaction {
+    /// Parse a comma-separated list of REDACTIONS, or `all`
+    pub fn parse(list: &str) -> Result<Self> {
+        let mut redaction = Self::default();
+        let names: Vec<&str> = list
+            .split(',')
+            .map(str::trim)
+            .filter(|n| !n.is_empty())
+            .collect();
+        for name in &names {
+            match *name {
+                "all" => {
+                    redaction.publisher = true;
+                    redaction.ip = true;
+                    redaction.ifa = true;
+                    redaction.domain = true;
+                }
+                "publisher" => redaction.publisher = true,
+                "ip" => redaction.ip = true,
+                "ifa" => redaction.ifa = true,
+                "domain" => redaction.domain = true,
+                _ => bail!(
+                    "unknown redaction '{name}', expected all or one of {}",
+                    REDACTIONS.join(", ")
+                ),
+            }
+        }
+        if names.is_empty() {
+            bail!("no redactions listed");
+        }
+        Ok(redaction)
+    }
+
+    /// The REDACTIONS on, for the report header
+    pub fn names(&self) -> Vec<&'static str> {
+        let on = [self.publisher, self.ip, self.ifa, self.domain];
+        REDACTIONS
+            .iter()
+            .zip(on)
+            .filter_map(|(&name, on)| on.then_some(name))
+            .collect()
+    }
+
+    /// A copy of `record` with the identifiers taken out
+    pub fn apply(&self, record: &LogRecord) -> LogRecord {
+        let mut record = record.clone();
+        let request = &mut record.request;
+        if self.publisher {
+            for inventory in ["site", "app"] {
+                let Some(publisher) = request[inventory]["publisher"].as_object_mut() else {
+                    continue;
+                };
+                publisher.remove("name");
+                if let Some(id) = publisher.get_mut("id") {
+                    if let Some(text) = id_text(id) {
+                        *id = Value::String(self.hash(&text));
+                    }
+                }
+            }
+        }
+        if let Some(device) = request["device"].as_object_mut() {
+            if self.ip {
+                device.remove("ip");
+                device.remove("ipv6");
+            }
+            if self.ifa {
+                device.remove("ifa");
+            }
+        }
+        if self.domain {
+            for (inventory, field) in [("site", "domain"), ("app", "domain")] {
+                if let Some(domain) = request[inventory][field].as_str() {
+                    request[inventory][field] = Value::String(truncate_host(domain).to_string());
+                }
+            }
+            for field in ["page", "ref"] {
+                if let Some(url) = request["site"][field].as_str() {
+                    request["site"][field] = Value::String(truncate_url(url));
+                }
+            }
+        }
+        record
+    }
+
+    /// First HASH_DIGITS hex digits of SHA-256(salt, id): the same id
+    /// always hashes the same within a report and across reports made
+    /// with the same salt
+    fn hash(&self, id: &str) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.salt.as_bytes());
+        hasher.update([0]);
+        hasher.update(id.as_bytes());
+        let digest = hasher.finalize();
+        let hex: String = digest.iter().map(|b| format!("{b:02x}")).collect();
+        hex[..HASH_DIGITS].to_string()
+    }
+}
+
+/// Publisher ids are strings, but some SSPs send numbers
+fn id_text(id: &Value) -> Option<String> {
+    match id {
+        Value::String(s) if !s.is_empty() => Some(s.clone()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// The registrable domain of a host; IP addresses are left alone
+fn truncate_host(host: &str) -> &str {
+    let host = host.trim().trim_end_matches('.');
+    if host.parse::<std::net::IpAddr>().is_ok() {
+        host
+    } else {
+        site_of(host)
+    }
+}
+
+/// A URL cut down to its scheme and registrable domain: paths and query
+/// strings can carry user data
+fn truncate_url(url: &str) -> String {
+    let host = truncate_host(url_host(url)).to_ascii_lowercase();
+    match url.split_once("://") {
+        Some((scheme, _)) => format!("{scheme}://{host}/"),
+        None => host,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_redaction() {
+        let record = LogRecord {
+            request: json!({
+                "site": {
+                    "domain": "news.example.co.uk",
+                    "page": "https://news.example.co.uk/story?email=a@b.com",
+                    "publisher": { "id": "pub-123", "name": "Example News Ltd" }
+                },
+                "app": { "publisher": { "id": 42 } },
+                "device": { "ip": "192.0.2.1", "ipv6": "2001:db8::1", "ifa": "abc", "ua": "UA" }
+            }),
+            response: Value::Null,
+            ts_ms: None,
+            outcome: Value::Null,
+            http_status: Value::Null,
+            line_no: 1,
+        };
+        let redaction = Redaction::parse("all").unwrap();
+        assert_eq!(redaction.names(), REDACTIONS);
+        let redacted = redaction.apply(&record).request;
+        let hashed = redacted["site"]["publisher"]["id"].as_str().unwrap();
+        assert_eq!(hashed.len(), HASH_DIGITS);
+        assert_ne!(hashed, "pub-123");
+        assert_eq!(redacted["site"]["publisher"], json!({ "id": hashed }));
+        assert_eq!(redacted["app"]["publisher"]["id"], redaction.hash("42"));
+        assert_eq!(redacted["device"], json!({ "ua": "UA" }));
+        assert_eq!(redacted["site"]["domain"], "example.co.uk");
+        assert_eq!(redacted["site"]["page"], "https://example.co.uk/");
+
+        // Stable per salt, different across salts
+        let again = redaction.apply(&record).request;
+        assert_eq!(again["site"]["publisher"]["id"], hashed);
+        let salted = Redaction {
+            salt: "secret".to_string(),
+            ..redaction
+        };
+        let salted_id = salted.apply(&record).request["site"]["publisher"]["id"].clone();
+        assert_ne!(salted_id, hashed);
+
+        // Only what's asked for
+        let ip_only = Redaction::parse("ip").unwrap();
+        assert_eq!(ip_only.names(), ["ip"]);
+        let redacted = ip_only.apply(&record).request;
+        assert_eq!(redacted["device"]["ifa"], "abc");
+        assert_eq!(redacted["site"], record.request["site"]);
+        assert!(Redaction::parse("emails").is_err());
+    }
+}
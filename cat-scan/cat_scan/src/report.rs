@@ -0,0 +1,2053 @@
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::{BTreeMap, HashMap},
+    io::Write,
+};
+
+use anyhow::{Context, Result};
+
+use crate::adm::AdmAuditSummary;
+use crate::alerts::{evaluate_rules, RuleAlert};
+use crate::bids::BidDensityReport;
+use crate::categories::category_label;
+use crate::cli::{Config, SortBy};
+use crate::consent::CONSENT_BUCKETS;
+use crate::coverage::COVERAGE_FIELDS;
+use crate::creatives::CreativeSummary;
+use crate::domains::DomainListReport;
+use crate::duplicates::DuplicateSummary;
+use crate::examples::{ExampleRef, RecordSample};
+use crate::fingerprint::FingerprintSummary;
+use crate::floors::FloorSummary;
+use crate::identity::ID_TYPES;
+use crate::input::{Sampler, SkippedLines};
+use crate::inventory::{INVENTORY_DIMENSIONS, INVENTORY_TYPES};
+use crate::ivt::SuspectTrafficReport;
+use crate::lifecycle::LifecycleReport;
+use crate::metadata::{create_csv, generated_at_ms, ScanMetadata};
+use crate::mix::adjusted_bid_rates;
+use crate::position::{POSITIONS, VIEWABILITY_BUCKETS};
+use crate::prices::PriceReport;
+use crate::problems::{find_problem_formats, Detectors, ProblemFormat};
+use crate::qps::{qps_by_ssp, QpsSummary, ALL_SSPS};
+use crate::recommendations::{build_recommendations, Recommendation};
+use crate::seats::SeatSummary;
+use crate::secure::SECURE_BUCKETS;
+use crate::sellers::SellerAuditReport;
+use crate::spoof::SpoofReport;
+use crate::stats::{
+    avg_bid_price, bid_rate, median_bid_price, p90_bid_price, revenue, win_rate, FormatStats,
+    GlobalStats,
+};
+use crate::status::StatusReport;
+
+#[derive(serde::Serialize, Clone)]
+pub struct FormatSummary {
+    pub w: u32,
+    pub h: u32,
+    pub requests: u64,
+    pub bids: u64,
+    pub bid_rate: f64,
+    pub avg_bid_price: f64,
+    pub median_bid_price: f64,
+    pub p90_bid_price: f64,
+    /// Wins per bid and revenue, when the logs carry auction outcomes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub win_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revenue: Option<f64>,
+}
+
+#[derive(serde::Serialize)]
+pub struct PublisherSummary {
+    pub ssp: String,
+    pub publisher_id: String,
+    pub requests: u64,
+    pub bids: u64,
+    pub bid_rate: f64,
+    /// Bid rate at the scan's format mix (see mix::adjusted_bid_rates)
+    pub adjusted_bid_rate: f64,
+    pub avg_bid_price: f64,
+    pub median_bid_price: f64,
+    pub p90_bid_price: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub win_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revenue: Option<f64>,
+}
+
+#[derive(serde::Serialize)]
+pub struct SegmentSummary {
+    pub ssp: String,
+    pub segment: String,
+    pub requests: u64,
+    pub bids: u64,
+    pub bid_rate: f64,
+    pub avg_bid_price: f64,
+    pub median_bid_price: f64,
+    pub p90_bid_price: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub win_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revenue: Option<f64>,
+}
+
+/// Stats for one segment within one publisher
+#[derive(serde::Serialize)]
+pub struct PublisherSegmentSummary {
+    pub ssp: String,
+    pub publisher_id: String,
+    pub segment: String,
+    pub requests: u64,
+    pub bids: u64,
+    pub bid_rate: f64,
+    pub avg_bid_price: f64,
+    pub median_bid_price: f64,
+    pub p90_bid_price: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub win_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revenue: Option<f64>,
+}
+
+#[derive(serde::Serialize)]
+pub struct SspSummary {
+    pub ssp: String,
+    pub requests: u64,
+    pub bids: u64,
+    pub bid_rate: f64,
+    pub avg_bid_price: f64,
+    pub median_bid_price: f64,
+    pub p90_bid_price: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub win_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revenue: Option<f64>,
+}
+
+/// Stats for one DSP that answered (--perspective ssp)
+#[derive(serde::Serialize)]
+pub struct DspSummary {
+    pub dsp: String,
+    pub requests: u64,
+    pub bids: u64,
+    pub bid_rate: f64,
+    pub avg_bid_price: f64,
+    pub median_bid_price: f64,
+    pub p90_bid_price: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub win_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revenue: Option<f64>,
+}
+
+/// Stats for one DSP and canonical format
+#[derive(serde::Serialize)]
+pub struct DspFormatSummary {
+    pub dsp: String,
+    pub w: u32,
+    pub h: u32,
+    pub requests: u64,
+    pub bids: u64,
+    pub bid_rate: f64,
+    pub avg_bid_price: f64,
+}
+
+/// Test traffic (`test` = 1) seen in the scan
+#[derive(serde::Serialize)]
+pub struct TestTrafficReport {
+    /// Left out of every other table (--exclude-test)
+    pub excluded: bool,
+    pub requests: u64,
+    pub bids: u64,
+    pub bid_rate: f64,
+    /// Per SSP, busiest first
+    pub ssps: Vec<SspSummary>,
+}
+
+/// Stats for one SSP and imp.secure bucket
+#[derive(serde::Serialize)]
+pub struct SecureSummary {
+    pub ssp: String,
+    /// See secure::SECURE_BUCKETS
+    pub bucket: &'static str,
+    pub requests: u64,
+    /// requests / the SSP's requests
+    pub share: f64,
+    pub bids: u64,
+    pub bid_rate: f64,
+    pub avg_bid_price: f64,
+    pub median_bid_price: f64,
+    pub p90_bid_price: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub win_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revenue: Option<f64>,
+}
+
+/// Stats for one SSP and consent bucket
+#[derive(serde::Serialize)]
+pub struct ConsentSummary {
+    pub ssp: String,
+    pub bucket: &'static str,
+    pub requests: u64,
+    pub bids: u64,
+    pub bid_rate: f64,
+    pub avg_bid_price: f64,
+    pub median_bid_price: f64,
+    pub p90_bid_price: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub win_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revenue: Option<f64>,
+}
+
+/// Stats for one content category
+#[derive(serde::Serialize)]
+pub struct CategorySummary {
+    pub category: String,
+    /// IAB name (see categories::category_label)
+    pub label: &'static str,
+    pub requests: u64,
+    /// Share of all requests; a request can carry several categories
+    pub share: f64,
+    pub bids: u64,
+    pub bid_rate: f64,
+    pub avg_bid_price: f64,
+    pub median_bid_price: f64,
+    pub p90_bid_price: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub win_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revenue: Option<f64>,
+}
+
+/// Stats for one browser or OS family
+#[derive(serde::Serialize)]
+pub struct UserAgentSummary {
+    /// "browser" or "os"
+    pub dimension: &'static str,
+    /// See ua::ua_families
+    pub family: &'static str,
+    pub requests: u64,
+    /// Share of the requests carrying a device.ua
+    pub share: f64,
+    pub bids: u64,
+    pub bid_rate: f64,
+    pub avg_bid_price: f64,
+    pub median_bid_price: f64,
+    pub p90_bid_price: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub win_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revenue: Option<f64>,
+}
+
+/// Stats for one country
+#[derive(serde::Serialize)]
+pub struct GeoSummary {
+    /// device.geo.country, upper-cased
+    pub country: String,
+    pub requests: u64,
+    /// Share of all requests
+    pub share: f64,
+    pub bids: u64,
+    pub bid_rate: f64,
+    pub avg_bid_price: f64,
+    pub median_bid_price: f64,
+    pub p90_bid_price: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub win_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revenue: Option<f64>,
+}
+
+/// Stats for one inventory type of one format, SSP or publisher
+#[derive(serde::Serialize)]
+pub struct InventorySummary {
+    pub dimension: &'static str,
+    pub key: String,
+    pub inventory: &'static str,
+    pub requests: u64,
+    /// Share of the key's requests
+    pub share: f64,
+    pub bids: u64,
+    pub bid_rate: f64,
+    pub avg_bid_price: f64,
+    pub median_bid_price: f64,
+    pub p90_bid_price: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub win_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revenue: Option<f64>,
+}
+
+/// Stats for one ad position or viewability bucket of one publisher, or of
+/// all requests ("(all)")
+#[derive(serde::Serialize)]
+pub struct PositionSummary {
+    /// "position" or "viewability"
+    pub signal: &'static str,
+    pub publisher: String,
+    pub bucket: &'static str,
+    pub requests: u64,
+    /// Share of the publisher's requests
+    pub share: f64,
+    pub bids: u64,
+    pub bid_rate: f64,
+    pub avg_bid_price: f64,
+    pub median_bid_price: f64,
+    pub p90_bid_price: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub win_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revenue: Option<f64>,
+}
+
+/// Coverage of one identifier type on one SSP, with bid rate and price for
+/// requests with vs without it
+#[derive(serde::Serialize)]
+pub struct IdentitySummary {
+    pub ssp: String,
+    pub id_type: String,
+    pub requests: u64,
+    pub coverage: f64,
+    pub bid_rate: f64,
+    pub avg_bid_price: f64,
+    pub bid_rate_without: f64,
+    pub avg_bid_price_without: f64,
+}
+
+/// Share of one SSP's requests carrying one OpenRTB field
+#[derive(serde::Serialize)]
+pub struct FieldCoverageSummary {
+    pub ssp: String,
+    pub field: &'static str,
+    pub requests: u64,
+    pub coverage: f64,
+}
+
+/// Stats for one value of a custom dimension (--extract) on one SSP
+#[derive(serde::Serialize)]
+pub struct ExtractSummary {
+    pub name: String,
+    pub ssp: String,
+    pub value: String,
+    pub requests: u64,
+    pub bids: u64,
+    pub bid_rate: f64,
+    pub avg_bid_price: f64,
+    pub median_bid_price: f64,
+    pub p90_bid_price: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub win_rate: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revenue: Option<f64>,
+}
+
+/// One time bucket of the report's traffic charts
+#[derive(serde::Serialize)]
+pub struct TimeSeriesPoint {
+    pub bucket_ts_ms: u64,
+    pub requests: u64,
+    pub bids: u64,
+    pub bid_rate: f64,
+    /// (requests, bids) per SSP
+    pub ssps: BTreeMap<String, (u64, u64)>,
+}
+
+/// Complete report data for HTML generation
+#[derive(serde::Serialize)]
+pub struct HtmlReportData {
+    pub source: String,
+    pub total_requests: u64,
+    pub total_publishers: u64,
+    pub total_raw_formats: u64,
+    pub total_canonical_formats: u64,
+    pub min_requests_filter: u64,
+    /// Fraction of input lines parsed (only with --sample / --sample-every)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<f64>,
+    /// Malformed lines skipped under --on-error skip
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub skipped_lines: Option<SkippedLines>,
+    /// Aggregations computed, when --group-by left some out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group_by: Option<Vec<&'static str>>,
+    /// --filter expression the records were scoped by
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filter: Option<String>,
+    /// Records --filter dropped
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filtered_out: Option<u64>,
+    /// Identifiers --redact took out of the records
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redacted: Option<Vec<&'static str>>,
+    /// Requests flagged test=1 (only when there were any)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub test_traffic: Option<TestTrafficReport>,
+    pub formats: Vec<FormatSummary>,
+    pub publishers: Vec<PublisherSummary>,
+    pub segments: Vec<SegmentSummary>,
+    /// Segments within each publisher (only when requests carry both)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub publisher_segments: Vec<PublisherSegmentSummary>,
+    pub ssps: Vec<SspSummary>,
+    /// Per DSP that answered (only with --perspective ssp)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dsps: Vec<DspSummary>,
+    /// Per DSP and canonical format, for comparing DSPs format by format
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub dsp_formats: Vec<DspFormatSummary>,
+    /// Secure vs non-secure requests per SSP (only when requests carry
+    /// imp.secure)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub secure: Vec<SecureSummary>,
+    /// Bid rate by consent bucket per SSP (only when the logs carry privacy signals)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub consent: Vec<ConsentSummary>,
+    /// Volume and bid rate per country (only when requests carry one)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub countries: Vec<GeoSummary>,
+    /// Volume and bid rate per content category (only when requests carry them)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub categories: Vec<CategorySummary>,
+    /// Volume and bid rate per browser and OS family (only when requests
+    /// carry a device.ua)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub user_agents: Vec<UserAgentSummary>,
+    /// Standard vs interstitial vs rewarded per format, SSP and publisher
+    /// (only when some requests are interstitial or rewarded)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub inventory: Vec<InventorySummary>,
+    /// Bid rate and price by ad position and viewability, overall and per
+    /// publisher (only when requests carry pos or a viewability metric)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub positions: Vec<PositionSummary>,
+    /// User ID coverage per SSP and identifier type
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub identity: Vec<IdentitySummary>,
+    /// OpenRTB field coverage per SSP
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub field_coverage: Vec<FieldCoverageSummary>,
+    /// Bid price histograms and percentiles (only when there were bids)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prices: Option<PriceReport>,
+    /// Bids per response (only when there were bids)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bid_density: Option<BidDensityReport>,
+    /// Bid-to-floor ratio per format and SSP (only when floored requests got bids)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub floors: Vec<FloorSummary>,
+    /// No-bid and error shares per SSP and over time (only when log lines
+    /// carry a response status)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response_status: Option<StatusReport>,
+    /// Buyer seats overall ("all") and per canonical format
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub seats: Vec<SeatSummary>,
+    /// Advertiser domains, creative ids and attributes per format, seat
+    /// and publisher
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub creatives: Vec<CreativeSummary>,
+    /// Markup audit per format and seat
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub adm_audit: Vec<AdmAuditSummary>,
+    /// Request id collisions per SSP
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub duplicate_ids: Vec<DuplicateSummary>,
+    /// Repeated requests per SSP (only with --fingerprint)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub fingerprints: Vec<FingerprintSummary>,
+    /// Average / peak QPS per SSP (needs timestamps)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub qps: Vec<QpsSummary>,
+    /// Requests and bids per time bucket, overall and per SSP
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub time_series: Vec<TimeSeriesPoint>,
+    /// Custom dimensions (only with --extract)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub custom_dimensions: Vec<ExtractSummary>,
+    pub problems: Vec<ProblemFormat>,
+    /// What the --alert-rule rules flagged (only with rules)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alerts: Option<Vec<RuleAlert>>,
+    /// Prioritized actions per SSP, from the problem detectors
+    pub recommendations: Vec<Recommendation>,
+    /// Example record refs per dimension and key (only with --examples)
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub examples: BTreeMap<&'static str, BTreeMap<String, Vec<ExampleRef>>>,
+    /// Raw records per problem format, keyed "WxH" (only with --samples)
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub samples: BTreeMap<String, Vec<RecordSample>>,
+    /// Suspicious traffic heuristics (only with --ivt)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suspect_traffic: Option<SuspectTrafficReport>,
+    /// Off-list traffic per SSP (only with --allowlist / --denylist)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub domain_lists: Option<DomainListReport>,
+    /// Supply sellers.json doesn't vouch for (only with --sellers-json)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unauthorized_supply: Option<SellerAuditReport>,
+    /// Likely spoofed domains and bundles (only with --spoof)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spoofed_inventory: Option<SpoofReport>,
+    /// New/disappeared formats and publishers since the previous run (only with --state)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lifecycle: Option<LifecycleReport>,
+    /// --max-rows-per-table, when set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_rows_per_table: Option<usize>,
+    /// Rows left out per table by --max-rows-per-table (only tables that lost rows)
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub truncated_rows: BTreeMap<&'static str, u64>,
+    /// Tool version, inputs, counts, time range and flags of the scan
+    pub metadata: ScanMetadata,
+}
+
+impl HtmlReportData {
+    /// Cut the tables that grow with the traffic (publishers, segments,
+    /// creatives, inventory, positions and custom dimensions) to their first
+    /// `max` rows, or per dimension (per publisher for publisher segments)
+    /// where a table holds several
+    pub fn truncate_tables(&mut self, max: usize) {
+        let dropped = [
+            (
+                "publishers",
+                truncate_groups(&mut self.publishers, max, |_| ""),
+            ),
+            ("segments", truncate_groups(&mut self.segments, max, |_| "")),
+            (
+                "dsp_formats",
+                truncate_groups(&mut self.dsp_formats, max, |r| &r.dsp),
+            ),
+            (
+                "publisher_segments",
+                truncate_groups(&mut self.publisher_segments, max, |r| &r.publisher_id),
+            ),
+            (
+                "creatives",
+                truncate_groups(&mut self.creatives, max, |r| r.dimension),
+            ),
+            (
+                "inventory",
+                truncate_groups(&mut self.inventory, max, |r| r.dimension),
+            ),
+            (
+                "positions",
+                truncate_groups(&mut self.positions, max, |r| r.signal),
+            ),
+            (
+                "custom_dimensions",
+                truncate_groups(&mut self.custom_dimensions, max, |r| &r.name),
+            ),
+        ];
+        self.max_rows_per_table = Some(max);
+        self.truncated_rows = dropped.into_iter().filter(|(_, n)| *n > 0).collect();
+    }
+}
+
+/// Keep the first `max` rows of each group (rows are in display order);
+/// returns how many were dropped
+fn truncate_groups<T>(rows: &mut Vec<T>, max: usize, group: impl Fn(&T) -> &str) -> u64 {
+    let before = rows.len();
+    let mut kept: HashMap<String, usize> = HashMap::new();
+    rows.retain(|r| {
+        let n = kept.entry(group(r).to_string()).or_default();
+        *n += 1;
+        *n <= max
+    });
+    (before - rows.len()) as u64
+}
+
+/// Canonical format rows after the min-requests filter and requested sort
+pub fn build_format_summaries(
+    global: &GlobalStats,
+    min_requests: u64,
+    sort_by: SortBy,
+) -> Vec<FormatSummary> {
+    // Use canonical format stats for main output (reduces 2000+ rows to manageable set)
+    // Move into a Vec for filtering & sorting
+    let mut rows: Vec<((u32, u32), FormatStats)> = global
+        .by_canonical_format
+        .iter()
+        .map(|(&k, v)| (k, v.clone()))
+        .collect();
+
+    // Min-requests filter
+    if min_requests > 0 {
+        rows.retain(|(_, s)| s.requests >= min_requests);
+    }
+
+    // Sorting
+    match sort_by {
+        SortBy::Format => {
+            // already sorted by (w,h) from BTreeMap
+        }
+        SortBy::RequestsDesc => {
+            rows.sort_by(|a, b| {
+                b.1.requests
+                    .cmp(&a.1.requests)
+                    .then_with(|| a.0.cmp(&b.0))
+            });
+        }
+        SortBy::BidRateDesc => {
+            rows.sort_by(|a, b| {
+                let ar = bid_rate(&a.1);
+                let br = bid_rate(&b.1);
+
+                br.partial_cmp(&ar)
+                    .unwrap_or(Ordering::Equal)
+                    .then_with(|| a.0.cmp(&b.0))
+            });
+        }
+    }
+
+    rows.iter()
+        .map(|((w, h), stat)| FormatSummary {
+            w: *w,
+            h: *h,
+            requests: stat.requests,
+            bids: stat.bids,
+            bid_rate: bid_rate(stat),
+            avg_bid_price: avg_bid_price(stat),
+            median_bid_price: median_bid_price(stat),
+            p90_bid_price: p90_bid_price(stat),
+            win_rate: win_rate(stat),
+            revenue: revenue(stat),
+        })
+        .collect()
+}
+
+pub fn build_ssp_summaries(global: &GlobalStats) -> Vec<SspSummary> {
+    ssp_summaries(&global.by_ssp)
+}
+
+fn ssp_summaries(by_ssp: &BTreeMap<String, FormatStats>) -> Vec<SspSummary> {
+    let mut ssps: Vec<SspSummary> = by_ssp
+        .iter()
+        .map(|(ssp, stats)| SspSummary {
+            ssp: ssp.clone(),
+            requests: stats.requests,
+            bids: stats.bids,
+            bid_rate: bid_rate(stats),
+            avg_bid_price: avg_bid_price(stats),
+            median_bid_price: median_bid_price(stats),
+            p90_bid_price: p90_bid_price(stats),
+            win_rate: win_rate(stats),
+            revenue: revenue(stats),
+        })
+        .collect();
+    ssps.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.ssp.cmp(&b.ssp)));
+    ssps
+}
+
+/// DSP rows, busiest first
+pub fn build_dsp_summaries(global: &GlobalStats) -> Vec<DspSummary> {
+    let mut dsps: Vec<DspSummary> = global
+        .by_dsp
+        .iter()
+        .map(|(dsp, stats)| DspSummary {
+            dsp: dsp.clone(),
+            requests: stats.requests,
+            bids: stats.bids,
+            bid_rate: bid_rate(stats),
+            avg_bid_price: avg_bid_price(stats),
+            median_bid_price: median_bid_price(stats),
+            p90_bid_price: p90_bid_price(stats),
+            win_rate: win_rate(stats),
+            revenue: revenue(stats),
+        })
+        .collect();
+    dsps.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.dsp.cmp(&b.dsp)));
+    dsps
+}
+
+/// DSP x canonical format rows, DSPs in name order and their busiest
+/// formats first
+pub fn build_dsp_format_summaries(global: &GlobalStats) -> Vec<DspFormatSummary> {
+    let mut rows: Vec<_> = global.by_dsp_format.iter().collect();
+    rows.sort_by_key(|&((dsp, format), s)| (dsp, Reverse(s.requests), format));
+    rows.into_iter()
+        .map(|((dsp, (w, h)), stats)| DspFormatSummary {
+            dsp: dsp.clone(),
+            w: *w,
+            h: *h,
+            requests: stats.requests,
+            bids: stats.bids,
+            bid_rate: bid_rate(stats),
+            avg_bid_price: avg_bid_price(stats),
+        })
+        .collect()
+}
+
+/// Test traffic totals and per-SSP rows; none when no request had test=1
+pub fn build_test_traffic_report(global: &GlobalStats) -> Option<TestTrafficReport> {
+    if global.test_traffic.is_empty() {
+        return None;
+    }
+    let mut total = FormatStats::default();
+    for stats in global.test_traffic.values() {
+        total.merge(stats);
+    }
+    Some(TestTrafficReport {
+        excluded: global.exclude_test,
+        requests: total.requests,
+        bids: total.bids,
+        bid_rate: bid_rate(&total),
+        ssps: ssp_summaries(&global.test_traffic),
+    })
+}
+
+/// imp.secure rows, SSPs in name order and buckets in SECURE_BUCKETS
+/// order; empty when no request sets imp.secure
+pub fn build_secure_summaries(global: &GlobalStats) -> Vec<SecureSummary> {
+    if global
+        .by_secure
+        .keys()
+        .all(|(_, bucket)| *bucket == "unknown")
+    {
+        return Vec::new();
+    }
+    let mut rows: Vec<SecureSummary> = global
+        .by_secure
+        .iter()
+        .map(|((ssp, bucket), stats)| {
+            let total = global.by_ssp.get(ssp).map_or(0, |s| s.requests);
+            SecureSummary {
+                ssp: ssp.clone(),
+                bucket,
+                requests: stats.requests,
+                share: if total == 0 {
+                    0.0
+                } else {
+                    stats.requests as f64 / total as f64
+                },
+                bids: stats.bids,
+                bid_rate: bid_rate(stats),
+                avg_bid_price: avg_bid_price(stats),
+                median_bid_price: median_bid_price(stats),
+                p90_bid_price: p90_bid_price(stats),
+                win_rate: win_rate(stats),
+                revenue: revenue(stats),
+            }
+        })
+        .collect();
+    let order = |bucket: &str| SECURE_BUCKETS.iter().position(|b| *b == bucket);
+    rows.sort_by(|a, b| {
+        a.ssp
+            .cmp(&b.ssp)
+            .then_with(|| order(a.bucket).cmp(&order(b.bucket)))
+    });
+    rows
+}
+
+/// Consent bucket rows, SSPs in name order and buckets in CONSENT_BUCKETS order
+pub fn build_consent_summaries(global: &GlobalStats) -> Vec<ConsentSummary> {
+    if global.consent_signalled == 0 {
+        return Vec::new();
+    }
+    let mut rows: Vec<ConsentSummary> = global
+        .by_consent
+        .iter()
+        .map(|((ssp, bucket), stats)| ConsentSummary {
+            ssp: ssp.clone(),
+            bucket,
+            requests: stats.requests,
+            bids: stats.bids,
+            bid_rate: bid_rate(stats),
+            avg_bid_price: avg_bid_price(stats),
+            median_bid_price: median_bid_price(stats),
+            p90_bid_price: p90_bid_price(stats),
+            win_rate: win_rate(stats),
+            revenue: revenue(stats),
+        })
+        .collect();
+    let order = |bucket: &str| CONSENT_BUCKETS.iter().position(|b| *b == bucket);
+    rows.sort_by(|a, b| {
+        a.ssp
+            .cmp(&b.ssp)
+            .then_with(|| order(a.bucket).cmp(&order(b.bucket)))
+    });
+    rows
+}
+
+/// Content category rows, busiest first
+pub fn build_category_summaries(global: &GlobalStats) -> Vec<CategorySummary> {
+    let total = global.total_requests();
+    let mut rows: Vec<CategorySummary> = global
+        .by_category
+        .iter()
+        .map(|(category, stats)| CategorySummary {
+            category: category.clone(),
+            label: category_label(category),
+            requests: stats.requests,
+            share: if total == 0 {
+                0.0
+            } else {
+                stats.requests as f64 / total as f64
+            },
+            bids: stats.bids,
+            bid_rate: bid_rate(stats),
+            avg_bid_price: avg_bid_price(stats),
+            median_bid_price: median_bid_price(stats),
+            p90_bid_price: p90_bid_price(stats),
+            win_rate: win_rate(stats),
+            revenue: revenue(stats),
+        })
+        .collect();
+    rows.sort_by(|a, b| {
+        b.requests
+            .cmp(&a.requests)
+            .then_with(|| a.category.cmp(&b.category))
+    });
+    rows
+}
+
+/// Browser rows then OS rows, busiest first within each
+pub fn build_user_agent_summaries(global: &GlobalStats) -> Vec<UserAgentSummary> {
+    // Every request with a device.ua has exactly one browser family
+    let total: u64 = global
+        .by_user_agent
+        .iter()
+        .filter(|((dimension, _), _)| *dimension == "browser")
+        .map(|(_, stats)| stats.requests)
+        .sum();
+    let mut rows: Vec<UserAgentSummary> = global
+        .by_user_agent
+        .iter()
+        .map(|(&(dimension, family), stats)| UserAgentSummary {
+            dimension,
+            family,
+            requests: stats.requests,
+            share: if total == 0 {
+                0.0
+            } else {
+                stats.requests as f64 / total as f64
+            },
+            bids: stats.bids,
+            bid_rate: bid_rate(stats),
+            avg_bid_price: avg_bid_price(stats),
+            median_bid_price: median_bid_price(stats),
+            p90_bid_price: p90_bid_price(stats),
+            win_rate: win_rate(stats),
+            revenue: revenue(stats),
+        })
+        .collect();
+    rows.sort_by(|a, b| {
+        a.dimension
+            .cmp(b.dimension)
+            .then_with(|| b.requests.cmp(&a.requests))
+            .then_with(|| a.family.cmp(b.family))
+    });
+    rows
+}
+
+/// Country rows, busiest first
+pub fn build_geo_summaries(global: &GlobalStats) -> Vec<GeoSummary> {
+    let total = global.total_requests();
+    let mut rows: Vec<GeoSummary> = global
+        .by_country
+        .iter()
+        .map(|(country, stats)| GeoSummary {
+            country: country.clone(),
+            requests: stats.requests,
+            share: if total == 0 {
+                0.0
+            } else {
+                stats.requests as f64 / total as f64
+            },
+            bids: stats.bids,
+            bid_rate: bid_rate(stats),
+            avg_bid_price: avg_bid_price(stats),
+            median_bid_price: median_bid_price(stats),
+            p90_bid_price: p90_bid_price(stats),
+            win_rate: win_rate(stats),
+            revenue: revenue(stats),
+        })
+        .collect();
+    rows.sort_by(|a, b| {
+        b.requests
+            .cmp(&a.requests)
+            .then_with(|| a.country.cmp(&b.country))
+    });
+    rows
+}
+
+/// Inventory type rows per dimension (INVENTORY_DIMENSIONS order), busiest
+/// key first, types in INVENTORY_TYPES order. Empty when every request was
+/// standard inventory.
+pub fn build_inventory_summaries(global: &GlobalStats) -> Vec<InventorySummary> {
+    if global
+        .by_inventory
+        .keys()
+        .all(|(_, _, inventory)| *inventory == "standard")
+    {
+        return Vec::new();
+    }
+    let mut key_requests: HashMap<(&str, &str), u64> = HashMap::new();
+    for ((dimension, key, _), stats) in &global.by_inventory {
+        *key_requests.entry((dimension, key)).or_default() += stats.requests;
+    }
+    let mut rows: Vec<InventorySummary> = global
+        .by_inventory
+        .iter()
+        .map(|((dimension, key, inventory), stats)| {
+            let total = key_requests[&(*dimension, key.as_str())];
+            InventorySummary {
+                dimension,
+                key: key.clone(),
+                inventory,
+                requests: stats.requests,
+                share: if total == 0 {
+                    0.0
+                } else {
+                    stats.requests as f64 / total as f64
+                },
+                bids: stats.bids,
+                bid_rate: bid_rate(stats),
+                avg_bid_price: avg_bid_price(stats),
+                median_bid_price: median_bid_price(stats),
+                p90_bid_price: p90_bid_price(stats),
+                win_rate: win_rate(stats),
+                revenue: revenue(stats),
+            }
+        })
+        .collect();
+    let position = |list: &[&str], item: &str| list.iter().position(|i| *i == item);
+    rows.sort_by_key(|r| {
+        (
+            position(INVENTORY_DIMENSIONS, r.dimension),
+            Reverse(key_requests[&(r.dimension, r.key.as_str())]),
+            r.key.clone(),
+            position(INVENTORY_TYPES, r.inventory),
+        )
+    });
+    rows
+}
+
+/// Position rows, then viewability rows, each only when some request
+/// signals it: "(all)" first, then publishers busiest first, buckets in
+/// POSITIONS / VIEWABILITY_BUCKETS order
+pub fn build_position_summaries(global: &GlobalStats) -> Vec<PositionSummary> {
+    let signals = [
+        ("position", &global.by_position, POSITIONS, "unknown"),
+        (
+            "viewability",
+            &global.by_viewability,
+            VIEWABILITY_BUCKETS,
+            "none",
+        ),
+    ];
+    let mut rows = Vec::new();
+    for (signal, by_bucket, buckets, unsignalled) in signals {
+        if by_bucket.keys().all(|(_, bucket)| *bucket == unsignalled) {
+            continue;
+        }
+        let mut totals: HashMap<&str, u64> = HashMap::new();
+        for ((publisher, _), stats) in by_bucket {
+            *totals.entry(publisher).or_default() += stats.requests;
+        }
+        let mut signal_rows: Vec<PositionSummary> = by_bucket
+            .iter()
+            .map(|((publisher, bucket), stats)| PositionSummary {
+                signal,
+                publisher: publisher.clone(),
+                bucket,
+                requests: stats.requests,
+                share: stats.requests as f64 / totals[publisher.as_str()].max(1) as f64,
+                bids: stats.bids,
+                bid_rate: bid_rate(stats),
+                avg_bid_price: avg_bid_price(stats),
+                median_bid_price: median_bid_price(stats),
+                p90_bid_price: p90_bid_price(stats),
+                win_rate: win_rate(stats),
+                revenue: revenue(stats),
+            })
+            .collect();
+        let order = |bucket: &str| buckets.iter().position(|b| *b == bucket);
+        signal_rows.sort_by(|a, b| {
+            (a.publisher != "(all)")
+                .cmp(&(b.publisher != "(all)"))
+                .then_with(|| totals[b.publisher.as_str()].cmp(&totals[a.publisher.as_str()]))
+                .then_with(|| a.publisher.cmp(&b.publisher))
+                .then_with(|| order(a.bucket).cmp(&order(b.bucket)))
+        });
+        rows.extend(signal_rows);
+    }
+    rows
+}
+
+/// Identifier coverage rows, SSPs in name order, then ID_TYPES order, then
+/// extended ID sources by name
+pub fn build_identity_summaries(global: &GlobalStats) -> Vec<IdentitySummary> {
+    let mut rows = Vec::new();
+    for ((ssp, id_type), with) in &global.by_identity {
+        if id_type == "total" {
+            continue;
+        }
+        let Some(total) = global.by_identity.get(&(ssp.clone(), "total".to_string())) else {
+            continue;
+        };
+        let without = FormatStats {
+            requests: total.requests - with.requests,
+            bids: total.bids - with.bids,
+            sum_bid_price: total.sum_bid_price - with.sum_bid_price,
+            ..Default::default()
+        };
+        rows.push(IdentitySummary {
+            ssp: ssp.clone(),
+            id_type: id_type.clone(),
+            requests: with.requests,
+            coverage: with.requests as f64 / total.requests as f64,
+            bid_rate: bid_rate(with),
+            avg_bid_price: avg_bid_price(with),
+            bid_rate_without: bid_rate(&without),
+            avg_bid_price_without: avg_bid_price(&without),
+        });
+    }
+    let order = |id_type: &str| {
+        ID_TYPES
+            .iter()
+            .position(|t| *t == id_type)
+            .unwrap_or(ID_TYPES.len())
+    };
+    rows.sort_by(|a, b| {
+        a.ssp
+            .cmp(&b.ssp)
+            .then_with(|| order(&a.id_type).cmp(&order(&b.id_type)))
+            .then_with(|| a.id_type.cmp(&b.id_type))
+    });
+    rows
+}
+
+/// Field coverage rows, SSPs in name order, then every COVERAGE_FIELDS entry
+/// (including fields no request carried)
+pub fn build_field_coverage_summaries(global: &GlobalStats) -> Vec<FieldCoverageSummary> {
+    let mut rows = Vec::new();
+    for ((ssp, field), &total) in &global.by_field {
+        if *field != "total" || total == 0 {
+            continue;
+        }
+        for (field, _) in COVERAGE_FIELDS {
+            let requests = global
+                .by_field
+                .get(&(ssp.clone(), *field))
+                .copied()
+                .unwrap_or(0);
+            rows.push(FieldCoverageSummary {
+                ssp: ssp.clone(),
+                field,
+                requests,
+                coverage: requests as f64 / total as f64,
+            });
+        }
+    }
+    rows
+}
+
+/// Custom dimension rows, grouped by dimension name (in rule order), busiest first
+pub fn build_extract_summaries(global: &GlobalStats) -> Vec<ExtractSummary> {
+    let Some(extract) = &global.extract else {
+        return Vec::new();
+    };
+    let mut rows: Vec<ExtractSummary> = extract
+        .by_value
+        .iter()
+        .map(|(key, stats)| ExtractSummary {
+            name: key.name.clone(),
+            ssp: key.ssp.clone(),
+            value: key.value.clone(),
+            requests: stats.requests,
+            bids: stats.bids,
+            bid_rate: bid_rate(stats),
+            avg_bid_price: avg_bid_price(stats),
+            median_bid_price: median_bid_price(stats),
+            p90_bid_price: p90_bid_price(stats),
+            win_rate: win_rate(stats),
+            revenue: revenue(stats),
+        })
+        .collect();
+    let order = |name: &str| extract.rules.iter().position(|r| r.name == name);
+    rows.sort_by(|a, b| {
+        order(&a.name)
+            .cmp(&order(&b.name))
+            .then_with(|| b.requests.cmp(&a.requests))
+            .then_with(|| (&a.ssp, &a.value).cmp(&(&b.ssp, &b.value)))
+    });
+    rows
+}
+
+/// Per-publisher summaries, busiest first
+pub fn build_publisher_summaries(global: &GlobalStats) -> Vec<PublisherSummary> {
+    let adjusted = adjusted_bid_rates(global);
+    let mut publishers: Vec<PublisherSummary> = global
+        .by_publisher
+        .iter()
+        .map(|(key, stats)| PublisherSummary {
+            ssp: key.ssp.clone(),
+            publisher_id: key.publisher_id.clone(),
+            requests: stats.requests,
+            bids: stats.bids,
+            bid_rate: bid_rate(stats),
+            adjusted_bid_rate: adjusted.get(key).copied().unwrap_or(bid_rate(stats)),
+            avg_bid_price: avg_bid_price(stats),
+            median_bid_price: median_bid_price(stats),
+            p90_bid_price: p90_bid_price(stats),
+            win_rate: win_rate(stats),
+            revenue: revenue(stats),
+        })
+        .collect();
+    publishers.sort_by(|a, b| {
+        b.requests
+            .cmp(&a.requests)
+            .then_with(|| (&a.ssp, &a.publisher_id).cmp(&(&b.ssp, &b.publisher_id)))
+    });
+    publishers
+}
+
+/// Per-segment summaries, busiest first
+pub fn build_segment_summaries(global: &GlobalStats) -> Vec<SegmentSummary> {
+    let mut segments: Vec<SegmentSummary> = global
+        .by_segment
+        .iter()
+        .map(|(key, stats)| SegmentSummary {
+            ssp: key.ssp.clone(),
+            segment: key.segment.clone(),
+            requests: stats.requests,
+            bids: stats.bids,
+            bid_rate: bid_rate(stats),
+            avg_bid_price: avg_bid_price(stats),
+            median_bid_price: median_bid_price(stats),
+            p90_bid_price: p90_bid_price(stats),
+            win_rate: win_rate(stats),
+            revenue: revenue(stats),
+        })
+        .collect();
+    segments.sort_by(|a, b| {
+        b.requests
+            .cmp(&a.requests)
+            .then_with(|| (&a.ssp, &a.segment).cmp(&(&b.ssp, &b.segment)))
+    });
+    segments
+}
+
+/// Segments within each publisher, grouped by publisher, busiest segment first
+pub fn build_publisher_segment_summaries(global: &GlobalStats) -> Vec<PublisherSegmentSummary> {
+    let mut rows: Vec<PublisherSegmentSummary> = global
+        .by_publisher_segment
+        .iter()
+        .map(|(key, stats)| PublisherSegmentSummary {
+            ssp: key.ssp.clone(),
+            publisher_id: key.publisher_id.clone(),
+            segment: key.segment.clone(),
+            requests: stats.requests,
+            bids: stats.bids,
+            bid_rate: bid_rate(stats),
+            avg_bid_price: avg_bid_price(stats),
+            median_bid_price: median_bid_price(stats),
+            p90_bid_price: p90_bid_price(stats),
+            win_rate: win_rate(stats),
+            revenue: revenue(stats),
+        })
+        .collect();
+    rows.sort_by(|a, b| {
+        (&a.ssp, &a.publisher_id)
+            .cmp(&(&b.ssp, &b.publisher_id))
+            .then_with(|| b.requests.cmp(&a.requests))
+            .then_with(|| a.segment.cmp(&b.segment))
+    });
+    rows
+}
+
+/// Time buckets in order, for the report's traffic charts
+pub fn build_time_series(global: &GlobalStats) -> Vec<TimeSeriesPoint> {
+    global
+        .time_stats
+        .iter()
+        .map(|(&bucket_ts_ms, t)| TimeSeriesPoint {
+            bucket_ts_ms,
+            requests: t.requests,
+            bids: t.bids,
+            bid_rate: if t.requests == 0 {
+                0.0
+            } else {
+                t.bids as f64 / t.requests as f64
+            },
+            ssps: t
+                .by_ssp
+                .iter()
+                .map(|(ssp, s)| (ssp.clone(), (s.requests, s.bids)))
+                .collect(),
+        })
+        .collect()
+}
+
+/// Assemble everything the HTML report needs from the aggregated stats
+pub fn build_report_data(
+    global: &GlobalStats,
+    config: &Config,
+    summaries: &[FormatSummary],
+    lifecycle: Option<&LifecycleReport>,
+    skipped: &SkippedLines,
+) -> HtmlReportData {
+    let publishers = build_publisher_summaries(global);
+    let segments = build_segment_summaries(global);
+    let problems = find_problem_formats(global, &config.detectors, config.min_requests.max(10));
+    let qps = qps_by_ssp(&global.time_stats);
+    let window_secs = qps
+        .iter()
+        .find(|q| q.ssp == ALL_SSPS)
+        .map(|q| q.window_secs);
+    let recommendations = build_recommendations(global, &qps, config.min_requests.max(10));
+    let samples = problems
+        .iter()
+        .filter_map(|p| {
+            let samples = global.samples.by_format.get(&(p.w, p.h))?;
+            Some((format!("{}x{}", p.w, p.h), samples.clone()))
+        })
+        .collect();
+
+    let mut report = HtmlReportData {
+        source: config.input_path.clone(),
+        total_requests: global.total_requests(),
+        total_publishers: global.by_publisher.len() as u64,
+        total_raw_formats: global.by_raw_format.len() as u64,
+        total_canonical_formats: global.by_canonical_format.len() as u64,
+        min_requests_filter: config.min_requests,
+        sample_rate: (config.sampler != Sampler::All).then(|| config.sampler.fraction()),
+        skipped_lines: (skipped.count > 0).then(|| skipped.clone()),
+        group_by: (!config.group_by.is_all()).then(|| config.group_by.names()),
+        filter: config.filter.as_ref().map(|f| f.text.clone()),
+        filtered_out: config.filter.as_ref().map(|_| global.filtered_out),
+        redacted: config.redact.as_ref().map(|redact| redact.names()),
+        test_traffic: build_test_traffic_report(global),
+        formats: summaries.to_vec(),
+        publishers,
+        segments,
+        publisher_segments: build_publisher_segment_summaries(global),
+        ssps: build_ssp_summaries(global),
+        dsps: build_dsp_summaries(global),
+        dsp_formats: build_dsp_format_summaries(global),
+        secure: build_secure_summaries(global),
+        consent: build_consent_summaries(global),
+        countries: build_geo_summaries(global),
+        categories: build_category_summaries(global),
+        user_agents: build_user_agent_summaries(global),
+        inventory: build_inventory_summaries(global),
+        positions: build_position_summaries(global),
+        identity: build_identity_summaries(global),
+        field_coverage: build_field_coverage_summaries(global),
+        prices: (global.prices.bids() > 0).then(|| global.prices.summarize()),
+        bid_density: (global.bid_density.requests_with_bids() > 0)
+            .then(|| global.bid_density.summarize()),
+        floors: global.floors.summarize(),
+        response_status: (!global.statuses.is_empty()).then(|| global.statuses.summarize()),
+        seats: global.seats.summarize(&global.by_canonical_format),
+        creatives: global.creatives.summarize(),
+        adm_audit: global.adm_audit.summarize(),
+        duplicate_ids: global.duplicate_ids.summarize(),
+        fingerprints: global
+            .fingerprints
+            .as_ref()
+            .map_or_else(Vec::new, |f| f.summarize()),
+        qps,
+        time_series: build_time_series(global),
+        custom_dimensions: build_extract_summaries(global),
+        problems,
+        alerts: (!config.alert_rules.is_empty())
+            .then(|| evaluate_rules(&config.alert_rules, global)),
+        recommendations,
+        examples: global.examples.by_dimension.clone(),
+        samples,
+        suspect_traffic: global.ivt.as_ref().map(|ivt| ivt.summarize()),
+        domain_lists: global
+            .domain_lists
+            .as_ref()
+            .map(|lists| lists.summarize(window_secs)),
+        unauthorized_supply: global
+            .sellers
+            .as_ref()
+            .map(|sellers| sellers.summarize(window_secs)),
+        spoofed_inventory: global.spoof.as_ref().map(|spoof| spoof.summarize()),
+        lifecycle: lifecycle.cloned(),
+        max_rows_per_table: None,
+        truncated_rows: BTreeMap::new(),
+        metadata: ScanMetadata::new(global, config, skipped, generated_at_ms()),
+    };
+    if let Some(max) = config.max_rows_per_table {
+        report.truncate_tables(max);
+    }
+    report
+}
+
+/// Write report.json (the data behind report.html, also served by `cat_scan serve`)
+pub fn write_report_json(out_dir: &str, report: &HtmlReportData) -> Result<()> {
+    let path = format!("{}/report.json", out_dir);
+    let json = serde_json::to_string(report).context("Failed to serialize report to JSON")?;
+    std::fs::write(&path, json).with_context(|| format!("Failed to write {}", path))?;
+    Ok(())
+}
+
+/// examples.json: the dimensions at the top level, next to `metadata`
+#[derive(serde::Serialize)]
+struct ExamplesFile<'a> {
+    metadata: &'a ScanMetadata,
+    #[serde(flatten)]
+    examples: &'a BTreeMap<&'static str, BTreeMap<String, Vec<ExampleRef>>>,
+}
+
+/// Write examples.json (example record refs per aggregation key)
+pub fn write_examples_json(out_dir: &str, global: &GlobalStats, meta: &ScanMetadata) -> Result<()> {
+    let path = format!("{}/examples.json", out_dir);
+    let file = ExamplesFile {
+        metadata: meta,
+        examples: &global.examples.by_dimension,
+    };
+    let json =
+        serde_json::to_string_pretty(&file).context("Failed to serialize examples to JSON")?;
+    std::fs::write(&path, json).with_context(|| format!("Failed to write {}", path))?;
+    if global.examples.is_full() {
+        eprintln!("Example budget exhausted; some keys have fewer examples than requested");
+    }
+    eprintln!("Example records written to: {}", path);
+    Ok(())
+}
+
+/// Extra CSV columns when the logs carry auction outcomes
+fn outcome_columns(outcomes: bool) -> &'static str {
+    if outcomes {
+        ",win_rate,revenue"
+    } else {
+        ""
+    }
+}
+
+/// A row's outcome_columns cells; 0 for a key none of whose records had one
+fn outcome_cells(outcomes: bool, win_rate: Option<f64>, revenue: Option<f64>) -> String {
+    if outcomes {
+        format!(
+            ",{:.4},{:.4}",
+            win_rate.unwrap_or(0.0),
+            revenue.unwrap_or(0.0)
+        )
+    } else {
+        String::new()
+    }
+}
+
+/// Write ssp_format_stats.csv (bid rate per SSP and canonical format)
+pub fn write_ssp_format_csv(
+    out_dir: &str,
+    global: &GlobalStats,
+    meta: &ScanMetadata,
+) -> Result<()> {
+    let path = format!("{}/ssp_format_stats.csv", out_dir);
+    write_keyed_format_csv(&path, "ssp", &global.by_ssp_format, global, meta)?;
+    eprintln!("SSP format stats written to: {}", path);
+    Ok(())
+}
+
+/// Write dsp_stats.csv and dsp_format_stats.csv (bid rate per DSP, and
+/// per DSP and canonical format)
+pub fn write_dsp_csvs(out_dir: &str, global: &GlobalStats, meta: &ScanMetadata) -> Result<()> {
+    let path = format!("{}/dsp_stats.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    let outcomes = global.has_outcomes();
+    writeln!(
+        csv,
+        "dsp,requests,bids,bid_rate,avg_bid_price,median_bid_price,p90_bid_price{}",
+        outcome_columns(outcomes)
+    )?;
+    for row in build_dsp_summaries(global) {
+        writeln!(
+            csv,
+            "{},{},{},{:.4},{:.4},{:.4},{:.4}{}",
+            row.dsp,
+            row.requests,
+            row.bids,
+            row.bid_rate,
+            row.avg_bid_price,
+            row.median_bid_price,
+            row.p90_bid_price,
+            outcome_cells(outcomes, row.win_rate, row.revenue)
+        )?;
+    }
+    eprintln!("DSP stats written to: {}", path);
+
+    let path = format!("{}/dsp_format_stats.csv", out_dir);
+    write_keyed_format_csv(&path, "dsp", &global.by_dsp_format, global, meta)?;
+    eprintln!("DSP format stats written to: {}", path);
+    Ok(())
+}
+
+/// Write per-(key, canonical format) rows with `key` as the first column
+fn write_keyed_format_csv(
+    path: &str,
+    key: &str,
+    by_format: &BTreeMap<(String, (u32, u32)), FormatStats>,
+    global: &GlobalStats,
+    meta: &ScanMetadata,
+) -> Result<()> {
+    let mut csv = create_csv(path, meta)?;
+    let outcomes = global.has_outcomes();
+    writeln!(
+        csv,
+        "{},w,h,requests,bids,bid_rate,avg_bid_price,median_bid_price,p90_bid_price{}",
+        key,
+        outcome_columns(outcomes)
+    )?;
+    let mut rows: Vec<_> = by_format.iter().collect();
+    rows.sort_by_key(|&((key, format), s)| (key, Reverse(s.requests), format));
+    for ((key, (w, h)), stats) in rows {
+        writeln!(
+            csv,
+            "{},{},{},{},{},{:.4},{:.4},{:.4},{:.4}{}",
+            key,
+            w,
+            h,
+            stats.requests,
+            stats.bids,
+            bid_rate(stats),
+            avg_bid_price(stats),
+            median_bid_price(stats),
+            p90_bid_price(stats),
+            outcome_cells(outcomes, win_rate(stats), revenue(stats))
+        )?;
+    }
+    Ok(())
+}
+
+/// Write secure_stats.csv (secure vs non-secure requests per SSP)
+pub fn write_secure_csv(out_dir: &str, global: &GlobalStats, meta: &ScanMetadata) -> Result<()> {
+    let path = format!("{}/secure_stats.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    let outcomes = global.has_outcomes();
+    writeln!(
+        csv,
+        "ssp,bucket,requests,share,bids,bid_rate,avg_bid_price,median_bid_price,p90_bid_price{}",
+        outcome_columns(outcomes)
+    )?;
+    for row in build_secure_summaries(global) {
+        writeln!(
+            csv,
+            "{},{},{},{:.4},{},{:.4},{:.4},{:.4},{:.4}{}",
+            row.ssp,
+            row.bucket,
+            row.requests,
+            row.share,
+            row.bids,
+            row.bid_rate,
+            row.avg_bid_price,
+            row.median_bid_price,
+            row.p90_bid_price,
+            outcome_cells(outcomes, row.win_rate, row.revenue)
+        )?;
+    }
+    eprintln!("Secure stats written to: {}", path);
+    Ok(())
+}
+
+/// Write consent_stats.csv (bid rate per SSP and consent bucket)
+pub fn write_consent_csv(out_dir: &str, global: &GlobalStats, meta: &ScanMetadata) -> Result<()> {
+    let path = format!("{}/consent_stats.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    let outcomes = global.has_outcomes();
+    writeln!(
+        csv,
+        "ssp,bucket,requests,bids,bid_rate,avg_bid_price,median_bid_price,p90_bid_price{}",
+        outcome_columns(outcomes)
+    )?;
+    for row in build_consent_summaries(global) {
+        writeln!(
+            csv,
+            "{},{},{},{},{:.4},{:.4},{:.4},{:.4}{}",
+            row.ssp,
+            row.bucket,
+            row.requests,
+            row.bids,
+            row.bid_rate,
+            row.avg_bid_price,
+            row.median_bid_price,
+            row.p90_bid_price,
+            outcome_cells(outcomes, row.win_rate, row.revenue)
+        )?;
+    }
+    eprintln!("Consent stats written to: {}", path);
+    Ok(())
+}
+
+/// Write category_stats.csv (volume and bid rate per content category)
+pub fn write_category_csv(out_dir: &str, global: &GlobalStats, meta: &ScanMetadata) -> Result<()> {
+    let path = format!("{}/category_stats.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    let outcomes = global.has_outcomes();
+    writeln!(
+        csv,
+        "category,label,requests,share,bids,bid_rate,avg_bid_price,median_bid_price,p90_bid_price{}",
+        outcome_columns(outcomes)
+    )?;
+    for row in build_category_summaries(global) {
+        writeln!(
+            csv,
+            "{},\"{}\",{},{:.4},{},{:.4},{:.4},{:.4},{:.4}{}",
+            row.category,
+            row.label,
+            row.requests,
+            row.share,
+            row.bids,
+            row.bid_rate,
+            row.avg_bid_price,
+            row.median_bid_price,
+            row.p90_bid_price,
+            outcome_cells(outcomes, row.win_rate, row.revenue)
+        )?;
+    }
+    eprintln!("Category stats written to: {}", path);
+    Ok(())
+}
+
+/// Write user_agent_stats.csv (volume and bid rate per browser and OS family)
+pub fn write_user_agent_csv(
+    out_dir: &str,
+    global: &GlobalStats,
+    meta: &ScanMetadata,
+) -> Result<()> {
+    let path = format!("{}/user_agent_stats.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    let outcomes = global.has_outcomes();
+    writeln!(
+        csv,
+        "dimension,family,requests,share,bids,bid_rate,avg_bid_price,median_bid_price,p90_bid_price{}",
+        outcome_columns(outcomes)
+    )?;
+    for row in build_user_agent_summaries(global) {
+        writeln!(
+            csv,
+            "{},{},{},{:.4},{},{:.4},{:.4},{:.4},{:.4}{}",
+            row.dimension,
+            row.family,
+            row.requests,
+            row.share,
+            row.bids,
+            row.bid_rate,
+            row.avg_bid_price,
+            row.median_bid_price,
+            row.p90_bid_price,
+            outcome_cells(outcomes, row.win_rate, row.revenue)
+        )?;
+    }
+    eprintln!("User agent stats written to: {}", path);
+    Ok(())
+}
+
+/// Write geo_stats.csv (volume and bid rate per country)
+pub fn write_geo_csv(out_dir: &str, global: &GlobalStats, meta: &ScanMetadata) -> Result<()> {
+    let path = format!("{}/geo_stats.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    let outcomes = global.has_outcomes();
+    writeln!(
+        csv,
+        "country,requests,share,bids,bid_rate,avg_bid_price,median_bid_price,p90_bid_price{}",
+        outcome_columns(outcomes)
+    )?;
+    for row in build_geo_summaries(global) {
+        writeln!(
+            csv,
+            "{},{},{:.4},{},{:.4},{:.4},{:.4},{:.4}{}",
+            row.country,
+            row.requests,
+            row.share,
+            row.bids,
+            row.bid_rate,
+            row.avg_bid_price,
+            row.median_bid_price,
+            row.p90_bid_price,
+            outcome_cells(outcomes, row.win_rate, row.revenue)
+        )?;
+    }
+    eprintln!("Geo stats written to: {}", path);
+    Ok(())
+}
+
+/// Write inventory_stats.csv (standard, interstitial and rewarded inventory
+/// per format, SSP and publisher)
+pub fn write_inventory_csv(out_dir: &str, global: &GlobalStats, meta: &ScanMetadata) -> Result<()> {
+    let path = format!("{}/inventory_stats.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    let outcomes = global.has_outcomes();
+    writeln!(
+        csv,
+        "dimension,key,inventory,requests,share,bids,bid_rate,avg_bid_price,median_bid_price,p90_bid_price{}",
+        outcome_columns(outcomes)
+    )?;
+    for row in build_inventory_summaries(global) {
+        writeln!(
+            csv,
+            "{},{},{},{},{:.4},{},{:.4},{:.4},{:.4},{:.4}{}",
+            row.dimension,
+            row.key,
+            row.inventory,
+            row.requests,
+            row.share,
+            row.bids,
+            row.bid_rate,
+            row.avg_bid_price,
+            row.median_bid_price,
+            row.p90_bid_price,
+            outcome_cells(outcomes, row.win_rate, row.revenue)
+        )?;
+    }
+    eprintln!("Inventory stats written to: {}", path);
+    Ok(())
+}
+
+/// Write position_stats.csv (bid rate and price by ad position and
+/// viewability, overall and per publisher)
+pub fn write_position_csv(out_dir: &str, global: &GlobalStats, meta: &ScanMetadata) -> Result<()> {
+    let path = format!("{}/position_stats.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    let outcomes = global.has_outcomes();
+    writeln!(
+        csv,
+        "signal,publisher,bucket,requests,share,bids,bid_rate,avg_bid_price,median_bid_price,p90_bid_price{}",
+        outcome_columns(outcomes)
+    )?;
+    for row in build_position_summaries(global) {
+        writeln!(
+            csv,
+            "{},{},{},{},{:.4},{},{:.4},{:.4},{:.4},{:.4}{}",
+            row.signal,
+            row.publisher,
+            row.bucket,
+            row.requests,
+            row.share,
+            row.bids,
+            row.bid_rate,
+            row.avg_bid_price,
+            row.median_bid_price,
+            row.p90_bid_price,
+            outcome_cells(outcomes, row.win_rate, row.revenue)
+        )?;
+    }
+    eprintln!("Position stats written to: {}", path);
+    Ok(())
+}
+
+/// Write identity_stats.csv (user ID coverage per SSP and identifier type)
+pub fn write_identity_csv(out_dir: &str, global: &GlobalStats, meta: &ScanMetadata) -> Result<()> {
+    let path = format!("{}/identity_stats.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    writeln!(
+        csv,
+        "ssp,id_type,requests,coverage,bid_rate,avg_bid_price,bid_rate_without,avg_bid_price_without"
+    )?;
+    for row in build_identity_summaries(global) {
+        writeln!(
+            csv,
+            "{},{},{},{:.4},{:.4},{:.4},{:.4},{:.4}",
+            row.ssp,
+            row.id_type,
+            row.requests,
+            row.coverage,
+            row.bid_rate,
+            row.avg_bid_price,
+            row.bid_rate_without,
+            row.avg_bid_price_without
+        )?;
+    }
+    eprintln!("Identity stats written to: {}", path);
+    Ok(())
+}
+
+/// Write field_coverage.csv (OpenRTB field presence per SSP)
+pub fn write_field_coverage_csv(
+    out_dir: &str,
+    global: &GlobalStats,
+    meta: &ScanMetadata,
+) -> Result<()> {
+    let path = format!("{}/field_coverage.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    writeln!(csv, "ssp,field,requests,coverage")?;
+    for row in build_field_coverage_summaries(global) {
+        writeln!(
+            csv,
+            "{},{},{},{:.4}",
+            row.ssp, row.field, row.requests, row.coverage
+        )?;
+    }
+    eprintln!("Field coverage written to: {}", path);
+    Ok(())
+}
+
+/// Write custom_dimensions.csv (--extract values per SSP)
+pub fn write_extract_csv(out_dir: &str, global: &GlobalStats, meta: &ScanMetadata) -> Result<()> {
+    let path = format!("{}/custom_dimensions.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    let outcomes = global.has_outcomes();
+    writeln!(
+        csv,
+        "dimension,ssp,value,requests,bids,bid_rate,avg_bid_price,median_bid_price,p90_bid_price{}",
+        outcome_columns(outcomes)
+    )?;
+    for row in build_extract_summaries(global) {
+        writeln!(
+            csv,
+            "{},{},{},{},{},{:.4},{:.4},{:.4},{:.4}{}",
+            row.name,
+            row.ssp,
+            row.value,
+            row.requests,
+            row.bids,
+            row.bid_rate,
+            row.avg_bid_price,
+            row.median_bid_price,
+            row.p90_bid_price,
+            outcome_cells(outcomes, row.win_rate, row.revenue)
+        )?;
+    }
+    eprintln!("Custom dimensions written to: {}", path);
+    Ok(())
+}
+
+/// Write format_stats.csv into the output directory
+pub fn write_format_csv(
+    out_dir: &str,
+    summaries: &[FormatSummary],
+    meta: &ScanMetadata,
+) -> Result<()> {
+    let format_csv_path = format!("{}/format_stats.csv", out_dir);
+    let mut format_csv = create_csv(&format_csv_path, meta)?;
+    let outcomes = summaries.iter().any(|s| s.win_rate.is_some());
+    writeln!(
+        format_csv,
+        "w,h,requests,bids,bid_rate,avg_bid_price,median_bid_price,p90_bid_price{}",
+        outcome_columns(outcomes)
+    )?;
+    for s in summaries {
+        writeln!(
+            format_csv,
+            "{},{},{},{},{:.4},{:.4},{:.4},{:.4}{}",
+            s.w,
+            s.h,
+            s.requests,
+            s.bids,
+            s.bid_rate,
+            s.avg_bid_price,
+            s.median_bid_price,
+            s.p90_bid_price,
+            outcome_cells(outcomes, s.win_rate, s.revenue)
+        )?;
+    }
+    eprintln!("Format stats written to: {}", format_csv_path);
+    Ok(())
+}
+
+/// Write segment_stats.csv (publisher + segment data) into the output directory
+pub fn write_segment_csv(out_dir: &str, global: &GlobalStats, meta: &ScanMetadata) -> Result<()> {
+    let segment_csv_path = format!("{}/segment_stats.csv", out_dir);
+    let mut segment_csv = create_csv(&segment_csv_path, meta)?;
+    let outcomes = global.has_outcomes();
+
+    // Publisher section
+    writeln!(segment_csv, "# Publishers")?;
+    writeln!(
+        segment_csv,
+        "type,id,ssp,requests,bids,bid_rate,avg_bid_price,median_bid_price,p90_bid_price{}",
+        outcome_columns(outcomes)
+    )?;
+    let mut pub_vec: Vec<_> = global.by_publisher.iter().collect();
+    pub_vec.sort_by_key(|&(key, s)| (Reverse(s.requests), key));
+    for (key, stats) in &pub_vec {
+        writeln!(
+            segment_csv,
+            "publisher,{},{},{},{},{:.4},{:.4},{:.4},{:.4}{}",
+            key.publisher_id,
+            key.ssp,
+            stats.requests,
+            stats.bids,
+            bid_rate(stats),
+            avg_bid_price(stats),
+            median_bid_price(stats),
+            p90_bid_price(stats),
+            outcome_cells(outcomes, win_rate(stats), revenue(stats))
+        )?;
+    }
+
+    // Segment section
+    writeln!(segment_csv, "\n# Segments")?;
+    let mut seg_vec: Vec<_> = global.by_segment.iter().collect();
+    seg_vec.sort_by_key(|&(key, s)| (Reverse(s.requests), key));
+    for (key, stats) in &seg_vec {
+        writeln!(
+            segment_csv,
+            "segment,{},{},{},{},{:.4},{:.4},{:.4},{:.4}{}",
+            key.segment,
+            key.ssp,
+            stats.requests,
+            stats.bids,
+            bid_rate(stats),
+            avg_bid_price(stats),
+            median_bid_price(stats),
+            p90_bid_price(stats),
+            outcome_cells(outcomes, win_rate(stats), revenue(stats))
+        )?;
+    }
+    eprintln!("Segment stats written to: {}", segment_csv_path);
+    Ok(())
+}
+
+/// Write publisher_segment_stats.csv (segments within each publisher)
+pub fn write_publisher_segment_csv(
+    out_dir: &str,
+    global: &GlobalStats,
+    meta: &ScanMetadata,
+) -> Result<()> {
+    let path = format!("{}/publisher_segment_stats.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    let outcomes = global.has_outcomes();
+    writeln!(
+        csv,
+        "ssp,publisher_id,segment,requests,bids,bid_rate,avg_bid_price,median_bid_price,p90_bid_price{}",
+        outcome_columns(outcomes)
+    )?;
+    for row in build_publisher_segment_summaries(global) {
+        writeln!(
+            csv,
+            "{},{},{},{},{},{:.4},{:.4},{:.4},{:.4}{}",
+            row.ssp,
+            row.publisher_id,
+            row.segment,
+            row.requests,
+            row.bids,
+            row.bid_rate,
+            row.avg_bid_price,
+            row.median_bid_price,
+            row.p90_bid_price,
+            outcome_cells(outcomes, row.win_rate, row.revenue)
+        )?;
+    }
+    eprintln!("Publisher segment stats written to: {}", path);
+    Ok(())
+}
+
+/// Print canonical format rows as CSV to stdout (default behavior)
+pub fn print_format_csv(summaries: &[FormatSummary]) {
+    let outcomes = summaries.iter().any(|s| s.win_rate.is_some());
+    println!(
+        "w,h,requests,bids,bid_rate,avg_bid_price,median_bid_price,p90_bid_price{}",
+        outcome_columns(outcomes)
+    );
+    for s in summaries {
+        println!(
+            "{},{},{},{},{:.4},{:.4},{:.4},{:.4}{}",
+            s.w,
+            s.h,
+            s.requests,
+            s.bids,
+            s.bid_rate,
+            s.avg_bid_price,
+            s.median_bid_price,
+            s.p90_bid_price,
+            outcome_cells(outcomes, s.win_rate, s.revenue)
+        );
+    }
+}
+
+/// Time-based analysis on stderr (--time-analysis)
+pub fn print_time_analysis(global: &GlobalStats) {
+    eprintln!("\n=== Time-based Analysis ===");
+    eprintln!("bucket_ts_ms,requests,bids,bid_rate,avg_bid_price,peak_qps");
+
+    for (bucket, stats) in &global.time_stats {
+        let rate = if stats.requests == 0 {
+            0.0
+        } else {
+            stats.bids as f64 / stats.requests as f64
+        };
+        let avg_price = if stats.bids == 0 {
+            0.0
+        } else {
+            stats.sum_bid_price / stats.bids as f64
+        };
+        eprintln!(
+            "{},{},{},{:.4},{:.4},{}",
+            bucket,
+            stats.requests,
+            stats.bids,
+            rate,
+            avg_price,
+            stats.peak_qps()
+        );
+    }
+
+    // Summary stats
+    let total_reqs: u64 = global.time_stats.values().map(|s| s.requests).sum();
+    let total_bids: u64 = global.time_stats.values().map(|s| s.bids).sum();
+    let overall_rate = if total_reqs == 0 {
+        0.0
+    } else {
+        total_bids as f64 / total_reqs as f64
+    };
+
+    // Time range
+    let min_ts = global
+        .time_stats
+        .values()
+        .map(|s| s.min_ts)
+        .min()
+        .unwrap_or(0);
+    let max_ts = global
+        .time_stats
+        .values()
+        .map(|s| s.max_ts)
+        .max()
+        .unwrap_or(0);
+    let duration_ms = max_ts.saturating_sub(min_ts);
+    let duration_sec = duration_ms as f64 / 1000.0;
+
+    eprintln!(
+        "\nTime range: {}ms ({:.2}s), {} buckets, overall bid rate: {:.2}%",
+        duration_ms,
+        duration_sec,
+        global.time_stats.len(),
+        overall_rate * 100.0
+    );
+
+    eprintln!("\n=== QPS per SSP ===");
+    eprintln!("ssp,requests,avg_qps,peak_qps,burstiness");
+    for r in qps_by_ssp(&global.time_stats) {
+        eprintln!(
+            "{},{},{:.2},{},{:.2}",
+            r.ssp, r.requests, r.avg_qps, r.peak_qps, r.burstiness
+        );
+    }
+}
+
+/// Publisher, segment, SSP and problem breakdowns on stderr (--segment-stats)
+pub fn print_segment_stats(global: &GlobalStats, min_requests: u64, detectors: &Detectors) {
+    // Publisher stats
+    if !global.by_publisher.is_empty() {
+        eprintln!("\n=== Publisher Stats ===");
+        eprintln!("publisher,requests,bids,bid_rate,avg_bid_price");
+
+        let mut pub_vec: Vec<_> = global.by_publisher.iter().collect();
+        pub_vec.sort_by_key(|&(key, s)| (Reverse(s.requests), key));
+
+        for (key, stats) in pub_vec {
+            eprintln!(
+                "{},{},{},{:.4},{:.4}",
+                key.publisher_id,
+                stats.requests,
+                stats.bids,
+                bid_rate(stats),
+                avg_bid_price(stats)
+            );
+        }
+    }
+
+    // Segment stats
+    if !global.by_segment.is_empty() {
+        eprintln!("\n=== Segment Stats ===");
+        eprintln!("segment,requests,bids,bid_rate,avg_bid_price");
+
+        let mut seg_vec: Vec<_> = global.by_segment.iter().collect();
+        seg_vec.sort_by_key(|&(key, s)| (Reverse(s.requests), key));
+
+        for (key, stats) in seg_vec {
+            eprintln!(
+                "{},{},{},{:.4},{:.4}",
+                key.segment,
+                stats.requests,
+                stats.bids,
+                bid_rate(stats),
+                avg_bid_price(stats)
+            );
+        }
+    }
+
+    // SSP stats
+    if !global.by_ssp.is_empty() {
+        eprintln!("\n=== SSP Stats ===");
+        eprintln!("ssp,requests,bids,bid_rate,avg_bid_price");
+
+        let mut ssp_vec: Vec<_> = global.by_ssp.iter().collect();
+        ssp_vec.sort_by_key(|&(key, s)| (Reverse(s.requests), key));
+
+        for (ssp, stats) in ssp_vec {
+            eprintln!(
+                "{},{},{},{:.4},{:.4}",
+                ssp,
+                stats.requests,
+                stats.bids,
+                bid_rate(stats),
+                avg_bid_price(stats)
+            );
+        }
+    }
+
+    // Problem formats
+    let problems = find_problem_formats(global, detectors, min_requests.max(10));
+    if !problems.is_empty() {
+        eprintln!("\n=== Problem Formats ===");
+        eprintln!("w,h,requests,bids,bid_rate,problem_type");
+
+        for p in &problems {
+            eprintln!(
+                "{},{},{},{},{:.4},{}",
+                p.w, p.h, p.requests, p.bids, p.bid_rate, p.problem_type
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_truncate_groups() {
+        let mut rows = vec![("a", 1), ("a", 2), ("b", 3), ("a", 4), ("b", 5), ("b", 6)];
+        assert_eq!(truncate_groups(&mut rows, 2, |r| r.0), 2);
+        assert_eq!(rows, vec![("a", 1), ("a", 2), ("b", 3), ("b", 5)]);
+        assert_eq!(truncate_groups(&mut rows, 1, |_| ""), 3);
+        assert_eq!(rows, vec![("a", 1)]);
+    }
+
+    #[test]
+    fn test_inventory_summaries() {
+        let mut global = GlobalStats::new();
+        let mut stats = FormatStats {
+            requests: 3,
+            bids: 1,
+            sum_bid_price: 1.0,
+            ..Default::default()
+        };
+        let key = |dimension, key: &str, inventory| (dimension, key.to_string(), inventory);
+        global
+            .by_inventory
+            .insert(key("format", "300x250", "standard"), stats.clone());
+        // All standard: nothing to separate
+        assert!(build_inventory_summaries(&global).is_empty());
+
+        stats.requests = 1;
+        global
+            .by_inventory
+            .insert(key("format", "300x250", "rewarded"), stats.clone());
+        global
+            .by_inventory
+            .insert(key("format", "320x480", "interstitial"), stats);
+        let rows: Vec<_> = build_inventory_summaries(&global)
+            .into_iter()
+            .map(|r| (r.key, r.inventory, r.share))
+            .collect();
+        assert_eq!(
+            rows,
+            vec![
+                ("300x250".to_string(), "standard", 0.75),
+                ("300x250".to_string(), "rewarded", 0.25),
+                ("320x480".to_string(), "interstitial", 1.0),
+            ]
+        );
+    }
+}
@@ -0,0 +1,315 @@
+use std::{cmp::Reverse, collections::BTreeMap, io::Write};
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::bids::{ResponseBids, SeatBids};
+use crate::metadata::{create_csv, ScanMetadata};
+use crate::stats::{scale_count, FormatStats};
+
+/// How one buyer seat bid on one format
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SeatStats {
+    /// Responses with at least one bid from this seat
+    pub responses: u64,
+    pub bids: u64,
+    pub sum_price: f64,
+    pub min_price: f64,
+    pub max_price: f64,
+    /// Responses where this seat held the highest bid
+    pub wins: u64,
+}
+
+impl SeatStats {
+    fn add(&mut self, seat: &SeatBids, won: bool) {
+        self.min_price = if self.bids == 0 {
+            seat.lowest_price
+        } else {
+            self.min_price.min(seat.lowest_price)
+        };
+        self.max_price = self.max_price.max(seat.highest_price);
+        self.responses += 1;
+        self.bids += seat.bids;
+        self.sum_price += seat.sum_price;
+        if won {
+            self.wins += 1;
+        }
+    }
+
+    fn merge(&mut self, other: &SeatStats) {
+        if other.bids == 0 {
+            return;
+        }
+        self.min_price = if self.bids == 0 {
+            other.min_price
+        } else {
+            self.min_price.min(other.min_price)
+        };
+        self.max_price = self.max_price.max(other.max_price);
+        self.responses += other.responses;
+        self.bids += other.bids;
+        self.sum_price += other.sum_price;
+        self.wins += other.wins;
+    }
+}
+
+fn seat_list(request: &Value, field: &str) -> Vec<String> {
+    request[field]
+        .as_array()
+        .map(|seats| {
+            seats
+                .iter()
+                .filter_map(|s| s.as_str().map(str::to_string))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Seat bidding per canonical format, plus the `wseat`/`bseat` restrictions
+/// needed to tell how many requests each seat was allowed to bid on
+#[derive(Debug, Default, Clone)]
+pub struct SeatActivity {
+    pub by_seat_format: BTreeMap<(String, (u32, u32)), SeatStats>,
+    /// Requests per format carrying a `wseat` allowlist
+    pub allowlisted: BTreeMap<(u32, u32), u64>,
+    /// Requests per (seat, format) whose allowlist names the seat
+    pub allowed: BTreeMap<(String, (u32, u32)), u64>,
+    /// Requests per (seat, format) without an allowlist that block the seat
+    pub blocked: BTreeMap<(String, (u32, u32)), u64>,
+}
+
+impl SeatActivity {
+    pub fn observe(&mut self, request: &Value, format: (u32, u32), bids: &ResponseBids) {
+        let wseat = seat_list(request, "wseat");
+        let bseat = seat_list(request, "bseat");
+        if !wseat.is_empty() {
+            *self.allowlisted.entry(format).or_default() += 1;
+            for seat in wseat.into_iter().filter(|s| !bseat.contains(s)) {
+                *self.allowed.entry((seat, format)).or_default() += 1;
+            }
+        } else {
+            for seat in bseat {
+                *self.blocked.entry((seat, format)).or_default() += 1;
+            }
+        }
+
+        let winner = bids.winning_seat();
+        for seat in &bids.seats {
+            self.by_seat_format
+                .entry((seat.seat.clone(), format))
+                .or_default()
+                .add(seat, winner == Some(seat.seat.as_str()));
+        }
+    }
+
+    pub fn merge(&mut self, other: &SeatActivity) {
+        for (key, stats) in &other.by_seat_format {
+            self.by_seat_format
+                .entry(key.clone())
+                .or_default()
+                .merge(stats);
+        }
+        for (format, count) in &other.allowlisted {
+            *self.allowlisted.entry(*format).or_default() += count;
+        }
+        for (key, count) in &other.allowed {
+            *self.allowed.entry(key.clone()).or_default() += count;
+        }
+        for (key, count) in &other.blocked {
+            *self.blocked.entry(key.clone()).or_default() += count;
+        }
+    }
+
+    /// Multiply counts by `factor` (extrapolating from a sample)
+    pub fn scale(&mut self, factor: f64) {
+        for stats in self.by_seat_format.values_mut() {
+            scale_count(&mut stats.responses, factor);
+            scale_count(&mut stats.bids, factor);
+            scale_count(&mut stats.wins, factor);
+            stats.sum_price *= factor;
+        }
+        let counts = self
+            .allowlisted
+            .values_mut()
+            .chain(self.allowed.values_mut())
+            .chain(self.blocked.values_mut());
+        for count in counts {
+            scale_count(count, factor);
+        }
+    }
+
+    /// Requests of `format` that `seat` was allowed to bid on, out of
+    /// `requests` of that format
+    fn requests_seen(&self, seat: &str, format: (u32, u32), requests: u64) -> u64 {
+        let key = (seat.to_string(), format);
+        let get = |map: &BTreeMap<(String, (u32, u32)), u64>| map.get(&key).copied().unwrap_or(0);
+        let allowlisted = self.allowlisted.get(&format).copied().unwrap_or(0);
+        (requests + get(&self.allowed)).saturating_sub(allowlisted + get(&self.blocked))
+    }
+
+    /// Per-seat totals (format "all", busiest seat first) followed by each
+    /// seat's formats, busiest first. `formats` holds the request counts per
+    /// canonical format.
+    pub fn summarize(&self, formats: &BTreeMap<(u32, u32), FormatStats>) -> Vec<SeatSummary> {
+        let mut totals: BTreeMap<&str, (u64, SeatStats)> = BTreeMap::new();
+        let mut by_format = Vec::new();
+        for ((seat, format), stats) in &self.by_seat_format {
+            let requests = formats.get(format).map_or(0, |f| f.requests);
+            let seen = self.requests_seen(seat, *format, requests);
+            let total = totals.entry(seat).or_default();
+            total.1.merge(stats);
+            by_format.push(SeatSummary::new(
+                seat,
+                format!("{}x{}", format.0, format.1),
+                seen,
+                stats,
+            ));
+        }
+        // A seat's total reach includes formats it never bid on
+        for (seat, (seen, _)) in totals.iter_mut() {
+            *seen = formats
+                .iter()
+                .map(|(format, f)| self.requests_seen(seat, *format, f.requests))
+                .sum();
+        }
+
+        let mut rows: Vec<SeatSummary> = totals
+            .into_iter()
+            .map(|(seat, (seen, stats))| SeatSummary::new(seat, "all".to_string(), seen, &stats))
+            .collect();
+        rows.sort_by(|a, b| {
+            b.responses
+                .cmp(&a.responses)
+                .then_with(|| a.seat.cmp(&b.seat))
+        });
+        // Stable, so ties stay in (w, h) order
+        by_format.sort_by_key(|r| Reverse(r.responses));
+        let order: Vec<String> = rows.iter().map(|r| r.seat.clone()).collect();
+        by_format.sort_by_key(|r| order.iter().position(|seat| *seat == r.seat));
+        rows.extend(by_format);
+        rows
+    }
+}
+
+/// One seat's bidding overall ("all") or on one canonical format
+#[derive(serde::Serialize)]
+pub struct SeatSummary {
+    pub seat: String,
+    pub format: String,
+    /// Requests the seat was allowed to bid on (per wseat/bseat)
+    pub requests_seen: u64,
+    pub responses: u64,
+    pub bid_rate: f64,
+    pub bids: u64,
+    pub avg_bid_price: f64,
+    pub min_bid_price: f64,
+    pub max_bid_price: f64,
+    pub wins: u64,
+    pub win_rate: f64,
+}
+
+impl SeatSummary {
+    fn new(seat: &str, format: String, requests_seen: u64, stats: &SeatStats) -> Self {
+        let ratio = |n: u64, d: u64| if d == 0 { 0.0 } else { n as f64 / d as f64 };
+        Self {
+            seat: seat.to_string(),
+            format,
+            requests_seen,
+            responses: stats.responses,
+            bid_rate: ratio(stats.responses, requests_seen),
+            bids: stats.bids,
+            avg_bid_price: if stats.bids == 0 {
+                0.0
+            } else {
+                stats.sum_price / stats.bids as f64
+            },
+            min_bid_price: stats.min_price,
+            max_bid_price: stats.max_price,
+            wins: stats.wins,
+            win_rate: ratio(stats.wins, stats.responses),
+        }
+    }
+}
+
+/// Write seat_stats.csv (per buyer seat, overall and per format)
+pub fn write_seat_csv(out_dir: &str, rows: &[SeatSummary], meta: &ScanMetadata) -> Result<()> {
+    let path = format!("{}/seat_stats.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    writeln!(
+        csv,
+        "seat,format,requests_seen,responses,bid_rate,bids,avg_bid_price,min_bid_price,max_bid_price,wins,win_rate"
+    )?;
+    for r in rows {
+        writeln!(
+            csv,
+            "{},{},{},{},{:.4},{},{:.4},{:.4},{:.4},{},{:.4}",
+            r.seat,
+            r.format,
+            r.requests_seen,
+            r.responses,
+            r.bid_rate,
+            r.bids,
+            r.avg_bid_price,
+            r.min_bid_price,
+            r.max_bid_price,
+            r.wins,
+            r.win_rate
+        )?;
+    }
+    eprintln!("Seat stats written to: {}", path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_seat_reach_and_wins() {
+        let mut seats = SeatActivity::default();
+        let mut formats: BTreeMap<(u32, u32), FormatStats> = BTreeMap::new();
+        let cases = [
+            (
+                json!({}),
+                (300, 250),
+                json!([
+                    { "seat": "a", "bid": [{ "price": 2.0 }] },
+                    { "seat": "b", "bid": [{ "price": 1.0 }] }
+                ]),
+            ),
+            (
+                json!({ "bseat": ["a"] }),
+                (300, 250),
+                json!([{ "seat": "b", "bid": [{ "price": 0.5 }, { "price": 0.75 }] }]),
+            ),
+            (json!({ "wseat": ["b"] }), (300, 250), json!([])),
+            (json!({}), (728, 90), json!([])),
+        ];
+        for (request, format, seatbid) in cases {
+            formats.entry(format).or_default().requests += 1;
+            let bids = ResponseBids::from_response(&json!({ "seatbid": seatbid }));
+            seats.observe(&request, format, &bids);
+        }
+
+        let rows = seats.summarize(&formats);
+        let row = |seat: &str, format: &str| {
+            rows.iter()
+                .find(|r| r.seat == seat && r.format == format)
+                .unwrap()
+        };
+        assert_eq!(rows[0].seat, "b");
+        let b = row("b", "all");
+        assert_eq!((b.requests_seen, b.responses, b.bids, b.wins), (4, 2, 3, 1));
+        assert_eq!((b.min_bid_price, b.max_bid_price), (0.5, 1.0));
+        let a = row("a", "300x250");
+        assert_eq!((a.requests_seen, a.responses, a.wins), (1, 1, 1));
+        assert_eq!(row("a", "all").requests_seen, 2);
+
+        let mut doubled = seats.clone();
+        doubled.merge(&seats);
+        seats.scale(2.0);
+        assert_eq!(doubled.by_seat_format, seats.by_seat_format);
+        assert_eq!(doubled.blocked, seats.blocked);
+    }
+}
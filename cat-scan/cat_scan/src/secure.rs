@@ -0,0 +1,32 @@
+use serde_json::Value;
+
+/// imp.secure buckets reported per SSP, in display order
+pub const SECURE_BUCKETS: &[&str] = &["secure", "non_secure", "unknown"];
+
+/// Whether a request's first impression requires HTTPS creative assets:
+/// "secure" for `imp.secure` = 1, "non_secure" for 0 and "unknown" when
+/// the field is missing (non-secure by default in OpenRTB 2.x)
+pub fn secure_bucket(request: &Value) -> &'static str {
+    let secure = &request["imp"][0]["secure"];
+    match secure.as_u64().or_else(|| secure.as_bool().map(u64::from)) {
+        Some(1) => "secure",
+        Some(0) => "non_secure",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_secure_bucket() {
+        let imp = |imp: Value| json!({ "imp": [imp] });
+        assert_eq!(secure_bucket(&imp(json!({ "secure": 1 }))), "secure");
+        assert_eq!(secure_bucket(&imp(json!({ "secure": true }))), "secure");
+        assert_eq!(secure_bucket(&imp(json!({ "secure": 0 }))), "non_secure");
+        assert_eq!(secure_bucket(&imp(json!({ "banner": {} }))), "unknown");
+        assert_eq!(secure_bucket(&json!({ "imp": [] })), "unknown");
+    }
+}
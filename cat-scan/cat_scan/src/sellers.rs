@@ -0,0 +1,439 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap, HashSet},
+    io::{BufReader, Read, Write},
+    sync::Arc,
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+use crate::metadata::{create_csv, ScanMetadata};
+use crate::qps::ALL_SSPS;
+use crate::stats::scale_count;
+
+/// Stop tracking new unauthorized seller ids past this many (memory guard)
+const MAX_TRACKED_SELLERS: usize = 100_000;
+
+/// Rows kept in the unauthorized seller list
+const TOP_SELLERS: usize = 20;
+
+/// How long fetching one sellers.json may take
+const FETCH_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// One ad system's sellers.json: the seller ids it vouches for
+#[derive(Debug)]
+pub struct SellersJson {
+    /// What requests call the system: the SSP as in source.ssp and/or its
+    /// domain as in schain node `asi`, lower-cased
+    pub names: Vec<String>,
+    seller_ids: HashSet<String>,
+}
+
+impl SellersJson {
+    /// `[NAME=]PATH|URL` (--sellers-json). A URL is also named after its
+    /// host, so `alpha=https://ssp.example/sellers.json` checks publisher
+    /// ids of source.ssp "alpha" and schain nodes of "ssp.example".
+    pub fn load(spec: &str) -> Result<Self> {
+        let (name, location) = match spec.split_once('=') {
+            Some((name, location)) if !name.contains('/') => (Some(name), location),
+            _ => (None, spec),
+        };
+        let is_url = location.starts_with("http://") || location.starts_with("https://");
+        let mut names: Vec<String> = name.map(system_name).into_iter().collect();
+        if is_url {
+            let host = location
+                .split("://")
+                .nth(1)
+                .and_then(|rest| rest.split(['/', ':', '?']).next())
+                .unwrap_or("");
+            names.push(system_name(host));
+        }
+        names.retain(|n| !n.is_empty());
+        names.dedup();
+        if names.is_empty() {
+            bail!("--sellers-json {spec}: name the SSP it belongs to, e.g. --sellers-json my_ssp={spec}");
+        }
+        let reader: Box<dyn Read> = if is_url {
+            let response = ureq::AgentBuilder::new()
+                .timeout(FETCH_TIMEOUT)
+                .build()
+                .get(location)
+                .call()
+                .with_context(|| format!("Failed to fetch {location}"))?;
+            Box::new(response.into_reader())
+        } else {
+            Box::new(
+                std::fs::File::open(location)
+                    .with_context(|| format!("Failed to open {location}"))?,
+            )
+        };
+        let file: Value = serde_json::from_reader(BufReader::new(reader))
+            .with_context(|| format!("{location} is not valid JSON"))?;
+        Self::from_value(names, &file).with_context(|| location.to_string())
+    }
+
+    /// The sellers of a parsed sellers.json
+    pub fn from_value(names: Vec<String>, file: &Value) -> Result<Self> {
+        let Some(sellers) = file["sellers"].as_array() else {
+            bail!("not a sellers.json: no \"sellers\" array");
+        };
+        let seller_ids = sellers
+            .iter()
+            .filter_map(|seller| id_string(&seller["seller_id"]))
+            .collect();
+        Ok(Self { names, seller_ids })
+    }
+
+    pub fn len(&self) -> usize {
+        self.seller_ids.len()
+    }
+
+    fn is_named(&self, name: &str) -> bool {
+        self.names.iter().any(|n| *n == system_name(name))
+    }
+}
+
+fn system_name(name: &str) -> String {
+    let name = name.trim().to_ascii_lowercase();
+    name.strip_prefix("www.").unwrap_or(&name).to_string()
+}
+
+/// Seller and publisher ids are strings, but some SSPs send numbers
+fn id_string(id: &Value) -> Option<String> {
+    match id {
+        Value::String(s) if !s.trim().is_empty() => Some(s.trim().to_string()),
+        Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+/// Per-SSP counts against the sellers.json files
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SellerCounts {
+    pub requests: u64,
+    /// Requests with a publisher id or schain node a loaded sellers.json
+    /// covers
+    pub checked: u64,
+    /// Checked requests with an id the sellers.json doesn't list
+    pub unauthorized: u64,
+    pub unauthorized_bids: u64,
+}
+
+impl SellerCounts {
+    fn merge(&mut self, other: &SellerCounts) {
+        self.requests += other.requests;
+        self.checked += other.checked;
+        self.unauthorized += other.unauthorized;
+        self.unauthorized_bids += other.unauthorized_bids;
+    }
+}
+
+/// Supply authorization (--sellers-json): whether the publisher ids and
+/// schain seller ids in requests are listed in the sellers.json of the
+/// system they claim to sell through
+#[derive(Debug, Default, Clone)]
+pub struct SellerAudit {
+    directories: Arc<Vec<SellersJson>>,
+    pub by_ssp: BTreeMap<String, SellerCounts>,
+    /// Unauthorized requests per (SSP, "publisher" | "schain", system,
+    /// seller id)
+    pub unauthorized: HashMap<(String, &'static str, String, String), u64>,
+}
+
+impl SellerAudit {
+    pub fn new(directories: Vec<SellersJson>) -> Self {
+        Self {
+            directories: Arc::new(directories),
+            ..Self::default()
+        }
+    }
+
+    pub fn observe(&mut self, request: &Value, ssp: &str, has_bid: bool) {
+        let mut checked = false;
+        let mut unlisted: Vec<(&'static str, String, String)> = Vec::new();
+        let mut check = |kind, system: &str, id: String| {
+            let Some(directory) = self.directories.iter().find(|d| d.is_named(system)) else {
+                return;
+            };
+            checked = true;
+            if !directory.seller_ids.contains(&id) {
+                unlisted.push((kind, system_name(system), id));
+            }
+        };
+
+        let publisher = [&request["site"], &request["app"]]
+            .into_iter()
+            .find_map(|inventory| id_string(&inventory["publisher"]["id"]));
+        if let Some(id) = publisher {
+            check("publisher", ssp, id);
+        }
+        let schain = match &request["source"]["schain"] {
+            Value::Null => &request["source"]["ext"]["schain"],
+            schain => schain,
+        };
+        for node in schain["nodes"].as_array().into_iter().flatten() {
+            if let (Some(asi), Some(sid)) = (node["asi"].as_str(), id_string(&node["sid"])) {
+                check("schain", asi, sid);
+            }
+        }
+
+        let counts = self.by_ssp.entry(ssp.to_string()).or_default();
+        counts.requests += 1;
+        counts.checked += u64::from(checked);
+        if unlisted.is_empty() {
+            return;
+        }
+        counts.unauthorized += 1;
+        counts.unauthorized_bids += u64::from(has_bid);
+        for (kind, system, id) in unlisted {
+            let key = (ssp.to_string(), kind, system, id);
+            if let Some(count) = self.unauthorized.get_mut(&key) {
+                *count += 1;
+            } else if self.unauthorized.len() < MAX_TRACKED_SELLERS {
+                self.unauthorized.insert(key, 1);
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &SellerAudit) {
+        if self.directories.is_empty() {
+            self.directories = other.directories.clone();
+        }
+        for (ssp, counts) in &other.by_ssp {
+            self.by_ssp.entry(ssp.clone()).or_default().merge(counts);
+        }
+        for (key, count) in &other.unauthorized {
+            *self.unauthorized.entry(key.clone()).or_default() += count;
+        }
+    }
+
+    /// Multiply counts by `factor` (extrapolating from a sample)
+    pub fn scale(&mut self, factor: f64) {
+        let scale = |count: &mut u64| scale_count(count, factor);
+        for counts in self.by_ssp.values_mut() {
+            scale(&mut counts.requests);
+            scale(&mut counts.checked);
+            scale(&mut counts.unauthorized);
+            scale(&mut counts.unauthorized_bids);
+        }
+        self.unauthorized.values_mut().for_each(scale);
+    }
+
+    /// An ALL_SSPS row then SSPs by unauthorized volume, and the busiest
+    /// unauthorized sellers. QPS needs the scan window (timestamps).
+    pub fn summarize(&self, window_secs: Option<u64>) -> SellerAuditReport {
+        let mut total = SellerCounts::default();
+        for counts in self.by_ssp.values() {
+            total.merge(counts);
+        }
+        let share = |n: u64, d: u64| if d == 0 { 0.0 } else { n as f64 / d as f64 };
+        let summary = |ssp: &str, c: &SellerCounts| SellerSummary {
+            ssp: ssp.to_string(),
+            requests: c.requests,
+            checked: c.checked,
+            unauthorized: c.unauthorized,
+            unauthorized_share: share(c.unauthorized, c.requests),
+            unauthorized_bids: c.unauthorized_bids,
+            unauthorized_qps: window_secs.map(|secs| c.unauthorized as f64 / secs.max(1) as f64),
+        };
+        let mut ssps: Vec<SellerSummary> = self
+            .by_ssp
+            .iter()
+            .map(|(ssp, counts)| summary(ssp, counts))
+            .collect();
+        ssps.sort_by(|a, b| {
+            (Reverse(a.unauthorized), Reverse(a.requests), &a.ssp).cmp(&(
+                Reverse(b.unauthorized),
+                Reverse(b.requests),
+                &b.ssp,
+            ))
+        });
+        ssps.insert(0, summary(ALL_SSPS, &total));
+
+        let mut sellers: Vec<UnauthorizedSeller> = self
+            .unauthorized
+            .iter()
+            .map(|(key, &requests)| {
+                let (ssp, kind, system, seller_id) = key.clone();
+                UnauthorizedSeller {
+                    ssp,
+                    kind,
+                    system,
+                    seller_id,
+                    requests,
+                }
+            })
+            .collect();
+        sellers.sort_by(|a, b| {
+            (Reverse(a.requests), &a.ssp, &a.system, &a.seller_id).cmp(&(
+                Reverse(b.requests),
+                &b.ssp,
+                &b.system,
+                &b.seller_id,
+            ))
+        });
+        sellers.truncate(TOP_SELLERS);
+
+        SellerAuditReport {
+            sellers_json: self
+                .directories
+                .iter()
+                .map(|d| SellersJsonSummary {
+                    names: d.names.clone(),
+                    sellers: d.len(),
+                })
+                .collect(),
+            ssps,
+            sellers,
+        }
+    }
+}
+
+/// Supply authorization of one SSP
+#[derive(serde::Serialize)]
+pub struct SellerSummary {
+    pub ssp: String,
+    pub requests: u64,
+    pub checked: u64,
+    pub unauthorized: u64,
+    /// unauthorized / requests
+    pub unauthorized_share: f64,
+    /// Bids we placed on unauthorized supply anyway
+    pub unauthorized_bids: u64,
+    /// Unauthorized requests per second over the scan window
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unauthorized_qps: Option<f64>,
+}
+
+/// A seller id a sellers.json doesn't list, as sent by one SSP
+#[derive(serde::Serialize)]
+pub struct UnauthorizedSeller {
+    pub ssp: String,
+    /// "publisher" (site/app.publisher.id) or "schain" (a node's sid)
+    pub kind: &'static str,
+    /// Whose sellers.json it is missing from: the SSP, or the node's asi
+    pub system: String,
+    pub seller_id: String,
+    pub requests: u64,
+}
+
+#[derive(serde::Serialize)]
+pub struct SellersJsonSummary {
+    pub names: Vec<String>,
+    pub sellers: usize,
+}
+
+/// "Unauthorized supply" section of the report
+#[derive(serde::Serialize)]
+pub struct SellerAuditReport {
+    pub sellers_json: Vec<SellersJsonSummary>,
+    pub ssps: Vec<SellerSummary>,
+    pub sellers: Vec<UnauthorizedSeller>,
+}
+
+/// Write unauthorized_supply.csv (per SSP, then the busiest unauthorized
+/// seller ids)
+pub fn write_seller_audit_csv(
+    out_dir: &str,
+    report: &SellerAuditReport,
+    meta: &ScanMetadata,
+) -> Result<()> {
+    let path = format!("{}/unauthorized_supply.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+
+    writeln!(csv, "# SSPs")?;
+    writeln!(
+        csv,
+        "ssp,requests,checked,unauthorized,unauthorized_share,unauthorized_bids,unauthorized_qps"
+    )?;
+    for s in &report.ssps {
+        writeln!(
+            csv,
+            "{},{},{},{},{:.4},{},{}",
+            s.ssp,
+            s.requests,
+            s.checked,
+            s.unauthorized,
+            s.unauthorized_share,
+            s.unauthorized_bids,
+            s.unauthorized_qps
+                .map_or(String::new(), |qps| format!("{:.2}", qps))
+        )?;
+    }
+
+    writeln!(csv, "\n# Unauthorized sellers")?;
+    writeln!(csv, "ssp,kind,system,seller_id,requests")?;
+    for s in &report.sellers {
+        writeln!(
+            csv,
+            "{},{},{},{},{}",
+            s.ssp, s.kind, s.system, s.seller_id, s.requests
+        )?;
+    }
+
+    eprintln!("Unauthorized supply written to: {}", path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_seller_audit() {
+        let sellers = |names: &[&str], ids: Value| {
+            let names = names.iter().map(|n| n.to_string()).collect();
+            SellersJson::from_value(names, &json!({ "sellers": ids })).unwrap()
+        };
+        let mut audit = SellerAudit::new(vec![
+            sellers(
+                &["alpha", "alpha-ssp.com"],
+                json!([{ "seller_id": "pub-a" }, { "seller_id": 7 }]),
+            ),
+            sellers(&["exchange.com"], json!([{ "seller_id": "x1" }])),
+        ]);
+        let request = |publisher: Value, nodes: Value| {
+            json!({
+                "site": { "publisher": { "id": publisher } },
+                "source": { "schain": { "nodes": nodes } }
+            })
+        };
+        // Listed publisher, listed numeric publisher, unlisted publisher
+        audit.observe(&request(json!("pub-a"), json!([])), "alpha", true);
+        audit.observe(&request(json!(7), json!([])), "alpha", false);
+        audit.observe(&request(json!("pub-z"), json!([])), "alpha", true);
+        // beta has no sellers.json, but its schain passes through exchange.com
+        let nodes =
+            json!([{ "asi": "www.Exchange.com", "sid": "x9" }, { "asi": "other.com", "sid": "1" }]);
+        audit.observe(&request(json!("p"), nodes), "beta", false);
+        audit.observe(&request(json!("p"), json!([])), "beta", false);
+
+        let report = audit.summarize(Some(10));
+        let rows: Vec<(&str, u64, u64, u64)> = report
+            .ssps
+            .iter()
+            .map(|s| (s.ssp.as_str(), s.requests, s.checked, s.unauthorized))
+            .collect();
+        assert_eq!(
+            rows,
+            [(ALL_SSPS, 5, 4, 2), ("alpha", 3, 3, 1), ("beta", 2, 1, 1)]
+        );
+        assert_eq!(report.ssps[1].unauthorized_bids, 1);
+        assert_eq!(report.ssps[0].unauthorized_qps, Some(0.2));
+        let sellers: Vec<(&str, &str, &str)> = report
+            .sellers
+            .iter()
+            .map(|s| (s.kind, s.system.as_str(), s.seller_id.as_str()))
+            .collect();
+        assert_eq!(
+            sellers,
+            [
+                ("publisher", "alpha", "pub-z"),
+                ("schain", "exchange.com", "x9")
+            ]
+        );
+    }
+}
@@ -0,0 +1,198 @@
+use std::{
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::UNIX_EPOCH,
+};
+
+use anyhow::{Context, Result};
+use axum::{
+    extract::{Path as UrlPath, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+
+use crate::cli::ServeConfig;
+
+/// How often a served report checks for a newer scan
+const RELOAD_POLL_SECS: u64 = 5;
+
+#[derive(Clone)]
+struct ServeState {
+    out_dir: Arc<PathBuf>,
+}
+
+/// Files in the output directory are re-read on every request, so a scan
+/// that writes into the same --out directory shows up without a restart.
+fn read_report_file(dir: &Path, name: &str) -> Option<Vec<u8>> {
+    std::fs::read(dir.join(name)).ok()
+}
+
+fn not_found(dir: &Path, name: &str) -> Response {
+    (
+        StatusCode::NOT_FOUND,
+        format!(
+            "No {} in {} yet. Run `cat_scan <input> --out {}` first.",
+            name,
+            dir.display(),
+            dir.display()
+        ),
+    )
+        .into_response()
+}
+
+/// Modification time of report.json in ms, used as the report version
+fn report_version(dir: &Path) -> u64 {
+    std::fs::metadata(dir.join("report.json"))
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+/// Script injected into the served report: reload when a new scan lands
+fn reload_script(version: u64) -> String {
+    format!(
+        r#"<script>
+        (function() {{
+            const loaded = {version};
+            setInterval(async () => {{
+                try {{
+                    const res = await fetch('api/version');
+                    const v = await res.json();
+                    if (v.version !== loaded) location.reload();
+                }} catch (e) {{}}
+            }}, {interval});
+        }})();
+    </script>
+</body>"#,
+        version = version,
+        interval = RELOAD_POLL_SECS * 1000
+    )
+}
+
+async fn report_page(State(state): State<ServeState>) -> Response {
+    match read_report_file(&state.out_dir, "report.html") {
+        Some(bytes) => {
+            let html = String::from_utf8_lossy(&bytes);
+            let version = report_version(&state.out_dir);
+            Html(html.replacen("</body>", &reload_script(version), 1)).into_response()
+        }
+        None => not_found(&state.out_dir, "report.html"),
+    }
+}
+
+async fn report_api(State(state): State<ServeState>) -> Response {
+    match read_report_file(&state.out_dir, "report.json") {
+        Some(bytes) => ([(header::CONTENT_TYPE, "application/json")], bytes).into_response(),
+        None => not_found(&state.out_dir, "report.json"),
+    }
+}
+
+async fn version_api(State(state): State<ServeState>) -> Json<serde_json::Value> {
+    Json(serde_json::json!({ "version": report_version(&state.out_dir) }))
+}
+
+/// Other outputs of the scan (CSV/JSON), by file name only
+async fn output_file(State(state): State<ServeState>, UrlPath(name): UrlPath<String>) -> Response {
+    if name.contains('/') || name.contains('\\') || name.starts_with('.') {
+        return (StatusCode::BAD_REQUEST, "invalid file name").into_response();
+    }
+    let content_type = match name.rsplit('.').next() {
+        Some("csv") => "text/csv",
+        Some("json") => "application/json",
+        Some("html") => "text/html",
+        _ => "application/octet-stream",
+    };
+    match read_report_file(&state.out_dir, &name) {
+        Some(bytes) => ([(header::CONTENT_TYPE, content_type)], bytes).into_response(),
+        None => not_found(&state.out_dir, &name),
+    }
+}
+
+pub fn serve_router(out_dir: PathBuf) -> Router {
+    Router::new()
+        .route("/", get(report_page))
+        .route("/api/report", get(report_api))
+        // Fetched relative to the page by --bundle reports
+        .route("/report.json", get(report_api))
+        .route("/api/version", get(version_api))
+        .route("/files/:name", get(output_file))
+        .with_state(ServeState {
+            out_dir: Arc::new(out_dir),
+        })
+}
+
+/// `cat_scan serve`: serve the report in an --out directory over HTTP
+pub async fn run_serve(config: ServeConfig) -> Result<()> {
+    let app = serve_router(PathBuf::from(&config.out_dir));
+    let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
+    let listener = tokio::net::TcpListener::bind(addr)
+        .await
+        .with_context(|| format!("Failed to bind report server to {}", addr))?;
+    eprintln!("Serving {} at http://{}", config.out_dir, addr);
+    axum::serve(listener, app)
+        .await
+        .context("Report server failed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::future::IntoFuture;
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    async fn get(addr: SocketAddr, path: &str) -> String {
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            path
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        response
+    }
+
+    #[tokio::test]
+    async fn test_serves_report_and_api() {
+        let dir = std::env::temp_dir().join(format!("cat_scan_serve_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("report.html"), "<html><body>report</body></html>").unwrap();
+        std::fs::write(dir.join("report.json"), r#"{"total_requests":7}"#).unwrap();
+        std::fs::write(dir.join("format_stats.csv"), "w,h\n").unwrap();
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(axum::serve(listener, serve_router(dir.clone())).into_future());
+
+        let page = get(addr, "/").await;
+        assert!(page.starts_with("HTTP/1.1 200"));
+        assert!(page.contains("report"));
+        assert!(page.contains("api/version"));
+
+        let api = get(addr, "/api/report").await;
+        assert!(api.contains("application/json"));
+        assert!(api.ends_with(r#"{"total_requests":7}"#));
+        assert!(get(addr, "/report.json")
+            .await
+            .ends_with(r#"{"total_requests":7}"#));
+
+        assert!(get(addr, "/files/format_stats.csv")
+            .await
+            .contains("text/csv"));
+        assert!(get(addr, "/files/missing.csv")
+            .await
+            .starts_with("HTTP/1.1 404"));
+        assert!(get(addr, "/files/..%2Freport.json")
+            .await
+            .starts_with("HTTP/1.1 400"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
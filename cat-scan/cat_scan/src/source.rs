@@ -0,0 +1,633 @@
+use std::{
+    fs::File,
+    io::{self, Read},
+    sync::Arc,
+};
+
+use anyhow::{bail, Context, Result};
+use bytes::Bytes;
+use futures::StreamExt;
+use object_store::{path::Path as ObjectPath, ObjectMeta, ObjectStore};
+use tokio::sync::mpsc;
+
+use crate::cli::parse_duration_secs;
+use crate::input::parse_s3_uri;
+use crate::kafka::{describe_bounds, KafkaReader, KafkaSource};
+
+/// Chunks buffered between the download task and the scanner
+const STREAM_BUFFER_CHUNKS: usize = 16;
+
+/// Days from 1970-01-01 to y-m-d (proleptic Gregorian)
+fn days_from_civil(y: i64, m: u32, d: u32) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (m as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Unix seconds at midnight UTC of a `YYYY-MM-DD` or `YYYYMMDD` date
+fn parse_date(value: &str) -> Option<i64> {
+    let (y, m, d) = match value.len() {
+        10 if value.as_bytes()[4] == b'-' && value.as_bytes()[7] == b'-' => {
+            (&value[0..4], &value[5..7], &value[8..10])
+        }
+        8 => (&value[0..4], &value[4..6], &value[6..8]),
+        _ => return None,
+    };
+    if ![y, m, d]
+        .iter()
+        .all(|p| p.bytes().all(|b| b.is_ascii_digit()))
+    {
+        return None;
+    }
+    let (y, m, d): (i64, u32, u32) = (y.parse().ok()?, m.parse().ok()?, d.parse().ok()?);
+    if !(1..=12).contains(&m) || !(1..=31).contains(&d) {
+        return None;
+    }
+    Some(days_from_civil(y, m, d) * 86400)
+}
+
+/// `hh:mm[:ss[.fff]]` as seconds since midnight
+fn parse_clock(value: &str) -> Option<i64> {
+    let value = value.split('.').next()?;
+    let mut parts = value.split(':');
+    let h: i64 = parts.next()?.parse().ok()?;
+    let m: i64 = parts.next()?.parse().ok()?;
+    let s: i64 = parts.next().map_or(Some(0), |s| s.parse().ok())?;
+    if parts.next().is_some() || h > 23 || m > 59 || s > 60 {
+        return None;
+    }
+    Some(h * 3600 + m * 60 + s)
+}
+
+/// RFC3339 timestamp (or a bare date, taken as midnight UTC) in unix seconds
+pub fn parse_rfc3339(value: &str) -> Option<u64> {
+    let (date, time) = match value.find(['T', 't', ' ']) {
+        Some(i) => (&value[..i], Some(&value[i + 1..])),
+        None => (value, None),
+    };
+    let mut secs = parse_date(date)?;
+    if let Some(time) = time {
+        let (clock, offset) = if let Some(clock) = time.strip_suffix(['Z', 'z']) {
+            (clock, 0)
+        } else if let Some(i) = time.rfind(['+', '-']) {
+            let sign = if time.as_bytes()[i] == b'-' { -1 } else { 1 };
+            (&time[..i], sign * parse_clock(&time[i + 1..])?)
+        } else {
+            (time, 0)
+        };
+        secs += parse_clock(clock)? - offset;
+    }
+    u64::try_from(secs).ok()
+}
+
+/// Unix seconds as `YYYY-MM-DDThh:mm:ssZ`
+pub fn format_rfc3339(secs: u64) -> String {
+    let days = (secs / 86400) as i64;
+    let clock = secs % 86400;
+    // Inverse of days_from_civil
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    let y = yoe + era * 400 + i64::from(m <= 2);
+    format!(
+        "{y:04}-{m:02}-{d:02}T{:02}:{:02}:{:02}Z",
+        clock / 3600,
+        clock / 60 % 60,
+        clock % 60
+    )
+}
+
+/// Parse a --since/--until value: RFC3339 (`2024-06-01T00:00:00Z`), a bare
+/// date (`2024-06-01`, `20240601`), or a duration before `now_secs` (`24h`,
+/// `7d`). Dates are tried first, so `20240601` is never taken as seconds.
+pub fn parse_time_bound(value: &str, now_secs: u64) -> Result<u64> {
+    if let Some(secs) = parse_rfc3339(value.trim()) {
+        return Ok(secs);
+    }
+    let ago = parse_duration_secs(value).with_context(|| {
+        format!(
+            "invalid time '{value}', expected RFC3339 (2024-06-01T00:00:00Z), a date or e.g. 24h"
+        )
+    })?;
+    Ok(now_secs.saturating_sub(ago))
+}
+
+/// Time span covered by a date-partitioned key such as `logs/dt=2024-06-01/`,
+/// `year=2024/month=06/day=01/hour=13/` or `2024/06/01/part-0.jsonl`, as
+/// unix seconds [start, end)
+pub fn key_time_range(key: &str) -> Option<(u64, u64)> {
+    let segments: Vec<&str> = key.split('/').collect();
+    let value = |seg: &str, names: &[&str]| {
+        names.iter().find_map(|name| {
+            seg.strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix('='))
+                .map(str::to_string)
+        })
+    };
+    let hour_at = |i: usize| -> Option<i64> {
+        let seg = segments.get(i)?;
+        let hour = value(seg, &["hour", "hr", "h"]).or_else(|| {
+            // A bare HH directory, but not the file name itself
+            (i + 1 < segments.len() && seg.len() == 2).then(|| seg.to_string())
+        })?;
+        hour.parse::<i64>().ok().filter(|h| (0..24).contains(h))
+    };
+
+    for (i, seg) in segments.iter().enumerate() {
+        let day = if let Some(date) = value(seg, &["dt", "date"]) {
+            parse_date(&date).map(|start| (start, i + 1))
+        } else if let Some(year) = value(seg, &["year"]) {
+            let month = segments.get(i + 1).and_then(|s| value(s, &["month"]))?;
+            let day = segments.get(i + 2).and_then(|s| value(s, &["day"]))?;
+            parse_date(&format!("{year}-{month:0>2}-{day:0>2}")).map(|start| (start, i + 3))
+        } else if seg.len() == 4 && seg.bytes().all(|b| b.is_ascii_digit()) {
+            match (segments.get(i + 1), segments.get(i + 2)) {
+                (Some(m), Some(d)) if m.len() == 2 && d.len() == 2 => {
+                    parse_date(&format!("{seg}-{m}-{d}")).map(|start| (start, i + 3))
+                }
+                _ => None,
+            }
+        } else {
+            None
+        };
+        if let Some((start, next)) = day {
+            let (start, len) = match hour_at(next) {
+                Some(hour) => (start + hour * 3600, 3600),
+                None => (start, 86400),
+            };
+            let start = u64::try_from(start).ok()?;
+            return Some((start, start + len));
+        }
+    }
+    None
+}
+
+/// --since/--until: which objects under a prefix get scanned
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct DateRange {
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+}
+
+impl DateRange {
+    pub fn is_set(&self) -> bool {
+        self.since.is_some() || self.until.is_some()
+    }
+
+    /// Objects in date-partitioned keys are kept when their partition
+    /// overlaps the range; others by LastModified
+    pub fn includes(&self, key: &str, last_modified_secs: u64) -> bool {
+        let since = self.since.unwrap_or(0);
+        let until = self.until.unwrap_or(u64::MAX);
+        match key_time_range(key) {
+            Some((start, end)) => start < until && end > since,
+            None => last_modified_secs >= since && last_modified_secs < until,
+        }
+    }
+}
+
+/// Object stores an input path can point at. GCS and Azure are each behind
+/// their own cargo feature; S3 (and S3-compatible stores like MinIO) is
+/// always available.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ObjectScheme {
+    /// s3://bucket/key
+    S3,
+    /// gs://bucket/key (feature `gcs`)
+    Gcs,
+    /// az://container/blob (feature `azure`)
+    Azure,
+}
+
+impl ObjectScheme {
+    fn prefix(self) -> &'static str {
+        match self {
+            ObjectScheme::S3 => "s3",
+            ObjectScheme::Gcs => "gs",
+            ObjectScheme::Azure => "az",
+        }
+    }
+}
+
+/// An object in S3, GCS or Azure Blob Storage given as an input path
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectUri {
+    pub scheme: ObjectScheme,
+    pub bucket: String,
+    pub key: String,
+}
+
+impl std::fmt::Display for ObjectUri {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}://{}/{}", self.scheme.prefix(), self.bucket, self.key)
+    }
+}
+
+/// Parse s3://, gs:// or az:// URIs; None for anything else (local paths)
+pub fn parse_object_uri(uri: &str) -> Option<ObjectUri> {
+    let (scheme, (bucket, key)) = if let Some(parts) = parse_s3_uri(uri) {
+        (ObjectScheme::S3, parts)
+    } else if let Some(s) = uri.strip_prefix("gs://") {
+        let (bucket, key) = s.split_once('/')?;
+        (ObjectScheme::Gcs, (bucket.to_string(), key.to_string()))
+    } else if let Some(s) = uri.strip_prefix("az://") {
+        let (bucket, key) = s.split_once('/')?;
+        (ObjectScheme::Azure, (bucket.to_string(), key.to_string()))
+    } else {
+        return None;
+    };
+    if bucket.is_empty() {
+        return None;
+    }
+    Some(ObjectUri {
+        scheme,
+        bucket,
+        key,
+    })
+}
+
+/// An input opened for scanning, whatever it is backed by
+pub struct InputStream {
+    /// Total bytes when known up front (not for Kafka)
+    pub size: Option<u64>,
+    pub reader: Box<dyn Read + Send>,
+}
+
+/// Open a local file, an object store URI or a kafka:// topic as a stream
+/// of log lines. Objects are streamed, not downloaded first; a URI ending in
+/// `/` is a prefix, and every object under it (within `range`) is scanned
+/// in key order. Reading from the returned reader blocks, so scan it inside
+/// `block_in_place`.
+pub async fn open_input(path: &str, quiet: bool, range: DateRange) -> Result<InputStream> {
+    let is_prefix = path.ends_with('/');
+    if range.is_set() && !(is_prefix && parse_object_uri(path).is_some()) {
+        bail!("--since/--until select objects under a prefix; use an input like s3://bucket/logs/");
+    }
+
+    if let Some(source) = KafkaSource::parse(path) {
+        let source = source?;
+        if !quiet {
+            eprintln!(
+                "Consuming {} from {} as group {} (until {})",
+                source.topic,
+                source.brokers,
+                source.group,
+                describe_bounds(&source)
+            );
+        }
+        return Ok(InputStream {
+            size: None,
+            reader: Box::new(KafkaReader::new(&source)?),
+        });
+    }
+
+    if let Some(uri) = parse_object_uri(path) {
+        return open_objects(&uri, is_prefix, range, quiet).await;
+    }
+
+    let file = File::open(path).with_context(|| format!("Failed to open log file: {}", path))?;
+    let size = file.metadata().map(|m| m.len()).ok();
+    Ok(InputStream {
+        size,
+        reader: Box::new(file),
+    })
+}
+
+/// List the objects under a prefix that fall inside `range`, in key order
+async fn list_objects(
+    store: &dyn ObjectStore,
+    uri: &ObjectUri,
+    range: DateRange,
+) -> Result<Vec<ObjectMeta>> {
+    let prefix = ObjectPath::from(uri.key.as_str());
+    let mut listing = store.list(Some(&prefix));
+    let mut objects = Vec::new();
+    while let Some(meta) = listing.next().await {
+        let meta = meta.with_context(|| format!("Failed to list {uri}"))?;
+        let modified = meta.last_modified.timestamp().max(0) as u64;
+        if range.includes(meta.location.as_ref(), modified) {
+            objects.push(meta);
+        }
+    }
+    objects.sort_by(|a, b| a.location.cmp(&b.location));
+    Ok(objects)
+}
+
+async fn open_objects(
+    uri: &ObjectUri,
+    is_prefix: bool,
+    range: DateRange,
+    quiet: bool,
+) -> Result<InputStream> {
+    if is_prefix {
+        let listing = list_prefix(uri, range).await?;
+        if listing.objects.is_empty() {
+            bail!("No objects under {uri} in the selected date range");
+        }
+        return Ok(listing.open(quiet));
+    }
+    let store = object_store_for(uri).await?;
+    let meta = store
+        .head(&ObjectPath::from(uri.key.as_str()))
+        .await
+        .with_context(|| format!("Failed to download {uri}"))?;
+    Ok(stream_objects(store, vec![meta], uri.to_string()))
+}
+
+/// Objects under a prefix, listed ahead of the scan so the caller can pick
+/// which to read (`cat_scan watch` drops the ones it already scanned)
+pub struct ObjectListing {
+    uri: ObjectUri,
+    store: Arc<dyn ObjectStore>,
+    /// In key order
+    pub objects: Vec<ObjectMeta>,
+}
+
+impl ObjectListing {
+    /// Stream the listed objects as one input, like open_input does for
+    /// the whole prefix
+    pub fn open(self, quiet: bool) -> InputStream {
+        if !quiet {
+            eprintln!("Scanning {} objects under {}", self.objects.len(), self.uri);
+        }
+        stream_objects(self.store, self.objects, self.uri.to_string())
+    }
+}
+
+/// List the objects under the prefix `uri` that fall inside `range`
+pub async fn list_prefix(uri: &ObjectUri, range: DateRange) -> Result<ObjectListing> {
+    let store = object_store_for(uri).await?;
+    let objects = list_objects(store.as_ref(), uri, range).await?;
+    Ok(ObjectListing {
+        uri: uri.clone(),
+        store,
+        objects,
+    })
+}
+
+/// Download `objects` one after the other into a single line stream
+fn stream_objects(
+    store: Arc<dyn ObjectStore>,
+    objects: Vec<ObjectMeta>,
+    display: String,
+) -> InputStream {
+    let size = objects.iter().map(|o| o.size).sum();
+
+    let (tx, rx) = mpsc::channel(STREAM_BUFFER_CHUNKS);
+    tokio::spawn(async move {
+        for meta in objects {
+            let mut stream = match store.get(&meta.location).await {
+                Ok(result) => result.into_stream(),
+                Err(e) => {
+                    let e = io::Error::other(format!("Failed to download {display}: {e}"));
+                    let _ = tx.send(Err(e)).await;
+                    return;
+                }
+            };
+            let mut ends_with_newline = true;
+            while let Some(chunk) = stream.next().await {
+                let failed = chunk.is_err();
+                if let Ok(bytes) = &chunk {
+                    if let Some(&last) = bytes.last() {
+                        ends_with_newline = last == b'\n';
+                    }
+                }
+                if tx.send(chunk.map_err(io::Error::other)).await.is_err() || failed {
+                    return;
+                }
+            }
+            // Keep the last line of one object apart from the first of the next
+            if !ends_with_newline && tx.send(Ok(Bytes::from_static(b"\n"))).await.is_err() {
+                return;
+            }
+        }
+    });
+
+    InputStream {
+        size: Some(size),
+        reader: Box::new(ChannelReader {
+            rx,
+            current: Bytes::new(),
+        }),
+    }
+}
+
+/// Blocking `Read` over chunks sent by the download task
+struct ChannelReader {
+    rx: mpsc::Receiver<io::Result<Bytes>>,
+    current: Bytes,
+}
+
+impl Read for ChannelReader {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        while self.current.is_empty() {
+            match self.rx.blocking_recv() {
+                Some(chunk) => self.current = chunk?,
+                None => return Ok(0),
+            }
+        }
+        let n = out.len().min(self.current.len());
+        out[..n].copy_from_slice(&self.current[..n]);
+        self.current = self.current.slice(n..);
+        Ok(n)
+    }
+}
+
+/// Credentials come from the environment for every provider: the usual AWS
+/// chain (env, profile, SSO, ECS/EC2 roles) for S3, with AWS_ENDPOINT_URL
+/// for MinIO and other S3-compatible stores; GOOGLE_APPLICATION_CREDENTIALS
+/// (or GOOGLE_SERVICE_ACCOUNT) for GCS; AZURE_STORAGE_ACCOUNT_NAME plus
+/// AZURE_STORAGE_ACCOUNT_KEY (or a service principal) for Azure.
+async fn object_store_for(uri: &ObjectUri) -> Result<Arc<dyn ObjectStore>> {
+    match uri.scheme {
+        ObjectScheme::S3 => Ok(Arc::new(s3_store(&uri.bucket).await?)),
+        #[cfg(feature = "gcs")]
+        ObjectScheme::Gcs => Ok(Arc::new(
+            object_store::gcp::GoogleCloudStorageBuilder::from_env()
+                .with_bucket_name(&uri.bucket)
+                .build()?,
+        )),
+        #[cfg(feature = "azure")]
+        ObjectScheme::Azure => Ok(Arc::new(
+            object_store::azure::MicrosoftAzureBuilder::from_env()
+                .with_container_name(&uri.bucket)
+                .build()?,
+        )),
+        #[allow(unreachable_patterns)]
+        scheme => bail!(
+            "cat_scan was built without {} support; rebuild with `--features {}`",
+            match scheme {
+                ObjectScheme::Azure => "Azure Blob Storage",
+                _ => "GCS",
+            },
+            match scheme {
+                ObjectScheme::Azure => "azure",
+                _ => "gcs",
+            }
+        ),
+    }
+}
+
+async fn s3_store(bucket: &str) -> Result<object_store::aws::AmazonS3> {
+    let aws_conf = aws_config::defaults(aws_config::BehaviorVersion::latest())
+        .load()
+        .await;
+    let mut builder = object_store::aws::AmazonS3Builder::from_env().with_bucket_name(bucket);
+    if let Some(region) = aws_conf.region() {
+        builder = builder.with_region(region.as_ref());
+    }
+    if let Some(endpoint) = aws_conf.endpoint_url() {
+        builder = builder
+            .with_endpoint(endpoint)
+            .with_allow_http(endpoint.starts_with("http://"));
+    }
+    if let Some(provider) = aws_conf.credentials_provider() {
+        builder = builder.with_credentials(Arc::new(AwsChainCredentials(provider)));
+    }
+    builder.build().context("Failed to configure S3 client")
+}
+
+/// Resolves S3 credentials through the AWS SDK's default chain, which
+/// object_store doesn't implement in full (profiles, SSO)
+#[derive(Debug)]
+struct AwsChainCredentials(aws_credential_types::provider::SharedCredentialsProvider);
+
+#[async_trait::async_trait]
+impl object_store::CredentialProvider for AwsChainCredentials {
+    type Credential = object_store::aws::AwsCredential;
+
+    async fn get_credential(&self) -> object_store::Result<Arc<Self::Credential>> {
+        use aws_credential_types::provider::ProvideCredentials;
+
+        let credentials =
+            self.0
+                .provide_credentials()
+                .await
+                .map_err(|e| object_store::Error::Generic {
+                    store: "S3",
+                    source: Box::new(e),
+                })?;
+        Ok(Arc::new(object_store::aws::AwsCredential {
+            key_id: credentials.access_key_id().to_string(),
+            secret_key: credentials.secret_access_key().to_string(),
+            token: credentials.session_token().map(str::to_string),
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_object_uri() {
+        let uri = parse_object_uri("gs://bid-logs/2024/01/01/logs.jsonl").unwrap();
+        assert_eq!(
+            uri,
+            ObjectUri {
+                scheme: ObjectScheme::Gcs,
+                bucket: "bid-logs".to_string(),
+                key: "2024/01/01/logs.jsonl".to_string(),
+            }
+        );
+        assert_eq!(uri.to_string(), "gs://bid-logs/2024/01/01/logs.jsonl");
+        assert_eq!(
+            parse_object_uri("s3://bucket/logs.jsonl").map(|u| u.scheme),
+            Some(ObjectScheme::S3)
+        );
+        assert_eq!(
+            parse_object_uri("az://logs/bids.jsonl").map(|u| u.scheme),
+            Some(ObjectScheme::Azure)
+        );
+        assert_eq!(parse_object_uri("gs://bucket-only"), None);
+        assert_eq!(
+            parse_object_uri("gs://bucket/").map(|u| u.key),
+            Some(String::new())
+        );
+        assert_eq!(parse_object_uri("logs.jsonl"), None);
+    }
+
+    #[test]
+    fn test_parse_time_bound() {
+        let now = 1_717_243_200; // 2024-06-01T12:00:00Z
+        assert_eq!(parse_time_bound("2024-06-01T12:00:00Z", 0).unwrap(), now);
+        assert_eq!(
+            parse_time_bound("2024-06-01T14:00:00+02:00", 0).unwrap(),
+            now
+        );
+        assert_eq!(
+            parse_time_bound("2024-06-01T12:00:00.250Z", 0).unwrap(),
+            now
+        );
+        assert_eq!(parse_time_bound("2024-06-01", 0).unwrap(), now - 12 * 3600);
+        assert_eq!(
+            parse_time_bound("20240101", now).unwrap(),
+            parse_rfc3339("2024-01-01T00:00:00Z").unwrap()
+        );
+        assert_eq!(parse_time_bound("24h", now).unwrap(), now - 86400);
+        assert_eq!(parse_time_bound("7d", now).unwrap(), now - 7 * 86400);
+        assert!(parse_time_bound("2024-13-01", now).is_err());
+        assert!(parse_time_bound("yesterday", now).is_err());
+
+        assert_eq!(format_rfc3339(now), "2024-06-01T12:00:00Z");
+        assert_eq!(format_rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_rfc3339(951_825_599), "2000-02-29T11:59:59Z");
+    }
+
+    #[test]
+    fn test_key_time_range_and_date_filter() {
+        let day = 1_717_200_000; // 2024-06-01T00:00:00Z
+        assert_eq!(
+            key_time_range("logs/dt=2024-06-01/part-0.jsonl"),
+            Some((day, day + 86400))
+        );
+        assert_eq!(
+            key_time_range("logs/year=2024/month=6/day=1/hour=13/a.jsonl"),
+            Some((day + 13 * 3600, day + 14 * 3600))
+        );
+        assert_eq!(
+            key_time_range("logs/2024/06/01/13/a.jsonl"),
+            Some((day + 13 * 3600, day + 14 * 3600))
+        );
+        assert_eq!(
+            key_time_range("logs/2024/06/01/a.jsonl"),
+            Some((day, day + 86400))
+        );
+        assert_eq!(key_time_range("logs/latest.jsonl"), None);
+
+        let range = DateRange {
+            since: Some(day + 86400),
+            until: None,
+        };
+        assert!(!range.includes("logs/dt=2024-06-01/a.jsonl", u64::MAX));
+        assert!(range.includes("logs/dt=2024-06-02/a.jsonl", 0));
+        assert!(range.includes("logs/latest.jsonl", day + 90000));
+        assert!(!range.includes("logs/latest.jsonl", day));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_channel_reader_streams_chunks() {
+        let (tx, rx) = mpsc::channel(2);
+        tokio::spawn(async move {
+            for chunk in ["{\"a\":", "1}\n{\"b\"", ":2}\n"] {
+                tx.send(Ok(Bytes::from(chunk))).await.unwrap();
+            }
+        });
+        let mut reader = ChannelReader {
+            rx,
+            current: Bytes::new(),
+        };
+        let mut text = String::new();
+        tokio::task::block_in_place(|| reader.read_to_string(&mut text)).unwrap();
+        assert_eq!(text, "{\"a\":1}\n{\"b\":2}\n");
+    }
+}
@@ -0,0 +1,440 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    io::Write,
+};
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::domains::{normalize, site_of, url_host};
+use crate::examples::RecordSample;
+use crate::metadata::{create_csv, ScanMetadata};
+use crate::qps::ALL_SSPS;
+use crate::stats::{scale_count, LogRecord};
+
+/// Stop tracking new spoofed inventory past this many (memory guard)
+const MAX_TRACKED_INVENTORY: usize = 100_000;
+
+/// Rows kept in the spoofed inventory list
+const TOP_INVENTORY: usize = 20;
+
+/// Raw records kept per SSP as evidence
+const SAMPLES_PER_SSP: usize = 3;
+
+/// site.domain and the host of site.page are different sites
+pub const PAGE_MISMATCH: &str = "page_mismatch";
+/// app.bundle looks like neither a package name nor a store id
+pub const INVALID_BUNDLE: &str = "invalid_bundle";
+/// app.storeurl points at another app than app.bundle
+pub const STORE_MISMATCH: &str = "store_mismatch";
+
+/// Hosts serving other sites' pages (AMP caches), so a page there matches
+/// any site.domain
+const PAGE_CACHES: &[&str] = &["ampproject.org", "ampproject.net"];
+
+/// Why site.domain and site.page disagree, as (claimed domain, page host)
+fn page_mismatch(site: &Value) -> Option<(String, String)> {
+    let domain = normalize(site["domain"].as_str()?);
+    let page = normalize(url_host(site["page"].as_str()?));
+    if domain.is_empty() || page.is_empty() || page.parse::<std::net::IpAddr>().is_ok() {
+        return None;
+    }
+    let cached = PAGE_CACHES
+        .iter()
+        .any(|cache| page == *cache || page.ends_with(&format!(".{cache}")));
+    (!cached && site_of(&domain) != site_of(&page)).then_some((domain, page))
+}
+
+/// A store id: Apple's numeric id (optionally `id`-prefixed), which CTV
+/// stores use too, or an Amazon ASIN
+fn is_store_id(bundle: &str) -> bool {
+    let digits = bundle.strip_prefix("id").unwrap_or(bundle);
+    let numeric = !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit());
+    let asin = bundle.len() == 10
+        && bundle.starts_with("B0")
+        && bundle
+            .bytes()
+            .all(|b| b.is_ascii_uppercase() || b.is_ascii_digit());
+    numeric || asin
+}
+
+/// A package name in reverse-DNS form, e.g. `com.example.game`
+fn is_package_name(bundle: &str) -> bool {
+    let mut segments = bundle.split('.');
+    let first_is_word = segments
+        .clone()
+        .next()
+        .and_then(|s| s.chars().next())
+        .is_some_and(|c| c.is_ascii_alphabetic());
+    first_is_word
+        && bundle.contains('.')
+        && segments.all(|s| {
+            !s.is_empty()
+                && s.bytes()
+                    .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+        })
+}
+
+/// The app a store URL points at, for the stores that name it in the URL
+fn store_app(storeurl: &str) -> Option<String> {
+    let host = normalize(url_host(storeurl));
+    if host == "play.google.com" {
+        let (_, query) = storeurl.split_once('?')?;
+        query
+            .split('&')
+            .find_map(|pair| pair.strip_prefix("id="))
+            .map(str::to_string)
+    } else if host.ends_with("apple.com") {
+        let path = storeurl.split(['?', '#']).next().unwrap_or("");
+        let id = path.rsplit('/').find_map(|segment| {
+            let digits = segment.strip_prefix("id")?;
+            (!digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())).then_some(digits)
+        })?;
+        Some(id.to_string())
+    } else {
+        None
+    }
+}
+
+/// The heuristics a request trips, each with what it claimed and what
+/// gave it away
+pub fn spoof_signals(request: &Value) -> Vec<(&'static str, String, String)> {
+    let mut signals = Vec::new();
+    if let Some((domain, page)) = page_mismatch(&request["site"]) {
+        signals.push((PAGE_MISMATCH, domain, page));
+    }
+    let app = &request["app"];
+    let bundle = app["bundle"].as_str().unwrap_or("").trim();
+    if bundle.is_empty() {
+        return signals;
+    }
+    if !is_package_name(bundle) && !is_store_id(bundle) {
+        signals.push((INVALID_BUNDLE, bundle.to_string(), String::new()));
+        return signals;
+    }
+    // Only compare like with like: iOS apps may send a package name with
+    // an App Store URL
+    let store = app["storeurl"].as_str().and_then(store_app);
+    let bundle_id = bundle.strip_prefix("id").unwrap_or(bundle);
+    let comparable = |app: &str| is_store_id(app) == is_store_id(bundle_id);
+    if let Some(store) = store.filter(|app| comparable(app)) {
+        if !store.eq_ignore_ascii_case(bundle_id) {
+            signals.push((STORE_MISMATCH, bundle.to_string(), store));
+        }
+    }
+    signals
+}
+
+/// Per-SSP counts of each heuristic
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct SpoofCounts {
+    pub requests: u64,
+    pub page_mismatch: u64,
+    pub invalid_bundle: u64,
+    pub store_mismatch: u64,
+    /// Requests tripping any heuristic
+    pub spoofed: u64,
+    pub spoofed_bids: u64,
+}
+
+impl SpoofCounts {
+    fn merge(&mut self, other: &SpoofCounts) {
+        self.requests += other.requests;
+        self.page_mismatch += other.page_mismatch;
+        self.invalid_bundle += other.invalid_bundle;
+        self.store_mismatch += other.store_mismatch;
+        self.spoofed += other.spoofed;
+        self.spoofed_bids += other.spoofed_bids;
+    }
+}
+
+/// A kept record and the heuristics it tripped
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct SpoofSample {
+    pub ssp: String,
+    pub reasons: Vec<&'static str>,
+    #[serde(flatten)]
+    pub sample: RecordSample,
+}
+
+/// Domain and bundle spoofing heuristics (--spoof): requests whose site or
+/// app fields contradict each other, per SSP, with sample records
+#[derive(Debug, Default, Clone)]
+pub struct SpoofStats {
+    pub by_ssp: BTreeMap<String, SpoofCounts>,
+    /// Requests per (SSP, heuristic, claimed, actual)
+    pub inventory: HashMap<(String, &'static str, String, String), u64>,
+    pub samples: BTreeMap<String, Vec<SpoofSample>>,
+}
+
+impl SpoofStats {
+    pub fn observe(&mut self, record: &LogRecord, ssp: &str, has_bid: bool) {
+        let signals = spoof_signals(&record.request);
+        let counts = self.by_ssp.entry(ssp.to_string()).or_default();
+        counts.requests += 1;
+        if signals.is_empty() {
+            return;
+        }
+        counts.spoofed += 1;
+        counts.spoofed_bids += u64::from(has_bid);
+        for (reason, _, _) in &signals {
+            match *reason {
+                PAGE_MISMATCH => counts.page_mismatch += 1,
+                INVALID_BUNDLE => counts.invalid_bundle += 1,
+                _ => counts.store_mismatch += 1,
+            }
+        }
+
+        let samples = self.samples.entry(ssp.to_string()).or_default();
+        if samples.len() < SAMPLES_PER_SSP {
+            samples.push(SpoofSample {
+                ssp: ssp.to_string(),
+                reasons: signals.iter().map(|(reason, _, _)| *reason).collect(),
+                sample: RecordSample::new(record, has_bid),
+            });
+        }
+        for (reason, claimed, actual) in signals {
+            let key = (ssp.to_string(), reason, claimed, actual);
+            if let Some(count) = self.inventory.get_mut(&key) {
+                *count += 1;
+            } else if self.inventory.len() < MAX_TRACKED_INVENTORY {
+                self.inventory.insert(key, 1);
+            }
+        }
+    }
+
+    pub fn merge(&mut self, other: &SpoofStats) {
+        for (ssp, counts) in &other.by_ssp {
+            self.by_ssp.entry(ssp.clone()).or_default().merge(counts);
+        }
+        for (key, count) in &other.inventory {
+            *self.inventory.entry(key.clone()).or_default() += count;
+        }
+        for (ssp, samples) in &other.samples {
+            let kept = self.samples.entry(ssp.clone()).or_default();
+            let room = SAMPLES_PER_SSP.saturating_sub(kept.len());
+            kept.extend(samples.iter().take(room).cloned());
+        }
+    }
+
+    /// Multiply counts by `factor` (extrapolating from a sample); the
+    /// sample records stay as they are
+    pub fn scale(&mut self, factor: f64) {
+        let scale = |count: &mut u64| scale_count(count, factor);
+        for counts in self.by_ssp.values_mut() {
+            scale(&mut counts.requests);
+            scale(&mut counts.page_mismatch);
+            scale(&mut counts.invalid_bundle);
+            scale(&mut counts.store_mismatch);
+            scale(&mut counts.spoofed);
+            scale(&mut counts.spoofed_bids);
+        }
+        self.inventory.values_mut().for_each(scale);
+    }
+
+    /// An ALL_SSPS row then SSPs by spoofed volume, the busiest spoofed
+    /// inventory and the sample records
+    pub fn summarize(&self) -> SpoofReport {
+        let mut total = SpoofCounts::default();
+        for counts in self.by_ssp.values() {
+            total.merge(counts);
+        }
+        let summary = |ssp: &str, c: &SpoofCounts| SpoofSspSummary {
+            ssp: ssp.to_string(),
+            requests: c.requests,
+            page_mismatch: c.page_mismatch,
+            invalid_bundle: c.invalid_bundle,
+            store_mismatch: c.store_mismatch,
+            spoofed: c.spoofed,
+            spoofed_share: if c.requests == 0 {
+                0.0
+            } else {
+                c.spoofed as f64 / c.requests as f64
+            },
+            spoofed_bids: c.spoofed_bids,
+        };
+        let mut ssps: Vec<SpoofSspSummary> = self
+            .by_ssp
+            .iter()
+            .map(|(ssp, counts)| summary(ssp, counts))
+            .collect();
+        ssps.sort_by(|a, b| {
+            (Reverse(a.spoofed), Reverse(a.requests), &a.ssp).cmp(&(
+                Reverse(b.spoofed),
+                Reverse(b.requests),
+                &b.ssp,
+            ))
+        });
+        ssps.insert(0, summary(ALL_SSPS, &total));
+
+        let mut inventory: Vec<SpoofedInventory> = self
+            .inventory
+            .iter()
+            .map(|(key, &requests)| {
+                let (ssp, reason, claimed, actual) = key.clone();
+                SpoofedInventory {
+                    ssp,
+                    reason,
+                    claimed,
+                    actual,
+                    requests,
+                }
+            })
+            .collect();
+        inventory.sort_by(|a, b| {
+            (Reverse(a.requests), &a.ssp, a.reason, &a.claimed, &a.actual).cmp(&(
+                Reverse(b.requests),
+                &b.ssp,
+                b.reason,
+                &b.claimed,
+                &b.actual,
+            ))
+        });
+        inventory.truncate(TOP_INVENTORY);
+
+        SpoofReport {
+            ssps,
+            inventory,
+            samples: self.samples.values().flatten().cloned().collect(),
+        }
+    }
+}
+
+/// Spoofing signals of one SSP
+#[derive(serde::Serialize)]
+pub struct SpoofSspSummary {
+    pub ssp: String,
+    pub requests: u64,
+    pub page_mismatch: u64,
+    pub invalid_bundle: u64,
+    pub store_mismatch: u64,
+    pub spoofed: u64,
+    /// spoofed / requests
+    pub spoofed_share: f64,
+    /// Bids we placed on likely spoofed inventory
+    pub spoofed_bids: u64,
+}
+
+/// A site or app one SSP sent with contradicting fields
+#[derive(serde::Serialize)]
+pub struct SpoofedInventory {
+    pub ssp: String,
+    /// The heuristic: page_mismatch, invalid_bundle or store_mismatch
+    pub reason: &'static str,
+    /// site.domain or app.bundle as sent
+    pub claimed: String,
+    /// The page host or store app id it contradicts (empty for
+    /// invalid_bundle)
+    pub actual: String,
+    pub requests: u64,
+}
+
+/// "Spoofed inventory" section of the report
+#[derive(serde::Serialize)]
+pub struct SpoofReport {
+    pub ssps: Vec<SpoofSspSummary>,
+    pub inventory: Vec<SpoofedInventory>,
+    pub samples: Vec<SpoofSample>,
+}
+
+/// Write spoofed_inventory.csv (per SSP, then the busiest spoofed
+/// inventory)
+pub fn write_spoof_csv(out_dir: &str, report: &SpoofReport, meta: &ScanMetadata) -> Result<()> {
+    let path = format!("{}/spoofed_inventory.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+
+    writeln!(csv, "# SSPs")?;
+    writeln!(
+        csv,
+        "ssp,requests,page_mismatch,invalid_bundle,store_mismatch,spoofed,spoofed_share,spoofed_bids"
+    )?;
+    for s in &report.ssps {
+        writeln!(
+            csv,
+            "{},{},{},{},{},{},{:.4},{}",
+            s.ssp,
+            s.requests,
+            s.page_mismatch,
+            s.invalid_bundle,
+            s.store_mismatch,
+            s.spoofed,
+            s.spoofed_share,
+            s.spoofed_bids
+        )?;
+    }
+
+    writeln!(csv, "\n# Spoofed inventory")?;
+    writeln!(csv, "ssp,reason,claimed,actual,requests")?;
+    for r in &report.inventory {
+        writeln!(
+            csv,
+            "{},{},{},{},{}",
+            r.ssp,
+            r.reason,
+            csv_field(&r.claimed),
+            csv_field(&r.actual),
+            r.requests
+        )?;
+    }
+
+    eprintln!("Spoofed inventory written to: {}", path);
+    Ok(())
+}
+
+/// Invalid bundles can hold anything, commas and quotes included
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_spoof_signals() {
+        let reasons = |request: Value| -> Vec<&'static str> {
+            spoof_signals(&request).into_iter().map(|s| s.0).collect()
+        };
+        let site = |domain: &str, page: &str| json!({ "site": { "domain": domain, "page": page } });
+        let app = |bundle: &str, storeurl: &str| json!({ "app": { "bundle": bundle, "storeurl": storeurl } });
+
+        // Subdomains, country second-level domains and AMP caches are fine
+        assert!(reasons(site("example.com", "https://news.example.com/a")).is_empty());
+        assert!(reasons(site("m.example.co.uk", "https://www.example.co.uk/")).is_empty());
+        let amp = "https://example-com.cdn.ampproject.org/c/s/example.com/a";
+        assert!(reasons(site("example.com", amp)).is_empty());
+        assert_eq!(
+            spoof_signals(&site("premium-news.com", "https://cheap-site.net/page?x=1")),
+            [(
+                PAGE_MISMATCH,
+                "premium-news.com".to_string(),
+                "cheap-site.net".to_string()
+            )]
+        );
+        assert_eq!(
+            reasons(site("bbc.co.uk", "https://itv.co.uk/")),
+            [PAGE_MISMATCH]
+        );
+
+        assert!(reasons(app("com.example.game", "")).is_empty());
+        assert!(reasons(app("id284882215", "")).is_empty());
+        assert!(reasons(app("B00EXAMPLE", "")).is_empty());
+        for bundle in ["My Game", "https://example.com", "game", "com..example"] {
+            assert_eq!(reasons(app(bundle, "")), [INVALID_BUNDLE], "{bundle}");
+        }
+        let play = "https://play.google.com/store/apps/details?id=com.other.app&hl=en";
+        assert_eq!(reasons(app("com.example.game", play)), [STORE_MISMATCH]);
+        assert!(reasons(app("com.other.app", play)).is_empty());
+        let apple = "https://apps.apple.com/us/app/some-game/id999";
+        assert_eq!(reasons(app("284882215", apple)), [STORE_MISMATCH]);
+        assert!(reasons(app("id999", apple)).is_empty());
+        // An iOS package name can't be compared with an App Store id
+        assert!(reasons(app("com.example.game", apple)).is_empty());
+    }
+}
@@ -0,0 +1,1387 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::adm::AdmAudit;
+use crate::bids::{BidDensityStats, ResponseBids};
+use crate::categories::request_categories;
+use crate::consent::PrivacySignals;
+use crate::coverage::present_fields;
+use crate::creatives::{creative_bids, CreativeStats};
+use crate::domains::DomainListStats;
+use crate::duplicates::DuplicateIds;
+use crate::examples::{ExampleRef, ExampleStore, RecordSample, SampleStore};
+use crate::extract::ExtractStats;
+use crate::filter::RecordFilter;
+use crate::fingerprint::RequestFingerprints;
+use crate::floors::FloorStats;
+use crate::groups::{Group, GroupBy};
+use crate::identity::present_ids;
+use crate::inventory::inventory_type;
+use crate::ivt::IvtStats;
+use crate::outcome::Outcome;
+use crate::position::{ad_position, viewability_bucket};
+use crate::prices::{PriceSketch, PriceStats};
+use crate::recommendations::tmax_bucket;
+use crate::redact::Redaction;
+use crate::seats::SeatActivity;
+use crate::secure::secure_bucket;
+use crate::sellers::SellerAudit;
+use crate::spoof::SpoofStats;
+use crate::status::{status_bucket, StatusStats};
+use crate::ua::ua_families;
+
+/// One log line from fake_ssp_logs.jsonl.
+#[derive(Deserialize, Clone)]
+pub struct LogRecord {
+    pub request: Value,
+    #[serde(default)]
+    pub response: Value,
+    #[serde(default)]
+    pub ts_ms: Option<u64>,
+    /// Auction outcome, when logged (see outcome::Outcome)
+    #[serde(default)]
+    pub outcome: Value,
+    /// HTTP status of the bidder's answer, when logged (see
+    /// status::status_bucket)
+    #[serde(default)]
+    pub http_status: Value,
+    /// 1-based line number in the input (0 when unknown)
+    #[serde(skip)]
+    pub line_no: u64,
+}
+
+#[derive(Debug, Default, PartialEq, Clone)]
+pub struct FormatStats {
+    pub requests: u64,
+    pub bids: u64,
+    pub sum_bid_price: f64,
+    /// Bid prices, for the median and p90 next to the mean
+    pub prices: PriceSketch,
+    /// Requests whose record carried an auction outcome
+    pub outcomes: u64,
+    pub wins: u64,
+    pub revenue: f64,
+}
+
+impl FormatStats {
+    /// Count one bid at `price`
+    pub fn add_bid(&mut self, price: f64) {
+        self.bids += 1;
+        self.sum_bid_price += price;
+        self.prices.observe(price);
+    }
+
+    pub fn add_outcome(&mut self, outcome: Outcome) {
+        self.outcomes += 1;
+        if outcome.win {
+            self.wins += 1;
+        }
+        self.revenue += outcome.revenue;
+    }
+
+    pub fn merge(&mut self, other: &FormatStats) {
+        self.requests += other.requests;
+        self.bids += other.bids;
+        self.sum_bid_price += other.sum_bid_price;
+        self.prices.merge(&other.prices);
+        self.outcomes += other.outcomes;
+        self.wins += other.wins;
+        self.revenue += other.revenue;
+    }
+
+    /// Multiply counts by `factor` (extrapolating from a sample)
+    pub fn scale(&mut self, factor: f64) {
+        scale_count(&mut self.requests, factor);
+        scale_count(&mut self.bids, factor);
+        self.sum_bid_price *= factor;
+        self.prices.scale(factor);
+        scale_count(&mut self.outcomes, factor);
+        scale_count(&mut self.wins, factor);
+        self.revenue *= factor;
+    }
+}
+
+/// Time analysis bucket width unless --bucket says otherwise
+pub const DEFAULT_TIME_BUCKET_MS: u64 = 60_000;
+
+/// Width of the time analysis buckets (--bucket)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeBucket {
+    pub ms: u64,
+}
+
+impl Default for TimeBucket {
+    fn default() -> Self {
+        Self {
+            ms: DEFAULT_TIME_BUCKET_MS,
+        }
+    }
+}
+
+impl TimeBucket {
+    /// Start (epoch ms) of the bucket `ts_ms` falls in
+    pub fn start(&self, ts_ms: u64) -> u64 {
+        ts_ms - ts_ms % self.ms
+    }
+}
+
+/// Stats for time-based analysis (per time bucket)
+#[derive(Debug, Default, Clone)]
+pub struct TimeStats {
+    pub requests: u64,
+    pub bids: u64,
+    pub sum_bid_price: f64,
+    pub min_ts: u64,
+    pub max_ts: u64,
+    /// Requests per (SSP, epoch second), for QPS (see qps::qps_by_ssp)
+    pub per_second: BTreeMap<(String, u64), u64>,
+    /// The bucket's traffic per SSP, for the report's time series
+    pub by_ssp: BTreeMap<String, FormatStats>,
+}
+
+impl TimeStats {
+    pub fn merge(&mut self, other: &TimeStats) {
+        self.requests += other.requests;
+        self.bids += other.bids;
+        self.sum_bid_price += other.sum_bid_price;
+        if self.min_ts == 0 || (other.min_ts != 0 && other.min_ts < self.min_ts) {
+            self.min_ts = other.min_ts;
+        }
+        self.max_ts = self.max_ts.max(other.max_ts);
+        for (key, count) in &other.per_second {
+            *self.per_second.entry(key.clone()).or_default() += count;
+        }
+        merge_map(&mut self.by_ssp, &other.by_ssp);
+    }
+
+    /// Most requests in any one second of the bucket, all SSPs together
+    pub fn peak_qps(&self) -> u64 {
+        let mut totals: BTreeMap<u64, u64> = BTreeMap::new();
+        for ((_, second), count) in &self.per_second {
+            *totals.entry(*second).or_default() += count;
+        }
+        totals.into_values().max().unwrap_or(0)
+    }
+}
+
+/// Key for publisher aggregation
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub struct PublisherKey {
+    pub ssp: String,
+    pub publisher_id: String,
+}
+
+/// Key for segment aggregation
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub struct SegmentKey {
+    pub ssp: String,
+    pub segment: String,
+}
+
+/// Key for the publisher x segment cross aggregation
+#[derive(Debug, Clone, Ord, PartialOrd, Eq, PartialEq)]
+pub struct PublisherSegmentKey {
+    pub ssp: String,
+    pub publisher_id: String,
+    pub segment: String,
+}
+
+/// (canonical, min, max) tolerance range for one IAB size
+type SizeRange = ((u32, u32), (u32, u32), (u32, u32));
+
+/// Canonical size families - maps raw sizes to standard IAB sizes
+pub fn canonical_size(w: u32, h: u32) -> (u32, u32) {
+    // Common IAB standard sizes and their tolerance ranges
+    let standards: &[SizeRange] = &[
+        // (canonical, min, max)
+        ((300, 250), (290, 240), (310, 260)),   // Medium Rectangle
+        ((320, 50), (310, 45), (330, 55)),      // Mobile Leaderboard
+        ((320, 100), (310, 90), (330, 110)),    // Large Mobile Banner
+        ((728, 90), (718, 85), (738, 95)),      // Leaderboard
+        ((160, 600), (150, 590), (170, 610)),   // Wide Skyscraper
+        ((300, 600), (290, 590), (310, 610)),   // Half Page
+        ((970, 250), (960, 240), (980, 260)),   // Billboard
+        ((970, 90), (960, 85), (980, 95)),      // Large Leaderboard
+        ((468, 60), (458, 55), (478, 65)),      // Full Banner
+        ((120, 600), (110, 590), (130, 610)),   // Skyscraper
+        ((250, 250), (240, 240), (260, 260)),   // Square
+        ((336, 280), (326, 270), (346, 290)),   // Large Rectangle
+        ((180, 150), (170, 140), (190, 160)),   // Rectangle
+        ((300, 100), (290, 90), (310, 110)),    // 3:1 Rectangle
+        ((320, 480), (310, 470), (330, 490)),   // Mobile Interstitial
+        ((480, 320), (470, 310), (490, 330)),   // Mobile Interstitial Landscape
+        ((1024, 768), (1014, 758), (1034, 778)), // Tablet Interstitial
+        ((768, 1024), (758, 1014), (778, 1034)), // Tablet Interstitial Portrait
+    ];
+
+    for &(canonical, (min_w, min_h), (max_w, max_h)) in standards {
+        if w >= min_w && w <= max_w && h >= min_h && h <= max_h {
+            return canonical;
+        }
+    }
+
+    // Not a standard size - return as-is (will be flagged as non-standard)
+    (w, h)
+}
+
+/// Check if a size is a standard IAB size
+pub fn is_standard_size(w: u32, h: u32) -> bool {
+    let canonical = canonical_size(w, h);
+    // If canonical matches common standards, it's standard
+    let standards: &[(u32, u32)] = &[
+        (300, 250), (320, 50), (320, 100), (728, 90), (160, 600),
+        (300, 600), (970, 250), (970, 90), (468, 60), (120, 600),
+        (250, 250), (336, 280), (180, 150), (300, 100), (320, 480),
+        (480, 320), (1024, 768), (768, 1024),
+    ];
+    standards.contains(&canonical)
+}
+
+/// Global stats container with multiple aggregation views
+#[derive(Debug, Default, Clone)]
+pub struct GlobalStats {
+    /// Raw format stats (original w,h)
+    pub by_raw_format: BTreeMap<(u32, u32), FormatStats>,
+
+    /// Canonical size bucket stats
+    pub by_canonical_format: BTreeMap<(u32, u32), FormatStats>,
+
+    /// Per-publisher stats
+    pub by_publisher: BTreeMap<PublisherKey, FormatStats>,
+
+    /// Per-segment stats
+    pub by_segment: BTreeMap<SegmentKey, FormatStats>,
+
+    /// Per-segment stats within each publisher
+    pub by_publisher_segment: BTreeMap<PublisherSegmentKey, FormatStats>,
+
+    /// Per-publisher stats by canonical format, for the mix-adjusted bid
+    /// rate (see mix::adjusted_bid_rates)
+    pub by_publisher_format: BTreeMap<(PublisherKey, (u32, u32)), FormatStats>,
+
+    /// Per-SSP/source stats
+    pub by_ssp: BTreeMap<String, FormatStats>,
+
+    /// Time-based stats keyed by bucket start (epoch ms)
+    pub time_stats: BTreeMap<u64, TimeStats>,
+
+    /// Width of the `time_stats` buckets
+    pub time_bucket: TimeBucket,
+
+    /// Per-SSP stats by canonical format
+    pub by_ssp_format: BTreeMap<(String, (u32, u32)), FormatStats>,
+
+    /// Per-DSP stats, from request.source.dsp (--perspective ssp)
+    pub by_dsp: BTreeMap<String, FormatStats>,
+
+    /// Per-DSP stats by canonical format
+    pub by_dsp_format: BTreeMap<(String, (u32, u32)), FormatStats>,
+
+    /// Per-SSP stats by tmax bucket (see recommendations::TMAX_BUCKETS)
+    pub by_ssp_tmax: BTreeMap<(String, &'static str), FormatStats>,
+
+    /// Per-SSP stats by imp.secure bucket (see secure::SECURE_BUCKETS)
+    pub by_secure: BTreeMap<(String, &'static str), FormatStats>,
+
+    /// Per-SSP stats by consent bucket (see consent::CONSENT_BUCKETS)
+    pub by_consent: BTreeMap<(String, &'static str), FormatStats>,
+
+    /// Requests carrying any GDPR/COPPA/US privacy signal
+    pub consent_signalled: u64,
+
+    /// Stats per country (device.geo.country, upper-cased)
+    pub by_country: BTreeMap<String, FormatStats>,
+
+    /// Stats per content category (site/app.cat and content.cat, see
+    /// categories::request_categories)
+    pub by_category: BTreeMap<String, FormatStats>,
+
+    /// Stats per ("browser" | "os", family) of device.ua (see
+    /// ua::ua_families)
+    pub by_user_agent: BTreeMap<(&'static str, &'static str), FormatStats>,
+
+    /// Stats by inventory type (see inventory::INVENTORY_TYPES) per
+    /// (dimension, key): canonical format "300x250", SSP, or "ssp|publisher"
+    pub by_inventory: BTreeMap<(&'static str, String, &'static str), FormatStats>,
+
+    /// Stats by ad position (see position::POSITIONS) per "ssp|publisher",
+    /// plus an "(all)" entry per position over every request
+    pub by_position: BTreeMap<(String, &'static str), FormatStats>,
+
+    /// Stats by viewability bucket (see position::VIEWABILITY_BUCKETS), keyed
+    /// like by_position
+    pub by_viewability: BTreeMap<(String, &'static str), FormatStats>,
+
+    /// Per-SSP stats for requests carrying each identifier type (see
+    /// identity::ID_TYPES), plus a "total" entry per SSP
+    pub by_identity: BTreeMap<(String, String), FormatStats>,
+
+    /// Per-SSP request counts carrying each of coverage::COVERAGE_FIELDS,
+    /// plus a "total" entry per SSP
+    pub by_field: BTreeMap<(String, &'static str), u64>,
+
+    /// Bid price histograms and distribution (see prices::PriceStats)
+    pub prices: PriceStats,
+
+    /// Bids per response and first vs highest price
+    pub bid_density: BidDensityStats,
+
+    /// Bid price relative to imp.bidfloor per format and SSP
+    pub floors: FloorStats,
+
+    /// Response statuses per SSP and time bucket, separating bidder errors
+    /// from no-bids
+    pub statuses: StatusStats,
+
+    /// Per buyer seat and canonical format bidding
+    pub seats: SeatActivity,
+
+    /// Advertiser domains, creative ids and attributes per format,
+    /// publisher and seat
+    pub creatives: CreativeStats,
+
+    /// Markup (adm) audit per format and seat
+    pub adm_audit: AdmAudit,
+
+    /// request.id collisions per SSP
+    pub duplicate_ids: DuplicateIds,
+
+    /// Example record references per aggregation key (--examples)
+    pub examples: ExampleStore,
+
+    /// Raw records per raw format for the report drill-down (--samples)
+    pub samples: SampleStore,
+
+    /// Suspicious traffic heuristics (--ivt)
+    pub ivt: Option<IvtStats>,
+
+    /// Off-list traffic per SSP (--allowlist / --denylist)
+    pub domain_lists: Option<DomainListStats>,
+
+    /// Publisher and schain seller ids missing from sellers.json
+    /// (--sellers-json)
+    pub sellers: Option<SellerAudit>,
+
+    /// Likely spoofed domains and bundles per SSP (--spoof)
+    pub spoof: Option<SpoofStats>,
+
+    /// Byte-identical and near-identical requests per SSP (--fingerprint)
+    pub fingerprints: Option<RequestFingerprints>,
+
+    /// User-defined dimensions (--extract)
+    pub extract: Option<ExtractStats>,
+
+    /// Test traffic (`test` = 1) per SSP, counted whether or not it is
+    /// excluded from the other stats
+    pub test_traffic: BTreeMap<String, FormatStats>,
+
+    /// Leave test traffic out of every other view (--exclude-test)
+    pub exclude_test: bool,
+
+    /// Only records matching this are aggregated (--filter)
+    pub filter: Option<RecordFilter>,
+
+    /// Records the filter dropped
+    pub filtered_out: u64,
+
+    /// Identifiers taken out of each record before it is aggregated
+    /// (--redact)
+    pub redact: Option<Redaction>,
+
+    /// Aggregations to compute; the others stay empty (--group-by)
+    pub group_by: GroupBy,
+}
+
+/// Multiply `count` by `factor`, rounding to the nearest whole count
+pub(crate) fn scale_count(count: &mut u64, factor: f64) {
+    *count = (*count as f64 * factor).round() as u64;
+}
+
+/// Scale every entry of `map` by `factor`
+fn scale_map<K>(map: &mut BTreeMap<K, FormatStats>, factor: f64) {
+    for stats in map.values_mut() {
+        stats.scale(factor);
+    }
+}
+
+/// Merge every entry of `src` into the matching entry of `dst`
+fn merge_map<K: Ord + Clone>(dst: &mut BTreeMap<K, FormatStats>, src: &BTreeMap<K, FormatStats>) {
+    for (key, stats) in src {
+        dst.entry(key.clone()).or_default().merge(stats);
+    }
+}
+
+impl GlobalStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether any record carried an auction outcome (see outcome::Outcome)
+    pub fn has_outcomes(&self) -> bool {
+        self.by_canonical_format.values().any(|s| s.outcomes > 0)
+    }
+
+    /// Fold another set of aggregates into this one (used by rolling windows)
+    pub fn merge(&mut self, other: &GlobalStats) {
+        merge_map(&mut self.by_raw_format, &other.by_raw_format);
+        merge_map(&mut self.by_canonical_format, &other.by_canonical_format);
+        merge_map(&mut self.by_publisher, &other.by_publisher);
+        merge_map(&mut self.by_segment, &other.by_segment);
+        merge_map(&mut self.by_publisher_segment, &other.by_publisher_segment);
+        merge_map(&mut self.by_publisher_format, &other.by_publisher_format);
+        merge_map(&mut self.by_ssp, &other.by_ssp);
+        merge_map(&mut self.by_ssp_format, &other.by_ssp_format);
+        merge_map(&mut self.by_dsp, &other.by_dsp);
+        merge_map(&mut self.by_dsp_format, &other.by_dsp_format);
+        merge_map(&mut self.by_ssp_tmax, &other.by_ssp_tmax);
+        merge_map(&mut self.by_secure, &other.by_secure);
+        merge_map(&mut self.test_traffic, &other.test_traffic);
+        merge_map(&mut self.by_consent, &other.by_consent);
+        merge_map(&mut self.by_identity, &other.by_identity);
+        self.consent_signalled += other.consent_signalled;
+        self.filtered_out += other.filtered_out;
+        merge_map(&mut self.by_country, &other.by_country);
+        merge_map(&mut self.by_category, &other.by_category);
+        merge_map(&mut self.by_user_agent, &other.by_user_agent);
+        merge_map(&mut self.by_inventory, &other.by_inventory);
+        merge_map(&mut self.by_position, &other.by_position);
+        merge_map(&mut self.by_viewability, &other.by_viewability);
+        for (key, count) in &other.by_field {
+            *self.by_field.entry(key.clone()).or_default() += count;
+        }
+        for (bucket, stats) in &other.time_stats {
+            self.time_stats.entry(*bucket).or_default().merge(stats);
+        }
+        self.prices.merge(&other.prices);
+        self.bid_density.merge(&other.bid_density);
+        self.floors.merge(&other.floors);
+        self.statuses.merge(&other.statuses);
+        self.seats.merge(&other.seats);
+        self.creatives.merge(&other.creatives);
+        self.adm_audit.merge(&other.adm_audit);
+        self.duplicate_ids.merge(&other.duplicate_ids);
+        self.examples.merge(&other.examples);
+        self.samples.merge(&other.samples);
+        if let Some(other_ivt) = &other.ivt {
+            self.ivt
+                .get_or_insert_with(IvtStats::default)
+                .merge(other_ivt);
+        }
+        if let Some(other_lists) = &other.domain_lists {
+            self.domain_lists
+                .get_or_insert_with(DomainListStats::default)
+                .merge(other_lists);
+        }
+        if let Some(other_sellers) = &other.sellers {
+            self.sellers
+                .get_or_insert_with(SellerAudit::default)
+                .merge(other_sellers);
+        }
+        if let Some(other_spoof) = &other.spoof {
+            self.spoof
+                .get_or_insert_with(SpoofStats::default)
+                .merge(other_spoof);
+        }
+        if let Some(other_prints) = &other.fingerprints {
+            self.fingerprints
+                .get_or_insert_with(RequestFingerprints::default)
+                .merge(other_prints);
+        }
+        if let Some(other_extract) = &other.extract {
+            self.extract
+                .get_or_insert_with(ExtractStats::default)
+                .merge(other_extract);
+        }
+    }
+
+    /// Turn counts from a 1-in-`factor` sample into full-volume estimates.
+    /// Rates and average prices are unaffected. Example refs, record
+    /// samples and IVT heuristics keep their sampled values.
+    pub fn scale(&mut self, factor: f64) {
+        scale_map(&mut self.by_raw_format, factor);
+        scale_map(&mut self.by_canonical_format, factor);
+        scale_map(&mut self.by_publisher, factor);
+        scale_map(&mut self.by_segment, factor);
+        scale_map(&mut self.by_publisher_segment, factor);
+        scale_map(&mut self.by_publisher_format, factor);
+        scale_map(&mut self.by_ssp, factor);
+        scale_map(&mut self.by_ssp_format, factor);
+        scale_map(&mut self.by_dsp, factor);
+        scale_map(&mut self.by_dsp_format, factor);
+        scale_map(&mut self.by_ssp_tmax, factor);
+        scale_map(&mut self.by_secure, factor);
+        scale_map(&mut self.test_traffic, factor);
+        scale_map(&mut self.by_consent, factor);
+        scale_map(&mut self.by_identity, factor);
+        scale_map(&mut self.by_country, factor);
+        scale_map(&mut self.by_category, factor);
+        scale_map(&mut self.by_user_agent, factor);
+        scale_map(&mut self.by_inventory, factor);
+        scale_map(&mut self.by_position, factor);
+        scale_map(&mut self.by_viewability, factor);
+        if let Some(extract) = &mut self.extract {
+            scale_map(&mut extract.by_value, factor);
+        }
+        if let Some(domain_lists) = &mut self.domain_lists {
+            domain_lists.scale(factor);
+        }
+        if let Some(sellers) = &mut self.sellers {
+            sellers.scale(factor);
+        }
+        if let Some(spoof) = &mut self.spoof {
+            spoof.scale(factor);
+        }
+        for stats in self.time_stats.values_mut() {
+            scale_count(&mut stats.requests, factor);
+            scale_count(&mut stats.bids, factor);
+            stats.sum_bid_price *= factor;
+            for count in stats.per_second.values_mut() {
+                scale_count(count, factor);
+            }
+            scale_map(&mut stats.by_ssp, factor);
+        }
+        scale_count(&mut self.consent_signalled, factor);
+        scale_count(&mut self.filtered_out, factor);
+        for count in self.by_field.values_mut() {
+            scale_count(count, factor);
+        }
+        self.prices.scale(factor);
+        self.bid_density.scale(factor);
+        self.floors.scale(factor);
+        self.statuses.scale(factor);
+        self.seats.scale(factor);
+        self.creatives.scale(factor);
+        self.adm_audit.scale(factor);
+        self.duplicate_ids.scale(factor);
+        if let Some(fingerprints) = &mut self.fingerprints {
+            fingerprints.scale(factor);
+        }
+    }
+
+    /// Total requests counted across all raw formats
+    pub fn total_requests(&self) -> u64 {
+        self.by_raw_format.values().map(|s| s.requests).sum()
+    }
+
+    /// Total bids counted across all raw formats
+    pub fn total_bids(&self) -> u64 {
+        self.by_raw_format.values().map(|s| s.bids).sum()
+    }
+}
+
+/// Process a single log record and update all GlobalStats views
+pub fn process_record_global(record: &LogRecord, global: &mut GlobalStats) {
+    if global.filter.as_ref().is_some_and(|f| !f.matches(record)) {
+        global.filtered_out += 1;
+        return;
+    }
+    let redacted;
+    let record = match &global.redact {
+        Some(redaction) => {
+            redacted = redaction.apply(record);
+            &redacted
+        }
+        None => record,
+    };
+
+    // Extract (w, h) from request.imp[0].banner.{w,h}
+    let w = record.request["imp"][0]["banner"]["w"]
+        .as_u64()
+        .unwrap_or(0) as u32;
+    let h = record.request["imp"][0]["banner"]["h"]
+        .as_u64()
+        .unwrap_or(0) as u32;
+
+    if w == 0 || h == 0 {
+        return;
+    }
+
+    // Every bid across all seatbids; a response counts as one bid at the
+    // highest price
+    let bids = ResponseBids::from_response(&record.response);
+    let has_bid = bids.count > 0;
+    let bid_price = bids.highest_price;
+    let outcome = Outcome::from_value(&record.outcome);
+
+    // Helper to update FormatStats
+    let update_stats = |entry: &mut FormatStats| {
+        entry.requests += 1;
+        if has_bid {
+            entry.add_bid(bid_price);
+        }
+        if let Some(outcome) = outcome {
+            entry.add_outcome(outcome);
+        }
+    };
+
+    // Test traffic is tallied on its own, and with --exclude-test goes no
+    // further
+    if record.request["test"].as_u64() == Some(1) {
+        let ssp = record.request["source"]["ssp"].as_str().unwrap_or("");
+        update_stats(global.test_traffic.entry(ssp.to_string()).or_default());
+        if global.exclude_test {
+            return;
+        }
+    }
+
+    let groups = global.group_by;
+    if groups.has(Group::Bids) {
+        global.bid_density.observe(&bids);
+    }
+
+    let example = global.examples.is_enabled().then(|| ExampleRef {
+        line: record.line_no,
+        id: record.request["id"].as_str().unwrap_or("").to_string(),
+    });
+
+    // 1. Raw format stats
+    update_stats(global.by_raw_format.entry((w, h)).or_default());
+
+    // 2. Canonical format stats
+    let canonical = canonical_size(w, h);
+    update_stats(global.by_canonical_format.entry(canonical).or_default());
+
+    let creatives = if has_bid && groups.has(Group::Bids) {
+        creative_bids(&record.response)
+    } else {
+        Vec::new()
+    };
+    let format_key = format!("{}x{}", canonical.0, canonical.1);
+    if groups.has(Group::Bids) {
+        global.seats.observe(&record.request, canonical, &bids);
+        global.creatives.observe("format", &format_key, &creatives);
+        global.creatives.observe_seats(&creatives);
+        if has_bid {
+            global
+                .adm_audit
+                .observe(&record.request, &record.response, &format_key);
+        }
+    }
+    let inventory = inventory_type(&record.request);
+    let position = ad_position(&record.request);
+    let viewability = viewability_bucket(&record.request);
+    if groups.has(Group::Content) {
+        update_stats(
+            global
+                .by_inventory
+                .entry(("format", format_key.clone(), inventory))
+                .or_default(),
+        );
+        update_stats(
+            global
+                .by_position
+                .entry(("(all)".to_string(), position))
+                .or_default(),
+        );
+        update_stats(
+            global
+                .by_viewability
+                .entry(("(all)".to_string(), viewability))
+                .or_default(),
+        );
+        for category in request_categories(&record.request) {
+            update_stats(global.by_category.entry(category).or_default());
+        }
+        if let Some((browser, os)) = ua_families(&record.request) {
+            update_stats(
+                global
+                    .by_user_agent
+                    .entry(("browser", browser))
+                    .or_default(),
+            );
+            update_stats(global.by_user_agent.entry(("os", os)).or_default());
+        }
+    }
+
+    if let Some(example) = &example {
+        global
+            .examples
+            .offer("raw_format", &format!("{}x{}", w, h), example);
+        global.examples.offer(
+            "canonical_format",
+            &format!("{}x{}", canonical.0, canonical.1),
+            example,
+        );
+    }
+
+    global
+        .samples
+        .offer((w, h), has_bid, || RecordSample::new(record, has_bid));
+
+    // 3. Extract SSP (from request.source.ssp or similar)
+    let ssp = record
+        .request
+        .get("source")
+        .and_then(|s| s.get("ssp"))
+        .and_then(|v| v.as_str())
+        .unwrap_or("")
+        .to_string();
+
+    // Update SSP stats
+    if !ssp.is_empty() && groups.has(Group::Ssp) {
+        update_stats(global.by_ssp.entry(ssp.clone()).or_default());
+        update_stats(
+            global
+                .by_ssp_format
+                .entry((ssp.clone(), canonical))
+                .or_default(),
+        );
+        if groups.has(Group::Content) {
+            update_stats(
+                global
+                    .by_inventory
+                    .entry(("ssp", ssp.clone(), inventory))
+                    .or_default(),
+            );
+        }
+        if let Some(bucket) = record.request["tmax"].as_u64().map(tmax_bucket) {
+            update_stats(global.by_ssp_tmax.entry((ssp.clone(), bucket)).or_default());
+        }
+        update_stats(
+            global
+                .by_secure
+                .entry((ssp.clone(), secure_bucket(&record.request)))
+                .or_default(),
+        );
+        if let Some(example) = &example {
+            global.examples.offer("ssp", &ssp, example);
+        }
+    }
+
+    // DSP that answered (see decode::JsonlDecoder::with_dsp_field)
+    let dsp = record.request["source"]["dsp"].as_str().unwrap_or("");
+    if !dsp.is_empty() && groups.has(Group::Ssp) {
+        update_stats(global.by_dsp.entry(dsp.to_string()).or_default());
+        update_stats(
+            global
+                .by_dsp_format
+                .entry((dsp.to_string(), canonical))
+                .or_default(),
+        );
+    }
+
+    if has_bid && groups.has(Group::Bids) {
+        global.prices.observe(canonical, &ssp, bid_price);
+        global.floors.observe(
+            &record.request,
+            &record.response,
+            canonical,
+            &ssp,
+            bid_price,
+        );
+    }
+
+    if groups.has(Group::Ssp) {
+        global.duplicate_ids.observe(&record.request, &ssp);
+
+        // Bidder errors vs no-bids, when the line logs a status
+        if let Some(status) = status_bucket(&record.http_status, &record.response, has_bid) {
+            let time_bucket = record
+                .ts_ms
+                .filter(|_| groups.has(Group::Hour))
+                .map(|ts_ms| global.time_bucket.start(ts_ms));
+            global.statuses.observe(&ssp, time_bucket, status);
+        }
+
+        // Consent / privacy signal breakdown
+        let signals = PrivacySignals::from_request(&record.request);
+        if signals.is_signalled() {
+            global.consent_signalled += 1;
+        }
+        for bucket in signals.buckets() {
+            update_stats(global.by_consent.entry((ssp.clone(), bucket)).or_default());
+        }
+
+        // User ID coverage
+        update_stats(
+            global
+                .by_identity
+                .entry((ssp.clone(), "total".to_string()))
+                .or_default(),
+        );
+        for id_type in present_ids(&record.request) {
+            update_stats(
+                global
+                    .by_identity
+                    .entry((ssp.clone(), id_type))
+                    .or_default(),
+            );
+        }
+
+        // OpenRTB field coverage
+        *global.by_field.entry((ssp.clone(), "total")).or_default() += 1;
+        for field in present_fields(&record.request) {
+            *global.by_field.entry((ssp.clone(), field)).or_default() += 1;
+        }
+    }
+
+    if let Some(ivt) = &mut global.ivt {
+        ivt.observe(record, &ssp, w, h);
+    }
+
+    if let Some(domain_lists) = &mut global.domain_lists {
+        domain_lists.observe(&record.request, &ssp, has_bid);
+    }
+
+    if let Some(sellers) = &mut global.sellers {
+        sellers.observe(&record.request, &ssp, has_bid);
+    }
+
+    if let Some(spoof) = &mut global.spoof {
+        spoof.observe(record, &ssp, has_bid);
+    }
+
+    if let Some(fingerprints) = &mut global.fingerprints {
+        fingerprints.observe(&record.request, &ssp, record.ts_ms);
+    }
+
+    if let Some(extract) = &mut global.extract {
+        extract.observe(&record.request, &ssp, update_stats);
+    }
+
+    // Country stats
+    if groups.has(Group::Geo) {
+        if let Some(country) = record.request["device"]["geo"]["country"].as_str() {
+            let country = country.trim().to_ascii_uppercase();
+            if !country.is_empty() {
+                update_stats(global.by_country.entry(country).or_default());
+            }
+        }
+    }
+
+    // 4. Publisher stats
+    let pub_id = record
+        .request
+        .get("site")
+        .and_then(|s| s.get("publisher"))
+        .and_then(|p| p.get("id"))
+        .and_then(|id| id.as_str());
+    if let Some(pub_id) = pub_id.filter(|_| groups.has(Group::Publisher)) {
+        let key = PublisherKey {
+            ssp: ssp.clone(),
+            publisher_id: pub_id.to_string(),
+        };
+        update_stats(
+            global
+                .by_publisher_format
+                .entry((key.clone(), canonical))
+                .or_default(),
+        );
+        update_stats(global.by_publisher.entry(key).or_default());
+        let pub_key = format!("{}|{}", ssp, pub_id);
+        global.creatives.observe("publisher", &pub_key, &creatives);
+        if groups.has(Group::Content) {
+            update_stats(
+                global
+                    .by_inventory
+                    .entry(("publisher", pub_key.clone(), inventory))
+                    .or_default(),
+            );
+            update_stats(
+                global
+                    .by_position
+                    .entry((pub_key.clone(), position))
+                    .or_default(),
+            );
+            update_stats(
+                global
+                    .by_viewability
+                    .entry((pub_key.clone(), viewability))
+                    .or_default(),
+            );
+        }
+        if let Some(example) = &example {
+            global.examples.offer("publisher", &pub_key, example);
+        }
+    }
+
+    // 5. Segment stats
+    let seg_id = record
+        .request
+        .get("user")
+        .and_then(|u| u.get("data"))
+        .and_then(|d| d.as_array())
+        .and_then(|arr| arr.first())
+        .and_then(|data| data.get("segment"))
+        .and_then(|s| s.as_array())
+        .and_then(|arr| arr.first())
+        .and_then(|seg| seg.get("id"))
+        .and_then(|id| id.as_str());
+    if let Some(seg_id) = seg_id.filter(|_| groups.has(Group::Segment)) {
+        let key = SegmentKey {
+            ssp: ssp.clone(),
+            segment: seg_id.to_string(),
+        };
+        update_stats(global.by_segment.entry(key).or_default());
+        if let Some(pub_id) = pub_id {
+            let key = PublisherSegmentKey {
+                ssp: ssp.clone(),
+                publisher_id: pub_id.to_string(),
+                segment: seg_id.to_string(),
+            };
+            update_stats(global.by_publisher_segment.entry(key).or_default());
+        }
+        if let Some(example) = &example {
+            global
+                .examples
+                .offer("segment", &format!("{}|{}", ssp, seg_id), example);
+        }
+    }
+
+    // 6. Time-based stats
+    if let Some(ts_ms) = record.ts_ms.filter(|_| groups.has(Group::Hour)) {
+        let bucket = global.time_bucket.start(ts_ms);
+        let entry = global.time_stats.entry(bucket).or_default();
+        entry.requests += 1;
+
+        if entry.min_ts == 0 || ts_ms < entry.min_ts {
+            entry.min_ts = ts_ms;
+        }
+        if ts_ms > entry.max_ts {
+            entry.max_ts = ts_ms;
+        }
+        *entry
+            .per_second
+            .entry((ssp.clone(), ts_ms / 1000))
+            .or_default() += 1;
+        update_stats(entry.by_ssp.entry(ssp.clone()).or_default());
+
+        if has_bid {
+            entry.bids += 1;
+            entry.sum_bid_price += bid_price;
+        }
+    }
+}
+
+pub fn bid_rate(stat: &FormatStats) -> f64 {
+    if stat.requests == 0 {
+        0.0
+    } else {
+        stat.bids as f64 / stat.requests as f64
+    }
+}
+
+pub fn avg_bid_price(stat: &FormatStats) -> f64 {
+    if stat.bids == 0 {
+        0.0
+    } else {
+        stat.sum_bid_price / stat.bids as f64
+    }
+}
+
+/// Wins per bid, None when no record of the key carried an outcome
+pub fn win_rate(stat: &FormatStats) -> Option<f64> {
+    match (stat.outcomes, stat.bids) {
+        (0, _) => None,
+        (_, 0) => Some(0.0),
+        (_, bids) => Some(stat.wins as f64 / bids as f64),
+    }
+}
+
+/// Revenue from auction outcomes, None when no record of the key carried one
+pub fn revenue(stat: &FormatStats) -> Option<f64> {
+    (stat.outcomes > 0).then_some(stat.revenue)
+}
+
+/// Approximate median bid price (see PriceSketch), 0 without bids
+pub fn median_bid_price(stat: &FormatStats) -> f64 {
+    stat.prices.percentile(50).unwrap_or(0.0)
+}
+
+/// Approximate 90th percentile bid price, 0 without bids
+pub fn p90_bid_price(stat: &FormatStats) -> f64 {
+    stat.prices.percentile(90).unwrap_or(0.0)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::examples::tests::examples_for;
+
+    /// Test helper: process a single log record and update the stats map
+    fn process_record(record: &LogRecord, stats: &mut BTreeMap<(u32, u32), FormatStats>) {
+        let w = record.request["imp"][0]["banner"]["w"]
+            .as_u64()
+            .unwrap_or(0) as u32;
+        let h = record.request["imp"][0]["banner"]["h"]
+            .as_u64()
+            .unwrap_or(0) as u32;
+
+        if w == 0 || h == 0 {
+            return;
+        }
+
+        let entry = stats.entry((w, h)).or_default();
+        entry.requests += 1;
+
+        if let Some(seatbids) = record
+            .response
+            .get("seatbid")
+            .and_then(|v| v.as_array())
+        {
+            if !seatbids.is_empty() {
+                entry.bids += 1;
+
+                if let Some(price) = seatbids
+                    .first()
+                    .and_then(|sb| sb.get("bid"))
+                    .and_then(|bids| bids.as_array())
+                    .and_then(|bids_arr| bids_arr.first())
+                    .and_then(|b| b.get("price"))
+                    .and_then(|p| p.as_f64())
+                {
+                    entry.sum_bid_price += price;
+                }
+            }
+        }
+    }
+
+    pub fn make_record(w: u32, h: u32, with_bid: bool, price: f64) -> LogRecord {
+        let request = serde_json::json!({
+            "imp": [{
+                "banner": {
+                    "w": w,
+                    "h": h
+                }
+            }]
+        });
+
+        let response = if with_bid {
+            serde_json::json!({
+                "seatbid": [{
+                    "bid": [{
+                        "price": price
+                    }]
+                }]
+            })
+        } else {
+            serde_json::json!({
+                "seatbid": []
+            })
+        };
+
+        LogRecord {
+            request,
+            response,
+            ts_ms: None,
+            outcome: Value::Null,
+            http_status: Value::Null,
+            line_no: 0,
+        }
+    }
+
+    #[test]
+    fn test_single_bid() {
+        let mut stats = BTreeMap::new();
+        let record = make_record(300, 250, true, 0.5);
+
+        process_record(&record, &mut stats);
+
+        assert_eq!(stats.len(), 1);
+        let s = stats.get(&(300, 250)).unwrap();
+        assert_eq!(
+            *s,
+            FormatStats {
+                requests: 1,
+                bids: 1,
+                sum_bid_price: 0.5,
+                ..Default::default()
+            }
+        );
+        assert!((bid_rate(s) - 1.0).abs() < 1e-9);
+        assert!((avg_bid_price(s) - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_single_no_bid() {
+        let mut stats = BTreeMap::new();
+        let record = make_record(320, 50, false, 0.0);
+
+        process_record(&record, &mut stats);
+
+        assert_eq!(stats.len(), 1);
+        let s = stats.get(&(320, 50)).unwrap();
+        assert_eq!(
+            *s,
+            FormatStats {
+                requests: 1,
+                bids: 0,
+                sum_bid_price: 0.0,
+                ..Default::default()
+            }
+        );
+        assert!((bid_rate(s) - 0.0).abs() < 1e-9);
+        assert!((avg_bid_price(s) - 0.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_multiple_formats() {
+        let mut stats = BTreeMap::new();
+
+        // 3 requests for 300x250, 2 bids with prices 0.5 and 1.0
+        process_record(&make_record(300, 250, true, 0.5), &mut stats);
+        process_record(&make_record(300, 250, true, 1.0), &mut stats);
+        process_record(&make_record(300, 250, false, 0.0), &mut stats);
+
+        // 1 request for 160x600, no bid
+        process_record(&make_record(160, 600, false, 0.0), &mut stats);
+
+        let s_300 = stats.get(&(300, 250)).unwrap();
+        assert_eq!(
+            *s_300,
+            FormatStats {
+                requests: 3,
+                bids: 2,
+                sum_bid_price: 1.5,
+                ..Default::default()
+            }
+        );
+        assert!((bid_rate(s_300) - (2.0 / 3.0)).abs() < 1e-9);
+        assert!((avg_bid_price(s_300) - 0.75).abs() < 1e-9);
+
+        let s_160 = stats.get(&(160, 600)).unwrap();
+        assert_eq!(
+            *s_160,
+            FormatStats {
+                requests: 1,
+                bids: 0,
+                sum_bid_price: 0.0,
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_malformed_record_skipped() {
+        let mut stats: BTreeMap<(u32, u32), FormatStats> = BTreeMap::new();
+
+        // Record with w=0 should be skipped entirely
+        let bad_record = LogRecord {
+            request: serde_json::json!({
+                "imp": [{
+                    "banner": {"w": 0, "h": 250}
+                }]
+            }),
+            response: serde_json::json!({}),
+            ts_ms: None,
+            outcome: Value::Null,
+            http_status: Value::Null,
+            line_no: 0,
+        };
+
+        process_record(&bad_record, &mut stats);
+
+        assert_eq!(stats.len(), 0);
+    }
+
+    #[test]
+    fn test_canonical_size_bucketing() {
+        // Test that slightly off sizes map to canonical
+        assert_eq!(canonical_size(298, 250), (300, 250));
+        assert_eq!(canonical_size(301, 246), (300, 250));
+        assert_eq!(canonical_size(300, 250), (300, 250));
+
+        // Test other standard sizes
+        assert_eq!(canonical_size(320, 50), (320, 50));
+        assert_eq!(canonical_size(728, 90), (728, 90));
+        assert_eq!(canonical_size(160, 600), (160, 600));
+
+        // Non-standard sizes should return as-is
+        assert_eq!(canonical_size(123, 456), (123, 456));
+        assert_eq!(canonical_size(999, 888), (999, 888));
+    }
+
+    #[test]
+    fn test_is_standard_size() {
+        assert!(is_standard_size(300, 250));
+        assert!(is_standard_size(320, 50));
+        assert!(is_standard_size(728, 90));
+
+        // Slightly off sizes that map to canonical should be standard
+        assert!(is_standard_size(298, 250));
+
+        // Non-standard sizes
+        assert!(!is_standard_size(123, 456));
+        assert!(!is_standard_size(999, 888));
+    }
+
+    #[test]
+    fn test_global_stats_canonical_aggregation() {
+        let mut global = GlobalStats::new();
+
+        // Create records with slightly different sizes that should bucket together
+        let record1 = make_record(298, 250, true, 0.5); // Should map to 300x250
+        let record2 = make_record(301, 246, true, 1.0); // Should map to 300x250
+        let record3 = make_record(300, 250, false, 0.0); // Exact 300x250
+
+        process_record_global(&record1, &mut global);
+        process_record_global(&record2, &mut global);
+        process_record_global(&record3, &mut global);
+
+        // Raw format stats should have 3 different sizes
+        assert_eq!(global.by_raw_format.len(), 3);
+
+        // Canonical format stats should have 1 bucket
+        assert_eq!(global.by_canonical_format.len(), 1);
+
+        let canonical_stats = global.by_canonical_format.get(&(300, 250)).unwrap();
+        assert_eq!(canonical_stats.requests, 3);
+        assert_eq!(canonical_stats.bids, 2);
+    }
+
+    #[test]
+    fn test_global_stats_merge() {
+        let mut a = GlobalStats::new();
+        let mut b = GlobalStats::new();
+        process_record_global(&make_record(300, 250, true, 0.5), &mut a);
+        process_record_global(&make_record(300, 250, false, 0.0), &mut b);
+        process_record_global(&make_record(728, 90, true, 1.0), &mut b);
+
+        a.merge(&b);
+
+        assert_eq!(a.total_requests(), 3);
+        assert_eq!(a.total_bids(), 2);
+        let s = a.by_raw_format.get(&(300, 250)).unwrap();
+        assert_eq!(s.requests, 2);
+        assert!((s.sum_bid_price - 0.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_examples_recorded_per_key() {
+        let mut global = GlobalStats::new();
+        global.examples = ExampleStore::new(2, 100);
+        for line_no in 1..=3 {
+            let mut record = make_record(300, 250, false, 0.0);
+            record.line_no = line_no;
+            process_record_global(&record, &mut global);
+        }
+
+        let lines: Vec<u64> = examples_for(&global.examples, "raw_format", "300x250")
+            .iter()
+            .map(|e| e.line)
+            .collect();
+        assert_eq!(lines, vec![1, 2]);
+        assert_eq!(
+            examples_for(&global.examples, "canonical_format", "300x250").len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_field_coverage_counted_per_ssp() {
+        let mut global = GlobalStats::new();
+        for ifa in ["a", "", "b"] {
+            let mut record = make_record(300, 250, false, 0.0);
+            record.request["source"] = serde_json::json!({ "ssp": "ssp_a" });
+            record.request["device"] = serde_json::json!({ "ifa": ifa });
+            process_record_global(&record, &mut global);
+        }
+        let count = |field| global.by_field.get(&("ssp_a".to_string(), field)).copied();
+        assert_eq!(count("total"), Some(3));
+        assert_eq!(count("device.ifa"), Some(2));
+        assert_eq!(count("user.eids"), None);
+    }
+
+    #[test]
+    fn test_dsp_stats() {
+        let mut global = GlobalStats::new();
+        for (dsp, with_bid) in [
+            ("dsp_a", true),
+            ("dsp_a", false),
+            ("dsp_b", false),
+            ("", true),
+        ] {
+            let mut record = make_record(300, 250, with_bid, 1.0);
+            record.request["source"] = serde_json::json!({ "ssp": "ssp_a", "dsp": dsp });
+            process_record_global(&record, &mut global);
+        }
+        assert_eq!(global.by_dsp.len(), 2);
+        assert_eq!(global.by_dsp["dsp_a"].requests, 2);
+        assert_eq!(global.by_dsp["dsp_a"].bids, 1);
+        assert_eq!(
+            global.by_dsp_format[&("dsp_b".to_string(), (300, 250))].bids,
+            0
+        );
+        assert_eq!(global.by_ssp["ssp_a"].requests, 4);
+    }
+
+    #[test]
+    fn test_publisher_segment_cross() {
+        let mut global = GlobalStats::new();
+        for (publisher, segment, with_bid) in [
+            (Some("pub1"), Some("sports"), true),
+            (Some("pub1"), Some("sports"), false),
+            (Some("pub1"), Some("news"), false),
+            (Some("pub2"), Some("sports"), true),
+            (None, Some("sports"), true),
+            (Some("pub1"), None, true),
+        ] {
+            let mut record = make_record(300, 250, with_bid, 1.0);
+            record.request["source"] = serde_json::json!({ "ssp": "ssp_a" });
+            if let Some(publisher) = publisher {
+                record.request["site"] = serde_json::json!({ "publisher": { "id": publisher } });
+            }
+            if let Some(segment) = segment {
+                record.request["user"] =
+                    serde_json::json!({ "data": [{ "segment": [{ "id": segment }] }] });
+            }
+            process_record_global(&record, &mut global);
+        }
+
+        let cell = |publisher: &str, segment: &str| {
+            let key = PublisherSegmentKey {
+                ssp: "ssp_a".to_string(),
+                publisher_id: publisher.to_string(),
+                segment: segment.to_string(),
+            };
+            global
+                .by_publisher_segment
+                .get(&key)
+                .map(|s| (s.requests, s.bids))
+        };
+        // Only records carrying both a publisher and a segment count
+        assert_eq!(global.by_publisher_segment.len(), 3);
+        assert_eq!(cell("pub1", "sports"), Some((2, 1)));
+        assert_eq!(cell("pub1", "news"), Some((1, 0)));
+        assert_eq!(cell("pub2", "sports"), Some((1, 1)));
+    }
+
+    #[test]
+    fn test_group_by_skips_other_views() {
+        let mut global = GlobalStats::new();
+        global.group_by = GroupBy::parse("geo").unwrap();
+        let mut record = make_record(300, 250, true, 1.0);
+        record.request["source"] = serde_json::json!({ "ssp": "ssp_a" });
+        record.request["site"] = serde_json::json!({ "publisher": { "id": "pub1" } });
+        record.request["device"] = serde_json::json!({ "geo": { "country": "se" } });
+        record.ts_ms = Some(1_000);
+        process_record_global(&record, &mut global);
+
+        assert_eq!(global.by_canonical_format[&(300, 250)].requests, 1);
+        assert_eq!(global.by_country["SE"].bids, 1);
+        assert!(global.by_ssp.is_empty());
+        assert!(global.by_publisher.is_empty());
+        assert!(global.time_stats.is_empty());
+        assert_eq!(global.prices.bids(), 0);
+    }
+
+    #[test]
+    fn test_exclude_test_traffic() {
+        for exclude_test in [false, true] {
+            let mut global = GlobalStats::new();
+            global.exclude_test = exclude_test;
+            for test in [0, 1, 1] {
+                let mut record = make_record(300, 250, true, 2.0);
+                record.request["source"] = serde_json::json!({ "ssp": "ssp_a" });
+                record.request["test"] = serde_json::json!(test);
+                process_record_global(&record, &mut global);
+            }
+            let test = &global.test_traffic["ssp_a"];
+            assert_eq!((test.requests, test.bids), (2, 2));
+            assert_eq!(global.total_requests(), if exclude_test { 1 } else { 3 });
+            assert_eq!(global.by_ssp["ssp_a"].requests, global.total_requests());
+        }
+    }
+}
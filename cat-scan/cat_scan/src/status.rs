@@ -0,0 +1,249 @@
+use std::{collections::BTreeMap, io::Write};
+
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::metadata::{create_csv, ScanMetadata};
+use crate::stats::scale_count;
+
+/// Response status buckets, in display order. "bid" and "no_bid" are
+/// answers; the rest are bidder errors.
+pub const STATUS_BUCKETS: [&str; 6] = ["bid", "no_bid", "4xx", "5xx", "timeout", "error"];
+
+/// Index of the first error bucket in STATUS_BUCKETS
+const FIRST_ERROR: usize = 2;
+
+/// Requests per STATUS_BUCKETS entry
+type StatusCounts = [u64; STATUS_BUCKETS.len()];
+
+/// The status bucket of a response, from the log line's `http_status` or
+/// else the response's own `status` / `error` fields; None when the line
+/// carries neither. A 2xx without a bid (204 included) is "no_bid", status
+/// 0 or an error mentioning a timeout is "timeout".
+pub fn status_bucket(http_status: &Value, response: &Value, has_bid: bool) -> Option<&'static str> {
+    let code = |code: u64| match code {
+        0 => "timeout",
+        204 => "no_bid",
+        200..=299 if has_bid => "bid",
+        200..=299 => "no_bid",
+        400..=499 => "4xx",
+        500..=599 => "5xx",
+        _ => "error",
+    };
+    let status = |value: &Value| match value {
+        Value::Number(n) => Some(n.as_u64().map_or("error", code)),
+        Value::String(s) => Some(match s.trim().parse() {
+            Ok(n) => code(n),
+            Err(_) if is_timeout(s) => "timeout",
+            Err(_) => "error",
+        }),
+        _ => None,
+    };
+    if let Some(bucket) = status(http_status) {
+        return Some(bucket);
+    }
+    match &response["error"] {
+        Value::Null => status(&response["status"]),
+        Value::String(error) if is_timeout(error) => Some("timeout"),
+        _ => Some(status(&response["status"]).unwrap_or("error")),
+    }
+}
+
+fn is_timeout(text: &str) -> bool {
+    let text = text.to_ascii_lowercase();
+    text.contains("timeout") || text.contains("timed out")
+}
+
+/// Response statuses per SSP and per time bucket, for the records whose
+/// log lines carry one (see status_bucket)
+#[derive(Debug, Default, Clone)]
+pub struct StatusStats {
+    pub by_ssp: BTreeMap<String, StatusCounts>,
+    /// Keyed by time bucket start (epoch ms)
+    pub by_time: BTreeMap<u64, StatusCounts>,
+}
+
+impl StatusStats {
+    pub fn observe(&mut self, ssp: &str, time_bucket: Option<u64>, bucket: &str) {
+        let Some(i) = STATUS_BUCKETS.iter().position(|b| *b == bucket) else {
+            return;
+        };
+        self.by_ssp.entry(ssp.to_string()).or_default()[i] += 1;
+        if let Some(start) = time_bucket {
+            self.by_time.entry(start).or_default()[i] += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: &StatusStats) {
+        for (ssp, counts) in &other.by_ssp {
+            add(self.by_ssp.entry(ssp.clone()).or_default(), counts);
+        }
+        for (start, counts) in &other.by_time {
+            add(self.by_time.entry(*start).or_default(), counts);
+        }
+    }
+
+    /// Multiply counts by `factor` (extrapolating from a sample)
+    pub fn scale(&mut self, factor: f64) {
+        for counts in self.by_ssp.values_mut().chain(self.by_time.values_mut()) {
+            for count in counts.iter_mut() {
+                scale_count(count, factor);
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_ssp.is_empty()
+    }
+
+    /// An "(all)" row then SSPs by volume, and the time buckets in order
+    pub fn summarize(&self) -> StatusReport {
+        let mut total = StatusCounts::default();
+        for counts in self.by_ssp.values() {
+            add(&mut total, counts);
+        }
+        let mut ssps: Vec<StatusSummary> = self
+            .by_ssp
+            .iter()
+            .map(|(ssp, counts)| StatusSummary::new(ssp.clone(), None, counts))
+            .collect();
+        ssps.sort_by(|a, b| b.requests.cmp(&a.requests).then_with(|| a.ssp.cmp(&b.ssp)));
+        ssps.insert(0, StatusSummary::new("(all)".to_string(), None, &total));
+        StatusReport {
+            ssps,
+            time_series: self
+                .by_time
+                .iter()
+                .map(|(start, counts)| StatusSummary::new(String::new(), Some(*start), counts))
+                .collect(),
+        }
+    }
+}
+
+fn add(counts: &mut StatusCounts, other: &StatusCounts) {
+    for (count, other) in counts.iter_mut().zip(other) {
+        *count += other;
+    }
+}
+
+/// Status shares per SSP and over time
+#[derive(serde::Serialize)]
+pub struct StatusReport {
+    pub ssps: Vec<StatusSummary>,
+    /// Every SSP together, per time bucket (only when lines carry
+    /// timestamps)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub time_series: Vec<StatusSummary>,
+}
+
+/// Response statuses of one SSP or time bucket
+#[derive(serde::Serialize)]
+pub struct StatusSummary {
+    #[serde(skip_serializing_if = "String::is_empty")]
+    pub ssp: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ts_ms: Option<u64>,
+    /// Requests with a known status
+    pub requests: u64,
+    /// Requests per STATUS_BUCKETS entry
+    pub counts: BTreeMap<&'static str, u64>,
+    /// Answered without a bid
+    pub no_bid_share: f64,
+    /// Failed: 4xx, 5xx, timeout or another error
+    pub error_share: f64,
+}
+
+impl StatusSummary {
+    fn new(ssp: String, ts_ms: Option<u64>, counts: &StatusCounts) -> Self {
+        let requests: u64 = counts.iter().sum();
+        let share = |n: u64| {
+            if requests == 0 {
+                0.0
+            } else {
+                n as f64 / requests as f64
+            }
+        };
+        Self {
+            ssp,
+            ts_ms,
+            requests,
+            counts: STATUS_BUCKETS
+                .iter()
+                .copied()
+                .zip(counts.iter().copied())
+                .collect(),
+            no_bid_share: share(counts[1]),
+            error_share: share(counts[FIRST_ERROR..].iter().sum()),
+        }
+    }
+}
+
+/// Write response_status.csv (status shares per SSP, then per time bucket)
+pub fn write_status_csv(out_dir: &str, report: &StatusReport, meta: &ScanMetadata) -> Result<()> {
+    let path = format!("{}/response_status.csv", out_dir);
+    let mut csv = create_csv(&path, meta)?;
+    writeln!(
+        csv,
+        "ssp,ts_ms,requests,{},no_bid_share,error_share",
+        STATUS_BUCKETS.join(",")
+    )?;
+    for r in report.ssps.iter().chain(&report.time_series) {
+        let counts: Vec<String> = STATUS_BUCKETS
+            .iter()
+            .map(|bucket| r.counts[bucket].to_string())
+            .collect();
+        writeln!(
+            csv,
+            "{},{},{},{},{:.4},{:.4}",
+            r.ssp,
+            r.ts_ms.map_or(String::new(), |ts| ts.to_string()),
+            r.requests,
+            counts.join(","),
+            r.no_bid_share,
+            r.error_share
+        )?;
+    }
+    eprintln!("Response statuses written to: {}", path);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_status_bucket() {
+        let line = |status: Value, has_bid| status_bucket(&status, &Value::Null, has_bid);
+        assert_eq!(line(json!(200), true), Some("bid"));
+        assert_eq!(line(json!(200), false), Some("no_bid"));
+        assert_eq!(line(json!(204), true), Some("no_bid"));
+        assert_eq!(line(json!("503"), false), Some("5xx"));
+        assert_eq!(line(json!(0), false), Some("timeout"));
+        assert_eq!(line(json!("timeout"), false), Some("timeout"));
+        assert_eq!(line(Value::Null, false), None);
+
+        let response = |response: Value| status_bucket(&Value::Null, &response, false);
+        assert_eq!(response(json!({ "status": 400 })), Some("4xx"));
+        assert_eq!(
+            response(json!({ "error": "Request timed out" })),
+            Some("timeout")
+        );
+        assert_eq!(
+            response(json!({ "error": "bad gateway", "status": 502 })),
+            Some("5xx")
+        );
+        assert_eq!(response(json!({ "error": { "code": 7 } })), Some("error"));
+        assert_eq!(response(json!({ "seatbid": [] })), None);
+
+        let mut stats = StatusStats::default();
+        for bucket in ["bid", "no_bid", "no_bid", "5xx"] {
+            stats.observe("ssp_a", Some(0), bucket);
+        }
+        let report = stats.summarize();
+        assert_eq!(report.ssps[0].ssp, "(all)");
+        assert_eq!(report.ssps[1].no_bid_share, 0.5);
+        assert_eq!(report.ssps[1].error_share, 0.25);
+        assert_eq!(report.time_series[0].requests, 4);
+    }
+}
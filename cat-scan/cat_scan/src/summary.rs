@@ -0,0 +1,180 @@
+use anyhow::{Context, Result};
+
+use crate::metadata::ScanMetadata;
+use crate::qps::ALL_SSPS;
+use crate::report::HtmlReportData;
+
+/// Bid rate at which the dashboard counts a format as healthy
+const HEALTHY_BID_RATE: f64 = 0.1;
+
+/// Headline KPIs of a scan: the figures on the dashboard of report.html
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct ScanSummary {
+    pub source: String,
+    pub requests: u64,
+    pub bids: u64,
+    pub bid_rate: f64,
+    /// Requests for formats that never got a bid
+    pub wasted_requests: u64,
+    /// wasted_requests / requests
+    pub wasted_share: f64,
+    pub zero_bid_formats: usize,
+    /// Formats bidding on at least HEALTHY_BID_RATE of their requests
+    pub healthy_formats: usize,
+    pub problem_formats: usize,
+    /// Malformed lines skipped under --on-error skip
+    pub parse_errors: u64,
+    /// parse_errors / (parsed requests + parse_errors)
+    pub parse_error_share: f64,
+    /// Fraction of input lines parsed (only with --sample / --sample-every)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sample_rate: Option<f64>,
+    /// Requests flagged test=1, included in the counts above unless
+    /// --exclude-test (only when there were any)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub test_requests: Option<u64>,
+    /// Average and peak requests per second over the scan (needs timestamps)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avg_qps: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub peak_qps: Option<u64>,
+    /// --alert-rule alerts raised (only when there are rules)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alerts: Option<usize>,
+    /// Requests with a seller id their sellers.json doesn't list, and their
+    /// share of all requests (only with --sellers-json)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unauthorized_requests: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unauthorized_share: Option<f64>,
+}
+
+impl ScanSummary {
+    pub fn from_report(report: &HtmlReportData) -> Self {
+        let rate = |n: u64, d: u64| if d == 0 { 0.0 } else { n as f64 / d as f64 };
+        let requests: u64 = report.formats.iter().map(|f| f.requests).sum();
+        let bids: u64 = report.formats.iter().map(|f| f.bids).sum();
+        let zero_bid: Vec<_> = report.formats.iter().filter(|f| f.bids == 0).collect();
+        let wasted_requests: u64 = zero_bid.iter().map(|f| f.requests).sum();
+        let parse_errors = report.skipped_lines.as_ref().map_or(0, |s| s.count);
+        let qps = report.qps.iter().find(|q| q.ssp == ALL_SSPS);
+        let supply = report
+            .unauthorized_supply
+            .as_ref()
+            .and_then(|s| s.ssps.iter().find(|s| s.ssp == ALL_SSPS));
+        Self {
+            source: report.source.clone(),
+            requests,
+            bids,
+            bid_rate: rate(bids, requests),
+            wasted_requests,
+            wasted_share: rate(wasted_requests, requests),
+            zero_bid_formats: zero_bid.len(),
+            healthy_formats: report
+                .formats
+                .iter()
+                .filter(|f| f.bid_rate >= HEALTHY_BID_RATE)
+                .count(),
+            problem_formats: report.problems.len(),
+            parse_errors,
+            parse_error_share: rate(parse_errors, report.total_requests + parse_errors),
+            sample_rate: report.sample_rate,
+            test_requests: report.test_traffic.as_ref().map(|t| t.requests),
+            avg_qps: qps.map(|q| q.avg_qps),
+            peak_qps: qps.map(|q| q.peak_qps),
+            alerts: report.alerts.as_ref().map(Vec::len),
+            unauthorized_requests: supply.map(|s| s.unauthorized),
+            unauthorized_share: supply.map(|s| s.unauthorized_share),
+        }
+    }
+}
+
+/// summary.json: the KPIs next to the scan metadata
+#[derive(serde::Serialize)]
+struct SummaryFile<'a> {
+    #[serde(flatten)]
+    summary: ScanSummary,
+    metadata: &'a ScanMetadata,
+}
+
+/// Write summary.json (the report dashboard KPIs, for automation)
+pub fn write_summary_json(out_dir: &str, report: &HtmlReportData) -> Result<()> {
+    let path = format!("{}/summary.json", out_dir);
+    let file = SummaryFile {
+        summary: ScanSummary::from_report(report),
+        metadata: &report.metadata,
+    };
+    let json =
+        serde_json::to_string_pretty(&file).context("Failed to serialize summary to JSON")?;
+    std::fs::write(&path, json).with_context(|| format!("Failed to write {}", path))?;
+    Ok(())
+}
+
+/// 1234567 -> "1,234,567"
+pub fn thousands(n: u64) -> String {
+    let digits = n.to_string();
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+/// 0.125 -> "12.5%"
+pub fn percent(rate: f64) -> String {
+    format!("{:.1}%", rate * 100.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::{parse_scan_args, SortBy};
+    use crate::input::SkippedLines;
+    use crate::report::{build_format_summaries, build_report_data};
+    use crate::stats::{process_record_global, tests::make_record, GlobalStats};
+
+    #[test]
+    fn test_summary_json() {
+        let mut global = GlobalStats::new();
+        for (w, with_bid, ts_ms) in [
+            (300, true, 90_000),
+            (300, false, 90_500),
+            (728, false, 92_000),
+        ] {
+            let mut record = make_record(w, if w == 300 { 250 } else { 90 }, with_bid, 1.5);
+            record.ts_ms = Some(ts_ms);
+            process_record_global(&record, &mut global);
+        }
+        let config = parse_scan_args("logs.jsonl".into(), &[]).unwrap();
+        let summaries = build_format_summaries(&global, 0, SortBy::Format);
+        let report =
+            build_report_data(&global, &config, &summaries, None, &SkippedLines::default());
+
+        let dir = std::env::temp_dir().join(format!("cat_scan_summary_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        write_summary_json(dir.to_str().unwrap(), &report).unwrap();
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("summary.json")).unwrap())
+                .unwrap();
+        assert_eq!(json["requests"], 3);
+        assert_eq!(json["bids"], 1);
+        assert_eq!(json["zero_bid_formats"], 1);
+        assert_eq!(json["healthy_formats"], 1);
+        assert_eq!(json["peak_qps"], 2);
+        assert!((json["wasted_share"].as_f64().unwrap() - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(json["metadata"]["inputs"][0], "logs.jsonl");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_thousands_and_percent() {
+        assert_eq!(thousands(0), "0");
+        assert_eq!(thousands(999), "999");
+        assert_eq!(thousands(1_000), "1,000");
+        assert_eq!(thousands(1_234_567), "1,234,567");
+        assert_eq!(percent(0.125), "12.5%");
+    }
+}
@@ -0,0 +1,189 @@
+use console::{pad_str, style, Alignment, Color};
+
+use crate::report::FormatSummary;
+use crate::summary::{percent, thousands};
+
+/// Requests a format needs before a low bid rate earns it a badge, as in
+/// the report's format table
+const BADGE_MIN_REQUESTS: u64 = 10;
+
+/// Status badge of a format, with the report's thresholds: STOP for a
+/// format that never got a bid, Low under 5%, Review under 20%, Good from
+/// 50%
+pub fn status_badge(bid_rate: f64, requests: u64) -> Option<(&'static str, Color)> {
+    if bid_rate == 0.0 && requests > BADGE_MIN_REQUESTS {
+        Some(("STOP", Color::Red))
+    } else if bid_rate < 0.05 && requests > BADGE_MIN_REQUESTS {
+        Some(("Low", Color::Red))
+    } else if bid_rate < 0.2 {
+        Some(("Review", Color::Yellow))
+    } else if bid_rate >= 0.5 {
+        Some(("Good", Color::Green))
+    } else {
+        None
+    }
+}
+
+/// Heat color of a bid rate: red when the format barely bids, yellow
+/// under 20%, green from 50%
+fn heat(bid_rate: f64) -> Option<Color> {
+    if bid_rate < 0.05 {
+        Some(Color::Red)
+    } else if bid_rate < 0.2 {
+        Some(Color::Yellow)
+    } else if bid_rate >= 0.5 {
+        Some(Color::Green)
+    } else {
+        None
+    }
+}
+
+/// Canonical format rows as an aligned table for a terminal (--format
+/// table, the default when stdout is one), bid rates heat-colored and
+/// formats badged when `color` is set
+pub fn render_format_table(summaries: &[FormatSummary], color: bool) -> String {
+    let header = [
+        "format",
+        "requests",
+        "bids",
+        "bid_rate",
+        "avg_bid_price",
+        "status",
+    ];
+    let rows: Vec<[String; 6]> = summaries
+        .iter()
+        .map(|s| {
+            [
+                format!("{}x{}", s.w, s.h),
+                thousands(s.requests),
+                thousands(s.bids),
+                percent(s.bid_rate),
+                format!("{:.4}", s.avg_bid_price),
+                status_badge(s.bid_rate, s.requests)
+                    .map(|(badge, _)| badge.to_string())
+                    .unwrap_or_default(),
+            ]
+        })
+        .collect();
+    let widths: Vec<usize> = (0..header.len())
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].len())
+                .chain([header[i].len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+    // Text columns left, numbers right
+    let align = |i: usize| match i {
+        0 | 5 => Alignment::Left,
+        _ => Alignment::Right,
+    };
+
+    let mut lines = vec![style(
+        header
+            .iter()
+            .enumerate()
+            .map(|(i, h)| pad_str(h, widths[i], align(i), None).into_owned())
+            .collect::<Vec<_>>()
+            .join("  "),
+    )
+    .bold()
+    .force_styling(color)
+    .to_string()];
+    for (s, row) in summaries.iter().zip(&rows) {
+        let cells: Vec<String> = row
+            .iter()
+            .enumerate()
+            .map(|(i, text)| {
+                // The status column is last: no padding to trail the line
+                let padded = if i == 5 {
+                    text.clone()
+                } else {
+                    pad_str(text, widths[i], align(i), None).into_owned()
+                };
+                let color_of = match i {
+                    3 => heat(s.bid_rate),
+                    5 => status_badge(s.bid_rate, s.requests).map(|(_, c)| c),
+                    _ => None,
+                };
+                match color_of {
+                    Some(c) => {
+                        let styled = style(padded).fg(c).force_styling(color);
+                        if i == 5 && text == "STOP" {
+                            styled.bold().reverse().to_string()
+                        } else {
+                            styled.to_string()
+                        }
+                    }
+                    None => padded,
+                }
+            })
+            .collect();
+        lines.push(cells.join("  ").trim_end().to_string());
+    }
+
+    let requests: u64 = summaries.iter().map(|s| s.requests).sum();
+    let bids: u64 = summaries.iter().map(|s| s.bids).sum();
+    let stop = rows.iter().filter(|row| row[5] == "STOP").count();
+    lines.push(String::new());
+    lines.push(format!(
+        "{} formats, {} requests, {} bid rate{}",
+        summaries.len(),
+        thousands(requests),
+        percent(if requests == 0 {
+            0.0
+        } else {
+            bids as f64 / requests as f64
+        }),
+        if stop > 0 {
+            format!(", {} to stop listening to", stop)
+        } else {
+            String::new()
+        }
+    ));
+    lines.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_format_table() {
+        let format = |w, h, requests, bids, avg_bid_price| FormatSummary {
+            w,
+            h,
+            requests,
+            bids,
+            bid_rate: bids as f64 / requests as f64,
+            avg_bid_price,
+            median_bid_price: avg_bid_price,
+            p90_bid_price: avg_bid_price,
+            win_rate: None,
+            revenue: None,
+        };
+        let summaries = vec![
+            format(300, 250, 12000, 7200, 1.25),
+            format(728, 90, 400, 0, 0.0),
+            format(320, 50, 50, 5, 0.8),
+        ];
+        let table = render_format_table(&summaries, false);
+        assert_eq!(
+            table,
+            "format   requests   bids  bid_rate  avg_bid_price  status\n\
+             300x250    12,000  7,200     60.0%         1.2500  Good\n\
+             728x90        400      0      0.0%         0.0000  STOP\n\
+             320x50         50      5     10.0%         0.8000  Review\n\
+             \n\
+             3 formats, 12,450 requests, 57.9% bid rate, 1 to stop listening to\n"
+        );
+
+        let colored = render_format_table(&summaries, true);
+        assert!(colored.contains("\x1b["));
+        assert_eq!(console::strip_ansi_codes(&colored), table);
+
+        assert_eq!(status_badge(0.0, 5), Some(("Review", Color::Yellow)));
+        assert_eq!(status_badge(0.3, 100), None);
+    }
+}
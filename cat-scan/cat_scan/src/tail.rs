@@ -0,0 +1,174 @@
+use std::{
+    fmt::Write as _,
+    io::{IsTerminal, Write},
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+
+use crate::cli::{SortBy, TailConfig};
+use crate::input::follow_input;
+use crate::live::{now_ms, ratio, RollingWindow};
+use crate::problems::{find_problem_formats, Detectors};
+use crate::report::build_format_summaries;
+
+/// Minimum requests before a format is listed as a problem
+const TAIL_PROBLEM_THRESHOLD: u64 = 10;
+
+/// Render the plain-text summary printed by `cat_scan tail`
+pub fn render_tail_summary(window: &RollingWindow, config: &TailConfig, now_ms: u64) -> String {
+    let mut out = String::new();
+    let secs_of_day = (now_ms / 1000) % 86400;
+    let _ = writeln!(
+        out,
+        "{} | {:02}:{:02}:{:02} UTC | {} lines read, {} parse errors",
+        config.input_path,
+        secs_of_day / 3600,
+        (secs_of_day / 60) % 60,
+        secs_of_day % 60,
+        window.lines_read,
+        window.parse_errors
+    );
+
+    let _ = writeln!(
+        out,
+        "\n{:<8} {:>12} {:>12} {:>9} {:>10}",
+        "window", "requests", "bids", "bid_rate", "qps"
+    );
+    for &minutes in &config.windows_minutes {
+        let stats = window.merged_last(minutes, now_ms);
+        let requests = stats.total_requests();
+        let bids = stats.total_bids();
+        let _ = writeln!(
+            out,
+            "{:<8} {:>12} {:>12} {:>8.2}% {:>10.1}",
+            format!("{}m", minutes),
+            requests,
+            bids,
+            ratio(bids, requests) * 100.0,
+            requests as f64 / window.covered_secs(minutes, now_ms)
+        );
+    }
+
+    // Detail tables for the shortest window: what is happening right now
+    let minutes = config.windows_minutes[0];
+    let stats = window.merged_last(minutes, now_ms);
+    let formats = build_format_summaries(&stats, 0, SortBy::RequestsDesc);
+    let _ = writeln!(out, "\nTop formats (last {}m):", minutes);
+    let _ = writeln!(
+        out,
+        "{:<12} {:>12} {:>12} {:>9}",
+        "format", "requests", "bids", "bid_rate"
+    );
+    for f in formats.iter().take(config.top) {
+        let _ = writeln!(
+            out,
+            "{:<12} {:>12} {:>12} {:>8.2}%",
+            format!("{}x{}", f.w, f.h),
+            f.requests,
+            f.bids,
+            f.bid_rate * 100.0
+        );
+    }
+
+    let problems = find_problem_formats(&stats, &Detectors::default(), TAIL_PROBLEM_THRESHOLD);
+    if !problems.is_empty() {
+        let _ = writeln!(out, "\nProblems (last {}m):", minutes);
+        for p in problems.iter().take(config.top) {
+            let _ = writeln!(
+                out,
+                "{:<12} {:>12} req {:>8.2}%  {}",
+                format!("{}x{}", p.w, p.h),
+                p.requests,
+                p.bid_rate * 100.0,
+                p.problem_type
+            );
+        }
+    }
+    out
+}
+
+/// `cat_scan tail`: follow a log file and periodically print rolling-window
+/// stats. Redraws in place when stdout is a terminal, appends otherwise.
+pub fn run_tail(config: TailConfig) -> Result<()> {
+    let longest = *config.windows_minutes.iter().max().unwrap_or(&60);
+    let window = Arc::new(Mutex::new(RollingWindow::new(longest * 60)));
+
+    let follower_window = Arc::clone(&window);
+    let path = config.input_path.clone();
+    let from_start = config.from_start;
+    let follower = thread::spawn(move || {
+        follow_input(&path, from_start, Duration::from_millis(250), |line| {
+            let mut window = follower_window.lock().unwrap_or_else(|e| e.into_inner());
+            window.observe_line(line, now_ms());
+        })
+    });
+
+    let redraw = std::io::stdout().is_terminal();
+    loop {
+        thread::sleep(Duration::from_secs(config.interval_secs));
+        if follower.is_finished() {
+            return match follower.join() {
+                Ok(result) => result,
+                Err(_) => bail!("Log follower thread panicked"),
+            };
+        }
+
+        let summary = {
+            let mut window = window.lock().unwrap_or_else(|e| e.into_inner());
+            let now = now_ms();
+            window.evict(now);
+            render_tail_summary(&window, &config, now)
+        };
+        let mut stdout = std::io::stdout().lock();
+        if redraw {
+            // Clear screen and move the cursor home
+            write!(stdout, "\x1b[2J\x1b[H")?;
+        } else {
+            writeln!(stdout)?;
+        }
+        write!(stdout, "{}", summary)?;
+        stdout.flush()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_tail_summary() {
+        let now = 100 * 60000 + 30000;
+        let mut window = RollingWindow::new(60 * 60);
+        for (age_min, w) in [(0, 300), (0, 123), (10, 300), (30, 728)] {
+            let line = serde_json::json!({
+                "ts_ms": now - age_min * 60000,
+                "request": {"imp": [{"banner": {"w": w, "h": 250}}]},
+                "response": {"seatbid": []},
+            });
+            window.observe_line(&line.to_string(), now);
+        }
+        let config = TailConfig {
+            input_path: "bids.jsonl".to_string(),
+            windows_minutes: vec![5, 15, 60],
+            interval_secs: 5,
+            from_start: false,
+            top: 10,
+        };
+
+        let text = render_tail_summary(&window, &config, now);
+        let row = |prefix: &str| {
+            text.lines()
+                .find(|l| l.starts_with(prefix))
+                .map(|l| l.split_whitespace().nth(1).unwrap().to_string())
+        };
+        assert_eq!(row("5m").as_deref(), Some("2"));
+        assert_eq!(row("15m").as_deref(), Some("3"));
+        assert_eq!(row("60m").as_deref(), Some("4"));
+        assert!(text.contains("Top formats (last 5m):"));
+        assert!(text.contains("123x250"));
+        assert!(!text.contains("728x250"));
+    }
+}
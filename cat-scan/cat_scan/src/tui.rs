@@ -0,0 +1,684 @@
+use std::{
+    cmp::Ordering,
+    collections::BTreeMap,
+    fmt, fs,
+    io::{self, IsTerminal},
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    layout::{Alignment, Constraint, Layout},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{self, Block, Borders, Cell, Paragraph, TableState, Tabs},
+    Frame,
+};
+use serde::de::{Deserialize, Deserializer, MapAccess, Visitor};
+use serde_json::Value;
+
+use crate::cli::TuiConfig;
+
+/// report.json tables shown as tabs, in order: (key, title)
+const TABS: &[(&str, &str)] = &[
+    ("formats", "Formats"),
+    ("ssps", "SSPs"),
+    ("publishers", "Publishers"),
+    ("segments", "Segments"),
+    ("publisher_segments", "Publisher segments"),
+    ("secure", "Secure"),
+    ("consent", "Consent"),
+    ("countries", "Geo"),
+    ("categories", "Categories"),
+    ("inventory", "Inventory"),
+    ("positions", "Position"),
+    ("identity", "Identity"),
+    ("field_coverage", "Field coverage"),
+    ("floors", "Floors"),
+    ("seats", "Seats"),
+    ("creatives", "Creatives"),
+    ("adm_audit", "Markup"),
+    ("duplicate_ids", "Duplicates"),
+    ("fingerprints", "Fingerprints"),
+    ("qps", "QPS"),
+    ("time_series", "Time"),
+    ("problems", "Problems"),
+    ("recommendations", "Recommendations"),
+];
+
+/// Fields a drill-down narrows on, when the selected row has them
+const DRILL_FIELDS: &[&str] = &[
+    "ssp",
+    "publisher_id",
+    "segment",
+    "w",
+    "h",
+    "seat",
+    "country",
+    "category",
+];
+
+/// Widest a column gets; longer cells are cut
+const MAX_COLUMN_WIDTH: usize = 40;
+
+/// Lines around the table: title, tab bar and its rule, table header, status
+const CHROME_HEIGHT: u16 = 5;
+
+const HELP: &str =
+    "Tab/[ ] table  \u{2191}\u{2193} row  \u{2190}\u{2192} sort column  r reverse  Enter drill down  Esc back  q quit";
+
+/// A report row, with its fields in report.json order (serde_json's `Map`
+/// would sort them, and the column order is part of the report)
+#[derive(Debug, Clone)]
+struct Row(Vec<(String, Value)>);
+
+static NULL: Value = Value::Null;
+
+impl Row {
+    fn get(&self, name: &str) -> Option<&Value> {
+        self.0.iter().find(|(n, _)| n == name).map(|(_, v)| v)
+    }
+
+    /// The field, or null when the row doesn't have it
+    fn field(&self, name: &str) -> &Value {
+        self.get(name).unwrap_or(&NULL)
+    }
+}
+
+impl<'de> Deserialize<'de> for Row {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        struct RowVisitor;
+
+        impl<'de> Visitor<'de> for RowVisitor {
+            type Value = Row;
+
+            fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str("a report row")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(
+                self,
+                mut map: A,
+            ) -> std::result::Result<Row, A::Error> {
+                let mut fields = Vec::new();
+                while let Some(field) = map.next_entry()? {
+                    fields.push(field);
+                }
+                Ok(Row(fields))
+            }
+        }
+
+        deserializer.deserialize_map(RowVisitor)
+    }
+}
+
+/// A top-level report.json field: a table of rows, or anything else
+#[derive(Debug, serde::Deserialize)]
+#[serde(untagged)]
+enum Section {
+    Table(Vec<Row>),
+    Other(Value),
+}
+
+type Report = BTreeMap<String, Section>;
+
+/// One report table: its scalar columns and the rows as in report.json
+struct Table {
+    title: &'static str,
+    columns: Vec<String>,
+    rows: Vec<Row>,
+    /// Sort column and whether it is descending; none keeps report order
+    sort: Option<(usize, bool)>,
+    /// Selected row and scroll position
+    state: TableState,
+}
+
+impl Table {
+    fn new(title: &'static str, rows: Vec<Row>) -> Self {
+        let mut columns: Vec<String> = Vec::new();
+        for row in &rows {
+            for (name, value) in &row.0 {
+                if !value.is_array() && !value.is_object() && !columns.contains(name) {
+                    columns.push(name.clone());
+                }
+            }
+        }
+        Self {
+            title,
+            columns,
+            rows,
+            sort: None,
+            state: TableState::new().with_selected(0),
+        }
+    }
+
+    fn selected(&self) -> usize {
+        self.state.selected().unwrap_or_default()
+    }
+
+    fn is_numeric(&self, column: usize) -> bool {
+        self.rows
+            .iter()
+            .find_map(|row| row.get(&self.columns[column]).filter(|v| !v.is_null()))
+            .is_some_and(Value::is_number)
+    }
+
+    /// Sort by `column`: numbers busiest first, text A to Z
+    fn sort_by(&mut self, column: usize) {
+        let descending = self.is_numeric(column);
+        self.sort = Some((column, descending));
+        self.apply_sort();
+    }
+
+    fn reverse(&mut self) {
+        if let Some((column, descending)) = self.sort {
+            self.sort = Some((column, !descending));
+            self.apply_sort();
+        }
+    }
+
+    fn apply_sort(&mut self) {
+        let Some((column, descending)) = self.sort else {
+            return;
+        };
+        let name = &self.columns[column];
+        self.rows.sort_by(|a, b| {
+            let order = compare(a.field(name), b.field(name));
+            if descending {
+                order.reverse()
+            } else {
+                order
+            }
+        });
+        self.state.select(Some(0));
+    }
+
+    fn move_selection(&mut self, delta: isize) {
+        let last = self.rows.len().saturating_sub(1);
+        let row = self.selected().saturating_add_signed(delta).min(last);
+        self.state.select(Some(row));
+    }
+
+    /// The table widget: a bold header with the sort arrow, numbers right
+    /// aligned, each column as wide as its widest cell up to MAX_COLUMN_WIDTH
+    fn widget(&self) -> widgets::Table<'static> {
+        let numeric: Vec<bool> = (0..self.columns.len())
+            .map(|i| self.is_numeric(i))
+            .collect();
+        let line = |i: usize, text: String| {
+            let line = Line::from(text);
+            if numeric[i] {
+                line.alignment(Alignment::Right)
+            } else {
+                line
+            }
+        };
+        let headers: Vec<String> = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| match self.sort {
+                Some((column, true)) if column == i => format!("{c} \u{25bc}"),
+                Some((column, false)) if column == i => format!("{c} \u{25b2}"),
+                _ => c.clone(),
+            })
+            .collect();
+        let texts: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| self.columns.iter().map(|c| cell(c, row.field(c))).collect())
+            .collect();
+        let widths: Vec<Constraint> = (0..self.columns.len())
+            .map(|i| {
+                let width = texts
+                    .iter()
+                    .map(|row| Span::raw(row[i].as_str()).width())
+                    .chain([Span::raw(headers[i].as_str()).width()])
+                    .max()
+                    .unwrap_or(0)
+                    .min(MAX_COLUMN_WIDTH);
+                Constraint::Length(width as u16)
+            })
+            .collect();
+
+        let header = widgets::Row::new(
+            headers
+                .into_iter()
+                .enumerate()
+                .map(|(i, text)| Cell::from(line(i, text))),
+        )
+        .style(Style::new().add_modifier(Modifier::BOLD));
+        let rows = texts.into_iter().map(|row| {
+            widgets::Row::new(
+                row.into_iter()
+                    .enumerate()
+                    .map(|(i, text)| Cell::from(line(i, text))),
+            )
+        });
+        widgets::Table::new(rows, widths)
+            .header(header)
+            .column_spacing(2)
+            .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+    }
+}
+
+/// Numbers before text; missing fields (null) compare as text
+fn compare(a: &Value, b: &Value) -> Ordering {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(x), Some(y)) => x.total_cmp(&y),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.to_string().cmp(&b.to_string()),
+    }
+}
+
+/// How a report value shows in a cell: rates and shares as percentages
+fn cell(column: &str, value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_string(),
+        Value::Bool(b) => if *b { "yes" } else { "no" }.to_string(),
+        Value::Number(n) if n.is_f64() => {
+            let x = n.as_f64().unwrap_or_default();
+            if column.ends_with("rate") || column.ends_with("share") || column == "coverage" {
+                format!("{:.2}%", x * 100.0)
+            } else {
+                format!("{:.4}", x)
+            }
+        }
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// The fields a row can be drilled on or matched by. Rows keyed by a
+/// `dimension` and `key` pair (inventory, floors, creatives, ...) and a
+/// "300x250" `format` are unpacked into the fields other tables use.
+fn drill_fields(row: &Row) -> Vec<(String, Value)> {
+    let mut fields: Vec<(String, Value)> = DRILL_FIELDS
+        .iter()
+        .filter_map(|name| row.get(name).map(|v| (name.to_string(), v.clone())))
+        .collect();
+    let format = |text: &str| {
+        let (w, h) = text.split_once('x')?;
+        Some([
+            ("w".to_string(), Value::from(w.parse::<u64>().ok()?)),
+            ("h".to_string(), Value::from(h.parse::<u64>().ok()?)),
+        ])
+    };
+    if let Some(parsed) = row.get("format").and_then(Value::as_str).and_then(format) {
+        fields.extend(parsed);
+    }
+    if let (Some(dimension), Some(key)) = (
+        row.get("dimension").and_then(Value::as_str),
+        row.get("key").and_then(Value::as_str),
+    ) {
+        match dimension {
+            "format" => fields.extend(format(key).into_iter().flatten()),
+            "publisher" => match key.split_once('|') {
+                Some((ssp, publisher)) => {
+                    fields.push(("ssp".to_string(), Value::from(ssp)));
+                    fields.push(("publisher_id".to_string(), Value::from(publisher)));
+                }
+                None => fields.push(("publisher_id".to_string(), Value::from(key))),
+            },
+            other if DRILL_FIELDS.contains(&other) => {
+                fields.push((other.to_string(), Value::from(key)))
+            }
+            _ => {}
+        }
+    }
+    fields
+}
+
+/// A stack entry: the full report, or the rows of every table that match
+/// a drill-down selection
+struct View {
+    title: String,
+    tables: Vec<Table>,
+    tab: usize,
+}
+
+struct App {
+    header: String,
+    views: Vec<View>,
+    /// Shown instead of the key help until the next key
+    status: Option<String>,
+    /// Rows PgUp and PgDn move, as many as the last draw showed
+    page: usize,
+}
+
+impl App {
+    fn new(report: &Report) -> Result<Self> {
+        let tables: Vec<Table> = TABS
+            .iter()
+            .filter_map(|(key, title)| match report.get(*key) {
+                Some(Section::Table(rows)) if !rows.is_empty() => {
+                    Some(Table::new(title, rows.clone()))
+                }
+                _ => None,
+            })
+            .collect();
+        if tables.is_empty() {
+            bail!("the report has no tables to show");
+        }
+        let scalar = |key: &str| match report.get(key) {
+            Some(Section::Other(value)) => value.clone(),
+            _ => Value::Null,
+        };
+        let mut header = format!(
+            "{} | {} requests",
+            scalar("source").as_str().unwrap_or_default(),
+            scalar("total_requests").as_u64().unwrap_or_default()
+        );
+        if let Some(filter) = scalar("filter").as_str() {
+            header.push_str(&format!(" | --filter {filter}"));
+        }
+        Ok(Self {
+            header,
+            views: vec![View {
+                title: String::new(),
+                tables,
+                tab: 0,
+            }],
+            status: None,
+            page: 1,
+        })
+    }
+
+    fn view(&mut self) -> &mut View {
+        self.views
+            .last_mut()
+            .expect("the report view is never popped")
+    }
+
+    fn table(&mut self) -> &mut Table {
+        let view = self.view();
+        &mut view.tables[view.tab]
+    }
+
+    /// Push a view of the rows in every report table that match the
+    /// selected row's dimensions (an SSP's publishers, consent, floors, ...)
+    fn drill_down(&mut self) {
+        let table = self.table();
+        let Some(row) = table.rows.get(table.selected()) else {
+            return;
+        };
+        let selection = drill_fields(row);
+        if selection.is_empty() {
+            self.status = Some(format!("Nothing to drill into in {}", table.title));
+            return;
+        }
+        let tables: Vec<Table> = self.views[0]
+            .tables
+            .iter()
+            .filter_map(|table| {
+                let rows: Vec<Row> = table
+                    .rows
+                    .iter()
+                    .filter(|row| {
+                        let fields = drill_fields(row);
+                        selection.iter().all(|field| fields.contains(field))
+                    })
+                    .cloned()
+                    .collect();
+                (!rows.is_empty()).then(|| Table::new(table.title, rows))
+            })
+            .collect();
+        let title = selection
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, cell(name, value)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.views.push(View {
+            title,
+            tables,
+            tab: 0,
+        });
+    }
+
+    /// Apply a key press; false quits
+    fn handle(&mut self, key: KeyEvent) -> bool {
+        self.status = None;
+        let page = self.page.max(1) as isize;
+        match key.code {
+            KeyCode::Char('q') => return false,
+            KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => return false,
+            KeyCode::Esc | KeyCode::Backspace if self.views.len() > 1 => {
+                self.views.pop();
+            }
+            KeyCode::Tab | KeyCode::Char(']') => {
+                let view = self.view();
+                view.tab = (view.tab + 1) % view.tables.len();
+            }
+            KeyCode::BackTab | KeyCode::Char('[') => {
+                let view = self.view();
+                view.tab = (view.tab + view.tables.len() - 1) % view.tables.len();
+            }
+            KeyCode::Char(c @ '1'..='9') => {
+                let view = self.view();
+                let tab = c as usize - '1' as usize;
+                if tab < view.tables.len() {
+                    view.tab = tab;
+                }
+            }
+            KeyCode::Right | KeyCode::Left => {
+                let table = self.table();
+                let count = table.columns.len();
+                let column = match (table.sort, key.code == KeyCode::Right) {
+                    (None, true) => 0,
+                    (None, false) => count - 1,
+                    (Some((column, _)), true) => (column + 1) % count,
+                    (Some((column, _)), false) => (column + count - 1) % count,
+                };
+                table.sort_by(column);
+            }
+            KeyCode::Char('r') => self.table().reverse(),
+            KeyCode::Down | KeyCode::Char('j') => self.table().move_selection(1),
+            KeyCode::Up | KeyCode::Char('k') => self.table().move_selection(-1),
+            KeyCode::PageDown => self.table().move_selection(page),
+            KeyCode::PageUp => self.table().move_selection(-page),
+            KeyCode::Home => self.table().move_selection(isize::MIN),
+            KeyCode::End => self.table().move_selection(isize::MAX),
+            KeyCode::Enter => self.drill_down(),
+            _ => {}
+        }
+        true
+    }
+
+    /// Title with the drill-down path, tab bar, the current table and a
+    /// status line
+    fn draw(&mut self, frame: &mut Frame) {
+        let [title_area, tabs_area, table_area, status_area] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(2),
+            Constraint::Min(0),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+        self.page = frame.area().height.saturating_sub(CHROME_HEIGHT) as usize;
+
+        let mut title = format!("cat_scan | {}", self.header);
+        for drill in &self.views[1..] {
+            title.push_str(&format!(" > {}", drill.title));
+        }
+        frame.render_widget(
+            Paragraph::new(title).style(Style::new().add_modifier(Modifier::BOLD)),
+            title_area,
+        );
+
+        let view = self
+            .views
+            .last_mut()
+            .expect("the report view is never popped");
+        // Tabs don't scroll, so start late enough for the current one to fit
+        // (each label is padded by a space on both sides and a divider)
+        let labels: Vec<String> = view
+            .tables
+            .iter()
+            .map(|t| format!("{} ({})", t.title, t.rows.len()))
+            .collect();
+        let label_width = |label: &String| Span::raw(label.as_str()).width() + 3;
+        let mut first = 0;
+        while first < view.tab
+            && labels[first..=view.tab]
+                .iter()
+                .map(label_width)
+                .sum::<usize>()
+                > tabs_area.width as usize
+        {
+            first += 1;
+        }
+        frame.render_widget(
+            Tabs::new(labels.into_iter().skip(first))
+                .select(view.tab - first)
+                .highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+                .block(Block::new().borders(Borders::BOTTOM)),
+            tabs_area,
+        );
+
+        let table = &mut view.tables[view.tab];
+        frame.render_stateful_widget(table.widget(), table_area, &mut table.state);
+
+        let position = format!("row {}/{}", table.selected() + 1, table.rows.len());
+        let status = self.status.as_deref().unwrap_or(HELP);
+        frame.render_widget(
+            Paragraph::new(format!("{position}  {status}"))
+                .style(Style::new().add_modifier(Modifier::DIM)),
+            status_area,
+        );
+    }
+}
+
+/// report.json, given the file or the --out directory holding it
+fn read_report(path: &str) -> Result<Report> {
+    let file = if Path::new(path).is_dir() {
+        Path::new(path).join("report.json")
+    } else {
+        Path::new(path).to_path_buf()
+    };
+    let text =
+        fs::read_to_string(&file).with_context(|| format!("Failed to read {}", file.display()))?;
+    serde_json::from_str(&text).with_context(|| format!("Invalid report in {}", file.display()))
+}
+
+/// `cat_scan tui`: browse a finished scan's report tables in the terminal
+pub fn run_tui(config: TuiConfig) -> Result<()> {
+    let report = read_report(&config.report_path)?;
+    let mut app =
+        App::new(&report).with_context(|| format!("Nothing to show in {}", config.report_path))?;
+
+    if !io::stdout().is_terminal() {
+        bail!("cat_scan tui needs a terminal; `cat_scan query` prints cross-tabs to any output");
+    }
+    // Raw mode on the alternate screen, so the shell comes back as it was
+    // on exit (ratatui also restores it if we panic)
+    let mut terminal = ratatui::try_init().context("Failed to set up the terminal")?;
+    let result = (|| -> Result<()> {
+        loop {
+            terminal.draw(|frame| app.draw(frame))?;
+            // Key releases too are reported on Windows
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !app.handle(key) {
+                    return Ok(());
+                }
+            }
+        }
+    })();
+    ratatui::restore();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::{backend::TestBackend, Terminal};
+
+    use super::*;
+
+    /// The screen as lines of text
+    fn render(app: &mut App, width: u16, height: u16) -> Vec<String> {
+        let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+        terminal.draw(|frame| app.draw(frame)).unwrap();
+        terminal
+            .backend()
+            .buffer()
+            .content()
+            .chunks(width as usize)
+            .map(|cells| cells.iter().map(|c| c.symbol()).collect())
+            .collect()
+    }
+
+    fn press(app: &mut App, code: KeyCode) -> bool {
+        app.handle(KeyEvent::from(code))
+    }
+
+    #[test]
+    fn test_tui_sort_and_drill_down() {
+        let report: Report = serde_json::from_str(
+            r#"{
+                "source": "logs.jsonl",
+                "total_requests": 60,
+                "formats": [
+                    { "w": 300, "h": 250, "requests": 40, "bids": 10, "bid_rate": 0.25 },
+                    { "w": 728, "h": 90, "requests": 20, "bids": 15, "bid_rate": 0.75 }
+                ],
+                "ssps": [
+                    { "ssp": "alpha", "requests": 10, "bids": 5, "bid_rate": 0.5 },
+                    { "ssp": "beta", "requests": 50, "bids": 20, "bid_rate": 0.4 }
+                ],
+                "publishers": [
+                    { "ssp": "alpha", "publisher_id": "pub-1", "requests": 10 },
+                    { "ssp": "beta", "publisher_id": "pub-2", "requests": 50 }
+                ],
+                "floors": [
+                    { "dimension": "format", "key": "728x90", "bids": 15 },
+                    { "dimension": "ssp", "key": "beta", "bids": 20 }
+                ],
+                "segments": [],
+                "metadata": { "tool": "cat_scan" }
+            }"#,
+        )
+        .unwrap();
+        let mut app = App::new(&report).unwrap();
+        let titles: Vec<&str> = app.views[0].tables.iter().map(|t| t.title).collect();
+        assert_eq!(titles, vec!["Formats", "SSPs", "Publishers", "Floors"]);
+
+        // SSPs tab, sorted by requests (the second column), busiest first
+        press(&mut app, KeyCode::Tab);
+        press(&mut app, KeyCode::Right);
+        press(&mut app, KeyCode::Right);
+        assert_eq!(app.table().rows[0].field("ssp"), "beta");
+        press(&mut app, KeyCode::Char('r'));
+        assert_eq!(app.table().rows[0].field("ssp"), "alpha");
+
+        let screen = render(&mut app, 100, 8);
+        assert_eq!(screen.len(), 8);
+        assert!(screen[1].contains("SSPs (2)"), "{screen:?}");
+        assert!(screen[3].contains("requests \u{25b2}"), "{screen:?}");
+        assert!(screen[4].contains("alpha") && screen[4].contains("50.00%"));
+        assert!(screen[7].starts_with("row 1/2"), "{screen:?}");
+        assert_eq!(app.page, 3);
+
+        // Drill into beta: its SSP row, publisher and floor row
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.views.len(), 2);
+        assert_eq!(app.views[1].title, "ssp=beta");
+        let drilled: Vec<(&str, usize)> = app.views[1]
+            .tables
+            .iter()
+            .map(|t| (t.title, t.rows.len()))
+            .collect();
+        assert_eq!(drilled, vec![("SSPs", 1), ("Publishers", 1), ("Floors", 1)]);
+        assert!(render(&mut app, 100, 8)[0].contains("> ssp=beta"));
+
+        // Back out, then drill into the 728x90 format
+        press(&mut app, KeyCode::Esc);
+        press(&mut app, KeyCode::Char('1'));
+        press(&mut app, KeyCode::End);
+        press(&mut app, KeyCode::Enter);
+        let floors = app.views[1].tables.iter().find(|t| t.title == "Floors");
+        assert_eq!(floors.unwrap().rows[0].field("key"), "728x90");
+        assert!(!press(&mut app, KeyCode::Char('q')));
+    }
+}
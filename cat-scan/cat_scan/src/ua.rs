@@ -0,0 +1,132 @@
+use serde_json::Value;
+
+/// Browser and OS family of a request's `device.ua`, None when it has
+/// none. Built with the `ua` feature the families come from woothee, which
+/// knows many more user agents; the in-app webview check applies either
+/// way.
+pub fn ua_families(request: &Value) -> Option<(&'static str, &'static str)> {
+    let ua = request["device"]["ua"]
+        .as_str()
+        .filter(|ua| !ua.is_empty())?;
+    let (browser, os) = parse(ua);
+    if is_webview(ua) {
+        Some(("WebView", os))
+    } else {
+        Some((browser, os))
+    }
+}
+
+/// Android marks its WebView with `; wv)`; iOS webviews leave out the
+/// `Safari/` token Mobile Safari sends
+fn is_webview(ua: &str) -> bool {
+    let ios = ua.contains("iPhone") || ua.contains("iPad") || ua.contains("iPod");
+    ua.contains("; wv)") || (ios && ua.contains("AppleWebKit") && !ua.contains("Safari/"))
+}
+
+#[cfg(feature = "ua")]
+fn parse(ua: &str) -> (&'static str, &'static str) {
+    let Some(result) = woothee::parser::Parser::new().parse(ua) else {
+        return ("Other", "Other");
+    };
+    let browser = match result.name {
+        _ if result.category == "crawler" => "Crawler",
+        "Chrome" => "Chrome",
+        "Safari" => "Safari",
+        "Firefox" => "Firefox",
+        "Edge" => "Edge",
+        "Opera" => "Opera",
+        "Internet Explorer" => "Internet Explorer",
+        "SamsungBrowser" => "Samsung Internet",
+        "Webview" => "WebView",
+        _ => "Other",
+    };
+    let os = match result.os {
+        "iPhone" | "iPad" | "iPod" | "iOS" => "iOS",
+        "Android" => "Android",
+        "Mac OSX" => "macOS",
+        "ChromeOS" => "ChromeOS",
+        "Linux" => "Linux",
+        os if os.starts_with("Windows") => "Windows",
+        _ => "Other",
+    };
+    (browser, os)
+}
+
+/// Token checks for the common browsers and platforms, in an order that
+/// keeps browsers claiming several tokens (Edge and Opera send Chrome/)
+/// apart
+#[cfg(not(feature = "ua"))]
+fn parse(ua: &str) -> (&'static str, &'static str) {
+    let lower = ua.to_ascii_lowercase();
+    let has = |token: &str| ua.contains(token);
+    let browser = if ["bot", "crawler", "spider"]
+        .iter()
+        .any(|t| lower.contains(t))
+    {
+        "Crawler"
+    } else if has("Edg/") || has("Edge/") || has("EdgA/") || has("EdgiOS/") {
+        "Edge"
+    } else if has("OPR/") || has("Opera") {
+        "Opera"
+    } else if has("SamsungBrowser/") {
+        "Samsung Internet"
+    } else if has("Chrome/") || has("CriOS/") {
+        "Chrome"
+    } else if has("Firefox/") || has("FxiOS/") {
+        "Firefox"
+    } else if has("MSIE ") || has("Trident/") {
+        "Internet Explorer"
+    } else if has("Safari/") {
+        "Safari"
+    } else {
+        "Other"
+    };
+    let os = if has("Windows") {
+        "Windows"
+    } else if has("iPhone") || has("iPad") || has("iPod") {
+        "iOS"
+    } else if has("Android") {
+        "Android"
+    } else if has("Mac OS X") {
+        "macOS"
+    } else if has("CrOS") {
+        "ChromeOS"
+    } else if has("Linux") {
+        "Linux"
+    } else {
+        "Other"
+    };
+    (browser, os)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_ua_families() {
+        let families = |ua: &str| ua_families(&json!({ "device": { "ua": ua } })).unwrap();
+        assert_eq!(
+            families("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36"),
+            ("Chrome", "Windows")
+        );
+        assert_eq!(
+            families("Mozilla/5.0 (iPhone; CPU iPhone OS 17_1 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.1 Mobile/15E148 Safari/604.1"),
+            ("Safari", "iOS")
+        );
+        assert_eq!(
+            families("Mozilla/5.0 (iPhone; CPU iPhone OS 17_1 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Mobile/15E148"),
+            ("WebView", "iOS")
+        );
+        assert_eq!(
+            families("Mozilla/5.0 (Linux; Android 13; Pixel 7 Build/TQ3A; wv) AppleWebKit/537.36 (KHTML, like Gecko) Version/4.0 Chrome/119.0.0.0 Mobile Safari/537.36"),
+            ("WebView", "Android")
+        );
+        assert_eq!(
+            families("Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36 Edg/120.0.0.0"),
+            ("Edge", "macOS")
+        );
+        assert_eq!(ua_families(&json!({ "device": {} })), None);
+    }
+}
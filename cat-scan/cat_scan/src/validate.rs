@@ -0,0 +1,627 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    fmt::Write as _,
+    hash::{DefaultHasher, Hash, Hasher},
+    io::{BufRead, BufReader, Write},
+};
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+
+use crate::cli::ValidateConfig;
+use crate::input::scan_progress;
+use crate::source::{open_input, DateRange};
+
+/// Example line numbers kept per SSP and rule
+const EXAMPLE_LINES: usize = 3;
+
+/// OpenRTB version the requests are checked against (--ortb)
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd, serde::Serialize)]
+pub enum OrtbVersion {
+    #[serde(rename = "2.5")]
+    V2_5,
+    #[serde(rename = "2.6")]
+    V2_6,
+}
+
+impl OrtbVersion {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "2.5" => Some(Self::V2_5),
+            "2.6" => Some(Self::V2_6),
+            _ => None,
+        }
+    }
+}
+
+/// Every rule code with what it checks, in report order
+pub const RULES: &[(&str, &str)] = &[
+    ("json.malformed", "line is not a JSON log record"),
+    ("id.missing", "request.id missing or empty"),
+    ("id.duplicate", "request.id already seen earlier in the log"),
+    ("imp.missing", "imp missing or empty"),
+    ("imp.id.missing", "imp.id missing or empty"),
+    ("imp.id.duplicate", "imp.id not unique within the request"),
+    (
+        "imp.no_media",
+        "imp has no banner, video, audio or native object",
+    ),
+    (
+        "imp.banner.size",
+        "banner has neither w/h nor a format list",
+    ),
+    ("imp.video.mimes", "video.mimes missing or empty"),
+    ("imp.audio.mimes", "audio.mimes missing or empty"),
+    ("imp.native.request", "native.request missing"),
+    (
+        "imp.bidfloor.range",
+        "imp.bidfloor negative or not a number",
+    ),
+    ("imp.instl.enum", "imp.instl not 0 or 1"),
+    ("imp.secure.enum", "imp.secure not 0 or 1"),
+    ("imp.rwdd.enum", "imp.rwdd not 0 or 1 (2.6)"),
+    ("distribution.missing", "none of site, app (or dooh in 2.6)"),
+    (
+        "distribution.multiple",
+        "more than one of site, app (or dooh in 2.6)",
+    ),
+    ("at.enum", "at not 1, 2 or an exchange-specific value > 500"),
+    ("tmax.range", "tmax not a positive integer"),
+    ("device.devicetype.enum", "device.devicetype outside 1-7"),
+    (
+        "device.connectiontype.enum",
+        "device.connectiontype outside 0-7",
+    ),
+    ("device.geo.range", "device.geo lat/lon out of range"),
+    ("regs.coppa.enum", "regs.coppa not 0 or 1"),
+    ("regs.gdpr.enum", "regs.gdpr not 0 or 1 (2.6)"),
+    ("cur.type", "cur is not a list of currency codes"),
+    ("bcat.type", "bcat is not a list of strings"),
+    ("badv.type", "badv is not a list of strings"),
+];
+
+/// What a rule code checks
+pub fn describe_rule(code: &str) -> &'static str {
+    RULES
+        .iter()
+        .find(|(c, _)| *c == code)
+        .map_or("", |(_, description)| description)
+}
+
+fn is_flag(value: &Value) -> bool {
+    matches!(value.as_u64(), Some(0 | 1))
+}
+
+fn in_range(value: &Value, min: u64, max: u64) -> bool {
+    value.as_u64().is_some_and(|v| (min..=max).contains(&v))
+}
+
+fn is_string_list(value: &Value) -> bool {
+    value
+        .as_array()
+        .is_some_and(|items| items.iter().all(Value::is_string))
+}
+
+fn non_empty_str(value: &Value) -> bool {
+    value.as_str().is_some_and(|s| !s.is_empty())
+}
+
+fn non_empty_array(value: &Value) -> bool {
+    value.as_array().is_some_and(|items| !items.is_empty())
+}
+
+/// Structural OpenRTB violations in one bid request, as rule codes. Fields
+/// that are absent are only flagged when the spec requires them.
+pub fn check_request(request: &Value, version: OrtbVersion) -> Vec<&'static str> {
+    let mut violations = Vec::new();
+    let mut flag = |code: &'static str| {
+        if !violations.contains(&code) {
+            violations.push(code);
+        }
+    };
+
+    if !non_empty_str(&request["id"]) {
+        flag("id.missing");
+    }
+
+    match request["imp"].as_array() {
+        Some(imps) if !imps.is_empty() => {
+            let mut imp_ids = HashSet::new();
+            for imp in imps {
+                match imp["id"].as_str() {
+                    Some(id) if !id.is_empty() => {
+                        if !imp_ids.insert(id) {
+                            flag("imp.id.duplicate");
+                        }
+                    }
+                    _ => flag("imp.id.missing"),
+                }
+
+                let banner = imp.get("banner");
+                let video = imp.get("video");
+                let audio = imp.get("audio");
+                let native = imp.get("native");
+                if banner.is_none() && video.is_none() && audio.is_none() && native.is_none() {
+                    flag("imp.no_media");
+                }
+                if let Some(banner) = banner {
+                    let sized = banner["w"].is_u64() && banner["h"].is_u64();
+                    if !sized && !non_empty_array(&banner["format"]) {
+                        flag("imp.banner.size");
+                    }
+                }
+                if video.is_some_and(|v| !non_empty_array(&v["mimes"])) {
+                    flag("imp.video.mimes");
+                }
+                if audio.is_some_and(|a| !non_empty_array(&a["mimes"])) {
+                    flag("imp.audio.mimes");
+                }
+                if native.is_some_and(|n| n["request"].is_null()) {
+                    flag("imp.native.request");
+                }
+
+                if let Some(floor) = imp.get("bidfloor") {
+                    if !floor.as_f64().is_some_and(|f| f >= 0.0) {
+                        flag("imp.bidfloor.range");
+                    }
+                }
+                if imp.get("instl").is_some_and(|v| !is_flag(v)) {
+                    flag("imp.instl.enum");
+                }
+                if imp.get("secure").is_some_and(|v| !is_flag(v)) {
+                    flag("imp.secure.enum");
+                }
+                if version >= OrtbVersion::V2_6 && imp.get("rwdd").is_some_and(|v| !is_flag(v)) {
+                    flag("imp.rwdd.enum");
+                }
+            }
+        }
+        _ => flag("imp.missing"),
+    }
+
+    let mut distribution = ["site", "app"]
+        .iter()
+        .filter(|key| request.get(**key).is_some())
+        .count();
+    if version >= OrtbVersion::V2_6 && request.get("dooh").is_some() {
+        distribution += 1;
+    }
+    match distribution {
+        0 => flag("distribution.missing"),
+        1 => {}
+        _ => flag("distribution.multiple"),
+    }
+
+    if let Some(at) = request.get("at") {
+        if !at.as_u64().is_some_and(|at| at == 1 || at == 2 || at > 500) {
+            flag("at.enum");
+        }
+    }
+    if request
+        .get("tmax")
+        .is_some_and(|t| t.as_u64().is_none_or(|t| t == 0))
+    {
+        flag("tmax.range");
+    }
+
+    let device = &request["device"];
+    if device.get("devicetype").is_some_and(|v| !in_range(v, 1, 7)) {
+        flag("device.devicetype.enum");
+    }
+    if device
+        .get("connectiontype")
+        .is_some_and(|v| !in_range(v, 0, 7))
+    {
+        flag("device.connectiontype.enum");
+    }
+    let geo = &device["geo"];
+    let lat_ok = geo
+        .get("lat")
+        .is_none_or(|v| v.as_f64().is_some_and(|l| l.abs() <= 90.0));
+    let lon_ok = geo
+        .get("lon")
+        .is_none_or(|v| v.as_f64().is_some_and(|l| l.abs() <= 180.0));
+    if !lat_ok || !lon_ok {
+        flag("device.geo.range");
+    }
+
+    let regs = &request["regs"];
+    if regs.get("coppa").is_some_and(|v| !is_flag(v)) {
+        flag("regs.coppa.enum");
+    }
+    if version >= OrtbVersion::V2_6 && regs.get("gdpr").is_some_and(|v| !is_flag(v)) {
+        flag("regs.gdpr.enum");
+    }
+
+    if request.get("cur").is_some_and(|v| {
+        !v.as_array()
+            .is_some_and(|c| c.iter().all(|c| c.as_str().is_some_and(|c| c.len() == 3)))
+    }) {
+        flag("cur.type");
+    }
+    if request.get("bcat").is_some_and(|v| !is_string_list(v)) {
+        flag("bcat.type");
+    }
+    if request.get("badv").is_some_and(|v| !is_string_list(v)) {
+        flag("badv.type");
+    }
+
+    violations
+}
+
+/// Violation counts for one SSP
+#[derive(Debug, Default, Clone)]
+pub struct SspValidation {
+    pub records: u64,
+    pub invalid_records: u64,
+    pub by_rule: BTreeMap<&'static str, u64>,
+    /// First EXAMPLE_LINES line numbers per rule
+    pub example_lines: BTreeMap<&'static str, Vec<u64>>,
+}
+
+/// Accumulated results of `cat_scan validate`
+#[derive(Debug)]
+pub struct ValidationStats {
+    version: OrtbVersion,
+    /// Hashes of request ids seen so far, for id.duplicate
+    seen_ids: HashSet<u64>,
+    pub by_ssp: BTreeMap<String, SspValidation>,
+}
+
+impl ValidationStats {
+    pub fn new(version: OrtbVersion) -> Self {
+        Self {
+            version,
+            seen_ids: HashSet::new(),
+            by_ssp: BTreeMap::new(),
+        }
+    }
+
+    fn record(&mut self, ssp: &str, line_no: u64, violations: &[&'static str]) {
+        let stats = self.by_ssp.entry(ssp.to_string()).or_default();
+        stats.records += 1;
+        if violations.is_empty() {
+            return;
+        }
+        stats.invalid_records += 1;
+        for &code in violations {
+            *stats.by_rule.entry(code).or_default() += 1;
+            let lines = stats.example_lines.entry(code).or_default();
+            if lines.len() < EXAMPLE_LINES {
+                lines.push(line_no);
+            }
+        }
+    }
+
+    /// Check one input line
+    pub fn observe_line(&mut self, line: &str, line_no: u64) {
+        let record: Value = match serde_json::from_str(line) {
+            Ok(record @ Value::Object(_)) => record,
+            _ => return self.record("(unparsed)", line_no, &["json.malformed"]),
+        };
+        let request = &record["request"];
+        let ssp = request["source"]["ssp"]
+            .as_str()
+            .filter(|s| !s.is_empty())
+            .unwrap_or("(unknown)");
+
+        let mut violations = check_request(request, self.version);
+        if let Some(id) = request["id"].as_str().filter(|id| !id.is_empty()) {
+            let mut hasher = DefaultHasher::new();
+            id.hash(&mut hasher);
+            if !self.seen_ids.insert(hasher.finish()) {
+                violations.push("id.duplicate");
+            }
+        }
+        self.record(ssp, line_no, &violations);
+    }
+
+    pub fn report(&self, top: usize) -> ValidationReport {
+        let ssps: Vec<SspValidationSummary> = self
+            .by_ssp
+            .iter()
+            .map(|(ssp, stats)| SspValidationSummary {
+                ssp: ssp.clone(),
+                records: stats.records,
+                invalid_records: stats.invalid_records,
+                invalid_rate: ratio(stats.invalid_records, stats.records),
+                violations: stats.by_rule.iter().map(|(&k, &v)| (k, v)).collect(),
+            })
+            .collect();
+
+        let mut problems: Vec<ValidationProblem> = self
+            .by_ssp
+            .iter()
+            .flat_map(|(ssp, stats)| {
+                stats
+                    .by_rule
+                    .iter()
+                    .map(move |(&rule, &count)| ValidationProblem {
+                        ssp: ssp.clone(),
+                        rule,
+                        description: describe_rule(rule),
+                        violations: count,
+                        rate: ratio(count, stats.records),
+                        example_lines: stats.example_lines.get(rule).cloned().unwrap_or_default(),
+                    })
+            })
+            .collect();
+        problems.sort_by(|a, b| {
+            b.violations
+                .cmp(&a.violations)
+                .then_with(|| (&a.ssp, a.rule).cmp(&(&b.ssp, b.rule)))
+        });
+        problems.truncate(top);
+
+        ValidationReport {
+            ortb_version: self.version,
+            records: ssps.iter().map(|s| s.records).sum(),
+            invalid_records: ssps.iter().map(|s| s.invalid_records).sum(),
+            ssps,
+            problems,
+        }
+    }
+}
+
+fn ratio(part: u64, total: u64) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        part as f64 / total as f64
+    }
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct SspValidationSummary {
+    pub ssp: String,
+    pub records: u64,
+    pub invalid_records: u64,
+    pub invalid_rate: f64,
+    pub violations: BTreeMap<&'static str, u64>,
+}
+
+/// One (SSP, rule) pair among the worst offenders
+#[derive(Debug, serde::Serialize)]
+pub struct ValidationProblem {
+    pub ssp: String,
+    pub rule: &'static str,
+    pub description: &'static str,
+    pub violations: u64,
+    /// Share of the SSP's records breaking the rule
+    pub rate: f64,
+    pub example_lines: Vec<u64>,
+}
+
+/// validation.json
+#[derive(Debug, serde::Serialize)]
+pub struct ValidationReport {
+    pub ortb_version: OrtbVersion,
+    pub records: u64,
+    pub invalid_records: u64,
+    pub ssps: Vec<SspValidationSummary>,
+    pub problems: Vec<ValidationProblem>,
+}
+
+/// Plain-text summary printed by `cat_scan validate`
+pub fn render_validation(report: &ValidationReport) -> String {
+    let mut out = String::new();
+    let _ = writeln!(
+        out,
+        "{} records, {} with OpenRTB {} violations ({:.2}%)",
+        report.records,
+        report.invalid_records,
+        match report.ortb_version {
+            OrtbVersion::V2_5 => "2.5",
+            OrtbVersion::V2_6 => "2.6",
+        },
+        ratio(report.invalid_records, report.records) * 100.0
+    );
+
+    let _ = writeln!(
+        out,
+        "\n{:<20} {:>12} {:>12} {:>9}  top violation",
+        "ssp", "records", "invalid", "rate"
+    );
+    for s in &report.ssps {
+        let top = s
+            .violations
+            .iter()
+            .max_by_key(|(_, &count)| count)
+            .map_or(String::from("-"), |(rule, count)| {
+                format!("{rule} ({count})")
+            });
+        let _ = writeln!(
+            out,
+            "{:<20} {:>12} {:>12} {:>8.2}%  {}",
+            s.ssp,
+            s.records,
+            s.invalid_records,
+            s.invalid_rate * 100.0,
+            top
+        );
+    }
+
+    if !report.problems.is_empty() {
+        let _ = writeln!(out, "\nProblems (worst offenders):");
+        for p in &report.problems {
+            let lines: Vec<String> = p.example_lines.iter().map(u64::to_string).collect();
+            let _ = writeln!(
+                out,
+                "{:<20} {:<28} {:>10} {:>7.2}%  {} (e.g. line {})",
+                p.ssp,
+                p.rule,
+                p.violations,
+                p.rate * 100.0,
+                p.description,
+                lines.join(", ")
+            );
+        }
+    }
+    out
+}
+
+/// Write validation.json and validation_stats.csv (violations per SSP and rule)
+fn write_validation_files(out_dir: &str, report: &ValidationReport) -> Result<()> {
+    std::fs::create_dir_all(out_dir)
+        .with_context(|| format!("Failed to create output directory: {}", out_dir))?;
+
+    let path = format!("{}/validation.json", out_dir);
+    let json = serde_json::to_string_pretty(report)
+        .context("Failed to serialize validation report to JSON")?;
+    std::fs::write(&path, json).with_context(|| format!("Failed to write {}", path))?;
+
+    let path = format!("{}/validation_stats.csv", out_dir);
+    let mut csv =
+        std::fs::File::create(&path).with_context(|| format!("Failed to create {}", path))?;
+    writeln!(csv, "ssp,rule,violations,records,rate")?;
+    for s in &report.ssps {
+        for (rule, count) in &s.violations {
+            writeln!(
+                csv,
+                "{},{},{},{},{:.4}",
+                s.ssp,
+                rule,
+                count,
+                s.records,
+                ratio(*count, s.records)
+            )?;
+        }
+    }
+    eprintln!("Validation results written to: {}", out_dir);
+    Ok(())
+}
+
+/// `cat_scan validate`: check every request against OpenRTB structural rules
+pub async fn run_validate(config: ValidateConfig) -> Result<()> {
+    let input = open_input(&config.input_path, config.quiet, DateRange::default()).await?;
+    let progress = scan_progress(input.size, config.quiet, "Validating");
+    let mut reader = BufReader::new(progress.wrap_read(input.reader));
+    let mut stats = ValidationStats::new(config.version);
+
+    tokio::task::block_in_place(|| -> Result<()> {
+        let mut buf = Vec::new();
+        let mut line_no = 0;
+        loop {
+            buf.clear();
+            let read = reader
+                .read_until(b'\n', &mut buf)
+                .with_context(|| format!("Failed to read line {}", line_no + 1))?;
+            if read == 0 {
+                return Ok(());
+            }
+            line_no += 1;
+            let line = String::from_utf8_lossy(&buf);
+            let line = line.trim();
+            if !line.is_empty() {
+                stats.observe_line(line, line_no);
+            }
+        }
+    })?;
+    progress.finish_and_clear();
+
+    let report = stats.report(config.top);
+    print!("{}", render_validation(&report));
+    if let Some(out_dir) = &config.out_dir {
+        write_validation_files(out_dir, &report)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn valid_request() -> Value {
+        json!({
+            "id": "r1",
+            "at": 1,
+            "tmax": 120,
+            "cur": ["USD"],
+            "imp": [{"id": "1", "banner": {"w": 300, "h": 250}, "bidfloor": 0.5, "secure": 1}],
+            "site": {"domain": "example.com"},
+            "device": {"devicetype": 4, "geo": {"lat": 52.5, "lon": 13.4}},
+            "regs": {"coppa": 0},
+            "source": {"ssp": "ssp_a"}
+        })
+    }
+
+    #[test]
+    fn test_check_request_rules() {
+        assert!(check_request(&valid_request(), OrtbVersion::V2_6).is_empty());
+
+        let bad = json!({
+            "imp": [
+                {"id": "1", "banner": {}, "bidfloor": -1},
+                {"id": "1", "video": {"mimes": []}, "rwdd": 2}
+            ],
+            "site": {}, "app": {},
+            "at": 3,
+            "device": {"devicetype": 9, "geo": {"lat": 120.0}},
+            "cur": "USD"
+        });
+        let mut violations = check_request(&bad, OrtbVersion::V2_6);
+        violations.sort_unstable();
+        assert_eq!(
+            violations,
+            vec![
+                "at.enum",
+                "cur.type",
+                "device.devicetype.enum",
+                "device.geo.range",
+                "distribution.multiple",
+                "id.missing",
+                "imp.banner.size",
+                "imp.bidfloor.range",
+                "imp.id.duplicate",
+                "imp.rwdd.enum",
+                "imp.video.mimes",
+            ]
+        );
+
+        // 2.6-only fields aren't checked against 2.5
+        let mut dooh = valid_request();
+        dooh["imp"][0]["rwdd"] = json!(5);
+        dooh["dooh"] = json!({});
+        assert!(check_request(&dooh, OrtbVersion::V2_5).is_empty());
+        assert_eq!(
+            check_request(&dooh, OrtbVersion::V2_6),
+            vec!["imp.rwdd.enum", "distribution.multiple"]
+        );
+    }
+
+    #[test]
+    fn test_validation_stats_per_ssp() {
+        let mut stats = ValidationStats::new(OrtbVersion::V2_6);
+        let line = |request: Value| json!({ "request": request }).to_string();
+        stats.observe_line(&line(valid_request()), 1);
+        stats.observe_line(&line(valid_request()), 2); // same id again
+        let mut no_imp = valid_request();
+        no_imp["id"] = json!("r2");
+        no_imp["imp"] = json!([]);
+        no_imp["source"]["ssp"] = json!("ssp_b");
+        stats.observe_line(&line(no_imp), 3);
+        stats.observe_line("{not json", 4);
+
+        let report = stats.report(10);
+        assert_eq!(report.records, 4);
+        assert_eq!(report.invalid_records, 3);
+        let ssp_a = report.ssps.iter().find(|s| s.ssp == "ssp_a").unwrap();
+        assert_eq!(ssp_a.records, 2);
+        assert_eq!(ssp_a.violations.get("id.duplicate"), Some(&1));
+
+        assert_eq!(report.problems.len(), 3);
+        let dup = report
+            .problems
+            .iter()
+            .find(|p| p.rule == "id.duplicate")
+            .unwrap();
+        assert_eq!(dup.example_lines, vec![2]);
+        assert_eq!(dup.rate, 0.5);
+        assert!(report.problems.iter().any(|p| p.ssp == "(unparsed)"));
+
+        let text = render_validation(&report);
+        assert!(text.starts_with("4 records, 3 with OpenRTB 2.6 violations"));
+        assert!(text.contains("ssp_b"));
+    }
+}
@@ -0,0 +1,121 @@
+use anyhow::{bail, Context, Result};
+use object_store::ObjectMeta;
+
+/// Default --every of `cat_scan watch`
+pub const DEFAULT_WATCH_EVERY_SECS: u64 = 15 * 60;
+
+/// Cursor file written to the --out directory unless --cursor says otherwise
+pub const DEFAULT_CURSOR_FILE: &str = "watch_cursor.json";
+
+/// How far `cat_scan watch` has scanned a prefix: every object up to the
+/// newest LastModified it saw. Objects sharing that LastModified are kept
+/// by key, so one uploaded in the same millisecond isn't skipped.
+#[derive(Debug, Default, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct WatchCursor {
+    /// The prefix being watched
+    pub input: String,
+    /// Unix milliseconds of the newest object scanned
+    pub last_modified_ms: i64,
+    /// Keys of the scanned objects modified at `last_modified_ms`
+    #[serde(default)]
+    pub keys: Vec<String>,
+}
+
+impl WatchCursor {
+    /// Load the cursor for `input` from `path`; a missing file starts from
+    /// the beginning of the prefix
+    pub fn load(path: &str, input: &str) -> Result<Self> {
+        let cursor: Self = match std::fs::read_to_string(path) {
+            Ok(text) => serde_json::from_str(&text)
+                .with_context(|| format!("Failed to parse cursor file {}", path))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Self {
+                    input: input.to_string(),
+                    ..Self::default()
+                })
+            }
+            Err(e) => {
+                return Err(e).with_context(|| format!("Failed to read cursor file {}", path))
+            }
+        };
+        if cursor.input != input {
+            bail!(
+                "Cursor file {} tracks {}, not {}; pass another --cursor",
+                path,
+                cursor.input,
+                input
+            );
+        }
+        Ok(cursor)
+    }
+
+    pub fn save(&self, path: &str) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).context("Failed to serialize cursor")?;
+        std::fs::write(path, json).with_context(|| format!("Failed to write cursor file {}", path))
+    }
+
+    /// Whether an object modified at `modified_ms` wasn't scanned yet
+    pub fn is_new(&self, key: &str, modified_ms: i64) -> bool {
+        modified_ms > self.last_modified_ms
+            || (modified_ms == self.last_modified_ms && !self.keys.iter().any(|k| k == key))
+    }
+
+    /// Move past `objects` once they have been scanned
+    pub fn advance(&mut self, objects: &[ObjectMeta]) {
+        for meta in objects {
+            let modified_ms = meta.last_modified.timestamp_millis();
+            if modified_ms > self.last_modified_ms {
+                self.last_modified_ms = modified_ms;
+                self.keys.clear();
+            }
+            if modified_ms == self.last_modified_ms {
+                self.keys.push(meta.location.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn object(key: &str, modified_ms: i64) -> ObjectMeta {
+        ObjectMeta {
+            location: key.into(),
+            last_modified: (UNIX_EPOCH + Duration::from_millis(modified_ms as u64)).into(),
+            size: 100,
+            e_tag: None,
+            version: None,
+        }
+    }
+
+    #[test]
+    fn test_watch_cursor() {
+        let mut cursor = WatchCursor::default();
+        assert!(cursor.is_new("logs/a.jsonl", 0));
+
+        cursor.advance(&[
+            object("logs/a.jsonl", 1_000),
+            object("logs/b.jsonl", 3_000),
+            object("logs/c.jsonl", 3_000),
+        ]);
+        assert_eq!(cursor.last_modified_ms, 3_000);
+        assert_eq!(cursor.keys, ["logs/b.jsonl", "logs/c.jsonl"]);
+        assert!(!cursor.is_new("logs/a.jsonl", 1_000));
+        assert!(!cursor.is_new("logs/c.jsonl", 3_000));
+        assert!(cursor.is_new("logs/d.jsonl", 3_000));
+        assert!(cursor.is_new("logs/e.jsonl", 4_000));
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cursor.json");
+        let path = path.to_str().unwrap();
+        cursor.input = "s3://bucket/logs/".to_string();
+        cursor.save(path).unwrap();
+        assert_eq!(
+            WatchCursor::load(path, "s3://bucket/logs/").unwrap(),
+            cursor
+        );
+        assert!(WatchCursor::load(path, "s3://bucket/other/").is_err());
+    }
+}
@@ -0,0 +1,188 @@
+//! Golden-file tests: run the cat_scan binary on the logs in
+//! tests/fixtures and compare every CSV and JSON output (report.json holds
+//! the data the HTML report renders) with tests/golden/<case>/.
+//!
+//! After an intended change to the outputs, regenerate the goldens with
+//!
+//!     UPDATE_GOLDEN=1 cargo test -p cat_scan --test golden
+//!
+//! and review the diff. The goldens hold for every feature build, e.g.
+//!
+//!     cargo test -p cat_scan --features simd --test golden
+
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+/// Pins the generation time embedded in every output
+const SOURCE_DATE_EPOCH: &str = "1717300000";
+
+struct Case {
+    name: &'static str,
+    fixture: &'static str,
+    args: &'static [&'static str],
+}
+
+const CASES: &[Case] = &[
+    Case {
+        name: "default",
+        fixture: "mixed_ssps.jsonl",
+        args: &["--on-error", "skip"],
+    },
+    Case {
+        name: "all_analyses",
+        fixture: "mixed_ssps.jsonl",
+        args: &[
+            "--on-error",
+            "skip",
+            "--sort-by",
+            "requests",
+            "--ivt",
+            "--fingerprint",
+            "--examples",
+            "2",
+            "--extract",
+            "domain=site.domain",
+        ],
+    },
+];
+
+fn crate_dir() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+}
+
+/// Run a case in a scratch directory, so the input and --out paths recorded
+/// in the outputs are the same on every machine; returns the output files
+fn run_case(case: &Case) -> (tempfile::TempDir, Vec<String>) {
+    let dir = tempfile::tempdir().unwrap();
+    fs::copy(
+        crate_dir().join("tests/fixtures").join(case.fixture),
+        dir.path().join(case.fixture),
+    )
+    .unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_cat_scan"))
+        .current_dir(dir.path())
+        .env("SOURCE_DATE_EPOCH", SOURCE_DATE_EPOCH)
+        .arg(case.fixture)
+        .args(["--out", "out", "--quiet"])
+        .args(case.args)
+        .output()
+        .unwrap();
+    assert!(
+        output.status.success(),
+        "case {} failed: {}",
+        case.name,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let files = outputs(&dir.path().join("out"));
+    (dir, files)
+}
+
+/// The CSV and JSON files in `dir`, by name
+fn outputs(dir: &Path) -> Vec<String> {
+    let mut files: Vec<String> = fs::read_dir(dir)
+        .map(|entries| {
+            entries
+                .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+                .filter(|name| name.ends_with(".csv") || name.ends_with(".json"))
+                .collect()
+        })
+        .unwrap_or_default();
+    files.sort();
+    files
+}
+
+/// The text of a skipped line's error comes from the JSON parser, which is
+/// serde_json or (with the `simd` feature) simd-json; only the line number
+/// is compared
+fn normalize(file: &str, text: String) -> String {
+    if file != "report.json" {
+        return text;
+    }
+    let mut report: serde_json::Value = serde_json::from_str(&text).unwrap();
+    if let Some(samples) = report["skipped_lines"]["samples"].as_array_mut() {
+        for sample in samples {
+            sample["error"] = "<parse error>".into();
+        }
+    }
+    report.to_string()
+}
+
+#[test]
+fn test_outputs_match_goldens() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let mut failures = Vec::new();
+    for case in CASES {
+        let (dir, files) = run_case(case);
+        let out = dir.path().join("out");
+        let golden = crate_dir().join("tests/golden").join(case.name);
+
+        if update {
+            let _ = fs::remove_dir_all(&golden);
+            fs::create_dir_all(&golden).unwrap();
+            for file in &files {
+                fs::copy(out.join(file), golden.join(file)).unwrap();
+            }
+            continue;
+        }
+
+        let expected: BTreeSet<String> = outputs(&golden).into_iter().collect();
+        let actual: BTreeSet<String> = files.iter().cloned().collect();
+        for missing in expected.difference(&actual) {
+            failures.push(format!("{}: {} no longer written", case.name, missing));
+        }
+        for file in &files {
+            if !expected.contains(file) {
+                failures.push(format!("{}: new output {}", case.name, file));
+                continue;
+            }
+            let want = normalize(file, fs::read_to_string(golden.join(file)).unwrap());
+            let got = normalize(file, fs::read_to_string(out.join(file)).unwrap());
+            if let Some((line, (w, g))) = want
+                .lines()
+                .zip(got.lines())
+                .enumerate()
+                .find(|(_, (w, g))| w != g)
+            {
+                failures.push(format!(
+                    "{}/{} line {}:\n  golden: {}\n  actual: {}",
+                    case.name,
+                    file,
+                    line + 1,
+                    w,
+                    g
+                ));
+            } else if want.lines().count() != got.lines().count() {
+                failures.push(format!(
+                    "{}/{}: {} lines, golden has {}",
+                    case.name,
+                    file,
+                    got.lines().count(),
+                    want.lines().count()
+                ));
+            }
+        }
+    }
+    assert!(
+        failures.is_empty(),
+        "outputs differ from tests/golden (rerun with UPDATE_GOLDEN=1 if intended):\n{}",
+        failures.join("\n")
+    );
+}
+
+#[test]
+fn test_outputs_are_deterministic() {
+    let case = &CASES[CASES.len() - 1];
+    let (first, files) = run_case(case);
+    let (second, _) = run_case(case);
+    for file in &files {
+        let a = fs::read(first.path().join("out").join(file)).unwrap();
+        let b = fs::read(second.path().join("out").join(file)).unwrap();
+        assert!(a == b, "{} differs between two runs", file);
+    }
+    let html = |dir: &Path| fs::read(dir.join("out/report.html")).unwrap();
+    assert!(html(first.path()) == html(second.path()));
+}
@@ -0,0 +1,158 @@
+use std::{env, path::PathBuf};
+
+use anyhow::{bail, Context, Result};
+
+use crate::expect::Expectation;
+
+/// Requests sent unless --requests says otherwise
+const DEFAULT_REQUESTS: u64 = 1000;
+
+const USAGE: &str = "Usage: cat_sim [OPTIONS]\n\n\
+     Runs fake_bidder, drives it with fake_ssp, scans the log with cat_scan and\n\
+     checks the scan summary.\n\n\
+     Options:\n  \
+     --requests N        Requests fake_ssp sends (default: 1000)\n  \
+     --qps N             fake_ssp's target QPS, 0 for as fast as possible (default: 0)\n  \
+     --concurrency N     fake_ssp's requests in flight (default: 16)\n  \
+     --traffic FILE      fake_ssp traffic profile\n  \
+     --policy FILE       fake_bidder bidding policy\n  \
+     --seed N            Seed fake_ssp's traffic for a reproducible run\n  \
+     --expect COND       A summary.json property the scan must have, e.g. bid_rate>=20%,\n                         requests=1000 or metadata.parse_errors=0; repeatable\n                         (default: requests=N, parse_errors=0, bids>0)\n  \
+     --out DIR           Where the log, reports and process output go\n                         (default: a new cat_sim_<ms> directory under the temp dir)\n  \
+     --bin-dir DIR       Where the fake_bidder, fake_ssp and cat_scan binaries are\n                         (CAT_SIM_BIN_DIR, default: next to cat_sim)\n\n\
+     Other fake_ssp and fake_bidder settings (MEDIA_MIX, IMPS, ...) are passed\n\
+     through from the environment. Exits 2 when an expectation fails.";
+
+/// One end-to-end run
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    pub requests: u64,
+    pub qps: String,
+    pub concurrency: String,
+    pub traffic: Option<PathBuf>,
+    pub policy: Option<PathBuf>,
+    pub seed: Option<String>,
+    pub expectations: Vec<Expectation>,
+    /// None makes a fresh directory under the temp dir
+    pub out: Option<PathBuf>,
+    /// None looks next to the cat_sim binary
+    pub bin_dir: Option<PathBuf>,
+}
+
+impl Config {
+    /// Options from the command line
+    pub fn load() -> Result<Self> {
+        let args: Vec<String> = env::args().skip(1).collect();
+        Self::parse(|name| env::var(name).ok(), &args)
+    }
+
+    fn parse(env: impl Fn(&str) -> Option<String>, args: &[String]) -> Result<Self> {
+        let mut config = Self {
+            requests: DEFAULT_REQUESTS,
+            qps: "0".to_string(),
+            concurrency: "16".to_string(),
+            traffic: None,
+            policy: None,
+            seed: None,
+            expectations: Vec::new(),
+            out: None,
+            bin_dir: env("CAT_SIM_BIN_DIR")
+                .filter(|d| !d.is_empty())
+                .map(PathBuf::from),
+        };
+
+        let mut i = 0;
+        while i < args.len() {
+            match args[i].as_str() {
+                "--help" | "-h" => bail!(USAGE),
+                flag @ ("--requests" | "--qps" | "--concurrency" | "--traffic" | "--policy"
+                | "--seed" | "--expect" | "--out" | "--bin-dir") => {
+                    let value = args
+                        .get(i + 1)
+                        .with_context(|| format!("{flag} requires a value"))?
+                        .clone();
+                    match flag {
+                        "--requests" => {
+                            config.requests =
+                                value
+                                    .trim()
+                                    .parse::<u64>()
+                                    .ok()
+                                    .filter(|n| *n >= 1)
+                                    .with_context(|| format!("invalid request count '{value}'"))?
+                        }
+                        // fake_ssp validates these itself
+                        "--qps" => config.qps = value,
+                        "--concurrency" => config.concurrency = value,
+                        "--traffic" => config.traffic = Some(PathBuf::from(value)),
+                        "--policy" => config.policy = Some(PathBuf::from(value)),
+                        "--seed" => config.seed = Some(value),
+                        "--expect" => config.expectations.push(Expectation::parse(&value)?),
+                        "--out" => config.out = Some(PathBuf::from(value)),
+                        _ => config.bin_dir = Some(PathBuf::from(value)),
+                    }
+                    i += 2;
+                }
+                other => bail!("Unknown argument: {other}\n\n{USAGE}"),
+            }
+        }
+
+        if config.expectations.is_empty() {
+            for spec in [
+                format!("requests={}", config.requests),
+                "parse_errors=0".to_string(),
+                "bids>0".to_string(),
+            ] {
+                config.expectations.push(Expectation::parse(&spec)?);
+            }
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(env: &[(&str, &str)], args: &[&str]) -> Result<Config> {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        Config::parse(
+            |name| {
+                env.iter()
+                    .find(|(k, _)| *k == name)
+                    .map(|(_, v)| v.to_string())
+            },
+            &args,
+        )
+    }
+
+    #[test]
+    fn test_defaults_and_expectations() {
+        let config = parse(&[], &["--requests", "500"]).unwrap();
+        let specs: Vec<&str> = config
+            .expectations
+            .iter()
+            .map(|e| e.spec.as_str())
+            .collect();
+        assert_eq!(specs, ["requests=500", "parse_errors=0", "bids>0"]);
+        assert_eq!(config.bin_dir, None);
+
+        let config = parse(
+            &[("CAT_SIM_BIN_DIR", "/opt/cat")],
+            &[
+                "--expect",
+                "bid_rate>=20%",
+                "--expect",
+                "zero_bid_formats=0",
+            ],
+        )
+        .unwrap();
+        assert_eq!(config.requests, 1000);
+        assert_eq!(config.expectations.len(), 2);
+        assert_eq!(config.bin_dir, Some(PathBuf::from("/opt/cat")));
+
+        assert!(parse(&[], &["--requests", "0"]).is_err());
+        assert!(parse(&[], &["--expect", "bid_rate"]).is_err());
+        assert!(parse(&[], &["--bogus"]).is_err());
+    }
+}
@@ -0,0 +1,95 @@
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+/// A property the scan summary must have, e.g. "bid_rate>=20%",
+/// "requests=1000" or "metadata.parse_errors=0"
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expectation {
+    /// As given on the command line
+    pub spec: String,
+    /// Path into summary.json, dot-separated
+    key: String,
+    op: &'static str,
+    value: f64,
+}
+
+impl Expectation {
+    pub fn parse(spec: &str) -> Result<Self> {
+        let spec = spec.trim();
+        let (at, op) = [">=", "<=", "!=", "==", ">", "<", "="]
+            .iter()
+            .find_map(|op| spec.find(op).map(|at| (at, *op)))
+            .with_context(|| format!("--expect '{spec}' needs a comparison, e.g. bid_rate>=20%"))?;
+        let key = spec[..at].trim();
+        if key.is_empty() {
+            bail!("--expect '{spec}' needs a summary.json key before '{op}'");
+        }
+        let raw = spec[at + op.len()..].trim();
+        let value = match raw.strip_suffix('%') {
+            Some(pct) => pct.trim().parse::<f64>().map(|pct| pct / 100.0),
+            None => raw.parse::<f64>(),
+        }
+        .ok()
+        .filter(|v| v.is_finite())
+        .with_context(|| format!("invalid number '{raw}' in --expect '{spec}'"))?;
+        Ok(Self {
+            spec: spec.to_string(),
+            key: key.to_string(),
+            op: if op == "==" { "=" } else { op },
+            value,
+        })
+    }
+
+    /// The summary's value for the key; an error when it has none
+    pub fn actual(&self, summary: &Value) -> Result<f64> {
+        let pointer = format!("/{}", self.key.replace('.', "/"));
+        summary
+            .pointer(&pointer)
+            .and_then(Value::as_f64)
+            .with_context(|| format!("summary.json has no number at '{}'", self.key))
+    }
+
+    /// Whether the summary meets the expectation
+    pub fn holds(&self, actual: f64) -> bool {
+        match self.op {
+            ">=" => actual >= self.value,
+            "<=" => actual <= self.value,
+            ">" => actual > self.value,
+            "<" => actual < self.value,
+            "!=" => (actual - self.value).abs() > f64::EPSILON,
+            _ => (actual - self.value).abs() <= f64::EPSILON,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_expectations() {
+        let summary = json!({
+            "requests": 1000,
+            "bid_rate": 0.25,
+            "metadata": { "parse_errors": 0 }
+        });
+        let check = |spec: &str| {
+            let expectation = Expectation::parse(spec).unwrap();
+            expectation.holds(expectation.actual(&summary).unwrap())
+        };
+        assert!(check("requests=1000"));
+        assert!(check("requests == 1000"));
+        assert!(!check("requests!=1000"));
+        assert!(check("bid_rate>=20%"));
+        assert!(check("bid_rate<0.3"));
+        assert!(!check("bid_rate>25%"));
+        assert!(check("metadata.parse_errors=0"));
+
+        let missing = Expectation::parse("peak_qps>0").unwrap();
+        assert!(missing.actual(&summary).is_err());
+        assert!(Expectation::parse("bid_rate").is_err());
+        assert!(Expectation::parse(">=5").is_err());
+        assert!(Expectation::parse("bid_rate>=lots").is_err());
+    }
+}
@@ -0,0 +1,234 @@
+mod config;
+mod expect;
+
+use std::{
+    env,
+    fs::{self, File},
+    net::{SocketAddr, TcpListener, TcpStream},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context, Result};
+use serde_json::Value;
+
+use config::Config;
+
+/// Exit code of a run that failed an expectation (errors exit 1)
+const FAIL_EXIT_CODE: i32 = 2;
+
+/// How long fake_bidder gets to start listening
+const BIDDER_STARTUP: Duration = Duration::from_secs(10);
+
+/// End-to-end simulation: starts fake_bidder on a free local port, sends it
+/// fake_ssp traffic logged to a local file, scans that log with cat_scan and
+/// checks the scan summary against the expectations (see config::Config,
+/// `cat_sim --help`). Everything the run produced stays in the output
+/// directory:
+///
+/// - fake_ssp_logs.jsonl, the log
+/// - report/, cat_scan's outputs
+/// - fake_bidder.log, fake_ssp.log, cat_scan.log, each process's output
+fn main() -> Result<()> {
+    let config = Config::load()?;
+
+    let bin_dir = match &config.bin_dir {
+        Some(dir) => dir.clone(),
+        None => env::current_exe()
+            .context("Failed to locate the cat_sim binary")?
+            .parent()
+            .context("cat_sim binary has no parent directory")?
+            .to_path_buf(),
+    };
+    let bidder = binary(&bin_dir, "fake_bidder")?;
+    let ssp = binary(&bin_dir, "fake_ssp")?;
+    let scanner = binary(&bin_dir, "cat_scan")?;
+
+    let out = match &config.out {
+        Some(dir) => dir.clone(),
+        None => {
+            let ms = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis());
+            env::temp_dir().join(format!("cat_sim_{ms}"))
+        }
+    };
+    fs::create_dir_all(&out)
+        .with_context(|| format!("Failed to create output directory: {}", out.display()))?;
+    println!("cat_sim output: {}", out.display());
+
+    // 1. The bidder, on a port nothing else is using
+    let addr = free_local_addr()?;
+    let mut command = Command::new(&bidder);
+    command.arg("--bind").arg(addr.to_string());
+    if let Some(policy) = &config.policy {
+        command.arg("--policy").arg(policy);
+    }
+    // Would override --bind or switch to HTTPS
+    for var in ["PORT", "TLS_CERT", "TLS_KEY"] {
+        command
+            .env_remove(var)
+            .env_remove(format!("FAKE_BIDDER_{var}"));
+    }
+    let mut bidder = Bidder(
+        command
+            .stdout(log_file(&out, "fake_bidder.log")?)
+            .stderr(log_file(&out, "fake_bidder.log")?)
+            .spawn()
+            .with_context(|| format!("Failed to start {}", bidder.display()))?,
+    );
+    wait_until_listening(&mut bidder.0, addr, &out)?;
+    println!("fake_bidder listening on {addr}");
+
+    // 2. Traffic, logged to one plain local file cat_scan can read
+    let log = out.join("fake_ssp_logs.jsonl");
+    let mut command = Command::new(&ssp);
+    command
+        .arg("--requests")
+        .arg(config.requests.to_string())
+        .arg("--qps")
+        .arg(&config.qps)
+        .arg("--concurrency")
+        .arg(&config.concurrency);
+    if let Some(traffic) = &config.traffic {
+        command.arg("--traffic").arg(traffic);
+    }
+    if let Some(seed) = &config.seed {
+        command.arg("--seed").arg(seed);
+    }
+    for var in ["DURATION", "REPLAY", "LOG_ROLL_SIZE", "LOG_ROLL_INTERVAL"] {
+        command
+            .env_remove(var)
+            .env_remove(format!("FAKE_SSP_{var}"));
+    }
+    println!("Sending {} requests with fake_ssp...", config.requests);
+    // The prefixed names win over the older ones and a CAT_CONFIG file
+    run(
+        command
+            .env("FAKE_SSP_BIDDER_ENDPOINT", format!("http://{addr}/bid"))
+            .env("FAKE_SSP_LOG_DESTINATION", "local")
+            .env("FAKE_SSP_LOG_FILE", &log)
+            .env("FAKE_SSP_LOG_GZIP", "0")
+            .env("FAKE_SSP_LOOP", "0"),
+        &out,
+        "fake_ssp",
+    )?;
+    drop(bidder);
+
+    // 3. The scan
+    let report = out.join("report");
+    println!("Scanning with cat_scan...");
+    run(
+        Command::new(&scanner).arg(&log).arg("--out").arg(&report),
+        &out,
+        "cat_scan",
+    )?;
+
+    // 4. The checks
+    let summary_path = report.join("summary.json");
+    let summary: Value = serde_json::from_str(
+        &fs::read_to_string(&summary_path)
+            .with_context(|| format!("Failed to read {}", summary_path.display()))?,
+    )
+    .with_context(|| format!("Invalid JSON in {}", summary_path.display()))?;
+
+    let mut failed = 0;
+    for expectation in &config.expectations {
+        let (passed, actual) = match expectation.actual(&summary) {
+            Ok(actual) => (expectation.holds(actual), actual.to_string()),
+            Err(e) => (false, e.to_string()),
+        };
+        if !passed {
+            failed += 1;
+        }
+        println!(
+            "  {} {} (got {})",
+            if passed { "PASS" } else { "FAIL" },
+            expectation.spec,
+            actual
+        );
+    }
+    if failed > 0 {
+        eprintln!(
+            "cat_sim: {failed} of {} expectations failed; see {}",
+            config.expectations.len(),
+            out.display()
+        );
+        std::process::exit(FAIL_EXIT_CODE);
+    }
+    println!("All {} expectations met.", config.expectations.len());
+    Ok(())
+}
+
+/// Stops the bidder when the run ends, however it ends
+struct Bidder(Child);
+
+impl Drop for Bidder {
+    fn drop(&mut self) {
+        let _ = self.0.kill();
+        let _ = self.0.wait();
+    }
+}
+
+/// One of the workspace binaries, built alongside cat_sim
+fn binary(dir: &Path, name: &str) -> Result<PathBuf> {
+    let path = dir.join(format!("{name}{}", env::consts::EXE_SUFFIX));
+    if !path.is_file() {
+        bail!(
+            "{} not found; build the workspace first (cargo build --workspace) or point --bin-dir at the binaries",
+            path.display()
+        );
+    }
+    Ok(path)
+}
+
+/// A loopback address with a port the OS just handed out
+fn free_local_addr() -> Result<SocketAddr> {
+    let listener = TcpListener::bind("127.0.0.1:0").context("Failed to find a free port")?;
+    Ok(listener.local_addr()?)
+}
+
+fn log_file(out: &Path, name: &str) -> Result<File> {
+    let path = out.join(name);
+    File::options()
+        .create(true)
+        .append(true)
+        .open(&path)
+        .with_context(|| format!("Failed to open {}", path.display()))
+}
+
+fn wait_until_listening(bidder: &mut Child, addr: SocketAddr, out: &Path) -> Result<()> {
+    let started = Instant::now();
+    loop {
+        if TcpStream::connect_timeout(&addr, Duration::from_millis(200)).is_ok() {
+            return Ok(());
+        }
+        if let Some(status) = bidder.try_wait()? {
+            bail!(
+                "fake_bidder exited ({status}) before listening; see {}",
+                out.join("fake_bidder.log").display()
+            );
+        }
+        if started.elapsed() > BIDDER_STARTUP {
+            bail!("fake_bidder isn't listening on {addr} after {BIDDER_STARTUP:?}");
+        }
+        thread::sleep(Duration::from_millis(50));
+    }
+}
+
+/// Run a step to completion, its output going to <name>.log
+fn run(command: &mut Command, out: &Path, name: &str) -> Result<()> {
+    let log = out.join(format!("{name}.log"));
+    let status = command
+        .stdin(Stdio::null())
+        .stdout(log_file(out, &format!("{name}.log"))?)
+        .stderr(log_file(out, &format!("{name}.log"))?)
+        .status()
+        .with_context(|| format!("Failed to run {name}"))?;
+    if !status.success() {
+        bail!("{name} failed ({status}); see {}", log.display());
+    }
+    Ok(())
+}
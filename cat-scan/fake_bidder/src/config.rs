@@ -0,0 +1,169 @@
+use std::{net::SocketAddr, path::PathBuf};
+
+use anyhow::{bail, Context, Result};
+use cat_config::{Setting, Settings};
+
+/// Where the bidder listens unless BIND_ADDR / --bind says otherwise
+const DEFAULT_BIND: &str = "0.0.0.0:3000";
+
+const USAGE: &str = "Usage: fake_bidder [OPTIONS]\n\n\
+     Options (each also settable as FAKE_BIDDER_<OPTION>, e.g. FAKE_BIDDER_TLS_CERT, through\n\
+     the environment variable shown, or in the [fake_bidder] section of the config file):\n  \
+     --config FILE       TOML settings file shared with fake_ssp and cat_scan (CAT_CONFIG)\n  \
+     --policy FILE       Bidding policy, TOML or JSON (POLICY_FILE, default: bid on\n                         300x250 banners at 1.2x the floor)\n  \
+     --bind ADDR         Address to listen on (BIND_ADDR, default: 0.0.0.0:3000)\n  \
+     --port N            Port to listen on, overriding the one in --bind (PORT)\n  \
+     --tls-cert FILE     Serve HTTPS with this PEM certificate chain (TLS_CERT)\n  \
+     --tls-key FILE      ...and this PEM private key (TLS_KEY)";
+
+/// Everything fake_bidder reads through cat_config
+pub const SETTINGS: &[Setting] = &[
+    Setting::value("policy").env("POLICY_FILE"),
+    Setting::value("bind").env("BIND_ADDR"),
+    Setting::value("port").env("PORT"),
+    Setting::value("tls_cert").env("TLS_CERT"),
+    Setting::value("tls_key").env("TLS_KEY"),
+];
+
+/// How the bidder behaves
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// Bidding policy file; None uses the built-in policy
+    pub policy: Option<PathBuf>,
+    /// Address to listen on
+    pub bind: SocketAddr,
+    /// Certificate and key to serve HTTPS with; None serves plain HTTP
+    pub tls: Option<Tls>,
+}
+
+/// PEM files for HTTPS
+#[derive(Debug, Clone, PartialEq)]
+pub struct Tls {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+}
+
+impl Config {
+    /// Options from the environment and config file, overridden by
+    /// command-line flags
+    pub fn load() -> Result<Self> {
+        let settings = Settings::load("fake_bidder", SETTINGS)?;
+        Self::parse(|name| settings.var(name), settings.args())
+    }
+
+    fn parse(env: impl Fn(&str) -> Option<String>, args: &[String]) -> Result<Self> {
+        let mut policy = env("POLICY_FILE");
+        let mut bind = env("BIND_ADDR");
+        let mut port = env("PORT");
+        let mut tls_cert = env("TLS_CERT");
+        let mut tls_key = env("TLS_KEY");
+
+        let mut i = 0;
+        while i < args.len() {
+            match args[i].as_str() {
+                "--help" | "-h" => bail!(USAGE),
+                flag @ ("--policy" | "--bind" | "--port" | "--tls-cert" | "--tls-key") => {
+                    let value = args
+                        .get(i + 1)
+                        .with_context(|| format!("{flag} requires a value"))?
+                        .clone();
+                    match flag {
+                        "--policy" => policy = Some(value),
+                        "--bind" => bind = Some(value),
+                        "--port" => port = Some(value),
+                        "--tls-cert" => tls_cert = Some(value),
+                        _ => tls_key = Some(value),
+                    }
+                    i += 2;
+                }
+                other => bail!("Unknown argument: {other}\n\n{USAGE}"),
+            }
+        }
+
+        let bind = bind.filter(|b| !b.is_empty());
+        let bind = bind.as_deref().unwrap_or(DEFAULT_BIND).trim();
+        let mut bind: SocketAddr = bind
+            .parse()
+            .with_context(|| format!("invalid bind address '{bind}', expected IP:PORT"))?;
+        if let Some(port) = port.filter(|p| !p.is_empty()) {
+            bind.set_port(
+                port.trim()
+                    .parse()
+                    .with_context(|| format!("invalid port '{port}'"))?,
+            );
+        }
+
+        let empty_to_none = |value: Option<String>| value.filter(|v| !v.is_empty());
+        let tls = match (empty_to_none(tls_cert), empty_to_none(tls_key)) {
+            (Some(cert), Some(key)) => Some(Tls {
+                cert: PathBuf::from(cert),
+                key: PathBuf::from(key),
+            }),
+            (None, None) => None,
+            _ => bail!("TLS needs both a certificate (--tls-cert) and a key (--tls-key)"),
+        };
+
+        Ok(Self {
+            policy: empty_to_none(policy).map(PathBuf::from),
+            bind,
+            tls,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(env: &[(&str, &str)], args: &[&str]) -> Result<Config> {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        Config::parse(
+            |name| {
+                env.iter()
+                    .find(|(k, _)| *k == name)
+                    .map(|(_, v)| v.to_string())
+            },
+            &args,
+        )
+    }
+
+    #[test]
+    fn test_defaults_and_overrides() {
+        let config = parse(&[], &[]).unwrap();
+        assert_eq!(config.policy, None);
+        assert_eq!(config.bind, "0.0.0.0:3000".parse().unwrap());
+        assert_eq!(config.tls, None);
+
+        // Flags win over the environment
+        let config = parse(&[("POLICY_FILE", "env.toml")], &["--policy", "flag.json"]).unwrap();
+        assert_eq!(config.policy, Some(PathBuf::from("flag.json")));
+        let config = parse(&[("POLICY_FILE", "env.toml")], &[]).unwrap();
+        assert_eq!(config.policy, Some(PathBuf::from("env.toml")));
+
+        assert!(parse(&[], &["--policy"]).is_err());
+        assert!(parse(&[], &["--bogus"]).is_err());
+    }
+
+    #[test]
+    fn test_listen_address_and_tls() {
+        let config = parse(&[("BIND_ADDR", "127.0.0.1:8080")], &[]).unwrap();
+        assert_eq!(config.bind, "127.0.0.1:8080".parse().unwrap());
+        // The port replaces the bind address's own
+        let config = parse(&[("BIND_ADDR", "[::1]:8080")], &["--port", "8443"]).unwrap();
+        assert_eq!(config.bind, "[::1]:8443".parse().unwrap());
+        let config = parse(&[("PORT", "9000")], &[]).unwrap();
+        assert_eq!(config.bind, "0.0.0.0:9000".parse().unwrap());
+        assert!(parse(&[], &["--bind", "localhost"]).is_err());
+        assert!(parse(&[], &["--port", "70000"]).is_err());
+
+        let config = parse(&[("TLS_KEY", "key.pem")], &["--tls-cert", "cert.pem"]).unwrap();
+        assert_eq!(
+            config.tls,
+            Some(Tls {
+                cert: PathBuf::from("cert.pem"),
+                key: PathBuf::from("key.pem"),
+            })
+        );
+        assert!(parse(&[("TLS_CERT", "cert.pem")], &[]).is_err());
+    }
+}
@@ -1,8 +1,22 @@
-use axum::{routing::post, Json, Router};
-use serde::{Deserialize, Serialize};
-use std::net::SocketAddr;
+mod config;
+mod policy;
+
+use anyhow::{Context, Result};
+use axum::{
+    extract::State,
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::post,
+    Json, Router,
+};
+use axum_server::tls_rustls::RustlsConfig;
+use serde::{de::IgnoredAny, Deserialize, Serialize};
+use std::sync::Arc;
 use tokio::net::TcpListener;
 
+use config::Config;
+use policy::{Decision, Outcome, Policy};
+
 /// Minimal OpenRTB-style structs (only what we need for now)
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -11,19 +25,99 @@ struct Banner {
     h: i32,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Deserialize)]
 struct Imp {
     id: String,
     #[serde(default)]
     banner: Option<Banner>,
     #[serde(default)]
+    video: Option<IgnoredAny>,
+    #[serde(default)]
+    native: Option<IgnoredAny>,
+    #[serde(default)]
+    audio: Option<IgnoredAny>,
+    #[serde(default)]
     bidfloor: Option<f64>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl Imp {
+    /// "WxH" of the banner, or the name of a media type the imp offers
+    fn matches_format(&self, format: &str) -> bool {
+        match format {
+            "banner" => self.banner.is_some(),
+            "video" => self.video.is_some(),
+            "native" => self.native.is_some(),
+            "audio" => self.audio.is_some(),
+            size => self
+                .banner
+                .as_ref()
+                .is_some_and(|b| format!("{}x{}", b.w, b.h) == size),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct Publisher {
+    #[serde(default)]
+    id: Option<String>,
+}
+
+/// site or app; only the publisher matters here
+#[derive(Debug, Deserialize)]
+struct Inventory {
+    #[serde(default)]
+    publisher: Option<Publisher>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Segment {
+    #[serde(default)]
+    id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Data {
+    #[serde(default)]
+    segment: Vec<Segment>,
+}
+
+#[derive(Debug, Deserialize)]
+struct User {
+    #[serde(default)]
+    data: Vec<Data>,
+}
+
+#[derive(Debug, Deserialize)]
 struct BidRequest {
     id: String,
     imp: Vec<Imp>,
+    /// ms the SSP waits for an answer
+    #[serde(default)]
+    tmax: Option<u64>,
+    #[serde(default)]
+    site: Option<Inventory>,
+    #[serde(default)]
+    app: Option<Inventory>,
+    #[serde(default)]
+    user: Option<User>,
+}
+
+impl BidRequest {
+    fn publisher_id(&self) -> Option<&str> {
+        self.site
+            .as_ref()
+            .or(self.app.as_ref())
+            .and_then(|inventory| inventory.publisher.as_ref())
+            .and_then(|publisher| publisher.id.as_deref())
+    }
+
+    fn segment_ids(&self) -> impl Iterator<Item = &str> {
+        self.user
+            .iter()
+            .flat_map(|user| &user.data)
+            .flat_map(|data| &data.segment)
+            .filter_map(|segment| segment.id.as_deref())
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -37,61 +131,151 @@ struct Bid {
 #[derive(Debug, Serialize, Deserialize)]
 struct SeatBid {
     bid: Vec<Bid>,
+    /// Buyer seat, when the policy names seats
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    seat: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 struct BidResponse {
     id: String,
     seatbid: Vec<SeatBid>,
+    /// No-bid reason
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    nbr: Option<u32>,
 }
 
+/// Answers OpenRTB bid requests on POST /bid, following a bidding policy
+/// (see policy::Policy, `fake_bidder --help`).
 #[tokio::main]
-async fn main() {
+async fn main() -> Result<()> {
+    let config = Config::load()?;
+    let policy = match &config.policy {
+        Some(path) => {
+            println!("Bidding policy: {}", path.display());
+            Policy::load(path)?
+        }
+        None => Policy::builtin(),
+    };
+
     // Build our application with a route
-    let app = Router::new().route("/bid", post(handle_bid));
+    let app = Router::new()
+        .route("/bid", post(handle_bid))
+        .with_state(Arc::new(policy));
 
-    // Listen on 0.0.0.0:3000
-    let addr: SocketAddr = "0.0.0.0:3000".parse().unwrap();
-    println!("fake_bidder listening on http://{}", addr);
+    let addr = config.bind;
+    match &config.tls {
+        Some(tls) => {
+            let rustls = RustlsConfig::from_pem_file(&tls.cert, &tls.key)
+                .await
+                .with_context(|| {
+                    format!(
+                        "Failed to load TLS certificate {} and key {}",
+                        tls.cert.display(),
+                        tls.key.display()
+                    )
+                })?;
+            println!("fake_bidder listening on https://{}", addr);
+            axum_server::bind_rustls(addr, rustls)
+                .serve(app.into_make_service())
+                .await
+                .with_context(|| format!("Failed to serve on {addr}"))?;
+        }
+        None => {
+            println!("fake_bidder listening on http://{}", addr);
 
-    // Axum 0.7 style: use TcpListener + axum::serve
-    let listener = TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+            // Axum 0.7 style: use TcpListener + axum::serve
+            let listener = TcpListener::bind(addr)
+                .await
+                .with_context(|| format!("Failed to listen on {addr}"))?;
+            axum::serve(listener, app).await?;
+        }
+    }
+    Ok(())
 }
 
-/// Very simple fake bidding logic:
-/// - If first impression is 300x250 -> bid
-/// - Otherwise -> no-bid (empty seatbid)
-async fn handle_bid(Json(req): Json<BidRequest>) -> Json<BidResponse> {
+/// Fake bidding logic: the policy decides which seats bid on each
+/// impression and at what prices; the first impression's decision says
+/// whether the response goes out normally or fails, and the slowest one how
+/// long it takes. No bid is an empty seatbid, with an nbr when the policy
+/// gives one
+async fn handle_bid(State(policy): State<Arc<Policy>>, Json(req): Json<BidRequest>) -> Response {
     println!("Received request id={} with {} imps", req.id, req.imp.len());
 
-    let mut seatbids: Vec<SeatBid> = Vec::new();
-
-    if let Some(first_imp) = req.imp.first() {
-        if let Some(banner) = &first_imp.banner {
-            let should_bid = banner.w == 300 && banner.h == 250;
-
-            if should_bid {
-                let floor = first_imp.bidfloor.unwrap_or(0.5);
-                let price = floor * 1.2_f64;
+    let decisions: Vec<Decision> = {
+        let mut rng = rand::rng();
+        req.imp
+            .iter()
+            .map(|imp| policy.decide(&req, imp, &mut rng))
+            .collect()
+    };
+    let latency = decisions
+        .iter()
+        .map(|d| d.latency)
+        .max()
+        .unwrap_or_default();
+    if !latency.is_zero() {
+        tokio::time::sleep(latency).await;
+    }
 
-                let bid = Bid {
-                    id: "bid-1".to_string(),
-                    impid: first_imp.id.clone(),
-                    price,
+    // One seatbid per seat, holding its bids on every impression
+    let mut seatbids: Vec<SeatBid> = Vec::new();
+    let mut bid_count = 0;
+    for (imp, decision) in req.imp.iter().zip(&decisions) {
+        for seat_prices in &decision.bids {
+            let index = match seatbids.iter().position(|s| s.seat == seat_prices.seat) {
+                Some(index) => index,
+                None => {
+                    seatbids.push(SeatBid {
+                        bid: Vec::new(),
+                        seat: seat_prices.seat.clone(),
+                    });
+                    seatbids.len() - 1
+                }
+            };
+            for price in &seat_prices.prices {
+                bid_count += 1;
+                seatbids[index].bid.push(Bid {
+                    id: format!("bid-{bid_count}"),
+                    impid: imp.id.clone(),
+                    price: *price,
                     adm: "<div>Fake ad</div>".to_string(),
-                };
-
-                seatbids.push(SeatBid { bid: vec![bid] });
+                });
             }
         }
     }
 
+    let first = decisions.first();
+    let outcome = match first.map(|d| d.outcome) {
+        // Only a response with no bid at all can go out empty
+        Some(Outcome::NoContent) if !seatbids.is_empty() => Outcome::Answer,
+        Some(outcome) => outcome,
+        None => Outcome::Answer,
+    };
+
     // If we never pushed a bid, seatbids will be empty = no-bid
     let resp = BidResponse {
         id: req.id,
+        nbr: if seatbids.is_empty() {
+            first.and_then(|d| d.nbr)
+        } else {
+            None
+        },
         seatbid: seatbids,
     };
 
-    Json(resp)
+    match outcome {
+        Outcome::Answer | Outcome::TimedOut => Json(resp).into_response(),
+        Outcome::NoContent => StatusCode::NO_CONTENT.into_response(),
+        Outcome::GatewayTimeout => StatusCode::GATEWAY_TIMEOUT.into_response(),
+        Outcome::ServerError(status) => StatusCode::from_u16(status)
+            .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+            .into_response(),
+        Outcome::Malformed => {
+            // Cut off half way, as by a crash mid-write
+            let mut body = serde_json::to_string(&resp).unwrap_or_default();
+            body.truncate(body.len() / 2);
+            ([(header::CONTENT_TYPE, "application/json")], body).into_response()
+        }
+    }
 }
@@ -0,0 +1,946 @@
+use std::{collections::BTreeMap, fs, path::Path, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use rand::Rng;
+use rand_distr::{Distribution, LogNormal};
+use serde::Deserialize;
+
+use crate::{BidRequest, Imp};
+
+/// The floor floor_margin prices against when an impression has none
+const NO_FLOOR: f64 = 0.5;
+
+/// How long a timed-out request without a tmax waits
+const NO_TMAX: Duration = Duration::from_secs(1);
+
+/// The policy fake_bidder has always followed: bid on 300x250 banners at
+/// 1.2x the floor, pass on everything else
+const BUILTIN: &str = r#"
+[default]
+bid_rate = 0.0
+
+[[rules]]
+format = "300x250"
+bid_rate = 1.0
+"#;
+
+/// How the bidder answers, read from a TOML or JSON file (--policy). Each
+/// impression follows the first rule whose conditions all match it, and
+/// whatever that rule leaves out comes from `[default]`.
+///
+/// ```toml
+/// [default]
+/// bid_rate = 0.1
+/// latency_ms = { median = 30, sigma = 0.5 }   # lognormal; or [5, 20], or 10
+/// timeout_rate = 0.02       # answer after the request's tmax
+/// gateway_timeout_rate = 0.01   # answer 504 Gateway Timeout
+/// server_error_rate = 0.01  # answer 500, 502 or 503
+/// malformed_rate = 0.005    # answer with the JSON cut short
+/// no_content_rate = 0.2     # pass with HTTP 204 instead of a body
+/// nbr = { 8 = 70, 7 = 30 }  # no-bid reason codes by weight, or one code
+///
+/// [[rules]]
+/// format = "300x250"        # WxH of a banner, or banner|video|native|audio
+/// publisher = "pub-news"    # site/app.publisher.id
+/// segment = "travel"        # any user.data segment id
+/// bid_rate = 0.9
+/// price = [0.8, 2.5]        # CPM drawn from this range, or one number
+/// latency_ms = 40
+///
+/// [[rules]]
+/// format = "video"
+/// price = { strategy = "shaded", median = 8.0, sigma = 0.4, shade = 0.3 }
+/// seats = ["dsp-a", "dsp-b"]   # each bids on its own, in its own seatbid
+/// bids_per_seat = [1, 3]    # distinct prices per seat, or one number
+/// ```
+///
+/// Without a price, a bid is placed at 1.2x the impression's floor (see
+/// Pricing for the other strategies). Without seats, bids go in one seatbid with no seat id.
+///
+/// A timed-out request is answered (with whatever the policy decided) 10 to
+/// 100 ms after its tmax, or after a second when it has none. The error
+/// rates are drawn together, so they add up; a request that escapes them
+/// all gets a bid or a no-bid as usual.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    #[serde(default)]
+    pub default: Behavior,
+    #[serde(default)]
+    pub rules: Vec<Rule>,
+}
+
+/// What to do with a matching impression
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Behavior {
+    /// Probability of bidding, 0 to 1
+    pub bid_rate: Option<f64>,
+    pub price: Option<Price>,
+    pub latency_ms: Option<Latency>,
+    /// Probability of answering after tmax
+    pub timeout_rate: Option<f64>,
+    /// Probability of answering 504 Gateway Timeout
+    pub gateway_timeout_rate: Option<f64>,
+    /// Probability of answering 500, 502 or 503
+    pub server_error_rate: Option<f64>,
+    /// Probability of answering with invalid JSON
+    pub malformed_rate: Option<f64>,
+    /// Probability of a no-bid being an empty HTTP 204
+    pub no_content_rate: Option<f64>,
+    /// Reason code of no-bid responses
+    pub nbr: Option<Nbr>,
+    /// Buyer seats bidding, each at bid_rate
+    pub seats: Option<Vec<String>>,
+    /// Bids each bidding seat places
+    pub bids_per_seat: Option<Count>,
+}
+
+impl Behavior {
+    fn validate(&self, name: &str) -> Result<()> {
+        let rates = [
+            ("bid_rate", self.bid_rate),
+            ("timeout_rate", self.timeout_rate),
+            ("gateway_timeout_rate", self.gateway_timeout_rate),
+            ("server_error_rate", self.server_error_rate),
+            ("malformed_rate", self.malformed_rate),
+            ("no_content_rate", self.no_content_rate),
+        ];
+        for (field, rate) in rates {
+            if rate.is_some_and(|rate| !(0.0..=1.0).contains(&rate)) {
+                bail!("bidding policy: {name} {field} must be 0 to 1");
+            }
+        }
+        if self.price.is_some_and(|p| !p.is_valid()) {
+            bail!("bidding policy: {name} has an invalid price");
+        }
+        if self.latency_ms.is_some_and(|l| !l.is_valid()) {
+            bail!("bidding policy: {name} has an invalid latency_ms");
+        }
+        if let Some(nbr) = &self.nbr {
+            nbr.validate()
+                .with_context(|| format!("bidding policy: {name} has an invalid nbr"))?;
+        }
+        if self.seats.as_ref().is_some_and(|seats| seats.is_empty()) {
+            bail!("bidding policy: {name} seats is empty");
+        }
+        if self.bids_per_seat.is_some_and(|count| !count.is_valid()) {
+            bail!("bidding policy: {name} bids_per_seat must be at least 1");
+        }
+        Ok(())
+    }
+
+    /// This behavior, with what it leaves out taken from `fallback`
+    fn or(self, fallback: &Behavior) -> Behavior {
+        Behavior {
+            bid_rate: self.bid_rate.or(fallback.bid_rate),
+            price: self.price.or(fallback.price),
+            latency_ms: self.latency_ms.or(fallback.latency_ms),
+            timeout_rate: self.timeout_rate.or(fallback.timeout_rate),
+            gateway_timeout_rate: self.gateway_timeout_rate.or(fallback.gateway_timeout_rate),
+            server_error_rate: self.server_error_rate.or(fallback.server_error_rate),
+            malformed_rate: self.malformed_rate.or(fallback.malformed_rate),
+            no_content_rate: self.no_content_rate.or(fallback.no_content_rate),
+            nbr: self.nbr.or_else(|| fallback.nbr.clone()),
+            seats: self.seats.or_else(|| fallback.seats.clone()),
+            bids_per_seat: self.bids_per_seat.or(fallback.bids_per_seat),
+        }
+    }
+}
+
+/// A rule: conditions (all optional) and the behavior they select
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    pub format: Option<String>,
+    pub publisher: Option<String>,
+    pub segment: Option<String>,
+    pub bid_rate: Option<f64>,
+    pub price: Option<Price>,
+    pub latency_ms: Option<Latency>,
+    pub timeout_rate: Option<f64>,
+    pub gateway_timeout_rate: Option<f64>,
+    pub server_error_rate: Option<f64>,
+    pub malformed_rate: Option<f64>,
+    pub no_content_rate: Option<f64>,
+    pub nbr: Option<Nbr>,
+    pub seats: Option<Vec<String>>,
+    pub bids_per_seat: Option<Count>,
+}
+
+/// OpenRTB no-bid reason (0 unknown, 1 technical error, 2 invalid request,
+/// 3 web spider, 4 suspected non-human traffic, 5 data center IP, 6
+/// unsupported device, 7 blocked publisher, 8 unmatched user, 9 daily
+/// reader cap, 10 daily domain cap): one code, or codes by weight
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Nbr {
+    Code(u32),
+    Weighted(BTreeMap<String, f64>),
+}
+
+impl Nbr {
+    fn validate(&self) -> Result<()> {
+        if let Nbr::Weighted(weights) = self {
+            if weights.is_empty() || weights.values().all(|w| *w <= 0.0) {
+                bail!("no code has a positive weight");
+            }
+            for (code, weight) in weights {
+                code.parse::<u32>()
+                    .with_context(|| format!("'{code}' is not a reason code"))?;
+                if !weight.is_finite() || *weight < 0.0 {
+                    bail!("code {code} has an invalid weight");
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> u32 {
+        match self {
+            Nbr::Code(code) => *code,
+            Nbr::Weighted(weights) => {
+                let total: f64 = weights.values().sum();
+                let mut point = rng.random_range(0.0..total);
+                let mut last = 0;
+                for (code, weight) in weights {
+                    last = code.parse().unwrap_or(0);
+                    if point < *weight {
+                        break;
+                    }
+                    point -= weight;
+                }
+                last
+            }
+        }
+    }
+}
+
+/// A fixed number or a [min, max] range to draw from uniformly
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Range {
+    Fixed(f64),
+    Between([f64; 2]),
+}
+
+impl Range {
+    pub fn sample<R: Rng>(self, rng: &mut R) -> f64 {
+        match self {
+            Range::Fixed(x) => x,
+            Range::Between([min, max]) if min < max => rng.random_range(min..max),
+            Range::Between([min, _]) => min,
+        }
+    }
+
+    fn is_valid(self) -> bool {
+        let valid = |x: f64| x.is_finite() && x >= 0.0;
+        match self {
+            Range::Fixed(x) => valid(x),
+            Range::Between([min, max]) => valid(min) && valid(max) && min <= max,
+        }
+    }
+}
+
+/// How a bid is priced: a fixed CPM (a number), a CPM drawn from a [min,
+/// max] range, or a strategy table
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Price {
+    Cpm(Range),
+    Strategy(Pricing),
+}
+
+impl Default for Price {
+    fn default() -> Self {
+        Price::Strategy(Pricing::FloorMargin {
+            margin: default_margin(),
+        })
+    }
+}
+
+impl Price {
+    /// The bid on an impression with this floor, rounded to the cent; None
+    /// when the strategy passes
+    pub fn bid<R: Rng>(self, floor: Option<f64>, rng: &mut R) -> Option<f64> {
+        let cents = |x: f64| (x * 100.0).round() / 100.0;
+        // Floor-aware prices never round down under the floor
+        let above_floor = |x: f64, floor: f64| cents(x).max((floor * 100.0 - 1e-6).ceil() / 100.0);
+        match self {
+            Price::Cpm(range) => Some(cents(range.sample(rng))),
+            Price::Strategy(Pricing::Fixed { cpm }) => Some(cents(cpm)),
+            Price::Strategy(Pricing::Range { min, max }) => {
+                Some(cents(Range::Between([min, max]).sample(rng)))
+            }
+            Price::Strategy(Pricing::FloorMargin { margin }) => {
+                let floor = floor.unwrap_or(NO_FLOOR);
+                Some(above_floor(floor * (1.0 + margin), floor))
+            }
+            Price::Strategy(Pricing::Shaded {
+                median,
+                sigma,
+                shade,
+            }) => {
+                let value = LogNormal::new(median.ln(), sigma).map_or(median, |v| v.sample(rng));
+                let floor = floor.unwrap_or(0.0);
+                (value > floor).then(|| above_floor(floor + shade * (value - floor), floor))
+            }
+        }
+    }
+
+    fn is_valid(self) -> bool {
+        let valid = |x: f64| x.is_finite() && x >= 0.0;
+        match self {
+            Price::Cpm(range) => range.is_valid(),
+            Price::Strategy(Pricing::Fixed { cpm }) => valid(cpm),
+            Price::Strategy(Pricing::Range { min, max }) => Range::Between([min, max]).is_valid(),
+            Price::Strategy(Pricing::FloorMargin { margin }) => margin.is_finite() && margin > -1.0,
+            Price::Strategy(Pricing::Shaded {
+                median,
+                sigma,
+                shade,
+            }) => {
+                median.is_finite() && median > 0.0 && valid(sigma) && (0.0..=1.0).contains(&shade)
+            }
+        }
+    }
+}
+
+/// Pricing strategies, picked by `strategy`:
+///
+/// - `floor_margin`: the floor plus `margin` (0.2 = 20% over, the default);
+///   0.50 stands in for a missing floor
+/// - `fixed`: always `cpm`, floor or not
+/// - `range`: uniformly between `min` and `max`, floor or not
+/// - `shaded`: how a first-price bidder shades: it values the impression at
+///   a lognormal draw (`median`, `sigma`), passes when that doesn't clear
+///   the floor, and otherwise bids `shade` of the way from the floor to its
+///   value, near what it would have paid in a second-price auction
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(tag = "strategy", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Pricing {
+    FloorMargin {
+        #[serde(default = "default_margin")]
+        margin: f64,
+    },
+    Fixed {
+        cpm: f64,
+    },
+    Range {
+        min: f64,
+        max: f64,
+    },
+    Shaded {
+        median: f64,
+        #[serde(default)]
+        sigma: f64,
+        #[serde(default = "default_shade")]
+        shade: f64,
+    },
+}
+
+fn default_margin() -> f64 {
+    0.2
+}
+
+fn default_shade() -> f64 {
+    0.5
+}
+
+/// A count, or a [min, max] range to pick from uniformly
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Count {
+    Fixed(u32),
+    Between([u32; 2]),
+}
+
+impl Count {
+    pub fn sample<R: Rng>(self, rng: &mut R) -> u32 {
+        match self {
+            Count::Fixed(n) => n,
+            Count::Between([min, max]) => rng.random_range(min..=max.max(min)),
+        }
+    }
+
+    fn is_valid(self) -> bool {
+        match self {
+            Count::Fixed(n) => n >= 1,
+            Count::Between([min, max]) => min >= 1 && min <= max,
+        }
+    }
+}
+
+/// Time taken to answer, in ms: a fixed number, a [min, max] range to draw
+/// from uniformly, or a lognormal distribution given by its median and the
+/// standard deviation of its logarithm (sigma 0.5 puts p99 at 3.2x the
+/// median)
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Latency {
+    Fixed(f64),
+    Uniform([f64; 2]),
+    LogNormal { median: f64, sigma: f64 },
+}
+
+impl Latency {
+    /// Draws are capped at a minute so a long tail can't hold requests forever
+    const MAX_MS: f64 = 60_000.0;
+
+    pub fn sample<R: Rng>(self, rng: &mut R) -> Duration {
+        let ms = match self {
+            Latency::Fixed(ms) => ms,
+            Latency::Uniform(range) => Range::Between(range).sample(rng),
+            Latency::LogNormal { median, sigma } => LogNormal::new(median.ln(), sigma)
+                .map_or(median, |distribution| distribution.sample(rng)),
+        };
+        Duration::from_secs_f64(ms.min(Self::MAX_MS) / 1000.0)
+    }
+
+    fn is_valid(self) -> bool {
+        match self {
+            Latency::Fixed(ms) => Range::Fixed(ms).is_valid(),
+            Latency::Uniform(range) => Range::Between(range).is_valid(),
+            Latency::LogNormal { median, sigma } => {
+                median.is_finite() && median > 0.0 && sigma.is_finite() && sigma >= 0.0
+            }
+        }
+    }
+}
+
+/// How the response goes out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    /// On time, or as late as the latency makes it
+    Answer,
+    /// Deliberately after the request's tmax
+    TimedOut,
+    /// HTTP 504 instead of a bid response
+    GatewayTimeout,
+    /// This HTTP 5xx status instead of a bid response
+    ServerError(u16),
+    /// A bid response cut short, so it isn't valid JSON
+    Malformed,
+    /// A no-bid as an empty HTTP 204
+    NoContent,
+}
+
+/// The bids of one seat on an impression
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeatPrices {
+    /// None for the bidder's own, unnamed seat
+    pub seat: Option<String>,
+    /// Distinct, highest first
+    pub prices: Vec<f64>,
+}
+
+/// The answer for one impression
+#[derive(Debug, Clone, PartialEq)]
+pub struct Decision {
+    /// Seats bidding, in policy order; empty to pass
+    pub bids: Vec<SeatPrices>,
+    /// Reason given with a no-bid
+    pub nbr: Option<u32>,
+    /// How long to wait before answering
+    pub latency: Duration,
+    pub outcome: Outcome,
+}
+
+impl Policy {
+    /// Read a policy; `.toml` files are TOML, anything else JSON
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read bidding policy: {}", path.display()))?;
+        let policy: Self = if path.extension().is_some_and(|ext| ext == "toml") {
+            toml::from_str(&text)
+                .with_context(|| format!("Invalid bidding policy: {}", path.display()))?
+        } else {
+            serde_json::from_str(&text)
+                .with_context(|| format!("Invalid bidding policy: {}", path.display()))?
+        };
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    pub fn builtin() -> Self {
+        toml::from_str(BUILTIN).expect("built-in policy is valid")
+    }
+
+    fn validate(&self) -> Result<()> {
+        self.default.validate("default")?;
+        for (i, rule) in self.rules.iter().enumerate() {
+            rule.behavior().validate(&format!("rule {}", i + 1))?;
+        }
+        Ok(())
+    }
+
+    /// Which seats bid on `imp` and at what prices, and how to answer
+    pub fn decide<R: Rng>(&self, request: &BidRequest, imp: &Imp, rng: &mut R) -> Decision {
+        let behavior = match self.rules.iter().find(|rule| rule.matches(request, imp)) {
+            Some(rule) => rule.behavior().or(&self.default),
+            None => self.default.clone(),
+        };
+        let bid_rate = behavior.bid_rate.unwrap_or(0.0);
+        let rate = |rate: Option<f64>| rate.unwrap_or(0.0);
+
+        let seats = match &behavior.seats {
+            Some(seats) => seats.iter().map(|seat| Some(seat.clone())).collect(),
+            None => vec![None],
+        };
+        let mut bids = Vec::new();
+        for seat in seats {
+            if bid_rate <= 0.0 || !rng.random_bool(bid_rate) {
+                continue;
+            }
+            let count = behavior.bids_per_seat.map_or(1, |count| count.sample(rng));
+            let price = behavior.price.unwrap_or_default();
+            let mut prices: Vec<f64> = (0..count)
+                .filter_map(|_| price.bid(imp.bidfloor, rng))
+                .collect();
+            prices.sort_by(|a, b| b.total_cmp(a));
+            // A cent apart at least, dropping any pushed down to nothing
+            for i in 1..prices.len() {
+                let below = ((prices[i - 1] - 0.01) * 100.0).round() / 100.0;
+                prices[i] = prices[i].min(below);
+            }
+            prices.retain(|price| *price > 0.0);
+            if !prices.is_empty() {
+                bids.push(SeatPrices { seat, prices });
+            }
+        }
+
+        let nbr = match (bids.is_empty(), &behavior.nbr) {
+            (true, Some(nbr)) => Some(nbr.sample(rng)),
+            _ => None,
+        };
+        let latency = behavior
+            .latency_ms
+            .map(|l| l.sample(rng))
+            .unwrap_or_default();
+        let passed = bids.is_empty();
+        let decision = |outcome| Decision {
+            bids,
+            nbr,
+            latency,
+            outcome,
+        };
+
+        // One draw picks at most one failure, so their rates add up
+        let mut roll = rng.random::<f64>();
+        let mut hit = |rate: f64| {
+            let hit = roll < rate;
+            roll -= rate;
+            hit
+        };
+        if hit(rate(behavior.gateway_timeout_rate)) {
+            decision(Outcome::GatewayTimeout)
+        } else if hit(rate(behavior.server_error_rate)) {
+            let status = [500, 502, 503][rng.random_range(0..3)];
+            decision(Outcome::ServerError(status))
+        } else if hit(rate(behavior.malformed_rate)) {
+            decision(Outcome::Malformed)
+        } else if hit(rate(behavior.timeout_rate)) {
+            let tmax = request.tmax.map_or(NO_TMAX, Duration::from_millis);
+            Decision {
+                latency: latency.max(tmax + Duration::from_millis(rng.random_range(10..=100))),
+                ..decision(Outcome::TimedOut)
+            }
+        } else if passed && rng.random_bool(rate(behavior.no_content_rate)) {
+            Decision {
+                nbr: None,
+                ..decision(Outcome::NoContent)
+            }
+        } else {
+            decision(Outcome::Answer)
+        }
+    }
+}
+
+impl Rule {
+    fn behavior(&self) -> Behavior {
+        Behavior {
+            bid_rate: self.bid_rate,
+            price: self.price,
+            latency_ms: self.latency_ms,
+            timeout_rate: self.timeout_rate,
+            gateway_timeout_rate: self.gateway_timeout_rate,
+            server_error_rate: self.server_error_rate,
+            malformed_rate: self.malformed_rate,
+            no_content_rate: self.no_content_rate,
+            nbr: self.nbr.clone(),
+            seats: self.seats.clone(),
+            bids_per_seat: self.bids_per_seat,
+        }
+    }
+
+    fn matches(&self, request: &BidRequest, imp: &Imp) -> bool {
+        self.format
+            .as_deref()
+            .is_none_or(|format| imp.matches_format(format))
+            && self
+                .publisher
+                .as_deref()
+                .is_none_or(|id| request.publisher_id() == Some(id))
+            && self
+                .segment
+                .as_deref()
+                .is_none_or(|id| request.segment_ids().any(|s| s == id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn request(json: serde_json::Value) -> BidRequest {
+        serde_json::from_value(json).unwrap()
+    }
+
+    fn top_price(decision: &Decision) -> Option<f64> {
+        decision.bids.first().map(|seat| seat.prices[0])
+    }
+
+    #[test]
+    fn test_first_matching_rule_wins() {
+        let policy: Policy = toml::from_str(
+            r#"
+            [default]
+            bid_rate = 1.0
+            price = 0.1
+            latency_ms = 5
+
+            [[rules]]
+            format = "300x250"
+            publisher = "pub-news"
+            price = [1.0, 2.0]
+
+            [[rules]]
+            segment = "travel"
+            bid_rate = 0.0
+
+            [[rules]]
+            format = "video"
+            price = 9.5
+            latency_ms = [30, 40]
+            "#,
+        )
+        .unwrap();
+        policy.validate().unwrap();
+        let mut rng = StdRng::seed_from_u64(1);
+
+        let news = request(serde_json::json!({
+            "id": "r1",
+            "imp": [{ "id": "1", "banner": { "w": 300, "h": 250 } }],
+            "site": { "publisher": { "id": "pub-news" } },
+            "user": { "data": [{ "segment": [{ "id": "travel" }] }] }
+        }));
+        let decision = policy.decide(&news, &news.imp[0], &mut rng);
+        let price = top_price(&decision).unwrap();
+        assert!((1.0..=2.0).contains(&price), "{price}");
+        assert_eq!(decision.latency, Duration::from_millis(5));
+
+        // Same imp elsewhere falls through to the segment rule
+        let travel = request(serde_json::json!({
+            "id": "r2",
+            "imp": [{ "id": "1", "banner": { "w": 300, "h": 250 } }],
+            "app": { "publisher": { "id": "pub-games" } },
+            "user": { "data": [{ "segment": [{ "id": "finance" }, { "id": "travel" }] }] }
+        }));
+        assert_eq!(
+            top_price(&policy.decide(&travel, &travel.imp[0], &mut rng)),
+            None
+        );
+
+        let video = request(serde_json::json!({
+            "id": "r3",
+            "imp": [{ "id": "1", "video": { "w": 640, "h": 360 }, "bidfloor": 2.0 }]
+        }));
+        let decision = policy.decide(&video, &video.imp[0], &mut rng);
+        assert_eq!(top_price(&decision), Some(9.5));
+        assert!(decision.latency >= Duration::from_millis(30));
+
+        // No match: the default
+        let other = request(serde_json::json!({
+            "id": "r4",
+            "imp": [{ "id": "1", "banner": { "w": 728, "h": 90 } }]
+        }));
+        assert_eq!(
+            top_price(&policy.decide(&other, &other.imp[0], &mut rng)),
+            Some(0.1)
+        );
+    }
+
+    #[test]
+    fn test_latency_distributions_and_timeouts() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut draws: Vec<f64> = (0..10_000)
+            .map(|_| {
+                let latency = Latency::LogNormal {
+                    median: 40.0,
+                    sigma: 0.5,
+                };
+                latency.sample(&mut rng).as_secs_f64() * 1000.0
+            })
+            .collect();
+        draws.sort_by(f64::total_cmp);
+        let median = draws[draws.len() / 2];
+        assert!((36.0..44.0).contains(&median), "{median}");
+        assert!(draws[draws.len() * 99 / 100] > 100.0);
+
+        let policy: Policy = toml::from_str(
+            r#"
+            [default]
+            bid_rate = 1.0
+            price = 1.0
+            latency_ms = [10, 20]
+            timeout_rate = 0.3
+            gateway_timeout_rate = 0.2
+            "#,
+        )
+        .unwrap();
+        policy.validate().unwrap();
+        let req = request(serde_json::json!({
+            "id": "r",
+            "tmax": 120,
+            "imp": [{ "id": "1", "banner": { "w": 300, "h": 250 } }]
+        }));
+        let decisions: Vec<Decision> = (0..1_000)
+            .map(|_| policy.decide(&req, &req.imp[0], &mut rng))
+            .collect();
+        let count = |outcome| decisions.iter().filter(|d| d.outcome == outcome).count();
+        assert!((250..350).contains(&count(Outcome::TimedOut)));
+        assert!((150..250).contains(&count(Outcome::GatewayTimeout)));
+        for decision in &decisions {
+            match decision.outcome {
+                Outcome::TimedOut => assert!(decision.latency > Duration::from_millis(120)),
+                Outcome::Answer | Outcome::GatewayTimeout => {
+                    assert!(decision.latency <= Duration::from_millis(20))
+                }
+                other => panic!("unexpected {other:?}"),
+            }
+        }
+
+        let bad: Policy =
+            toml::from_str("[default]\nlatency_ms = { median = 0, sigma = 0.5 }").unwrap();
+        assert!(bad.validate().is_err());
+        let bad: Policy = toml::from_str("[default]\ntimeout_rate = -0.1").unwrap();
+        assert!(bad.validate().is_err());
+    }
+
+    #[test]
+    fn test_no_bid_reasons_and_errors() {
+        let policy: Policy = toml::from_str(
+            r#"
+            [default]
+            bid_rate = 0.5
+            nbr = { 8 = 3, 2 = 1 }
+            no_content_rate = 0.5
+            server_error_rate = 0.1
+            malformed_rate = 0.1
+
+            [[rules]]
+            format = "728x90"
+            bid_rate = 0.0
+            nbr = 7
+            no_content_rate = 0.0
+            server_error_rate = 0.0
+            malformed_rate = 0.0
+            "#,
+        )
+        .unwrap();
+        policy.validate().unwrap();
+        let mut rng = StdRng::seed_from_u64(4);
+        let req = request(serde_json::json!({
+            "id": "r",
+            "imp": [
+                { "id": "1", "banner": { "w": 300, "h": 250 } },
+                { "id": "2", "banner": { "w": 728, "h": 90 } }
+            ]
+        }));
+
+        let decisions: Vec<Decision> = (0..4_000)
+            .map(|_| policy.decide(&req, &req.imp[0], &mut rng))
+            .collect();
+        let count = |f: &dyn Fn(&Decision) -> bool| decisions.iter().filter(|d| f(d)).count();
+        assert!((300..500).contains(&count(&|d| matches!(d.outcome, Outcome::ServerError(_)))));
+        assert!((300..500).contains(&count(&|d| d.outcome == Outcome::Malformed)));
+        // Half of the remaining no-bids
+        assert!((600..1000).contains(&count(&|d| d.outcome == Outcome::NoContent)));
+        for decision in &decisions {
+            assert_eq!(
+                decision.nbr.is_some(),
+                decision.bids.is_empty() && decision.outcome != Outcome::NoContent
+            );
+            if let Outcome::ServerError(status) = decision.outcome {
+                assert!([500, 502, 503].contains(&status));
+            }
+        }
+        let unmatched = count(&|d| d.nbr == Some(8));
+        let invalid = count(&|d| d.nbr == Some(2));
+        assert!(unmatched > 2 * invalid, "{unmatched} vs {invalid}");
+
+        let leaderboard = policy.decide(&req, &req.imp[1], &mut rng);
+        assert_eq!(
+            (leaderboard.nbr, leaderboard.outcome),
+            (Some(7), Outcome::Answer)
+        );
+
+        let bad: Policy = toml::from_str("[default]\nnbr = { spider = 1 }").unwrap();
+        assert!(bad.validate().is_err());
+        let bad: Policy = toml::from_str("[default]\nserver_error_rate = 2").unwrap();
+        assert!(bad.validate().is_err());
+    }
+
+    #[test]
+    fn test_seats_bid_distinct_prices() {
+        let policy: Policy = toml::from_str(
+            r#"
+            [default]
+            bid_rate = 1.0
+            price = [0.5, 0.6]
+            seats = ["dsp-a", "dsp-b", "dsp-c"]
+            bids_per_seat = [2, 4]
+
+            [[rules]]
+            format = "728x90"
+            seats = ["dsp-b"]
+            bids_per_seat = 3
+            price = 1.0
+            "#,
+        )
+        .unwrap();
+        policy.validate().unwrap();
+        let mut rng = StdRng::seed_from_u64(5);
+        let req = request(serde_json::json!({
+            "id": "r",
+            "imp": [
+                { "id": "1", "banner": { "w": 300, "h": 250 } },
+                { "id": "2", "banner": { "w": 728, "h": 90 } }
+            ]
+        }));
+
+        for _ in 0..100 {
+            let decision = policy.decide(&req, &req.imp[0], &mut rng);
+            let seats: Vec<_> = decision.bids.iter().map(|b| b.seat.as_deref()).collect();
+            assert_eq!(seats, [Some("dsp-a"), Some("dsp-b"), Some("dsp-c")]);
+            for seat in &decision.bids {
+                assert!((2..=4).contains(&seat.prices.len()));
+                assert!(seat.prices.windows(2).all(|w| w[0] >= w[1] + 0.0099));
+            }
+        }
+
+        // A fixed price still gives distinct bids, a cent apart
+        let decision = policy.decide(&req, &req.imp[1], &mut rng);
+        assert_eq!(
+            decision.bids,
+            [SeatPrices {
+                seat: Some("dsp-b".to_string()),
+                prices: vec![1.0, 0.99, 0.98],
+            }]
+        );
+
+        let bad: Policy = toml::from_str("[default]\nbids_per_seat = [3, 1]").unwrap();
+        assert!(bad.validate().is_err());
+        let bad: Policy = toml::from_str("[default]\nseats = []").unwrap();
+        assert!(bad.validate().is_err());
+    }
+
+    #[test]
+    fn test_pricing_strategies() {
+        #[derive(Deserialize)]
+        struct Prices {
+            price: Vec<Price>,
+        }
+        let prices: Prices = toml::from_str(
+            r#"
+            price = [
+                0.75,
+                [1.0, 2.0],
+                { strategy = "floor_margin", margin = 0.5 },
+                { strategy = "fixed", cpm = 3.0 },
+                { strategy = "range", min = 0.2, max = 0.4 },
+                { strategy = "shaded", median = 2.0, sigma = 0.5, shade = 0.4 },
+            ]
+            "#,
+        )
+        .unwrap();
+        let [fixed, range, margin, cpm, strategy_range, shaded] = prices.price[..] else {
+            panic!("{:?}", prices.price);
+        };
+        assert!(prices.price.iter().all(|p| p.is_valid()));
+        let mut rng = StdRng::seed_from_u64(6);
+
+        assert_eq!(fixed.bid(Some(5.0), &mut rng), Some(0.75));
+        assert_eq!(cpm.bid(None, &mut rng), Some(3.0));
+        assert!((1.0..=2.0).contains(&range.bid(None, &mut rng).unwrap()));
+        assert!((0.2..=0.4).contains(&strategy_range.bid(Some(9.0), &mut rng).unwrap()));
+        assert_eq!(margin.bid(Some(1.0), &mut rng), Some(1.5));
+        assert_eq!(margin.bid(None, &mut rng), Some(0.75));
+        // Never rounded down under the floor
+        assert_eq!(Price::default().bid(Some(0.123), &mut rng), Some(0.15));
+        assert_eq!(Price::default().bid(Some(0.001), &mut rng), Some(0.01));
+
+        // Shaded bids sit between the floor and the bidder's value; values
+        // under the floor pass
+        let bids: Vec<Option<f64>> = (0..2_000)
+            .map(|_| shaded.bid(Some(1.5), &mut rng))
+            .collect();
+        let passed = bids.iter().filter(|b| b.is_none()).count();
+        assert!((400..1_000).contains(&passed), "{passed}");
+        for bid in bids.iter().flatten() {
+            assert!(*bid >= 1.5, "{bid}");
+        }
+        let unfloored = shaded.bid(None, &mut rng).unwrap();
+        assert!(unfloored > 0.0 && unfloored < 10.0, "{unfloored}");
+
+        for bad in [
+            "price = { strategy = \"shaded\", median = 2.0, shade = 1.5 }",
+            "price = { strategy = \"floor_margin\", margin = -1.0 }",
+            "price = { strategy = \"range\", min = 2.0, max = 1.0 }",
+        ] {
+            let policy: Policy = toml::from_str(&format!("[default]\n{bad}")).unwrap();
+            assert!(policy.validate().is_err(), "{bad}");
+        }
+        for bad in [
+            "price = { strategy = \"fixed\", cpm = 1.0, margin = 0.1 }",
+            "price = { strategy = \"second_price\" }",
+        ] {
+            assert!(
+                toml::from_str::<Policy>(&format!("[default]\n{bad}")).is_err(),
+                "{bad}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_builtin_policy_bids_on_mrecs_at_floor_markup() {
+        let policy = Policy::builtin();
+        let mut rng = StdRng::seed_from_u64(2);
+        let req = request(serde_json::json!({
+            "id": "r",
+            "imp": [
+                { "id": "1", "banner": { "w": 300, "h": 250 }, "bidfloor": 1.0 },
+                { "id": "2", "banner": { "w": 320, "h": 50 } }
+            ]
+        }));
+        assert_eq!(
+            top_price(&policy.decide(&req, &req.imp[0], &mut rng)),
+            Some(1.2)
+        );
+        assert_eq!(top_price(&policy.decide(&req, &req.imp[1], &mut rng)), None);
+    }
+
+    #[test]
+    fn test_policy_files() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("policies/example.toml");
+        let policy = Policy::load(&path).unwrap();
+        assert!(!policy.rules.is_empty());
+
+        let bad: Policy = toml::from_str("[[rules]]\nbid_rate = 1.5").unwrap();
+        assert!(bad.validate().is_err());
+        let bad: Policy = toml::from_str("[default]\nprice = [2.0, 1.0]").unwrap();
+        assert!(bad.validate().is_err());
+        assert!(toml::from_str::<Policy>("[[rules]]\nfromat = \"300x250\"").is_err());
+    }
+}
@@ -0,0 +1,211 @@
+use rand::{seq::IndexedRandom, Rng};
+use serde_json::{json, Value};
+
+use crate::traffic::Draw;
+
+/// Where GDPR (or the UK's copy of it) applies: the EU, the rest of the EEA
+/// and the UK, as ISO-3166-1 alpha-3
+const GDPR_COUNTRIES: &[&str] = &[
+    "AUT", "BEL", "BGR", "HRV", "CYP", "CZE", "DNK", "EST", "FIN", "FRA", "DEU", "GRC", "HUN",
+    "IRL", "ITA", "LVA", "LTU", "LUX", "MLT", "NLD", "POL", "PRT", "ROU", "SVK", "SVN", "ESP",
+    "SWE", "ISL", "LIE", "NOR", "GBR",
+];
+
+const PHONE_UAS: &[&str] = &[
+    "Mozilla/5.0 (iPhone; CPU iPhone OS 17_5 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Mobile/15E148 Safari/604.1",
+    "Mozilla/5.0 (Linux; Android 14; SM-S918B) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.6478.122 Mobile Safari/537.36",
+    "Mozilla/5.0 (Linux; Android 14; Pixel 8) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.6478.122 Mobile Safari/537.36",
+];
+
+const TABLET_UAS: &[&str] = &[
+    "Mozilla/5.0 (iPad; CPU OS 17_5 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Mobile/15E148 Safari/604.1",
+    "Mozilla/5.0 (Linux; Android 13; SM-X710) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.6478.122 Safari/537.36",
+];
+
+const DESKTOP_UAS: &[&str] = &[
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36",
+    "Mozilla/5.0 (Macintosh; Intel Mac OS X 10_15_7) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.5 Safari/605.1.15",
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64; rv:127.0) Gecko/20100101 Firefox/127.0",
+    "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/126.0.0.0 Safari/537.36 Edg/126.0.0.0",
+];
+
+const TV_UAS: &[&str] = &[
+    "Roku/DVP-13.0 (13.0.0.4195-46)",
+    "Mozilla/5.0 (Linux; Android 11; AFTKA Build/RS8129) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.6099.230 Safari/537.36",
+    "Mozilla/5.0 (SMART-TV; LINUX; Tizen 7.0) AppleWebKit/537.36 (KHTML, like Gecko) 5.0 Chrome/94.0.4606.31 TV Safari/537.36",
+];
+
+/// Extended ID sources, the share of users each one resolves and its
+/// OpenRTB agent type (1 = cookie/device, 3 = person-based)
+const EID_SOURCES: &[(&str, f64, u8)] = &[
+    ("pubcid.org", 0.6, 1),
+    ("id5-sync.com", 0.45, 1),
+    ("liveramp.com", 0.25, 3),
+    ("uidapi.com", 0.2, 3),
+];
+
+/// Fill in who the request is for: device.devicetype/ua/ip/geo, user.id
+/// and user.eids, the privacy signals in regs and user.consent, and
+/// source.tid. Fields already on the request, such as user.data, are kept.
+pub fn fill<R: Rng>(request: &mut Value, draw: &Draw, rng: &mut R) {
+    let country = draw.country.code.as_str();
+    let gdpr = GDPR_COUNTRIES.contains(&country);
+    let consent = gdpr && rng.random_bool(0.8);
+
+    let ua = *match draw.device_type.devicetype {
+        1 | 4 => PHONE_UAS,
+        5 => TABLET_UAS,
+        3 | 7 => TV_UAS,
+        _ => DESKTOP_UAS,
+    }
+    .choose(rng)
+    .unwrap();
+    let device = &mut request["device"];
+    device["devicetype"] = json!(draw.device_type.devicetype);
+    device["ua"] = json!(ua);
+    if rng.random_bool(0.1) {
+        device["ipv6"] = json!(ipv6(rng));
+    } else {
+        // Without consent, SSPs drop the last octet
+        device["ip"] = json!(ipv4(rng, !gdpr || consent));
+    }
+    // type 2 = derived from the IP address
+    device["geo"] = json!({ "country": country, "type": 2 });
+
+    // Safari blocks third-party cookies, so SSP user ids are rarer there
+    let safari = ua.contains("Safari") && !ua.contains("Chrome");
+    let user = &mut request["user"];
+    if rng.random_bool(if safari { 0.4 } else { 0.85 }) {
+        user["id"] = json!(format!("u-{:016x}", rng.random::<u64>()));
+    }
+    let mut eids = Vec::new();
+    for (source, share, atype) in EID_SOURCES {
+        if rng.random_bool(*share) {
+            eids.push(json!({
+                "source": source,
+                "uids": [{ "id": format!("{:032x}", rng.random::<u128>()), "atype": atype }]
+            }));
+        }
+    }
+    if !eids.is_empty() {
+        user["eids"] = json!(eids);
+    }
+    if consent {
+        user["consent"] = json!(tcf_string(rng));
+    }
+
+    let regs = &mut request["regs"];
+    regs["gdpr"] = json!(u8::from(gdpr));
+    if country == "USA" {
+        // Mostly "notice given, no opt-out", some opt-outs of sale
+        regs["us_privacy"] = json!(*["1YNN", "1YNN", "1YNN", "1YNN", "1YNN", "1YYN", "1---"]
+            .choose(rng)
+            .unwrap());
+    }
+    if rng.random_bool(0.005) {
+        regs["coppa"] = json!(1);
+    }
+
+    request["source"]["tid"] = json!(uuid(rng));
+}
+
+/// A public-looking IPv4 address, with the last octet zeroed unless `full`
+fn ipv4<R: Rng>(rng: &mut R, full: bool) -> String {
+    let first = loop {
+        let octet: u8 = rng.random_range(1..=223);
+        // Skip private, loopback, CGNAT and link-local ranges
+        if !matches!(octet, 10 | 100 | 127 | 169 | 172 | 192) {
+            break octet;
+        }
+    };
+    let last = if full { rng.random_range(1..=254) } else { 0 };
+    format!(
+        "{}.{}.{}.{}",
+        first,
+        rng.random::<u8>(),
+        rng.random::<u8>(),
+        last
+    )
+}
+
+fn ipv6<R: Rng>(rng: &mut R) -> String {
+    format!(
+        "2a02:{:x}:{:x}:{:x}::",
+        rng.random::<u16>(),
+        rng.random::<u16>(),
+        rng.random::<u16>()
+    )
+}
+
+/// Looks like a TCF v2 consent string (base64url, starting "CP"); nothing
+/// reads its contents
+fn tcf_string<R: Rng>(rng: &mut R) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+    let body: String = (0..60)
+        .map(|_| *ALPHABET.choose(rng).unwrap() as char)
+        .collect();
+    format!("CP{}", body)
+}
+
+/// A random (version 4) UUID
+fn uuid<R: Rng>(rng: &mut R) -> String {
+    let n = rng.random::<u128>() & !(0xf << 76) & !(0x3 << 62) | (0x4 << 76) | (0x2 << 62);
+    let hex = format!("{:032x}", n);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traffic::{TrafficProfile, TrafficSampler};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_fill_is_consistent_with_the_draw() {
+        let sampler = TrafficSampler::new(TrafficProfile::builtin()).unwrap();
+        let mut rng = StdRng::seed_from_u64(17);
+        let mut with_consent = 0;
+        for _ in 0..2000 {
+            let draw = sampler.draw(&mut rng);
+            let mut request = json!({ "user": { "data": [{ "segment": [] }] } });
+            fill(&mut request, &draw, &mut rng);
+
+            let device = &request["device"];
+            let country = device["geo"]["country"].as_str().unwrap();
+            assert_eq!(country, draw.country.code);
+            assert_eq!(device["devicetype"], draw.device_type.devicetype);
+            let ua = device["ua"].as_str().unwrap();
+            if draw.device_type.devicetype == 4 {
+                assert!(ua.contains("Mobile"), "{ua}");
+            }
+            assert!(device["ip"].is_string() != device["ipv6"].is_string());
+
+            let gdpr = GDPR_COUNTRIES.contains(&country);
+            assert_eq!(request["regs"]["gdpr"], u8::from(gdpr));
+            assert_eq!(request["regs"]["us_privacy"].is_string(), country == "USA");
+            let consent = request["user"]["consent"].as_str();
+            assert!(gdpr || consent.is_none());
+            with_consent += usize::from(consent.is_some());
+            if gdpr && consent.is_none() {
+                if let Some(ip) = device["ip"].as_str() {
+                    assert!(ip.ends_with(".0"), "{ip}");
+                }
+            }
+
+            assert!(request["user"]["data"].is_array());
+            for eid in request["user"]["eids"].as_array().into_iter().flatten() {
+                assert_eq!(eid["uids"][0]["id"].as_str().unwrap().len(), 32);
+            }
+            let tid = request["source"]["tid"].as_str().unwrap();
+            assert_eq!((tid.len(), &tid[14..15]), (36, "4"));
+        }
+        assert!(with_consent > 0);
+    }
+}
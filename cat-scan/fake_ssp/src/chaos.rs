@@ -0,0 +1,171 @@
+use rand::{seq::IndexedRandom, Rng};
+use serde_json::{json, Value};
+
+/// A defect injected into a log line (--chaos), mimicking what real log
+/// pipelines deliver now and then
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// A line that isn't JSON at all
+    Malformed,
+    /// Banner impressions lose their banner object
+    MissingBanner,
+    /// Banner sizes of 0x0
+    ZeroSize,
+    /// Bid prices far beyond any real CPM
+    HugePrice,
+    /// The line is cut off half way, as after a crash mid-write
+    Truncated,
+}
+
+impl Fault {
+    const ALL: [Fault; 5] = [
+        Fault::Malformed,
+        Fault::MissingBanner,
+        Fault::ZeroSize,
+        Fault::HugePrice,
+        Fault::Truncated,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Fault::Malformed => "malformed",
+            Fault::MissingBanner => "missing banner",
+            Fault::ZeroSize => "zero size",
+            Fault::HugePrice => "huge price",
+            Fault::Truncated => "truncated",
+        }
+    }
+
+    /// With probability `rate`, one of the faults, each equally likely
+    pub fn roll<R: Rng>(rate: f64, rng: &mut R) -> Option<Fault> {
+        if rate > 0.0 && rng.random_bool(rate) {
+            Fault::ALL.choose(rng).copied()
+        } else {
+            None
+        }
+    }
+
+    /// The log line for `record` with this fault applied
+    pub fn apply(self, mut record: Value) -> String {
+        match self {
+            Fault::Malformed => {
+                return format!(
+                    "ts_ms={} request={} ERROR upstream closed connection",
+                    record["ts_ms"], record["request"]["id"]
+                );
+            }
+            Fault::Truncated => {
+                let line = record.to_string();
+                let mut cut = line.len() / 2;
+                while !line.is_char_boundary(cut) {
+                    cut -= 1;
+                }
+                return line[..cut].to_string();
+            }
+            Fault::MissingBanner => {
+                for imp in banner_imps(&mut record) {
+                    if let Some(imp) = imp.as_object_mut() {
+                        imp.remove("banner");
+                    }
+                }
+            }
+            Fault::ZeroSize => {
+                for imp in banner_imps(&mut record) {
+                    imp["banner"]["w"] = json!(0);
+                    imp["banner"]["h"] = json!(0);
+                    if let Some(formats) = imp["banner"]["format"].as_array_mut() {
+                        formats
+                            .iter_mut()
+                            .for_each(|f| *f = json!({ "w": 0, "h": 0 }));
+                    }
+                }
+            }
+            Fault::HugePrice => {
+                let price = json!(99_999.99);
+                let mut bids: Vec<&mut Value> = record["response"]["seatbid"]
+                    .as_array_mut()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|seatbid| seatbid["bid"].as_array_mut())
+                    .flatten()
+                    .collect();
+                if bids.is_empty() {
+                    // A no-bid gets a bid, so the fault always shows up
+                    let impid = record["request"]["imp"][0]["id"].clone();
+                    record["response"]["seatbid"] = json!([{
+                        "bid": [{ "id": "chaos-1", "impid": impid, "price": price }]
+                    }]);
+                } else {
+                    bids.iter_mut().for_each(|bid| bid["price"] = price.clone());
+                }
+            }
+        }
+        record.to_string()
+    }
+}
+
+fn banner_imps(record: &mut Value) -> impl Iterator<Item = &mut Value> {
+    record["request"]["imp"]
+        .as_array_mut()
+        .into_iter()
+        .flatten()
+        .filter(|imp| imp.get("banner").is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    fn record(response: Value) -> Value {
+        json!({
+            "ts_ms": 1700000000000u64,
+            "request": {
+                "id": "req-300x250-1",
+                "imp": [
+                    { "id": "1", "banner": { "w": 300, "h": 250, "format": [{ "w": 300, "h": 250 }] } },
+                    { "id": "2", "video": { "w": 640, "h": 360 } }
+                ]
+            },
+            "response": response
+        })
+    }
+
+    #[test]
+    fn test_faults_break_the_line() {
+        let bid = json!({ "seatbid": [{ "bid": [{ "price": 1.2 }, { "price": 0.8 }] }] });
+
+        let line = Fault::Malformed.apply(record(json!({})));
+        assert!(serde_json::from_str::<Value>(&line).is_err());
+        assert!(line.contains("req-300x250-1"));
+
+        let line = Fault::Truncated.apply(record(bid.clone()));
+        assert!(serde_json::from_str::<Value>(&line).is_err());
+        assert!(line.len() < record(bid.clone()).to_string().len());
+
+        let out: Value =
+            serde_json::from_str(&Fault::MissingBanner.apply(record(json!({})))).unwrap();
+        assert!(out["request"]["imp"][0].get("banner").is_none());
+        assert!(out["request"]["imp"][1].get("video").is_some());
+
+        let out: Value = serde_json::from_str(&Fault::ZeroSize.apply(record(json!({})))).unwrap();
+        assert_eq!(out["request"]["imp"][0]["banner"]["w"], 0);
+        assert_eq!(out["request"]["imp"][0]["banner"]["format"][0]["h"], 0);
+        assert_eq!(out["request"]["imp"][1]["video"]["w"], 640);
+
+        let out: Value = serde_json::from_str(&Fault::HugePrice.apply(record(bid))).unwrap();
+        assert_eq!(out["response"]["seatbid"][0]["bid"][1]["price"], 99_999.99);
+        let out: Value = serde_json::from_str(&Fault::HugePrice.apply(record(json!({})))).unwrap();
+        assert_eq!(out["response"]["seatbid"][0]["bid"][0]["impid"], "1");
+    }
+
+    #[test]
+    fn test_roll_respects_rate() {
+        let mut rng = StdRng::seed_from_u64(5);
+        assert!((0..1000).all(|_| Fault::roll(0.0, &mut rng).is_none()));
+        let faults = (0..10_000)
+            .filter(|_| Fault::roll(0.1, &mut rng).is_some())
+            .count();
+        assert!((900..1100).contains(&faults), "{faults}");
+    }
+}
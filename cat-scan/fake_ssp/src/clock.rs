@@ -0,0 +1,115 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rand::Rng;
+
+use crate::config::Config;
+
+/// The ts_ms each request is logged with
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Clock {
+    /// When the request is actually sent
+    Wall,
+    /// Spread over a simulated window that ends when the run starts, so a
+    /// one-minute run can leave a day's worth of timestamps (--time-window)
+    Window {
+        start_ms: u64,
+        window_ms: u64,
+        span: Span,
+    },
+}
+
+/// How far through the run a request is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Span {
+    /// The n-th of this many requests
+    Requests(u64),
+    /// Sent this long after the start of a run this long
+    Duration(Duration),
+}
+
+impl Clock {
+    pub fn new(config: &Config) -> Self {
+        let Some(window) = config.time_window else {
+            return Clock::Wall;
+        };
+        // Config only accepts a window for runs that stop by themselves
+        let span = match (config.max_requests, config.duration) {
+            (Some(n), _) => Span::Requests(n.max(1)),
+            (None, Some(d)) => Span::Duration(d),
+            (None, None) => return Clock::Wall,
+        };
+        let window_ms = window.as_millis() as u64;
+        Clock::Window {
+            start_ms: now_ms().saturating_sub(window_ms),
+            window_ms,
+            span,
+        }
+    }
+
+    /// The timestamp of request `n`, sent `elapsed` into the run
+    pub fn ts_ms<R: Rng>(&self, n: u64, elapsed: Duration, rng: &mut R) -> u64 {
+        match *self {
+            Clock::Wall => now_ms(),
+            Clock::Window {
+                start_ms,
+                window_ms,
+                span,
+            } => {
+                let offset = match span {
+                    // Somewhere in the request's own slice of the window
+                    Span::Requests(total) => {
+                        let slot = window_ms as f64 / total as f64;
+                        (n as f64 + rng.random::<f64>()) * slot
+                    }
+                    Span::Duration(d) => {
+                        elapsed.as_secs_f64() / d.as_secs_f64().max(1e-9) * window_ms as f64
+                    }
+                };
+                start_ms + (offset as u64).min(window_ms.saturating_sub(1))
+            }
+        }
+    }
+}
+
+/// Current timestamp in ms
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_window_spreads_timestamps() {
+        let mut rng = StdRng::seed_from_u64(9);
+        let day = 86_400_000;
+        let clock = Clock::Window {
+            start_ms: 1_000,
+            window_ms: day,
+            span: Span::Requests(4),
+        };
+        let stamps: Vec<u64> = (0..4)
+            .map(|n| clock.ts_ms(n, Duration::ZERO, &mut rng))
+            .collect();
+        for (n, ts) in stamps.iter().enumerate() {
+            let slot = 1_000 + n as u64 * day / 4;
+            assert!((slot..slot + day / 4).contains(ts), "{n}: {ts}");
+        }
+
+        let clock = Clock::Window {
+            start_ms: 0,
+            window_ms: day,
+            span: Span::Duration(Duration::from_secs(60)),
+        };
+        assert_eq!(clock.ts_ms(0, Duration::from_secs(30), &mut rng), day / 2);
+        // Stragglers after the deadline stay inside the window
+        assert_eq!(clock.ts_ms(0, Duration::from_secs(61), &mut rng), day - 1);
+
+        let wall = Clock::Wall.ts_ms(0, Duration::ZERO, &mut rng);
+        assert!(wall.abs_diff(now_ms()) < 1_000);
+    }
+}
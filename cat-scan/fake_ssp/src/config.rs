@@ -0,0 +1,362 @@
+use std::{path::PathBuf, time::Duration};
+
+use anyhow::{bail, Context, Result};
+use cat_config::{Setting, Settings};
+use fake_ssp::{
+    media::{self, Media},
+    request::CountRange,
+};
+use tokio::sync::Semaphore;
+
+/// Requests sent when neither a count, a duration nor --loop is given
+const DEFAULT_REQUESTS: u64 = 200;
+
+/// Requests per second unless --qps / TARGET_QPS says otherwise
+const DEFAULT_QPS: f64 = 10.0;
+
+const USAGE: &str = "Usage: fake_ssp [OPTIONS]\n\n\
+     Options (each also settable as FAKE_SSP_<OPTION>, e.g. FAKE_SSP_REPLAY_SPEED, through\n\
+     the environment variable shown, or in the [fake_ssp] section of the config file):\n  \
+     --config FILE       TOML settings file shared with fake_bidder and cat_scan (CAT_CONFIG)\n  \
+     --requests N        Stop after N requests (NUM_REQUESTS, default: 200)\n  \
+     --qps N             Target requests per second, 0 for as fast as possible (TARGET_QPS, default: 10)\n  \
+     --duration TIME     Stop after TIME, e.g. 90s, 15m, 2h (DURATION)\n  \
+     --loop              Run until interrupted with ctrl-c (LOOP=1)\n  \
+     --traffic FILE      Weighted traffic profile, TOML or JSON (TRAFFIC_PROFILE)\n  \
+     --seed N            Seed the random traffic for a reproducible run (SEED)\n  \
+     --media MIX         Media type ratios, e.g. banner=70,video=20,native=7,audio=3\n                         (MEDIA_MIX, overrides the profile's media list)\n  \
+     --imps N[-M]        Impressions per request, or a range to pick from (IMPS, default: 1)\n  \
+     --sizes N[-M]       Sizes per banner, listed in banner.format when over 1 (SIZES, default: 1)\n  \
+     --concurrency N     Requests in flight at once (CONCURRENCY, default: 1)\n  \
+     --chaos RATE        Fraction of log lines to corrupt, 0 to 1 (CHAOS, default: 0)\n  \
+     --time-window TIME  Spread the logged ts_ms over TIME ending at the start of the\n                         run, e.g. 24h in a 1 minute run (TIME_WINDOW)\n  \
+     --replay FILE       Re-send the requests of a fake_ssp log (.jsonl or .jsonl.gz)\n                         with their original spacing (REPLAY)\n  \
+     --replay-speed X    Replay X times faster than recorded (REPLAY_SPEED, default: 1)\n\n\
+     A run stops at whichever of --requests and --duration comes first; with\n\
+     only --duration it sends requests until the time is up. A replay sends\n\
+     the whole file unless limited, and --loop replays it over and over.\n\
+     ctrl-c (or SIGTERM) always stops the run after flushing the logs.";
+
+/// Everything fake_ssp reads through cat_config, flags or not: the
+/// options below plus where to send requests and logs (see main)
+pub const SETTINGS: &[Setting] = &[
+    Setting::value("requests").env("NUM_REQUESTS"),
+    Setting::value("qps").env("TARGET_QPS"),
+    Setting::value("duration").env("DURATION"),
+    Setting::switch("loop").env("LOOP"),
+    Setting::value("traffic").env("TRAFFIC_PROFILE"),
+    Setting::value("seed").env("SEED"),
+    Setting::value("media").env("MEDIA_MIX"),
+    Setting::value("imps").env("IMPS"),
+    Setting::value("sizes").env("SIZES"),
+    Setting::value("concurrency").env("CONCURRENCY"),
+    Setting::value("chaos").env("CHAOS"),
+    Setting::value("time_window").env("TIME_WINDOW"),
+    Setting::value("replay").env("REPLAY"),
+    Setting::value("replay_speed").env("REPLAY_SPEED"),
+    Setting::value("bidder_endpoint").env("BIDDER_ENDPOINT"),
+    Setting::value("log_destination").env("LOG_DESTINATION"),
+    Setting::value("log_file").env("LOG_FILE"),
+    Setting::switch("log_gzip").env("LOG_GZIP"),
+    Setting::value("log_roll_size").env("LOG_ROLL_SIZE"),
+    Setting::value("log_roll_interval").env("LOG_ROLL_INTERVAL"),
+    Setting::value("s3_bucket").env("S3_BUCKET"),
+    Setting::value("s3_prefix").env("S3_PREFIX"),
+    Setting::value("s3_batch_lines").env("S3_BATCH_LINES"),
+    Setting::value("s3_batch_size").env("S3_BATCH_SIZE"),
+    Setting::value("s3_flush_interval").env("S3_FLUSH_INTERVAL"),
+    Setting::value("s3_part_size").env("S3_PART_SIZE"),
+];
+
+/// How much traffic to generate
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// Stop after this many requests
+    pub max_requests: Option<u64>,
+    /// Requests per second; None sends as fast as the bidder answers
+    pub qps: Option<f64>,
+    /// Stop after this long
+    pub duration: Option<Duration>,
+    /// Traffic profile file; None uses the built-in mix
+    pub traffic: Option<PathBuf>,
+    /// Seed for the traffic draws; None seeds from the OS
+    pub seed: Option<u64>,
+    /// Media type mix replacing the profile's
+    pub media: Option<Vec<Media>>,
+    /// Impressions per request
+    pub imps: CountRange,
+    /// Sizes offered per banner impression
+    pub sizes: CountRange,
+    /// Requests in flight at once
+    pub concurrency: usize,
+    /// Fraction of log lines with an injected fault
+    pub chaos: f64,
+    /// Simulated time the logged timestamps are spread over
+    pub time_window: Option<Duration>,
+    /// Log file whose requests are re-sent instead of generated ones
+    pub replay: Option<PathBuf>,
+    /// How much faster than recorded a replay runs
+    pub replay_speed: f64,
+    /// Keep going until interrupted (--loop)
+    pub forever: bool,
+}
+
+impl Config {
+    /// Options from the environment and config file, overridden by
+    /// command-line flags
+    pub fn load(settings: &Settings) -> Result<Self> {
+        Self::parse(|name| settings.var(name), settings.args())
+    }
+
+    fn parse(env: impl Fn(&str) -> Option<String>, args: &[String]) -> Result<Self> {
+        let mut requests = env("NUM_REQUESTS");
+        let mut qps = env("TARGET_QPS");
+        let mut duration = env("DURATION");
+        let mut traffic = env("TRAFFIC_PROFILE");
+        let mut seed = env("SEED");
+        let mut media = env("MEDIA_MIX");
+        let mut imps = env("IMPS");
+        let mut sizes = env("SIZES");
+        let mut concurrency = env("CONCURRENCY");
+        let mut chaos = env("CHAOS");
+        let mut time_window = env("TIME_WINDOW");
+        let mut replay = env("REPLAY");
+        let mut replay_speed = env("REPLAY_SPEED");
+        let mut forever = env("LOOP").is_some_and(|v| matches!(v.as_str(), "1" | "true" | "yes"));
+
+        let mut i = 0;
+        while i < args.len() {
+            match args[i].as_str() {
+                "--help" | "-h" => bail!(USAGE),
+                "--loop" => {
+                    forever = true;
+                    i += 1;
+                }
+                flag @ ("--requests" | "--qps" | "--duration" | "--traffic" | "--seed"
+                | "--media" | "--imps" | "--sizes" | "--concurrency" | "--chaos"
+                | "--time-window" | "--replay" | "--replay-speed") => {
+                    let value = args
+                        .get(i + 1)
+                        .with_context(|| format!("{flag} requires a value"))?
+                        .clone();
+                    match flag {
+                        "--requests" => requests = Some(value),
+                        "--qps" => qps = Some(value),
+                        "--duration" => duration = Some(value),
+                        "--traffic" => traffic = Some(value),
+                        "--media" => media = Some(value),
+                        "--imps" => imps = Some(value),
+                        "--sizes" => sizes = Some(value),
+                        "--concurrency" => concurrency = Some(value),
+                        "--chaos" => chaos = Some(value),
+                        "--time-window" => time_window = Some(value),
+                        "--replay" => replay = Some(value),
+                        "--replay-speed" => replay_speed = Some(value),
+                        _ => seed = Some(value),
+                    }
+                    i += 2;
+                }
+                other => bail!("Unknown argument: {other}\n\n{USAGE}"),
+            }
+        }
+
+        let max_requests = match requests {
+            Some(n) => Some(
+                n.trim()
+                    .parse::<u64>()
+                    .with_context(|| format!("invalid request count '{n}'"))?,
+            ),
+            None => None,
+        };
+        let duration = duration.as_deref().map(parse_duration).transpose()?;
+        let seed = match seed {
+            Some(s) => Some(
+                s.trim()
+                    .parse::<u64>()
+                    .with_context(|| format!("invalid seed '{s}'"))?,
+            ),
+            None => None,
+        };
+        let qps = match qps {
+            Some(q) => q
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|q| q.is_finite() && *q >= 0.0)
+                .with_context(|| format!("invalid QPS '{q}'"))?,
+            None => DEFAULT_QPS,
+        };
+
+        let time_window = time_window.as_deref().map(parse_duration).transpose()?;
+        if time_window.is_some_and(|w| w.is_zero()) {
+            bail!("--time-window must be longer than 0s");
+        }
+        let replay = replay.map(PathBuf::from);
+        if time_window.is_some() && (forever || replay.is_some()) {
+            bail!("--time-window needs a run of known length; it can't be combined with --loop or --replay");
+        }
+        let replay_speed = match replay_speed {
+            Some(x) => x
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|x| x.is_finite() && *x > 0.0)
+                .with_context(|| format!("invalid replay speed '{x}'"))?,
+            None => 1.0,
+        };
+
+        Ok(Self {
+            max_requests: match (max_requests, duration, forever) {
+                (_, _, true) => None,
+                (Some(n), _, _) => Some(n),
+                (None, Some(_), _) => None,
+                // A replay sends the whole file
+                (None, None, false) if replay.is_some() => None,
+                (None, None, false) => Some(DEFAULT_REQUESTS),
+            },
+            qps: (qps > 0.0).then_some(qps),
+            duration: if forever { None } else { duration },
+            traffic: traffic.map(PathBuf::from),
+            seed,
+            media: media.as_deref().map(media::parse_mix).transpose()?,
+            imps: match imps {
+                Some(n) => CountRange::parse("impression count", &n)?,
+                None => CountRange::ONE,
+            },
+            sizes: match sizes {
+                Some(n) => CountRange::parse("size count", &n)?,
+                None => CountRange::ONE,
+            },
+            concurrency: match concurrency {
+                Some(n) => n
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| (1..=Semaphore::MAX_PERMITS).contains(n))
+                    .with_context(|| format!("invalid concurrency '{n}'"))?,
+                None => 1,
+            },
+            chaos: match chaos {
+                Some(rate) => rate
+                    .trim()
+                    .parse::<f64>()
+                    .ok()
+                    .filter(|r| (0.0..=1.0).contains(r))
+                    .with_context(|| format!("invalid chaos rate '{rate}', expected 0 to 1"))?,
+                None => 0.0,
+            },
+            time_window,
+            replay,
+            replay_speed,
+            forever,
+        })
+    }
+
+    /// Time between requests at the target QPS
+    pub fn interval(&self) -> Option<Duration> {
+        self.qps.map(|qps| Duration::from_secs_f64(1.0 / qps))
+    }
+}
+
+/// A duration like "90s", "15m", "2h" or "1d"; a bare number is seconds
+pub(crate) fn parse_duration(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (digits, unit) = value.split_at(split);
+    let amount: u64 = digits
+        .parse()
+        .with_context(|| format!("invalid duration '{value}', expected e.g. 30s, 15m, 2h"))?;
+    let multiplier = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        other => bail!("unknown duration unit '{other}' in '{value}', expected s|m|h|d"),
+    };
+    Ok(Duration::from_secs(amount * multiplier))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(env: &[(&str, &str)], args: &[&str]) -> Result<Config> {
+        let args: Vec<String> = args.iter().map(|a| a.to_string()).collect();
+        Config::parse(
+            |name| {
+                env.iter()
+                    .find(|(k, _)| *k == name)
+                    .map(|(_, v)| v.to_string())
+            },
+            &args,
+        )
+    }
+
+    #[test]
+    fn test_defaults_and_overrides() {
+        let config = parse(&[], &[]).unwrap();
+        assert_eq!(config.max_requests, Some(200));
+        assert_eq!(config.interval(), Some(Duration::from_millis(100)));
+        assert_eq!(config.duration, None);
+
+        // Flags win over the environment
+        let config = parse(
+            &[("NUM_REQUESTS", "50"), ("TARGET_QPS", "5")],
+            &["--qps", "0"],
+        )
+        .unwrap();
+        assert_eq!(config.max_requests, Some(50));
+        assert_eq!(config.qps, None);
+
+        // A duration alone lifts the default request count
+        let config = parse(&[("DURATION", "15m")], &[]).unwrap();
+        assert_eq!(config.max_requests, None);
+        assert_eq!(config.duration, Some(Duration::from_secs(900)));
+
+        let config = parse(&[], &["--requests", "10", "--loop"]).unwrap();
+        assert_eq!((config.max_requests, config.duration), (None, None));
+
+        assert!(parse(&[], &["--qps", "-1"]).is_err());
+        assert!(parse(&[], &["--duration", "5w"]).is_err());
+        assert!(parse(&[], &["--bogus"]).is_err());
+
+        let config = parse(&[("TRAFFIC_PROFILE", "mix.toml")], &["--seed", "42"]).unwrap();
+        assert_eq!(config.traffic, Some(PathBuf::from("mix.toml")));
+        assert_eq!(config.seed, Some(42));
+        assert!(parse(&[], &["--seed", "x"]).is_err());
+
+        let config = parse(&[("MEDIA_MIX", "video")], &["--media", "banner=3,audio=1"]).unwrap();
+        assert_eq!(config.media.map(|m| m.len()), Some(2));
+
+        let config = parse(&[("IMPS", "2")], &["--sizes", "1-3"]).unwrap();
+        assert_eq!(config.imps, CountRange { min: 2, max: 2 });
+        assert_eq!(config.sizes, CountRange { min: 1, max: 3 });
+        assert_eq!(parse(&[], &[]).unwrap().imps, CountRange::ONE);
+        assert!(parse(&[], &["--imps", "0"]).is_err());
+        assert!(parse(&[], &["--sizes", "4-2"]).is_err());
+
+        assert_eq!(parse(&[], &[]).unwrap().concurrency, 1);
+        assert_eq!(
+            parse(&[("CONCURRENCY", "64")], &[]).unwrap().concurrency,
+            64
+        );
+        assert!(parse(&[], &["--concurrency", "0"]).is_err());
+
+        assert_eq!(parse(&[], &["--chaos", "0.05"]).unwrap().chaos, 0.05);
+        assert!(parse(&[("CHAOS", "5%")], &[]).is_err());
+        assert!(parse(&[], &["--chaos", "1.5"]).is_err());
+
+        let config = parse(&[("TIME_WINDOW", "24h")], &["--duration", "1m"]).unwrap();
+        assert_eq!(config.time_window, Some(Duration::from_secs(86400)));
+        assert!(parse(&[], &["--time-window", "24h", "--loop"]).is_err());
+        assert!(parse(&[], &["--time-window", "0s"]).is_err());
+
+        let config = parse(&[("REPLAY", "old.jsonl")], &["--replay-speed", "10"]).unwrap();
+        assert_eq!(config.replay, Some(PathBuf::from("old.jsonl")));
+        assert_eq!((config.max_requests, config.replay_speed), (None, 10.0));
+        assert!(parse(&[], &["--replay", "a.jsonl", "--time-window", "1h"]).is_err());
+        assert!(parse(&[], &["--replay-speed", "0"]).is_err());
+    }
+}
@@ -0,0 +1,8 @@
+//! fake_ssp's traffic model, shared with `cat_scan generate-sample`: a
+//! weighted TrafficProfile, the sampler drawing from it, and the OpenRTB
+//! requests built from each draw.
+
+pub mod audience;
+pub mod media;
+pub mod request;
+pub mod traffic;
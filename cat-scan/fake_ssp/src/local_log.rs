@@ -0,0 +1,329 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{self, BufWriter, Write},
+    path::{Path, PathBuf},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context, Result};
+use cat_config::Settings;
+use flate2::{write::GzEncoder, Compression};
+
+use crate::config::parse_duration;
+
+/// Where and how local logs are written
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalLogConfig {
+    /// The log file, or the name rolled files are derived from
+    pub path: PathBuf,
+    pub gzip: bool,
+    /// Start a new file once the current one reaches this many bytes
+    pub roll_size: Option<u64>,
+    /// Start a new file once the current one is this old
+    pub roll_every: Option<Duration>,
+}
+
+impl LocalLogConfig {
+    /// LOG_FILE, LOG_GZIP, LOG_ROLL_SIZE and LOG_ROLL_INTERVAL
+    pub fn load(settings: &Settings) -> Result<Self> {
+        Self::parse(|name| settings.var(name))
+    }
+
+    fn parse(env: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let mut path =
+            PathBuf::from(env("LOG_FILE").unwrap_or_else(|| "fake_ssp_logs.jsonl".to_string()));
+        let gzip = path.extension().is_some_and(|ext| ext == "gz")
+            || env("LOG_GZIP").is_some_and(|v| matches!(v.as_str(), "1" | "true" | "yes"));
+        if gzip && path.extension().is_none_or(|ext| ext != "gz") {
+            path.as_mut_os_string().push(".gz");
+        }
+        Ok(Self {
+            path,
+            gzip,
+            roll_size: env("LOG_ROLL_SIZE")
+                .as_deref()
+                .map(parse_size)
+                .transpose()?,
+            roll_every: env("LOG_ROLL_INTERVAL")
+                .as_deref()
+                .map(parse_duration)
+                .transpose()?,
+        })
+    }
+
+    pub fn rolls(&self) -> bool {
+        self.roll_size.is_some() || self.roll_every.is_some()
+    }
+
+    /// `dir/fake_ssp_logs.jsonl.gz` -> `dir/fake_ssp_logs_<ms>.jsonl.gz`,
+    /// so rolled files sort by the time they were opened
+    fn rolled_path(&self, timestamp_ms: u128) -> PathBuf {
+        let name = self
+            .path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let (stem, suffix) = [".jsonl.gz", ".jsonl", ".gz"]
+            .iter()
+            .find_map(|suffix| name.strip_suffix(suffix).map(|stem| (stem, *suffix)))
+            .unwrap_or((&name, ""));
+        self.path
+            .with_file_name(format!("{stem}_{timestamp_ms}{suffix}"))
+    }
+}
+
+/// A byte size like "500000", "64K", "100MB" or "1G" (powers of 1024)
+pub(crate) fn parse_size(value: &str) -> Result<u64> {
+    let value = value.trim();
+    let split = value
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (digits, unit) = value.split_at(split);
+    let amount: u64 = digits
+        .parse()
+        .with_context(|| format!("invalid size '{value}', expected e.g. 64K, 100MB, 1G"))?;
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        other => bail!("unknown size unit '{other}' in '{value}', expected K, M or G"),
+    };
+    match amount.checked_mul(multiplier) {
+        Some(0) | None => bail!("invalid size '{value}'"),
+        Some(bytes) => Ok(bytes),
+    }
+}
+
+/// Counts the bytes that reach the file, compressed or not
+struct Counting<W> {
+    inner: W,
+    bytes: u64,
+}
+
+impl<W: Write> Write for Counting<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.bytes += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+enum Writer {
+    Plain(Counting<BufWriter<File>>),
+    Gzip(GzEncoder<Counting<BufWriter<File>>>),
+}
+
+/// The file being written
+struct Segment {
+    writer: Writer,
+    opened: Instant,
+}
+
+impl Segment {
+    fn bytes(&self) -> u64 {
+        match &self.writer {
+            Writer::Plain(w) => w.bytes,
+            Writer::Gzip(w) => w.get_ref().bytes,
+        }
+    }
+
+    fn finish(self) -> io::Result<()> {
+        match self.writer {
+            Writer::Plain(mut w) => w.flush(),
+            Writer::Gzip(w) => w.finish()?.flush(),
+        }
+    }
+}
+
+/// JSONL log on local disk, optionally gzipped and rolled by size or age
+/// (as logrotate or a log shipper would leave it). Without rolling, lines
+/// are appended to the one file; a gzipped file that is appended to gets
+/// another gzip member, which gzip tools read as one stream.
+pub struct LocalLog {
+    config: LocalLogConfig,
+    segment: Option<Segment>,
+    /// Files started so far
+    pub files: u64,
+}
+
+impl LocalLog {
+    pub fn new(config: LocalLogConfig) -> Self {
+        Self {
+            config,
+            segment: None,
+            files: 0,
+        }
+    }
+
+    pub fn config(&self) -> &LocalLogConfig {
+        &self.config
+    }
+
+    pub fn write_line(&mut self, line: &str) -> Result<()> {
+        let due = self.segment.as_ref().is_some_and(|segment| {
+            self.config
+                .roll_size
+                .is_some_and(|max| segment.bytes() >= max)
+                || self
+                    .config
+                    .roll_every
+                    .is_some_and(|every| segment.opened.elapsed() >= every)
+        });
+        if due {
+            self.finish()?;
+        }
+        if self.segment.is_none() {
+            self.segment = Some(self.open_segment()?);
+        }
+        let segment = self.segment.as_mut().expect("segment was just opened");
+        let result = match &mut segment.writer {
+            Writer::Plain(w) => writeln!(w, "{}", line),
+            Writer::Gzip(w) => writeln!(w, "{}", line),
+        };
+        result.context("Failed to write log line")
+    }
+
+    pub fn flush(&mut self) -> Result<()> {
+        if let Some(segment) = &mut self.segment {
+            match &mut segment.writer {
+                Writer::Plain(w) => w.flush()?,
+                Writer::Gzip(w) => w.flush()?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Close the current file (writing the gzip trailer); the next line
+    /// starts a new one
+    pub fn finish(&mut self) -> Result<()> {
+        if let Some(segment) = self.segment.take() {
+            segment.finish().context("Failed to finish log file")?;
+        }
+        Ok(())
+    }
+
+    fn open_segment(&mut self) -> Result<Segment> {
+        let path = if self.config.rolls() {
+            self.unused_rolled_path()
+        } else {
+            self.config.path.clone()
+        };
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("Failed to open log file: {}", path.display()))?;
+        if self.config.rolls() {
+            println!("  Writing {}", path.display());
+        }
+        self.files += 1;
+        let counting = Counting {
+            inner: BufWriter::new(file),
+            bytes: 0,
+        };
+        let writer = if self.config.gzip {
+            Writer::Gzip(GzEncoder::new(counting, Compression::default()))
+        } else {
+            Writer::Plain(counting)
+        };
+        Ok(Segment {
+            writer,
+            opened: Instant::now(),
+        })
+    }
+
+    fn unused_rolled_path(&self) -> PathBuf {
+        let mut timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis());
+        loop {
+            let path = self.config.rolled_path(timestamp);
+            if !Path::new(&path).exists() {
+                return path;
+            }
+            timestamp += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::MultiGzDecoder;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+    use std::io::Read;
+
+    fn config(env: &[(&str, &str)]) -> Result<LocalLogConfig> {
+        LocalLogConfig::parse(|name| {
+            env.iter()
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| v.to_string())
+        })
+    }
+
+    #[test]
+    fn test_config_from_env() {
+        let plain = config(&[]).unwrap();
+        assert_eq!(plain.path, PathBuf::from("fake_ssp_logs.jsonl"));
+        assert!(!plain.gzip && !plain.rolls());
+
+        let gzip = config(&[("LOG_GZIP", "1"), ("LOG_ROLL_SIZE", "64K")]).unwrap();
+        assert_eq!(gzip.path, PathBuf::from("fake_ssp_logs.jsonl.gz"));
+        assert_eq!(gzip.roll_size, Some(64 << 10));
+        assert_eq!(
+            gzip.rolled_path(1700000000000),
+            PathBuf::from("fake_ssp_logs_1700000000000.jsonl.gz")
+        );
+
+        let by_name = config(&[("LOG_FILE", "logs/bids.gz"), ("LOG_ROLL_INTERVAL", "1h")]).unwrap();
+        assert!(by_name.gzip);
+        assert_eq!(by_name.roll_every, Some(Duration::from_secs(3600)));
+        assert_eq!(by_name.rolled_path(7), PathBuf::from("logs/bids_7.gz"));
+
+        assert!(config(&[("LOG_ROLL_SIZE", "10 parsecs")]).is_err());
+        assert!(config(&[("LOG_ROLL_SIZE", "0")]).is_err());
+    }
+
+    #[test]
+    fn test_rolls_gzipped_files_by_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut log = LocalLog::new(LocalLogConfig {
+            path: dir.path().join("fake_ssp_logs.jsonl.gz"),
+            gzip: true,
+            roll_size: Some(16 << 10),
+            roll_every: None,
+        });
+        // Random ids compress poorly, so the encoder hands bytes to the file
+        // long before it is finished
+        let mut rng = StdRng::seed_from_u64(1);
+        let lines: Vec<String> = (0..4000)
+            .map(|i| format!(r#"{{"n":{i},"id":"{:032x}"}}"#, rng.random::<u128>()))
+            .collect();
+        for line in &lines {
+            log.write_line(line).unwrap();
+        }
+        log.finish().unwrap();
+
+        let mut paths: Vec<PathBuf> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().path())
+            .collect();
+        paths.sort();
+        assert!(paths.len() > 1, "{paths:?}");
+        assert_eq!(paths.len() as u64, log.files);
+
+        let mut text = String::new();
+        for path in &paths {
+            assert!(path.to_string_lossy().ends_with(".jsonl.gz"));
+            MultiGzDecoder::new(File::open(path).unwrap())
+                .read_to_string(&mut text)
+                .unwrap();
+        }
+        assert_eq!(text.lines().collect::<Vec<_>>(), lines);
+    }
+}
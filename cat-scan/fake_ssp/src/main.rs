@@ -1,129 +1,131 @@
-use std::{
-    env,
-    fs::OpenOptions,
-    io::Write,
-    time::{SystemTime, UNIX_EPOCH},
-};
+mod chaos;
+mod clock;
+mod config;
+mod local_log;
+mod replay;
+mod s3_log;
+
+use std::{collections::BTreeMap, env, sync::Arc, time::Duration};
 
 use anyhow::{Context, Result};
 use aws_sdk_s3::Client as S3Client;
+use cat_config::Settings;
+use fake_ssp::{
+    request,
+    traffic::{TrafficProfile, TrafficSampler},
+};
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use reqwest::Client;
 use serde_json::{json, Value};
-use tokio::time::{sleep, Duration};
+use tokio::{
+    sync::{mpsc, Semaphore},
+    time::{interval, sleep_until, Instant, MissedTickBehavior},
+};
+
+use chaos::Fault;
+use clock::Clock;
+use config::Config;
+use local_log::{LocalLog, LocalLogConfig};
+use replay::Replay;
+use s3_log::{S3Log, S3LogConfig};
 
 enum LogDestination {
-    LocalFile(std::fs::File),
-    S3 {
-        client: S3Client,
-        bucket: String,
-        prefix: String,
-        buffer: Vec<String>,
-    },
+    LocalFile(LocalLog),
+    S3(S3Log),
 }
 
 impl LogDestination {
-    async fn new_from_env() -> Result<Self> {
-        let destination_type = env::var("LOG_DESTINATION").unwrap_or_else(|_| "local".to_string());
+    async fn load(settings: &Settings) -> Result<Self> {
+        let destination_type = settings
+            .var("LOG_DESTINATION")
+            .unwrap_or_else(|| "local".to_string());
 
         match destination_type.as_str() {
             "s3" => {
-                let bucket = env::var("S3_BUCKET")
-                    .context("S3_BUCKET environment variable required when LOG_DESTINATION=s3")?;
-                let prefix = env::var("S3_PREFIX").unwrap_or_default();
+                let config = S3LogConfig::load(settings)?;
 
                 println!("Initializing S3 client...");
-                let config = aws_config::defaults(aws_config::BehaviorVersion::latest())
+                let aws_config = aws_config::defaults(aws_config::BehaviorVersion::latest())
                     .load()
                     .await;
-                let client = S3Client::new(&config);
-
-                println!("  S3 bucket: {}", bucket);
-                println!("  S3 prefix: {}", prefix);
+                // MinIO and other S3-compatible stores want path-style URLs
+                let s3_config = aws_sdk_s3::config::Builder::from(&aws_config)
+                    .force_path_style(env::var("AWS_ENDPOINT_URL").is_ok())
+                    .build();
+                let client = S3Client::from_conf(s3_config);
+
+                println!("  S3 bucket: {}", config.bucket);
+                println!("  S3 prefix: {}", config.prefix);
+                if config.gzip {
+                    println!("  Compression: gzip");
+                }
+                println!(
+                    "  Batches: {} lines, {} bytes or {:?}, whichever comes first",
+                    config.batch_lines, config.batch_bytes, config.flush_every
+                );
 
-                Ok(LogDestination::S3 {
-                    client,
-                    bucket,
-                    prefix,
-                    buffer: Vec::new(),
-                })
+                Ok(LogDestination::S3(S3Log::new(client, config)))
             }
-            "local" | _ => {
-                let log_file_path =
-                    env::var("LOG_FILE").unwrap_or_else(|_| "fake_ssp_logs.jsonl".to_string());
+            _ => {
+                let config = LocalLogConfig::load(settings)?;
 
                 println!("Using local file logging");
-                println!("  Log file: {}", log_file_path);
-
-                let file = OpenOptions::new()
-                    .create(true)
-                    .append(true)
-                    .open(&log_file_path)
-                    .with_context(|| format!("Failed to open log file: {}", log_file_path))?;
+                println!("  Log file: {}", config.path.display());
+                if config.gzip {
+                    println!("  Compression: gzip");
+                }
+                if let Some(bytes) = config.roll_size {
+                    println!("  Rolling every {} bytes", bytes);
+                }
+                if let Some(every) = config.roll_every {
+                    println!("  Rolling every {:?}", every);
+                }
 
-                Ok(LogDestination::LocalFile(file))
+                Ok(LogDestination::LocalFile(LocalLog::new(config)))
             }
         }
     }
 
     async fn write_log(&mut self, log_line: String) -> Result<()> {
         match self {
-            LogDestination::LocalFile(file) => {
-                writeln!(file, "{}", log_line)?;
-                Ok(())
-            }
-            LogDestination::S3 { buffer, .. } => {
-                buffer.push(log_line);
+            LogDestination::LocalFile(log) => log.write_line(&log_line),
+            LogDestination::S3(log) => log.write_line(log_line).await,
+        }
+    }
 
-                // Flush buffer every 50 lines or when buffer gets too large
-                if buffer.len() >= 50 {
-                    self.flush().await?;
-                }
-                Ok(())
+    /// How often the writer should call `tick`
+    fn tick_every(&self) -> Option<Duration> {
+        match self {
+            LogDestination::LocalFile(_) => None,
+            // Often enough that no line waits much past the interval
+            LogDestination::S3(log) => {
+                Some((log.config().flush_every / 4).max(Duration::from_millis(100)))
             }
         }
     }
 
-    async fn flush(&mut self) -> Result<()> {
+    /// Upload a batch that has waited long enough
+    async fn tick(&mut self) -> Result<()> {
         match self {
-            LogDestination::LocalFile(file) => {
-                file.flush()?;
+            LogDestination::LocalFile(_) => Ok(()),
+            LogDestination::S3(log) => log.flush_if_stale().await,
+        }
+    }
+
+    /// Flush, and close the destination for good
+    async fn close(&mut self) -> Result<()> {
+        match self {
+            LogDestination::LocalFile(log) => {
+                log.flush()?;
+                log.finish()?;
+                if log.config().rolls() {
+                    println!("  Wrote {} log file(s)", log.files);
+                }
                 Ok(())
             }
-            LogDestination::S3 {
-                client,
-                bucket,
-                prefix,
-                buffer,
-            } => {
-                if buffer.is_empty() {
-                    return Ok(());
-                }
-
-                let timestamp = SystemTime::now()
-                    .duration_since(UNIX_EPOCH)?
-                    .as_millis();
-
-                let key = if prefix.is_empty() {
-                    format!("fake_ssp_logs_{}.jsonl", timestamp)
-                } else {
-                    format!("{}/fake_ssp_logs_{}.jsonl", prefix.trim_end_matches('/'), timestamp)
-                };
-
-                let content = buffer.join("\n") + "\n";
-
-                println!("Flushing {} log lines to s3://{}/{}", buffer.len(), bucket, key);
-
-                client
-                    .put_object()
-                    .bucket(bucket.as_str())
-                    .key(&key)
-                    .body(content.into_bytes().into())
-                    .content_type("application/x-ndjson")
-                    .send()
-                    .await
-                    .with_context(|| format!("Failed to write to S3: s3://{}/{}", bucket, key))?;
-
-                buffer.clear();
+            LogDestination::S3(log) => {
+                log.close().await?;
+                println!("  Uploaded {} object(s)", log.objects);
                 Ok(())
             }
         }
@@ -131,134 +133,347 @@ impl LogDestination {
 }
 
 /// Simple fake SSP / publisher:
-/// - Cycles through a few banner sizes.
-/// - Sends OpenRTB-ish requests to fake_bidder.
+/// - Draws banner sizes, publishers, segments, media types (banner, video,
+///   native, audio), countries and device types from a weighted traffic
+///   profile.
+/// - Sends OpenRTB-ish requests to fake_bidder, with floors, tmax, device,
+///   user ids and privacy signals filled in.
 /// - Logs request + response to JSONL file or S3.
 ///
 /// Environment variables:
 /// - BIDDER_ENDPOINT: URL of bidder (default: http://127.0.0.1:3000/bid)
 /// - LOG_DESTINATION: "local" or "s3" (default: local)
 /// - LOG_FILE: Path to log file when using local (default: fake_ssp_logs.jsonl)
+/// - LOG_GZIP: "1" to gzip the logs, local or S3 (also on for a LOG_FILE ending in .gz)
+/// - LOG_ROLL_SIZE / LOG_ROLL_INTERVAL: start a new local file at this size
+///   (e.g. 100MB) or age (e.g. 1h); files are named <stem>_<unix ms>.jsonl[.gz]
 /// - S3_BUCKET: S3 bucket name when using s3 destination (required for s3)
 /// - S3_PREFIX: S3 prefix for log files when using s3 destination (optional)
+/// - S3_BATCH_LINES / S3_BATCH_SIZE / S3_FLUSH_INTERVAL: upload a batch once it
+///   has this many lines (default 50), bytes (default 64MB) or its oldest line
+///   is this old (default 10s)
+/// - S3_PART_SIZE: batches larger than this go up as a multipart upload in
+///   parts of this size (default 8MB)
+///
+/// How many requests to send, how fast and for how long come from flags or
+/// the environment too (see config::Config, `fake_ssp --help`). Each of
+/// these settings can also be given as FAKE_SSP_<NAME> (FAKE_SSP_LOG_FILE)
+/// or in the [fake_ssp] section of a --config / CAT_CONFIG file (log_file).
 #[tokio::main]
 async fn main() -> Result<()> {
-    // Configuration from environment
-    let bidder_endpoint =
-        env::var("BIDDER_ENDPOINT").unwrap_or_else(|_| "http://127.0.0.1:3000/bid".to_string());
+    let settings = Settings::load("fake_ssp", config::SETTINGS)?;
+    let config = Config::load(&settings)?;
+
+    let bidder_endpoint = settings
+        .var("BIDDER_ENDPOINT")
+        .unwrap_or_else(|| "http://127.0.0.1:3000/bid".to_string());
 
     println!("fake_ssp starting...");
     println!("  Bidder endpoint: {}", bidder_endpoint);
 
     // Initialize log destination
-    let mut log_dest = LogDestination::new_from_env().await?;
-
-    // HTTP client
-    let client = Client::new();
+    let log_dest = LogDestination::load(&settings).await?;
+
+    // HTTP client, shared by every in-flight request; keeps enough idle
+    // keep-alive connections around that each worker can reuse one
+    let client = Client::builder()
+        .pool_max_idle_per_host(config.concurrency)
+        .pool_idle_timeout(Duration::from_secs(90))
+        .tcp_keepalive(Duration::from_secs(60))
+        .tcp_nodelay(true)
+        .build()
+        .context("Failed to build HTTP client")?;
+
+    // What to send and how often
+    let mut profile = match &config.traffic {
+        Some(path) => {
+            println!("  Traffic profile: {}", path.display());
+            TrafficProfile::load(path)?
+        }
+        None => TrafficProfile::builtin(),
+    };
+    if let Some(mix) = &config.media {
+        profile.media = mix.clone();
+    }
+    let sampler = TrafficSampler::new(profile)?;
+    let mut rng = match config.seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => StdRng::from_os_rng(),
+    };
+
+    let mut replay = match &config.replay {
+        Some(path) => {
+            println!(
+                "Replaying {} at {}x the recorded pace...",
+                path.display(),
+                config.replay_speed
+            );
+            Some(Replay::open(path)?)
+        }
+        None => None,
+    };
+    match (config.max_requests, config.duration) {
+        (Some(n), Some(d)) => println!("Generating up to {} bid requests for up to {:?}...", n, d),
+        (Some(n), None) => println!("Generating {} bid requests...", n),
+        (None, Some(d)) => println!("Generating bid requests for {:?}...", d),
+        (None, None) if config.forever => println!("Generating bid requests until ctrl-c..."),
+        (None, None) => {}
+    }
+    match config.qps {
+        _ if replay.is_some() => {}
+        Some(qps) => println!("  Target QPS: {}", qps),
+        None => println!("  Target QPS: unthrottled"),
+    }
+    println!("  Concurrency: {}", config.concurrency);
+    if config.chaos > 0.0 {
+        println!("  Chaos: corrupting {}% of log lines", config.chaos * 100.0);
+    }
+    let clock = Clock::new(&config);
+    if let Some(window) = config.time_window {
+        println!("  Timestamps spread over the last {:?}", window);
+    }
 
-    // A few example formats to cycle through
-    let formats: &[(u32, u32)] = &[(300, 250), (320, 50), (160, 600), (728, 90)];
+    // Requests run as separate tasks, at most `concurrency` at a time, and
+    // hand their log lines to a single writer task that owns the destination
+    let semaphore = Arc::new(Semaphore::new(config.concurrency));
+    let (log_tx, log_rx) = mpsc::channel::<String>(config.concurrency * 4);
+    let writer = tokio::spawn(write_logs(log_dest, log_rx));
+
+    // Pace requests at the target QPS (a replay keeps its recorded pace);
+    // a slow bidder delays the schedule rather than causing a burst to
+    // catch up
+    let mut ticker = config
+        .interval()
+        .filter(|_| replay.is_none())
+        .map(|period| {
+            let mut ticker = interval(period);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            ticker
+        });
+    let started = Instant::now();
+    let deadline = config.duration.map(|d| started + d);
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    let mut sent: u64 = 0;
+    let mut faults: BTreeMap<&str, u64> = BTreeMap::new();
+    loop {
+        if config.max_requests.is_some_and(|max| sent >= max)
+            || deadline.is_some_and(|d| Instant::now() >= d)
+        {
+            break;
+        }
+        // The writer only stops early on an error, reported below
+        if log_tx.is_closed() {
+            break;
+        }
 
-    // Publishers and segments for realistic testing
-    let publishers: &[(&str, &str)] = &[
-        ("pub-news", "news.example.com"),
-        ("pub-sports", "sports.example.com"),
-        ("pub-tech", "tech.example.com"),
-    ];
+        let replayed = match &mut replay {
+            Some(replay) => match replay.next()? {
+                Some(replayed) => Some(replayed),
+                None if config.forever => {
+                    replay.rewind()?;
+                    match replay.next()? {
+                        Some(replayed) => Some(replayed),
+                        None => break,
+                    }
+                }
+                None => break,
+            },
+            None => None,
+        };
 
-    let segments: &[&str] = &["automotive", "travel", "finance", "entertainment"];
+        // Wait for the next tick (or the replayed request's time) and a
+        // free worker
+        let permit = tokio::select! {
+            _ = &mut shutdown => {
+                println!("Interrupted, stopping...");
+                break;
+            }
+            permit = async {
+                if let Some(replayed) = &replayed {
+                    sleep_until(started + replayed.offset.div_f64(config.replay_speed)).await;
+                } else if let Some(ticker) = &mut ticker {
+                    ticker.tick().await;
+                }
+                semaphore.clone().acquire_owned().await
+            } => permit.expect("semaphore is never closed"),
+        };
 
-    let mut format_idx = 0usize;
-    let mut pub_idx = 0usize;
-    let mut seg_idx = 0usize;
+        let ts_ms = clock.ts_ms(sent, started.elapsed(), &mut rng);
+        let (request, timeout) = match replayed {
+            Some(replayed) => {
+                let tmax = replayed.request["tmax"].as_u64();
+                (
+                    replayed.request,
+                    Timeout::After(tmax.map(Duration::from_millis)),
+                )
+            }
+            None => build_request(&sampler, &config, &mut rng, sent),
+        };
+        let fault = Fault::roll(config.chaos, &mut rng);
+        if let Some(fault) = fault {
+            *faults.entry(fault.label()).or_default() += 1;
+        }
+        let client = client.clone();
+        let endpoint = bidder_endpoint.clone();
+        let log_tx = log_tx.clone();
+        tokio::spawn(async move {
+            let response = call_bidder(&client, &endpoint, &request, timeout).await;
+
+            // Single log record
+            let log_line = json!({
+                "ts_ms": ts_ms,
+                "request": request,
+                "response": response,
+            });
+            let log_line = match fault {
+                Some(fault) => fault.apply(log_line),
+                None => log_line.to_string(),
+            };
+            // Fails only once the writer has stopped on an error
+            let _ = log_tx.send(log_line).await;
+            drop(permit);
+        });
 
-    // Send a bunch of requests then exit
-    // (You can bump this number or later change to a "loop { ... }")
-    let num_requests = 200;
-    println!("Generating {} bid requests...", num_requests);
+        sent += 1;
 
-    for i in 0..num_requests {
-        let (w, h) = formats[format_idx];
-        format_idx = (format_idx + 1) % formats.len();
+        // Progress indicator every 50 requests
+        if sent.is_multiple_of(50) {
+            println!("  Generated {} requests...", sent);
+        }
+    }
 
-        let (pub_id, pub_domain) = publishers[pub_idx];
-        pub_idx = (pub_idx + 1) % publishers.len();
+    // Let in-flight requests finish, then flush everything they logged
+    // (also after ctrl-c or SIGTERM)
+    let workers = u32::try_from(config.concurrency).unwrap_or(u32::MAX);
+    let _ = semaphore.acquire_many(workers).await;
+    drop(log_tx);
+    println!("Flushing remaining logs...");
+    writer.await.context("Log writer panicked")??;
+
+    let elapsed = started.elapsed().as_secs_f64();
+    println!(
+        "Done! Generated {} requests in {:.1}s ({:.0} requests/s).",
+        sent,
+        elapsed,
+        sent as f64 / elapsed.max(1e-9)
+    );
+    if !faults.is_empty() {
+        let injected: Vec<String> = faults
+            .iter()
+            .map(|(fault, n)| format!("{} {}", n, fault))
+            .collect();
+        println!("Injected faults: {}", injected.join(", "));
+    }
+    if let Some(replay) = replay.filter(|r| r.skipped > 0) {
+        println!(
+            "Skipped {} replay lines without a request or ts_ms",
+            replay.skipped
+        );
+    }
 
-        let segment = segments[seg_idx];
-        seg_idx = (seg_idx + 1) % segments.len();
+    Ok(())
+}
 
-        // Minimal OpenRTB-like request with publisher and segment info
-        let request = json!({
-            "id": format!("req-{}x{}-{}", w, h, i),
-            "source": {
-                "ssp": "fake_ssp"
-            },
-            "site": {
-                "publisher": {
-                    "id": pub_id
-                },
-                "domain": pub_domain
-            },
-            "user": {
-                "data": [{
-                    "segment": [{
-                        "id": segment
-                    }]
-                }]
-            },
-            "imp": [{
-                "id": "1",
-                "banner": {
-                    "w": w,
-                    "h": h
+/// Resolves on ctrl-c, or on SIGTERM as sent by `docker stop`, ECS and
+/// Kubernetes, so those shut the run down cleanly too
+async fn shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => {}
+                    _ = terminate.recv() => {}
                 }
-            }]
-        });
-
-        // Current timestamp in ms
-        let ts_ms = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis() as u64;
+            }
+            Err(_) => {
+                let _ = tokio::signal::ctrl_c().await;
+            }
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
+    }
+}
 
-        // Call fake_bidder
-        let response: Value = match client
-            .post(&bidder_endpoint)
-            .json(&request)
-            .send()
-            .await
-        {
-            Ok(resp) => match resp.json::<Value>().await {
-                Ok(json) => json,
-                Err(_) => json!({}), // bad JSON -> treat as empty response
+/// Write every line received, flushing batches that have waited too long,
+/// then close the destination once all senders are gone. The destination is
+/// closed after a failed write too, so the lines it holds are not lost.
+async fn write_logs(mut log_dest: LogDestination, mut lines: mpsc::Receiver<String>) -> Result<()> {
+    let mut ticker = log_dest.tick_every().map(|period| {
+        let mut ticker = interval(period);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ticker
+    });
+    let written = loop {
+        tokio::select! {
+            line = lines.recv() => match line {
+                Some(line) => {
+                    if let Err(err) = log_dest.write_log(line).await {
+                        break Err(err.context("Failed to write log line"));
+                    }
+                }
+                None => break Ok(()),
             },
-            Err(_) => json!({}), // network error -> empty response
-        };
+            _ = async { ticker.as_mut().unwrap().tick().await }, if ticker.is_some() => {
+                if let Err(err) = log_dest.tick().await {
+                    break Err(err);
+                }
+            }
+        }
+    };
+    // A failed close says where the unflushed lines went, so it wins
+    let closed = log_dest.close().await;
+    closed.and(written)
+}
 
-        // Single log record
-        let log_line = json!({
-            "ts_ms": ts_ms,
-            "request": request,
-            "response": response,
-        });
+/// A generated request, and how its SSP times it out
+fn build_request<R: Rng>(
+    sampler: &TrafficSampler,
+    config: &Config,
+    rng: &mut R,
+    n: u64,
+) -> (Value, Timeout) {
+    let (request, draw) = request::build_request(sampler, config.imps, config.sizes, rng, n);
+    let timeout = if draw.ssp.timeout_rate > 0.0 && rng.random_bool(draw.ssp.timeout_rate) {
+        Timeout::Always
+    } else {
+        Timeout::After(draw.ssp.tmax.map(Duration::from_millis))
+    };
+    (request, timeout)
+}
 
-        // Write log line
-        log_dest
-            .write_log(log_line.to_string())
-            .await
-            .context("Failed to write log line")?;
+/// When the SSP stops waiting for the bidder
+#[derive(Debug, Clone, Copy)]
+enum Timeout {
+    /// After tmax, if the SSP sets one
+    After(Option<Duration>),
+    /// Whatever the bidder does (the SSP's timeout_rate)
+    Always,
+}
 
-        // Progress indicator every 50 requests
-        if (i + 1) % 50 == 0 {
-            println!("  Generated {} requests...", i + 1);
+/// POST a request to fake_bidder; failures and timeouts are logged as an
+/// empty response
+async fn call_bidder(client: &Client, endpoint: &str, request: &Value, timeout: Timeout) -> Value {
+    let mut post = client.post(endpoint).json(request);
+    match timeout {
+        Timeout::After(Some(tmax)) => post = post.timeout(tmax),
+        Timeout::After(None) => {}
+        Timeout::Always => {
+            // The bidder still gets the request; its answer comes too late
+            let _ = post.send().await;
+            return json!({});
         }
-
-        // Small pause so we don't hammer localhost
-        sleep(Duration::from_millis(100)).await;
     }
-
-    // Final flush to ensure all logs are written
-    println!("Flushing remaining logs...");
-    log_dest.flush().await?;
-
-    println!("Done! Generated {} requests.", num_requests);
-
-    Ok(())
+    match post.send().await {
+        Ok(resp) => match resp.json::<Value>().await {
+            Ok(json) => json,
+            Err(_) => json!({}), // bad JSON -> treat as empty response
+        },
+        Err(_) => json!({}), // network error -> empty response
+    }
 }
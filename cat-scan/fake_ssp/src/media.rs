@@ -0,0 +1,262 @@
+use anyhow::{bail, Context, Result};
+use rand::{seq::IndexedRandom, Rng};
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::traffic::ImpDraw;
+
+/// The kind of impression a request offers
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaType {
+    Banner,
+    Video,
+    Native,
+    Audio,
+}
+
+impl MediaType {
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "banner" => Some(MediaType::Banner),
+            "video" => Some(MediaType::Video),
+            "native" => Some(MediaType::Native),
+            "audio" => Some(MediaType::Audio),
+            _ => None,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MediaType::Banner => "banner",
+            MediaType::Video => "video",
+            MediaType::Native => "native",
+            MediaType::Audio => "audio",
+        }
+    }
+}
+
+/// A media type and how often it's sent, relative to the others
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Media {
+    #[serde(rename = "type")]
+    pub media_type: MediaType,
+    #[serde(default = "crate::traffic::default_weight")]
+    pub weight: f64,
+}
+
+/// A mix like "banner=70,video=20,native=7,audio=3" (--media)
+pub fn parse_mix(spec: &str) -> Result<Vec<Media>> {
+    let mut mix = Vec::new();
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        let (name, weight) = part.split_once('=').unwrap_or((part, "1"));
+        let media_type = MediaType::parse(name.trim()).with_context(|| {
+            format!("unknown media type '{name}', expected banner|video|native|audio")
+        })?;
+        let weight: f64 = weight
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid weight in '{part}'"))?;
+        mix.push(Media { media_type, weight });
+    }
+    if mix.is_empty() {
+        bail!("empty media mix, expected e.g. banner=80,video=20");
+    }
+    Ok(mix)
+}
+
+/// Player sizes for video: the common instream ones, plus outstream in an MREC
+const PLAYER_SIZES: &[(u32, u32)] = &[(640, 360), (1280, 720), (1920, 1080), (300, 250)];
+
+/// OpenRTB protocols: VAST 2.0, 3.0, 2.0 wrapper, 3.0 wrapper, 4.0, 4.0 wrapper
+const VIDEO_PROTOCOLS: &[u32] = &[2, 3, 5, 6, 7, 8];
+
+/// OpenRTB protocols: VAST 2.0, 3.0, 4.0 and DAAST 1.0
+const AUDIO_PROTOCOLS: &[u32] = &[2, 3, 7, 9];
+
+/// The tag used in the request id, e.g. "300x250" or "native"
+pub fn tag(imp: &Value) -> String {
+    for media_type in [MediaType::Banner, MediaType::Video] {
+        let size = &imp[media_type.label()];
+        if size.is_object() {
+            return format!("{}x{}", size["w"], size["h"]);
+        }
+    }
+    [MediaType::Native, MediaType::Audio]
+        .into_iter()
+        .find(|m| imp.get(m.label()).is_some())
+        .map_or("imp", MediaType::label)
+        .to_string()
+}
+
+/// The impression object with the given id; banners use the drawn sizes
+/// (listing them all in banner.format when there are several), the other
+/// types pick their own realistic parameters
+pub fn imp<R: Rng>(id: usize, draw: &ImpDraw, rng: &mut R) -> Value {
+    let mut imp = match draw.media_type {
+        MediaType::Banner => banner_imp(draw),
+        MediaType::Video => video_imp(rng),
+        MediaType::Native => native_imp(rng),
+        MediaType::Audio => audio_imp(rng),
+    };
+    imp["id"] = json!(id.to_string());
+    imp
+}
+
+fn banner_imp(draw: &ImpDraw) -> Value {
+    let primary = draw.formats[0];
+    let mut banner = json!({ "w": primary.w, "h": primary.h });
+    if draw.formats.len() > 1 {
+        banner["format"] = draw
+            .formats
+            .iter()
+            .map(|f| json!({ "w": f.w, "h": f.h }))
+            .collect();
+    }
+    json!({ "banner": banner })
+}
+
+fn video_imp<R: Rng>(rng: &mut R) -> Value {
+    let &(w, h) = PLAYER_SIZES.choose(rng).unwrap();
+    let outstream = (w, h) == (300, 250);
+    let maxduration = *[15, 30, 60].choose(rng).unwrap();
+    // Pre-roll mostly, then mid- and post-roll (startdelay -1 / -2)
+    let startdelay = if outstream {
+        0
+    } else {
+        *[0, 0, 0, -1, -2].choose(rng).unwrap()
+    };
+    json!({
+        "video": {
+            "mimes": ["video/mp4", "video/webm", "application/javascript"],
+            "w": w,
+            "h": h,
+            "minduration": 5,
+            "maxduration": maxduration,
+            "protocols": VIDEO_PROTOCOLS,
+            "startdelay": startdelay,
+            // plcmt 1 = instream, 4 = standalone outstream
+            "plcmt": if outstream { 4 } else { 1 },
+            "linearity": 1,
+            "skip": u8::from(maxduration > 15),
+            "skipafter": 5,
+            "playbackmethod": if outstream { [6] } else { [1] },
+            "api": [2, 7]
+        }
+    })
+}
+
+fn audio_imp<R: Rng>(rng: &mut R) -> Value {
+    // feed 1 = music service, 3 = podcast
+    let podcast = rng.random_bool(0.3);
+    json!({
+        "audio": {
+            "mimes": ["audio/mp4", "audio/mpeg"],
+            "minduration": 5,
+            "maxduration": if podcast { 60 } else { *[15, 30].choose(rng).unwrap() },
+            "protocols": AUDIO_PROTOCOLS,
+            "startdelay": if podcast { -1 } else { 0 },
+            "feed": if podcast { 3 } else { 1 },
+            "maxseq": if podcast { 3 } else { 1 },
+            "stitched": u8::from(podcast)
+        }
+    })
+}
+
+/// A Native 1.2 request (sent as a string, as the spec requires): title,
+/// main image, sponsor and description, and sometimes an icon and a call to
+/// action
+fn native_imp<R: Rng>(rng: &mut R) -> Value {
+    let mut assets = vec![
+        json!({ "id": 1, "required": 1, "title": { "len": 90 } }),
+        json!({ "id": 2, "required": 1, "img": { "type": 3, "wmin": 1200, "hmin": 627 } }),
+        json!({ "id": 3, "required": 1, "data": { "type": 1, "len": 25 } }),
+        json!({ "id": 4, "required": 0, "data": { "type": 2, "len": 140 } }),
+    ];
+    if rng.random_bool(0.5) {
+        assets.push(json!({ "id": 5, "required": 0, "img": { "type": 1, "w": 80, "h": 80 } }));
+    }
+    if rng.random_bool(0.5) {
+        assets.push(json!({ "id": 6, "required": 0, "data": { "type": 12, "len": 15 } }));
+    }
+    // context 1 = content feed, 2 = social; plcmttype 1 = in-feed, 4 = recommendation widget
+    let (context, plcmttype) = *[(1, 1), (2, 1), (1, 4)].choose(rng).unwrap();
+    let request = json!({
+        "ver": "1.2",
+        "context": context,
+        "plcmttype": plcmttype,
+        "plcmtcnt": 1,
+        "assets": assets,
+        "eventtrackers": [{ "event": 1, "methods": [1, 2] }]
+    });
+    json!({
+        "native": { "request": request.to_string(), "ver": "1.2" }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traffic::Format;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_parse_mix() {
+        let mix = parse_mix("banner=70, video=20,native").unwrap();
+        assert_eq!(mix.len(), 3);
+        assert_eq!(mix[1].media_type, MediaType::Video);
+        assert_eq!(mix[1].weight, 20.0);
+        assert_eq!(mix[2].weight, 1.0);
+
+        assert!(parse_mix("banner=70,popunder=5").is_err());
+        assert!(parse_mix("video=lots").is_err());
+        assert!(parse_mix("").is_err());
+    }
+
+    #[test]
+    fn test_imps_carry_their_media_object() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let formats = [(320, 50), (300, 50)].map(|(w, h)| Format { w, h, weight: 1.0 });
+        let draw = |media_type, sizes: usize| ImpDraw {
+            media_type,
+            formats: formats.iter().take(sizes).collect(),
+        };
+
+        let banner = imp(1, &draw(MediaType::Banner, 1), &mut rng);
+        assert_eq!(
+            banner,
+            json!({ "id": "1", "banner": { "w": 320, "h": 50 } })
+        );
+        assert_eq!(tag(&banner), "320x50");
+
+        let multi = imp(2, &draw(MediaType::Banner, 2), &mut rng);
+        assert_eq!(multi["id"], "2");
+        assert_eq!(multi["banner"]["w"], 320);
+        assert_eq!(
+            multi["banner"]["format"],
+            json!([{ "w": 320, "h": 50 }, { "w": 300, "h": 50 }])
+        );
+
+        let video = imp(1, &draw(MediaType::Video, 0), &mut rng);
+        assert!(video["video"]["mimes"]
+            .as_array()
+            .is_some_and(|m| !m.is_empty()));
+        assert!(video["video"]["maxduration"].as_u64().unwrap() >= 15);
+        assert_eq!(
+            tag(&video),
+            format!("{}x{}", video["video"]["w"], video["video"]["h"])
+        );
+
+        let audio = imp(1, &draw(MediaType::Audio, 0), &mut rng);
+        assert!(audio["audio"]["protocols"].as_array().is_some());
+        assert_eq!(tag(&audio), "audio");
+
+        let native = imp(1, &draw(MediaType::Native, 0), &mut rng);
+        let request: Value =
+            serde_json::from_str(native["native"]["request"].as_str().unwrap()).unwrap();
+        assert_eq!(request["ver"], "1.2");
+        assert!(request["assets"].as_array().unwrap().len() >= 4);
+        assert_eq!(tag(&native), "native");
+    }
+}
@@ -0,0 +1,149 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use flate2::read::MultiGzDecoder;
+use serde_json::Value;
+
+/// A request read back from a log, and when to send it
+pub struct Replayed {
+    pub request: Value,
+    /// Since the start of the replay, at the recorded pace
+    pub offset: Duration,
+}
+
+/// Reads the requests of a fake_ssp log (plain or gzipped JSONL) in file
+/// order, with each one's offset from the first line's ts_ms. Lines without
+/// a request object or a ts_ms (including corrupted ones) are skipped.
+pub struct Replay {
+    path: PathBuf,
+    lines: Box<dyn BufRead + Send>,
+    /// ts_ms of the first request this lap
+    first_ms: Option<u64>,
+    /// Where this lap starts, after any earlier laps (--loop)
+    lap_start: Duration,
+    last_offset: Duration,
+    pub skipped: u64,
+}
+
+impl Replay {
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            lines: open_lines(path)?,
+            first_ms: None,
+            lap_start: Duration::ZERO,
+            last_offset: Duration::ZERO,
+            skipped: 0,
+        })
+    }
+
+    /// The next request, or None at the end of the file
+    pub fn next(&mut self) -> Result<Option<Replayed>> {
+        let mut line = String::new();
+        loop {
+            line.clear();
+            let read = self
+                .lines
+                .read_line(&mut line)
+                .with_context(|| format!("Failed to read replay file: {}", self.path.display()))?;
+            if read == 0 {
+                return Ok(None);
+            }
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut record: Value = match serde_json::from_str(&line) {
+                Ok(record) => record,
+                Err(_) => {
+                    self.skipped += 1;
+                    continue;
+                }
+            };
+            let (Some(ts_ms), true) = (record["ts_ms"].as_u64(), record["request"].is_object())
+            else {
+                self.skipped += 1;
+                continue;
+            };
+            let first_ms = *self.first_ms.get_or_insert(ts_ms);
+            // Lines logged out of order (concurrent runs) go out right away
+            let offset = self.lap_start + Duration::from_millis(ts_ms.saturating_sub(first_ms));
+            self.last_offset = self.last_offset.max(offset);
+            return Ok(Some(Replayed {
+                request: record["request"].take(),
+                offset,
+            }));
+        }
+    }
+
+    /// Start over from the top of the file, continuing the schedule after
+    /// the last request of this lap
+    pub fn rewind(&mut self) -> Result<()> {
+        self.lines = open_lines(&self.path)?;
+        self.first_ms = None;
+        self.lap_start = self.last_offset + Duration::from_millis(1);
+        Ok(())
+    }
+}
+
+fn open_lines(path: &Path) -> Result<Box<dyn BufRead + Send>> {
+    let file = File::open(path)
+        .with_context(|| format!("Failed to open replay file: {}", path.display()))?;
+    Ok(if path.extension().is_some_and(|ext| ext == "gz") {
+        Box::new(BufReader::new(MultiGzDecoder::new(file)))
+    } else {
+        Box::new(BufReader::new(file))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    const LOG: &str = r#"{"ts_ms":1000,"request":{"id":"req-1"},"response":{}}
+ts_ms=1200 request="req-2" ERROR upstream closed connection
+{"ts_ms":1500,"request":{"id":"req-3"},"response":{}}
+
+{"ts_ms":900,"request":{"id":"req-4"},"response":{}}
+{"ts_ms":1600,"response":{}}
+"#;
+
+    fn ids_and_offsets(replay: &mut Replay) -> Vec<(String, u64)> {
+        std::iter::from_fn(|| replay.next().unwrap())
+            .map(|r| {
+                let id = r.request["id"].as_str().unwrap().to_string();
+                (id, r.offset.as_millis() as u64)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_replay_keeps_relative_timing() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("old.jsonl.gz");
+        let mut gz = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        gz.write_all(LOG.as_bytes()).unwrap();
+        gz.finish().unwrap();
+
+        let mut replay = Replay::open(&path).unwrap();
+        let expected = [("req-1", 0), ("req-3", 500), ("req-4", 0)];
+        let expected: Vec<(String, u64)> = expected
+            .iter()
+            .map(|(id, ms)| (id.to_string(), *ms))
+            .collect();
+        assert_eq!(ids_and_offsets(&mut replay), expected);
+        assert_eq!(replay.skipped, 2);
+
+        // A second lap carries on after the first
+        replay.rewind().unwrap();
+        let second = ids_and_offsets(&mut replay);
+        assert_eq!(second[0], ("req-1".to_string(), 501));
+        assert_eq!(second[1], ("req-3".to_string(), 1001));
+    }
+}
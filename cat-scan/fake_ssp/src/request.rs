@@ -0,0 +1,115 @@
+use anyhow::{bail, Result};
+use rand::Rng;
+use serde_json::{json, Value};
+
+use crate::{
+    audience, media,
+    traffic::{Draw, TrafficSampler},
+};
+
+/// A count picked uniformly from `min..=max` for each request or impression
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CountRange {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl CountRange {
+    pub const ONE: CountRange = CountRange { min: 1, max: 1 };
+
+    /// "3" or "1-4"; both ends at least 1
+    pub fn parse(what: &str, value: &str) -> Result<Self> {
+        let value = value.trim();
+        let (min, max) = value.split_once('-').unwrap_or((value, value));
+        let parse = |n: &str| n.trim().parse::<usize>().ok().filter(|n| *n >= 1);
+        match (parse(min), parse(max)) {
+            (Some(min), Some(max)) if min <= max => Ok(Self { min, max }),
+            _ => bail!("invalid {what} '{value}', expected N or MIN-MAX with 1 <= MIN <= MAX"),
+        }
+    }
+
+    pub fn pick<R: Rng>(&self, rng: &mut R) -> usize {
+        if self.min == self.max {
+            self.min
+        } else {
+            rng.random_range(self.min..=self.max)
+        }
+    }
+}
+
+/// OpenRTB-like request number `n` with publisher, segment, device, user
+/// and privacy info, and the draw it was built from
+pub fn build_request<'a, R: Rng>(
+    sampler: &'a TrafficSampler,
+    imps: CountRange,
+    sizes: CountRange,
+    rng: &mut R,
+    n: u64,
+) -> (Value, Draw<'a>) {
+    let draw = sampler.draw(rng);
+    let imps: Vec<Value> = (1..=imps.pick(rng))
+        .map(|id| {
+            let imp = sampler.draw_imp(&draw, sizes.pick(rng), rng);
+            let mut imp = media::imp(id, &imp, rng);
+            if let Some(floor) = draw.ssp.floor {
+                imp["bidfloor"] = json!(floor.sample(rng));
+                imp["bidfloorcur"] = json!("USD");
+            }
+            imp
+        })
+        .collect();
+
+    let mut request = json!({
+        "id": format!("req-{}-{}", media::tag(&imps[0]), n),
+        "source": {
+            "ssp": draw.ssp.name
+        },
+        "site": {
+            "publisher": {
+                "id": draw.publisher.id
+            },
+            "domain": draw.publisher.domain
+        },
+        "user": {
+            "data": [{
+                "segment": [{
+                    "id": draw.segment.id
+                }]
+            }]
+        },
+        "imp": imps
+    });
+    audience::fill(&mut request, &draw, rng);
+    if let Some(tmax) = draw.ssp.tmax {
+        request["tmax"] = json!(tmax);
+    }
+    (request, draw)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::traffic::TrafficProfile;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_build_request() {
+        let sampler = TrafficSampler::new(TrafficProfile::builtin()).unwrap();
+        let imps = CountRange::parse("impression count", "2-3").unwrap();
+        let mut rng = StdRng::seed_from_u64(7);
+        let (request, draw) = build_request(&sampler, imps, CountRange::ONE, &mut rng, 5);
+        let count = request["imp"].as_array().unwrap().len();
+        assert!((2..=3).contains(&count));
+        assert!(request["id"].as_str().unwrap().ends_with("-5"));
+        assert_eq!(request["source"]["ssp"], draw.ssp.name);
+        assert_eq!(request["site"]["publisher"]["id"], draw.publisher.id);
+
+        // The same seed builds the same request
+        let mut again = StdRng::seed_from_u64(7);
+        assert_eq!(
+            build_request(&sampler, imps, CountRange::ONE, &mut again, 5).0,
+            request
+        );
+        assert!(CountRange::parse("size count", "3-1").is_err());
+    }
+}
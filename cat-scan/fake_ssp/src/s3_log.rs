@@ -0,0 +1,401 @@
+use std::{
+    fs,
+    io::Write,
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{bail, Context, Result};
+use aws_sdk_s3::{
+    primitives::ByteStream,
+    types::{CompletedMultipartUpload, CompletedPart},
+    Client as S3Client,
+};
+use cat_config::Settings;
+use flate2::{write::GzEncoder, Compression};
+
+use crate::{config::parse_duration, local_log::parse_size};
+
+/// Lines per object unless S3_BATCH_LINES says otherwise
+const DEFAULT_BATCH_LINES: usize = 50;
+
+/// Uncompressed bytes per object unless S3_BATCH_SIZE says otherwise
+const DEFAULT_BATCH_BYTES: u64 = 64 << 20;
+
+/// Longest a line waits in the buffer unless S3_FLUSH_INTERVAL says otherwise
+const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Objects larger than this go up as a multipart upload in parts of this
+/// size (S3 needs at least 5 MiB for every part but the last)
+const DEFAULT_PART_SIZE: u64 = 8 << 20;
+const MIN_PART_SIZE: u64 = 5 << 20;
+
+/// Where and how logs are batched into S3 objects
+#[derive(Debug, Clone, PartialEq)]
+pub struct S3LogConfig {
+    pub bucket: String,
+    pub prefix: String,
+    pub gzip: bool,
+    /// Upload once the buffer holds this many lines...
+    pub batch_lines: usize,
+    /// ...or this many bytes of JSONL...
+    pub batch_bytes: u64,
+    /// ...or its oldest line is this old
+    pub flush_every: Duration,
+    /// Objects over this size are uploaded in parts of this size
+    pub part_size: u64,
+}
+
+impl S3LogConfig {
+    /// S3_BUCKET, S3_PREFIX, LOG_GZIP, S3_BATCH_LINES, S3_BATCH_SIZE,
+    /// S3_FLUSH_INTERVAL and S3_PART_SIZE
+    pub fn load(settings: &Settings) -> Result<Self> {
+        Self::parse(|name| settings.var(name))
+    }
+
+    fn parse(env: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let bucket = env("S3_BUCKET")
+            .context("S3_BUCKET (s3_bucket in the config file) required when LOG_DESTINATION=s3")?;
+        let part_size = match env("S3_PART_SIZE") {
+            Some(size) => {
+                let bytes = parse_size(&size)?;
+                if bytes < MIN_PART_SIZE {
+                    bail!("S3_PART_SIZE '{size}' is below the 5MB S3 minimum");
+                }
+                bytes
+            }
+            None => DEFAULT_PART_SIZE,
+        };
+        Ok(Self {
+            bucket,
+            prefix: env("S3_PREFIX").unwrap_or_default(),
+            gzip: env("LOG_GZIP").is_some_and(|v| matches!(v.as_str(), "1" | "true" | "yes")),
+            batch_lines: match env("S3_BATCH_LINES") {
+                Some(n) => n
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .filter(|n| *n >= 1)
+                    .with_context(|| format!("invalid S3_BATCH_LINES '{n}'"))?,
+                None => DEFAULT_BATCH_LINES,
+            },
+            batch_bytes: match env("S3_BATCH_SIZE") {
+                Some(size) => parse_size(&size)?,
+                None => DEFAULT_BATCH_BYTES,
+            },
+            flush_every: match env("S3_FLUSH_INTERVAL") {
+                Some(every) => Some(parse_duration(&every)?)
+                    .filter(|d| !d.is_zero())
+                    .with_context(|| format!("invalid S3_FLUSH_INTERVAL '{every}'"))?,
+                None => DEFAULT_FLUSH_INTERVAL,
+            },
+            part_size,
+        })
+    }
+
+    /// `prefix/fake_ssp_logs_<ms>.jsonl[.gz]`
+    fn key(&self, timestamp_ms: u128) -> String {
+        let extension = if self.gzip { "jsonl.gz" } else { "jsonl" };
+        if self.prefix.is_empty() {
+            format!("fake_ssp_logs_{}.{}", timestamp_ms, extension)
+        } else {
+            format!(
+                "{}/fake_ssp_logs_{}.{}",
+                self.prefix.trim_end_matches('/'),
+                timestamp_ms,
+                extension
+            )
+        }
+    }
+}
+
+/// Lines waiting to be uploaded
+#[derive(Default)]
+struct Batch {
+    lines: Vec<String>,
+    bytes: u64,
+    oldest: Option<Instant>,
+}
+
+impl Batch {
+    fn push(&mut self, line: String) {
+        self.bytes += line.len() as u64 + 1;
+        self.oldest.get_or_insert_with(Instant::now);
+        self.lines.push(line);
+    }
+
+    fn is_full(&self, config: &S3LogConfig) -> bool {
+        self.lines.len() >= config.batch_lines || self.bytes >= config.batch_bytes
+    }
+
+    fn is_stale(&self, config: &S3LogConfig) -> bool {
+        self.oldest
+            .is_some_and(|oldest| oldest.elapsed() >= config.flush_every)
+    }
+
+    /// The object body: JSONL, gzipped if asked for
+    fn body(&self, gzip: bool) -> Result<Vec<u8>> {
+        let mut content = Vec::with_capacity(self.bytes as usize);
+        for line in &self.lines {
+            content.extend_from_slice(line.as_bytes());
+            content.push(b'\n');
+        }
+        if gzip {
+            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(&content)?;
+            content = encoder.finish()?;
+        }
+        Ok(content)
+    }
+}
+
+/// JSONL log batched into S3 objects by line count, size and age. A batch
+/// that fails to upload stays buffered for the next attempt, and whatever
+/// still can't be uploaded at shutdown is saved to a local file.
+pub struct S3Log {
+    client: S3Client,
+    config: S3LogConfig,
+    batch: Batch,
+    /// Objects uploaded so far
+    pub objects: u64,
+}
+
+impl S3Log {
+    pub fn new(client: S3Client, config: S3LogConfig) -> Self {
+        Self {
+            client,
+            config,
+            batch: Batch::default(),
+            objects: 0,
+        }
+    }
+
+    pub fn config(&self) -> &S3LogConfig {
+        &self.config
+    }
+
+    pub async fn write_line(&mut self, line: String) -> Result<()> {
+        self.batch.push(line);
+        if self.batch.is_full(&self.config) {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    /// Upload the buffer if its oldest line has waited long enough
+    pub async fn flush_if_stale(&mut self) -> Result<()> {
+        if self.batch.is_stale(&self.config) {
+            self.flush().await?;
+        }
+        Ok(())
+    }
+
+    pub async fn flush(&mut self) -> Result<()> {
+        if self.batch.lines.is_empty() {
+            return Ok(());
+        }
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+        let key = self.config.key(timestamp);
+        let body = self.batch.body(self.config.gzip)?;
+
+        println!(
+            "Flushing {} log lines to s3://{}/{}",
+            self.batch.lines.len(),
+            self.config.bucket,
+            key
+        );
+        if body.len() as u64 > self.config.part_size {
+            self.upload_multipart(&key, body).await
+        } else {
+            self.client
+                .put_object()
+                .bucket(&self.config.bucket)
+                .key(&key)
+                .body(body.into())
+                .content_type(self.content_type())
+                .send()
+                .await
+                .map(|_| ())
+                .map_err(anyhow::Error::from)
+        }
+        .with_context(|| format!("Failed to write to S3: s3://{}/{}", self.config.bucket, key))?;
+
+        self.batch = Batch::default();
+        self.objects += 1;
+        Ok(())
+    }
+
+    /// Upload what's left; if that fails, save it to a local file instead
+    /// so the lines aren't lost
+    pub async fn close(&mut self) -> Result<()> {
+        if let Err(err) = self.flush().await {
+            let path = PathBuf::from(format!(
+                "fake_ssp_unflushed_{}.jsonl",
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map_or(0, |d| d.as_millis())
+            ));
+            fs::write(&path, self.batch.body(false)?)
+                .with_context(|| format!("Failed to save unflushed logs to {}", path.display()))?;
+            return Err(err.context(format!(
+                "{} unflushed log lines saved to {}",
+                self.batch.lines.len(),
+                path.display()
+            )));
+        }
+        Ok(())
+    }
+
+    fn content_type(&self) -> &'static str {
+        if self.config.gzip {
+            "application/gzip"
+        } else {
+            "application/x-ndjson"
+        }
+    }
+
+    async fn upload_multipart(&self, key: &str, body: Vec<u8>) -> Result<()> {
+        let upload = self
+            .client
+            .create_multipart_upload()
+            .bucket(&self.config.bucket)
+            .key(key)
+            .content_type(self.content_type())
+            .send()
+            .await?;
+        let upload_id = upload
+            .upload_id()
+            .context("S3 returned no multipart upload id")?;
+
+        match self.upload_parts(key, upload_id, &body).await {
+            Ok(parts) => {
+                self.client
+                    .complete_multipart_upload()
+                    .bucket(&self.config.bucket)
+                    .key(key)
+                    .upload_id(upload_id)
+                    .multipart_upload(
+                        CompletedMultipartUpload::builder()
+                            .set_parts(Some(parts))
+                            .build(),
+                    )
+                    .send()
+                    .await?;
+                Ok(())
+            }
+            Err(err) => {
+                // Don't leave the uploaded parts around (and billed)
+                let _ = self
+                    .client
+                    .abort_multipart_upload()
+                    .bucket(&self.config.bucket)
+                    .key(key)
+                    .upload_id(upload_id)
+                    .send()
+                    .await;
+                Err(err)
+            }
+        }
+    }
+
+    async fn upload_parts(
+        &self,
+        key: &str,
+        upload_id: &str,
+        body: &[u8],
+    ) -> Result<Vec<CompletedPart>> {
+        let mut parts = Vec::new();
+        for (i, chunk) in body.chunks(self.config.part_size as usize).enumerate() {
+            let part_number = i32::try_from(i + 1).context("too many multipart parts")?;
+            let part = self
+                .client
+                .upload_part()
+                .bucket(&self.config.bucket)
+                .key(key)
+                .upload_id(upload_id)
+                .part_number(part_number)
+                .body(ByteStream::from(chunk.to_vec()))
+                .send()
+                .await?;
+            parts.push(
+                CompletedPart::builder()
+                    .part_number(part_number)
+                    .set_e_tag(part.e_tag().map(str::to_string))
+                    .build(),
+            );
+        }
+        Ok(parts)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    fn config(env: &[(&str, &str)]) -> Result<S3LogConfig> {
+        S3LogConfig::parse(|name| {
+            env.iter()
+                .find(|(k, _)| *k == name)
+                .map(|(_, v)| v.to_string())
+        })
+    }
+
+    #[test]
+    fn test_config_from_env() {
+        assert!(config(&[]).is_err());
+
+        let defaults = config(&[("S3_BUCKET", "logs")]).unwrap();
+        assert_eq!(defaults.batch_lines, 50);
+        assert_eq!(defaults.flush_every, Duration::from_secs(10));
+        assert_eq!(defaults.key(7), "fake_ssp_logs_7.jsonl");
+
+        let tuned = config(&[
+            ("S3_BUCKET", "logs"),
+            ("S3_PREFIX", "rtb/"),
+            ("LOG_GZIP", "1"),
+            ("S3_BATCH_LINES", "10000"),
+            ("S3_BATCH_SIZE", "32MB"),
+            ("S3_FLUSH_INTERVAL", "1m"),
+            ("S3_PART_SIZE", "16M"),
+        ])
+        .unwrap();
+        assert_eq!(tuned.batch_lines, 10_000);
+        assert_eq!(tuned.batch_bytes, 32 << 20);
+        assert_eq!(tuned.flush_every, Duration::from_secs(60));
+        assert_eq!(tuned.part_size, 16 << 20);
+        assert_eq!(tuned.key(7), "rtb/fake_ssp_logs_7.jsonl.gz");
+
+        let bucket = ("S3_BUCKET", "logs");
+        assert!(config(&[bucket, ("S3_BATCH_LINES", "0")]).is_err());
+        assert!(config(&[bucket, ("S3_FLUSH_INTERVAL", "0s")]).is_err());
+        assert!(config(&[bucket, ("S3_PART_SIZE", "1MB")]).is_err());
+    }
+
+    #[test]
+    fn test_batch_fills_by_lines_bytes_and_age() {
+        let mut config = config(&[("S3_BUCKET", "logs"), ("S3_BATCH_LINES", "3")]).unwrap();
+        let mut batch = Batch::default();
+        assert!(!batch.is_stale(&config));
+        batch.push(r#"{"n":1}"#.to_string());
+        batch.push(r#"{"n":2}"#.to_string());
+        assert!(!batch.is_full(&config));
+        assert_eq!(batch.bytes, 16);
+        batch.push(r#"{"n":3}"#.to_string());
+        assert!(batch.is_full(&config));
+
+        config.batch_lines = 100;
+        config.batch_bytes = 16;
+        assert!(batch.is_full(&config));
+
+        config.flush_every = Duration::ZERO;
+        assert!(batch.is_stale(&config));
+
+        let mut text = String::new();
+        GzDecoder::new(batch.body(true).unwrap().as_slice())
+            .read_to_string(&mut text)
+            .unwrap();
+        assert_eq!(text, "{\"n\":1}\n{\"n\":2}\n{\"n\":3}\n");
+    }
+}
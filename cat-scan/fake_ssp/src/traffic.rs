@@ -0,0 +1,546 @@
+use std::{fs, path::Path};
+
+use anyhow::{bail, Context, Result};
+use rand::{
+    distr::{weighted::WeightedIndex, Distribution},
+    seq::index::sample_weighted,
+    Rng,
+};
+use serde::Deserialize;
+
+use crate::media::{Media, MediaType};
+
+/// What the generated traffic looks like: the values of each request
+/// dimension and how often each one occurs, read from a TOML or JSON file
+/// (--traffic). Weights are relative and default to 1, so a list without
+/// weights is picked uniformly.
+///
+/// ```toml
+/// [[formats]]
+/// w = 300
+/// h = 250
+/// weight = 60
+///
+/// [[publishers]]
+/// id = "pub-news"
+/// domain = "news.example.com"
+/// weight = 10
+///
+/// [[countries]]
+/// code = "USA"
+/// weight = 5
+///
+/// [[device_types]]
+/// type = 4    # OpenRTB devicetype: 2 = PC, 4 = phone, 5 = tablet
+///
+/// [[media]]
+/// type = "video"    # banner, video, native or audio
+/// weight = 20
+///
+/// [[ssps]]
+/// name = "ssp-mobile"
+/// weight = 3
+/// floor = [0.1, 0.8]      # bidfloor drawn from this range, or one number
+/// tmax = 120              # ms; responses later than this are dropped
+/// timeout_rate = 0.05     # extra share of requests that time out anyway
+/// formats = [{ w = 320, h = 50, weight = 4 }, { w = 300, h = 250 }]
+/// ```
+///
+/// Missing lists fall back to the built-in ones (banners only, for media,
+/// and a single "fake_ssp" source with modest floors and a 300ms tmax). An
+/// SSP without its own formats uses the profile's.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TrafficProfile {
+    #[serde(default)]
+    pub formats: Vec<Format>,
+    #[serde(default)]
+    pub publishers: Vec<Publisher>,
+    #[serde(default)]
+    pub segments: Vec<Segment>,
+    #[serde(default)]
+    pub countries: Vec<Country>,
+    #[serde(default)]
+    pub device_types: Vec<DeviceType>,
+    #[serde(default)]
+    pub media: Vec<Media>,
+    #[serde(default)]
+    pub ssps: Vec<Ssp>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Format {
+    pub w: u32,
+    pub h: u32,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Publisher {
+    pub id: String,
+    pub domain: String,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Segment {
+    pub id: String,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Country {
+    /// ISO-3166-1 alpha-3, as in OpenRTB device.geo.country
+    pub code: String,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceType {
+    /// OpenRTB device.devicetype
+    #[serde(rename = "type")]
+    pub devicetype: u32,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+}
+
+/// A simulated supply source, with its own size mix, floors and timeouts
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Ssp {
+    /// Sent as source.ssp
+    pub name: String,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+    /// Sizes this SSP sells instead of the profile's
+    #[serde(default)]
+    pub formats: Vec<Format>,
+    #[serde(default)]
+    pub floor: Option<Floor>,
+    /// Milliseconds the SSP waits for a response (request tmax)
+    #[serde(default)]
+    pub tmax: Option<u64>,
+    /// Share of requests logged as timed out whatever the bidder does
+    #[serde(default)]
+    pub timeout_rate: f64,
+}
+
+/// imp.bidfloor: a fixed CPM or a [min, max] range to draw from
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(untagged)]
+pub enum Floor {
+    Fixed(f64),
+    Range([f64; 2]),
+}
+
+impl Floor {
+    /// A floor in whole cents
+    pub fn sample<R: Rng>(self, rng: &mut R) -> f64 {
+        let cpm = match self {
+            Floor::Fixed(cpm) => cpm,
+            Floor::Range([min, max]) if min < max => rng.random_range(min..max),
+            Floor::Range([min, _]) => min,
+        };
+        (cpm * 100.0).round() / 100.0
+    }
+
+    fn is_valid(self) -> bool {
+        let valid = |cpm: f64| cpm.is_finite() && cpm >= 0.0;
+        match self {
+            Floor::Fixed(cpm) => valid(cpm),
+            Floor::Range([min, max]) => valid(min) && valid(max) && min <= max,
+        }
+    }
+}
+
+pub(crate) fn default_weight() -> f64 {
+    1.0
+}
+
+impl TrafficProfile {
+    /// Read a profile; `.toml` files are TOML, anything else JSON
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read traffic profile: {}", path.display()))?;
+        let profile: Self = if path.extension().is_some_and(|ext| ext == "toml") {
+            toml::from_str(&text)
+                .with_context(|| format!("Invalid traffic profile: {}", path.display()))?
+        } else {
+            serde_json::from_str(&text)
+                .with_context(|| format!("Invalid traffic profile: {}", path.display()))?
+        };
+        Ok(profile.with_defaults())
+    }
+
+    /// The built-in formats, publishers, segments, countries and device
+    /// types, from a single source
+    pub fn builtin() -> Self {
+        Self::default().with_defaults()
+    }
+
+    fn with_defaults(mut self) -> Self {
+        if self.formats.is_empty() {
+            self.formats = [(300, 250), (320, 50), (160, 600), (728, 90)]
+                .into_iter()
+                .map(|(w, h)| Format { w, h, weight: 1.0 })
+                .collect();
+        }
+        if self.publishers.is_empty() {
+            self.publishers = [
+                ("pub-news", "news.example.com"),
+                ("pub-sports", "sports.example.com"),
+                ("pub-tech", "tech.example.com"),
+            ]
+            .into_iter()
+            .map(|(id, domain)| Publisher {
+                id: id.to_string(),
+                domain: domain.to_string(),
+                weight: 1.0,
+            })
+            .collect();
+        }
+        if self.media.is_empty() {
+            self.media = vec![Media {
+                media_type: MediaType::Banner,
+                weight: 1.0,
+            }];
+        }
+        if self.ssps.is_empty() {
+            self.ssps = vec![Ssp {
+                name: "fake_ssp".to_string(),
+                weight: 1.0,
+                formats: Vec::new(),
+                floor: Some(Floor::Range([0.05, 0.5])),
+                tmax: Some(300),
+                timeout_rate: 0.0,
+            }];
+        }
+        if self.countries.is_empty() {
+            self.countries = [
+                ("USA", 40.0),
+                ("GBR", 10.0),
+                ("DEU", 10.0),
+                ("FRA", 8.0),
+                ("CAN", 8.0),
+                ("BRA", 8.0),
+                ("IND", 8.0),
+                ("JPN", 8.0),
+            ]
+            .into_iter()
+            .map(|(code, weight)| Country {
+                code: code.to_string(),
+                weight,
+            })
+            .collect();
+        }
+        if self.device_types.is_empty() {
+            self.device_types = [(4, 55.0), (2, 35.0), (5, 10.0)]
+                .into_iter()
+                .map(|(devicetype, weight)| DeviceType { devicetype, weight })
+                .collect();
+        }
+        if self.segments.is_empty() {
+            self.segments = ["automotive", "travel", "finance", "entertainment"]
+                .into_iter()
+                .map(|id| Segment {
+                    id: id.to_string(),
+                    weight: 1.0,
+                })
+                .collect();
+        }
+        self
+    }
+}
+
+/// One request's worth of picks from a profile
+#[derive(Debug, Clone, Copy)]
+pub struct Draw<'a> {
+    pub ssp: &'a Ssp,
+    ssp_index: usize,
+    pub publisher: &'a Publisher,
+    pub segment: &'a Segment,
+    pub country: &'a Country,
+    pub device_type: &'a DeviceType,
+}
+
+/// One impression's picks: its media type and, for banners, its sizes
+#[derive(Debug, Clone)]
+pub struct ImpDraw<'a> {
+    pub media_type: MediaType,
+    /// The primary size first; empty for other media types
+    pub formats: Vec<&'a Format>,
+}
+
+/// Draws each dimension independently with the profile's weights
+pub struct TrafficSampler {
+    profile: TrafficProfile,
+    formats: WeightedIndex<f64>,
+    publishers: WeightedIndex<f64>,
+    segments: WeightedIndex<f64>,
+    countries: WeightedIndex<f64>,
+    device_types: WeightedIndex<f64>,
+    media: WeightedIndex<f64>,
+    ssps: WeightedIndex<f64>,
+    /// Per SSP, the weights of its own formats if it has any
+    ssp_formats: Vec<Option<WeightedIndex<f64>>>,
+}
+
+impl TrafficSampler {
+    pub fn new(profile: TrafficProfile) -> Result<Self> {
+        let mut ssp_formats = Vec::new();
+        for ssp in &profile.ssps {
+            if ssp.floor.is_some_and(|f| !f.is_valid()) {
+                bail!("traffic profile: ssp {} has an invalid floor", ssp.name);
+            }
+            if !(0.0..=1.0).contains(&ssp.timeout_rate) {
+                bail!(
+                    "traffic profile: ssp {} timeout_rate must be 0 to 1",
+                    ssp.name
+                );
+            }
+            let name = format!("ssp {} formats", ssp.name);
+            ssp_formats.push(optional_weights(
+                &name,
+                ssp.formats.iter().map(|f| f.weight),
+            )?);
+        }
+        Ok(Self {
+            formats: weights("formats", profile.formats.iter().map(|f| f.weight))?,
+            publishers: weights("publishers", profile.publishers.iter().map(|p| p.weight))?,
+            segments: weights("segments", profile.segments.iter().map(|s| s.weight))?,
+            countries: weights("countries", profile.countries.iter().map(|c| c.weight))?,
+            device_types: weights(
+                "device_types",
+                profile.device_types.iter().map(|d| d.weight),
+            )?,
+            media: weights("media", profile.media.iter().map(|m| m.weight))?,
+            ssps: weights("ssps", profile.ssps.iter().map(|s| s.weight))?,
+            ssp_formats,
+            profile,
+        })
+    }
+
+    pub fn draw<R: Rng>(&self, rng: &mut R) -> Draw<'_> {
+        let ssp_index = self.ssps.sample(rng);
+        Draw {
+            ssp: &self.profile.ssps[ssp_index],
+            ssp_index,
+            publisher: &self.profile.publishers[self.publishers.sample(rng)],
+            segment: &self.profile.segments[self.segments.sample(rng)],
+            country: &self.profile.countries[self.countries.sample(rng)],
+            device_type: &self.profile.device_types[self.device_types.sample(rng)],
+        }
+    }
+
+    /// An impression of the request `draw` offering up to `sizes` banner
+    /// sizes from its SSP's format mix
+    pub fn draw_imp<R: Rng>(&self, draw: &Draw, sizes: usize, rng: &mut R) -> ImpDraw<'_> {
+        let media_type = self.profile.media[self.media.sample(rng)].media_type;
+        let formats = if media_type == MediaType::Banner {
+            match &self.ssp_formats[draw.ssp_index] {
+                Some(weights) => draw_formats(
+                    &self.profile.ssps[draw.ssp_index].formats,
+                    weights,
+                    sizes,
+                    rng,
+                ),
+                None => draw_formats(&self.profile.formats, &self.formats, sizes, rng),
+            }
+        } else {
+            Vec::new()
+        };
+        ImpDraw {
+            media_type,
+            formats,
+        }
+    }
+}
+
+/// Distinct sizes, the primary one drawn by weight and the rest by weight
+/// among the others; fewer if there are fewer sizes
+fn draw_formats<'a, R: Rng>(
+    formats: &'a [Format],
+    weights: &WeightedIndex<f64>,
+    count: usize,
+    rng: &mut R,
+) -> Vec<&'a Format> {
+    let primary = weights.sample(rng);
+    let mut picked = vec![&formats[primary]];
+    if count > 1 {
+        let weight = |i: usize| if i == primary { 0.0 } else { formats[i].weight };
+        // Weights were validated when the sampler was built
+        if let Ok(extra) = sample_weighted(rng, formats.len(), weight, count - 1) {
+            picked.extend(extra.iter().map(|i| &formats[i]));
+        }
+    }
+    picked
+}
+
+fn weights(name: &str, weights: impl Iterator<Item = f64>) -> Result<WeightedIndex<f64>> {
+    let weights: Vec<f64> = weights.collect();
+    if let Some(bad) = weights.iter().find(|w| !w.is_finite() || **w < 0.0) {
+        bail!("traffic profile: {name} has an invalid weight {bad}");
+    }
+    WeightedIndex::new(&weights)
+        .map_err(|_| anyhow::anyhow!("traffic profile: {name} needs at least one positive weight"))
+}
+
+fn optional_weights(
+    name: &str,
+    weights: impl ExactSizeIterator<Item = f64>,
+) -> Result<Option<WeightedIndex<f64>>> {
+    if weights.len() == 0 {
+        return Ok(None);
+    }
+    self::weights(name, weights).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn test_profile_weights_skew_draws() {
+        let profile: TrafficProfile = toml::from_str(
+            r#"
+            [[formats]]
+            w = 300
+            h = 250
+            weight = 9
+
+            [[formats]]
+            w = 728
+            h = 90
+
+            [[countries]]
+            code = "USA"
+
+            [[media]]
+            type = "banner"
+            weight = 3
+
+            [[media]]
+            type = "native"
+            "#,
+        )
+        .unwrap();
+        let sampler = TrafficSampler::new(profile.with_defaults()).unwrap();
+        assert_eq!(sampler.profile.publishers.len(), 3);
+        assert_eq!(sampler.profile.device_types.len(), 3);
+
+        let mut rng = StdRng::seed_from_u64(7);
+        let draws: Vec<Draw> = (0..1000).map(|_| sampler.draw(&mut rng)).collect();
+        assert!(draws.iter().all(|d| d.country.code == "USA"));
+        let phones = draws
+            .iter()
+            .filter(|d| d.device_type.devicetype == 4)
+            .count();
+        assert!((480..620).contains(&phones), "{phones}");
+
+        let imps: Vec<ImpDraw> = (0..1000)
+            .map(|_| sampler.draw_imp(&sampler.draw(&mut rng), 1, &mut rng))
+            .collect();
+        let natives = imps
+            .iter()
+            .filter(|d| d.media_type == MediaType::Native)
+            .count();
+        assert!((200..300).contains(&natives), "{natives}");
+        assert!(imps
+            .iter()
+            .all(|d| d.formats.len() == usize::from(d.media_type == MediaType::Banner)));
+        let banners = imps.iter().filter(|d| !d.formats.is_empty());
+        let mrecs = banners.clone().filter(|d| d.formats[0].w == 300).count();
+        let share = mrecs as f64 / banners.count() as f64;
+        assert!((0.85..0.95).contains(&share), "{share}");
+    }
+
+    #[test]
+    fn test_draw_imp_sizes_are_distinct() {
+        let sampler = TrafficSampler::new(TrafficProfile::builtin()).unwrap();
+        let mut rng = StdRng::seed_from_u64(11);
+        for _ in 0..100 {
+            let draw = sampler.draw(&mut rng);
+            let imp = sampler.draw_imp(&draw, 3, &mut rng);
+            let mut sizes: Vec<(u32, u32)> = imp.formats.iter().map(|f| (f.w, f.h)).collect();
+            assert_eq!(sizes.len(), 3);
+            sizes.sort();
+            sizes.dedup();
+            assert_eq!(sizes.len(), 3);
+        }
+        // Capped at the sizes the profile has
+        let draw = sampler.draw(&mut rng);
+        assert_eq!(sampler.draw_imp(&draw, 10, &mut rng).formats.len(), 4);
+    }
+
+    #[test]
+    fn test_ssps_have_their_own_formats_and_floors() {
+        let profile: TrafficProfile = toml::from_str(
+            r#"
+            [[ssps]]
+            name = "ssp-mobile"
+            floor = [0.1, 0.8]
+            tmax = 120
+            formats = [{ w = 320, h = 50 }]
+
+            [[ssps]]
+            name = "ssp-desktop"
+            floor = 1.25
+            "#,
+        )
+        .unwrap();
+        let sampler = TrafficSampler::new(profile.with_defaults()).unwrap();
+        let mut rng = StdRng::seed_from_u64(13);
+        for _ in 0..200 {
+            let draw = sampler.draw(&mut rng);
+            let imp = sampler.draw_imp(&draw, 1, &mut rng);
+            let floor = draw.ssp.floor.unwrap().sample(&mut rng);
+            if draw.ssp.name == "ssp-mobile" {
+                assert_eq!((imp.formats[0].w, imp.formats[0].h), (320, 50));
+                assert!((0.1..=0.8).contains(&floor), "{floor}");
+                assert_eq!(floor, (floor * 100.0).round() / 100.0);
+            } else {
+                assert_eq!(floor, 1.25);
+            }
+        }
+        assert_eq!(TrafficProfile::builtin().ssps[0].name, "fake_ssp");
+
+        let bad: TrafficProfile =
+            serde_json::from_str(r#"{ "ssps": [{ "name": "x", "floor": [2.0, 1.0] }] }"#).unwrap();
+        assert!(TrafficSampler::new(bad.with_defaults()).is_err());
+    }
+
+    #[test]
+    fn test_example_profiles_load() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("profiles/skewed.toml");
+        let sampler = TrafficSampler::new(TrafficProfile::load(&path).unwrap()).unwrap();
+        assert_eq!(sampler.profile.formats.len(), 6);
+        assert_eq!(sampler.profile.device_types.len(), 3);
+
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("profiles/multi_ssp.toml");
+        let sampler = TrafficSampler::new(TrafficProfile::load(&path).unwrap()).unwrap();
+        assert_eq!(sampler.profile.ssps.len(), 3);
+    }
+
+    #[test]
+    fn test_profile_rejects_bad_weights() {
+        let zero: TrafficProfile =
+            serde_json::from_str(r#"{ "segments": [{ "id": "travel", "weight": 0 }] }"#).unwrap();
+        assert!(TrafficSampler::new(zero.with_defaults()).is_err());
+
+        let negative: TrafficProfile =
+            serde_json::from_str(r#"{ "device_types": [{ "type": 4, "weight": -1 }] }"#).unwrap();
+        assert!(TrafficSampler::new(negative.with_defaults()).is_err());
+
+        assert!(serde_json::from_str::<TrafficProfile>(r#"{ "format": [] }"#).is_err());
+    }
+}